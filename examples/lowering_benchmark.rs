@@ -0,0 +1,49 @@
+//! Compares set extraction on the default BNF lowering and the one with a
+//! shared `___empty` rule, on a generated grammar with many `[ ]` and `{ }`.
+//!
+//! ```text
+//! cargo run --release --example lowering_benchmark -- 200
+//! ```
+
+use std::time::Instant;
+
+use sebnf_tool::Sebnf;
+use sebnf_tool::converter::LoweringOptions;
+
+fn grammar(rules: usize) -> String {
+    let mut source = String::new();
+    for i in 0..rules {
+        let next = (i + 1) % rules;
+        source.push_str(&format!(
+            "r{i} := \"a{i}\" [ \"b{i}\" r{next} ] {{ \",\" \"c{i}\" }} sep_by(\"d{i}\", \";\").\n"
+        ));
+    }
+    source
+}
+
+fn main() {
+    let rules = std::env::args()
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(100);
+    let sebnf = Sebnf::parse_str(&grammar(rules), "generated").expect("generated grammar is valid");
+
+    for shared_epsilon in [false, true] {
+        let options = LoweringOptions {
+            shared_epsilon,
+            ..LoweringOptions::default()
+        };
+        let bnf = sebnf.to_bnf_with(&options);
+        let productions: usize = bnf.rules.values().map(Vec::len).sum();
+        let start = Instant::now();
+        let sets = bnf.first_and_follow_sets();
+        let elapsed = start.elapsed();
+        println!(
+            "shared_epsilon={shared_epsilon}: {} rules, {} productions, sets in {:.1?} ({} FIRST entries)",
+            bnf.rules.len(),
+            productions,
+            elapsed,
+            sets.first.values().map(|s| s.len()).sum::<usize>()
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! The analyses of a BNF grammar that share its FIRST and FOLLOW sets.
+//!
+//! [`Bnf::is_ll1`] and a parse table built next to it each need the sets.
+//! An [`Analysis`] from [`Bnf::analyze`] computes them once, and the
+//! conflicts and the table once on first use, handing out references.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//!
+//! let bnf = Sebnf::parse_str("s := \"a\" s | \"a\".", "g").unwrap().to_bnf();
+//! let analysis = bnf.analyze();
+//! assert!(!analysis.is_ll1()?);
+//! assert_eq!(analysis.conflicts()?.conflicts.len(), 1);
+//! assert!(analysis.table().has_conflicts());
+//! # Ok::<(), sebnf_tool::sets::Ll1Error>(())
+//! ```
+
+use std::cell::OnceCell;
+
+use crate::bnf::Bnf;
+use crate::sets::{Ll1Error, Ll1Result, Sets};
+use crate::table::{ParseTable, build_parse_table};
+
+/// A grammar with its sets, and the results computed from them so far.
+#[derive(Debug)]
+pub struct Analysis<'a> {
+    bnf: &'a Bnf,
+    sets: Sets,
+    ignore_regex_conflicts: bool,
+    conflicts: OnceCell<Ll1Result>,
+    table: OnceCell<ParseTable>,
+}
+
+impl<'a> Analysis<'a> {
+    pub(crate) fn new(bnf: &'a Bnf, sets: Sets) -> Self {
+        Analysis {
+            bnf,
+            sets,
+            ignore_regex_conflicts: false,
+            conflicts: OnceCell::new(),
+            table: OnceCell::new(),
+        }
+    }
+
+    /// Skips regex-vs-regex conflicts, as `Bnf::is_ll1(true)` does.
+    pub fn ignore_regex_conflicts(mut self, ignore: bool) -> Self {
+        if ignore != self.ignore_regex_conflicts {
+            self.ignore_regex_conflicts = ignore;
+            self.conflicts = OnceCell::new();
+        }
+        self
+    }
+
+    pub fn bnf(&self) -> &'a Bnf {
+        self.bnf
+    }
+
+    pub fn sets(&self) -> &Sets {
+        &self.sets
+    }
+
+    /// The LL(1) conflicts, as [`Bnf::is_ll1`] reports them. An error is
+    /// not kept, so the next call checks again.
+    pub fn conflicts(&self) -> Result<&Ll1Result, Ll1Error> {
+        if let Some(conflicts) = self.conflicts.get() {
+            return Ok(conflicts);
+        }
+        let conflicts = self
+            .bnf
+            .ll1_conflicts(&self.sets, self.ignore_regex_conflicts)?;
+        Ok(self.conflicts.get_or_init(|| conflicts))
+    }
+
+    pub fn is_ll1(&self) -> Result<bool, Ll1Error> {
+        Ok(self.conflicts()?.is_ll1())
+    }
+
+    /// The LL(1) parse table; regex conflicts always show in it.
+    pub fn table(&self) -> &ParseTable {
+        self.table
+            .get_or_init(|| build_parse_table(self.bnf, &self.sets))
+    }
+
+    /// The sets, for callers done with the rest.
+    pub fn into_sets(self) -> Sets {
+        self.sets
+    }
+}
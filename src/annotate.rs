@@ -0,0 +1,122 @@
+//! LL(1) conflicts as comments in the grammar source.
+//!
+//! [`annotate_conflicts`] inserts a `(* ... *)` comment above every rule
+//! with conflicts, so the grammar can be reviewed top to bottom with its
+//! conflicts in place. Conflicts of generated helper rules are shown at the
+//! rule they were generated from. The result is still valid SEBNF.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::bnf::Bnf;
+use crate::sarif::rule_span;
+use crate::sebnf::Sebnf;
+use crate::sets::{Ll1Conflict, Ll1ConflictKind, Ll1Result, format_production};
+
+/// Returns `source` with a comment listing the conflicts of each rule of
+/// `sebnf` inserted above it; `bnf` and `result` are its conversion and
+/// LL(1) check. Conflicts without a rule in the source are listed at the top.
+pub fn annotate_conflicts(source: &str, sebnf: &Sebnf, bnf: &Bnf, result: &Ll1Result) -> String {
+    // Line start of the rule, or of its annotations, to the conflicts there
+    let mut by_line: BTreeMap<usize, Vec<&Ll1Conflict>> = BTreeMap::new();
+    for conflict in &result.conflicts {
+        let start = rule_span(sebnf, bnf, &conflict.non_terminal).map(|span| {
+            let rule = sebnf
+                .rule_spans
+                .iter()
+                .find(|(_, s)| **s == span)
+                .map(|(rule, _)| rule);
+            let annotations = rule.and_then(|rule| sebnf.annotations.get(rule));
+            let start = annotations.map_or(span.start, |a| a.span.start.min(span.start));
+            line_start(source, start)
+        });
+        by_line
+            .entry(start.unwrap_or(0))
+            .or_default()
+            .push(conflict);
+    }
+
+    let mut annotated = String::with_capacity(source.len());
+    let mut end = 0;
+    for (start, conflicts) in by_line {
+        annotated.push_str(&source[end..start]);
+        let indent: String = source[start..]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        annotated.push_str(&comment(&conflicts, &indent));
+        end = start;
+    }
+    annotated.push_str(&source[end..]);
+    annotated
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn comment(conflicts: &[&Ll1Conflict], indent: &str) -> String {
+    let mut text = format!(
+        "{}(* LL(1): {} conflict{}\n",
+        indent,
+        conflicts.len(),
+        if conflicts.len() == 1 { "" } else { "s" }
+    );
+    for conflict in conflicts {
+        let (kind, first, second) = match &conflict.kind {
+            Ll1ConflictKind::FirstFirst {
+                production1,
+                production2,
+            } => ("FIRST/FIRST", production1, production2),
+            Ll1ConflictKind::FirstFollow {
+                nullable_production,
+                other_production,
+            } => ("FIRST/FOLLOW", nullable_production, other_production),
+        };
+        let items: Vec<_> = conflict.conflicts.iter().map(|c| c.to_string()).collect();
+        let _ = write!(
+            text,
+            "{}   {} in '{}' between `{}` and `{}` on {}",
+            indent,
+            kind,
+            conflict.non_terminal,
+            format_production(first),
+            format_production(second),
+            items.join(", ")
+        );
+        if let Some(lookahead) = &conflict.lookahead {
+            let _ = write!(text, ", {}", lookahead);
+        }
+        text.push('\n');
+    }
+    let _ = writeln!(text, "{}*)", indent);
+    // A witness must not end the comment early
+    text.replace("*)\n", "\u{0}")
+        .replace("*)", "* )")
+        .replace('\u{0}', "*)\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_go_above_the_rules_with_conflicts() {
+        let source = "s := list.\n(* items *)\n@max_depth(3)\nlist := item { \",\" item } [ \",\" ].\nitem := \"x\".\n";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let bnf = sebnf.to_bnf();
+        let result = bnf.is_ll1(false).unwrap();
+        let annotated = annotate_conflicts(source, &sebnf, &bnf, &result);
+        assert_eq!(
+            annotated,
+            "s := list.\n(* items *)\n\
+             (* LL(1): 1 conflict\n   \
+             FIRST/FOLLOW in '___rep_0' between `ε` and `\",\" item ___rep_0` on , ∩ , (e.g., \",\")\n\
+             *)\n\
+             @max_depth(3)\nlist := item { \",\" item } [ \",\" ].\nitem := \"x\".\n"
+        );
+        // Still a grammar with the same rules
+        let reparsed = Sebnf::parse_str(&annotated, "g").unwrap();
+        assert_eq!(reparsed.to_string(), sebnf.to_string());
+    }
+}
@@ -0,0 +1,149 @@
+//! Analysis results as JSON with diagnostics, shared by the `serve` command
+//! and the WebAssembly bindings.
+//!
+//! Every result carries `diagnostics`, a list of
+//! `{"severity", "code", "message", "span": [start, end] | null}` with byte
+//! spans into the grammar for highlighting.
+
+use miette::{Diagnostic, Severity};
+use serde_json::{Value, json};
+
+use crate::sebnf::{GrammarWarning, Sebnf};
+use crate::token_class::TokenClasses;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    /// `{"valid"}`
+    Validate,
+    /// `{"bnf"}`
+    ToBnf,
+    /// `{"first", "follow"}`
+    Sets,
+    /// `{"ll1", "conflicts"}`
+    IsLl1,
+}
+
+impl Endpoint {
+    pub const ALL: [Endpoint; 4] = [
+        Endpoint::Validate,
+        Endpoint::ToBnf,
+        Endpoint::Sets,
+        Endpoint::IsLl1,
+    ];
+
+    /// The name used in URLs, like `to-bnf`
+    pub fn name(self) -> &'static str {
+        match self {
+            Endpoint::Validate => "validate",
+            Endpoint::ToBnf => "to-bnf",
+            Endpoint::Sets => "sets",
+            Endpoint::IsLl1 => "is-ll1",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.name() == name)
+    }
+}
+
+/// Prepares grammars the same way the non-interactive commands do.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub token_classes: TokenClasses,
+    pub augment_start: bool,
+    pub ignore_regex_conflicts: bool,
+}
+
+pub fn analyze(endpoint: Endpoint, grammar: &str, options: &Options) -> Value {
+    let sebnf = match Sebnf::parse_str(grammar, "grammar") {
+        Ok(sebnf) => sebnf,
+        Err(e) => {
+            let diagnostics: Vec<_> = e
+                .errors
+                .iter()
+                .map(|e| diagnostic_json(e, Severity::Error))
+                .collect();
+            return json!({ "valid": false, "diagnostics": diagnostics });
+        }
+    };
+    let mut diagnostics: Vec<_> = sebnf
+        .warnings(grammar, "grammar")
+        .iter()
+        .filter(|w| {
+            !(options.augment_start && matches!(w, GrammarWarning::ReferencedStartSymbol { .. }))
+        })
+        .map(|w| diagnostic_json(w, Severity::Warning))
+        .collect();
+
+    let mut bnf = sebnf.to_bnf();
+    if options.augment_start {
+        bnf = bnf.augment_start();
+    }
+    let mut result = match endpoint {
+        Endpoint::Validate => json!({ "valid": true }),
+        Endpoint::ToBnf => json!({ "bnf": bnf.to_string() }),
+        Endpoint::Sets => options
+            .token_classes
+            .apply(&bnf)
+            .first_and_follow_sets()
+            .to_json(),
+        Endpoint::IsLl1 => {
+            let bnf = options.token_classes.apply(&bnf);
+            match bnf.is_ll1(options.ignore_regex_conflicts) {
+                Ok(ll1) => {
+                    diagnostics.extend(ll1.conflicts.iter().map(|conflict| {
+                        let span = bnf.conflict_span(conflict);
+                        json!({
+                            "severity": "error",
+                            "code": "sebnf::ll1_conflict",
+                            "message": conflict.to_string().trim_end(),
+                            "span": span.map(|s| [s.start, s.end]),
+                        })
+                    }));
+                    ll1.to_json()
+                }
+                Err(e) => {
+                    diagnostics.push(diagnostic_json(&e, Severity::Error));
+                    json!({ "ll1": false, "conflicts": [] })
+                }
+            }
+        }
+    };
+    result["diagnostics"] = Value::from(diagnostics);
+    result
+}
+
+fn diagnostic_json(diagnostic: &dyn Diagnostic, default_severity: Severity) -> Value {
+    let severity = match diagnostic.severity().unwrap_or(default_severity) {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Advice => "advice",
+    };
+    let span = diagnostic
+        .labels()
+        .and_then(|mut labels| labels.next())
+        .map(|label| [label.offset(), label.offset() + label.len()]);
+    json!({
+        "severity": severity,
+        "code": diagnostic.code().map(|c| c.to_string()),
+        "message": diagnostic.to_string(),
+        "span": span,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_conflicts_with_spans() {
+        let options = Options::default();
+        let value = analyze(Endpoint::IsLl1, "a := \"x\" | \"x\" \"y\".", &options);
+        assert_eq!(value["ll1"], false);
+        assert_eq!(value["diagnostics"][0]["span"], json!([11, 18]));
+
+        let value = analyze(Endpoint::Validate, "a := ", &options);
+        assert_eq!(value["valid"], false);
+        assert_eq!(value["diagnostics"][0]["severity"], "error");
+    }
+}
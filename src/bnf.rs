@@ -1,35 +1,281 @@
 use indexmap::IndexMap;
+use std::collections::HashSet;
 use std::fmt;
+use std::ops::Range;
 
+use crate::analysis::Analysis;
+use crate::lookahead::{
+    AmbiguityWindow, LlkConflict, LlkResult, LookaheadResolution, ambiguous_lookahead,
+    extract_lookahead_sets, lookahead_sets_conflict,
+};
+use crate::lr::{
+    Lr0Automaton, LrConflict, LrItem, LrMethod, LrResult, LrTable, build_lr_table,
+    build_lr0_automaton, lalr1_lookaheads, lr_conflicts,
+};
+use crate::render::{RenderError, render_display};
+use crate::sentences::{ConflictInput, GenerateError, regex_witnesses, shortest_prefixes};
 use crate::sets::{
-    Ll1Conflict, Ll1ConflictKind, Ll1Error, Ll1Result, Sets, extract_sets, find_set_conflicts,
-    first_of_sequence,
+    Ll1Conflict, Ll1ConflictKind, Ll1Error, Ll1Result, SetItem, Sets, UnknownStart, extract_sets,
+    extract_sets_from, find_set_conflicts, first_of_sequence, strip_terminal_quotes,
 };
+use crate::symbols::Symbols;
 
-#[derive(Debug)]
+/// A grammar in plain BNF: every rule is a list of productions, each a
+/// sequence of items. The first rule is the start symbol.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bnf {
     pub rules: IndexMap<String, Vec<Vec<Item>>>,
+    /// Source span of every item, shaped like `rules`. Items of generated
+    /// helper rules point at the SEBNF construct they were derived from.
+    /// Rules or items without a span (e.g. added by a transformation) are
+    /// simply missing.
+    pub spans: IndexMap<String, Vec<Vec<Range<usize>>>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Item {
     NonTerminal(String),
     Terminal(String),
     Regex(String),
+    /// Token class of an external lexer, see `TokenClasses`
+    TokenClass(String),
+    /// Explicit end of input, only used by the augmented start rule
+    EndOfInput,
 }
 
+/// Name of the start rule added by [`Bnf::augment_start`]
+pub const AUGMENTED_START: &str = "___start";
+
 impl Bnf {
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
+
+    /// Returns a copy with a fresh start rule `___start := S $`, where `S` is
+    /// the current start symbol. This keeps FOLLOW(S) correct even when `S`
+    /// is referenced by other rules.
+    pub fn augment_start(&self) -> Bnf {
+        let mut rules = IndexMap::new();
+        if let Some(start) = self.rules.keys().next() {
+            rules.insert(
+                AUGMENTED_START.to_string(),
+                vec![vec![Item::NonTerminal(start.clone()), Item::EndOfInput]],
+            );
+        }
+        rules.extend(self.rules.iter().map(|(nt, p)| (nt.clone(), p.clone())));
+        Bnf {
+            rules,
+            spans: self.spans.clone(),
+        }
+    }
+
+    /// Source span of the `index`-th item of a production.
+    pub fn item_span(&self, nt: &str, production: usize, index: usize) -> Option<Range<usize>> {
+        self.spans.get(nt)?.get(production)?.get(index).cloned()
+    }
+
+    /// Source span covering all items of a production, `None` for ε.
+    pub fn production_span(&self, nt: &str, production: usize) -> Option<Range<usize>> {
+        let spans = self.spans.get(nt)?.get(production)?;
+        let start = spans.iter().map(|s| s.start).min()?;
+        let end = spans.iter().map(|s| s.end).max()?;
+        Some(start..end)
+    }
+
+    /// Source span of the production a conflict is reported at: the second
+    /// of the two productions, or the first if that one has no span (ε).
+    pub fn conflict_span(&self, conflict: &Ll1Conflict) -> Option<Range<usize>> {
+        let (first, second) = conflict.production_indices;
+        self.production_span(&conflict.non_terminal, second)
+            .or_else(|| self.production_span(&conflict.non_terminal, first))
+    }
+
+    /// Like [`Bnf::conflict_span`], for an LL(k) conflict.
+    pub fn llk_conflict_span(&self, conflict: &LlkConflict) -> Option<Range<usize>> {
+        let (first, second) = conflict.production_indices;
+        self.production_span(&conflict.non_terminal, second)
+            .or_else(|| self.production_span(&conflict.non_terminal, first))
+    }
+
+    /// The production reduced in an LR conflict; of a reduce/reduce conflict
+    /// the second, or the first if that one has no span (ε).
+    pub fn lr_conflict_span(&self, conflict: &LrConflict) -> Option<Range<usize>> {
+        (conflict.reductions().iter().rev())
+            .find_map(|item| self.production_span(&item.rule, item.production))
+    }
+
+    /// Non-terminals that derive at least one string of terminals.
+    /// References to undefined rules are never productive.
+    pub fn productive_rules(&self) -> HashSet<String> {
+        let mut productive = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (nt, productions) in &self.rules {
+                if productive.contains(nt) {
+                    continue;
+                }
+                let derives_terminals = productions.iter().any(|production| {
+                    production.iter().all(|item| match item {
+                        Item::NonTerminal(name) => productive.contains(name),
+                        _ => true,
+                    })
+                });
+                if derives_terminals {
+                    productive.insert(nt.clone());
+                    changed = true;
+                }
+            }
+        }
+        productive
+    }
+
+    /// Non-terminals reachable from the start symbol, the start symbol included.
+    pub fn reachable_rules(&self) -> HashSet<String> {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<&String> = self.rules.keys().take(1).collect();
+        while let Some(nt) = stack.pop() {
+            if !reachable.insert(nt.clone()) {
+                continue;
+            }
+            for item in self.rules.get(nt).into_iter().flatten().flatten() {
+                if let Item::NonTerminal(name) = item
+                    && !reachable.contains(name)
+                {
+                    stack.push(name);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Terminals, regexes and token classes occurring anywhere in the strings
+    /// each non-terminal derives, not only at their start. Productions using
+    /// a non-productive rule derive no string and are left out.
+    pub fn reachable_terminals(&self) -> IndexMap<String, HashSet<SetItem>> {
+        let productive = self.productive_rules();
+        let mut terminals: IndexMap<String, HashSet<SetItem>> = self
+            .rules
+            .keys()
+            .map(|nt| (nt.clone(), HashSet::new()))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (nt, productions) in &self.rules {
+                let derives_terminals = |production: &&Vec<Item>| {
+                    production.iter().all(|item| match item {
+                        Item::NonTerminal(name) => productive.contains(name),
+                        _ => true,
+                    })
+                };
+                for item in productions.iter().filter(derives_terminals).flatten() {
+                    let found: Vec<SetItem> = match item {
+                        Item::NonTerminal(name) => terminals[name].iter().cloned().collect(),
+                        Item::EndOfInput => Vec::new(),
+                        item => vec![SetItem::try_from(item).expect("not a non-terminal")],
+                    };
+                    let set = &mut terminals[nt];
+                    for terminal in found {
+                        changed |= set.insert(terminal);
+                    }
+                }
+            }
+        }
+        terminals
+    }
+
+    /// Computes the FIRST and FOLLOW set of every non-terminal.
     pub fn first_and_follow_sets(&self) -> Sets {
         extract_sets(self)
     }
 
+    /// Computes the FIRST and FOLLOW sets with `start` as the start symbol
+    /// instead of the first rule.
+    pub fn first_and_follow_sets_from(&self, start: &str) -> Result<Sets, UnknownStart> {
+        if !self.rules.contains_key(start) {
+            return Err(UnknownStart(start.to_string()));
+        }
+        Ok(extract_sets_from(self, Some(start)))
+    }
+
+    /// FIRST set of the non-terminal `nt`, `None` if there is no such rule.
+    /// Contains ε if `nt` is nullable.
+    ///
+    /// Computes the sets of the whole grammar; use [`Bnf::first_and_follow_sets`]
+    /// when asking about several rules.
+    pub fn first_of(&self, nt: &str) -> Option<HashSet<SetItem>> {
+        let mut first = self
+            .rules
+            .contains_key(nt)
+            .then(|| self.first_and_follow_sets().first)?;
+        Some(first.remove(nt).unwrap_or_default())
+    }
+
+    /// FOLLOW set of the non-terminal `nt`, `None` if there is no such rule.
+    pub fn follow_of(&self, nt: &str) -> Option<HashSet<SetItem>> {
+        let mut follow = self
+            .rules
+            .contains_key(nt)
+            .then(|| self.first_and_follow_sets().follow)?;
+        Some(follow.remove(nt).unwrap_or_default())
+    }
+
+    /// Whether the non-terminal `nt` derives ε, `false` if there is no such rule.
+    pub fn is_nullable(&self, nt: &str) -> bool {
+        self.first_of(nt)
+            .is_some_and(|first| first.contains(&SetItem::Epsilon))
+    }
+
+    /// Whether the non-terminal `nt` can be reached from the start symbol,
+    /// `false` if there is no such rule.
+    pub fn is_reachable(&self, nt: &str) -> bool {
+        let Some(start) = self.rules.keys().next() else {
+            return false;
+        };
+        let mut seen = HashSet::from([start.as_str()]);
+        let mut pending = vec![start.as_str()];
+        while let Some(rule) = pending.pop() {
+            if rule == nt {
+                return true;
+            }
+            for item in self.rules.get(rule).into_iter().flatten().flatten() {
+                if let Item::NonTerminal(next) = item
+                    && seen.insert(next.as_str())
+                {
+                    pending.push(next.as_str());
+                }
+            }
+        }
+        false
+    }
+
     /// Checks if the grammar is LL(1) and returns detailed information
     ///
     /// If `ignore_regex_conflicts` is true, regex-vs-regex conflicts are skipped.
     /// This is useful when the lexer uses a priority system to resolve such conflicts.
+    ///
+    /// This computes the FIRST and FOLLOW sets again; use [`Bnf::analyze`] to
+    /// share them with the parse table and other checks.
     pub fn is_ll1(&self, ignore_regex_conflicts: bool) -> Result<Ll1Result, Ll1Error> {
-        let sets = self.first_and_follow_sets();
+        self.ll1_conflicts(&self.first_and_follow_sets(), ignore_regex_conflicts)
+    }
+
+    /// Computes the FIRST and FOLLOW sets once, for the checks of [`Analysis`].
+    pub fn analyze(&self) -> Analysis<'_> {
+        Analysis::new(self, self.first_and_follow_sets())
+    }
+
+    /// The LL(1) conflicts, given the grammar's sets.
+    pub(crate) fn ll1_conflicts(
+        &self,
+        sets: &Sets,
+        ignore_regex_conflicts: bool,
+    ) -> Result<Ll1Result, Ll1Error> {
         let mut conflicts = Vec::new();
+        let no_follow = HashSet::new();
 
         for (nt, productions) in &self.rules {
             // Skip non-terminals with only one production
@@ -46,7 +292,11 @@ impl Bnf {
             // Check FIRST/FIRST conflicts between all pairs of productions
             for i in 0..productions.len() {
                 for j in (i + 1)..productions.len() {
-                    let item_conflicts = find_set_conflicts(&prod_firsts[i].0, &prod_firsts[j].0, ignore_regex_conflicts)?;
+                    let item_conflicts = find_set_conflicts(
+                        &prod_firsts[i].0,
+                        &prod_firsts[j].0,
+                        ignore_regex_conflicts,
+                    )?;
 
                     if !item_conflicts.is_empty() {
                         conflicts.push(Ll1Conflict {
@@ -55,7 +305,11 @@ impl Bnf {
                                 production1: productions[i].clone(),
                                 production2: productions[j].clone(),
                             },
+                            production_indices: (i, j),
                             conflicts: item_conflicts,
+                            lookahead: None,
+                            ambiguity: None,
+                            input: None,
                         });
                     }
                 }
@@ -64,15 +318,18 @@ impl Bnf {
             // Check FIRST/FOLLOW conflicts
             // If production i is nullable, check if FIRST of other productions
             // conflicts with FOLLOW(nt)
-            let follow_set = sets.follow.get(nt).cloned().unwrap_or_default();
+            let follow_set = sets.follow.get(nt).unwrap_or(&no_follow);
 
             for i in 0..productions.len() {
                 // Check if production i is nullable
                 if prod_firsts[i].1 {
                     for j in 0..productions.len() {
                         if i != j {
-                            let item_conflicts =
-                                find_set_conflicts(&prod_firsts[j].0, &follow_set, ignore_regex_conflicts)?;
+                            let item_conflicts = find_set_conflicts(
+                                &prod_firsts[j].0,
+                                follow_set,
+                                ignore_regex_conflicts,
+                            )?;
 
                             if !item_conflicts.is_empty() {
                                 conflicts.push(Ll1Conflict {
@@ -81,7 +338,11 @@ impl Bnf {
                                         nullable_production: productions[i].clone(),
                                         other_production: productions[j].clone(),
                                     },
+                                    production_indices: (i, j),
                                     conflicts: item_conflicts,
+                                    lookahead: None,
+                                    ambiguity: None,
+                                    input: None,
                                 });
                             }
                         }
@@ -90,8 +351,253 @@ impl Bnf {
             }
         }
 
+        // The order documented on `Ll1Result`
+        conflicts.sort_by_key(|c| {
+            (
+                self.rules.get_index_of(&c.non_terminal),
+                c.production_indices,
+                matches!(c.kind, Ll1ConflictKind::FirstFollow { .. }),
+            )
+        });
+
         Ok(Ll1Result { conflicts })
     }
+
+    /// Checks if the grammar is strong LL(k): for every rule, no two
+    /// productions share a lookahead of k tokens, from FIRST_k of the
+    /// production followed by FOLLOW_k of the rule. With `k = 1` this is the
+    /// check of [`Bnf::is_ll1`], with one conflict per pair of productions.
+    pub fn is_llk(&self, k: usize, ignore_regex_conflicts: bool) -> Result<LlkResult, Ll1Error> {
+        let sets = extract_lookahead_sets(self, k);
+        let mut conflicts = Vec::new();
+        for (nt, productions) in &self.rules {
+            let lookaheads: Vec<_> = productions
+                .iter()
+                .map(|production| sets.production_lookahead(nt, production))
+                .collect();
+            for i in 0..productions.len() {
+                for j in (i + 1)..productions.len() {
+                    let sequences = ambiguous_lookahead(
+                        &lookaheads[i],
+                        &lookaheads[j],
+                        ignore_regex_conflicts,
+                    )?;
+                    if !sequences.is_empty() {
+                        conflicts.push(LlkConflict {
+                            non_terminal: nt.clone(),
+                            production_indices: (i, j),
+                            productions: (productions[i].clone(), productions[j].clone()),
+                            sequences,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(LlkResult {
+            k: sets.k,
+            conflicts,
+        })
+    }
+
+    /// Checks k = 1, 2, ... `max_k` with [`Bnf::is_llk`] and returns the
+    /// first result without conflicts, or the one for `max_k` if there is
+    /// none, so `result.k` is the smallest k for which the grammar is LL(k).
+    pub fn smallest_llk(
+        &self,
+        max_k: usize,
+        ignore_regex_conflicts: bool,
+    ) -> Result<LlkResult, Ll1Error> {
+        let mut k = 1;
+        loop {
+            let result = self.is_llk(k, ignore_regex_conflicts)?;
+            if result.is_llk() || k >= max_k {
+                return Ok(result);
+            }
+            k += 1;
+        }
+    }
+
+    /// The LR(0) automaton, of the grammar with a fresh start rule
+    /// `___start := S $` unless it already has one (see [`Bnf::augment_start`]).
+    pub fn lr0_automaton(&self) -> Lr0Automaton {
+        build_lr0_automaton(&self.with_augmented_start())
+    }
+
+    /// Checks if the grammar is SLR(1): in no state of the LR(0) automaton
+    /// is a rule reduced on a lookahead from its FOLLOW set that is also
+    /// shifted, or on which another rule is reduced.
+    pub fn is_slr1(&self, ignore_regex_conflicts: bool) -> Result<LrResult, Ll1Error> {
+        let bnf = self.with_augmented_start();
+        let automaton = build_lr0_automaton(&bnf);
+        let sets = bnf.first_and_follow_sets();
+        let follow = |_: usize, item: &LrItem| -> HashSet<SetItem> {
+            (sets.follow.get(&item.rule).into_iter().flatten())
+                .filter(|lookahead| **lookahead != SetItem::Epsilon)
+                .cloned()
+                .collect()
+        };
+        let conflicts = lr_conflicts(&automaton, follow, ignore_regex_conflicts)?;
+        Ok(LrResult {
+            method: LrMethod::Slr1,
+            states: automaton.states.len(),
+            conflicts,
+        })
+    }
+
+    /// Checks if the grammar is LALR(1), like [`Bnf::is_slr1`] but reducing
+    /// each item on its LALR(1) lookaheads, as yacc and lalrpop do.
+    pub fn is_lalr1(&self, ignore_regex_conflicts: bool) -> Result<LrResult, Ll1Error> {
+        let bnf = self.with_augmented_start();
+        let automaton = build_lr0_automaton(&bnf);
+        let lookaheads = lalr1_lookaheads(&bnf, &automaton, &bnf.first_and_follow_sets().first);
+        let lookahead = |state: usize, item: &LrItem| -> HashSet<SetItem> {
+            (lookaheads[state].get(&(item.rule.clone(), item.production)))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let conflicts = lr_conflicts(&automaton, lookahead, ignore_regex_conflicts)?;
+        Ok(LrResult {
+            method: LrMethod::Lalr1,
+            states: automaton.states.len(),
+            conflicts,
+        })
+    }
+
+    /// The LALR(1) parse table, of the grammar with the start rule of
+    /// [`Bnf::lr0_automaton`].
+    pub fn lalr1_table(&self) -> LrTable {
+        let bnf = self.with_augmented_start();
+        let automaton = build_lr0_automaton(&bnf);
+        let lookaheads = lalr1_lookaheads(&bnf, &automaton, &bnf.first_and_follow_sets().first);
+        build_lr_table(&automaton, LrMethod::Lalr1, |state, item| {
+            (lookaheads[state].get(&(item.rule.clone(), item.production)))
+                .cloned()
+                .unwrap_or_default()
+        })
+    }
+
+    fn with_augmented_start(&self) -> Bnf {
+        match self.rules.keys().next() {
+            Some(start) if start == AUGMENTED_START => self.clone(),
+            _ => self.augment_start(),
+        }
+    }
+
+    /// Determines for every conflict in `result` the smallest k ≤ `max_k` for
+    /// which k tokens of lookahead tell the two productions apart.
+    ///
+    /// Uses strong LL(k) lookahead (FIRST_k · FOLLOW_k), so a conflict reported
+    /// as unresolved may still be resolvable by a parser that tracks context.
+    pub fn annotate_lookahead(
+        &self,
+        result: &mut Ll1Result,
+        max_k: usize,
+        ignore_regex_conflicts: bool,
+    ) -> Result<(), Ll1Error> {
+        let mut pending: Vec<usize> = (0..result.conflicts.len()).collect();
+
+        for k in 2..=max_k {
+            if pending.is_empty() {
+                break;
+            }
+            let sets = extract_lookahead_sets(self, k);
+            let mut unresolved = Vec::new();
+            for idx in pending {
+                let conflict = &mut result.conflicts[idx];
+                let (production1, production2) = conflict.kind.productions();
+                let lookahead1 = sets.production_lookahead(&conflict.non_terminal, production1);
+                let lookahead2 = sets.production_lookahead(&conflict.non_terminal, production2);
+                if lookahead_sets_conflict(&lookahead1, &lookahead2, ignore_regex_conflicts)? {
+                    unresolved.push(idx);
+                } else {
+                    conflict.lookahead = Some(LookaheadResolution::ResolvedAt(k));
+                }
+            }
+            pending = unresolved;
+        }
+
+        for idx in pending {
+            result.conflicts[idx].lookahead = Some(LookaheadResolution::Unresolved(max_k));
+        }
+        Ok(())
+    }
+
+    /// Finds for every conflict in `result` the longest lookahead, up to
+    /// `max_k` tokens, on which its two productions still overlap, and the
+    /// ambiguous token sequences of that length.
+    ///
+    /// Like [`Bnf::annotate_lookahead`], this uses strong LL(k) lookahead.
+    pub fn annotate_ambiguity(
+        &self,
+        result: &mut Ll1Result,
+        max_k: usize,
+        ignore_regex_conflicts: bool,
+    ) -> Result<(), Ll1Error> {
+        let mut pending: Vec<usize> = (0..result.conflicts.len()).collect();
+
+        for k in 1..=max_k.max(1) {
+            if pending.is_empty() {
+                break;
+            }
+            let sets = extract_lookahead_sets(self, k);
+            let mut unresolved = Vec::new();
+            for idx in pending {
+                let conflict = &mut result.conflicts[idx];
+                let (production1, production2) = conflict.kind.productions();
+                let lookahead1 = sets.production_lookahead(&conflict.non_terminal, production1);
+                let lookahead2 = sets.production_lookahead(&conflict.non_terminal, production2);
+                let sequences =
+                    ambiguous_lookahead(&lookahead1, &lookahead2, ignore_regex_conflicts)?;
+                if sequences.is_empty() {
+                    if let Some(window) = &mut conflict.ambiguity {
+                        window.resolved = true;
+                    }
+                } else {
+                    conflict.ambiguity = Some(AmbiguityWindow {
+                        tokens: k,
+                        sequences,
+                        resolved: false,
+                    });
+                    unresolved.push(idx);
+                }
+            }
+            pending = unresolved;
+        }
+        Ok(())
+    }
+
+    /// Adds to every conflict in `result` the fewest tokens that take a
+    /// parser from the start rule to its decision, followed by a token both
+    /// productions accept, to reproduce it with a real parser.
+    ///
+    /// For a FIRST/FOLLOW conflict the next token follows the rule in some
+    /// context, not necessarily in the one the prefix leads to.
+    pub fn annotate_input(&self, result: &mut Ll1Result) -> Result<(), GenerateError> {
+        let prefixes = shortest_prefixes(self)?;
+        let witnesses = regex_witnesses(self)?;
+        for conflict in &mut result.conflicts {
+            let (Some(prefix), Some(overlap)) = (
+                prefixes.get(conflict.non_terminal.as_str()),
+                conflict.conflicts.first(),
+            ) else {
+                continue;
+            };
+            let next = match (&overlap.witness, &overlap.item1) {
+                (Some(witness), _) => Some(witness.clone()),
+                (None, SetItem::Terminal(terminal)) => {
+                    Some(strip_terminal_quotes(terminal).to_string())
+                }
+                (None, SetItem::Regex(regex)) => witnesses.get(regex).cloned(),
+                (None, SetItem::TokenClass(class)) => Some(class.clone()),
+                (None, SetItem::Epsilon | SetItem::EndOfInput) => None,
+            };
+            conflict.input = Some(ConflictInput {
+                prefix: prefix.clone(),
+                next,
+            });
+        }
+        Ok(())
+    }
 }
 
 impl fmt::Display for Item {
@@ -100,11 +606,18 @@ impl fmt::Display for Item {
             Item::NonTerminal(s) => write!(f, "{}", s),
             Item::Terminal(s) => write!(f, "{}", s),
             Item::Regex(s) => write!(f, "{}", s),
+            Item::TokenClass(s) => write!(f, "{}", s),
+            Item::EndOfInput => Symbols::with_current(|s| write!(f, "{}", s.end_of_input)),
         }
     }
 }
 
+/// Writes an empty production as ε, so it reads back without an
+/// empty-alternative warning
 fn write_items(f: &mut fmt::Formatter<'_>, items: &[Item]) -> fmt::Result {
+    if items.is_empty() {
+        return Symbols::with_current(|s| write!(f, "{}", s.grammar_epsilon()));
+    }
     for (i, item) in items.iter().enumerate() {
         if i > 0 {
             write!(f, " ")?;
@@ -142,3 +655,49 @@ impl fmt::Display for Bnf {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn queries_answer_for_one_rule() {
+        let source = "list := \"[\" items \"]\".\nitems := [ item { \",\" item } ].\nitem := /[0-9]+/.\nold := \"x\".";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+
+        let first = bnf.first_of("items").unwrap();
+        assert!(first.contains(&SetItem::Regex("/[0-9]+/".to_string())));
+        assert!(first.contains(&SetItem::Epsilon));
+        let follow = HashSet::from([
+            SetItem::Terminal("\",\"".to_string()),
+            SetItem::Terminal("\"]\"".to_string()),
+        ]);
+        assert_eq!(bnf.follow_of("item").unwrap(), follow);
+        assert!(bnf.first_of("missing").is_none() && bnf.follow_of("missing").is_none());
+
+        assert!(bnf.is_nullable("items"));
+        assert!(!bnf.is_nullable("item") && !bnf.is_nullable("missing"));
+
+        assert!(bnf.is_reachable("list") && bnf.is_reachable("item"));
+        assert!(!bnf.is_reachable("old") && !bnf.is_reachable("missing"));
+    }
+
+    #[test]
+    fn printed_bnf_reads_back_without_warnings() {
+        let source = "list := \"[\" [ item { \",\" item } ] \"]\".\nitem := /[0-9]+/.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+
+        for symbols in [Symbols::default(), Symbols::ascii()] {
+            let printed = symbols.scope(|| bnf.to_string());
+            assert!(!printed.contains("| \n"));
+            let reparsed = Sebnf::parse_str(&printed, "printed").unwrap();
+            assert!(
+                reparsed.warnings(&printed, "printed").is_empty(),
+                "{}",
+                printed
+            );
+            assert_eq!(reparsed.to_bnf().rules, bnf.rules);
+        }
+    }
+}
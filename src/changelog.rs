@@ -0,0 +1,252 @@
+//! Release notes from the structural differences between two grammar
+//! versions.
+//!
+//! Rules are matched by name and their alternatives by how they are
+//! written, as in [`crate::merge`], so reordering alternatives is not a
+//! change. Terminals and regexes are compared as the
+//! [`vocabulary`](crate::vocabulary::vocabulary) of each version.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::changelog::changelog;
+//!
+//! let old = Sebnf::parse_str("stmt := expr \";\".\nexpr := /[0-9]+/.", "old").unwrap();
+//! let new = Sebnf::parse_str(
+//!     "stmt := expr \";\" | match.\nmatch := \"match\" expr \"=>\" expr.\nexpr := /[0-9]+/.",
+//!     "new",
+//! )
+//! .unwrap();
+//! let changes = changelog(&old, &new);
+//! assert_eq!(changes.rules_added, ["match"]);
+//! assert_eq!(changes.alternatives_added, [("stmt".to_string(), "match".to_string())]);
+//! assert_eq!(changes.tokens_introduced.len(), 2);
+//! ```
+
+use std::fmt::Write;
+
+use crate::sebnf::{Sebnf, written};
+use crate::vocabulary::{Token, vocabulary};
+
+/// What changed from one grammar version to the next.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changelog {
+    /// The start rule before and after, if it changed
+    pub start: Option<(String, String)>,
+    /// In the order of the new version
+    pub rules_added: Vec<String>,
+    /// In the order of the old version
+    pub rules_removed: Vec<String>,
+    /// Rule and alternative as written, for rules in both versions
+    pub alternatives_added: Vec<(String, String)>,
+    pub alternatives_removed: Vec<(String, String)>,
+    /// Terminals and regexes only the new version uses, with their uses there
+    pub tokens_introduced: Vec<Token>,
+    /// Terminals and regexes only the old version uses, with their uses there
+    pub tokens_removed: Vec<Token>,
+}
+
+impl Changelog {
+    pub fn is_empty(&self) -> bool {
+        *self == Changelog::default()
+    }
+
+    /// Release notes in Markdown, with a list of additions, removals and
+    /// other changes.
+    pub fn to_markdown(&self) -> String {
+        if self.is_empty() {
+            return "No grammar changes.\n".to_string();
+        }
+        let mut added = Vec::new();
+        for rule in &self.rules_added {
+            added.push(format!("new rule {}", code(rule)));
+        }
+        for (rule, alternative) in &self.alternatives_added {
+            added.push(format!(
+                "{} gained alternative {}",
+                code(rule),
+                code(alternative)
+            ));
+        }
+        for token in &self.tokens_introduced {
+            added.push(format!("{} {} introduced", token.kind, code(&token.symbol)));
+        }
+        let mut removed = Vec::new();
+        for rule in &self.rules_removed {
+            removed.push(format!("rule {} removed", code(rule)));
+        }
+        for (rule, alternative) in &self.alternatives_removed {
+            removed.push(format!(
+                "{} lost alternative {}",
+                code(rule),
+                code(alternative)
+            ));
+        }
+        for token in &self.tokens_removed {
+            removed.push(format!(
+                "{} {} no longer used",
+                token.kind,
+                code(&token.symbol)
+            ));
+        }
+        let mut changed = Vec::new();
+        if let Some((old, new)) = &self.start {
+            changed.push(format!(
+                "start rule is now {} (was {})",
+                code(new),
+                code(old)
+            ));
+        }
+
+        let mut text = String::from("## Grammar changes\n");
+        for (heading, entries) in [("Added", added), ("Removed", removed), ("Changed", changed)] {
+            if entries.is_empty() {
+                continue;
+            }
+            let _ = write!(text, "\n### {}\n\n", heading);
+            for entry in entries {
+                let _ = writeln!(text, "- {}", entry);
+            }
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let alternatives = |alternatives: &[(String, String)]| -> Vec<serde_json::Value> {
+            (alternatives.iter())
+                .map(|(rule, alternative)| {
+                    serde_json::json!({ "rule": rule, "alternative": alternative })
+                })
+                .collect()
+        };
+        let tokens = |tokens: &[Token]| -> Vec<serde_json::Value> {
+            (tokens.iter())
+                .map(|token| {
+                    serde_json::json!({
+                        "symbol": token.symbol,
+                        "kind": token.kind.to_string(),
+                        "rules": token.rules,
+                    })
+                })
+                .collect()
+        };
+        let start = self
+            .start
+            .as_ref()
+            .map(|(old, new)| serde_json::json!({ "old": old, "new": new }));
+        serde_json::json!({
+            "start": start,
+            "rules_added": self.rules_added,
+            "rules_removed": self.rules_removed,
+            "alternatives_added": alternatives(&self.alternatives_added),
+            "alternatives_removed": alternatives(&self.alternatives_removed),
+            "tokens_introduced": tokens(&self.tokens_introduced),
+            "tokens_removed": tokens(&self.tokens_removed),
+        })
+    }
+}
+
+/// A Markdown code span, with enough backticks around text containing some
+fn code(text: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let ticks = "`".repeat(longest + 1);
+    match longest {
+        0 => format!("`{}`", text),
+        _ => format!("{} {} {}", ticks, text, ticks),
+    }
+}
+
+/// The differences from `old` to `new`.
+pub fn changelog(old: &Sebnf, new: &Sebnf) -> Changelog {
+    let mut changes = Changelog::default();
+    if let (Some(old_start), Some(new_start)) = (old.rules.keys().next(), new.rules.keys().next())
+        && old_start != new_start
+    {
+        changes.start = Some((old_start.clone(), new_start.clone()));
+    }
+
+    changes.rules_added = (new.rules.keys())
+        .filter(|rule| !old.rules.contains_key(*rule))
+        .cloned()
+        .collect();
+    changes.rules_removed = (old.rules.keys())
+        .filter(|rule| !new.rules.contains_key(*rule))
+        .cloned()
+        .collect();
+    for (rule, alternatives) in &new.rules {
+        let Some(old_alternatives) = old.rules.get(rule) else {
+            continue;
+        };
+        let old_written: Vec<String> = old_alternatives.iter().map(|alt| written(alt)).collect();
+        let new_written: Vec<String> = alternatives.iter().map(|alt| written(alt)).collect();
+        for alternative in &new_written {
+            if !old_written.contains(alternative) {
+                changes
+                    .alternatives_added
+                    .push((rule.clone(), alternative.clone()));
+            }
+        }
+        for alternative in &old_written {
+            if !new_written.contains(alternative) {
+                changes
+                    .alternatives_removed
+                    .push((rule.clone(), alternative.clone()));
+            }
+        }
+    }
+
+    let (old_tokens, new_tokens) = (vocabulary(old), vocabulary(new));
+    let used = |tokens: &[Token], token: &Token| tokens.iter().any(|t| t.symbol == token.symbol);
+    changes.tokens_introduced = (new_tokens.iter())
+        .filter(|token| !used(&old_tokens, token))
+        .cloned()
+        .collect();
+    changes.tokens_removed = (old_tokens.iter())
+        .filter(|token| !used(&new_tokens, token))
+        .cloned()
+        .collect();
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_notes_list_added_removed_and_changed() {
+        let old = Sebnf::parse_str(
+            "prog := { stmt }.\nstmt := expr \";\" | \"skip\" \";\".\nexpr := /[0-9]+/ | \"`\".",
+            "old",
+        )
+        .unwrap();
+        let new = Sebnf::parse_str(
+            "file := { stmt }.\nstmt := expr \";\" | matchexpr.\n\
+             matchexpr := \"match\" expr \"=>\" expr.\nexpr := \"`\" | /[0-9]+/.",
+            "new",
+        )
+        .unwrap();
+        let changes = changelog(&old, &new);
+        assert_eq!(
+            changes.to_markdown(),
+            "## Grammar changes\n\n\
+             ### Added\n\n\
+             - new rule `file`\n\
+             - new rule `matchexpr`\n\
+             - `stmt` gained alternative `matchexpr`\n\
+             - terminal `\"=>\"` introduced\n\
+             - terminal `\"match\"` introduced\n\n\
+             ### Removed\n\n\
+             - rule `prog` removed\n\
+             - `stmt` lost alternative `\"skip\" \";\"`\n\
+             - terminal `\"skip\"` no longer used\n\n\
+             ### Changed\n\n\
+             - start rule is now `file` (was `prog`)\n"
+        );
+        assert_eq!(code("\"`\""), "`` \"`\" ``");
+        assert!(changelog(&new, &new).is_empty());
+    }
+}
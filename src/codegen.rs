@@ -0,0 +1,1345 @@
+//! Rust parser crates generated from a grammar.
+//!
+//! [`parser_crate`] writes everything a crate needs to parse the language of
+//! an LL(1) grammar: a token enum and a lexer, a recursive descent parser
+//! that picks productions from the LL(1) parse table, syntax tree types with
+//! a node per rule, typed syntax trees from [`typed_ast`](crate::typed_ast),
+//! a visitor, tests seeded from generated sentences, and a
+//! build script that regenerates the sources when the grammar changes.
+//!
+//! The generated crate depends on `regex` if the grammar has regexes, and on
+//! nothing else. Its lexer skips whitespace and takes the longest match, a
+//! terminal winning over a regex that matches the same text.
+//!
+//! [`rust_rd_parser`] writes the same lexer, tree types and parser as one
+//! module without dependencies on other files, to drop into an existing
+//! crate.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::codegen::{CrateOptions, parser_crate};
+//!
+//! let source = "sum := num { \"+\" num }.\nnum := /[0-9]+/.\n";
+//! let sebnf = Sebnf::parse_str(source, "g").unwrap();
+//! let files = parser_crate(source, &sebnf, &CrateOptions::new("sums")).unwrap();
+//! let parser = files.iter().find(|f| f.path.ends_with("parser.rs")).unwrap();
+//! assert!(parser.contents.contains("fn parse_sum(&mut self"));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+
+use crate::analysis::Analysis;
+use crate::bnf::{Bnf, Item};
+use crate::converter::{fnv1a, soft_keyword_regex};
+use crate::sebnf::{self, Sebnf};
+use crate::sentences::{self, GenerateError, GenerateOptions};
+use crate::sets::{SetItem, format_production, strip_regex_delimiters, strip_terminal_quotes};
+use crate::typed_ast::typed_rs;
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum CodegenError {
+    #[error("the grammar has no rules")]
+    #[diagnostic(code(sebnf::no_rules), help("the parser starts with the first rule"))]
+    NoRules,
+    #[error("the grammar is not LL(1): several productions apply to {}", cells.join(", "))]
+    #[diagnostic(code(sebnf::not_ll1), help("`is-ll1` lists the conflicts"))]
+    NotLl1 {
+        /// Each as `'rule' on lookahead`
+        cells: Vec<String>,
+    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Generate(#[from] GenerateError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateOptions {
+    /// The package name
+    pub name: String,
+    /// Number of generated sentences the tests parse
+    pub tests: usize,
+    /// Start rule passed with `--start`, kept for the regeneration
+    pub start: Option<String>,
+}
+
+impl CrateOptions {
+    pub fn new(name: &str) -> Self {
+        CrateOptions {
+            name: name.to_string(),
+            tests: 20,
+            start: None,
+        }
+    }
+}
+
+/// A file of the crate, with its path relative to the crate's directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    pub contents: String,
+    /// Written only if missing, so that e.g. dependencies added to
+    /// `Cargo.toml` by hand survive a regeneration
+    pub keep_existing: bool,
+}
+
+/// Generates the crate for `sebnf`, whose source `source` is copied into
+/// the crate as `grammar.sebnf`.
+pub fn parser_crate(
+    source: &str,
+    sebnf: &Sebnf,
+    options: &CrateOptions,
+) -> Result<Vec<GeneratedFile>, CodegenError> {
+    let bnf = sebnf.to_bnf();
+    check_rules(&bnf)?;
+    let analysis = bnf.analyze();
+    check_ll1(&analysis)?;
+    let generate = GenerateOptions {
+        count: options.tests,
+        rule_depths: sebnf.max_depths(),
+        ..GenerateOptions::default()
+    };
+    let sentences = sentences::generate(&bnf, &generate)?;
+
+    let names = Names::new(sebnf, &bnf);
+    let file = |path: &str, contents: String| GeneratedFile {
+        path: PathBuf::from(path),
+        contents,
+        keep_existing: false,
+    };
+    let manifest = GeneratedFile {
+        keep_existing: true,
+        ..file("Cargo.toml", cargo_toml(&options.name, &names))
+    };
+    Ok(vec![
+        manifest,
+        file("build.rs", build_rs(source, options)),
+        file("grammar.sebnf", source.to_string()),
+        file("src/lib.rs", LIB_RS.to_string()),
+        file("src/lexer.rs", lexer_rs(&names)),
+        file("src/ast.rs", ast_rs(sebnf, &names)),
+        file("src/parser.rs", parser_rs(&bnf, &analysis, &names)),
+        file("src/visitor.rs", visitor_rs(&names)),
+        file("src/typed.rs", typed_rs(sebnf, &names)),
+        file("tests/sentences.rs", tests_rs(&options.name, &sentences)),
+    ])
+}
+
+/// Generates a recursive descent parser for `sebnf` as a single Rust
+/// module: the lexer, the syntax tree types and the parser of
+/// [`parser_crate`], with `parse` choosing each production by the FIRST and
+/// FOLLOW sets of the LL(1) table and listing them in its errors.
+pub fn rust_rd_parser(sebnf: &Sebnf) -> Result<String, CodegenError> {
+    let bnf = sebnf.to_bnf();
+    check_rules(&bnf)?;
+    let analysis = bnf.analyze();
+    check_ll1(&analysis)?;
+    let names = Names::new(sebnf, &bnf);
+    let parts = [
+        lexer_rs(&names),
+        ast_rs(sebnf, &names),
+        parser_rs(&bnf, &analysis, &names),
+    ];
+    let doc =
+        "A recursive descent parser for the grammar, generated by\n`sebnf_tool codegen rust-rd`.";
+    Ok(single_module(doc, &parts))
+}
+
+/// Generates a table-driven LL(1) parser for `sebnf` as a single Rust
+/// module: the lexer and syntax tree types of [`parser_crate`], the LL(1)
+/// table as constant arrays, and a loop that expands the rules on a stack.
+/// It builds the same tree as [`rust_rd_parser`] without recursion.
+pub fn rust_table_parser(sebnf: &Sebnf) -> Result<String, CodegenError> {
+    let bnf = sebnf.to_bnf();
+    check_rules(&bnf)?;
+    let analysis = bnf.analyze();
+    check_ll1(&analysis)?;
+    let names = Names::new(sebnf, &bnf);
+    let parts = [
+        lexer_rs(&names),
+        ast_rs(sebnf, &names),
+        table_rs(&bnf, &analysis, &names),
+    ];
+    let doc = "A table-driven LL(1) parser for the grammar, generated by\n`sebnf_tool codegen rust-table`.";
+    Ok(single_module(doc, &parts))
+}
+
+/// The files of a crate as one module headed by `doc`: their imports of
+/// each other dropped and the others merged
+fn single_module(doc: &str, parts: &[String]) -> String {
+    let mut imports: Vec<&str> = Vec::new();
+    let mut body = String::new();
+    for part in parts {
+        for line in part.lines().skip_while(|line| line.starts_with("//!")) {
+            if line.starts_with("use crate::") {
+                continue;
+            }
+            if let Some(import) = line.strip_prefix("use ") {
+                if !imports.contains(&import) {
+                    imports.push(import);
+                }
+                continue;
+            }
+            if !(line.is_empty() && (body.is_empty() || body.ends_with("\n\n"))) {
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+    }
+    let mut code = String::new();
+    for line in doc.lines() {
+        let _ = writeln!(code, "//! {}", line);
+    }
+    code.push_str(
+        "//!\n//! [`parse`] turns a string into a tree with a [`Node`] for every rule it\n//! matched and the tokens as leaves.\n\n",
+    );
+    let (std, other): (Vec<_>, Vec<_>) =
+        (imports.into_iter()).partition(|i| i.starts_with("std::"));
+    for group in [std, other].iter().filter(|group| !group.is_empty()) {
+        for import in group {
+            let _ = writeln!(code, "use {}", import);
+        }
+        code.push('\n');
+    }
+    code.push_str(&body);
+    code
+}
+
+/// Generates a lexer for the terminals and regexes of `sebnf`: the
+/// `lexer.rs` of [`parser_crate`], or with `logos` the same types and
+/// `tokenize` built on a `logos` derive, whose priorities resolve matches of
+/// the same length the same way.
+pub fn lexer_module(sebnf: &Sebnf, logos: bool) -> String {
+    let names = Names::new(sebnf, &sebnf.to_bnf());
+    match logos {
+        true => logos_lexer_rs(&names),
+        false => lexer_rs(&names),
+    }
+}
+
+/// Fails if there is no start rule to generate `parse` for
+fn check_rules(bnf: &Bnf) -> Result<(), CodegenError> {
+    match bnf.rules.is_empty() {
+        true => Err(CodegenError::NoRules),
+        false => Ok(()),
+    }
+}
+
+/// Fails with every cell of the LL(1) table with several productions
+fn check_ll1(analysis: &Analysis) -> Result<(), CodegenError> {
+    let conflicts: Vec<String> = (analysis.table().rows.iter())
+        .flat_map(|(rule, cells)| {
+            (cells.iter())
+                .filter(|cell| cell.is_conflict())
+                .map(move |cell| format!("'{}' on {}", rule, cell.lookahead))
+        })
+        .collect();
+    match conflicts.is_empty() {
+        true => Ok(()),
+        false => Err(CodegenError::NotLl1 { cells: conflicts }),
+    }
+}
+
+/// The Rust names of the grammar's tokens and rules
+pub(crate) struct Names {
+    /// Terminals and regexes as written to their `TokenKind` variant
+    pub(crate) tokens: IndexMap<String, String>,
+    /// SEBNF rules to their `Rule` variant and snake case name
+    pub(crate) rules: IndexMap<String, (String, String)>,
+    /// BNF rules, including helpers, to their parse function
+    functions: IndexMap<String, String>,
+}
+
+impl Names {
+    pub(crate) fn new(sebnf: &Sebnf, bnf: &Bnf) -> Self {
+        // A regex that is all of a rule is named after the rule
+        let mut regex_rules: IndexMap<&str, &str> = IndexMap::new();
+        for (rule, alternatives) in &sebnf.rules {
+            if let [alternative] = alternatives.as_slice()
+                && let [sebnf::Item::Regex(regex, _)] = alternative.as_slice()
+            {
+                regex_rules.entry(regex.as_str()).or_insert(rule.as_str());
+            }
+        }
+        // A soft keyword, which the BNF spells as a regex, after the keyword
+        let soft_keywords: HashMap<String, &str> = (sebnf.annotations.values())
+            .flat_map(|annotations| &annotations.soft_keywords)
+            .map(|keyword| (soft_keyword_regex(keyword), keyword.as_str()))
+            .collect();
+        let mut taken = HashSet::new();
+        let mut tokens = IndexMap::new();
+        for item in bnf.rules.values().flatten().flatten() {
+            let name = match item {
+                Item::Terminal(terminal) if !tokens.contains_key(terminal) => {
+                    terminal_name(&unescape(strip_terminal_quotes(terminal)))
+                }
+                Item::Regex(regex) if !tokens.contains_key(regex) => {
+                    match (regex_rules.get(regex.as_str()), soft_keywords.get(regex)) {
+                        (Some(rule), _) => camel(rule),
+                        (None, Some(keyword)) => {
+                            terminal_name(&unescape(strip_terminal_quotes(keyword)))
+                        }
+                        (None, None) => "Regex".to_string(),
+                    }
+                }
+                _ => continue,
+            };
+            let symbol = match item {
+                Item::Terminal(symbol) | Item::Regex(symbol) => symbol.clone(),
+                _ => unreachable!("only tokens are named"),
+            };
+            tokens.insert(symbol, unique(name, &mut taken));
+        }
+
+        let mut taken = HashSet::new();
+        let mut taken_snake = HashSet::new();
+        let rules = (sebnf.rules.keys())
+            .map(|rule| {
+                let variant = unique(camel(rule), &mut taken);
+                let snake = unique(snake(rule), &mut taken_snake);
+                (rule.clone(), (variant, snake))
+            })
+            .collect::<IndexMap<_, _>>();
+        let functions = (bnf.rules.keys())
+            .map(|rule| {
+                let name = match rules.get(rule) {
+                    Some((_, snake)) => snake.clone(),
+                    None => unique(snake(rule.trim_start_matches('_')), &mut taken_snake),
+                };
+                (rule.clone(), format!("parse_{}", name))
+            })
+            .collect();
+        Names {
+            tokens,
+            rules,
+            functions,
+        }
+    }
+
+    fn has_regexes(&self) -> bool {
+        self.tokens.keys().any(|symbol| symbol.starts_with('/'))
+    }
+}
+
+/// `name`, or `name` with the first free number appended
+pub(crate) fn unique(name: String, taken: &mut HashSet<String>) -> String {
+    let mut candidate = name.clone();
+    let mut number = 2;
+    while !taken.insert(candidate.clone()) {
+        candidate = format!("{}{}", name, number);
+        number += 1;
+    }
+    candidate
+}
+
+/// `match_expr` and `matchExpr` as `MatchExpr`
+pub(crate) fn camel(name: &str) -> String {
+    let mut camel = String::new();
+    for part in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.push(first.to_ascii_uppercase());
+            camel.extend(chars);
+        }
+    }
+    match camel.chars().next() {
+        None => "Empty".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("T{}", camel),
+        // The only keyword spelled in camel case
+        _ if camel == "Self" => "Self_".to_string(),
+        _ => camel,
+    }
+}
+
+/// `MatchExpr` and `matchExpr` as `match_expr`
+pub(crate) fn snake(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            snake.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        snake.push(if c.is_ascii_alphanumeric() {
+            c.to_ascii_lowercase()
+        } else {
+            '_'
+        });
+    }
+    snake
+}
+
+/// A variant name for a terminal: keywords in camel case, punctuation by
+/// the names of its characters, like `EqGt` for `=>`
+pub(crate) fn terminal_name(terminal: &str) -> String {
+    if terminal
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return camel(terminal);
+    }
+    let mut name = String::new();
+    for c in terminal.chars() {
+        let part = match c {
+            '(' => "LParen",
+            ')' => "RParen",
+            '[' => "LBracket",
+            ']' => "RBracket",
+            '{' => "LBrace",
+            '}' => "RBrace",
+            '<' => "Lt",
+            '>' => "Gt",
+            '=' => "Eq",
+            '!' => "Bang",
+            '+' => "Plus",
+            '-' => "Minus",
+            '*' => "Star",
+            '/' => "Slash",
+            '%' => "Percent",
+            '^' => "Caret",
+            '&' => "Amp",
+            '|' => "Pipe",
+            '~' => "Tilde",
+            '?' => "Question",
+            ':' => "Colon",
+            ';' => "Semi",
+            ',' => "Comma",
+            '.' => "Dot",
+            '@' => "At",
+            '#' => "Hash",
+            '$' => "Dollar",
+            '\\' => "Backslash",
+            '\'' => "Quote",
+            '"' => "DoubleQuote",
+            '`' => "Backtick",
+            '_' => "Underscore",
+            ' ' => "Space",
+            c if c.is_ascii_alphanumeric() => {
+                name.push(c.to_ascii_uppercase());
+                continue;
+            }
+            c => {
+                let _ = write!(name, "U{:X}", u32::from(c));
+                continue;
+            }
+        };
+        name.push_str(part);
+    }
+    camel(&name)
+}
+
+/// A terminal's text, with `\"`, `\\`, `\n` and `\t` replaced
+pub(crate) fn unescape(terminal: &str) -> String {
+    let mut text = String::new();
+    let mut chars = terminal.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(next @ ('"' | '\\' | 'n' | 't'))) => {
+                chars.next();
+                text.push(match next {
+                    'n' => '\n',
+                    't' => '\t',
+                    next => next,
+                });
+            }
+            (c, _) => text.push(c),
+        }
+    }
+    text
+}
+
+/// A line of documentation, kept on one line
+pub(crate) fn doc_line(text: &str) -> String {
+    text.replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn cargo_toml(name: &str, names: &Names) -> String {
+    let mut toml = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2024\"\n\n[dependencies]\n",
+        name
+    );
+    if names.has_regexes() {
+        toml.push_str("regex = \"1\"\n");
+    }
+    toml
+}
+
+fn build_rs(source: &str, options: &CrateOptions) -> String {
+    let mut args = vec!["-i".to_string(), "grammar.sebnf".to_string()];
+    if let Some(start) = &options.start {
+        args.extend(["--start".to_string(), start.clone()]);
+    }
+    args.extend(
+        [
+            "codegen",
+            "crate",
+            "--out-dir",
+            ".",
+            "--name",
+            &options.name,
+            "--tests",
+        ]
+        .map(str::to_string),
+    );
+    args.push(options.tests.to_string());
+    let args: Vec<String> = args.iter().map(|arg| format!("{:?}", arg)).collect();
+    format!(
+        r#"//! Regenerates the sources when `grammar.sebnf` changes, by running
+//! `sebnf_tool` (or the program in `SEBNF_TOOL`).
+//!
+//! Generated by `sebnf_tool codegen crate`.
+
+use std::path::Path;
+use std::process::Command;
+
+/// FNV-1a hash of the grammar the sources were generated from
+const GRAMMAR_HASH: u64 = {hash:#018x};
+
+fn fnv1a(bytes: &[u8]) -> u64 {{
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {{
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }}
+    hash
+}}
+
+fn main() {{
+    println!("cargo:rerun-if-changed=grammar.sebnf");
+    println!("cargo:rerun-if-env-changed=SEBNF_TOOL");
+    let dir = std::env::var("CARGO_MANIFEST_DIR").expect("cargo sets CARGO_MANIFEST_DIR");
+    let grammar = std::fs::read(Path::new(&dir).join("grammar.sebnf")).expect("grammar.sebnf is readable");
+    if fnv1a(&grammar) == GRAMMAR_HASH {{
+        return;
+    }}
+    let tool = std::env::var("SEBNF_TOOL").unwrap_or_else(|_| "sebnf_tool".to_string());
+    let status = Command::new(&tool)
+        .current_dir(&dir)
+        .args([{args}])
+        .status();
+    match status {{
+        Ok(status) if status.success() => {{}}
+        Ok(status) => panic!("regenerating the parser from grammar.sebnf failed: {{}} exited with {{}}", tool, status),
+        Err(error) => println!(
+            "cargo:warning=grammar.sebnf changed, but {{}} could not be run to regenerate the parser: {{}}",
+            tool, error
+        ),
+    }}
+}}
+"#,
+        hash = fnv1a(source),
+        args = args.join(", ")
+    )
+}
+
+const LIB_RS: &str = r#"//! A parser for `grammar.sebnf`, generated by `sebnf_tool codegen crate`.
+//!
+//! [`parse`] turns a string into a tree with a [`Node`] for every rule it
+//! matched and the tokens as leaves. A [`Visitor`] walks the tree, and
+//! [`typed::parse`] parses into a type per rule instead.
+
+pub mod ast;
+pub mod lexer;
+pub mod parser;
+pub mod typed;
+pub mod visitor;
+
+pub use ast::{Node, Rule, Tree};
+pub use lexer::{LexError, Token, TokenKind, tokenize};
+pub use parser::{Error, parse};
+pub use visitor::Visitor;
+"#;
+
+/// The `TokenKind` enum and the `Token` and `LexError` types. `attributes`
+/// gives those of each variant by its terminal or regex, and, given `None`,
+/// those of the enum in place of its derives.
+fn token_types(names: &Names, attributes: impl Fn(Option<&str>) -> Option<String>) -> String {
+    let derives = "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]".to_string();
+    let mut code = format!(
+        "{}\npub enum TokenKind {{\n",
+        attributes(None).unwrap_or(derives)
+    );
+    for (symbol, variant) in &names.tokens {
+        let _ = writeln!(code, "    /// `{}`", doc_line(symbol));
+        if let Some(attributes) = attributes(Some(symbol)) {
+            let _ = writeln!(code, "    {}", attributes);
+        }
+        let _ = writeln!(code, "    {},", variant);
+    }
+    code.push_str(
+        "}\n\nimpl TokenKind {\n    /// The terminal or regex as written in the grammar\n    pub fn symbol(self) -> &'static str {\n        match self {\n",
+    );
+    for (symbol, variant) in &names.tokens {
+        let _ = writeln!(code, "            TokenKind::{} => {:?},", variant, symbol);
+    }
+    code.push_str(
+        r#"        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    /// Byte offsets into the input
+    pub span: Range<usize>,
+}
+
+/// No token matches the input at `offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub offset: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no token matches the input at offset {}", self.offset)
+    }
+}
+
+impl std::error::Error for LexError {}
+"#,
+    );
+    code
+}
+
+fn logos_lexer_rs(names: &Names) -> String {
+    let regexes = names.tokens.keys().filter(|symbol| symbol.starts_with('/'));
+    // Both take the longest match; of equally long ones, a terminal wins
+    // over a regex and a regex over the ones after it
+    let priorities: HashMap<&str, usize> = (regexes.rev().enumerate())
+        .map(|(i, regex)| (regex.as_str(), i + 1))
+        .collect();
+    let terminal_priority = priorities.len() + 1;
+    let attributes = |symbol: Option<&str>| match symbol {
+        None => Some(
+            "#[derive(Logos, Debug, Clone, Copy, PartialEq, Eq, Hash)]\n#[logos(skip r\"\\s+\")]"
+                .to_string(),
+        ),
+        Some(regex) if regex.starts_with('/') => Some(format!(
+            "#[regex({:?}, priority = {})]",
+            strip_regex_delimiters(regex),
+            priorities[regex]
+        )),
+        Some(terminal) => Some(format!(
+            "#[token({:?}, priority = {})]",
+            unescape(strip_terminal_quotes(terminal)),
+            terminal_priority
+        )),
+    };
+    let mut code = String::from(
+        "//! The tokens of the grammar and a lexer for them, generated by\n//! `sebnf_tool codegen lexer`.\n\nuse std::fmt;\nuse std::ops::Range;\n\nuse logos::Logos;\n\n",
+    );
+    code.push_str(&token_types(names, attributes));
+    code.push_str(
+        r#"
+/// Splits `input` into tokens, skipping whitespace. The longest match wins,
+/// and a terminal wins over a regex matching the same text.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    let mut lexer = TokenKind::lexer(input);
+    let mut tokens = Vec::new();
+    while let Some(kind) = lexer.next() {
+        let Ok(kind) = kind else {
+            return Err(LexError {
+                offset: lexer.span().start,
+            });
+        };
+        tokens.push(Token {
+            kind,
+            text: lexer.slice().to_string(),
+            span: lexer.span(),
+        });
+    }
+    Ok(tokens)
+}
+"#,
+    );
+    code
+}
+
+fn lexer_rs(names: &Names) -> String {
+    let regexes = names.has_regexes();
+    let mut code = String::from(
+        "//! The tokens of the grammar and a lexer for them.\n\nuse std::fmt;\nuse std::ops::Range;\n",
+    );
+    if regexes {
+        code.push_str("\nuse regex::Regex;\n");
+    }
+    code.push('\n');
+    code.push_str(&token_types(names, |_| None));
+    code.push_str(
+        r#"
+const TERMINALS: &[(&str, TokenKind)] = &[
+"#,
+    );
+    for (symbol, variant) in &names.tokens {
+        if !symbol.starts_with('/') {
+            let text = unescape(strip_terminal_quotes(symbol));
+            let _ = writeln!(code, "    ({:?}, TokenKind::{}),", text, variant);
+        }
+    }
+    code.push_str("];\n");
+    if regexes {
+        code.push_str("\nconst REGEXES: &[(&str, TokenKind)] = &[\n");
+        for (symbol, variant) in &names.tokens {
+            if symbol.starts_with('/') {
+                let pattern = strip_regex_delimiters(symbol);
+                let _ = writeln!(code, "    ({:?}, TokenKind::{}),", pattern, variant);
+            }
+        }
+        code.push_str("];\n");
+    }
+    code.push_str(
+        r#"
+/// Splits `input` into tokens, skipping whitespace. The longest match wins,
+/// and a terminal wins over a regex matching the same text.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+"#,
+    );
+    if regexes {
+        code.push_str(
+            r#"    let regexes: Vec<(Regex, TokenKind)> = REGEXES
+        .iter()
+        .map(|(pattern, kind)| {
+            let regex = Regex::new(&format!("^(?:{})", pattern));
+            (regex.expect("the grammar's regexes are valid"), *kind)
+        })
+        .collect();
+"#,
+        );
+    }
+    code.push_str(
+        r#"    let mut tokens = Vec::new();
+    let mut offset = 0;
+    while offset < input.len() {
+        let rest = &input[offset..];
+        let trimmed = rest.trim_start();
+        if trimmed.len() < rest.len() {
+            offset += rest.len() - trimmed.len();
+            continue;
+        }
+        let mut longest: Option<(usize, TokenKind)> = None;
+        for (terminal, kind) in TERMINALS {
+            if !terminal.is_empty()
+                && rest.starts_with(terminal)
+                && longest.is_none_or(|(len, _)| terminal.len() > len)
+            {
+                longest = Some((terminal.len(), *kind));
+            }
+        }
+"#,
+    );
+    if regexes {
+        code.push_str(
+            r#"        for (regex, kind) in &regexes {
+            if let Some(found) = regex.find(rest)
+                && found.end() > 0
+                && longest.is_none_or(|(len, _)| found.end() > len)
+            {
+                longest = Some((found.end(), *kind));
+            }
+        }
+"#,
+        );
+    }
+    code.push_str(
+        r#"        let Some((len, kind)) = longest else {
+            return Err(LexError { offset });
+        };
+        tokens.push(Token {
+            kind,
+            text: rest[..len].to_string(),
+            span: offset..offset + len,
+        });
+        offset += len;
+    }
+    Ok(tokens)
+}
+"#,
+    );
+    code
+}
+
+fn ast_rs(sebnf: &Sebnf, names: &Names) -> String {
+    let mut code = String::from(
+        r#"//! The syntax tree.
+
+use std::ops::Range;
+
+use crate::lexer::Token;
+
+/// The rules of the grammar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+"#,
+    );
+    for (rule, (variant, _)) in &names.rules {
+        let alternatives: Vec<String> = sebnf.rules[rule]
+            .iter()
+            .map(|alternative| sebnf::written(alternative))
+            .collect();
+        let doc = doc_line(&format!("{} := {}", rule, alternatives.join(" | ")));
+        let _ = writeln!(code, "    /// `{}`\n    {},", doc, variant);
+    }
+    code.push_str(
+        "}\n\nimpl Rule {\n    /// The name of the rule in the grammar\n    pub fn name(self) -> &'static str {\n        match self {\n",
+    );
+    for (rule, (variant, _)) in &names.rules {
+        let _ = writeln!(code, "            Rule::{} => {:?},", variant, rule);
+    }
+    code.push_str(
+        r#"        }
+    }
+}
+
+/// What a rule matched. Its `[ ]`, `{ }` and groups add no nodes of their
+/// own, so their children are the node's children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub rule: Rule,
+    pub children: Vec<Tree>,
+    /// Byte offsets into the input; empty if the rule matched no tokens
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tree {
+    Node(Node),
+    Token(Token),
+}
+
+impl Node {
+    /// The child nodes of `rule`
+    pub fn nodes(&self, rule: Rule) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter_map(move |child| match child {
+            Tree::Node(node) if node.rule == rule => Some(node),
+            _ => None,
+        })
+    }
+
+    /// The tokens among the children
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.children.iter().filter_map(|child| match child {
+            Tree::Token(token) => Some(token),
+            Tree::Node(_) => None,
+        })
+    }
+}
+"#,
+    );
+    code
+}
+
+/// The error type of the generated parsers
+const ERROR_RS: &str = r#"#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Lex(LexError),
+    /// A token, or the end of the input, where one of `expected` must come
+    Unexpected {
+        found: Option<Token>,
+        offset: usize,
+        expected: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Lex(error) => write!(f, "{}", error),
+            Error::Unexpected {
+                found,
+                offset,
+                expected,
+            } => {
+                match found {
+                    Some(token) => write!(f, "unexpected {:?} at offset {}", token.text, offset)?,
+                    None => write!(f, "unexpected end of input at offset {}", offset)?,
+                }
+                write!(f, ", expected {}", expected.join(" or "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<LexError> for Error {
+    fn from(error: LexError) -> Self {
+        Error::Lex(error)
+    }
+}
+"#;
+
+/// How a lookahead is matched and shown in errors
+fn lookahead(item: &SetItem, names: &Names) -> Option<(String, String)> {
+    match item {
+        SetItem::Terminal(symbol) | SetItem::Regex(symbol) => Some((
+            format!("Some(TokenKind::{})", names.tokens.get(symbol)?),
+            symbol.clone(),
+        )),
+        SetItem::EndOfInput => Some(("None".to_string(), "end of input".to_string())),
+        SetItem::TokenClass(_) | SetItem::Epsilon => None,
+    }
+}
+
+fn parser_rs(bnf: &Bnf, analysis: &Analysis, names: &Names) -> String {
+    let start = bnf.rules.keys().next().map_or("", String::as_str);
+    let mut code = format!(
+        r#"//! An LL(1) parser for the grammar, with a function per rule that picks
+//! the production by the next token.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::ast::{{Node, Rule, Tree}};
+use crate::lexer::{{LexError, Token, TokenKind, tokenize}};
+
+{error}
+/// Parses all of `input` as the start rule.
+pub fn parse(input: &str) -> Result<Node, Error> {{
+    let mut parser = Parser {{
+        tokens: tokenize(input)?,
+        position: 0,
+        len: input.len(),
+    }};
+    let mut children = Vec::new();
+    parser.{start}(&mut children)?;
+    if parser.peek().is_some() {{
+        return Err(parser.unexpected(vec!["end of input"]));
+    }}
+    match children.pop() {{
+        Some(Tree::Node(node)) => Ok(node),
+        _ => unreachable!("the start rule adds a node"),
+    }}
+}}
+
+struct Parser {{
+    tokens: Vec<Token>,
+    position: usize,
+    len: usize,
+}}
+
+impl Parser {{
+    fn peek(&self) -> Option<TokenKind> {{
+        self.tokens.get(self.position).map(|token| token.kind)
+    }}
+
+    fn offset(&self) -> usize {{
+        self.tokens
+            .get(self.position)
+            .map_or(self.len, |token| token.span.start)
+    }}
+
+    /// From the token at `first` to the last one consumed
+    fn span_from(&self, first: usize) -> Range<usize> {{
+        match self.position > first {{
+            true => self.tokens[first].span.start..self.tokens[self.position - 1].span.end,
+            false => self.offset()..self.offset(),
+        }}
+    }}
+
+    fn unexpected(&self, expected: Vec<&'static str>) -> Error {{
+        Error::Unexpected {{
+            found: self.tokens.get(self.position).cloned(),
+            offset: self.offset(),
+            expected,
+        }}
+    }}
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, Error> {{
+        if self.peek() != Some(kind) {{
+            return Err(self.unexpected(vec![kind.symbol()]));
+        }}
+        self.position += 1;
+        Ok(self.tokens[self.position - 1].clone())
+    }}
+"#,
+        error = ERROR_RS,
+        start = names.functions[start]
+    );
+
+    // Rules the start rule does not use would never be called
+    let reachable = bnf.reachable_rules();
+    for (rule, productions) in bnf
+        .rules
+        .iter()
+        .filter(|(rule, _)| reachable.contains(*rule))
+    {
+        let node = names.rules.get(rule);
+        // A helper rule adds its children to the node of the rule using it
+        let target = match node {
+            Some(_) => "&mut children",
+            None => "children",
+        };
+        let alternatives: Vec<String> = productions.iter().map(|p| format_production(p)).collect();
+        let _ = write!(
+            code,
+            "\n    /// `{}`\n    fn {}(&mut self, {}: &mut Vec<Tree>) -> Result<(), Error> {{\n",
+            doc_line(&format!("{} := {}", rule, alternatives.join(" | "))),
+            names.functions[rule],
+            if node.is_some() { "parent" } else { "children" }
+        );
+        if node.is_some() {
+            code.push_str(
+                "        let first = self.position;\n        let mut children = Vec::new();\n",
+            );
+        }
+
+        let cells = &analysis.table().rows[rule];
+        let mut expected = Vec::new();
+        code.push_str("        match self.peek() {\n");
+        for (index, production) in productions.iter().enumerate() {
+            let patterns: Vec<String> = (cells.iter())
+                .filter(|cell| cell.productions == [index])
+                .filter_map(|cell| lookahead(&cell.lookahead, names))
+                .map(|(pattern, shown)| {
+                    expected.push(shown);
+                    pattern
+                })
+                .collect();
+            if patterns.is_empty() {
+                continue;
+            }
+            if production.is_empty() {
+                let _ = writeln!(code, "            {} => {{}}", patterns.join(" | "));
+                continue;
+            }
+            let _ = writeln!(code, "            {} => {{", patterns.join(" | "));
+            for item in production {
+                match item {
+                    Item::NonTerminal(callee) => {
+                        let _ = writeln!(
+                            code,
+                            "                self.{}({})?;",
+                            names.functions[callee], target
+                        );
+                    }
+                    Item::Terminal(symbol) | Item::Regex(symbol) => {
+                        let _ = writeln!(
+                            code,
+                            "                children.push(Tree::Token(self.expect(TokenKind::{})?));",
+                            names.tokens[symbol]
+                        );
+                    }
+                    Item::TokenClass(_) | Item::EndOfInput => {}
+                }
+            }
+            code.push_str("            }\n");
+        }
+        let expected: Vec<String> = expected
+            .iter()
+            .map(|shown| format!("{:?}", shown))
+            .collect();
+        let _ = writeln!(
+            code,
+            "            _ => return Err(self.unexpected(vec![{}])),\n        }}",
+            expected.join(", ")
+        );
+        if let Some((variant, _)) = node {
+            let _ = writeln!(
+                code,
+                "        parent.push(Tree::Node(Node {{\n            rule: Rule::{},\n            children,\n            span: self.span_from(first),\n        }}));",
+                variant
+            );
+        }
+        code.push_str("        Ok(())\n    }\n");
+    }
+    code.push_str("}\n");
+    code
+}
+
+fn table_rs(bnf: &Bnf, analysis: &Analysis, names: &Names) -> String {
+    let index: IndexMap<&str, usize> = (bnf.rules.keys().enumerate())
+        .map(|(i, rule)| (rule.as_str(), i))
+        .collect();
+    let symbol = |item: &Item| match item {
+        Item::NonTerminal(rule) => Some(format!("Symbol::Rule({})", index[rule.as_str()])),
+        Item::Terminal(symbol) | Item::Regex(symbol) => Some(format!(
+            "Symbol::Token(TokenKind::{})",
+            names.tokens[symbol]
+        )),
+        Item::TokenClass(_) | Item::EndOfInput => None,
+    };
+
+    let mut code = String::from(
+        r#"//! A table-driven LL(1) parser for the grammar: the next token and the
+//! rule on top of the stack pick the production from `TABLE`.
+
+use std::fmt;
+
+use crate::ast::{Node, Rule, Tree};
+use crate::lexer::{LexError, Token, TokenKind, tokenize};
+
+"#,
+    );
+    code.push_str(ERROR_RS);
+    code.push_str(
+        r#"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symbol {
+    Token(TokenKind),
+    /// An index into `RULES`
+    Rule(usize),
+}
+
+/// The rules of the BNF, with the node each one builds; helper rules add
+/// their children to the node of the rule using them
+const RULES: &[(&str, Option<Rule>)] = &[
+"#,
+    );
+    for rule in bnf.rules.keys() {
+        let node = match names.rules.get(rule) {
+            Some((variant, _)) => format!("Some(Rule::{})", variant),
+            None => "None".to_string(),
+        };
+        let _ = writeln!(code, "    ({:?}, {}),", rule, node);
+    }
+    code.push_str("];\n\n/// The productions of the BNF\nconst PRODUCTIONS: &[&[Symbol]] = &[\n");
+    let mut first_production = Vec::new();
+    let mut count = 0;
+    for (rule, productions) in &bnf.rules {
+        first_production.push(count);
+        for production in productions {
+            let symbols: Vec<String> = production.iter().filter_map(symbol).collect();
+            let doc = doc_line(&format!("{} := {}", rule, format_production(production)));
+            let _ = writeln!(
+                code,
+                "    // {}: {}\n    &[{}],",
+                count,
+                doc,
+                symbols.join(", ")
+            );
+            count += 1;
+        }
+    }
+    code.push_str(
+        "];\n\n/// For every rule, the production to expand it by on each next token, or\n/// `None` at the end of the input\nconst TABLE: &[&[(Option<TokenKind>, usize)]] = &[\n",
+    );
+    for (i, rule) in bnf.rules.keys().enumerate() {
+        // By production, so that errors list the tokens as the recursive
+        // descent parser does
+        let (cells, first) = (&analysis.table().rows[rule], first_production[i]);
+        let cells: Vec<String> = (0..bnf.rules[i].len())
+            .flat_map(|index| {
+                (cells.iter())
+                    .filter(move |cell| cell.productions == [index])
+                    .filter_map(move |cell| {
+                        let (pattern, _) = lookahead(&cell.lookahead, names)?;
+                        Some(format!("({}, {})", pattern, first + index))
+                    })
+            })
+            .collect();
+        let _ = writeln!(code, "    // {}\n    &[{}],", rule, cells.join(", "));
+    }
+    let _ = write!(
+        code,
+        r#"];
+
+enum Task {{
+    Expand(Symbol),
+    /// Builds the node of a rule from the children parsed since the token at
+    /// `first`
+    Close(Rule, usize),
+}}
+
+/// Parses all of `input` as the start rule.
+pub fn parse(input: &str) -> Result<Node, Error> {{
+    let tokens = tokenize(input)?;
+    let offset = |position: usize| tokens.get(position).map_or(input.len(), |token| token.span.start);
+    let unexpected = |position: usize, expected: Vec<&'static str>| Error::Unexpected {{
+        found: tokens.get(position).cloned(),
+        offset: offset(position),
+        expected,
+    }};
+    let mut position = 0;
+    let mut stack = vec![Task::Expand(Symbol::Rule(0))];
+    // The children of the nodes being built, innermost last
+    let mut children: Vec<Vec<Tree>> = vec![Vec::new()];
+    while let Some(task) = stack.pop() {{
+        match task {{
+            Task::Expand(Symbol::Token(kind)) => {{
+                match tokens.get(position) {{
+                    Some(token) if token.kind == kind => {{
+                        let token = Tree::Token(token.clone());
+                        children.last_mut().expect("a node is open").push(token);
+                        position += 1;
+                    }}
+                    _ => return Err(unexpected(position, vec![kind.symbol()])),
+                }}
+            }}
+            Task::Expand(Symbol::Rule(rule)) => {{
+                let next = tokens.get(position).map(|token| token.kind);
+                let Some(&(_, production)) = TABLE[rule].iter().find(|(kind, _)| *kind == next) else {{
+                    let expected = TABLE[rule]
+                        .iter()
+                        .map(|(kind, _)| kind.map_or("end of input", TokenKind::symbol))
+                        .collect();
+                    return Err(unexpected(position, expected));
+                }};
+                if let (_, Some(node)) = RULES[rule] {{
+                    stack.push(Task::Close(node, position));
+                    children.push(Vec::new());
+                }}
+                for &symbol in PRODUCTIONS[production].iter().rev() {{
+                    stack.push(Task::Expand(symbol));
+                }}
+            }}
+            Task::Close(rule, first) => {{
+                let span = match position > first {{
+                    true => tokens[first].span.start..tokens[position - 1].span.end,
+                    false => offset(position)..offset(position),
+                }};
+                let node = Node {{
+                    rule,
+                    children: children.pop().expect("the node is open"),
+                    span,
+                }};
+                children.last_mut().expect("a node is open").push(Tree::Node(node));
+            }}
+        }}
+    }}
+    if position < tokens.len() {{
+        return Err(unexpected(position, vec!["end of input"]));
+    }}
+    match children.pop().and_then(|mut nodes| nodes.pop()) {{
+        Some(Tree::Node(node)) => Ok(node),
+        _ => unreachable!("the start rule adds a node"),
+    }}
+}}
+"#
+    );
+    code
+}
+
+fn visitor_rs(names: &Names) -> String {
+    let mut code = String::from(
+        r#"//! Walks the syntax tree, with a method per rule.
+
+use crate::ast::{Node, Rule, Tree};
+use crate::lexer::Token;
+
+/// Override the methods of the rules of interest; the others visit the
+/// children of their node.
+pub trait Visitor {
+    fn visit_token(&mut self, _token: &Token) {}
+"#,
+    );
+    for (_, snake) in names.rules.values() {
+        let _ = write!(
+            code,
+            "\n    fn visit_{}(&mut self, node: &Node) {{\n        walk_node(self, node);\n    }}\n",
+            snake
+        );
+    }
+    code.push_str(
+        r#"}
+
+/// Visits the children of `node`
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    for child in &node.children {
+        walk_tree(visitor, child);
+    }
+}
+
+pub fn walk_tree<V: Visitor + ?Sized>(visitor: &mut V, tree: &Tree) {
+    match tree {
+        Tree::Token(token) => visitor.visit_token(token),
+        Tree::Node(node) => match node.rule {
+"#,
+    );
+    for (variant, snake) in names.rules.values() {
+        let _ = writeln!(
+            code,
+            "            Rule::{} => visitor.visit_{}(node),",
+            variant, snake
+        );
+    }
+    code.push_str("        },\n    }\n}\n");
+    code
+}
+
+fn tests_rs(name: &str, sentences: &[String]) -> String {
+    let mut code = String::from(
+        "//! Sentences generated from the grammar, which the parser must accept.\n\nconst SENTENCES: &[&str] = &[\n",
+    );
+    for sentence in sentences {
+        let _ = writeln!(code, "    {:?},", sentence);
+    }
+    let _ = write!(
+        code,
+        r#"];
+
+#[test]
+fn parses_generated_sentences() {{
+    for sentence in SENTENCES {{
+        if let Err(error) = {}::parse(sentence) {{
+            panic!("{{:?}}: {{}}", sentence, error);
+        }}
+    }}
+}}
+"#,
+        name.replace('-', "_")
+    );
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_tokens_and_rules() {
+        let sebnf = Sebnf::parse_str(
+            "match_expr := \"match\" ident \"=>\" { \"(\" ident \")\" }.\nident := /[a-z]+/.",
+            "g",
+        )
+        .unwrap();
+        let names = Names::new(&sebnf, &sebnf.to_bnf());
+        let tokens: Vec<_> = names.tokens.values().map(String::as_str).collect();
+        assert_eq!(tokens, ["Match", "EqGt", "Ident", "LParen", "RParen"]);
+        assert_eq!(
+            names.rules["match_expr"],
+            ("MatchExpr".to_string(), "match_expr".to_string())
+        );
+        assert_eq!(names.functions["___rep_0"], "parse_rep_0");
+
+        let module = rust_rd_parser(&sebnf).unwrap();
+        assert!(module.contains("fn parse_match_expr(&mut self"));
+        assert!(module.contains("pub fn tokenize("));
+        assert!(!module.contains("use crate::"));
+        assert_eq!(module.matches("use std::fmt;").count(), 1);
+        let lexer = lexer_module(&sebnf, true);
+        assert!(lexer.contains("    #[token(\"=>\", priority = 2)]\n    EqGt,\n"));
+        assert!(lexer.contains("    #[regex(\"[a-z]+\", priority = 1)]\n    Ident,\n"));
+        let table = rust_table_parser(&sebnf).unwrap();
+        assert!(table.contains("    (\"match_expr\", Some(Rule::MatchExpr)),\n"));
+        assert!(
+            table.contains("    // ___rep_0\n    &[(Some(TokenKind::LParen), 2), (None, 3)],\n")
+        );
+
+        let not_ll1 = Sebnf::parse_str("s := \"a\" | \"a\" \"b\".", "g").unwrap();
+        let error = parser_crate("", &not_ll1, &CrateOptions::new("p")).unwrap_err();
+        assert!(matches!(error, CodegenError::NotLl1 { cells } if cells == ["'s' on a"]));
+    }
+
+    #[test]
+    fn empty_grammar_has_no_parser() {
+        let empty = Sebnf::parse_str("", "g").unwrap();
+        assert!(matches!(rust_rd_parser(&empty), Err(CodegenError::NoRules)));
+        assert!(matches!(
+            rust_table_parser(&empty),
+            Err(CodegenError::NoRules)
+        ));
+        let error = parser_crate("", &empty, &CrateOptions::new("p")).unwrap_err();
+        assert_eq!(error.to_string(), "the grammar has no rules");
+    }
+}
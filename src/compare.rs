@@ -0,0 +1,312 @@
+//! Semantic differences between the analyses of two grammar versions.
+//!
+//! Unlike a textual diff of the reports, conflicts are matched by their
+//! non-terminal, kind and productions (not by their number), and sets and
+//! table cells are compared member by member.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::bnf::Bnf;
+use crate::sets::{Ll1Conflict, Ll1ConflictKind, Ll1Result, SetItem, Sets, format_production};
+use crate::table::ParseTable;
+
+/// Conflicts only present in one of the two versions.
+#[derive(Debug, Clone, Default)]
+pub struct ConflictsDiff {
+    pub introduced: Vec<Ll1Conflict>,
+    pub resolved: Vec<Ll1Conflict>,
+}
+
+impl ConflictsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.introduced.is_empty() && self.resolved.is_empty()
+    }
+}
+
+/// Identifies a conflict across versions, where production indices may differ
+fn conflict_key(conflict: &Ll1Conflict) -> String {
+    let kind = match conflict.kind {
+        Ll1ConflictKind::FirstFirst { .. } => "FIRST/FIRST",
+        Ll1ConflictKind::FirstFollow { .. } => "FIRST/FOLLOW",
+    };
+    let (first, second) = conflict.kind.productions();
+    format!(
+        "'{}': {} conflict between `{}` and `{}` on {}",
+        conflict.non_terminal,
+        kind,
+        format_production(first),
+        format_production(second),
+        conflict_items(conflict)
+    )
+}
+
+fn conflict_items(conflict: &Ll1Conflict) -> String {
+    let items: BTreeSet<String> = conflict
+        .conflicts
+        .iter()
+        .map(|c| c.item1.to_string())
+        .collect();
+    items.into_iter().collect::<Vec<_>>().join(", ")
+}
+
+pub fn compare_conflicts(old: &Ll1Result, new: &Ll1Result) -> ConflictsDiff {
+    let old_keys: HashSet<String> = old.conflicts.iter().map(conflict_key).collect();
+    let new_keys: HashSet<String> = new.conflicts.iter().map(conflict_key).collect();
+    ConflictsDiff {
+        introduced: new
+            .conflicts
+            .iter()
+            .filter(|c| !old_keys.contains(&conflict_key(c)))
+            .cloned()
+            .collect(),
+        resolved: old
+            .conflicts
+            .iter()
+            .filter(|c| !new_keys.contains(&conflict_key(c)))
+            .cloned()
+            .collect(),
+    }
+}
+
+impl fmt::Display for ConflictsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No conflicts introduced or resolved");
+        }
+        for (heading, sign, conflicts) in [
+            ("Introduced", '+', &self.introduced),
+            ("Resolved", '-', &self.resolved),
+        ] {
+            if !conflicts.is_empty() {
+                writeln!(f, "{} {} conflict(s):", heading, conflicts.len())?;
+                for conflict in conflicts {
+                    writeln!(f, "  {} {}", sign, conflict_key(conflict))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Members added to and removed from one FIRST or FOLLOW set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetChange {
+    /// `FIRST` or `FOLLOW`
+    pub set: &'static str,
+    pub symbol: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetsDiff {
+    pub added_symbols: Vec<String>,
+    pub removed_symbols: Vec<String>,
+    /// Changes of symbols present in both versions, sorted by symbol
+    pub changes: Vec<SetChange>,
+}
+
+impl SetsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_symbols.is_empty() && self.removed_symbols.is_empty() && self.changes.is_empty()
+    }
+}
+
+fn members<'a>(sets: &'a HashMap<String, HashSet<SetItem>>, symbol: &str) -> BTreeSet<&'a SetItem> {
+    sets.get(symbol).into_iter().flatten().collect()
+}
+
+fn to_strings<'a>(items: impl Iterator<Item = &'a SetItem>) -> Vec<String> {
+    items.map(SetItem::to_string).collect()
+}
+
+pub fn compare_sets(old: &Sets, new: &Sets) -> SetsDiff {
+    let old_symbols: BTreeSet<&String> = old.first.keys().collect();
+    let new_symbols: BTreeSet<&String> = new.first.keys().collect();
+    let mut diff = SetsDiff {
+        added_symbols: new_symbols
+            .difference(&old_symbols)
+            .map(|s| s.to_string())
+            .collect(),
+        removed_symbols: old_symbols
+            .difference(&new_symbols)
+            .map(|s| s.to_string())
+            .collect(),
+        changes: Vec::new(),
+    };
+
+    for symbol in old_symbols.intersection(&new_symbols) {
+        for (set, old_map, new_map) in [
+            ("FIRST", &old.first, &new.first),
+            ("FOLLOW", &old.follow, &new.follow),
+        ] {
+            let (old_members, new_members) = (members(old_map, symbol), members(new_map, symbol));
+            let added = to_strings(new_members.difference(&old_members).copied());
+            let removed = to_strings(old_members.difference(&new_members).copied());
+            if !added.is_empty() || !removed.is_empty() {
+                diff.changes.push(SetChange {
+                    set,
+                    symbol: symbol.to_string(),
+                    added,
+                    removed,
+                });
+            }
+        }
+    }
+    diff
+}
+
+impl fmt::Display for SetsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No set changes");
+        }
+        for symbol in &self.added_symbols {
+            writeln!(f, "+ new non-terminal {}", symbol)?;
+        }
+        for symbol in &self.removed_symbols {
+            writeln!(f, "- removed non-terminal {}", symbol)?;
+        }
+        for change in &self.changes {
+            write!(f, "{}({}):", change.set, change.symbol)?;
+            if !change.added.is_empty() {
+                write!(f, " added {}", change.added.join(", "))?;
+                if !change.removed.is_empty() {
+                    write!(f, ";")?;
+                }
+            }
+            if !change.removed.is_empty() {
+                write!(f, " removed {}", change.removed.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A parse table cell whose productions differ; empty means no entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableChange {
+    pub symbol: String,
+    pub lookahead: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableDiff {
+    pub changes: Vec<TableChange>,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Cells keyed by (non-terminal, lookahead), holding the productions' text
+fn table_cells(bnf: &Bnf, table: &ParseTable) -> BTreeMap<(String, String), Vec<String>> {
+    let mut cells = BTreeMap::new();
+    for (nt, row) in &table.rows {
+        for cell in row {
+            let productions = cell
+                .productions
+                .iter()
+                .filter_map(|&i| bnf.rules.get(nt).and_then(|p| p.get(i)))
+                .map(|items| format_production(items))
+                .collect();
+            cells.insert((nt.clone(), cell.lookahead.to_string()), productions);
+        }
+    }
+    cells
+}
+
+pub fn compare_tables(
+    old_bnf: &Bnf,
+    old_table: &ParseTable,
+    new_bnf: &Bnf,
+    new_table: &ParseTable,
+) -> TableDiff {
+    let old_cells = table_cells(old_bnf, old_table);
+    let new_cells = table_cells(new_bnf, new_table);
+    let keys: BTreeSet<_> = old_cells.keys().chain(new_cells.keys()).collect();
+    let changes = keys
+        .into_iter()
+        .filter_map(|key| {
+            let old = old_cells.get(key).cloned().unwrap_or_default();
+            let new = new_cells.get(key).cloned().unwrap_or_default();
+            (old != new).then(|| TableChange {
+                symbol: key.0.clone(),
+                lookahead: key.1.clone(),
+                old,
+                new,
+            })
+        })
+        .collect();
+    TableDiff { changes }
+}
+
+impl fmt::Display for TableDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No table changes");
+        }
+        let cell = |productions: &[String]| match productions {
+            [] => "(none)".to_string(),
+            _ => productions.join(" | "),
+        };
+        for change in &self.changes {
+            writeln!(
+                f,
+                "[{}, {}]: {} => {}",
+                change.symbol,
+                change.lookahead,
+                cell(&change.old),
+                cell(&change.new)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+    use crate::table::build_parse_table;
+
+    fn bnf(source: &str) -> Bnf {
+        Sebnf::parse_str(source, "g").unwrap().to_bnf()
+    }
+
+    #[test]
+    fn reports_only_semantic_changes() {
+        let old = bnf("s := a | \"y\".\na := \"x\" | \"y\".");
+        // Reordered and fixed: the conflict on "y" goes away, a new one on "z" appears
+        let new = bnf("s := \"z\" | a.\na := \"x\" | \"z\".");
+
+        let conflicts = compare_conflicts(&old.is_ll1(false).unwrap(), &new.is_ll1(false).unwrap());
+        assert_eq!(conflicts.introduced.len(), 1);
+        assert_eq!(conflicts.resolved.len(), 1);
+        assert_eq!(
+            conflict_key(&conflicts.introduced[0]),
+            "'s': FIRST/FIRST conflict between `\"z\"` and `a` on z"
+        );
+
+        let sets = compare_sets(&old.first_and_follow_sets(), &new.first_and_follow_sets());
+        assert_eq!(
+            sets.to_string(),
+            "FIRST(a): added z; removed y\nFIRST(s): added z; removed y\n"
+        );
+
+        let (old_sets, new_sets) = (old.first_and_follow_sets(), new.first_and_follow_sets());
+        let table = compare_tables(
+            &old,
+            &build_parse_table(&old, &old_sets),
+            &new,
+            &build_parse_table(&new, &new_sets),
+        );
+        assert!(table.to_string().contains("[s, y]: a | \"y\" => (none)\n"));
+    }
+}
@@ -0,0 +1,157 @@
+//! Tool configuration read from a TOML-like file.
+//!
+//! Only `key = value` lines, `[section]` headers and `#` comments are
+//! supported. Keys may be dotted instead of using a section:
+//!
+//! ```text
+//! # older terminals cannot show ε
+//! symbols.epsilon = "eps"
+//!
+//! [symbols]
+//! end_of_input = "EOF"
+//! ascii = true
+//! ```
+
+use miette::{NamedSource, SourceSpan};
+
+use crate::symbols::Symbols;
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum ConfigError {
+    #[error("invalid configuration line")]
+    #[diagnostic(
+        code(sebnf::config_syntax),
+        help("settings look like `symbols.epsilon = \"eps\"` or `ascii = true`")
+    )]
+    Syntax {
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("{message}")]
+        span: SourceSpan,
+        message: String,
+    },
+
+    #[error("unknown setting '{key}'")]
+    #[diagnostic(
+        code(sebnf::config_unknown_key),
+        help("known settings: symbols.epsilon, symbols.end_of_input, symbols.ascii")
+    )]
+    UnknownKey {
+        key: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("unknown setting")]
+        span: SourceSpan,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    pub symbols: Symbols,
+}
+
+impl Config {
+    pub fn parse(source: &str, source_name: &str) -> Result<Self, ConfigError> {
+        let mut config = Config::default();
+        let mut section = String::new();
+        let mut line_start = 0;
+
+        for line in source.split_inclusive('\n') {
+            let offset = line_start;
+            line_start += line.len();
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let start = offset + (line.len() - line.trim_start().len());
+            let span: SourceSpan = (start, trimmed.len()).into();
+            let syntax_error = |message: &str| ConfigError::Syntax {
+                src: NamedSource::new(source_name, source.to_string()),
+                span,
+                message: message.to_string(),
+            };
+
+            if let Some(name) = trimmed.strip_prefix('[') {
+                let Some(name) = name.strip_suffix(']') else {
+                    return Err(syntax_error("missing ']'"));
+                };
+                section = name.trim().to_string();
+                continue;
+            }
+
+            let Some((key, value)) = trimmed.split_once('=') else {
+                return Err(syntax_error("missing '='"));
+            };
+            let key = match key.trim() {
+                key if section.is_empty() => key.to_string(),
+                key => format!("{}.{}", section, key),
+            };
+            let value = value.trim();
+
+            match key.as_str() {
+                "symbols.epsilon" | "symbols.end_of_input" => {
+                    let Some(value) = value
+                        .strip_prefix('"')
+                        .and_then(|v| v.strip_suffix('"'))
+                        .filter(|v| !v.is_empty())
+                    else {
+                        return Err(syntax_error("expected a non-empty \"string\""));
+                    };
+                    if key == "symbols.epsilon" {
+                        config.symbols.epsilon = value.to_string();
+                    } else {
+                        config.symbols.end_of_input = value.to_string();
+                    }
+                }
+                "symbols.ascii" => {
+                    config.symbols.ascii = match value {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(syntax_error("expected true or false")),
+                    };
+                }
+                _ => {
+                    return Err(ConfigError::UnknownKey {
+                        key,
+                        src: NamedSource::new(source_name, source.to_string()),
+                        span,
+                    });
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotted_keys_and_sections() {
+        let config = Config::parse(
+            "# comment\nsymbols.epsilon = \"eps\"\n\n[symbols]\nend_of_input = \"EOF\"\nascii = true\n",
+            "config",
+        )
+        .unwrap();
+        assert_eq!(
+            config.symbols,
+            Symbols {
+                epsilon: "eps".to_string(),
+                end_of_input: "EOF".to_string(),
+                ascii: true,
+            }
+        );
+
+        assert!(matches!(
+            Config::parse("symbols.epsilon = eps", "config"),
+            Err(ConfigError::Syntax { .. })
+        ));
+        assert!(matches!(
+            Config::parse("[symbols]\nepsilon_sign = \"e\"", "config"),
+            Err(ConfigError::UnknownKey { .. })
+        ));
+    }
+}
@@ -1,128 +1,994 @@
-use std::collections::HashMap;
+//! Lowering of SEBNF to plain BNF.
+//!
+//! The conversion is a [`Pipeline`] of passes, each a [`Transform`] on the
+//! SEBNF that replaces one construct by references to generated helper
+//! rules. Once only non-terminals, terminals and regexes are left, [`lower`]
+//! turns the grammar into a [`Bnf`](bnf::Bnf). The standard pipeline is:
+//!
+//! | Pass                   | Replaces                                   |
+//! |------------------------|--------------------------------------------|
+//! | [`FoldLiterals`]       | `/while/` by `"while"` (if enabled)        |
+//! | [`SoftKeywords`]       | soft keyword `"async"` by `/async/`        |
+//! | [`DesugarBuiltins`]    | `sep_by`, `delimited`, `non_empty` calls   |
+//! | [`DesugarOptionals`]   | `[ A ]` by `___opt_N := A \| ε`            |
+//! | [`DesugarRepetitions`] | `{ A }` by `___rep_N := A ___rep_N \| ε`   |
+//! | [`DesugarChoices`]     | `( A \| B )` by `___choice_N := A \| B`    |
+//! | [`SharedEpsilon`]      | generated `ε` by `___empty` (if enabled)   |
+//! | [`DedupeHelpers`]      | helper rules with the same body            |
+//! | [`InlineHelpers`]      | small helpers by their body (if enabled)   |
+//! | [`NameHelpers`]        | provisional helper names by `___kind_N`    |
+//! | [`StableNames`]        | those by `___rule_kind_hash` (if enabled)  |
+//!
+//! Passes can be inserted, removed or reordered, and running the passes
+//! without [`lower`] shows the grammar midway:
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::converter::{DesugarOptionals, Pipeline};
+//!
+//! let sebnf = Sebnf::parse_str("a := [ \"x\" ] { \"y\" }.", "g").unwrap();
+//! let (midway, _log) = Pipeline::new().with(DesugarOptionals).run_passes(&sebnf).unwrap();
+//! assert!(midway.to_string().starts_with("a        := ___opt_0 { \"y\" }.\n"));
+//! assert!(Pipeline::new().with(DesugarOptionals).run(&sebnf).is_err());
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 
 use indexmap::IndexMap;
 
-use crate::{bnf, sebnf};
+use crate::bnf;
+use crate::regex_usage::{FoldLiterals, LiteralForm};
+use crate::sebnf::{Builtin, Item, Sebnf};
+use crate::sets::strip_terminal_quotes;
+use crate::transform::{ChangeLog, Transform, TransformError};
+use crate::visit::{Visitor, VisitorMut, walk_item_mut, walk_sebnf_mut};
 
-pub fn sebnf_to_bnf(sebnf: &sebnf::Sebnf) -> bnf::Bnf {
-    let mut ctx = ConverterContext::new();
+/// Name of the rule shared by all empty productions with
+/// [`LoweringOptions::shared_epsilon`]
+pub const EMPTY_RULE: &str = "___empty";
 
-    // Convert all rules
-    let mut original_rules: Vec<(String, Vec<Vec<bnf::Item>>)> = Vec::new();
-    for (name, alternatives) in &sebnf.rules {
-        let mut bnf_alternatives = Vec::new();
-        for alt in alternatives {
-            bnf_alternatives.push(ctx.convert_sequence(alt));
+/// Kinds of helper rules; a helper is named `___<kind>_<N>`, or
+/// `___<rule>_<kind>_<hash>` (`___<kind>_<hash>` if shared) by [`StableNames`]
+const HELPER_KINDS: &[&str] = &["opt", "rep", "choice", "sep_by", "delimited", "non_empty"];
+
+/// Helper kinds whose last production is the generated empty one
+const EMPTY_ENDED_KINDS: &[&str] = &["opt", "rep", "sep_by"];
+
+/// Choices in how SEBNF is lowered to BNF.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoweringOptions {
+    /// Give the generated rules a production `___empty` instead of an empty
+    /// one, with a single rule `___empty := ε` after the grammar's own rules
+    pub shared_epsilon: bool,
+    /// Replace helper rules that are a single symbol or used only once by
+    /// their productions; see [`InlineHelpers`]
+    pub inline_helpers: bool,
+    /// Spell a regex matching a single string and the terminal of that
+    /// string the same way, before desugaring; see [`FoldLiterals`]
+    pub fold_literals: Option<LiteralForm>,
+    /// Name helper rules after the rule using them and their body instead
+    /// of numbering them; see [`StableNames`]
+    pub stable_names: bool,
+}
+
+pub fn sebnf_to_bnf(sebnf: &Sebnf) -> bnf::Bnf {
+    sebnf_to_bnf_with(sebnf, &LoweringOptions::default())
+}
+
+pub fn sebnf_to_bnf_with(sebnf: &Sebnf, options: &LoweringOptions) -> bnf::Bnf {
+    Pipeline::standard(options)
+        .run(sebnf)
+        .expect("the standard passes desugar every construct")
+        .0
+}
+
+/// Passes applied in order to an SEBNF, then lowered to BNF.
+pub struct Pipeline {
+    passes: Vec<(String, Box<dyn Transform<Sebnf>>)>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::standard(&LoweringOptions::default())
+    }
+}
+
+impl Pipeline {
+    /// A pipeline without passes
+    pub fn new() -> Self {
+        Self { passes: Vec::new() }
+    }
+
+    /// The passes of [`sebnf_to_bnf_with`]
+    pub fn standard(options: &LoweringOptions) -> Self {
+        let mut pipeline = Self::new();
+        if let Some(form) = options.fold_literals {
+            pipeline.push(FoldLiterals::new(form));
+        }
+        let mut pipeline = pipeline
+            .with(SoftKeywords)
+            .with(DesugarBuiltins)
+            .with(DesugarOptionals)
+            .with(DesugarRepetitions)
+            .with(DesugarChoices);
+        if options.shared_epsilon {
+            pipeline.push(SharedEpsilon);
+        }
+        pipeline.push(DedupeHelpers);
+        if options.inline_helpers {
+            pipeline.push(InlineHelpers);
         }
-        original_rules.push((name.clone(), bnf_alternatives));
+        pipeline.push(NameHelpers);
+        if options.stable_names {
+            pipeline.push(StableNames);
+        }
+        pipeline
     }
 
-    // Build new index map to preserve correct order
-    let mut final_rules = IndexMap::new();
-    for (name, alts) in original_rules {
-        final_rules.insert(name, alts);
+    pub fn with(mut self, pass: impl Transform<Sebnf> + 'static) -> Self {
+        self.push(pass);
+        self
     }
-    for (name, alts) in ctx.bnf_rules {
-        final_rules.insert(name, alts);
+
+    pub fn push(&mut self, pass: impl Transform<Sebnf> + 'static) {
+        self.insert(self.passes.len(), pass);
     }
 
-    bnf::Bnf { rules: final_rules }
+    /// Inserts `pass` before the `index`-th pass.
+    pub fn insert(&mut self, index: usize, pass: impl Transform<Sebnf> + 'static) {
+        let name = std::any::type_name_of_val(&pass)
+            .rsplit("::")
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        self.passes.insert(index, (name, Box::new(pass)));
+    }
+
+    /// Removes the `index`-th pass.
+    pub fn remove(&mut self, index: usize) {
+        self.passes.remove(index);
+    }
+
+    /// Drops every pass from the `len`-th on, to stop midway.
+    pub fn truncate(&mut self, len: usize) {
+        self.passes.truncate(len);
+    }
+
+    /// Type names of the passes, e.g. `DesugarOptionals`
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.passes.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Applies the passes to a copy of `sebnf`, without its labels.
+    pub fn run_passes(&self, sebnf: &Sebnf) -> Result<(Sebnf, ChangeLog), TransformError> {
+        let mut sebnf = sebnf.clone();
+        strip_labels(&mut sebnf);
+        let mut log = ChangeLog::default();
+        for (_, pass) in &self.passes {
+            pass.apply(&mut sebnf, &mut log)?;
+        }
+        Ok((sebnf, log))
+    }
+
+    /// Applies the passes and lowers the result to BNF.
+    pub fn run(&self, sebnf: &Sebnf) -> Result<(bnf::Bnf, ChangeLog), TransformError> {
+        let (sebnf, log) = self.run_passes(sebnf)?;
+        Ok((lower(&sebnf)?, log))
+    }
 }
 
-struct ConverterContext {
-    bnf_rules: IndexMap<String, Vec<Vec<bnf::Item>>>,
-    rule_cache: HashMap<String, String>,
-    // Separate cache: repetition bodies contain self-references, can't hash before naming
-    rep_cache: HashMap<String, String>,
-    uid_counter: usize,
+/// Replaces every labeled item by the item itself; labels only matter to
+/// code generators.
+fn strip_labels(sebnf: &mut Sebnf) {
+    struct Strip;
+    impl VisitorMut for Strip {
+        fn visit_item_mut(&mut self, item: &mut Item) {
+            if let Item::Labeled(_, inner, _) = item {
+                *item = std::mem::replace(inner.as_mut(), Item::Choice(Vec::new(), 0..0));
+            }
+            walk_item_mut(self, item);
+        }
+    }
+    walk_sebnf_mut(&mut Strip, sebnf);
 }
 
-impl ConverterContext {
-    fn new() -> Self {
-        Self {
-            bnf_rules: IndexMap::new(),
-            rule_cache: HashMap::new(),
-            rep_cache: HashMap::new(),
-            uid_counter: 0,
+/// Maps an SEBNF that only consists of non-terminals, terminals, regexes and
+/// `ε` to BNF, keeping every item's span; `ε` is left out.
+pub fn lower(sebnf: &Sebnf) -> Result<bnf::Bnf, TransformError> {
+    let mut rules = IndexMap::new();
+    let mut spans = IndexMap::new();
+    for (name, alternatives) in &sebnf.rules {
+        let mut productions = Vec::new();
+        let mut production_spans = Vec::new();
+        for alternative in alternatives {
+            let mut production = Vec::new();
+            let mut item_spans = Vec::new();
+            for item in alternative {
+                production.push(match item {
+                    Item::NonTerminal(s, _) => bnf::Item::NonTerminal(s.clone()),
+                    Item::Terminal(s, _) => bnf::Item::Terminal(s.clone()),
+                    Item::Regex(s, _) => bnf::Item::Regex(s.clone()),
+                    Item::Epsilon(_) => continue,
+                    _ => return Err(TransformError::NotLowered(name.clone())),
+                });
+                item_spans.push(item.span());
+            }
+            productions.push(production);
+            production_spans.push(item_spans);
         }
+        rules.insert(name.clone(), productions);
+        spans.insert(name.clone(), production_spans);
     }
+    Ok(bnf::Bnf { rules, spans })
+}
+
+/// Kind of a generated helper rule, e.g. `rep` for `___rep_3` and
+/// `___expr_rep_1f0c`
+pub fn helper_kind(name: &str) -> Option<&str> {
+    let (prefix, number) = name.strip_prefix("___")?.rsplit_once('_')?;
+    let is_number = !number.is_empty()
+        && number
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+    let kind = HELPER_KINDS.iter().find(|&&kind| {
+        prefix == kind
+            || prefix
+                .strip_suffix(kind)
+                .is_some_and(|rule| rule.len() > 1 && rule.ends_with('_'))
+    })?;
+    is_number.then_some(kind)
+}
 
-    fn next_name(&mut self, prefix: &str) -> String {
-        let name = format!("___{}_{}", prefix, self.uid_counter);
-        self.uid_counter += 1;
-        name
+fn helper_number(name: &str) -> Option<usize> {
+    helper_kind(name)?;
+    name.rsplit_once('_')?.1.parse().ok()
+}
+
+/// Helper kind and body for an item a pass desugars, `None` for the rest
+type Desugaring = fn(&Item) -> Option<(&'static str, Vec<Vec<Item>>)>;
+
+/// Replaces the items a pass desugars by references to new helper rules.
+fn desugar(sebnf: &mut Sebnf, log: &mut ChangeLog, pass: &'static str, desugar: Desugaring) {
+    struct Desugar {
+        desugar: Desugaring,
+        next: usize,
+        helpers: Vec<(String, Vec<Vec<Item>>, Range<usize>)>,
+    }
+    impl VisitorMut for Desugar {
+        fn visit_item_mut(&mut self, item: &mut Item) {
+            // Inner constructs first, so helper bodies need no second visit
+            walk_item_mut(self, item);
+            if let Some((kind, body)) = (self.desugar)(item) {
+                let name = format!("___{}_{}", kind, self.next);
+                self.next += 1;
+                let span = item.span();
+                self.helpers.push((name.clone(), body, span.clone()));
+                *item = Item::NonTerminal(name, span);
+            }
+        }
+    }
+
+    let next = sebnf
+        .rules
+        .keys()
+        .filter_map(|name| helper_number(name))
+        .max()
+        .map_or(0, |n| n + 1);
+    let mut visitor = Desugar {
+        desugar,
+        next,
+        helpers: Vec::new(),
+    };
+    walk_sebnf_mut(&mut visitor, sebnf);
+    if !visitor.helpers.is_empty() {
+        log.record(
+            pass,
+            format!("introduced {} helper rule(s)", visitor.helpers.len()),
+        );
+    }
+    for (name, body, span) in visitor.helpers {
+        sebnf.rules.insert(name.clone(), body);
+        sebnf.rule_spans.insert(name, span);
+    }
+}
+
+/// Replaces the soft keywords of each rule (see
+/// [`RuleAnnotations::soft_keywords`](crate::sebnf::RuleAnnotations::soft_keywords))
+/// by a regex matching only their spelling. The lexer reads them as
+/// identifiers, so the LL(1) check then reports where a soft keyword cannot
+/// be told apart from an identifier.
+#[derive(Debug, Clone, Default)]
+pub struct SoftKeywords;
+
+impl Transform<Sebnf> for SoftKeywords {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        struct Soften<'a> {
+            keywords: &'a [String],
+            replaced: usize,
+        }
+        impl VisitorMut for Soften<'_> {
+            fn visit_item_mut(&mut self, item: &mut Item) {
+                if let Item::Terminal(terminal, span) = item
+                    && self.keywords.contains(terminal)
+                {
+                    *item = Item::Regex(soft_keyword_regex(terminal), span.clone());
+                    self.replaced += 1;
+                }
+                walk_item_mut(self, item);
+            }
+        }
+
+        for (name, alternatives) in sebnf.rules.iter_mut() {
+            let Some(annotations) = sebnf.annotations.get(name) else {
+                continue;
+            };
+            let mut soften = Soften {
+                keywords: &annotations.soft_keywords,
+                replaced: 0,
+            };
+            soften.visit_alternatives_mut(alternatives);
+            if soften.replaced > 0 {
+                log.record(
+                    "soft-keywords",
+                    format!(
+                        "spelled {} soft keyword(s) in '{}' as regexes",
+                        soften.replaced, name
+                    ),
+                );
+            }
+        }
+        Ok(())
     }
+}
+
+/// The regex a soft keyword (with its quotes) is spelled as
+pub(crate) fn soft_keyword_regex(terminal: &str) -> String {
+    let pattern = regex_syntax::escape(strip_terminal_quotes(terminal));
+    format!("/{}/", pattern.replace('/', "\\/"))
+}
 
-    fn convert_sequence(&mut self, items: &[sebnf::Item]) -> Vec<bnf::Item> {
-        items.iter().map(|item| self.convert_item(item)).collect()
+/// Replaces `sep_by(X, d)` by `___sep_by_N := X { d X } | ε`,
+/// `delimited(l, X, r)` by `___delimited_N := l X r` and `non_empty(X)` by
+/// `___non_empty_N := X { X }`.
+#[derive(Debug, Clone, Default)]
+pub struct DesugarBuiltins;
+
+impl Transform<Sebnf> for DesugarBuiltins {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        desugar(sebnf, log, "desugar-builtins", |item| {
+            let Item::Builtin(builtin, args, span) = item else {
+                return None;
+            };
+            let repeated = |items: Vec<Item>| Item::AnyAmount(items, span.clone());
+            let body = match (builtin, args.as_slice()) {
+                (Builtin::SepBy, [element, separator]) => {
+                    let tail = repeated([separator.clone(), element.clone()].concat());
+                    let mut first = element.clone();
+                    first.push(tail);
+                    vec![first, Vec::new()]
+                }
+                (Builtin::Delimited, [left, inner, right]) => {
+                    vec![[left.clone(), inner.clone(), right.clone()].concat()]
+                }
+                (Builtin::NonEmpty, [element]) => {
+                    let mut body = element.clone();
+                    body.push(repeated(element.clone()));
+                    vec![body]
+                }
+                // The parser rejects calls with the wrong number of arguments
+                _ => args.clone(),
+            };
+            Some((builtin.name(), body))
+        });
+        Ok(())
     }
+}
 
-    fn convert_item(&mut self, item: &sebnf::Item) -> bnf::Item {
-        match item {
-            sebnf::Item::NonTerminal(s, _) => bnf::Item::NonTerminal(s.clone()),
-            sebnf::Item::Terminal(s) => bnf::Item::Terminal(s.clone()),
-            sebnf::Item::Regex(s) => bnf::Item::Regex(s.clone()),
+/// Replaces `[ A ]` by `___opt_N := A | ε`.
+#[derive(Debug, Clone, Default)]
+pub struct DesugarOptionals;
 
-            // [ A B ] -> __opt_N := A B | epsilon
-            sebnf::Item::Optional(children) => {
-                let converted_seq = self.convert_sequence(children);
-                let body = vec![converted_seq, vec![]];
-                let key = format!("{:?}", body);
+impl Transform<Sebnf> for DesugarOptionals {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        desugar(sebnf, log, "desugar-optionals", |item| match item {
+            Item::Optional(items, _) => Some(("opt", vec![items.clone(), Vec::new()])),
+            _ => None,
+        });
+        Ok(())
+    }
+}
 
-                let name = if let Some(existing_name) = self.rule_cache.get(&key) {
-                    existing_name.clone()
-                } else {
-                    let new_name = self.next_name("opt");
-                    self.rule_cache.insert(key, new_name.clone());
-                    self.bnf_rules.insert(new_name.clone(), body);
-                    new_name
-                };
+/// Replaces `{ A }` by `___rep_N := A ___rep_N | ε`.
+#[derive(Debug, Clone, Default)]
+pub struct DesugarRepetitions;
 
-                bnf::Item::NonTerminal(name)
+impl Transform<Sebnf> for DesugarRepetitions {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        desugar(sebnf, log, "desugar-repetitions", |item| match item {
+            // The self-reference is named once the helper has a name
+            Item::AnyAmount(items, _) => Some(("rep", vec![items.clone(), Vec::new()])),
+            _ => None,
+        });
+        // Close the loop of every new `___rep_N`
+        for (name, alternatives) in sebnf.rules.iter_mut() {
+            if helper_kind(name) == Some("rep")
+                && let [recursive, _] = alternatives.as_mut_slice()
+                && !matches!(recursive.last(), Some(Item::NonTerminal(n, _)) if n == name)
+            {
+                let span = sebnf.rule_spans.get(name).cloned().unwrap_or_default();
+                recursive.push(Item::NonTerminal(name.clone(), span));
             }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces `( A | B )` by `___choice_N := A | B`.
+#[derive(Debug, Clone, Default)]
+pub struct DesugarChoices;
+
+impl Transform<Sebnf> for DesugarChoices {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        desugar(sebnf, log, "desugar-choices", |item| match item {
+            Item::Choice(alternatives, _) => Some(("choice", alternatives.clone())),
+            _ => None,
+        });
+        Ok(())
+    }
+}
+
+/// Gives the generated empty production of every `___opt_N`, `___rep_N` and
+/// `___sep_by_N` the single item `___empty`, and adds `___empty := ε`.
+#[derive(Debug, Clone, Default)]
+pub struct SharedEpsilon;
 
-            // ( A | B ) -> __choice_N := A | B
-            sebnf::Item::Choice(alternatives) => {
-                let mut body = Vec::new();
-                for alt in alternatives {
-                    body.push(self.convert_sequence(alt));
+impl Transform<Sebnf> for SharedEpsilon {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let mut replaced = 0;
+        for (name, alternatives) in sebnf.rules.iter_mut() {
+            if !helper_kind(name).is_some_and(|kind| EMPTY_ENDED_KINDS.contains(&kind)) {
+                continue;
+            }
+            if let Some(empty) = alternatives.last_mut()
+                && empty.is_empty()
+            {
+                let span = sebnf.rule_spans.get(name).cloned().unwrap_or_default();
+                empty.push(Item::NonTerminal(EMPTY_RULE.to_string(), span));
+                replaced += 1;
+            }
+        }
+        if replaced > 0 && !sebnf.rules.contains_key(EMPTY_RULE) {
+            sebnf.rules.insert(EMPTY_RULE.to_string(), vec![Vec::new()]);
+            log.record(
+                "shared-epsilon",
+                format!(
+                    "routed {} empty production(s) through '{}'",
+                    replaced, EMPTY_RULE
+                ),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Helper rules in the order they are first referenced, depth first, with a
+/// helper following the helpers its body uses. Unreferenced ones come last.
+fn helper_order(sebnf: &Sebnf) -> Vec<String> {
+    struct Order<'a> {
+        sebnf: &'a Sebnf,
+        visited: HashSet<&'a str>,
+        order: Vec<String>,
+    }
+    impl Visitor for Order<'_> {
+        fn visit_non_terminal(&mut self, name: &str, _span: &Range<usize>) {
+            let Some((name, alternatives)) = self.sebnf.rules.get_key_value(name) else {
+                return;
+            };
+            if helper_kind(name).is_some() && self.visited.insert(name) {
+                self.visit_alternatives(alternatives);
+                self.order.push(name.clone());
+            }
+        }
+    }
+
+    let mut order = Order {
+        sebnf,
+        visited: HashSet::new(),
+        order: Vec::new(),
+    };
+    for (name, alternatives) in &sebnf.rules {
+        if helper_kind(name).is_none() {
+            order.visit_alternatives(alternatives);
+        }
+    }
+    let mut helpers = order.order;
+    for name in sebnf.rules.keys() {
+        if helper_kind(name).is_some() && !order.visited.contains(name.as_str()) {
+            helpers.push(name.clone());
+        }
+    }
+    helpers
+}
+
+/// Points every reference to a key of `renames` at its value.
+fn rename_references(sebnf: &mut Sebnf, renames: &HashMap<String, String>) {
+    struct Rename<'a>(&'a HashMap<String, String>);
+    impl VisitorMut for Rename<'_> {
+        fn visit_item_mut(&mut self, item: &mut Item) {
+            if let Item::NonTerminal(name, _) = item
+                && let Some(new_name) = self.0.get(name)
+            {
+                *name = new_name.clone();
+            }
+            walk_item_mut(self, item);
+        }
+    }
+    walk_sebnf_mut(&mut Rename(renames), sebnf);
+}
+
+/// Merges helper rules with the same productions into the one referenced
+/// first. Repetitions are only merged with repetitions.
+#[derive(Debug, Clone, Default)]
+pub struct DedupeHelpers;
+
+impl Transform<Sebnf> for DedupeHelpers {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let mut merged = 0;
+        // Merging two helpers can make the helpers using them equal
+        loop {
+            let mut first: HashMap<(bool, String), String> = HashMap::new();
+            let mut renames = HashMap::new();
+            for name in helper_order(sebnf) {
+                let is_repetition = helper_kind(&name) == Some("rep");
+                let key = (is_repetition, body_key(&name, &sebnf.rules[&name]));
+                match first.get(&key) {
+                    Some(existing) => {
+                        renames.insert(name, existing.clone());
+                    }
+                    None => {
+                        first.insert(key, name);
+                    }
                 }
+            }
+            if renames.is_empty() {
+                break;
+            }
+            merged += renames.len();
+            for name in renames.keys() {
+                sebnf.rules.shift_remove(name);
+                sebnf.rule_spans.shift_remove(name);
+            }
+            rename_references(sebnf, &renames);
+        }
+        if merged > 0 {
+            log.record(
+                "dedupe-helpers",
+                format!("merged {} helper rule(s) into equal ones", merged),
+            );
+        }
+        Ok(())
+    }
+}
 
-                let key = format!("{:?}", body);
+/// Replaces references to small helper rules by their productions: helpers
+/// whose body is a single symbol, and helpers used only once where that
+/// duplicates no items. A helper with several productions is thus only
+/// inlined where it makes up a whole production, which keeps the FIRST and
+/// FOLLOW sets of every remaining rule.
+#[derive(Debug, Clone, Default)]
+pub struct InlineHelpers;
 
-                let name = if let Some(existing_name) = self.rule_cache.get(&key) {
-                    existing_name.clone()
-                } else {
-                    let new_name = self.next_name("choice");
-                    self.rule_cache.insert(key, new_name.clone());
-                    self.bnf_rules.insert(new_name.clone(), body);
-                    new_name
-                };
+impl Transform<Sebnf> for InlineHelpers {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let mut inlined = 0;
+        // Inlining a helper can leave another one used once as a whole production
+        while let Some(helper) = inlinable_helper(sebnf) {
+            let body = sebnf.rules.shift_remove(&helper).unwrap_or_default();
+            sebnf.rule_spans.shift_remove(&helper);
+            for alternatives in sebnf.rules.values_mut() {
+                let mut inlined_alternatives = Vec::new();
+                for alternative in alternatives.drain(..) {
+                    match alternative.as_slice() {
+                        [Item::NonTerminal(name, _)] if *name == helper => {
+                            inlined_alternatives.extend(body.iter().cloned());
+                        }
+                        _ => inlined_alternatives.push(
+                            alternative
+                                .into_iter()
+                                .flat_map(|item| match item {
+                                    Item::NonTerminal(name, _) if name == helper => body[0].clone(),
+                                    item => vec![item],
+                                })
+                                .collect(),
+                        ),
+                    }
+                }
+                *alternatives = inlined_alternatives;
+            }
+            inlined += 1;
+        }
+        if inlined > 0 {
+            log.record(
+                "inline-helpers",
+                format!("inlined {} helper rule(s)", inlined),
+            );
+        }
+        Ok(())
+    }
+}
 
-                bnf::Item::NonTerminal(name)
+/// The first helper rule [`InlineHelpers`] can replace by its productions
+fn inlinable_helper(sebnf: &Sebnf) -> Option<String> {
+    // Uses of every rule, and whether one of them is a whole production
+    let mut uses: HashMap<&str, (usize, bool)> = HashMap::new();
+    for alternatives in sebnf.rules.values() {
+        for alternative in alternatives {
+            for item in alternative {
+                if let Item::NonTerminal(name, _) = item {
+                    let entry = uses.entry(name.as_str()).or_default();
+                    entry.0 += 1;
+                    entry.1 |= alternative.len() == 1;
+                }
             }
+        }
+    }
+    sebnf
+        .rules
+        .iter()
+        .find(|(name, alternatives)| {
+            let refers_to_itself = alternatives
+                .iter()
+                .flatten()
+                .any(|item| matches!(item, Item::NonTerminal(n, _) if n == *name));
+            let inlinable = match (alternatives.as_slice(), uses.get(name.as_str())) {
+                ([single], _) if single.len() == 1 => true,
+                ([_], Some((1, _))) => true,
+                (_, Some((1, whole_production))) => *whole_production,
+                _ => false,
+            };
+            helper_kind(name).is_some() && !refers_to_itself && inlinable
+        })
+        .map(|(name, _)| name.clone())
+}
 
-            // { A B } -> __rep_N := A B __rep_N | epsilon
-            sebnf::Item::AnyAmount(children) => {
-                let converted_seq = self.convert_sequence(children);
-                let key = format!("{:?}", converted_seq);
+/// The productions of `rule` without spans, with references to itself
+/// made anonymous.
+fn body_key(rule: &str, alternatives: &[Vec<Item>]) -> String {
+    let sequences: Vec<String> = alternatives
+        .iter()
+        .map(|sequence| {
+            let items: Vec<String> = sequence
+                .iter()
+                .map(|item| match item {
+                    Item::NonTerminal(name, _) if name == rule => "\0self".to_string(),
+                    item => item.to_string(),
+                })
+                .collect();
+            items.join(" ")
+        })
+        .collect();
+    sequences.join(" | ")
+}
+
+/// Numbers the helper rules `___<kind>_0`, `___<kind>_1`, ... in the order
+/// they are referenced, and moves them behind the grammar's own rules.
+#[derive(Debug, Clone, Default)]
+pub struct NameHelpers;
+
+impl Transform<Sebnf> for NameHelpers {
+    fn apply(&self, sebnf: &mut Sebnf, _log: &mut ChangeLog) -> Result<(), TransformError> {
+        let helpers = helper_order(sebnf);
+        let renames: HashMap<String, String> = helpers
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let kind = helper_kind(name).expect("only helpers are ordered");
+                (name.clone(), format!("___{}_{}", kind, i))
+            })
+            .collect();
+        rename_references(sebnf, &renames);
 
-                let name = if let Some(existing_name) = self.rep_cache.get(&key) {
-                    existing_name.clone()
-                } else {
-                    let new_name = self.next_name("rep");
-                    self.rep_cache.insert(key, new_name.clone());
+        let mut rules = std::mem::take(&mut sebnf.rules);
+        let mut rule_spans = std::mem::take(&mut sebnf.rule_spans);
+        let own_rules: Vec<String> = rules
+            .keys()
+            .filter(|name| helper_kind(name).is_none())
+            .cloned()
+            .collect();
+        for name in own_rules.iter().chain(&helpers) {
+            let new_name = renames.get(name).unwrap_or(name);
+            if let Some(alternatives) = rules.shift_remove(name) {
+                sebnf.rules.insert(new_name.clone(), alternatives);
+            }
+            if let Some(span) = rule_spans.shift_remove(name) {
+                sebnf.rule_spans.insert(new_name.clone(), span);
+            }
+        }
+        Ok(())
+    }
+}
 
-                    let mut recursive_alt = converted_seq;
-                    recursive_alt.push(bnf::Item::NonTerminal(new_name.clone()));
+/// Renames the helper rules `___<rule>_<kind>_<hash>`, after the grammar
+/// rule referencing them (directly or through other helpers) and a hash of
+/// their productions. A helper shared by several rules is `___<kind>_<hash>`,
+/// named after its body alone. Editing one rule thus leaves the names of
+/// other rules' helpers alone. If two helpers get the same name, the hash
+/// grows to 8 digits, then to 16 with a counter mixed in.
+///
+/// Runs after [`NameHelpers`], which orders the helpers.
+#[derive(Debug, Clone, Default)]
+pub struct StableNames;
 
-                    let body = vec![recursive_alt, vec![]];
-                    self.bnf_rules.insert(new_name.clone(), body);
-                    new_name
-                };
+impl Transform<Sebnf> for StableNames {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let owners = helper_owners(sebnf);
+        let mut taken: HashSet<String> = sebnf
+            .rules
+            .keys()
+            .filter(|name| helper_kind(name).is_none())
+            .cloned()
+            .collect();
+        let mut renames: HashMap<String, String> = HashMap::new();
+        // Nested helpers come first, so their new names go into the hash
+        for name in helper_order(sebnf) {
+            let kind = helper_kind(&name).expect("only helpers are ordered");
+            let prefix = match owners.get(&name) {
+                Some(owner) => format!("___{}_{}", owner, kind),
+                None => format!("___{}", kind),
+            };
+            let mut alternatives = sebnf.rules[&name].clone();
+            for item in alternatives.iter_mut().flatten() {
+                if let Item::NonTerminal(reference, _) = item
+                    && let Some(new_name) = renames.get(reference)
+                {
+                    *reference = new_name.clone();
+                }
+            }
+            let key = body_key(&name, &alternatives);
+            let hash = fnv1a(&key);
+            let mut stable = format!("{}_{:04x}", prefix, hash >> 48);
+            if taken.contains(&stable) {
+                stable = format!("{}_{:08x}", prefix, hash >> 32);
+            }
+            let mut salt = 0;
+            while taken.contains(&stable) {
+                salt += 1;
+                stable = format!("{}_{:016x}", prefix, fnv1a(&format!("{}\0{}", key, salt)));
+            }
+            taken.insert(stable.clone());
+            renames.insert(name, stable);
+        }
+        rename_references(sebnf, &renames);
+        let renamed = |name: String| renames.get(&name).cloned().unwrap_or(name);
+        let rules = std::mem::take(&mut sebnf.rules);
+        sebnf.rules = rules
+            .into_iter()
+            .map(|(name, body)| (renamed(name), body))
+            .collect();
+        let rule_spans = std::mem::take(&mut sebnf.rule_spans);
+        sebnf.rule_spans = rule_spans
+            .into_iter()
+            .map(|(name, span)| (renamed(name), span))
+            .collect();
+        if !renames.is_empty() {
+            log.record(
+                "stable-names",
+                format!(
+                    "named {} helper rule(s) after their rules and bodies",
+                    renames.len()
+                ),
+            );
+        }
+        Ok(())
+    }
+}
 
-                bnf::Item::NonTerminal(name)
+/// The grammar rule each helper is referenced from, directly or through
+/// other helpers; helpers referenced from several rules have none
+fn helper_owners(sebnf: &Sebnf) -> HashMap<String, String> {
+    let mut users: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (rule, alternatives) in &sebnf.rules {
+        if helper_kind(rule).is_some() {
+            continue;
+        }
+        let mut seen = HashSet::new();
+        let mut pending: Vec<&Vec<Vec<Item>>> = vec![alternatives];
+        while let Some(alternatives) = pending.pop() {
+            for item in alternatives.iter().flatten() {
+                if let Item::NonTerminal(name, _) = item
+                    && helper_kind(name).is_some()
+                    && seen.insert(name.as_str())
+                    && let Some(body) = sebnf.rules.get(name)
+                {
+                    users.entry(name).or_default().insert(rule);
+                    pending.push(body);
+                }
             }
         }
     }
+    (users.into_iter())
+        .filter(|(_, rules)| rules.len() == 1)
+        .flat_map(|(helper, rules)| {
+            rules
+                .into_iter()
+                .map(|rule| (helper.to_string(), rule.to_string()))
+        })
+        .collect()
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is the same on every
+/// platform and release
+pub(crate) fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_follow_items_into_helper_rules() {
+        let source = "a := \"x\" [ b ] .\nb := \"y\".";
+        let sebnf = Sebnf::parse_str(source, "test").unwrap();
+        let bnf = sebnf_to_bnf(&sebnf);
+
+        let text = |span: Range<usize>| &source[span];
+        assert_eq!(bnf.item_span("a", 0, 0).map(text), Some("\"x\""));
+        assert_eq!(bnf.item_span("a", 0, 1).map(text), Some("[ b ]"));
+        assert_eq!(bnf.item_span("___opt_0", 0, 0).map(text), Some("b"));
+        assert_eq!(bnf.production_span("a", 0).map(text), Some("\"x\" [ b ]"));
+        assert_eq!(bnf.production_span("___opt_0", 1), None);
+    }
+
+    #[test]
+    fn shared_epsilon_keeps_the_sets_of_the_grammar() {
+        let sebnf = Sebnf::parse_str("a := [ \"x\" ] { \"y\" } \"z\".", "test").unwrap();
+        let shared = sebnf_to_bnf_with(
+            &sebnf,
+            &LoweringOptions {
+                shared_epsilon: true,
+                ..LoweringOptions::default()
+            },
+        );
+        assert_eq!(shared.rules.get_index_of(EMPTY_RULE), Some(1));
+        let empty_productions = shared.rules.values().flatten().filter(|p| p.is_empty());
+        assert_eq!(empty_productions.count(), 1);
+
+        let default = sebnf_to_bnf(&sebnf).first_and_follow_sets();
+        let shared = shared.first_and_follow_sets();
+        assert_eq!(default.first["a"], shared.first["a"]);
+        assert_eq!(default.follow["___opt_0"], shared.follow["___opt_0"]);
+    }
+
+    #[test]
+    fn pipeline_passes_can_be_changed() {
+        let sebnf =
+            Sebnf::parse_str("a := [ \"b\" ] { [ \"b\" ] } ( \"x\" | \"y\" ).", "test").unwrap();
+        let (bnf, _) = Pipeline::default().run(&sebnf).unwrap();
+        assert_eq!(
+            bnf.rules.keys().collect::<Vec<_>>(),
+            ["a", "___opt_0", "___rep_1", "___choice_2"]
+        );
+
+        // Without deduplication, the two `[ b ]` stay apart
+        let mut pipeline = Pipeline::default();
+        let dedupe = pipeline
+            .pass_names()
+            .iter()
+            .position(|p| *p == "DedupeHelpers");
+        pipeline.remove(dedupe.unwrap());
+        let (bnf, _) = pipeline.run(&sebnf).unwrap();
+        assert_eq!(bnf.rules.len(), 5);
+
+        // Stopping before the choices are desugared leaves no BNF
+        let mut pipeline = Pipeline::default();
+        pipeline.truncate(3);
+        assert_eq!(
+            pipeline.run(&sebnf).unwrap_err(),
+            TransformError::NotLowered("a".to_string())
+        );
+    }
+
+    #[test]
+    fn stable_names_survive_edits_elsewhere() {
+        let options = LoweringOptions {
+            stable_names: true,
+            ..LoweringOptions::default()
+        };
+        let helpers = |source: &str| {
+            let sebnf = Sebnf::parse_str(source, "test").unwrap();
+            let bnf = sebnf_to_bnf_with(&sebnf, &options);
+            let names: Vec<String> = bnf
+                .rules
+                .keys()
+                .filter(|name| helper_kind(name).is_some())
+                .cloned()
+                .collect();
+            names
+        };
+        let before = helpers("a := [ \"x\" ] { \"y\" [ \"x\" ] }.");
+        let after = helpers("b := { \"z\" }.\na := [ \"x\" ] { \"y\" [ \"x\" ] }.");
+        assert_eq!(before.len(), 2);
+        assert!(before.iter().all(|name| name.starts_with("___a_")));
+        assert_eq!(after.len(), 3);
+        assert!(before.iter().all(|name| after.contains(name)));
+        assert_eq!(helper_kind("___a_sep_by_1f0c"), Some("sep_by"));
+
+        // A helper shared with `a` is named after its body, not after `a`
+        let before = helpers("a := [ \"x\" ] b.\nb := { \"y\" } | [ \"x\" ].");
+        let after = helpers("a := [ \"x\" ] \"z\" b.\nb := { \"y\" } | [ \"x\" ].");
+        assert_eq!(before, after);
+        assert!(before.iter().any(|name| name.starts_with("___opt_")));
+        assert!(before.iter().any(|name| name.starts_with("___b_rep_")));
+        assert_eq!(helper_kind("___a_sep_by_1F0C"), None);
+    }
+
+    #[test]
+    fn desugars_builtin_calls() {
+        let production = |items: &[&str]| -> Vec<bnf::Item> {
+            (items.iter())
+                .map(|item| match item.strip_prefix('"') {
+                    Some(_) => bnf::Item::Terminal(item.to_string()),
+                    None => bnf::Item::NonTerminal(item.to_string()),
+                })
+                .collect()
+        };
+        let source = "a := sep_by(b, \",\") delimited(\"(\", b, \")\") non_empty(b).\nb := \"x\".";
+        let bnf = sebnf_to_bnf(&Sebnf::parse_str(source, "test").unwrap());
+        assert_eq!(
+            bnf.rules["a"],
+            [production(&[
+                "___sep_by_1",
+                "___delimited_2",
+                "___non_empty_4"
+            ])]
+        );
+        assert_eq!(
+            bnf.rules["___sep_by_1"],
+            [production(&["b", "___rep_0"]), production(&[])]
+        );
+        assert_eq!(
+            bnf.rules["___rep_0"],
+            [production(&["\",\"", "b", "___rep_0"]), production(&[])]
+        );
+        assert_eq!(
+            bnf.rules["___delimited_2"],
+            [production(&["\"(\"", "b", "\")\""])]
+        );
+        assert_eq!(
+            bnf.rules["___non_empty_4"],
+            [production(&["b", "___rep_3"])]
+        );
+
+        // A rule named like a built-in is only used without the call syntax
+        let source = "a := sep_by(b, \",\") sep_by (b).\nsep_by := \"y\".\nb := \"x\".";
+        let bnf = sebnf_to_bnf(&Sebnf::parse_str(source, "test").unwrap());
+        assert_eq!(
+            bnf.rules["a"],
+            [production(&["___sep_by_1", "sep_by", "b"])]
+        );
+        assert_eq!(bnf.rules["sep_by"], [production(&["\"y\""])]);
+    }
+
+    #[test]
+    fn inlines_helpers_without_duplicating_items() {
+        let source =
+            "a := ( \"x\" | \"y\" ) delimited(\"(\", b, \")\").\nb := [ ( \"m\" | \"n\" ) ].";
+        let sebnf = Sebnf::parse_str(source, "test").unwrap();
+        let options = LoweringOptions {
+            inline_helpers: true,
+            ..LoweringOptions::default()
+        };
+        let bnf = sebnf_to_bnf_with(&sebnf, &options);
+        // `( "x" | "y" )` is followed by more items, so it keeps its rule
+        assert_eq!(
+            bnf.to_string(),
+            "a           := ___choice_0 \"(\" b \")\".\n\
+             b           := \"m\"\n             | \"n\"\n             | ε\n             .\n\
+             ___choice_0 := \"x\"\n             | \"y\"\n             .\n"
+        );
+    }
 }
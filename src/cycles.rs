@@ -0,0 +1,107 @@
+//! Derivation cycles, `A ⇒+ A`.
+//!
+//! A rule can derive itself through unit productions such as `A := B` and
+//! productions whose other symbols are nullable. Every sentence it derives
+//! then has infinitely many parse trees, and rewrites that expand rules until
+//! nothing changes never stop. [`derivation_cycles`] reports each such cycle
+//! with the productions along it.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::cycles::derivation_cycles;
+//!
+//! let bnf = Sebnf::parse_str("a := b | \"x\".\nb := a [ \"y\" ].", "g")
+//!     .unwrap()
+//!     .to_bnf();
+//! let cycles = derivation_cycles(&bnf);
+//! assert_eq!(cycles.len(), 1);
+//! assert_eq!(cycles[0].steps.len(), 2);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::left_recursion::{Edges, LeftStep, shortest_cycles};
+use crate::nullable::{is_empty_regex, nullable_rules};
+use crate::sets::format_production;
+use crate::symbols::Symbols;
+
+/// Rules that each derive the next, the last the first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationCycle {
+    /// Starting at the rule of the cycle that comes first in the grammar
+    pub steps: Vec<LeftStep>,
+}
+
+impl fmt::Display for DerivationCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = Symbols::with_current(|s| s.glyph("⇒", "=>"));
+        let mut rules: Vec<&str> = self.steps.iter().map(|step| step.rule.as_str()).collect();
+        rules.extend(self.steps.first().map(|step| step.rule.as_str()));
+        writeln!(f, "{}", rules.join(&format!(" {} ", arrow)))?;
+        for step in &self.steps {
+            writeln!(
+                f,
+                "   {} := {}",
+                step.rule,
+                format_production(&step.production)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The derivation cycles of `bnf`: for every rule that derives itself, a
+/// shortest cycle through it.
+pub fn derivation_cycles(bnf: &Bnf) -> Vec<DerivationCycle> {
+    let nullable = nullable_rules(bnf);
+    let vanishes = |item: &Item| match item {
+        Item::NonTerminal(nt) => nullable.is_nullable(nt),
+        item => is_empty_regex(item),
+    };
+    // The rules each rule derives alone, and the production that does
+    let mut edges: Edges = HashMap::new();
+    for (rule, productions) in &bnf.rules {
+        let rule_edges = edges.entry(rule).or_default();
+        for production in productions {
+            for (i, item) in production.iter().enumerate() {
+                let Item::NonTerminal(nt) = item else {
+                    continue;
+                };
+                let rest_vanishes =
+                    (production.iter().enumerate()).all(|(j, other)| j == i || vanishes(other));
+                if bnf.rules.contains_key(nt)
+                    && rest_vanishes
+                    && !rule_edges.iter().any(|(next, _)| next == nt)
+                {
+                    rule_edges.push((nt, production));
+                }
+            }
+        }
+    }
+
+    (shortest_cycles(bnf, &edges).into_iter())
+        .map(|steps| DerivationCycle { steps })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn finds_cycles_through_unit_and_nullable_productions() {
+        let source = "s := a \"x\".\na := c b c | \"y\".\nb := a | \"z\".\nc := [ \"w\" ].";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let cycles = derivation_cycles(&bnf);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(
+            cycles[0].to_string(),
+            "a ⇒ b ⇒ a\n   \
+             a := c b c\n   \
+             b := a\n"
+        );
+    }
+}
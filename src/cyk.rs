@@ -0,0 +1,408 @@
+//! CYK parsing, on the grammar in Chomsky normal form.
+//!
+//! [`to_cnf`] rewrites a BNF grammar so that every production is two rules
+//! or a single terminal or regex, the way textbooks do it: tokens in longer
+//! productions get a rule of their own (`___token_N`), longer productions are
+//! split into pairs (`___pair_N`), rules matching nothing are dropped by
+//! adding the productions without them, and a production that is a single
+//! rule is replaced by the productions of that rule. Only the start rule may
+//! still match the empty input, which [`Cnf::start_nullable`] records.
+//!
+//! [`cyk_recognize`] then fills the CYK table of the input: for every stretch
+//! of tokens, the rules that match it, from single tokens up to the whole
+//! input. It takes cubic time and does not build trees, but it shares no
+//! code with the other parsers beyond the tokens of [`crate::interpret`], so
+//! it can check them.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::cyk::cyk_recognize;
+//!
+//! let sebnf = Sebnf::parse_str("sum := num { \"+\" num }.\nnum := /[0-9]+/.", "g").unwrap();
+//! assert!(cyk_recognize(&sebnf, "1 + 2", "input").unwrap().accepted());
+//! assert!(!cyk_recognize(&sebnf, "1 +", "input").unwrap().accepted());
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::Sebnf;
+use crate::bnf::{Bnf, Item};
+use crate::interpret::{InterpretError, Token, token_span, tokenize};
+use crate::render::{RenderError, render_display};
+use crate::sets::strip_terminal_quotes;
+
+/// A grammar in Chomsky normal form.
+#[derive(Debug, Clone)]
+pub struct Cnf {
+    pub start: String,
+    /// Every production is two rules or one terminal or regex. The start
+    /// rule comes first, unless it matches no tokens
+    pub bnf: Bnf,
+    /// Whether the start rule matches the empty input, which no production
+    /// of `bnf` does
+    pub start_nullable: bool,
+}
+
+/// Converts `bnf` to Chomsky normal form. Rules that no longer matter to
+/// the start rule are left out.
+pub fn to_cnf(bnf: &Bnf) -> Cnf {
+    let start = bnf.rules.keys().next().cloned().unwrap_or_default();
+    let mut rules: IndexMap<String, Vec<Vec<Item>>> = bnf.rules.clone();
+    // An empty terminal matches no token
+    for production in rules.values_mut().flatten() {
+        production.retain(|item| {
+            !matches!(item, Item::Terminal(symbol) if strip_terminal_quotes(symbol).is_empty())
+        });
+    }
+
+    // Tokens next to other symbols get a rule of their own
+    let mut token_rules: HashMap<Item, String> = HashMap::new();
+    let mut added = IndexMap::new();
+    for production in rules.values_mut().flatten() {
+        if production.len() < 2 {
+            continue;
+        }
+        for item in production.iter_mut() {
+            if !matches!(item, Item::NonTerminal(_)) {
+                let rule = token_rules.entry(item.clone()).or_insert_with(|| {
+                    let rule = format!("___token_{}", added.len());
+                    added.insert(rule.clone(), vec![vec![item.clone()]]);
+                    rule
+                });
+                *item = Item::NonTerminal(rule.clone());
+            }
+        }
+    }
+    rules.extend(added);
+
+    // Longer productions are split into pairs, sharing the same tails
+    let mut pair_rules: HashMap<Vec<Item>, String> = HashMap::new();
+    let mut added: IndexMap<String, Vec<Vec<Item>>> = IndexMap::new();
+    for production in rules.values_mut().flatten() {
+        while production.len() > 2 {
+            let tail = production.split_off(production.len() - 2);
+            let rule = pair_rules.entry(tail.clone()).or_insert_with(|| {
+                let rule = format!("___pair_{}", added.len());
+                added.insert(rule.clone(), vec![tail]);
+                rule
+            });
+            production.push(Item::NonTerminal(rule.clone()));
+        }
+    }
+    rules.extend(added);
+
+    // Rules matching nothing are left out of the productions in every way
+    // (tokens are never empty, even where a regex matches the empty string)
+    let nullable = fixpoint(&rules, |nullable, production| {
+        (production.iter())
+            .all(|item| matches!(item, Item::NonTerminal(rule) if nullable.contains(rule)))
+    });
+    let is_nullable =
+        |item: &Item| matches!(item, Item::NonTerminal(rule) if nullable.contains(rule));
+    for productions in rules.values_mut() {
+        let mut without = Vec::new();
+        for production in productions.iter() {
+            if let [first, second] = production.as_slice() {
+                if is_nullable(first) {
+                    without.push(vec![second.clone()]);
+                }
+                if is_nullable(second) {
+                    without.push(vec![first.clone()]);
+                }
+            }
+        }
+        productions.extend(without);
+        productions.retain(|production| !production.is_empty());
+        dedupe(productions);
+    }
+
+    // A single rule is replaced by its productions, and theirs
+    let units = |rule: &str| -> Vec<String> {
+        let mut reached = vec![rule.to_string()];
+        let mut i = 0;
+        while i < reached.len() {
+            for production in rules.get(&reached[i]).into_iter().flatten() {
+                if let [Item::NonTerminal(unit)] = production.as_slice()
+                    && !reached.contains(unit)
+                {
+                    reached.push(unit.clone());
+                }
+            }
+            i += 1;
+        }
+        reached
+    };
+    let mut cnf: IndexMap<String, Vec<Vec<Item>>> = IndexMap::new();
+    for rule in rules.keys() {
+        let mut productions: Vec<Vec<Item>> = (units(rule).iter())
+            .flat_map(|unit| rules.get(unit).into_iter().flatten())
+            .filter(|production| !matches!(production.as_slice(), [Item::NonTerminal(_)]))
+            .cloned()
+            .collect();
+        dedupe(&mut productions);
+        cnf.insert(rule.clone(), productions);
+    }
+
+    // Only the rules matching some tokens, and that the start rule uses, are
+    // kept
+    let uses_only = |rules: &HashSet<String>, production: &[Item]| {
+        (production.iter()).all(|item| match item {
+            Item::NonTerminal(rule) => rules.contains(rule),
+            _ => true,
+        })
+    };
+    let productive = fixpoint(&cnf, uses_only);
+    for productions in cnf.values_mut() {
+        productions.retain(|production| uses_only(&productive, production));
+    }
+    let mut reachable = HashSet::new();
+    let mut queue: Vec<String> = productive.get(&start).cloned().into_iter().collect();
+    reachable.extend(queue.iter().cloned());
+    while let Some(rule) = queue.pop() {
+        for item in cnf[&rule].iter().flatten() {
+            if let Item::NonTerminal(used) = item
+                && reachable.insert(used.clone())
+            {
+                queue.push(used.clone());
+            }
+        }
+    }
+    cnf.retain(|rule, _| reachable.contains(rule));
+    Cnf {
+        start_nullable: nullable.contains(&start),
+        start,
+        bnf: Bnf {
+            rules: cnf,
+            spans: IndexMap::new(),
+        },
+    }
+}
+
+/// The rules with a production for which `holds` holds, given the rules
+/// found before
+fn fixpoint(
+    rules: &IndexMap<String, Vec<Vec<Item>>>,
+    holds: impl Fn(&HashSet<String>, &[Item]) -> bool,
+) -> HashSet<String> {
+    let mut found = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (rule, productions) in rules {
+            if !found.contains(rule)
+                && productions
+                    .iter()
+                    .any(|production| holds(&found, production))
+            {
+                found.insert(rule.clone());
+                changed = true;
+            }
+        }
+    }
+    found
+}
+
+/// Removes the productions written before, keeping the order
+fn dedupe(productions: &mut Vec<Vec<Item>>) {
+    let mut seen = HashSet::new();
+    productions.retain(|production| seen.insert(production.clone()));
+}
+
+/// The CYK table of an input.
+#[derive(Debug, Clone)]
+pub struct CykTable {
+    pub cnf: Cnf,
+    pub tokens: Vec<Token>,
+    /// `cells[len - 1][start]` are the rules of `cnf` matching the `len`
+    /// tokens from the one at `start`, in the order of the grammar
+    pub cells: Vec<Vec<Vec<String>>>,
+}
+
+impl CykTable {
+    /// Whether the start rule matches the whole input
+    pub fn accepted(&self) -> bool {
+        match self.cells.last() {
+            Some(row) => row[0].contains(&self.cnf.start),
+            None => self.cnf.start_nullable,
+        }
+    }
+
+    /// The error for an input that is not accepted
+    pub fn rejection(&self, input: &str, input_name: &str) -> InterpretError {
+        let span = token_span(&self.tokens, 0, self.tokens.len());
+        InterpretError::Rejected {
+            rule: self.cnf.start.clone(),
+            src: miette::NamedSource::new(input_name, input.to_string()),
+            span: (span.start, span.len()).into(),
+        }
+    }
+
+    /// Renders the grammar in Chomsky normal form and the table below it:
+    /// a column per token, and a row per number of tokens, whose cells list
+    /// the rules matching that many tokens from the column's
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
+}
+
+impl fmt::Display for CykTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cnf.bnf)?;
+        if self.cnf.start_nullable {
+            writeln!(f, "(the start rule also matches the empty input)")?;
+        }
+        writeln!(f)?;
+        let mut rows = vec![(
+            String::new(),
+            self.tokens
+                .iter()
+                .map(|token| format!("{:?}", token.text))
+                .collect::<Vec<_>>(),
+        )];
+        for (len, row) in self.cells.iter().enumerate() {
+            let cells = (row.iter())
+                .map(|rules| match rules.is_empty() {
+                    true => "-".to_string(),
+                    false => rules.join(", "),
+                })
+                .collect();
+            rows.push(((len + 1).to_string(), cells));
+        }
+        let label = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..self.tokens.len())
+            .map(|column| {
+                (rows.iter())
+                    .filter_map(|(_, cells)| cells.get(column))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        for (label_text, cells) in &rows {
+            let mut line = format!("{:label$}", label_text, label = label);
+            for (cell, width) in cells.iter().zip(&widths) {
+                line.push_str(&format!("  {:width$}", cell, width = width));
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Fills the CYK table of `input`, named `input_name` in errors, for the
+/// start rule of `sebnf`.
+pub fn cyk_recognize(
+    sebnf: &Sebnf,
+    input: &str,
+    input_name: &str,
+) -> Result<CykTable, InterpretError> {
+    let bnf = sebnf.to_bnf();
+    let tokens = tokenize(&bnf, input, input_name)?;
+    let cnf = to_cnf(&bnf);
+    let rules: Vec<&String> = cnf.bnf.rules.keys().collect();
+    let index: HashMap<&str, usize> = (rules.iter().enumerate())
+        .map(|(i, rule)| (rule.as_str(), i))
+        .collect();
+    let mut by_token: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut by_pair: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (rule, productions) in cnf.bnf.rules.values().enumerate() {
+        for production in productions {
+            match production.as_slice() {
+                [Item::NonTerminal(first), Item::NonTerminal(second)] => {
+                    let pair = (index[first.as_str()], index[second.as_str()]);
+                    by_pair.entry(pair).or_default().push(rule);
+                }
+                [Item::Terminal(symbol) | Item::Regex(symbol) | Item::TokenClass(symbol)] => {
+                    by_token.entry(symbol).or_default().push(rule);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let n = tokens.len();
+    let mut cells: Vec<Vec<Vec<bool>>> = Vec::with_capacity(n);
+    let first = (tokens.iter())
+        .map(|token| {
+            let mut cell = vec![false; rules.len()];
+            for &rule in by_token.get(token.symbol.as_str()).into_iter().flatten() {
+                cell[rule] = true;
+            }
+            cell
+        })
+        .collect();
+    cells.push(first);
+    for len in 2..=n {
+        let row = (0..=n - len)
+            .map(|start| {
+                let mut cell = vec![false; rules.len()];
+                for split in 1..len {
+                    let left = &cells[split - 1][start];
+                    let right = &cells[len - split - 1][start + split];
+                    for (&(first, second), matched) in &by_pair {
+                        if left[first] && right[second] {
+                            for &rule in matched {
+                                cell[rule] = true;
+                            }
+                        }
+                    }
+                }
+                cell
+            })
+            .collect();
+        cells.push(row);
+    }
+    if n == 0 {
+        cells.clear();
+    }
+
+    let cells = (cells.iter())
+        .map(|row| {
+            (row.iter())
+                .map(|cell| {
+                    (rules.iter().zip(cell))
+                        .filter(|(_, matched)| **matched)
+                        .map(|(rule, _)| rule.to_string())
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+    Ok(CykTable { cnf, tokens, cells })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::earley::{EarleyOptions, earley_parse};
+
+    #[test]
+    fn agrees_with_the_earley_parser() {
+        let cases = [
+            ("s := a a \"x\".\na := [ \"b\" ].", "x|b x|b b x|b b b x|b"),
+            (
+                "s := [ \"b\" ] s \"x\" | \"y\".",
+                "y|b y x|y x x|b b y x x|y y|",
+            ),
+            (
+                "e := e \"-\" e | /[0-9]+/ | \"(\" [ e ] \")\".",
+                "1|1 - 2 - 3|( )|(1 - ) - 2|- 1",
+            ),
+            (
+                "s := { \"a\" } [ t ].\nt := s \"b\".",
+                "|a|a b|a a b b|b|a b a b",
+            ),
+        ];
+        for (grammar, inputs) in cases {
+            let sebnf = Sebnf::parse_str(grammar, "g").unwrap();
+            for input in inputs.split('|') {
+                let cyk = cyk_recognize(&sebnf, input, "input").unwrap();
+                let earley = earley_parse(&sebnf, input, "input", &EarleyOptions::default());
+                assert_eq!(cyk.accepted(), earley.is_ok(), "{:?} on {}", input, grammar);
+            }
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! Grammars as structured data, for programs that produce or consume them
+//! without writing SEBNF text.
+//!
+//! [`describe`] turns a grammar into JSON (what `dump-ast` prints), and
+//! [`sebnf_source`] turns such a description back into SEBNF source:
+//!
+//! ```json
+//! { "rules": [
+//!     { "name": "list", "max_depth": 3, "alternatives": [[
+//!         { "terminal": "[" },
+//!         { "builtin": "sep_by", "args": [[{ "non_terminal": "list" }], [{ "terminal": "," }]] },
+//!         { "terminal": "]" }
+//!     ], [{ "regex": "[0-9]+" }]] }
+//! ] }
+//! ```
+//!
+//! Besides `non_terminal`, `terminal`, `regex` and `builtin`, an item is one
+//! of `{ "optional": [items] }`, `{ "repeat": [items] }` and
+//! `{ "choice": [[items], ...] }`, and any item may have a `"label"`. Rules
+//! may have `max_depth` and `soft_keywords` (a list of strings).
+//! Terminals are given as the text they match and regexes without their
+//! slashes; quoting and escaping happen here.
+
+use serde_json::{Map, Value, json};
+
+use crate::sebnf::{Builtin, Item, Sebnf};
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum DescriptionError {
+    #[error("invalid JSON")]
+    #[diagnostic(code(sebnf::invalid_json))]
+    Json(#[source] serde_json::Error),
+    #[cfg(feature = "yaml")]
+    #[error("invalid YAML")]
+    #[diagnostic(code(sebnf::invalid_yaml))]
+    Yaml(#[source] serde_yaml::Error),
+    #[error("{path}: {message}")]
+    #[diagnostic(
+        code(sebnf::invalid_description),
+        help("run `dump-ast` on an SEBNF grammar to see the expected format")
+    )]
+    Invalid { path: String, message: String },
+}
+
+/// Parses a JSON grammar description.
+pub fn parse_json(text: &str) -> Result<Value, DescriptionError> {
+    serde_json::from_str(text).map_err(DescriptionError::Json)
+}
+
+/// Parses a YAML grammar description, with the same structure as in JSON.
+#[cfg(feature = "yaml")]
+pub fn parse_yaml(text: &str) -> Result<Value, DescriptionError> {
+    serde_yaml::from_str(text).map_err(DescriptionError::Yaml)
+}
+
+/// Describes every rule of the grammar, in order.
+pub fn describe(sebnf: &Sebnf) -> Value {
+    let rules: Vec<_> = sebnf
+        .rules
+        .iter()
+        .map(|(name, alternatives)| {
+            let mut rule = json!({ "name": name });
+            if let Some(annotations) = sebnf.annotations.get(name) {
+                if let Some(limit) = annotations.max_depth {
+                    rule["max_depth"] = json!(limit);
+                }
+                if !annotations.soft_keywords.is_empty() {
+                    let keywords: Vec<_> = annotations
+                        .soft_keywords
+                        .iter()
+                        .map(|keyword| unquote_terminal(keyword))
+                        .collect();
+                    rule["soft_keywords"] = json!(keywords);
+                }
+            }
+            rule["alternatives"] = describe_alternatives(alternatives);
+            rule
+        })
+        .collect();
+    json!({ "rules": rules })
+}
+
+fn describe_alternatives(alternatives: &[Vec<Item>]) -> Value {
+    Value::Array(alternatives.iter().map(|a| describe_items(a)).collect())
+}
+
+fn describe_items(items: &[Item]) -> Value {
+    Value::Array(items.iter().map(describe_item).collect())
+}
+
+fn describe_item(item: &Item) -> Value {
+    match item {
+        Item::NonTerminal(name, _) => json!({ "non_terminal": name }),
+        Item::Terminal(terminal, _) => json!({ "terminal": unquote_terminal(terminal) }),
+        Item::Regex(regex, _) => json!({ "regex": unslash_regex(regex) }),
+        Item::Optional(items, _) => json!({ "optional": describe_items(items) }),
+        Item::AnyAmount(items, _) => json!({ "repeat": describe_items(items) }),
+        Item::Choice(alternatives, _) => json!({ "choice": describe_alternatives(alternatives) }),
+        Item::Builtin(builtin, args, _) => {
+            json!({ "builtin": builtin.name(), "args": describe_alternatives(args) })
+        }
+        Item::Labeled(label, inner, _) => {
+            let mut described = describe_item(inner);
+            described["label"] = json!(label);
+            described
+        }
+        Item::Epsilon(_) => json!({ "epsilon": true }),
+    }
+}
+
+/// The SEBNF source of a grammar description, to be parsed with
+/// [`Sebnf::parse_str`].
+pub fn sebnf_source(description: &Value) -> Result<String, DescriptionError> {
+    let mut sebnf = Sebnf {
+        rules: Default::default(),
+        rule_spans: Default::default(),
+        annotations: Default::default(),
+    };
+    let rules = field(description, "rules", "")?;
+    for (i, rule) in array(rules, "rules")?.iter().enumerate() {
+        let path = format!("rules[{}]", i);
+        let name = string(field(rule, "name", &path)?, &format!("{}.name", path))?;
+        if !is_name(name) {
+            return Err(invalid(
+                &path,
+                format!("'{}' is not a valid rule name", name),
+            ));
+        }
+        if sebnf.rules.contains_key(name) {
+            return Err(invalid(&path, format!("rule '{}' is defined twice", name)));
+        }
+        if let Some(limit) = rule.get("max_depth") {
+            let limit = limit
+                .as_u64()
+                .filter(|limit| *limit > 0)
+                .ok_or_else(|| invalid(&path, "max_depth must be a positive whole number"))?;
+            let annotations = sebnf.annotations.entry(name.to_string()).or_default();
+            annotations.max_depth = Some(limit as usize);
+        }
+        if let Some(keywords) = rule.get("soft_keywords") {
+            let keywords_path = format!("{}.soft_keywords", path);
+            let keywords = array(keywords, &keywords_path)?
+                .iter()
+                .enumerate()
+                .map(|(i, k)| {
+                    Ok(quote_terminal(string(
+                        k,
+                        &format!("{}[{}]", keywords_path, i),
+                    )?))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if !keywords.is_empty() {
+                let annotations = sebnf.annotations.entry(name.to_string()).or_default();
+                annotations.soft_keywords = keywords;
+            }
+        }
+        let alternatives = field(rule, "alternatives", &path)?;
+        let alternatives = read_alternatives(alternatives, &format!("{}.alternatives", path))?;
+        if alternatives.is_empty() {
+            return Err(invalid(
+                &path,
+                "a rule needs an alternative; [[]] derives ε",
+            ));
+        }
+        sebnf.rules.insert(name.to_string(), alternatives);
+    }
+    Ok(sebnf.to_string())
+}
+
+fn read_alternatives(value: &Value, path: &str) -> Result<Vec<Vec<Item>>, DescriptionError> {
+    array(value, path)?
+        .iter()
+        .enumerate()
+        .map(|(i, items)| read_items(items, &format!("{}[{}]", path, i)))
+        .collect()
+}
+
+fn read_items(value: &Value, path: &str) -> Result<Vec<Item>, DescriptionError> {
+    array(value, path)?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| read_item(item, &format!("{}[{}]", path, i)))
+        .collect()
+}
+
+fn read_item(value: &Value, path: &str) -> Result<Item, DescriptionError> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| invalid(path, "expected an item object"))?;
+    let kind = [
+        "non_terminal",
+        "terminal",
+        "regex",
+        "optional",
+        "repeat",
+        "choice",
+        "builtin",
+        "epsilon",
+    ]
+    .into_iter()
+    .find(|kind| object.contains_key(*kind))
+    .ok_or_else(|| {
+        invalid(
+            path,
+            "expected one of non_terminal, terminal, regex, optional, repeat, choice, builtin or epsilon",
+        )
+    })?;
+    let content = &object[kind];
+    let content_path = format!("{}.{}", path, kind);
+    let span = 0..0;
+    let item = match kind {
+        "non_terminal" => {
+            let name = string(content, &content_path)?;
+            if !is_name(name) {
+                return Err(invalid(
+                    path,
+                    format!("'{}' is not a valid rule name", name),
+                ));
+            }
+            Item::NonTerminal(name.to_string(), span)
+        }
+        "terminal" => Item::Terminal(quote_terminal(string(content, &content_path)?), span),
+        "regex" => Item::Regex(slash_regex(string(content, &content_path)?), span),
+        "optional" => Item::Optional(read_items(content, &content_path)?, span),
+        "repeat" => Item::AnyAmount(read_items(content, &content_path)?, span),
+        "choice" => Item::Choice(read_alternatives(content, &content_path)?, span),
+        "epsilon" => Item::Epsilon(span),
+        _ => {
+            let name = string(content, &content_path)?;
+            let builtin = Builtin::from_name(name)
+                .ok_or_else(|| invalid(&content_path, format!("unknown built-in '{}'", name)))?;
+            let args_path = format!("{}.args", path);
+            let args = read_alternatives(field(value, "args", path)?, &args_path)?;
+            Item::Builtin(builtin, args, span)
+        }
+    };
+    match object.get("label") {
+        Some(label) => {
+            let label = string(label, &format!("{}.label", path))?;
+            if !is_name(label) {
+                return Err(invalid(path, format!("'{}' is not a valid label", label)));
+            }
+            Ok(Item::Labeled(label.to_string(), Box::new(item), 0..0))
+        }
+        None => Ok(item),
+    }
+}
+
+fn invalid(path: &str, message: impl Into<String>) -> DescriptionError {
+    DescriptionError::Invalid {
+        path: if path.is_empty() { "grammar" } else { path }.to_string(),
+        message: message.into(),
+    }
+}
+
+fn field<'a>(value: &'a Value, name: &str, path: &str) -> Result<&'a Value, DescriptionError> {
+    value
+        .as_object()
+        .and_then(|object: &Map<String, Value>| object.get(name))
+        .ok_or_else(|| invalid(path, format!("missing field '{}'", name)))
+}
+
+fn array<'a>(value: &'a Value, path: &str) -> Result<&'a Vec<Value>, DescriptionError> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid(path, "expected an array"))
+}
+
+fn string<'a>(value: &'a Value, path: &str) -> Result<&'a str, DescriptionError> {
+    value
+        .as_str()
+        .ok_or_else(|| invalid(path, "expected a string"))
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// `text` as an SEBNF terminal, e.g. `a"b` as `"a\"b"`
+fn quote_terminal(text: &str) -> String {
+    let mut terminal = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => terminal.push_str("\\\""),
+            '\\' => terminal.push_str("\\\\"),
+            '\n' => terminal.push_str("\\n"),
+            '\r' => terminal.push_str("\\r"),
+            '\t' => terminal.push_str("\\t"),
+            c => terminal.push(c),
+        }
+    }
+    terminal.push('"');
+    terminal
+}
+
+/// The text an SEBNF terminal matches; the inverse of [`quote_terminal`].
+/// Unknown escapes are kept as written.
+fn unquote_terminal(terminal: &str) -> String {
+    let inner = terminal
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(terminal);
+    let mut text = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('r') => text.push('\r'),
+            Some('t') => text.push('\t'),
+            Some(c @ ('"' | '\\')) => text.push(c),
+            Some(c) => {
+                text.push('\\');
+                text.push(c);
+            }
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// `pattern` as an SEBNF regex, escaping every `/` that is not escaped yet
+fn slash_regex(pattern: &str) -> String {
+    let mut regex = String::from("/");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                regex.push('\\');
+                regex.extend(chars.next());
+            }
+            '/' => regex.push_str("\\/"),
+            c => regex.push(c),
+        }
+    }
+    regex.push('/');
+    regex
+}
+
+/// The pattern of an SEBNF regex, with `\/` written as `/`
+fn unslash_regex(regex: &str) -> String {
+    let inner = regex
+        .strip_prefix('/')
+        .and_then(|r| r.strip_suffix('/'))
+        .unwrap_or(regex);
+    let mut pattern = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            pattern.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('/') => pattern.push('/'),
+            next => {
+                pattern.push('\\');
+                pattern.extend(next);
+            }
+        }
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptions_round_trip() {
+        let source = "@max_depth(2)\n@soft_keywords(\"async\")\ns := \"async\" \"a\\\"b\" { /x\\/y/ } | ( t | sep_by(t, \",\") ).\nt := [ \"\\\\\" ] l:t.\n";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let description = describe(&sebnf);
+        assert_eq!(description["rules"][0]["soft_keywords"], json!(["async"]));
+        assert_eq!(
+            description["rules"][0]["alternatives"][0][1],
+            json!({ "terminal": "a\"b" })
+        );
+        assert_eq!(
+            description["rules"][0]["alternatives"][0][2],
+            json!({ "repeat": [{ "regex": "x/y" }] })
+        );
+        assert_eq!(sebnf_source(&description).unwrap(), sebnf.to_string());
+
+        let broken = json!({ "rules": [{ "name": "s", "alternatives": [[{ "optional": "a" }]] }] });
+        assert_eq!(
+            sebnf_source(&broken).unwrap_err().to_string(),
+            "rules[0].alternatives[0][0].optional: expected an array"
+        );
+    }
+}
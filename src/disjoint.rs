@@ -0,0 +1,475 @@
+//! Whether two rules derive a common sentence.
+//!
+//! Sentences are compared by the text they match, so a terminal and a regex
+//! matching it, or two regexes with a common match, read the same. A rule
+//! that refers to itself, directly or through other rules, is only compared
+//! on its sentences of up to `max_len` tokens; without such recursion, `[ ]`,
+//! `{ }` and the built-in idioms included, its language is regular and the
+//! comparison is exact.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+use indexmap::IndexMap;
+
+use crate::regex_intersect::{Error as RegexError, do_regexs_intersect};
+use crate::sebnf::{Builtin, Item, Sebnf};
+use crate::self_check::{self, SelfCheckError, Sentence};
+use crate::sets::{strip_regex_delimiters, strip_terminal_quotes};
+use crate::symbols::Symbols;
+use crate::visit::{Visitor, walk_alternatives};
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum DisjointError {
+    #[error("no rule named '{0}'")]
+    #[diagnostic(code(sebnf::disjoint_unknown_rule))]
+    UnknownRule(String),
+    #[error("invalid regex pattern in grammar: {pattern}")]
+    #[diagnostic(code(sebnf::invalid_regex))]
+    InvalidRegex {
+        pattern: String,
+        #[source]
+        source: RegexError,
+    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    TooManySentences(#[from] SelfCheckError),
+}
+
+/// A sentence of each rule that both read the same text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Overlap {
+    pub first: Sentence,
+    pub second: Sentence,
+    /// A text matched by the two tokens at every position
+    pub text: Vec<String>,
+}
+
+/// Outcome of [`check_disjoint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disjointness {
+    pub first: String,
+    pub second: String,
+    /// Whether sentences of any length were compared, not only those of up
+    /// to `max_len` tokens
+    pub exact: bool,
+    pub max_len: usize,
+    /// A common sentence with the fewest tokens, if there is one
+    pub overlap: Option<Overlap>,
+}
+
+impl Disjointness {
+    pub fn is_disjoint(&self) -> bool {
+        self.overlap.is_none()
+    }
+}
+
+impl fmt::Display for Disjointness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sentence = |f: &mut fmt::Formatter<'_>, tokens: &[String]| {
+            if tokens.is_empty() {
+                Symbols::with_current(|s| write!(f, "{}", s.epsilon))
+            } else {
+                write!(f, "{}", tokens.join(" "))
+            }
+        };
+        let Some(overlap) = &self.overlap else {
+            write!(f, "'{}' and '{}' ", self.first, self.second)?;
+            return if self.exact {
+                writeln!(f, "never derive a common sentence")
+            } else {
+                writeln!(
+                    f,
+                    "derive no common sentence of up to {} tokens",
+                    self.max_len
+                )
+            };
+        };
+        write!(f, "'{}' and '{}' both derive ", self.first, self.second)?;
+        sentence(f, &overlap.text)?;
+        write!(f, "\n  '{}': ", self.first)?;
+        sentence(f, &overlap.first)?;
+        write!(f, "\n  '{}': ", self.second)?;
+        sentence(f, &overlap.second)?;
+        writeln!(f)
+    }
+}
+
+/// Looks for a sentence derived by both `first` and `second`, comparing
+/// sentences of up to `max_len` tokens of a rule that is recursive.
+pub fn check_disjoint(
+    sebnf: &Sebnf,
+    first: &str,
+    second: &str,
+    max_len: usize,
+) -> Result<Disjointness, DisjointError> {
+    for rule in [first, second] {
+        if !sebnf.rules.contains_key(rule) {
+            return Err(DisjointError::UnknownRule(rule.to_string()));
+        }
+    }
+    let references = References::of(sebnf);
+    let mut bounded = None;
+    let mut automaton = |rule: &str| -> Result<(Automaton, bool), DisjointError> {
+        if !references.is_recursive(rule) {
+            return Ok((Automaton::of_rule(sebnf, rule), true));
+        }
+        if bounded.is_none() {
+            bounded = Some(self_check::bounded_sentences(
+                &references.sub_grammar(sebnf, [first, second]),
+                max_len,
+            )?);
+        }
+        let sentences = &bounded.as_ref().expect("computed above")[rule];
+        Ok((Automaton::of_sentences(sentences), false))
+    };
+    let (a, exact_a) = automaton(first)?;
+    let (b, exact_b) = automaton(second)?;
+    Ok(Disjointness {
+        first: first.to_string(),
+        second: second.to_string(),
+        exact: exact_a && exact_b,
+        max_len,
+        overlap: shortest_overlap(&a, &b)?,
+    })
+}
+
+/// Rules referenced by every rule
+struct References(IndexMap<String, Vec<String>>);
+
+impl References {
+    fn of(sebnf: &Sebnf) -> References {
+        struct Collector(Vec<String>);
+        impl Visitor for Collector {
+            fn visit_non_terminal(&mut self, name: &str, _span: &Range<usize>) {
+                self.0.push(name.to_string());
+            }
+        }
+        References(
+            sebnf
+                .rules
+                .iter()
+                .map(|(rule, alternatives)| {
+                    let mut collector = Collector(Vec::new());
+                    walk_alternatives(&mut collector, alternatives);
+                    (rule.clone(), collector.0)
+                })
+                .collect(),
+        )
+    }
+
+    /// Rules reachable from the roots, the roots included
+    fn reachable<'a>(&'a self, roots: impl IntoIterator<Item = &'a str>) -> HashSet<&'a str> {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<&str> = roots.into_iter().collect();
+        while let Some(rule) = stack.pop() {
+            if let Some((rule, references)) = self.0.get_key_value(rule)
+                && reachable.insert(rule.as_str())
+            {
+                stack.extend(references.iter().map(String::as_str));
+            }
+        }
+        reachable
+    }
+
+    /// Whether a rule reachable from `rule` refers back to itself
+    fn is_recursive(&self, rule: &str) -> bool {
+        self.reachable([rule]).into_iter().any(|from| {
+            self.0[from]
+                .iter()
+                .any(|to| self.reachable([to.as_str()]).contains(from))
+        })
+    }
+
+    fn sub_grammar<'a>(&'a self, sebnf: &Sebnf, roots: [&'a str; 2]) -> Sebnf {
+        let reachable = self.reachable(roots);
+        let mut sub = Sebnf {
+            rules: IndexMap::new(),
+            rule_spans: IndexMap::new(),
+            annotations: IndexMap::new(),
+        };
+        for (rule, alternatives) in &sebnf.rules {
+            if reachable.contains(rule.as_str()) {
+                sub.rules.insert(rule.clone(), alternatives.clone());
+            }
+        }
+        sub
+    }
+}
+
+/// A nondeterministic automaton over the terminals and regexes of a rule.
+/// State 0 is the start state.
+struct Automaton {
+    /// Transitions of every state, on a token or on nothing
+    edges: Vec<Vec<(Option<String>, usize)>>,
+    accepting: Vec<bool>,
+}
+
+impl Automaton {
+    fn state(&mut self) -> usize {
+        self.edges.push(Vec::new());
+        self.accepting.push(false);
+        self.edges.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, token: Option<&str>, to: usize) {
+        self.edges[from].push((token.map(str::to_string), to));
+    }
+
+    /// The automaton of a rule without recursion, with every referenced
+    /// rule expanded in place
+    fn of_rule(sebnf: &Sebnf, rule: &str) -> Automaton {
+        let mut automaton = Automaton {
+            edges: Vec::new(),
+            accepting: Vec::new(),
+        };
+        let (start, accept) = (automaton.state(), automaton.state());
+        automaton.accepting[accept] = true;
+        automaton.alternatives(sebnf, &sebnf.rules[rule], start, accept);
+        automaton
+    }
+
+    /// The automaton of a finite set of sentences, as a trie
+    fn of_sentences(sentences: &HashSet<Sentence>) -> Automaton {
+        let mut automaton = Automaton {
+            edges: Vec::new(),
+            accepting: Vec::new(),
+        };
+        let start = automaton.state();
+        let mut children: HashMap<(usize, &str), usize> = HashMap::new();
+        for sentence in sentences {
+            let mut state = start;
+            for token in sentence {
+                state = match children.get(&(state, token.as_str())) {
+                    Some(&child) => child,
+                    None => {
+                        let child = automaton.state();
+                        automaton.edge(state, Some(token), child);
+                        children.insert((state, token.as_str()), child);
+                        child
+                    }
+                };
+            }
+            automaton.accepting[state] = true;
+        }
+        automaton
+    }
+
+    fn alternatives(&mut self, sebnf: &Sebnf, alternatives: &[Vec<Item>], from: usize, to: usize) {
+        for items in alternatives {
+            self.sequence(sebnf, items, from, to);
+        }
+    }
+
+    fn sequence(&mut self, sebnf: &Sebnf, items: &[Item], from: usize, to: usize) {
+        let mut state = from;
+        for item in items {
+            let next = self.state();
+            self.item(sebnf, item, state, next);
+            state = next;
+        }
+        self.edge(state, None, to);
+    }
+
+    fn item(&mut self, sebnf: &Sebnf, item: &Item, from: usize, to: usize) {
+        match item {
+            Item::NonTerminal(name, _) => {
+                // Undefined rules derive nothing
+                if let Some(alternatives) = sebnf.rules.get(name) {
+                    self.alternatives(sebnf, alternatives, from, to);
+                }
+            }
+            Item::Terminal(token, _) | Item::Regex(token, _) => self.edge(from, Some(token), to),
+            Item::Optional(items, _) => {
+                self.sequence(sebnf, items, from, to);
+                self.edge(from, None, to);
+            }
+            Item::AnyAmount(items, _) => self.star(sebnf, items, from, to),
+            Item::Choice(alternatives, _) => self.alternatives(sebnf, alternatives, from, to),
+            Item::Labeled(_, inner, _) => self.item(sebnf, inner, from, to),
+            Item::Epsilon(_) => self.edge(from, None, to),
+            Item::Builtin(builtin, args, _) => match (builtin, args.as_slice()) {
+                (Builtin::SepBy, [element, separator]) => {
+                    let (first, loop_start) = (self.state(), self.state());
+                    self.sequence(sebnf, element, from, first);
+                    self.edge(first, None, loop_start);
+                    let after_separator = self.state();
+                    self.sequence(sebnf, separator, loop_start, after_separator);
+                    self.sequence(sebnf, element, after_separator, loop_start);
+                    self.edge(loop_start, None, to);
+                    self.edge(from, None, to);
+                }
+                (Builtin::Delimited, [left, element, right]) => {
+                    let (after_left, after_element) = (self.state(), self.state());
+                    self.sequence(sebnf, left, from, after_left);
+                    self.sequence(sebnf, element, after_left, after_element);
+                    self.sequence(sebnf, right, after_element, to);
+                }
+                (Builtin::NonEmpty, [element]) => {
+                    let first = self.state();
+                    self.sequence(sebnf, element, from, first);
+                    self.star(sebnf, element, first, to);
+                }
+                // The parser rejects calls with the wrong number of arguments
+                _ => {}
+            },
+        }
+    }
+
+    fn star(&mut self, sebnf: &Sebnf, items: &[Item], from: usize, to: usize) {
+        let loop_start = self.state();
+        self.edge(from, None, loop_start);
+        self.sequence(sebnf, items, loop_start, loop_start);
+        self.edge(loop_start, None, to);
+    }
+}
+
+/// A sentence of both automatons with the fewest tokens, found by a
+/// breadth-first search of their product
+fn shortest_overlap(a: &Automaton, b: &Automaton) -> Result<Option<Overlap>, DisjointError> {
+    type Pair = (usize, usize);
+    /// The tokens of a move and their common text, `None` for a free move
+    type Tokens<'a> = Option<(&'a str, &'a str, String)>;
+    /// How a pair of states was reached
+    type Step<'a> = (Pair, Tokens<'a>);
+
+    let mut matches = Matches::default();
+    // Fewest tokens to reach every pair, with the step it was reached by
+    let mut reached: HashMap<Pair, (usize, Option<Step>)> = HashMap::from([((0, 0), (0, None))]);
+    let mut settled = HashSet::new();
+    // Moves on nothing cost no token, so they go to the front
+    let mut queue = VecDeque::from([(0, 0)]);
+    let mut found = None;
+    while let Some((p, q)) = queue.pop_front() {
+        if !settled.insert((p, q)) {
+            continue;
+        }
+        if a.accepting[p] && b.accepting[q] {
+            found = Some((p, q));
+            break;
+        }
+        let mut moves: Vec<(Pair, Tokens)> = Vec::new();
+        for (token_a, next_p) in &a.edges[p] {
+            match token_a {
+                None => moves.push(((*next_p, q), None)),
+                Some(token_a) => {
+                    for (token_b, next_q) in &b.edges[q] {
+                        if let Some(token_b) = token_b
+                            && let Some(text) = matches.common(token_a, token_b)?
+                        {
+                            let tokens = (token_a.as_str(), token_b.as_str(), text);
+                            moves.push(((*next_p, *next_q), Some(tokens)));
+                        }
+                    }
+                }
+            }
+        }
+        for (token_b, next_q) in &b.edges[q] {
+            if token_b.is_none() {
+                moves.push(((p, *next_q), None));
+            }
+        }
+        let cost = reached[&(p, q)].0;
+        for (next, tokens) in moves {
+            let free = tokens.is_none();
+            let cost = cost + usize::from(!free);
+            if reached.get(&next).is_none_or(|(best, _)| cost < *best) {
+                reached.insert(next, (cost, Some(((p, q), tokens))));
+                if free {
+                    queue.push_front(next);
+                } else {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    let Some(mut pair) = found else {
+        return Ok(None);
+    };
+    let mut overlap = Overlap {
+        first: Vec::new(),
+        second: Vec::new(),
+        text: Vec::new(),
+    };
+    while let Some((_, Some((previous, tokens)))) = reached.get(&pair) {
+        if let Some((token_a, token_b, text)) = tokens {
+            overlap.first.push(token_a.to_string());
+            overlap.second.push(token_b.to_string());
+            overlap.text.push(text.clone());
+        }
+        pair = *previous;
+    }
+    overlap.first.reverse();
+    overlap.second.reverse();
+    overlap.text.reverse();
+    Ok(Some(overlap))
+}
+
+/// Common matches of pairs of tokens, computed once per pair
+#[derive(Default)]
+struct Matches(HashMap<(String, String), Option<String>>);
+
+impl Matches {
+    fn common(&mut self, a: &str, b: &str) -> Result<Option<String>, DisjointError> {
+        let key = (a.to_string(), b.to_string());
+        if let Some(text) = self.0.get(&key) {
+            return Ok(text.clone());
+        }
+        let text = if a == b && a.starts_with('"') {
+            Some(strip_terminal_quotes(a).to_string())
+        } else {
+            let (pattern_a, pattern_b) = (pattern(a), pattern(b));
+            do_regexs_intersect(&pattern_a, &pattern_b).map_err(|source| {
+                let pattern = match source {
+                    RegexError::InvalidRegexB(_) => b,
+                    _ => a,
+                };
+                DisjointError::InvalidRegex {
+                    pattern: pattern.to_string(),
+                    source,
+                }
+            })?
+        };
+        self.0.insert(key, text.clone());
+        Ok(text)
+    }
+}
+
+/// The regex pattern matching what a terminal or regex token matches
+fn pattern(token: &str) -> String {
+    if token.starts_with('"') {
+        regex_syntax::escape(strip_terminal_quotes(token))
+    } else {
+        strip_regex_delimiters(token).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_common_sentences() {
+        let source = "type := /[A-Z][a-z]*/ [ \"<\" sep_by(type, \",\") \">\" ].\n\
+                      var := /[a-z_][a-z0-9_]*/.\n\
+                      path := { /[a-zA-Z]+/ \"::\" } /[a-z]+/.\n\
+                      call := var \"(\" \")\" | var \"::\" var.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+
+        let check = check_disjoint(&sebnf, "type", "var", 4).unwrap();
+        assert!(check.is_disjoint() && !check.exact);
+        let check = check_disjoint(&sebnf, "var", "path", 4).unwrap();
+        assert!(check.exact);
+        assert_eq!(check.overlap.unwrap().second, ["/[a-z]+/"]);
+        let check = check_disjoint(&sebnf, "path", "call", 4).unwrap();
+        let overlap = check.overlap.unwrap();
+        assert_eq!(overlap.first, ["/[a-zA-Z]+/", "\"::\"", "/[a-z]+/"]);
+        assert_eq!(
+            overlap.second,
+            ["/[a-z_][a-z0-9_]*/", "\"::\"", "/[a-z_][a-z0-9_]*/"]
+        );
+        assert_eq!(overlap.text[1], "::");
+    }
+}
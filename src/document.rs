@@ -0,0 +1,262 @@
+//! An editable grammar document for editors and other embedding tools.
+//!
+//! Edits only mark the parse as stale; parsing and analysis run lazily when
+//! a result or the diagnostics are requested. If an edit does not change the
+//! grammar itself (whitespace, comments, reformatting), the previous
+//! FIRST/FOLLOW sets and LL(1) result are reused.
+
+use std::ops::Range;
+
+use miette::{Diagnostic, Severity};
+
+use crate::bnf::Bnf;
+use crate::sebnf::{ParseErrors, Sebnf};
+use crate::sets::{Ll1Error, Ll1Result, Sets};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, miette::Diagnostic)]
+pub enum EditError {
+    #[error("edit range {start}..{end} is outside the document (length {len})", start = range.start, end = range.end)]
+    #[diagnostic(code(sebnf::edit_out_of_bounds))]
+    OutOfBounds { range: Range<usize>, len: usize },
+    #[error("edit offset {0} is not on a character boundary")]
+    #[diagnostic(code(sebnf::edit_not_char_boundary))]
+    NotCharBoundary(usize),
+}
+
+/// A diagnostic detached from its source, for display in an editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentDiagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    pub help: Option<String>,
+    /// Byte range in the current source
+    pub span: Option<Range<usize>>,
+}
+
+impl DocumentDiagnostic {
+    fn from_diagnostic(diagnostic: &dyn Diagnostic, default_severity: Severity) -> Self {
+        let span = diagnostic
+            .labels()
+            .and_then(|mut labels| labels.next())
+            .map(|label| label.offset()..label.offset() + label.len());
+        Self {
+            severity: diagnostic.severity().unwrap_or(default_severity),
+            code: diagnostic.code().map(|c| c.to_string()),
+            message: diagnostic.to_string(),
+            help: diagnostic.help().map(|h| h.to_string()),
+            span,
+        }
+    }
+}
+
+struct Analysis {
+    /// Revision whose BNF was last compared against this analysis
+    revision: u64,
+    bnf: Bnf,
+    sets: Sets,
+    ll1: Result<Ll1Result, Ll1Error>,
+}
+
+/// Grammar source plus lazily computed, cached analyses of it.
+pub struct Document {
+    source: String,
+    source_name: String,
+    revision: u64,
+    ignore_regex_conflicts: bool,
+    /// `None` while stale
+    parsed: Option<Result<Sebnf, ParseErrors>>,
+    /// Kept across edits and reused while the BNF stays the same
+    analysis: Option<Analysis>,
+    analysis_runs: usize,
+}
+
+impl Document {
+    pub fn new(source: impl Into<String>, source_name: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            source_name: source_name.into(),
+            revision: 0,
+            ignore_regex_conflicts: false,
+            parsed: None,
+            analysis: None,
+            analysis_runs: 0,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Incremented on every edit, so callers can tell if cached results are current.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Replaces the bytes in `range` with `new_text`.
+    pub fn update_source(&mut self, range: Range<usize>, new_text: &str) -> Result<(), EditError> {
+        if range.start > range.end || range.end > self.source.len() {
+            return Err(EditError::OutOfBounds {
+                range,
+                len: self.source.len(),
+            });
+        }
+        for offset in [range.start, range.end] {
+            if !self.source.is_char_boundary(offset) {
+                return Err(EditError::NotCharBoundary(offset));
+            }
+        }
+        self.source.replace_range(range, new_text);
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Replaces the whole source.
+    pub fn set_source(&mut self, source: impl Into<String>) {
+        self.source = source.into();
+        self.invalidate();
+    }
+
+    pub fn set_ignore_regex_conflicts(&mut self, ignore: bool) {
+        if self.ignore_regex_conflicts != ignore {
+            self.ignore_regex_conflicts = ignore;
+            self.analysis = None;
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.revision += 1;
+        self.parsed = None;
+    }
+
+    pub fn sebnf(&mut self) -> Result<&Sebnf, &ParseErrors> {
+        self.parsed
+            .get_or_insert_with(|| Sebnf::parse_str(&self.source, &self.source_name))
+            .as_ref()
+    }
+
+    fn analyze(&mut self) -> Option<&Analysis> {
+        let revision = self.revision;
+        if self
+            .analysis
+            .as_ref()
+            .is_some_and(|a| a.revision == revision)
+        {
+            return self.analysis.as_ref();
+        }
+        let bnf = self.sebnf().ok()?.to_bnf();
+        let reusable = self.analysis.as_ref().is_some_and(|analysis| {
+            analysis.bnf.rules.len() == bnf.rules.len()
+                && analysis.bnf.rules.iter().eq(bnf.rules.iter())
+        });
+
+        match &mut self.analysis {
+            // Same grammar: keep the results, but take the new spans
+            Some(analysis) if reusable => {
+                analysis.bnf = bnf;
+                analysis.revision = revision;
+            }
+            _ => {
+                let sets = bnf.first_and_follow_sets();
+                let ll1 = bnf.is_ll1(self.ignore_regex_conflicts);
+                self.analysis_runs += 1;
+                self.analysis = Some(Analysis {
+                    revision,
+                    bnf,
+                    sets,
+                    ll1,
+                });
+            }
+        }
+        self.analysis.as_ref()
+    }
+
+    /// The grammar converted to BNF, or `None` if it does not parse.
+    pub fn bnf(&mut self) -> Option<&Bnf> {
+        self.analyze().map(|analysis| &analysis.bnf)
+    }
+
+    pub fn sets(&mut self) -> Option<&Sets> {
+        self.analyze().map(|analysis| &analysis.sets)
+    }
+
+    pub fn ll1(&mut self) -> Option<Result<&Ll1Result, &Ll1Error>> {
+        self.analyze().map(|analysis| analysis.ll1.as_ref())
+    }
+
+    /// Parse errors, grammar warnings and LL(1) conflicts of the current source.
+    pub fn diagnostics(&mut self) -> Vec<DocumentDiagnostic> {
+        let (source, source_name) = (self.source.clone(), self.source_name.clone());
+        let sebnf = match self.sebnf() {
+            Ok(sebnf) => sebnf,
+            Err(e) => {
+                return e
+                    .errors
+                    .iter()
+                    .map(|e| DocumentDiagnostic::from_diagnostic(e, Severity::Error))
+                    .collect();
+            }
+        };
+        let mut diagnostics: Vec<_> = sebnf
+            .warnings(&source, &source_name)
+            .iter()
+            .map(|w| DocumentDiagnostic::from_diagnostic(w, Severity::Warning))
+            .collect();
+
+        if let Some(analysis) = self.analyze() {
+            match &analysis.ll1 {
+                Ok(result) => {
+                    diagnostics.extend(result.conflicts.iter().map(|conflict| DocumentDiagnostic {
+                        severity: Severity::Error,
+                        code: Some("sebnf::ll1_conflict".to_string()),
+                        message: conflict.to_string().trim_end().to_string(),
+                        help: None,
+                        span: analysis.bnf.conflict_span(conflict),
+                    }))
+                }
+                Err(e) => diagnostics.push(DocumentDiagnostic::from_diagnostic(e, Severity::Error)),
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_reparse_lazily_and_reuse_analysis() {
+        let mut doc = Document::new("a := \"x\" | \"x\" \"y\".", "test");
+        assert_eq!(doc.diagnostics().len(), 1);
+        assert_eq!(doc.analysis_runs, 1);
+
+        // Only layout changes: the analysis is reused, spans move along
+        doc.update_source(100..101, "x").unwrap_err();
+        doc.update_source(0..0, "(* c *)\n").unwrap();
+        let diagnostics = doc.diagnostics();
+        assert_eq!(doc.analysis_runs, 1);
+        let span = diagnostics[0].span.clone().unwrap();
+        assert_eq!(&doc.source()[span], "\"x\" \"y\"");
+
+        // A real change is analyzed again
+        let len = doc.source().len();
+        doc.update_source(len - 8..len - 5, "\"y\"").unwrap();
+        assert!(doc.diagnostics().is_empty());
+        assert_eq!(doc.analysis_runs, 2);
+        assert_eq!(doc.revision(), 2);
+    }
+
+    #[test]
+    fn parse_errors_become_diagnostics() {
+        let mut doc = Document::new("a := \"x\"", "test");
+        let diagnostics = doc.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(
+            diagnostics[0].code.as_deref(),
+            Some("sebnf::unexpected_eof")
+        );
+        assert!(doc.bnf().is_none());
+    }
+}
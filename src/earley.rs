@@ -0,0 +1,659 @@
+//! Earley parsing, for any context-free grammar.
+//!
+//! [`earley_parse`] recognizes the input with an Earley chart in at most
+//! cubic time, whatever conflicts, left recursion or ambiguity the grammar
+//! has, and then reads the parse trees off the chart. An ambiguous input has
+//! several; they are listed up to [`EarleyOptions::max_parses`], and the
+//! shortest piece of input that a rule matches in more than one way is
+//! reported as an [`Ambiguity`]. Derivations that go around a cycle of rules
+//! without consuming input, as in `a := a | "x".`, are left out.
+//!
+//! The tokens and trees are those of [`crate::interpret`].
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::earley::{EarleyOptions, earley_parse};
+//!
+//! let sebnf = Sebnf::parse_str("e := e \"-\" e | /[0-9]+/.", "g").unwrap();
+//! let parse = earley_parse(&sebnf, "1 - 2 - 3", "input", &EarleyOptions::default()).unwrap();
+//! assert_eq!(parse.trees.len(), 2);
+//! assert_eq!(parse.ambiguity.unwrap().text, "1 - 2 - 3");
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use miette::{NamedSource, SourceSpan};
+
+use crate::Sebnf;
+use crate::bnf::Item;
+use crate::interpret::{InterpretError, ParseTree, Token, syntax_error, token_span, tokenize};
+use crate::sets::strip_terminal_quotes;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EarleyOptions {
+    /// How many parse trees to list at most
+    pub max_parses: usize,
+}
+
+impl Default for EarleyOptions {
+    fn default() -> Self {
+        EarleyOptions { max_parses: 100 }
+    }
+}
+
+/// The parse trees of an input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EarleyParse {
+    /// At least one, and at most [`EarleyOptions::max_parses`]
+    pub trees: Vec<ParseTree>,
+    /// Where the trees differ, if there are several
+    pub ambiguity: Option<Ambiguity>,
+}
+
+/// The shortest piece of the input that a rule matches in several ways.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, miette::Diagnostic)]
+#[error("'{rule}' matches {text:?} in {ways} ways")]
+#[diagnostic(
+    code(sebnf::ambiguous_input),
+    severity(Warning),
+    help("`parse --all` lists every parse")
+)]
+pub struct Ambiguity {
+    pub rule: String,
+    pub text: String,
+    /// Counted up to [`EarleyOptions::max_parses`]
+    pub ways: usize,
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("matched in {ways} ways")]
+    pub span: SourceSpan,
+}
+
+/// Parses all of `input`, named `input_name` in errors, as the start rule of
+/// `sebnf`.
+pub fn earley_parse(
+    sebnf: &Sebnf,
+    input: &str,
+    input_name: &str,
+    options: &EarleyOptions,
+) -> Result<EarleyParse, InterpretError> {
+    let bnf = sebnf.to_bnf();
+    let tokens = tokenize(&bnf, input, input_name)?;
+    let grammar = Grammar::new(sebnf, &bnf);
+    let chart = Chart::recognize(&grammar, &tokens);
+    if !chart.completed.contains(&(0, 0, tokens.len())) {
+        let (position, expected) = chart.failure(&grammar, tokens.len());
+        return Err(syntax_error(&tokens, position, expected, input, input_name));
+    }
+
+    let mut forest = Forest {
+        grammar: &grammar,
+        chart: &chart,
+        tokens: &tokens,
+        // Counting to two at least tells ambiguous inputs apart
+        max_ways: options.max_parses.max(2),
+        counts: HashMap::new(),
+        prefix_counts: HashMap::new(),
+        active: HashSet::new(),
+    };
+    // Counting the matches that end first keeps the recursion shallow
+    let mut matches: Vec<Match> = chart.completed.iter().copied().collect();
+    matches.sort_by_key(|&(rule, start, end)| (end, Reverse(start), rule));
+    for m in matches {
+        forest.count(m);
+    }
+    let root = (0, 0, tokens.len());
+    // Building a tree recurses once per nested match, and a long repetition
+    // nests as deep as it is long
+    let stack_size = (8 << 20) + (tokens.len() << 12);
+    let trees = std::thread::scope(|scope| {
+        let build = || forest.trees(root, options.max_parses.max(1));
+        (std::thread::Builder::new().stack_size(stack_size))
+            .spawn_scoped(scope, build)
+            .expect("the parse tree thread starts")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    });
+    let trees = trees.into_iter().flatten().collect();
+    let ambiguity = forest.ambiguity(root).map(|(rule, start, end)| {
+        let span = forest.span(start, end);
+        Ambiguity {
+            rule: grammar.rules[rule].to_string(),
+            text: input[span.clone()].to_string(),
+            ways: forest.count((rule, start, end)),
+            src: NamedSource::new(input_name, input.to_string()),
+            span: (span.start, span.len()).into(),
+        }
+    });
+    Ok(EarleyParse { trees, ambiguity })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symbol<'a> {
+    Rule(usize),
+    /// A terminal or regex as written
+    Token(&'a str),
+}
+
+/// The BNF with its rules numbered, the start rule first
+struct Grammar<'a> {
+    rules: Vec<&'a str>,
+    productions: Vec<Vec<Vec<Symbol<'a>>>>,
+    /// Whether a rule matches no tokens, which an empty terminal does too
+    nullable: Vec<bool>,
+    /// Whether a rule builds a node; helper rules add their children to the
+    /// node of the rule using them
+    nodes: Vec<bool>,
+}
+
+impl<'a> Grammar<'a> {
+    fn new(sebnf: &Sebnf, bnf: &'a crate::Bnf) -> Self {
+        let rules: Vec<&str> = bnf.rules.keys().map(String::as_str).collect();
+        let index: HashMap<&str, usize> = (rules.iter().enumerate())
+            .map(|(i, rule)| (*rule, i))
+            .collect();
+        let mut productions: Vec<Vec<Vec<Symbol>>> = (bnf.rules.values())
+            .map(|productions| {
+                (productions.iter())
+                    .map(|production| {
+                        (production.iter())
+                            .filter_map(|item| match item {
+                                Item::NonTerminal(rule) => Some(Symbol::Rule(index[rule.as_str()])),
+                                // An empty terminal matches no token
+                                Item::Terminal(symbol)
+                                    if strip_terminal_quotes(symbol).is_empty() =>
+                                {
+                                    None
+                                }
+                                Item::Terminal(symbol) | Item::Regex(symbol) => {
+                                    Some(Symbol::Token(symbol))
+                                }
+                                _ => None,
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        let nodes: Vec<bool> = (rules.iter())
+            .map(|rule| sebnf.rules.contains_key(*rule))
+            .collect();
+        // Earley parsing takes quadratic time on right recursion, so helper
+        // rules repeating `{ }` recur on the left instead; they still add
+        // their children to the parent in the same order
+        for (rule, productions) in productions.iter_mut().enumerate() {
+            let repeats = |production: &Vec<Symbol>| {
+                (production.iter().rev().skip(1)).all(|symbol| *symbol != Symbol::Rule(rule))
+                    && production.last() == Some(&Symbol::Rule(rule))
+            };
+            if !nodes[rule]
+                && productions.iter().any(Vec::is_empty)
+                && (productions.iter())
+                    .all(|production| production.is_empty() || repeats(production))
+            {
+                for production in productions.iter_mut().filter(|p| !p.is_empty()) {
+                    production.rotate_right(1);
+                }
+            }
+        }
+        let mut nullable = vec![false; rules.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (rule, productions) in productions.iter().enumerate() {
+                let matches_nothing = |production: &Vec<Symbol>| {
+                    (production.iter())
+                        .all(|symbol| matches!(symbol, Symbol::Rule(r) if nullable[*r]))
+                };
+                if !nullable[rule] && productions.iter().any(matches_nothing) {
+                    nullable[rule] = true;
+                    changed = true;
+                }
+            }
+        }
+        Grammar {
+            rules,
+            productions,
+            nullable,
+            nodes,
+        }
+    }
+}
+
+/// A production of `rule` matched up to `dot` from the token at `origin`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct State {
+    rule: usize,
+    production: usize,
+    dot: usize,
+    origin: usize,
+}
+
+struct Chart {
+    /// The states before each token and after the last one
+    sets: Vec<Vec<State>>,
+    /// Rules matched from a token to before another, as `(rule, start, end)`
+    completed: HashSet<(usize, usize, usize)>,
+    /// The states with the sets they are in
+    states: HashSet<(State, usize)>,
+    /// The sets each state is in
+    positions: HashMap<State, Vec<usize>>,
+    /// Where the matches of a rule before a token start
+    starts: HashMap<(usize, usize), Vec<usize>>,
+}
+
+impl Chart {
+    fn recognize(grammar: &Grammar, tokens: &[Token]) -> Self {
+        let mut sets: Vec<Vec<State>> = vec![Vec::new(); tokens.len() + 1];
+        let mut seen: Vec<HashSet<State>> = vec![HashSet::new(); tokens.len() + 1];
+        let mut add = |sets: &mut Vec<Vec<State>>, position: usize, state: State| {
+            if seen[position].insert(state) {
+                sets[position].push(state);
+            }
+        };
+        for production in 0..grammar.productions[0].len() {
+            let state = State {
+                rule: 0,
+                production,
+                dot: 0,
+                origin: 0,
+            };
+            add(&mut sets, 0, state);
+        }
+        for position in 0..=tokens.len() {
+            let mut i = 0;
+            while i < sets[position].len() {
+                let state = sets[position][i];
+                i += 1;
+                let symbols = &grammar.productions[state.rule][state.production];
+                let advanced = State {
+                    dot: state.dot + 1,
+                    ..state
+                };
+                match symbols.get(state.dot) {
+                    None => {
+                        // Complete: advance the states waiting for the rule
+                        for j in 0..sets[state.origin].len() {
+                            let waiting = sets[state.origin][j];
+                            let next = grammar.productions[waiting.rule][waiting.production]
+                                .get(waiting.dot);
+                            if next == Some(&Symbol::Rule(state.rule)) {
+                                let advanced = State {
+                                    dot: waiting.dot + 1,
+                                    ..waiting
+                                };
+                                add(&mut sets, position, advanced);
+                            }
+                        }
+                    }
+                    Some(&Symbol::Rule(rule)) => {
+                        for production in 0..grammar.productions[rule].len() {
+                            let predicted = State {
+                                rule,
+                                production,
+                                dot: 0,
+                                origin: position,
+                            };
+                            add(&mut sets, position, predicted);
+                        }
+                        // A rule matching nothing completes without a
+                        // state to advance this one later
+                        if grammar.nullable[rule] {
+                            add(&mut sets, position, advanced);
+                        }
+                    }
+                    Some(Symbol::Token(symbol)) => {
+                        if tokens
+                            .get(position)
+                            .is_some_and(|token| token.symbol == *symbol)
+                        {
+                            add(&mut sets, position + 1, advanced);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut completed = HashSet::new();
+        let mut states = HashSet::new();
+        let mut positions: HashMap<State, Vec<usize>> = HashMap::new();
+        let mut starts: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (end, set) in sets.iter().enumerate() {
+            for &state in set {
+                if state.dot == grammar.productions[state.rule][state.production].len()
+                    && completed.insert((state.rule, state.origin, end))
+                {
+                    starts
+                        .entry((state.rule, end))
+                        .or_default()
+                        .push(state.origin);
+                }
+                states.insert((state, end));
+                positions.entry(state).or_default().push(end);
+            }
+        }
+        Chart {
+            sets,
+            completed,
+            states,
+            positions,
+            starts,
+        }
+    }
+
+    /// The furthest token the parse got to, and what could have come there
+    fn failure(&self, grammar: &Grammar, len: usize) -> (usize, Vec<String>) {
+        let position = (0..=len)
+            .rev()
+            .find(|&i| !self.sets[i].is_empty())
+            .unwrap_or(0);
+        let mut expected: Vec<String> = Vec::new();
+        for state in &self.sets[position] {
+            let symbols = &grammar.productions[state.rule][state.production];
+            if let Some(Symbol::Token(symbol)) = symbols.get(state.dot)
+                && !expected.iter().any(|e| e == symbol)
+            {
+                expected.push(symbol.to_string());
+            }
+        }
+        if position < len && self.completed.contains(&(0, 0, position)) {
+            expected.push("end of input".to_string());
+        }
+        (position, expected)
+    }
+}
+
+/// A rule matched from a token to before another, as `(rule, start, end)`
+type Match = (usize, usize, usize);
+
+/// Reads the parse trees off a chart. Every match of a rule is counted first,
+/// up to `max_ways`, so that only the trees asked for are built. Productions
+/// are read from their end, following the states of the chart, so that only
+/// the matches the chart supports are tried.
+struct Forest<'a> {
+    grammar: &'a Grammar<'a>,
+    chart: &'a Chart,
+    tokens: &'a [Token],
+    max_ways: usize,
+    counts: HashMap<Match, usize>,
+    /// Ways the symbols of a production before a dot match, by the state
+    /// and the end
+    prefix_counts: HashMap<(State, usize), usize>,
+    /// The matches being counted or built, to skip cycles
+    active: HashSet<Match>,
+}
+
+impl Forest<'_> {
+    /// In how many ways, up to `max_ways`, `rule` matches from the token at
+    /// `start` to before the one at `end`
+    fn count(&mut self, (rule, start, end): Match) -> usize {
+        let key = (rule, start, end);
+        if let Some(&count) = self.counts.get(&key) {
+            return count;
+        }
+        if !self.chart.completed.contains(&key) || !self.active.insert(key) {
+            return 0;
+        }
+        let mut count = 0;
+        for production in 0..self.grammar.productions[rule].len() {
+            let dot = self.grammar.productions[rule][production].len();
+            let state = State {
+                rule,
+                production,
+                dot,
+                origin: start,
+            };
+            count += self.count_prefix(state, end);
+        }
+        let count = count.min(self.max_ways);
+        self.active.remove(&key);
+        self.counts.insert(key, count);
+        count
+    }
+
+    /// In how many ways the symbols before the dot of `state` match from its
+    /// origin to before the token at `end`
+    fn count_prefix(&mut self, state: State, end: usize) -> usize {
+        if state.dot == 0 {
+            return usize::from(state.origin == end);
+        }
+        if let Some(&count) = self.prefix_counts.get(&(state, end)) {
+            return count;
+        }
+        let mut count = 0;
+        for (last, before, mid) in self.splits(state, end) {
+            let lasts = match last {
+                Some(last) => self.count(last),
+                None => 1,
+            };
+            if lasts > 0 {
+                let befores = self.count_prefix(before, mid);
+                count += lasts.saturating_mul(befores).min(self.max_ways);
+            }
+        }
+        let count = count.min(self.max_ways);
+        self.prefix_counts.insert((state, end), count);
+        count
+    }
+
+    /// The ways to split the symbols before the dot of `state`, matched up to
+    /// `end`, into the last one and the ones before: the match of the last
+    /// one if it is a rule, the state before it, and where it starts
+    fn splits(&self, state: State, end: usize) -> Vec<(Option<Match>, State, usize)> {
+        if state.dot == 0 {
+            return Vec::new();
+        }
+        let before = State {
+            dot: state.dot - 1,
+            ..state
+        };
+        let symbol = self.grammar.productions[state.rule][state.production][state.dot - 1];
+        let (last, mids) = match symbol {
+            Symbol::Token(symbol) => {
+                let mid = end.checked_sub(1);
+                let mids = mid.filter(|&mid| self.tokens[mid].symbol == symbol);
+                (None, mids.into_iter().collect())
+            }
+            // Either list can be long: a repetition has the state before its
+            // last symbol in every set, and right recursion completes a rule
+            // from every set
+            Symbol::Rule(last) => {
+                let starts = self.chart.starts.get(&(last, end));
+                let positions = self.chart.positions.get(&before);
+                let (starts, positions) = (
+                    starts.map_or(&[][..], Vec::as_slice),
+                    positions.map_or(&[][..], Vec::as_slice),
+                );
+                let mids = if starts.len() <= positions.len() {
+                    starts.to_vec()
+                } else {
+                    (positions.iter().copied())
+                        .filter(|&mid| {
+                            mid <= end && self.chart.completed.contains(&(last, mid, end))
+                        })
+                        .collect()
+                };
+                (Some(last), mids)
+            }
+        };
+        (mids.into_iter())
+            .filter(|&mid| self.chart.states.contains(&(before, mid)))
+            .map(|mid| (last.map(|last| (last, mid, end)), before, mid))
+            .collect()
+    }
+
+    /// Whether the symbols before the dot of `state` match from its origin to
+    /// before the token at `end`
+    fn matches_prefix(&mut self, state: State, end: usize) -> bool {
+        self.count_prefix(state, end) > 0
+    }
+
+    /// Up to `need` ways `rule` matches from the token at `start` to before
+    /// the one at `end`, each as what it adds to its parent: a node, or the
+    /// children of a helper rule
+    fn trees(&mut self, (rule, start, end): Match, need: usize) -> Vec<Vec<ParseTree>> {
+        if self.count((rule, start, end)) == 0 || !self.active.insert((rule, start, end)) {
+            return Vec::new();
+        }
+        let mut derivations = Vec::new();
+        for production in 0..self.grammar.productions[rule].len() {
+            let state = State {
+                rule,
+                production,
+                dot: self.grammar.productions[rule][production].len(),
+                origin: start,
+            };
+            if derivations.len() < need && self.matches_prefix(state, end) {
+                let more = self.prefix_trees(state, end, need - derivations.len());
+                derivations.extend(more);
+            }
+        }
+        self.active.remove(&(rule, start, end));
+        if !self.grammar.nodes[rule] {
+            return derivations;
+        }
+        let span = self.span(start, end);
+        (derivations.into_iter())
+            .map(|children| {
+                vec![ParseTree::Node {
+                    rule: self.grammar.rules[rule].to_string(),
+                    children,
+                    span: span.clone(),
+                }]
+            })
+            .collect()
+    }
+
+    /// Up to `need` ways the symbols before the dot of `state` match from its
+    /// origin to before the token at `end`
+    fn prefix_trees(&mut self, state: State, end: usize, need: usize) -> Vec<Vec<ParseTree>> {
+        if state.dot == 0 {
+            return vec![Vec::new()];
+        }
+        let mut matches = Vec::new();
+        for (last, before, mid) in self.splits(state, end) {
+            let left = need - matches.len();
+            if left == 0 {
+                break;
+            }
+            if !self.matches_prefix(before, mid) {
+                continue;
+            }
+            let mut lasts = match last {
+                Some(last) => self.trees(last, left),
+                None => vec![vec![ParseTree::Token(self.tokens[mid].clone())]],
+            };
+            if lasts.is_empty() {
+                continue;
+            }
+            let befores = self.prefix_trees(before, mid, left.div_ceil(lasts.len()));
+            let ways = befores.len() * lasts.len();
+            for (i, mut children) in befores.into_iter().enumerate() {
+                for j in 0..lasts.len() {
+                    if matches.len() == need {
+                        break;
+                    }
+                    // Repetitions recur on the left, so the trees are moved
+                    // rather than copied where they are used the last time
+                    let mut way = if j + 1 == lasts.len() {
+                        std::mem::take(&mut children)
+                    } else {
+                        children.clone()
+                    };
+                    let last = if i * lasts.len() + j + 1 == ways {
+                        std::mem::take(&mut lasts[j])
+                    } else {
+                        lasts[j].clone()
+                    };
+                    if way.is_empty() {
+                        way = last;
+                    } else {
+                        way.extend(last);
+                    }
+                    matches.push(way);
+                }
+            }
+        }
+        matches
+    }
+
+    /// The shortest match of a rule with a node in several ways, among those
+    /// in the trees of `root`
+    fn ambiguity(&mut self, root: Match) -> Option<Match> {
+        let mut visited = HashSet::new();
+        let mut prefixes = HashSet::new();
+        let mut stack = vec![root];
+        let mut shortest: Option<Match> = None;
+        while let Some((rule, start, end)) = stack.pop() {
+            if !visited.insert((rule, start, end)) {
+                continue;
+            }
+            if self.grammar.nodes[rule]
+                && self.count((rule, start, end)) > 1
+                && shortest.is_none_or(|(r, s, e)| (end - start, start, rule) < (e - s, s, r))
+            {
+                shortest = Some((rule, start, end));
+            }
+            // The matches of the rules in each way the productions match
+            let mut pending: Vec<(State, usize)> = (0..self.grammar.productions[rule].len())
+                .map(|production| {
+                    let dot = self.grammar.productions[rule][production].len();
+                    let state = State {
+                        rule,
+                        production,
+                        dot,
+                        origin: start,
+                    };
+                    (state, end)
+                })
+                .collect();
+            while let Some((state, end)) = pending.pop() {
+                if !prefixes.insert((state, end)) || !self.matches_prefix(state, end) {
+                    continue;
+                }
+                for (last, before, mid) in self.splits(state, end) {
+                    if last.is_none_or(|last| self.count(last) > 0)
+                        && self.matches_prefix(before, mid)
+                    {
+                        stack.extend(last);
+                        pending.push((before, mid));
+                    }
+                }
+            }
+        }
+        shortest
+    }
+
+    /// From the token at `start` to before the one at `end`, in bytes
+    fn span(&self, start: usize, end: usize) -> std::ops::Range<usize> {
+        token_span(self.tokens, start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_every_parse_of_an_ambiguous_input() {
+        let source = "s := e [ \";\" ].\ne := e \"-\" e | n.\nn := /[0-9]+/.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let parse =
+            earley_parse(&sebnf, "1 - 2 - 3 - 4", "input", &EarleyOptions::default()).unwrap();
+        // The Catalan number of ways to group four operands
+        assert_eq!(parse.trees.len(), 5);
+        let ambiguity = parse.ambiguity.unwrap();
+        assert_eq!(
+            (ambiguity.rule.as_str(), ambiguity.text.as_str()),
+            ("e", "1 - 2 - 3")
+        );
+
+        let single = earley_parse(&sebnf, "1 - 2;", "input", &EarleyOptions::default()).unwrap();
+        assert_eq!(single.trees.len(), 1);
+        assert!(single.ambiguity.is_none());
+
+        let error = earley_parse(&sebnf, "1 - ;", "input", &EarleyOptions::default()).unwrap_err();
+        assert_eq!(error.to_string(), "unexpected \";\", expected /[0-9]+/");
+    }
+}
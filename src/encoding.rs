@@ -0,0 +1,212 @@
+//! Grammar files in encodings other than UTF-8.
+//!
+//! Everything else works on UTF-8 text, so a file is transcoded once when
+//! it is loaded. [`Decoded`] remembers where each character came from, to
+//! map spans back to byte offsets in the file, and writes edited text back
+//! in the file's encoding.
+//!
+//! ```
+//! use sebnf_tool::encoding::{Encoding, decode};
+//!
+//! let decoded = decode(b"a := \"\xe9t\xe9\".", Some(Encoding::Latin1)).unwrap();
+//! assert_eq!(decoded.text, "a := \"été\".");
+//! // "t" is the 8th byte of the file, but the 9th of the UTF-8 text
+//! assert_eq!(decoded.original_offset(8), 7);
+//! ```
+
+use std::fmt;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, miette::Diagnostic)]
+pub enum EncodingError {
+    #[error("the input is not valid {encoding}: invalid byte at offset {offset}")]
+    #[diagnostic(code(sebnf::invalid_encoding))]
+    Invalid { encoding: Encoding, offset: usize },
+    #[error("the input is not valid {encoding}: it ends in the middle of a character")]
+    #[diagnostic(code(sebnf::invalid_encoding))]
+    Truncated { encoding: Encoding },
+}
+
+/// Encodings grammar files are read in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO 8859-1: every byte is the code point of the same value
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A file transcoded to UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+    /// Byte offset in the file of each character of `text`, in order, and
+    /// of its end; empty if the offsets are the same but for the BOM
+    offsets: Vec<(usize, usize)>,
+}
+
+impl Decoded {
+    /// The byte offset in the file of the byte offset `offset` of `text`.
+    /// An offset within a character maps to that character's start.
+    pub fn original_offset(&self, offset: usize) -> usize {
+        let bom = if self.bom { self.bom_bytes().len() } else { 0 };
+        if self.offsets.is_empty() {
+            return bom + offset;
+        }
+        let index = match self
+            .offsets
+            .binary_search_by_key(&offset, |&(text, _)| text)
+        {
+            Ok(index) => index,
+            Err(index) => index.saturating_sub(1),
+        };
+        bom + self.offsets.get(index).map_or(0, |&(_, original)| original)
+    }
+
+    /// Encodes `text` like the file, e.g. to write back an edited source.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        if self.bom {
+            bytes.extend_from_slice(self.bom_bytes());
+        }
+        match self.encoding {
+            Encoding::Utf8 => bytes.extend_from_slice(text.as_bytes()),
+            // Characters beyond Latin-1 cannot be written; `?` stands in
+            Encoding::Latin1 => bytes.extend(text.chars().map(|c| u8::try_from(c).unwrap_or(b'?'))),
+            Encoding::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Encoding::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+        }
+        bytes
+    }
+
+    fn bom_bytes(&self) -> &'static [u8] {
+        match self.encoding {
+            Encoding::Utf8 => UTF8_BOM,
+            Encoding::Latin1 => &[],
+            Encoding::Utf16Le => UTF16LE_BOM,
+            Encoding::Utf16Be => UTF16BE_BOM,
+        }
+    }
+}
+
+/// Transcodes a file to UTF-8.
+///
+/// A byte order mark decides the encoding unless the file is read as
+/// Latin-1, where those bytes are ordinary characters. Without either, the
+/// file is read as UTF-8.
+pub fn decode(bytes: &[u8], encoding: Option<Encoding>) -> Result<Decoded, EncodingError> {
+    let boms = [
+        (UTF8_BOM, Encoding::Utf8),
+        (UTF16LE_BOM, Encoding::Utf16Le),
+        (UTF16BE_BOM, Encoding::Utf16Be),
+    ];
+    let bom = boms
+        .into_iter()
+        .filter(|_| encoding != Some(Encoding::Latin1))
+        .find(|(bom, _)| bytes.starts_with(bom));
+    let (encoding, body) = match bom {
+        Some((bom, encoding)) => (encoding, &bytes[bom.len()..]),
+        None => (encoding.unwrap_or_default(), bytes),
+    };
+
+    let mut text = String::with_capacity(body.len());
+    let mut offsets = Vec::new();
+    match encoding {
+        Encoding::Utf8 => match std::str::from_utf8(body) {
+            Ok(body) => text.push_str(body),
+            Err(e) if e.error_len().is_none() => return Err(EncodingError::Truncated { encoding }),
+            Err(e) => {
+                return Err(EncodingError::Invalid {
+                    encoding,
+                    offset: bytes.len() - body.len() + e.valid_up_to(),
+                });
+            }
+        },
+        Encoding::Latin1 => {
+            for (offset, &byte) in body.iter().enumerate() {
+                offsets.push((text.len(), offset));
+                text.push(char::from(byte));
+            }
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            if body.len() % 2 != 0 {
+                return Err(EncodingError::Truncated { encoding });
+            }
+            let units = body.chunks_exact(2).map(|pair| {
+                let pair = [pair[0], pair[1]];
+                match encoding {
+                    Encoding::Utf16Le => u16::from_le_bytes(pair),
+                    _ => u16::from_be_bytes(pair),
+                }
+            });
+            let mut offset = 0;
+            for c in char::decode_utf16(units) {
+                let Ok(c) = c else {
+                    let offset = bytes.len() - body.len() + offset;
+                    return Err(EncodingError::Invalid { encoding, offset });
+                };
+                offsets.push((text.len(), offset));
+                text.push(c);
+                offset += 2 * c.len_utf16();
+            }
+        }
+    }
+    if !offsets.is_empty() {
+        offsets.push((text.len(), body.len()));
+    }
+    Ok(Decoded {
+        text,
+        encoding,
+        bom: bom.is_some(),
+        offsets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_round_trips_and_maps_offsets() {
+        let source = "a := \"ä\" b.";
+        let mut bytes = UTF16BE_BOM.to_vec();
+        bytes.extend(source.encode_utf16().flat_map(u16::to_be_bytes));
+
+        let decoded = decode(&bytes, None).unwrap();
+        assert_eq!(decoded.text, source);
+        assert_eq!(decoded.encoding, Encoding::Utf16Be);
+        // `b` follows 9 characters, after the 2-byte BOM
+        let b = source.find('b').unwrap();
+        assert_eq!(decoded.original_offset(b), 2 + 9 * 2);
+        assert_eq!(decoded.encode(&decoded.text), bytes);
+
+        let error = decode(b"a := \"\xe9\".", None).unwrap_err();
+        assert_eq!(
+            error,
+            EncodingError::Invalid {
+                encoding: Encoding::Utf8,
+                offset: 6
+            }
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Bounded equivalence of two grammars.
+//!
+//! Whether two grammars accept the same sentences is undecidable in
+//! general. [`equivalence`] compares the sentences of both up to a number
+//! of tokens instead, which catches a refactoring that changed the language
+//! as long as the change shows in short sentences, and reports a shortest
+//! sentence only one of them accepts. Sentences are compared token by
+//! token: terminals by their spelling, regexes and token classes as written.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::equiv::{Equivalence, equivalence};
+//!
+//! let old = Sebnf::parse_str("list := item { \",\" item }.\nitem := \"x\".", "old").unwrap().to_bnf();
+//! let new = Sebnf::parse_str("list := { item \",\" } item.\nitem := \"x\".", "new").unwrap().to_bnf();
+//! assert!(matches!(equivalence(&old, &new, 5, 1000), Equivalence::Equivalent { sentences: 3, .. }));
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::bnf::Bnf;
+use crate::finite::derive_sentences;
+use crate::symbols::Symbols;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Equivalence {
+    /// Both accept the same sentences up to the bound
+    Equivalent { sentences: usize, max_tokens: usize },
+    Different {
+        /// A shortest sentence only one grammar accepts, as its tokens
+        sentence: Vec<String>,
+        /// Whether the first grammar is the one accepting it
+        in_first: bool,
+    },
+    /// One grammar has more sentences up to the bound than the limit
+    Inconclusive { max_tokens: usize, limit: usize },
+}
+
+impl fmt::Display for Equivalence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Equivalence::Equivalent {
+                sentences,
+                max_tokens,
+            } => writeln!(
+                f,
+                "The grammars accept the same {} sentence(s) of up to {} tokens.",
+                sentences, max_tokens
+            ),
+            Equivalence::Different { sentence, in_first } => {
+                let (accepting, other) = match in_first {
+                    true => ("first", "second"),
+                    false => ("second", "first"),
+                };
+                let text = match sentence.is_empty() {
+                    true => Symbols::with_current(|s| s.epsilon.clone()),
+                    false => sentence.join(" "),
+                };
+                writeln!(
+                    f,
+                    "The grammars differ: the {} accepts, but the {} rejects\n  {}",
+                    accepting, other, text
+                )
+            }
+            Equivalence::Inconclusive { max_tokens, limit } => writeln!(
+                f,
+                "Inconclusive: more than {} sentences of up to {} tokens; \
+                 lower the number of tokens or raise the limit.",
+                limit, max_tokens
+            ),
+        }
+    }
+}
+
+/// Compares the sentences of `first` and `second` of up to `max_tokens`
+/// tokens, giving up if either has more than `limit` of them.
+pub fn equivalence(first: &Bnf, second: &Bnf, max_tokens: usize, limit: usize) -> Equivalence {
+    let sentences = |bnf: &Bnf| match bnf.rules.keys().next() {
+        Some(start) => derive_sentences(bnf, start, |_| true, max_tokens, usize::MAX, limit),
+        None => (Vec::new(), true),
+    };
+    let ((first, first_complete), (second, second_complete)) =
+        (sentences(first), sentences(second));
+    if !first_complete || !second_complete {
+        return Equivalence::Inconclusive { max_tokens, limit };
+    }
+
+    let (first, second): (BTreeSet<_>, BTreeSet<_>) =
+        (first.into_iter().collect(), second.into_iter().collect());
+    let only_first = first.difference(&second).map(|sentence| (sentence, true));
+    let only_second = second.difference(&first).map(|sentence| (sentence, false));
+    let shortest = (only_first.chain(only_second)).min_by_key(|(sentence, _)| sentence.len());
+    match shortest {
+        Some((sentence, in_first)) => Equivalence::Different {
+            sentence: sentence.clone(),
+            in_first,
+        },
+        None => Equivalence::Equivalent {
+            sentences: first.len(),
+            max_tokens,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn reports_a_shortest_distinguishing_sentence() {
+        let old = Sebnf::parse_str("s := \"a\" { \"b\" } \"c\".", "old")
+            .unwrap()
+            .to_bnf();
+        let new = Sebnf::parse_str("s := \"a\" \"b\" { \"b\" } \"c\".", "new")
+            .unwrap()
+            .to_bnf();
+        let result = equivalence(&old, &new, 6, 1000);
+        assert_eq!(
+            result.to_string(),
+            "The grammars differ: the first accepts, but the second rejects\n  a c\n"
+        );
+        assert_eq!(
+            equivalence(&old, &old, 6, 2),
+            Equivalence::Inconclusive {
+                max_tokens: 6,
+                limit: 2
+            }
+        );
+    }
+}
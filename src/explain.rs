@@ -0,0 +1,153 @@
+//! Everything the analyses know about one rule, in one place.
+//!
+//! [`explain_symbol`] gathers a rule's productions, its FIRST and FOLLOW
+//! sets, whether it is nullable and reachable, the rules that refer to it and
+//! the LL(1) conflicts it takes part in, which otherwise means reading the
+//! output of `to-bnf`, `extract-sets` and `is-ll1` side by side.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::explain::explain_symbol;
+//!
+//! let bnf = Sebnf::parse_str("s := a \"x\".\na := [ \"y\" ].", "g").unwrap().to_bnf();
+//! let report = explain_symbol(&bnf, "a", false).unwrap();
+//! assert!(report.nullable);
+//! assert_eq!(report.users, ["s"]);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::render::{RenderError, render_display};
+use crate::sets::{Ll1Conflict, Ll1Error, SetItem, format_production, sorted};
+
+/// What the analyses say about one rule of a BNF.
+#[derive(Debug, Clone)]
+pub struct SymbolReport {
+    pub symbol: String,
+    pub productions: Vec<Vec<Item>>,
+    /// In [`SetItem`] order
+    pub first: Vec<SetItem>,
+    /// In [`SetItem`] order
+    pub follow: Vec<SetItem>,
+    pub nullable: bool,
+    /// Whether the start rule reaches it
+    pub reachable: bool,
+    /// Rules referring to it, in grammar order
+    pub users: Vec<String>,
+    /// The conflicts between its own productions
+    pub conflicts: Vec<Ll1Conflict>,
+    /// Other rules with a conflict between productions that refer to it
+    pub conflicts_elsewhere: Vec<String>,
+}
+
+impl SymbolReport {
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
+}
+
+impl fmt::Display for SymbolReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        let set = |items: &[SetItem]| {
+            let items: Vec<_> = items.iter().map(|item| item.to_string()).collect();
+            format!("{{ {} }}", items.join(", "))
+        };
+        writeln!(f, "Productions:")?;
+        for production in &self.productions {
+            writeln!(f, "   {} := {}", self.symbol, format_production(production))?;
+        }
+        writeln!(f, "FIRST:     {}", set(&self.first))?;
+        writeln!(f, "FOLLOW:    {}", set(&self.follow))?;
+        writeln!(f, "Nullable:  {}", yes_no(self.nullable))?;
+        writeln!(f, "Reachable: {}", yes_no(self.reachable))?;
+        match self.users.is_empty() {
+            true => writeln!(f, "Used by:   no rule")?,
+            false => writeln!(f, "Used by:   {}", self.users.join(", "))?,
+        }
+        if !self.conflicts_elsewhere.is_empty() {
+            writeln!(
+                f,
+                "Used in conflicts of: {}",
+                self.conflicts_elsewhere.join(", ")
+            )?;
+        }
+        match self.conflicts.len() {
+            0 => writeln!(f, "Conflicts: none")?,
+            n => {
+                writeln!(f, "Conflicts: {}", n)?;
+                for (i, conflict) in self.conflicts.iter().enumerate() {
+                    writeln!(f, "\n{}. {}", i + 1, conflict)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gathers what the analyses of `bnf` say about the rule `symbol`, which
+/// must be a rule of `bnf`.
+pub fn explain_symbol(
+    bnf: &Bnf,
+    symbol: &str,
+    ignore_regex_conflicts: bool,
+) -> Result<SymbolReport, Ll1Error> {
+    let sets = bnf.first_and_follow_sets();
+    let set = |sets: &HashMap<String, _>| -> Vec<SetItem> {
+        sets.get(symbol)
+            .map(|set| sorted(set).into_iter().cloned().collect())
+            .unwrap_or_default()
+    };
+    let refers = |production: &[Item]| {
+        (production.iter()).any(|item| matches!(item, Item::NonTerminal(nt) if nt == symbol))
+    };
+    let users = (bnf.rules.iter())
+        .filter(|(_, productions)| productions.iter().any(|p| refers(p)))
+        .map(|(rule, _)| rule.clone())
+        .collect();
+
+    let mut conflicts = Vec::new();
+    let mut conflicts_elsewhere: Vec<String> = Vec::new();
+    for conflict in bnf.is_ll1(ignore_regex_conflicts)?.conflicts {
+        let (production1, production2) = conflict.kind.productions();
+        if conflict.non_terminal == symbol {
+            conflicts.push(conflict);
+        } else if (refers(production1) || refers(production2))
+            && !conflicts_elsewhere.contains(&conflict.non_terminal)
+        {
+            conflicts_elsewhere.push(conflict.non_terminal);
+        }
+    }
+
+    let first = set(&sets.first);
+    Ok(SymbolReport {
+        symbol: symbol.to_string(),
+        productions: bnf.rules.get(symbol).cloned().unwrap_or_default(),
+        nullable: first.contains(&SetItem::Epsilon),
+        first,
+        follow: set(&sets.follow),
+        reachable: bnf.reachable_rules().contains(symbol),
+        users,
+        conflicts,
+        conflicts_elsewhere,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn lists_conflicts_of_the_rule_and_of_its_users() {
+        let source = "s := a \"x\" | \"y\" \"z\".\na := [ \"y\" ].\nunused := a.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let report = explain_symbol(&bnf, "a", false).unwrap();
+        assert_eq!(report.users, ["s", "unused"]);
+        assert_eq!(report.conflicts_elsewhere, ["s"]);
+        assert!(report.conflicts.is_empty());
+        assert!(!explain_symbol(&bnf, "unused", false).unwrap().reachable);
+    }
+}
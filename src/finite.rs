@@ -0,0 +1,362 @@
+//! Whether a grammar describes finitely many sentences.
+//!
+//! The language is infinite exactly when a rule the start rule reaches can
+//! derive itself with some tokens next to it, `A ⇒+ α A β` with `α β` not
+//! empty; rules that derive no string of terminals are left out, since they
+//! add no sentences. Sentences are counted in tokens: a regex or token class
+//! is one token however many strings it matches, so a grammar describing a
+//! fixed set of commands with number arguments is still finite.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::finite::{LanguageSize, language_size};
+//!
+//! let bnf = Sebnf::parse_str("cmd := \"go\" ( \"north\" | \"south\" ) | \"quit\".", "g")
+//!     .unwrap()
+//!     .to_bnf();
+//! let LanguageSize::Finite { sentences, complete } = language_size(&bnf, 100) else {
+//!     panic!("the language is finite");
+//! };
+//! assert!(complete);
+//! assert_eq!(sentences, ["quit", "go north", "go south"]);
+//! ```
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::left_recursion::LeftStep;
+use crate::sets::{format_production, strip_terminal_quotes};
+use crate::symbols::Symbols;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LanguageSize {
+    /// The start rule derives no string of terminals
+    Empty { start: String },
+    Finite {
+        /// Shortest first, each as its tokens joined by a space; terminals
+        /// without their quotes, regexes and token classes as written
+        sentences: Vec<String>,
+        /// False if a rule had more sentences than the limit, and only
+        /// those found until then are listed
+        complete: bool,
+    },
+    /// Rules that each derive the next, the first again with tokens around
+    /// it
+    Infinite { cycle: Vec<LeftStep> },
+}
+
+impl LanguageSize {
+    pub fn is_finite(&self) -> bool {
+        !matches!(self, LanguageSize::Infinite { .. })
+    }
+}
+
+impl fmt::Display for LanguageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LanguageSize::Empty { start } => writeln!(
+                f,
+                "The language is empty: '{}' derives no string of terminals.",
+                start
+            ),
+            LanguageSize::Finite {
+                sentences,
+                complete: true,
+            } => writeln!(
+                f,
+                "The language is finite: {} sentence(s).",
+                sentences.len()
+            ),
+            LanguageSize::Finite { .. } => writeln!(
+                f,
+                "The language is finite, but has more sentences than the limit."
+            ),
+            LanguageSize::Infinite { cycle } => {
+                let arrow = Symbols::with_current(|s| s.glyph("→", "->"));
+                let mut rules: Vec<&str> = cycle.iter().map(|step| step.rule.as_str()).collect();
+                rules.extend(cycle.first().map(|step| step.rule.as_str()));
+                writeln!(
+                    f,
+                    "The language is infinite: {} repeats without bound.",
+                    rules.join(&format!(" {} ", arrow))
+                )?;
+                for step in cycle {
+                    writeln!(
+                        f,
+                        "   {} := {}",
+                        step.rule,
+                        format_production(&step.production)
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Decides whether the language of `bnf` is finite, and if it is, lists up
+/// to `limit` of its sentences.
+pub fn language_size(bnf: &Bnf, limit: usize) -> LanguageSize {
+    let Some(start) = bnf.rules.keys().next() else {
+        return LanguageSize::Empty {
+            start: String::new(),
+        };
+    };
+    let productive = bnf.productive_rules();
+    if !productive.contains(start) {
+        return LanguageSize::Empty {
+            start: start.clone(),
+        };
+    }
+    let usable = |production: &Vec<Item>| {
+        (production.iter())
+            .all(|item| !matches!(item, Item::NonTerminal(nt) if !productive.contains(nt)))
+    };
+    // Rules reached from the start rule through productions that derive
+    // strings of terminals
+    let mut reachable: HashSet<&str> = HashSet::new();
+    let mut stack = vec![start.as_str()];
+    while let Some(rule) = stack.pop() {
+        if !reachable.insert(rule) {
+            continue;
+        }
+        for production in bnf.rules[rule].iter().filter(|p| usable(p)) {
+            for item in production {
+                if let Item::NonTerminal(nt) = item {
+                    stack.push(nt);
+                }
+            }
+        }
+    }
+
+    // Rules that derive at least one token
+    let mut solid: HashSet<&str> = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (rule, productions) in &bnf.rules {
+            let derives_token = (productions.iter().filter(|p| usable(p)))
+                .flatten()
+                .any(|item| {
+                    is_token(item)
+                        || matches!(item, Item::NonTerminal(nt) if solid.contains(nt.as_str()))
+                });
+            if derives_token && solid.insert(rule) {
+                changed = true;
+            }
+        }
+    }
+    let is_solid = |item: &Item| {
+        is_token(item) || matches!(item, Item::NonTerminal(nt) if solid.contains(nt.as_str()))
+    };
+
+    for (rule, productions) in bnf
+        .rules
+        .iter()
+        .filter(|(rule, _)| reachable.contains(rule.as_str()))
+    {
+        for production in productions.iter().filter(|p| usable(p)) {
+            for (i, item) in production.iter().enumerate() {
+                let Item::NonTerminal(next) = item else {
+                    continue;
+                };
+                let grows =
+                    (production.iter().enumerate()).any(|(j, other)| j != i && is_solid(other));
+                if !grows {
+                    continue;
+                }
+                if let Some(mut path) = path_between(bnf, &usable, next, rule) {
+                    let mut cycle = vec![LeftStep {
+                        rule: rule.clone(),
+                        production: production.clone(),
+                        next: next.clone(),
+                    }];
+                    cycle.append(&mut path);
+                    return LanguageSize::Infinite { cycle };
+                }
+            }
+        }
+    }
+
+    let include = |rule: &str| reachable.contains(rule);
+    let (sentences, complete) =
+        derive_sentences(bnf, start, include, usize::MAX, usize::MAX, limit);
+    let sentences = (sentences.into_iter())
+        .map(|sentence| sentence.join(" "))
+        .collect();
+    LanguageSize::Finite {
+        sentences,
+        complete,
+    }
+}
+
+/// Whether `item` stands for one token of a sentence
+fn is_token(item: &Item) -> bool {
+    matches!(
+        item,
+        Item::Terminal(_) | Item::Regex(_) | Item::TokenClass(_)
+    )
+}
+
+/// A shortest way from rule `from` to rule `to`, empty if they are the same
+fn path_between(
+    bnf: &Bnf,
+    usable: &impl Fn(&Vec<Item>) -> bool,
+    from: &str,
+    to: &str,
+) -> Option<Vec<LeftStep>> {
+    let mut previous: HashMap<&str, (&str, &Vec<Item>)> = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    let mut seen = HashSet::from([from]);
+    while let Some(rule) = queue.pop_front() {
+        if rule == to {
+            let mut path = Vec::new();
+            let mut rule = rule;
+            while let Some(&(before, production)) = previous.get(rule) {
+                path.push(LeftStep {
+                    rule: before.to_string(),
+                    production: production.clone(),
+                    next: rule.to_string(),
+                });
+                rule = before;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for production in bnf
+            .rules
+            .get(rule)
+            .into_iter()
+            .flatten()
+            .filter(|p| usable(p))
+        {
+            for item in production {
+                if let Item::NonTerminal(next) = item
+                    && seen.insert(next)
+                {
+                    previous.insert(next, (rule, production));
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The sentences `start` derives with at most `max_tokens` tokens and a
+/// derivation tree at most `max_depth` rules deep, shortest first and then
+/// in token order, and whether there were no more than `limit` of them.
+/// Round `d` forms the sentences of each production from those the rules
+/// had after round `d - 1`, until nothing changes or `max_depth` rounds are
+/// done, so only rules `include` accepts are expanded. Once a rule has more
+/// than `limit`, the search stops with the sentences of the last full round.
+pub(crate) fn derive_sentences(
+    bnf: &Bnf,
+    start: &str,
+    include: impl Fn(&str) -> bool,
+    max_tokens: usize,
+    max_depth: usize,
+    limit: usize,
+) -> (Vec<Vec<String>>, bool) {
+    let mut sentences: HashMap<&str, BTreeSet<Vec<String>>> = HashMap::new();
+    let mut complete = true;
+    let mut changed = true;
+    let mut depth = 0;
+    'rounds: while changed && depth < max_depth {
+        changed = false;
+        depth += 1;
+        let mut round = HashMap::new();
+        for (rule, productions) in bnf.rules.iter().filter(|(rule, _)| include(rule)) {
+            let mut derived: BTreeSet<Vec<String>> = BTreeSet::new();
+            for production in productions {
+                let mut prefixes: BTreeSet<Vec<String>> = BTreeSet::from([Vec::new()]);
+                for item in production {
+                    let token = match item {
+                        Item::Terminal(terminal) => {
+                            vec![strip_terminal_quotes(terminal).to_string()]
+                        }
+                        Item::Regex(token) | Item::TokenClass(token) => vec![token.clone()],
+                        Item::NonTerminal(_) | Item::EndOfInput => Vec::new(),
+                    };
+                    let mut suffixes: Vec<&[String]> = match item {
+                        Item::NonTerminal(nt) => (sentences.get(nt.as_str()).into_iter().flatten())
+                            .map(Vec::as_slice)
+                            .collect(),
+                        _ => vec![token.as_slice()],
+                    };
+                    suffixes.sort_by_key(|suffix| suffix.len());
+                    let mut next = BTreeSet::new();
+                    for prefix in &prefixes {
+                        let room = max_tokens.saturating_sub(prefix.len());
+                        for suffix in suffixes.iter().take_while(|suffix| suffix.len() <= room) {
+                            if next.len() == limit {
+                                complete = false;
+                                break 'rounds;
+                            }
+                            next.insert([prefix.as_slice(), suffix].concat());
+                        }
+                    }
+                    prefixes = next;
+                }
+                derived.extend(prefixes);
+            }
+            if derived.len() > limit {
+                complete = false;
+                break 'rounds;
+            }
+            changed |= sentences
+                .get(rule.as_str())
+                .map_or(!derived.is_empty(), |known| *known != derived);
+            round.insert(rule.as_str(), derived);
+        }
+        sentences = round;
+    }
+
+    let mut sentences: Vec<Vec<String>> = sentences
+        .remove(start)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    sentences.sort_by_key(|sentence| sentence.len());
+    (sentences, complete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn finds_the_repeating_rules_of_an_infinite_language() {
+        let source = "s := \"(\" a \")\" | \"x\".\na := b | ε.\nb := [ \",\" ] s.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let size = language_size(&bnf, 100);
+        assert!(!size.is_finite());
+        assert_eq!(
+            size.to_string(),
+            "The language is infinite: s → a → b → s repeats without bound.\n   \
+             s := \"(\" a \")\"\n   \
+             a := b\n   \
+             b := ___opt_0 s\n"
+        );
+
+        let bnf = Sebnf::parse_str("s := a a.\na := [ ( \"x\" | /[0-9]+/ ) ].", "g")
+            .unwrap()
+            .to_bnf();
+        let LanguageSize::Finite {
+            sentences,
+            complete,
+        } = language_size(&bnf, 100)
+        else {
+            panic!("the language is finite");
+        };
+        assert!(complete);
+        assert_eq!(sentences.len(), 7);
+        assert_eq!(&sentences[..3], ["", "/[0-9]+/", "x"]);
+        assert_eq!(
+            language_size(&bnf, 4).to_string(),
+            "The language is finite, but has more sentences than the limit.\n"
+        );
+    }
+}
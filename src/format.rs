@@ -0,0 +1,312 @@
+//! An opinionated formatter for SEBNF files.
+//!
+//! [`Sebnf`]'s `Display` prints the rules alone. [`format_source`] rewrites
+//! the rules where they are written instead, so comments, blank lines and
+//! anything else between rules stay in place. A rule with a comment inside
+//! it is left as written, since its items no longer show where the comment
+//! belongs.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::format::{FormatOptions, Wrap, format_source};
+//!
+//! let source = "(* lists *)\nlist:=item{\",\" item}.\nitem := \"x\"|\"y\" .";
+//! let sebnf = Sebnf::parse_str(source, "g").unwrap();
+//! let options = FormatOptions {
+//!     wrap: Wrap::Fit,
+//!     ..FormatOptions::default()
+//! };
+//! assert_eq!(
+//!     format_source(source, &sebnf, &options),
+//!     "(* lists *)\nlist := item { \",\" item }.\nitem := \"x\" | \"y\".\n"
+//! );
+//! ```
+
+use std::ops::Range;
+
+use logos::Logos;
+
+use crate::lex::Token;
+use crate::sebnf::{Item, Sebnf};
+use crate::symbols::Symbols;
+
+/// Which rules have their `:=` in the same column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Align {
+    /// All rules, as printed by `Display`
+    #[default]
+    File,
+    /// Rules not separated by a blank line or a comment
+    Block,
+    /// None; every name is followed by a single space
+    None,
+}
+
+/// Where a rule with several alternatives is broken into lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Wrap {
+    /// Every alternative on its own line, as printed by `Display`
+    #[default]
+    Always,
+    /// All alternatives on one line if they fit into the width
+    Fit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub align: Align,
+    /// Lines longer than this are broken between the items of an
+    /// alternative, where possible
+    pub width: usize,
+    pub wrap: Wrap,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            align: Align::File,
+            width: 80,
+            wrap: Wrap::Always,
+        }
+    }
+}
+
+/// A rule in the source
+struct Written<'a> {
+    name: &'a str,
+    /// From the first annotation to the `.`
+    span: Range<usize>,
+    has_comment: bool,
+}
+
+/// Formats the rules of `sebnf` in `source`, the text their spans point
+/// into, and tidies the text between them: trailing whitespace is removed,
+/// blank lines are collapsed to one, and every rule starts on a new line.
+pub fn format_source(source: &str, sebnf: &Sebnf, options: &FormatOptions) -> String {
+    let tokens: Vec<Range<usize>> = Token::lexer(source)
+        .spanned()
+        .filter(|(token, _)| !matches!(token, Ok(Token::NewLine) | Err(_)))
+        .map(|(_, span)| span)
+        .collect();
+    let dots: Vec<usize> = Token::lexer(source)
+        .spanned()
+        .filter(|(token, _)| *token == Ok(Token::Dot))
+        .map(|(_, span)| span.end)
+        .collect();
+
+    let mut rules: Vec<Written> = Vec::new();
+    for (name, name_span) in &sebnf.rule_spans {
+        let start = sebnf
+            .annotations
+            .get(name)
+            .map_or(name_span.start, |a| a.span.start.min(name_span.start));
+        let Some(&end) = dots.iter().find(|&&end| end > name_span.end) else {
+            continue;
+        };
+        let inside: Vec<&Range<usize>> = (tokens.iter())
+            .filter(|token| token.start >= start && token.end <= end)
+            .collect();
+        let has_comment = inside
+            .windows(2)
+            .any(|pair| !source[pair[0].end..pair[1].start].trim().is_empty());
+        rules.push(Written {
+            name,
+            span: start..end,
+            has_comment,
+        });
+    }
+    rules.sort_by_key(|rule| rule.span.start);
+
+    let widths = name_widths(source, &rules, options.align);
+    let mut formatted = String::with_capacity(source.len());
+    let mut end = 0;
+    for (rule, width) in rules.iter().zip(widths) {
+        let gap = tidy_gap(&source[end..rule.span.start], end == 0);
+        formatted.push_str(&gap);
+        // The indentation of a rule inside a `grammar NAME { }` section
+        let indent = gap.rsplit('\n').next().unwrap_or("");
+        let indent = match indent.trim().is_empty() {
+            true => indent.to_string(),
+            false => String::new(),
+        };
+        if rule.has_comment {
+            formatted.push_str(&source[rule.span.clone()]);
+        } else {
+            formatted.push_str(&format_rule(sebnf, rule.name, width, &indent, options));
+        }
+        end = rule.span.end;
+    }
+    let rest = tidy_gap(&source[end..], end == 0);
+    formatted.push_str(rest.trim_end());
+    formatted.push('\n');
+    formatted
+}
+
+/// The column width of each rule's name
+fn name_widths(source: &str, rules: &[Written], align: Align) -> Vec<usize> {
+    let len = |rule: &Written| rule.name.len();
+    match align {
+        Align::None => rules.iter().map(len).collect(),
+        Align::File => {
+            let width = rules.iter().map(len).max().unwrap_or(0);
+            vec![width; rules.len()]
+        }
+        Align::Block => {
+            let mut widths = Vec::new();
+            let mut block: Vec<usize> = Vec::new();
+            for (i, rule) in rules.iter().enumerate() {
+                let separated = i > 0 && {
+                    let gap = &source[rules[i - 1].span.end..rule.span.start];
+                    let lines: Vec<&str> = gap.split('\n').collect();
+                    // A blank line, or a comment on a line of its own
+                    lines.len() > 2 || lines.iter().skip(1).any(|line| !line.trim().is_empty())
+                };
+                if separated {
+                    let width = block.iter().copied().max().unwrap_or(0);
+                    widths.extend(block.drain(..).map(|_| width));
+                }
+                block.push(len(rule));
+            }
+            let width = block.iter().copied().max().unwrap_or(0);
+            widths.extend(block.iter().map(|_| width));
+            widths
+        }
+    }
+}
+
+/// Removes trailing whitespace and repeated blank lines from the text
+/// between two rules, keeping the indentation of the next rule. Text that is
+/// not at the start of the file ends in a newline, so the next rule starts a
+/// line of its own.
+fn tidy_gap(gap: &str, at_start: bool) -> String {
+    let lines: Vec<&str> = gap.split('\n').collect();
+    let mut tidy = String::new();
+    let mut blank = false;
+    for (i, line) in lines.iter().enumerate() {
+        if i + 1 == lines.len() {
+            if i == 0 && !at_start {
+                // On the line of the previous rule's `.`
+                tidy.push_str(line.trim_end());
+                tidy.push('\n');
+            } else {
+                tidy.push_str(line);
+            }
+            break;
+        }
+        let line = line.trim_end();
+        if (i > 0 || at_start) && line.is_empty() {
+            if blank || (tidy.is_empty() && at_start) {
+                continue;
+            }
+            blank = true;
+        } else {
+            blank = false;
+        }
+        tidy.push_str(line);
+        tidy.push('\n');
+    }
+    tidy
+}
+
+fn format_rule(
+    sebnf: &Sebnf,
+    name: &str,
+    width: usize,
+    indent: &str,
+    options: &FormatOptions,
+) -> String {
+    let mut text = String::new();
+    if let Some(annotations) = sebnf.annotations.get(name) {
+        if let Some(limit) = annotations.max_depth {
+            text.push_str(&format!("@max_depth({})\n{}", limit, indent));
+        }
+        if !annotations.soft_keywords.is_empty() {
+            let keywords = annotations.soft_keywords.join(", ");
+            text.push_str(&format!("@soft_keywords({})\n{}", keywords, indent));
+        }
+    }
+    let alternatives: Vec<Vec<String>> = sebnf.rules[name]
+        .iter()
+        .map(|alt| match alt.as_slice() {
+            [] => vec![Symbols::with_current(|s| s.grammar_epsilon().to_string())],
+            items => items.iter().map(Item::to_string).collect(),
+        })
+        .collect();
+    let head = format!("{:width$} := ", name, width = width);
+
+    let one_line = alternatives
+        .iter()
+        .map(|items| items.join(" "))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    let fits = indent.len() + head.len() + one_line.chars().count() < options.width;
+    if alternatives.len() == 1 || (options.wrap == Wrap::Fit && fits) {
+        if fits {
+            return format!("{}{}{}.", text, head, one_line);
+        }
+        if let [items] = alternatives.as_slice() {
+            let lines = break_items(items, indent.len() + head.len(), options.width);
+            let continuation = format!("\n{}{}", indent, " ".repeat(head.len() + 2));
+            return format!("{}{}{}.", text, head, lines.join(&continuation));
+        }
+    }
+
+    let bar = " ".repeat(width + 2);
+    let continuation = format!("\n{}{}", indent, " ".repeat(width + 6));
+    for (i, items) in alternatives.iter().enumerate() {
+        let prefix = match i {
+            0 => head.clone(),
+            _ => format!("\n{}{}| ", indent, bar),
+        };
+        text.push_str(&prefix);
+        let lines = break_items(items, indent.len() + width + 4, options.width);
+        text.push_str(&lines.join(&continuation));
+    }
+    text.push_str(&format!("\n{}{}.", indent, bar));
+    text
+}
+
+/// Splits items into lines that fit after `column`, the first at `column`
+/// and the others 2 further in; an item too long for any line gets its own.
+fn break_items(items: &[String], column: usize, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![String::new()];
+    for item in items {
+        let start = if lines.len() == 1 { column } else { column + 2 };
+        let line = lines.last_mut().expect("there is always a line");
+        let line_len = line.chars().count();
+        if line.is_empty() {
+            line.push_str(item);
+        } else if start + line_len + 1 + item.chars().count() < width {
+            line.push(' ');
+            line.push_str(item);
+        } else {
+            lines.push(item.clone());
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_rules_and_keeps_comments() {
+        let source = "\n  (* start *)\n\n\ns:=a|b .   (* trailing *)\n\n\n\n@max_depth(2)\na := \"(\"   a \")\"  | ( ) .\nb := \"x\" (* why *) | \"y\".\nlong := \"aaaa\" \"bbbb\" \"cccc\" \"dddd\".   ";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let options = FormatOptions {
+            align: Align::Block,
+            width: 30,
+            wrap: Wrap::Always,
+        };
+        assert_eq!(
+            format_source(source, &sebnf, &options),
+            "  (* start *)\n\n\
+             s := a\n   | b\n   .   (* trailing *)\n\n\
+             @max_depth(2)\n\
+             a    := \"(\" a \")\"\n      | ε\n      .\n\
+             b := \"x\" (* why *) | \"y\".\n\
+             long := \"aaaa\" \"bbbb\" \"cccc\"\n          \"dddd\".\n"
+        );
+    }
+}
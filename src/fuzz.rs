@@ -0,0 +1,87 @@
+//! Inputs for grammar-aware fuzzing with AFL or libFuzzer.
+//!
+//! [`dictionary`] lists the tokens of a grammar in the dictionary format
+//! both fuzzers accept (`-x` for AFL, `-dict=` for libFuzzer), and
+//! [`write_seeds`] stores generated sentences as a seed corpus.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
+
+use crate::bnf::{Bnf, Item};
+use crate::codegen::unescape;
+use crate::sentences::{GenerateError, regex_witnesses};
+use crate::sets::strip_terminal_quotes;
+
+/// Every terminal spelling, then a witness string for every regex.
+pub fn dictionary(bnf: &Bnf) -> Result<String, GenerateError> {
+    let terminals: BTreeSet<String> = bnf
+        .rules
+        .values()
+        .flatten()
+        .flatten()
+        .filter_map(|item| match item {
+            Item::Terminal(terminal) => Some(unescape(strip_terminal_quotes(terminal))),
+            _ => None,
+        })
+        .filter(|spelling| !spelling.is_empty())
+        .collect();
+    let witnesses = regex_witnesses(bnf)?;
+
+    let mut out = String::from("# Terminals\n");
+    for terminal in terminals {
+        out.push_str(&format!("\"{}\"\n", escape(&terminal)));
+    }
+    out.push_str("\n# Strings matched by the regexes\n");
+    for (regex, witness) in witnesses {
+        if !witness.is_empty() {
+            out.push_str(&format!("# {}\n\"{}\"\n", regex, escape(&witness)));
+        }
+    }
+    Ok(out)
+}
+
+/// Writes each sentence to its own file `seed-NNNN` in `dir`, creating it
+/// if needed.
+pub fn write_seeds(dir: &Path, sentences: &[String]) -> io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for (i, sentence) in sentences.iter().enumerate() {
+        std::fs::write(dir.join(format!("seed-{:04}", i)), sentence)?;
+    }
+    Ok(())
+}
+
+/// Escapes a dictionary value: `\` and `"` with a backslash, bytes outside
+/// printable ASCII as `\xNN`.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'\\' | b'"' => {
+                escaped.push('\\');
+                escaped.push(byte as char);
+            }
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02X}", byte)),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn dictionary_lists_terminals_and_regex_witnesses() {
+        let source = "s := \"let\" id \"=\" /[0-9]+/ \"\\t\" \"\\\"\".\nid := /[a-z_]+/.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        assert_eq!(
+            dictionary(&bnf).unwrap(),
+            "# Terminals\n\"\\x09\"\n\"\\\"\"\n\"=\"\n\"let\"\n\n\
+             # Strings matched by the regexes\n# /[0-9]+/\n\"0\"\n# /[a-z_]+/\n\"_\"\n"
+        );
+        assert_eq!(escape("é\"\n"), "\\xC3\\xA9\\\"\\x0A");
+    }
+}
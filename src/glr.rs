@@ -0,0 +1,446 @@
+//! GLR parsing, on the LALR(1) automaton.
+//!
+//! [`glr_parse`] runs the LALR(1) table of [`Bnf::lalr1_table`] on the input,
+//! and where the table has several actions for a token it takes all of them.
+//! The stacks it forks into are kept in one graph-structured stack: they
+//! share what lies below the fork, and merge again where they reach the same
+//! state at the same token. What they reduce is kept as a [`ParseForest`],
+//! with one node per rule and stretch of input, holding every way the rule
+//! matches it. So an ambiguous input takes polynomial space however many
+//! parses it has.
+//!
+//! The tokens are those of [`crate::interpret`].
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::glr::glr_parse;
+//!
+//! let sebnf = Sebnf::parse_str("e := e \"-\" e | /[0-9]+/.", "g").unwrap();
+//! let parse = glr_parse(&sebnf, "1 - 2 - 3", "input").unwrap();
+//! assert!(!parse.lalr1);
+//! assert_eq!(parse.forest.nodes[0].alternatives.len(), 2);
+//! assert!(parse.forest.is_ambiguous());
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::Sebnf;
+use crate::bnf::{Bnf, Item};
+use crate::interpret::{InterpretError, Token, expected_name, syntax_error, token_span, tokenize};
+use crate::lr::{LrAction, LrRow};
+use crate::render::{RenderError, render_display};
+use crate::sets::{SetItem, strip_terminal_quotes};
+use crate::symbols::Symbols;
+
+/// The forest of a successful parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlrParse {
+    pub forest: ParseForest,
+    /// Whether the grammar is LALR(1), so that the parser never forked
+    pub lalr1: bool,
+}
+
+/// A shared packed parse forest: the parse trees of an input, with a node
+/// for each rule matching a stretch of the input that the trees share.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseForest {
+    /// The start rule first
+    pub nodes: Vec<ForestNode>,
+}
+
+/// The ways a rule matches a stretch of the input. Its `[ ]`, `{ }` and
+/// groups add no nodes of their own, so their children are the node's
+/// children, unless they match in several ways themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForestNode {
+    pub rule: String,
+    /// Byte offsets into the input; empty if the rule matched no tokens
+    pub span: Range<usize>,
+    /// The children of each way, more than one if the input is ambiguous
+    pub alternatives: Vec<Vec<ForestChild>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForestChild {
+    /// Index into [`ParseForest::nodes`]
+    Node(usize),
+    Token(Token),
+}
+
+impl ParseForest {
+    pub fn is_ambiguous(&self) -> bool {
+        (self.nodes.iter()).any(|node| node.alternatives.len() > 1)
+    }
+
+    /// Renders a line per node and way, quoting the input it matches, like
+    /// `#1 e "1 - 2" := #3 "-" #4`
+    pub fn render(&self, input: &str) -> Result<String, RenderError> {
+        render_display(&ForestDisplay {
+            forest: self,
+            input,
+        })
+    }
+}
+
+struct ForestDisplay<'a> {
+    forest: &'a ParseForest,
+    input: &'a str,
+}
+
+impl fmt::Display for ForestDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (epsilon, ellipsis) =
+            Symbols::with_current(|s| (s.epsilon.clone(), s.glyph("…", "...")));
+        for (index, node) in self.forest.nodes.iter().enumerate() {
+            let mut text: String = self.input[node.span.clone()].chars().take(41).collect();
+            if text.chars().count() > 40 {
+                text = text.chars().take(40).collect::<String>() + ellipsis;
+            }
+            let head = format!("#{} {} {:?} ", index, node.rule, text);
+            for (i, children) in node.alternatives.iter().enumerate() {
+                match i {
+                    0 => write!(f, "{}:=", head)?,
+                    _ => write!(f, "{:width$} |", "", width = head.chars().count())?,
+                }
+                if children.is_empty() {
+                    write!(f, " {}", epsilon)?;
+                }
+                for child in children {
+                    match child {
+                        ForestChild::Node(node) => write!(f, " #{}", node)?,
+                        ForestChild::Token(token) => write!(f, " {:?}", token.text)?,
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses all of `input`, named `input_name` in errors, as the start rule of
+/// `sebnf`.
+pub fn glr_parse(sebnf: &Sebnf, input: &str, input_name: &str) -> Result<GlrParse, InterpretError> {
+    let bnf = without_empty_terminals(&sebnf.to_bnf());
+    let tokens = tokenize(&bnf, input, input_name)?;
+    let table = bnf.lalr1_table();
+    let mut parser = Parser {
+        bnf: &bnf,
+        rows: &table.rows,
+        tokens: &tokens,
+        stack: Vec::new(),
+        level: IndexMap::new(),
+        pending: VecDeque::new(),
+        matches: IndexMap::new(),
+    };
+    let root = match parser.run() {
+        Ok(root) => root,
+        Err((position, expected)) => {
+            return Err(syntax_error(&tokens, position, expected, input, input_name));
+        }
+    };
+    Ok(GlrParse {
+        forest: parser.forest(sebnf, root),
+        lalr1: !table.has_conflicts(),
+    })
+}
+
+/// A copy of `bnf` without the empty terminals, which match no token
+fn without_empty_terminals(bnf: &Bnf) -> Bnf {
+    let mut bnf = bnf.clone();
+    for production in bnf.rules.values_mut().flatten() {
+        production.retain(|item| {
+            !matches!(item, Item::Terminal(symbol) if strip_terminal_quotes(symbol).is_empty())
+        });
+    }
+    bnf
+}
+
+/// What lies between two nodes of the stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Child {
+    /// Index into `Parser::matches`
+    Match(usize),
+    /// Index into the tokens
+    Token(usize),
+}
+
+/// A state the parser reached at a token, with the nodes it can return to
+/// and what lies between
+struct StackNode {
+    state: usize,
+    position: usize,
+    edges: Vec<(usize, Child)>,
+}
+
+/// A production to reduce from a node, along only the paths that take the
+/// given edge, or along all of them
+struct Reduction<'a> {
+    node: usize,
+    rule: &'a str,
+    len: usize,
+    through: Option<(usize, usize)>,
+}
+
+struct Parser<'a> {
+    bnf: &'a Bnf,
+    rows: &'a [LrRow],
+    tokens: &'a [Token],
+    stack: Vec<StackNode>,
+    /// The nodes at the current token, by state
+    level: IndexMap<usize, usize>,
+    pending: VecDeque<Reduction<'a>>,
+    /// The ways each rule matched from a token to before another
+    matches: IndexMap<(&'a str, usize, usize), IndexSet<Vec<Child>>>,
+}
+
+impl<'a> Parser<'a> {
+    /// The match of the start rule, or the furthest token reached and what
+    /// could have come there
+    fn run(&mut self) -> Result<usize, (usize, Vec<String>)> {
+        self.add_node(0, 0, None);
+        for position in 0..=self.tokens.len() {
+            while let Some(reduction) = self.pending.pop_front() {
+                self.reduce(reduction, position);
+            }
+            let lookahead = self.lookahead(position);
+            let mut next = IndexMap::new();
+            for &node in self.level.values() {
+                for action in self.actions(self.stack[node].state, &lookahead) {
+                    match *action {
+                        LrAction::Shift(state) => {
+                            next.entry(state).or_insert_with(Vec::new).push(node);
+                        }
+                        LrAction::Accept => {
+                            let Some(&(_, Child::Match(root))) = self.stack[node].edges.first()
+                            else {
+                                continue;
+                            };
+                            return Ok(root);
+                        }
+                        LrAction::Reduce { .. } => {}
+                    }
+                }
+            }
+            if next.is_empty() {
+                let mut expected = Vec::new();
+                for &node in self.level.values() {
+                    let row = &self.rows[self.stack[node].state];
+                    // Reductions on a lookahead need not lead to a shift
+                    let shifted = (row.actions.iter()).filter(|(_, actions)| {
+                        (actions.iter()).any(|action| !matches!(action, LrAction::Reduce { .. }))
+                    });
+                    for (lookahead, _) in shifted {
+                        let name = expected_name(lookahead);
+                        if !expected.contains(&name) {
+                            expected.push(name);
+                        }
+                    }
+                }
+                return Err((position, expected));
+            }
+            self.level.clear();
+            for (state, below) in next {
+                let node = self.add_node(state, position + 1, None);
+                for below in below {
+                    self.stack[node].edges.push((below, Child::Token(position)));
+                }
+            }
+        }
+        unreachable!("the end of input is shifted by accepting")
+    }
+
+    fn lookahead(&self, position: usize) -> SetItem {
+        match self.tokens.get(position) {
+            Some(token) if token.symbol.starts_with('/') => SetItem::Regex(token.symbol.clone()),
+            Some(token) => SetItem::Terminal(token.symbol.clone()),
+            None => SetItem::EndOfInput,
+        }
+    }
+
+    fn actions(&self, state: usize, lookahead: &SetItem) -> &'a [LrAction] {
+        (self.rows[state].actions.iter())
+            .find(|(item, _)| item == lookahead)
+            .map_or(&[], |(_, actions)| actions.as_slice())
+    }
+
+    /// Adds a node in `state` at `position`, above `edge` if given, and
+    /// queues its reductions
+    fn add_node(&mut self, state: usize, position: usize, edge: Option<(usize, Child)>) -> usize {
+        let node = self.stack.len();
+        self.stack.push(StackNode {
+            state,
+            position,
+            edges: edge.into_iter().collect(),
+        });
+        self.level.insert(state, node);
+        self.queue_reductions(node, None);
+        node
+    }
+
+    fn queue_reductions(&mut self, node: usize, through: Option<(usize, usize)>) {
+        let lookahead = self.lookahead(self.stack[node].position);
+        for action in self.actions(self.stack[node].state, &lookahead) {
+            if let LrAction::Reduce { rule, production } = action {
+                let (rule, productions) = self.bnf.rules.get_key_value(rule).expect("a rule");
+                let len = productions[*production].len();
+                if through.is_none() || len > 0 {
+                    self.pending.push_back(Reduction {
+                        node,
+                        rule,
+                        len,
+                        through,
+                    });
+                }
+            }
+        }
+    }
+
+    fn reduce(&mut self, reduction: Reduction<'a>, position: usize) {
+        for (below, children) in self.paths(&reduction, position) {
+            let start = self.stack[below].position;
+            let entry = self.matches.entry((reduction.rule, start, position));
+            let matched = entry.index();
+            entry.or_default().insert(children);
+
+            let row = &self.rows[self.stack[below].state];
+            let Some(&(_, state)) = row.gotos.iter().find(|(rule, _)| rule == reduction.rule)
+            else {
+                continue;
+            };
+            let edge = (below, Child::Match(matched));
+            let Some(&node) = self.level.get(&state) else {
+                self.add_node(state, position, Some(edge));
+                continue;
+            };
+            if self.stack[node].edges.contains(&edge) {
+                continue;
+            }
+            self.stack[node].edges.push(edge);
+            // The new edge makes new paths for the nodes that reach this one
+            // over rules matching nothing, besides itself
+            let through = Some((node, self.stack[node].edges.len() - 1));
+            for above in self.reaching(node) {
+                self.queue_reductions(above, through);
+            }
+        }
+    }
+
+    /// The nodes at the current token with a path to `node` through nodes
+    /// at the current token, `node` first
+    fn reaching(&self, node: usize) -> Vec<usize> {
+        let mut reaching = vec![node];
+        let mut i = 0;
+        while i < reaching.len() {
+            for &above in self.level.values() {
+                let edges = &self.stack[above].edges;
+                if !reaching.contains(&above)
+                    && edges.iter().any(|&(below, _)| below == reaching[i])
+                {
+                    reaching.push(above);
+                }
+            }
+            i += 1;
+        }
+        reaching
+    }
+
+    /// The nodes `reduction` returns to, with the children between
+    fn paths(&self, reduction: &Reduction, position: usize) -> Vec<(usize, Vec<Child>)> {
+        let mut paths = Vec::new();
+        // Node, children from the top, and whether the edge to take is taken
+        let mut stack = vec![(reduction.node, Vec::new(), reduction.through.is_none())];
+        while let Some((node, children, taken)) = stack.pop() {
+            if children.len() == reduction.len {
+                if taken {
+                    let mut children = children;
+                    children.reverse();
+                    paths.push((node, children));
+                }
+                continue;
+            }
+            for (i, &(below, child)) in self.stack[node].edges.iter().enumerate() {
+                let takes = reduction.through == Some((node, i));
+                // Before the edge, the path stays at the current token
+                if taken || takes || self.stack[below].position == position {
+                    let mut children = children.clone();
+                    children.push(child);
+                    stack.push((below, children, taken || takes));
+                }
+            }
+        }
+        paths
+    }
+
+    /// Numbers the matches reachable from `root`, adding the children of
+    /// the helper rules that match in one way to their parent
+    fn forest(&self, sebnf: &Sebnf, root: usize) -> ParseForest {
+        let is_node = |matched: usize| {
+            let ((rule, _, _), alternatives) = self.matches.get_index(matched).expect("a match");
+            sebnf.rules.contains_key(*rule) || alternatives.len() > 1
+        };
+        let mut numbers = HashMap::from([(root, 0)]);
+        let mut order = vec![root];
+        let mut nodes = Vec::new();
+        while let Some(&matched) = order.get(nodes.len()) {
+            let (&(rule, start, end), ways) = self.matches.get_index(matched).expect("a match");
+            let mut alternatives = Vec::new();
+            for way in ways {
+                let mut children = Vec::new();
+                let mut stack: Vec<Child> = way.iter().rev().copied().collect();
+                while let Some(child) = stack.pop() {
+                    match child {
+                        Child::Token(token) => {
+                            children.push(ForestChild::Token(self.tokens[token].clone()))
+                        }
+                        Child::Match(inner) if !is_node(inner) => {
+                            let (_, ways) = self.matches.get_index(inner).expect("a match");
+                            stack.extend(ways[0].iter().rev().copied());
+                        }
+                        Child::Match(inner) => {
+                            let number = *numbers.entry(inner).or_insert_with(|| {
+                                order.push(inner);
+                                order.len() - 1
+                            });
+                            children.push(ForestChild::Node(number));
+                        }
+                    }
+                }
+                alternatives.push(children);
+            }
+            nodes.push(ForestNode {
+                rule: rule.to_string(),
+                span: token_span(self.tokens, start, end),
+                alternatives,
+            });
+        }
+        ParseForest { nodes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_the_ways_to_match_rules_that_match_nothing() {
+        let sebnf = Sebnf::parse_str("s := a a \"x\".\na := [ \"b\" ].", "g").unwrap();
+        let parse = glr_parse(&sebnf, "b x", "input").unwrap();
+        assert!(!parse.lalr1);
+        assert_eq!(
+            parse.forest.render("b x").unwrap(),
+            "#0 s \"b x\" := #1 #2 \"x\"\n            \
+             | #3 #1 \"x\"\n\
+             #1 a \"b\" := \"b\"\n\
+             #2 a \"\" := ε\n\
+             #3 a \"\" := ε\n"
+        );
+
+        let error = glr_parse(&sebnf, "b b b x", "input").unwrap_err();
+        assert_eq!(error.to_string(), "unexpected \"b\", expected \"x\"");
+    }
+}
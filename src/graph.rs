@@ -0,0 +1,168 @@
+//! The dependency graph of the rules in GraphViz DOT.
+//!
+//! [`dependency_graph`] draws a node per SEBNF rule and an edge from each
+//! rule to every rule it refers to; helper rules of the BNF do not show up.
+//! [`GraphOptions`] adds what makes a large grammar readable: the groups of
+//! mutually recursive rules (see [`recursive_groups`]) as clusters, the start
+//! rule highlighted, and unreachable and unproductive rules marked.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::graph::{GraphOptions, dependency_graph};
+//!
+//! let sebnf = Sebnf::parse_str("list := \"[\" [ item { \",\" item } ] \"]\".\nitem := /[0-9]+/ | list.", "g").unwrap();
+//! let dot = dependency_graph(&sebnf, &GraphOptions::default());
+//! assert!(dot.contains("  \"list\" -> \"item\";\n  \"item\" -> \"list\";\n"));
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::productivity::productivity;
+use crate::reachability::reachability;
+use crate::recursion::recursive_groups;
+use crate::sebnf::Sebnf;
+use crate::visit::Visitor;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphOptions {
+    /// Put each group of mutually recursive rules in a cluster
+    pub clusters: bool,
+    /// Draw the start rule filled and bold
+    pub highlight_start: bool,
+    /// Draw unreachable rules dashed and unproductive rules red
+    pub mark_useless: bool,
+}
+
+/// The rules of `sebnf` and their references as a DOT `digraph`.
+pub fn dependency_graph(sebnf: &Sebnf, options: &GraphOptions) -> String {
+    let bnf = sebnf.to_bnf();
+    let mut unreachable = HashSet::new();
+    let mut unproductive = HashSet::new();
+    if options.mark_useless {
+        unreachable.extend(reachability(sebnf).rules.into_iter().map(|rule| rule.rule));
+        unproductive.extend(productivity(&bnf).rules);
+    }
+
+    let mut dot = String::new();
+    let _ = writeln!(dot, "digraph grammar {{");
+    let _ = writeln!(dot, "  rankdir=LR;");
+    let _ = writeln!(dot, "  node [shape=box, fontname=\"monospace\"];");
+    if options.clusters {
+        let groups = (recursive_groups(&bnf).into_iter())
+            .map(|group| {
+                group
+                    .rules
+                    .into_iter()
+                    .filter(|rule| sebnf.rules.contains_key(rule))
+            })
+            .map(Vec::from_iter)
+            .filter(|rules| rules.len() > 1);
+        for (i, rules) in groups.enumerate() {
+            let _ = writeln!(dot, "  subgraph cluster_{} {{", i);
+            let _ = writeln!(dot, "    label=\"recursive\";");
+            let _ = writeln!(dot, "    style=dashed;");
+            for rule in rules {
+                let _ = writeln!(dot, "    {};", id(&rule));
+            }
+            let _ = writeln!(dot, "  }}");
+        }
+    }
+    for (i, rule) in sebnf.rules.keys().enumerate() {
+        let mut style = Vec::new();
+        let mut attributes = Vec::new();
+        if i == 0 && options.highlight_start {
+            style.extend(["filled", "bold"]);
+            attributes.push("fillcolor=\"#dbeafe\"".to_string());
+        }
+        let mut notes = Vec::new();
+        if unreachable.contains(rule) {
+            style.push("dashed");
+            notes.push("unreachable");
+        }
+        if unproductive.contains(rule) {
+            attributes.push("color=red, fontcolor=red".to_string());
+            notes.push("unproductive");
+        }
+        if !style.is_empty() {
+            attributes.insert(0, format!("style=\"{}\"", style.join(",")));
+        }
+        if !notes.is_empty() {
+            attributes.push(format!("tooltip=\"{}\"", notes.join(", ")));
+        }
+        let _ = match attributes.is_empty() {
+            true => writeln!(dot, "  {};", id(rule)),
+            false => writeln!(dot, "  {} [{}];", id(rule), attributes.join(", ")),
+        };
+    }
+    for (rule, references) in references(sebnf) {
+        for reference in references {
+            let _ = writeln!(dot, "  {} -> {};", id(rule), id(&reference));
+        }
+    }
+    let _ = writeln!(dot, "}}");
+    dot
+}
+
+/// Each rule with the defined rules it refers to, in the order of the
+/// grammar and of their first reference
+pub(crate) fn references(sebnf: &Sebnf) -> Vec<(&String, Vec<String>)> {
+    struct References(Vec<String>);
+    impl Visitor for References {
+        fn visit_non_terminal(&mut self, name: &str, _span: &Range<usize>) {
+            if !self.0.iter().any(|n| n == name) {
+                self.0.push(name.to_string());
+            }
+        }
+    }
+
+    let mut rules = Vec::new();
+    for (rule, alternatives) in &sebnf.rules {
+        let mut references = References(Vec::new());
+        references.visit_alternatives(alternatives);
+        references
+            .0
+            .retain(|reference| sebnf.rules.contains_key(reference));
+        rules.push((rule, references.0));
+    }
+    rules
+}
+
+/// `name` as a quoted DOT identifier
+fn id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clusters_recursive_rules_and_marks_useless_ones() {
+        let source = "prog := { stmt }.\nstmt := expr \";\".\nexpr := term { \"+\" term }.\n\
+                      term := /[0-9]+/ | \"(\" expr \")\".\nloop := loop \"x\".\nold := \"y\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let options = GraphOptions {
+            clusters: true,
+            highlight_start: true,
+            mark_useless: true,
+        };
+        let dot = dependency_graph(&sebnf, &options);
+        assert!(dot.contains(
+            "  subgraph cluster_0 {\n    label=\"recursive\";\n    style=dashed;\n    \
+             \"expr\";\n    \"term\";\n  }\n"
+        ));
+        assert!(!dot.contains("cluster_1"));
+        assert!(dot.contains("  \"prog\" [style=\"filled,bold\", fillcolor=\"#dbeafe\"];\n"));
+        assert!(dot.contains(
+            "  \"loop\" [style=\"dashed\", color=red, fontcolor=red, \
+             tooltip=\"unreachable, unproductive\"];\n"
+        ));
+        assert!(dot.contains("  \"old\" [style=\"dashed\", tooltip=\"unreachable\"];\n"));
+        assert!(dot.contains("  \"loop\" -> \"loop\";\n"));
+
+        let plain = dependency_graph(&sebnf, &GraphOptions::default());
+        assert!(plain.contains("  \"loop\";\n") && !plain.contains("subgraph"));
+    }
+}
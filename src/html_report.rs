@@ -0,0 +1,230 @@
+//! A self-contained HTML report on a grammar, to share the analysis with
+//! people who do not run the tool.
+//!
+//! [`html_report`] puts into one file, with its styles inline and no
+//! scripts: the grammar as formatted, the FIRST and FOLLOW sets of the BNF
+//! as a table, the LL(1) conflicts as [`Ll1Result`] prints them, with the
+//! witnesses of overlapping regexes and, if annotated, an input reaching
+//! them, and the dependency graph of the rules as a table linking each
+//! rule to the rules it refers to and is referred to by. The graph's DOT
+//! source from [`dependency_graph`] comes along, to render with GraphViz.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::html_report::html_report;
+//!
+//! let source = "s := \"a\" s | \"a\".";
+//! let sebnf = Sebnf::parse_str(source, "g").unwrap();
+//! let bnf = sebnf.to_bnf();
+//! let report = html_report("g", source, &sebnf, &bnf, &bnf.is_ll1(false)?);
+//! assert!(report.contains("<p>The grammar is <strong>not LL(1)</strong>: 1 conflict.</p>"));
+//! # Ok::<(), sebnf_tool::sets::Ll1Error>(())
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::bnf::Bnf;
+use crate::graph::{GraphOptions, dependency_graph, references};
+use crate::productivity::productivity;
+use crate::railroad::escape;
+use crate::reachability::reachability;
+use crate::sebnf::Sebnf;
+use crate::sets::{Ll1Result, SetItem, sorted};
+
+const STYLE: &str = "<style>\n\
+  body { font-family: sans-serif; margin: 2em; max-width: 70em; }\n\
+  pre { font-family: monospace; font-size: 14px; background: #f4f4f4; padding: 0.5em; white-space: pre-wrap; }\n\
+  table { border-collapse: collapse; }\n\
+  th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: left; vertical-align: top; }\n\
+  td code { white-space: nowrap; }\n\
+  .conflict { color: #a00; }\n\
+  .note { color: #850; }\n\
+</style>\n";
+
+/// The report on `sebnf`, its BNF `bnf` and the conflicts found in it,
+/// headed by `title`, with `grammar` as the text of the grammar.
+pub fn html_report(
+    title: &str,
+    grammar: &str,
+    sebnf: &Sebnf,
+    bnf: &Bnf,
+    conflicts: &Ll1Result,
+) -> String {
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, "<html lang=\"en\">");
+    let _ = writeln!(html, "<head>");
+    let _ = writeln!(html, "<meta charset=\"utf-8\">");
+    let _ = writeln!(html, "<title>{}</title>", escape(title));
+    html.push_str(STYLE);
+    let _ = writeln!(html, "</head>");
+    let _ = writeln!(html, "<body>");
+    let _ = writeln!(html, "<h1>{}</h1>", escape(title));
+    let verdict = match conflicts.conflicts.len() {
+        0 => "The grammar is <strong>LL(1)</strong>.".to_string(),
+        1 => "The grammar is <strong>not LL(1)</strong>: 1 conflict.".to_string(),
+        n => format!(
+            "The grammar is <strong>not LL(1)</strong>: {} conflicts.",
+            n
+        ),
+    };
+    let _ = writeln!(
+        html,
+        "<p>{} rules, {} in BNF.</p>",
+        sebnf.rules.len(),
+        bnf.rules.len()
+    );
+    let _ = writeln!(html, "<p>{}</p>", verdict);
+    let _ = writeln!(
+        html,
+        "<p><a href=\"#grammar\">Grammar</a> · <a href=\"#sets\">FIRST and FOLLOW sets</a> · \
+         <a href=\"#conflicts\">LL(1) conflicts</a> · <a href=\"#dependencies\">Dependencies</a></p>"
+    );
+
+    let _ = writeln!(html, "<h2 id=\"grammar\">Grammar</h2>");
+    let _ = writeln!(html, "<pre>{}</pre>", escape(grammar.trim_end()));
+
+    write_sets(&mut html, bnf, conflicts);
+
+    let _ = writeln!(html, "<h2 id=\"conflicts\">LL(1) conflicts</h2>");
+    if conflicts.is_ll1() {
+        let _ = writeln!(html, "<p>None.</p>");
+    }
+    for (i, conflict) in conflicts.conflicts.iter().enumerate() {
+        let _ = writeln!(
+            html,
+            "<pre id=\"conflict-{}\">{}. {}</pre>",
+            i + 1,
+            i + 1,
+            escape(conflict.to_string().trim_end())
+        );
+    }
+
+    write_dependencies(&mut html, sebnf);
+    let _ = writeln!(html, "</body>");
+    let _ = writeln!(html, "</html>");
+    html
+}
+
+/// The sets of every rule of the BNF in the order of the grammar, the
+/// rules with a conflict marked
+fn write_sets(html: &mut String, bnf: &Bnf, conflicts: &Ll1Result) {
+    let sets = bnf.first_and_follow_sets();
+    let conflicting: HashSet<&str> = (conflicts.conflicts.iter())
+        .map(|conflict| conflict.non_terminal.as_str())
+        .collect();
+    let cell = |set: Option<&HashSet<SetItem>>| {
+        let items: Vec<String> = (set.map(sorted).unwrap_or_default().into_iter())
+            .map(|item| format!("<code>{}</code>", escape(&item.to_string())))
+            .collect();
+        items.join(" ")
+    };
+    let _ = writeln!(html, "<h2 id=\"sets\">FIRST and FOLLOW sets</h2>");
+    let _ = writeln!(html, "<table>");
+    let _ = writeln!(html, "<tr><th>Rule</th><th>FIRST</th><th>FOLLOW</th></tr>");
+    for rule in bnf.rules.keys() {
+        let class = match conflicting.contains(rule.as_str()) {
+            true => " class=\"conflict\"",
+            false => "",
+        };
+        let _ = writeln!(
+            html,
+            "<tr><td{}><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+            class,
+            escape(rule),
+            cell(sets.first.get(rule)),
+            cell(sets.follow.get(rule))
+        );
+    }
+    let _ = writeln!(html, "</table>");
+}
+
+/// A row per rule linking the rules it refers to and is referred to by,
+/// with the DOT source of the same graph
+fn write_dependencies(html: &mut String, sebnf: &Sebnf) {
+    let unreachable: HashSet<String> = (reachability(sebnf).rules.into_iter())
+        .map(|rule| rule.rule)
+        .collect();
+    let unproductive: HashSet<String> = productivity(&sebnf.to_bnf()).rules.into_iter().collect();
+    let references = references(sebnf);
+    let links = |rules: Vec<&String>| {
+        let links: Vec<String> = (rules.into_iter())
+            .map(|rule| {
+                let rule = escape(rule);
+                format!("<a href=\"#rule-{}\"><code>{}</code></a>", rule, rule)
+            })
+            .collect();
+        links.join(" ")
+    };
+    let _ = writeln!(html, "<h2 id=\"dependencies\">Dependencies</h2>");
+    let _ = writeln!(html, "<table>");
+    let _ = writeln!(
+        html,
+        "<tr><th>Rule</th><th>Refers to</th><th>Referred to by</th><th>Notes</th></tr>"
+    );
+    for (rule, referred) in &references {
+        let referrers = (references.iter())
+            .filter(|(_, referred)| referred.contains(rule))
+            .map(|(referrer, _)| *referrer)
+            .collect();
+        let mut notes = Vec::new();
+        if unreachable.contains(*rule) {
+            notes.push("unreachable");
+        }
+        if unproductive.contains(*rule) {
+            notes.push("unproductive");
+        }
+        let _ = writeln!(
+            html,
+            "<tr><td id=\"rule-{}\"><code>{}</code></td><td>{}</td><td>{}</td>\
+             <td class=\"note\">{}</td></tr>",
+            escape(rule),
+            escape(rule),
+            links(referred.iter().collect()),
+            links(referrers),
+            notes.join(", ")
+        );
+    }
+    let _ = writeln!(html, "</table>");
+    let options = GraphOptions {
+        clusters: true,
+        highlight_start: true,
+        mark_useless: true,
+    };
+    let _ = writeln!(
+        html,
+        "<details>\n<summary>GraphViz DOT source</summary>\n<pre>{}</pre>\n</details>",
+        escape(dependency_graph(sebnf, &options).trim_end())
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_dependencies_and_escapes_the_grammar() {
+        let source = "expr := term { (\"+\" | \"<\") term }.\nterm := /[0-9]+/ | \"(\" expr \")\".\nold := \"&\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let bnf = sebnf.to_bnf();
+        let report = html_report("a <b>", source, &sebnf, &bnf, &bnf.is_ll1(false).unwrap());
+        assert!(report.contains("<h1>a &lt;b&gt;</h1>"));
+        assert!(report.contains("<p>The grammar is <strong>LL(1)</strong>.</p>"));
+        assert!(report.contains("expr := term { (&quot;+&quot; | &quot;&lt;&quot;) term }."));
+        assert!(report.contains(
+            "<tr><td id=\"rule-term\"><code>term</code></td>\
+             <td><a href=\"#rule-expr\"><code>expr</code></a></td>\
+             <td><a href=\"#rule-expr\"><code>expr</code></a></td><td class=\"note\"></td></tr>"
+        ));
+        assert!(report.contains(
+            "<tr><td id=\"rule-old\"><code>old</code></td><td></td><td></td>\
+             <td class=\"note\">unreachable</td></tr>"
+        ));
+        assert!(
+            report.contains(
+                "<td><code>term</code></td><td><code>(</code> <code>/[0-9]+/</code></td>"
+            )
+        );
+    }
+}
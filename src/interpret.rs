@@ -0,0 +1,548 @@
+//! Running a grammar on an input.
+//!
+//! [`parse_input`] splits the input into the grammar's terminals and regexes
+//! the way the generated lexers do, skipping whitespace and taking the longest
+//! match, a terminal winning over a regex and a regex over the ones after it.
+//! It then parses the tokens with the LL(1) table of the grammar. Where the
+//! table has several productions for a token, it tries them in order and
+//! backtracks, so grammars that are not LL(1) parse too, at a cost that can
+//! grow exponentially; [`ParseOptions::max_steps`] bounds it. Left recursion
+//! is cut off once a rule is open at the same token more often than tokens
+//! are left.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::interpret::{ParseOptions, ParseTree, parse_input};
+//!
+//! let sebnf = Sebnf::parse_str("sum := num { \"+\" num }.\nnum := /[0-9]+/.", "g").unwrap();
+//! let parse = parse_input(&sebnf, "1 + 2", "input", &ParseOptions::default()).unwrap();
+//! let ParseTree::Node { rule, children, .. } = &parse.tree else { unreachable!() };
+//! assert_eq!(rule, "sum");
+//! assert_eq!(children.len(), 3);
+//! assert!(parse.ll1);
+//! ```
+
+use std::fmt;
+use std::ops::Range;
+
+use miette::{NamedSource, SourceSpan};
+use regex_automata::meta::{BuildError, Regex};
+
+use crate::bnf::{Bnf, Item};
+use crate::codegen::unescape;
+use crate::sets::{SetItem, strip_regex_delimiters, strip_terminal_quotes};
+use crate::symbols::Symbols;
+use crate::table::{ParseTable, build_parse_table};
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum InterpretError {
+    #[error("invalid regex pattern in grammar: {pattern}")]
+    #[diagnostic(code(sebnf::invalid_regex))]
+    InvalidRegex {
+        pattern: String,
+        #[source]
+        source: Box<BuildError>,
+    },
+    #[error("no terminal or regex of the grammar matches the input")]
+    #[diagnostic(code(sebnf::input_lex))]
+    Lex {
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("no token starts here")]
+        span: SourceSpan,
+    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Syntax(Box<SyntaxError>),
+    #[error("'{rule}' does not match the input")]
+    #[diagnostic(
+        code(sebnf::input_rejected),
+        help("`parse --algo earley` points at the furthest token a parse reaches")
+    )]
+    Rejected {
+        rule: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("not a '{rule}'")]
+        span: SourceSpan,
+    },
+    #[error("gave up parsing after {0} steps")]
+    #[diagnostic(
+        code(sebnf::parse_limit),
+        help(
+            "grammars with LL(1) conflicts are parsed by backtracking, which can take exponentially many steps; raise --max-steps or resolve the conflicts `is-ll1` lists"
+        )
+    )]
+    TooManySteps(usize),
+}
+
+/// A token no rule can go on with, boxed in [`InterpretError::Syntax`] to
+/// keep the error small.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("unexpected {found}, expected {}", expected.join(" or "))]
+#[diagnostic(code(sebnf::input_syntax))]
+pub struct SyntaxError {
+    /// The token as written in the input, or `end of input`
+    pub found: String,
+    /// Terminals and regexes as written in the grammar
+    pub expected: Vec<String>,
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("expected {}", expected.join(" or "))]
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// How many rules and tokens the parser may try before giving up
+    pub max_steps: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_steps: 1_000_000,
+        }
+    }
+}
+
+/// A token of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// The terminal or regex as written in the grammar
+    pub symbol: String,
+    pub text: String,
+    /// Byte offsets into the input
+    pub span: Range<usize>,
+}
+
+/// What a rule of the grammar matched. Its `[ ]`, `{ }` and groups add no
+/// nodes of their own, so their children are the node's children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTree {
+    Node {
+        rule: String,
+        children: Vec<ParseTree>,
+        /// Byte offsets into the input; empty if the rule matched no tokens
+        span: Range<usize>,
+    },
+    Token(Token),
+}
+
+/// The tree of a successful parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parse {
+    pub tree: ParseTree,
+    /// Whether the grammar is LL(1), so that no production was guessed
+    pub ll1: bool,
+}
+
+impl fmt::Display for ParseTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ascii = Symbols::with_current(|symbols| symbols.ascii);
+        write_tree(f, self, "", "", ascii)
+    }
+}
+
+fn write_tree(
+    f: &mut fmt::Formatter<'_>,
+    tree: &ParseTree,
+    branch: &str,
+    prefix: &str,
+    ascii: bool,
+) -> fmt::Result {
+    match tree {
+        ParseTree::Token(token) if token.symbol.starts_with('/') => {
+            writeln!(f, "{}{:?} {}", branch, token.text, token.symbol)
+        }
+        ParseTree::Token(token) => writeln!(f, "{}{}", branch, token.symbol),
+        ParseTree::Node { rule, children, .. } => {
+            writeln!(f, "{}{}", branch, rule)?;
+            for (i, child) in children.iter().enumerate() {
+                let (branch, cont) = match (i + 1 == children.len(), ascii) {
+                    (true, false) => (format!("{}└── ", prefix), format!("{}    ", prefix)),
+                    (false, false) => (format!("{}├── ", prefix), format!("{}│   ", prefix)),
+                    (true, true) => (format!("{}`-- ", prefix), format!("{}    ", prefix)),
+                    (false, true) => (format!("{}|-- ", prefix), format!("{}|   ", prefix)),
+                };
+                write_tree(f, child, &branch, &cont, ascii)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Splits `input` into the terminals and regexes of `bnf`, skipping
+/// whitespace. The longest match wins, a terminal wins over a regex matching
+/// the same text, and a regex over the regexes after it.
+pub fn tokenize(bnf: &Bnf, input: &str, input_name: &str) -> Result<Vec<Token>, InterpretError> {
+    let mut terminals: Vec<(String, &str)> = Vec::new();
+    let mut regexes: Vec<(Regex, &str)> = Vec::new();
+    for item in bnf.rules.values().flatten().flatten() {
+        match item {
+            Item::Terminal(symbol) if terminals.iter().all(|(_, s)| s != symbol) => {
+                let text = unescape(strip_terminal_quotes(symbol));
+                if !text.is_empty() {
+                    terminals.push((text, symbol));
+                }
+            }
+            Item::Regex(symbol) if regexes.iter().all(|(_, s)| s != symbol) => {
+                let pattern = format!("^(?:{})", strip_regex_delimiters(symbol));
+                let regex =
+                    Regex::new(&pattern).map_err(|source| InterpretError::InvalidRegex {
+                        pattern: symbol.clone(),
+                        source: Box::new(source),
+                    })?;
+                regexes.push((regex, symbol));
+            }
+            _ => {}
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    while offset < input.len() {
+        let rest = &input[offset..];
+        let trimmed = rest.trim_start();
+        if trimmed.len() < rest.len() {
+            offset += rest.len() - trimmed.len();
+            continue;
+        }
+        let mut longest: Option<(usize, &str)> = None;
+        for (text, symbol) in &terminals {
+            if rest.starts_with(text.as_str()) && longest.is_none_or(|(len, _)| text.len() > len) {
+                longest = Some((text.len(), symbol));
+            }
+        }
+        for (regex, symbol) in &regexes {
+            if let Some(found) = regex.find(rest)
+                && found.end() > 0
+                && longest.is_none_or(|(len, _)| found.end() > len)
+            {
+                longest = Some((found.end(), symbol));
+            }
+        }
+        let Some((len, symbol)) = longest else {
+            return Err(InterpretError::Lex {
+                src: NamedSource::new(input_name, input.to_string()),
+                span: (offset, rest.chars().next().map_or(0, char::len_utf8)).into(),
+            });
+        };
+        tokens.push(Token {
+            symbol: symbol.to_string(),
+            text: rest[..len].to_string(),
+            span: offset..offset + len,
+        });
+        offset += len;
+    }
+    Ok(tokens)
+}
+
+/// Parses all of `input`, named `input_name` in errors, as the start rule of
+/// `sebnf`.
+pub fn parse_input(
+    sebnf: &crate::Sebnf,
+    input: &str,
+    input_name: &str,
+    options: &ParseOptions,
+) -> Result<Parse, InterpretError> {
+    let bnf = sebnf.to_bnf();
+    let tokens = tokenize(&bnf, input, input_name)?;
+    let table = build_parse_table(&bnf, &bnf.first_and_follow_sets());
+    let Some(start) = bnf.rules.keys().next() else {
+        return Err(syntax_error(&tokens, 0, Vec::new(), input, input_name));
+    };
+    let parser = Parser {
+        bnf: &bnf,
+        table: &table,
+        sebnf,
+        tokens: &tokens,
+    };
+    match parser.run(start, options.max_steps) {
+        Ok(tree) => Ok(Parse {
+            tree,
+            ll1: !table.has_conflicts(),
+        }),
+        Err(Failure::Steps) => Err(InterpretError::TooManySteps(options.max_steps)),
+        Err(Failure::Syntax { position, expected }) => {
+            Err(syntax_error(&tokens, position, expected, input, input_name))
+        }
+    }
+}
+
+/// The bytes from the token at `start` to before the one at `end`; where no
+/// tokens are, the empty range before the token at `start`
+pub(crate) fn token_span(tokens: &[Token], start: usize, end: usize) -> Range<usize> {
+    match end > start {
+        true => tokens[start].span.start..tokens[end - 1].span.end,
+        false => {
+            let offset = match tokens.get(start) {
+                Some(token) => token.span.start,
+                None => tokens.last().map_or(0, |token| token.span.end),
+            };
+            offset..offset
+        }
+    }
+}
+
+pub(crate) fn syntax_error(
+    tokens: &[Token],
+    position: usize,
+    expected: Vec<String>,
+    input: &str,
+    input_name: &str,
+) -> InterpretError {
+    let (found, span) = match tokens.get(position) {
+        Some(token) => (
+            format!("{:?}", token.text),
+            (token.span.start, token.span.len()).into(),
+        ),
+        None => ("end of input".to_string(), (input.len(), 0).into()),
+    };
+    InterpretError::Syntax(Box::new(SyntaxError {
+        found,
+        expected,
+        src: NamedSource::new(input_name, input.to_string()),
+        span,
+    }))
+}
+
+enum Failure {
+    Steps,
+    /// The furthest token no branch could go on from
+    Syntax {
+        position: usize,
+        expected: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Task<'a> {
+    Rule(&'a str),
+    /// A terminal or regex as written
+    Token(&'a str),
+    /// Ends the rule begun at the token at `first`
+    Close {
+        rule: &'a str,
+        first: usize,
+    },
+}
+
+/// A branch of the parse
+#[derive(Debug, Clone)]
+struct State<'a> {
+    position: usize,
+    tasks: Vec<Task<'a>>,
+    /// The children of the nodes being built, innermost last
+    children: Vec<Vec<ParseTree>>,
+}
+
+struct Parser<'a> {
+    bnf: &'a Bnf,
+    table: &'a ParseTable,
+    /// Its rules build nodes; the helper rules of the BNF add their children
+    /// to the node of the rule using them
+    sebnf: &'a crate::Sebnf,
+    tokens: &'a [Token],
+}
+
+impl<'a> Parser<'a> {
+    fn run(&self, start: &'a str, max_steps: usize) -> Result<ParseTree, Failure> {
+        let mut branches = vec![State {
+            position: 0,
+            tasks: vec![Task::Rule(start)],
+            children: vec![Vec::new()],
+        }];
+        let mut furthest = (0, Vec::new());
+        let mut steps = 0;
+        while let Some(mut state) = branches.pop() {
+            loop {
+                steps += 1;
+                if steps > max_steps {
+                    return Err(Failure::Steps);
+                }
+                let Some(task) = state.tasks.pop() else {
+                    if state.position == self.tokens.len() {
+                        let mut root = state.children.pop().unwrap_or_default();
+                        return Ok(root.pop().expect("the start rule adds a node"));
+                    }
+                    note_expected(&mut furthest, state.position, ["end of input".to_string()]);
+                    break;
+                };
+                match task {
+                    Task::Rule(rule) => {
+                        if self.left_recursion(&state, rule) {
+                            break;
+                        }
+                        let next = match self.tokens.get(state.position) {
+                            Some(token) if token.symbol.starts_with('/') => {
+                                SetItem::Regex(token.symbol.clone())
+                            }
+                            Some(token) => SetItem::Terminal(token.symbol.clone()),
+                            None => SetItem::EndOfInput,
+                        };
+                        let cells = &self.table.rows[rule];
+                        let productions = (cells.iter())
+                            .find(|cell| cell.lookahead == next)
+                            .map(|cell| cell.productions.as_slice())
+                            .unwrap_or_default();
+                        let Some((&first, others)) = productions.split_first() else {
+                            let expected = cells.iter().map(|cell| expected_name(&cell.lookahead));
+                            note_expected(&mut furthest, state.position, expected);
+                            break;
+                        };
+                        for &production in others.iter().rev() {
+                            let mut branch = state.clone();
+                            self.open(&mut branch, rule, production);
+                            branches.push(branch);
+                        }
+                        self.open(&mut state, rule, first);
+                    }
+                    Task::Token(symbol) => match self.tokens.get(state.position) {
+                        Some(token) if token.symbol == symbol => {
+                            let node = state.children.last_mut().expect("a rule is open");
+                            node.push(ParseTree::Token(token.clone()));
+                            state.position += 1;
+                        }
+                        _ => {
+                            note_expected(&mut furthest, state.position, [symbol.to_string()]);
+                            break;
+                        }
+                    },
+                    Task::Close { rule, first } => {
+                        if !self.sebnf.rules.contains_key(rule) {
+                            continue;
+                        }
+                        let children = state.children.pop().expect("the rule is open");
+                        let span = match state.position > first {
+                            true => {
+                                self.tokens[first].span.start
+                                    ..self.tokens[state.position - 1].span.end
+                            }
+                            false => {
+                                let offset = self.offset(state.position);
+                                offset..offset
+                            }
+                        };
+                        let node = ParseTree::Node {
+                            rule: rule.to_string(),
+                            children,
+                            span,
+                        };
+                        state
+                            .children
+                            .last_mut()
+                            .expect("a rule is open")
+                            .push(node);
+                    }
+                }
+            }
+        }
+        let (position, expected) = furthest;
+        Err(Failure::Syntax { position, expected })
+    }
+
+    /// Begins `rule` with its production `production`
+    fn open(&self, state: &mut State<'a>, rule: &'a str, production: usize) {
+        state.tasks.push(Task::Close {
+            rule,
+            first: state.position,
+        });
+        if self.sebnf.rules.contains_key(rule) {
+            state.children.push(Vec::new());
+        }
+        for item in self.bnf.rules[rule][production].iter().rev() {
+            match item {
+                Item::NonTerminal(rule) => state.tasks.push(Task::Rule(rule)),
+                // An empty terminal matches no token
+                Item::Terminal(symbol) if strip_terminal_quotes(symbol).is_empty() => {}
+                Item::Terminal(symbol) | Item::Regex(symbol) => {
+                    state.tasks.push(Task::Token(symbol))
+                }
+                Item::TokenClass(_) | Item::EndOfInput => {}
+            }
+        }
+    }
+
+    /// Whether `rule` is open at the current token as often as tokens are
+    /// left, so that opening it again cannot lead anywhere
+    fn left_recursion(&self, state: &State, rule: &str) -> bool {
+        let open = (state.tasks.iter())
+            .filter(|task| {
+                matches!(task, Task::Close { rule: r, first } if *r == rule && *first == state.position)
+            })
+            .count();
+        open > self.tokens.len() - state.position
+    }
+
+    fn offset(&self, position: usize) -> usize {
+        match self.tokens.get(position) {
+            Some(token) => token.span.start,
+            None => self.tokens.last().map_or(0, |token| token.span.end),
+        }
+    }
+}
+
+/// Keeps the expected symbols at the furthest token a branch failed at
+fn note_expected(
+    furthest: &mut (usize, Vec<String>),
+    position: usize,
+    expected: impl IntoIterator<Item = String>,
+) {
+    if position > furthest.0 {
+        *furthest = (position, Vec::new());
+    }
+    if position == furthest.0 {
+        for symbol in expected {
+            if !furthest.1.contains(&symbol) {
+                furthest.1.push(symbol);
+            }
+        }
+    }
+}
+
+pub(crate) fn expected_name(item: &SetItem) -> String {
+    match item {
+        SetItem::Terminal(symbol) | SetItem::Regex(symbol) => symbol.clone(),
+        SetItem::EndOfInput => "end of input".to_string(),
+        item => item.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn backtracks_through_conflicts_and_left_recursion() {
+        let source = "e := e \"-\" t | t.\nt := /[0-9]+/ | \"(\" e \")\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let parse = parse_input(&sebnf, "1 - (2 - 3)", "input", &ParseOptions::default()).unwrap();
+        assert!(!parse.ll1);
+        assert_eq!(
+            parse.tree.to_string(),
+            "e\n\
+             ├── e\n\
+             │   └── t\n\
+             │       └── \"1\" /[0-9]+/\n\
+             ├── \"-\"\n\
+             └── t\n    \
+                 ├── \"(\"\n    \
+                 ├── e\n    \
+                 │   ├── e\n    \
+                 │   │   └── t\n    \
+                 │   │       └── \"2\" /[0-9]+/\n    \
+                 │   ├── \"-\"\n    \
+                 │   └── t\n    \
+                 │       └── \"3\" /[0-9]+/\n    \
+                 └── \")\"\n"
+        );
+
+        let error = parse_input(&sebnf, "1 - )", "input", &ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected \")\", expected \"(\" or /[0-9]+/"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Keywords of a grammar and the identifiers they take away.
+//!
+//! Lexers give terminals precedence over regexes, so a terminal such as
+//! `"match"` that an identifier regex also matches is reserved: that regex
+//! never matches `match`, anywhere in the grammar. Soft keywords, declared
+//! with `@soft_keywords(...)` on the rules using them, are lexed as
+//! identifiers instead and reserve nothing.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::regex_intersect::{Error as RegexError, do_regexs_intersect};
+use crate::sebnf::{Item, Sebnf};
+use crate::sets::{strip_regex_delimiters, strip_terminal_quotes};
+use crate::visit::{Visitor, walk_alternatives, walk_sebnf};
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum KeywordError {
+    #[error("invalid regex pattern in grammar: {pattern}")]
+    #[diagnostic(code(sebnf::invalid_regex))]
+    InvalidRegex {
+        pattern: String,
+        #[source]
+        source: RegexError,
+    },
+}
+
+/// A terminal that a regex of the grammar also matches, or a soft keyword.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyword {
+    /// The terminal with its quotes, e.g. `"match"`
+    pub terminal: String,
+    /// Regexes of the grammar matching its spelling
+    pub regexes: Vec<String>,
+    /// Rules using it as a reserved keyword
+    pub reserved_in: Vec<String>,
+    /// Rules declaring it a soft keyword
+    pub soft_in: Vec<String>,
+}
+
+impl Keyword {
+    /// Whether the lexer always reads it as a keyword; a single rule without
+    /// the soft declaration is enough.
+    pub fn is_reserved(&self) -> bool {
+        !self.reserved_in.is_empty()
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules = |rules: &[String]| {
+            let quoted: Vec<_> = rules.iter().map(|r| format!("'{}'", r)).collect();
+            quoted.join(", ")
+        };
+        let regexes = self.regexes.join(", ");
+        if self.is_reserved() {
+            write!(f, "{} is reserved", self.terminal)?;
+            if !self.soft_in.is_empty() {
+                write!(f, " (soft only in {})", rules(&self.soft_in))?;
+            }
+            write!(f, ": {} never matches it", regexes)
+        } else if self.regexes.is_empty() {
+            write!(
+                f,
+                "{} is soft in {}, but no regex matches it, so it cannot be lexed as an identifier",
+                self.terminal,
+                rules(&self.soft_in)
+            )
+        } else {
+            write!(
+                f,
+                "{} is soft in {}: lexed as {}",
+                self.terminal,
+                rules(&self.soft_in),
+                regexes
+            )
+        }
+    }
+}
+
+/// Every keyword of the grammar, in the order of first use.
+pub fn keywords(sebnf: &Sebnf) -> Result<Vec<Keyword>, KeywordError> {
+    let mut collector = Collector::default();
+    walk_sebnf(&mut collector, sebnf);
+
+    let mut keywords: Vec<Keyword> = Vec::new();
+    for (rule, terminal) in collector.terminals {
+        let index = match keywords.iter().position(|k| k.terminal == terminal) {
+            Some(index) => index,
+            None => {
+                keywords.push(Keyword {
+                    regexes: matching_regexes(&terminal, &collector.regexes)?,
+                    terminal,
+                    reserved_in: Vec::new(),
+                    soft_in: Vec::new(),
+                });
+                keywords.len() - 1
+            }
+        };
+        let keyword = &mut keywords[index];
+        let rules = if sebnf.is_soft_keyword(&rule, &keyword.terminal) {
+            &mut keyword.soft_in
+        } else {
+            &mut keyword.reserved_in
+        };
+        if !rules.contains(&rule) {
+            rules.push(rule);
+        }
+    }
+    keywords.retain(|k| !k.regexes.is_empty() || !k.soft_in.is_empty());
+    Ok(keywords)
+}
+
+fn matching_regexes(terminal: &str, regexes: &[String]) -> Result<Vec<String>, KeywordError> {
+    let literal = regex_syntax::escape(strip_terminal_quotes(terminal));
+    let mut matching = Vec::new();
+    for regex in regexes {
+        let pattern = strip_regex_delimiters(regex);
+        // Both anchored, so a match is a full match of the terminal
+        let matches = do_regexs_intersect(&literal, pattern).map_err(|source| {
+            KeywordError::InvalidRegex {
+                pattern: regex.clone(),
+                source,
+            }
+        })?;
+        if matches.is_some() {
+            matching.push(regex.clone());
+        }
+    }
+    Ok(matching)
+}
+
+#[derive(Default)]
+struct Collector {
+    rule: String,
+    /// Every terminal with its rule
+    terminals: Vec<(String, String)>,
+    /// Distinct regexes
+    regexes: Vec<String>,
+}
+
+impl Visitor for Collector {
+    fn visit_rule(&mut self, name: &str, alternatives: &[Vec<Item>]) {
+        self.rule = name.to_string();
+        walk_alternatives(self, alternatives);
+    }
+
+    fn visit_terminal(&mut self, terminal: &str, _span: &Range<usize>) {
+        self.terminals
+            .push((self.rule.clone(), terminal.to_string()));
+    }
+
+    fn visit_regex(&mut self, regex: &str, _span: &Range<usize>) {
+        if !self.regexes.iter().any(|r| r == regex) {
+            self.regexes.push(regex.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soft_keywords_reserve_nothing() {
+        let source = "stmt := fn_decl | \"match\" ident | ident \"=\" /[0-9]+/.\n\
+                      @soft_keywords(\"async\", \"match\")\n\
+                      fn_decl := [ \"async\" ] \"fn\" ident \"match\".\n\
+                      ident := /[a-z]+/.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let keywords = keywords(&sebnf).unwrap();
+        let lines: Vec<_> = keywords.iter().map(|k| k.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "\"match\" is reserved (soft only in 'fn_decl'): /[a-z]+/ never matches it",
+                "\"async\" is soft in 'fn_decl': lexed as /[a-z]+/",
+                "\"fn\" is reserved: /[a-z]+/ never matches it",
+            ]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Export of a grammar to LALRPOP, to move a grammar prototyped here to a
+//! parser generator.
+//!
+//! [`lalrpop_grammar`] writes a `.lalrpop` file with a nonterminal per rule,
+//! named in camel case, the start rule public. Optional items and
+//! repetitions keep their form as `?` and `*`, built-in idioms are spelled
+//! out, labels become named bindings `<label:X>`, and a choice inside a
+//! production, which LALRPOP has no syntax for, gets a nonterminal of its
+//! own. Every nonterminal is of type `()` with `=> ()` actions, so the file
+//! builds as it is and the actions can be filled in one by one.
+//!
+//! Tokens come from LALRPOP's own lexer, which like the lexers of
+//! [`codegen`](crate::codegen) skips whitespace, takes the longest match and
+//! prefers a terminal over a regex; a `match` block ranks the regexes in the
+//! order of the grammar. With `extern_tokens`, the grammar instead reads the
+//! `TokenKind`s of the lexer `codegen lexer` prints, declared in an `extern`
+//! block, with a regex's token named in upper snake case.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::lalrpop::lalrpop_grammar;
+//!
+//! let sebnf = Sebnf::parse_str("sum := num { (\"+\" | \"-\") num }.\nnum := /[0-9]+/.", "g").unwrap();
+//! let grammar = lalrpop_grammar(&sebnf, false);
+//! assert!(grammar.contains("pub Sum: () = Num (SumChoice Num)* => ();\n"));
+//! assert!(grammar.contains("Num: () = r\"[0-9]+\" => ();\n"));
+//! ```
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use indexmap::IndexMap;
+
+use crate::codegen::{Names, camel, unescape, unique};
+use crate::sebnf::{Builtin, Item, Sebnf};
+use crate::sets::{strip_regex_delimiters, strip_terminal_quotes};
+
+/// A LALRPOP grammar recognizing the language of `sebnf`, reading tokens
+/// from LALRPOP's lexer or, with `extern_tokens`, from the `TokenKind`s of
+/// `codegen lexer`.
+pub fn lalrpop_grammar(sebnf: &Sebnf, extern_tokens: bool) -> String {
+    let mut exporter = Exporter::new(sebnf, extern_tokens);
+    let mut nonterminals = Vec::new();
+    for (i, (rule, alternatives)) in sebnf.rules.iter().enumerate() {
+        let name = exporter.rules[rule].clone();
+        let alternatives = (alternatives.iter())
+            .map(|alternative| exporter.sequence(&name, alternative))
+            .collect();
+        nonterminals.push((i == 0, name, alternatives));
+        nonterminals.extend(
+            exporter
+                .choices
+                .drain(..)
+                .map(|(name, alternatives)| (false, name, alternatives)),
+        );
+    }
+
+    let mut text = String::new();
+    match extern_tokens {
+        true => {
+            let start = nonterminals
+                .first()
+                .map_or("Start", |(_, name, _)| name.as_str());
+            let _ = writeln!(
+                text,
+                "// Generated by `sebnf_tool export lalrpop --extern-tokens`. Tokens"
+            );
+            let _ = writeln!(
+                text,
+                "// come from the lexer of `sebnf_tool codegen lexer`:"
+            );
+            let _ = writeln!(text, "//");
+            let _ = writeln!(text, "//     let tokens = lexer::tokenize(input)?");
+            let _ = writeln!(text, "//         .into_iter()");
+            let _ = writeln!(
+                text,
+                "//         .map(|token| Ok((token.span.start, token.kind, token.span.end)));"
+            );
+            let _ = writeln!(text, "//     grammar::{}Parser::new().parse(tokens)", start);
+            let _ = writeln!(text);
+            let _ = writeln!(text, "use crate::lexer::{{LexError, TokenKind}};");
+        }
+        false => {
+            let _ = writeln!(text, "// Generated by `sebnf_tool export lalrpop`.");
+        }
+    }
+    let _ = writeln!(text);
+    let _ = writeln!(text, "grammar;");
+    let _ = writeln!(text);
+    if extern_tokens {
+        exporter.write_extern(&mut text);
+    } else {
+        exporter.write_match(&mut text);
+    }
+
+    for (public, name, alternatives) in nonterminals {
+        let public = if public { "pub " } else { "" };
+        match alternatives.as_slice() {
+            [alternative] if !alternative.is_empty() => {
+                let _ = writeln!(text, "{}{}: () = {} => ();", public, name, alternative);
+            }
+            _ => {
+                let _ = writeln!(text, "{}{}: () = {{", public, name);
+                for alternative in &alternatives {
+                    let _ = match alternative.is_empty() {
+                        true => writeln!(text, "    => (),"),
+                        false => writeln!(text, "    {} => (),", alternative),
+                    };
+                }
+                let _ = writeln!(text, "}};");
+            }
+        }
+        let _ = writeln!(text);
+    }
+    text.pop();
+    text
+}
+
+struct Exporter {
+    /// Rules to their nonterminal
+    rules: IndexMap<String, String>,
+    /// Terminals and regexes as written to their spelling in the grammar
+    tokens: IndexMap<String, String>,
+    /// Terminals and regexes as written to their `TokenKind` variant
+    variants: IndexMap<String, String>,
+    /// Names of the nonterminals and named terminals so far
+    taken: HashSet<String>,
+    /// Choices of the current rule lifted into nonterminals, with their
+    /// alternatives
+    choices: Vec<(String, Vec<String>)>,
+}
+
+impl Exporter {
+    fn new(sebnf: &Sebnf, extern_tokens: bool) -> Self {
+        let mut taken = HashSet::new();
+        let rules = (sebnf.rules.keys())
+            .map(|rule| (rule.clone(), unique(camel(rule), &mut taken)))
+            .collect();
+        let names = Names::new(sebnf, &sebnf.to_bnf());
+        let mut tokens = IndexMap::new();
+        for (symbol, variant) in &names.tokens {
+            let spelling = match symbol.starts_with('/') {
+                false => format!("{:?}", unescape(strip_terminal_quotes(symbol))),
+                true if extern_tokens => unique(upper_snake(variant), &mut taken),
+                true => raw_string(strip_regex_delimiters(symbol)),
+            };
+            tokens.insert(symbol.clone(), spelling);
+        }
+        Exporter {
+            rules,
+            tokens,
+            variants: names.tokens,
+            taken,
+            choices: Vec::new(),
+        }
+    }
+
+    /// The declarations of the tokens as `TokenKind` variants
+    fn write_extern(&self, text: &mut String) {
+        let _ = writeln!(text, "extern {{");
+        let _ = writeln!(text, "    type Location = usize;");
+        let _ = writeln!(text, "    type Error = LexError;");
+        let _ = writeln!(text);
+        let _ = writeln!(text, "    enum TokenKind {{");
+        for (symbol, spelling) in &self.tokens {
+            let _ = writeln!(
+                text,
+                "        {} => TokenKind::{},",
+                spelling, self.variants[symbol]
+            );
+        }
+        let _ = writeln!(text, "    }}");
+        let _ = writeln!(text, "}}");
+        let _ = writeln!(text);
+    }
+
+    /// A `match` block ranking the regexes, if any two could tie
+    fn write_match(&self, text: &mut String) {
+        let regexes: Vec<&String> = (self.tokens.iter())
+            .filter(|(symbol, _)| symbol.starts_with('/'))
+            .map(|(_, spelling)| spelling)
+            .collect();
+        if regexes.len() < 2 {
+            return;
+        }
+        let terminals = (self.tokens.iter()).filter(|(symbol, _)| !symbol.starts_with('/'));
+        let _ = writeln!(text, "match {{");
+        for (_, spelling) in terminals {
+            let _ = writeln!(text, "    {},", spelling);
+        }
+        for (i, regex) in regexes.iter().enumerate() {
+            if i > 0 {
+                let _ = writeln!(text, "}} else {{");
+            }
+            let _ = writeln!(text, "    {},", regex);
+        }
+        let _ = writeln!(text, "}}");
+        let _ = writeln!(text);
+    }
+
+    /// The items of a production separated by spaces, with choices lifted
+    /// into nonterminals named after `rule`
+    fn sequence(&mut self, rule: &str, items: &[Item]) -> String {
+        let items: Vec<String> = (items.iter())
+            .map(|item| self.item(rule, item))
+            .filter(|item| !item.is_empty())
+            .collect();
+        items.join(" ")
+    }
+
+    fn item(&mut self, rule: &str, item: &Item) -> String {
+        match item {
+            Item::NonTerminal(name, _) => {
+                self.rules.get(name).cloned().unwrap_or_else(|| camel(name))
+            }
+            Item::Terminal(symbol, _) | Item::Regex(symbol, _) => match self.tokens.get(symbol) {
+                Some(spelling) => spelling.clone(),
+                None => format!("{:?}", unescape(strip_terminal_quotes(symbol))),
+            },
+            Item::Optional(items, _) => format!("{}?", self.group(rule, items)),
+            Item::AnyAmount(items, _) => format!("{}*", self.group(rule, items)),
+            Item::Choice(alternatives, _) if alternatives.len() == 1 => {
+                format!("({})", self.sequence(rule, &alternatives[0]))
+            }
+            Item::Choice(alternatives, _) => {
+                let name = unique(format!("{}Choice", rule), &mut self.taken);
+                let alternatives = (alternatives.iter())
+                    .map(|alternative| self.sequence(rule, alternative))
+                    .collect();
+                self.choices.push((name.clone(), alternatives));
+                name
+            }
+            Item::Builtin(builtin, arguments, _) => {
+                let arguments: Vec<String> = (arguments.iter())
+                    .map(|argument| self.sequence(rule, argument))
+                    .collect();
+                match (builtin, arguments.as_slice()) {
+                    (Builtin::SepBy, [item, separator]) => {
+                        format!("({} ({} {})*)?", item, separator, item)
+                    }
+                    (Builtin::Delimited, [left, item, right]) => {
+                        format!("{} {} {}", left, item, right)
+                    }
+                    (Builtin::NonEmpty, [item]) => format!("({})+", item),
+                    _ => unreachable!("built-in calls are checked when parsing"),
+                }
+            }
+            Item::Labeled(label, item, _) => format!("<{}:{}>", label, self.item(rule, item)),
+            Item::Epsilon(_) => String::new(),
+        }
+    }
+
+    /// `items` as the operand of `?`, `*` or `+`
+    fn group(&mut self, rule: &str, items: &[Item]) -> String {
+        let sequence = self.sequence(rule, items);
+        match items {
+            [Item::NonTerminal(..) | Item::Terminal(..) | Item::Regex(..)] => sequence,
+            [Item::Choice(alternatives, _)] if alternatives.len() > 1 => sequence,
+            _ => format!("({})", sequence),
+        }
+    }
+}
+
+/// `PlusPlus` as `PLUS_PLUS`
+pub(crate) fn upper_snake(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous.is_some_and(|p: char| p.is_ascii_lowercase()) {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_uppercase());
+        previous = Some(c);
+    }
+    snake
+}
+
+/// A raw string literal `r"..."`, with as many `#` as it takes
+pub(crate) fn raw_string(pattern: &str) -> String {
+    let mut hashes = String::new();
+    while pattern.contains(&format!("\"{}", hashes)) {
+        hashes.push('#');
+    }
+    format!("r{}\"{}\"{}", hashes, pattern, hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_regex_tokens_apart_from_rules() {
+        let source = "list := [ item { \",\" item } ] | ε.\nitem := NUM | num.\n\
+                      NUM := /[0-9]+/.\nnum := \"n\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let grammar = lalrpop_grammar(&sebnf, true);
+        assert!(
+            grammar.contains("        NUM2 => TokenKind::NUM,\n"),
+            "{}",
+            grammar
+        );
+        assert!(
+            grammar
+                .contains("pub List: () = {\n    (Item (\",\" Item)*)? => (),\n    => (),\n};\n")
+        );
+        assert!(grammar.contains("NUM: () = NUM2 => ();\n"));
+        assert!(grammar.contains("Num: () = \"n\" => ();\n"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Left recursion, direct and through other rules.
+//!
+//! A rule is left-recursive when it can derive a sentential form starting
+//! with itself. No LL(k) parser handles that, and [`Bnf::is_ll1`] reports it
+//! as overlapping FIRST sets without saying why. [`left_recursion`] follows
+//! the symbols each production can start with, skipping nullable ones, and
+//! reports every cycle back to a rule with the productions along it.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::left_recursion::left_recursion;
+//!
+//! let bnf = Sebnf::parse_str("expr := expr \"+\" term | term.\nterm := /[0-9]+/.", "g")
+//!     .unwrap()
+//!     .to_bnf();
+//! let cycles = left_recursion(&bnf);
+//! assert_eq!(cycles.len(), 1);
+//! assert!(cycles[0].is_direct());
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::nullable::{is_empty_regex, nullable_rules};
+use crate::sets::format_production;
+use crate::symbols::Symbols;
+
+/// A rule's production that leads to the next rule of a cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeftStep {
+    pub rule: String,
+    pub production: Vec<Item>,
+    pub next: String,
+}
+
+/// Rules that each start with the next, the last with the first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeftCycle {
+    /// Starting at the rule of the cycle that comes first in the grammar
+    pub steps: Vec<LeftStep>,
+}
+
+impl LeftCycle {
+    /// Whether a rule starts with itself
+    pub fn is_direct(&self) -> bool {
+        self.steps.len() == 1
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &str> {
+        self.steps.iter().map(|step| step.rule.as_str())
+    }
+}
+
+impl fmt::Display for LeftCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = Symbols::with_current(|s| s.glyph("→", "->"));
+        let kind = match self.is_direct() {
+            true => "Direct",
+            false => "Indirect",
+        };
+        let mut rules: Vec<&str> = self.rules().collect();
+        rules.extend(self.steps.first().map(|step| step.rule.as_str()));
+        writeln!(
+            f,
+            "{} left recursion: {}",
+            kind,
+            rules.join(&format!(" {} ", arrow))
+        )?;
+        for step in &self.steps {
+            writeln!(
+                f,
+                "   {} := {}",
+                step.rule,
+                format_production(&step.production)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The left-recursive cycles of `bnf`: for every left-recursive rule, a
+/// shortest cycle through it.
+pub fn left_recursion(bnf: &Bnf) -> Vec<LeftCycle> {
+    let nullable = nullable_rules(bnf);
+    // The rules each rule can start with, and the production that does
+    let mut edges: Edges = HashMap::new();
+    for (rule, productions) in &bnf.rules {
+        let rule_edges = edges.entry(rule).or_default();
+        for production in productions {
+            for item in production {
+                match item {
+                    Item::NonTerminal(nt) if bnf.rules.contains_key(nt) => {
+                        if !rule_edges.iter().any(|(next, _)| next == nt) {
+                            rule_edges.push((nt, production));
+                        }
+                        if !nullable.is_nullable(nt) {
+                            break;
+                        }
+                    }
+                    item if is_empty_regex(item) => {}
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    (shortest_cycles(bnf, &edges).into_iter())
+        .map(|steps| LeftCycle { steps })
+        .collect()
+}
+
+/// Rules an edge leads to from each rule, with the production it stands for
+pub(crate) type Edges<'a> = HashMap<&'a str, Vec<(&'a str, &'a Vec<Item>)>>;
+
+/// For every rule on a cycle of `edges`, a shortest cycle through it,
+/// unless an earlier rule's cycle already passes through it the same way.
+/// Each cycle starts at its rule that comes first in the grammar.
+pub(crate) fn shortest_cycles(bnf: &Bnf, edges: &Edges<'_>) -> Vec<Vec<LeftStep>> {
+    let mut cycles: Vec<Vec<LeftStep>> = Vec::new();
+    let order: HashMap<&str, usize> = (bnf.rules.keys().enumerate())
+        .map(|(i, rule)| (rule.as_str(), i))
+        .collect();
+    for start in bnf.rules.keys() {
+        // Breadth-first search for the shortest way back to `start`
+        let mut previous: HashMap<&str, (&str, &Vec<Item>)> = HashMap::new();
+        let mut queue = VecDeque::from([start.as_str()]);
+        let mut closing = None;
+        'search: while let Some(rule) = queue.pop_front() {
+            for &(next, production) in edges.get(rule).into_iter().flatten() {
+                if next == start {
+                    closing = Some((rule, production));
+                    break 'search;
+                }
+                if !previous.contains_key(next) {
+                    previous.insert(next, (rule, production));
+                    queue.push_back(next);
+                }
+            }
+        }
+        let Some((mut rule, production)) = closing else {
+            continue;
+        };
+        let mut steps = vec![LeftStep {
+            rule: rule.to_string(),
+            production: production.clone(),
+            next: start.clone(),
+        }];
+        while rule != start {
+            let (before, production) = previous[rule];
+            steps.push(LeftStep {
+                rule: before.to_string(),
+                production: production.clone(),
+                next: rule.to_string(),
+            });
+            rule = before;
+        }
+        steps.reverse();
+        let first = (0..steps.len())
+            .min_by_key(|&i| order[steps[i].rule.as_str()])
+            .unwrap_or(0);
+        steps.rotate_left(first);
+        if !cycles.contains(&steps) {
+            cycles.push(steps);
+        }
+    }
+    cycles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn reports_indirect_cycles_through_nullable_prefixes() {
+        let source = "a := b \"x\" | \"y\".\nb := c a | \"z\".\nc := [ \"w\" ].";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let cycles = left_recursion(&bnf);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(
+            cycles[0].to_string(),
+            "Indirect left recursion: a → b → a\n   \
+             a := b \"x\"\n   \
+             b := c a\n"
+        );
+    }
+}
@@ -24,6 +24,14 @@ pub enum Token {
     BracketCurlyClose,
     #[token("|")]
     Separator,
+    #[token(",")]
+    Comma,
+    #[token(":")]
+    Colon,
+    /// The empty sequence, written out
+    #[token("ε")]
+    #[token("EPSILON")]
+    Epsilon,
 
     #[regex(r"\r?\n")]
     NewLine,
@@ -33,6 +41,9 @@ pub enum Token {
     Terminal(String),
     #[regex(r"\/(?:[^\/\\]|\\.)*?\/", |lex| lex.slice().to_string())]
     Regex(String),
+    /// Annotation name without the `@`, e.g. `max_depth`
+    #[regex(r"@[A-Za-z_]+", |lex| lex.slice()[1..].to_string())]
+    Annotation(String),
 }
 
 impl fmt::Display for Token {
@@ -47,10 +58,14 @@ impl fmt::Display for Token {
             Token::BracketCurlyOpen => write!(f, "'{{'"),
             Token::BracketCurlyClose => write!(f, "'}}'"),
             Token::Separator => write!(f, "'|'"),
+            Token::Comma => write!(f, "','"),
+            Token::Colon => write!(f, "':'"),
+            Token::Epsilon => write!(f, "'ε'"),
             Token::NewLine => write!(f, "newline"),
             Token::NonTerminal(s) => write!(f, "non-terminal '{}'", s),
             Token::Terminal(s) => write!(f, "terminal \"{}\"", s),
             Token::Regex(s) => write!(f, "regex /{}/", s),
+            Token::Annotation(s) => write!(f, "annotation '@{}'", s),
         }
     }
 }
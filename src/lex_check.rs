@@ -0,0 +1,141 @@
+//! Lexical ambiguities between any two tokens of a grammar.
+//!
+//! The LL(1) check only compares the tokens that meet in a FIRST or FOLLOW
+//! set, but a lexer sees every token everywhere. [`lex_check`] compares every
+//! pair of distinct terminals and regexes, and reports each pair that matches
+//! a common string with the shortest such string. A terminal that a regex
+//! also matches is a keyword the regex never yields, since lexers give
+//! terminals precedence; two regexes matching the same string are ambiguous,
+//! and which one wins depends on the lexer.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::lex_check::lex_check;
+//!
+//! let sebnf = Sebnf::parse_str("s := \"if\" id | num.\nid := /[a-z]+/.\nnum := /[0-9a-f]+/.", "g").unwrap();
+//! let overlaps = lex_check(&sebnf).unwrap();
+//! assert_eq!(overlaps.len(), 2);
+//! assert!(overlaps[0].is_ambiguous());
+//! assert_eq!(overlaps[1].witness, "if");
+//! ```
+
+use std::fmt;
+
+use crate::regex_intersect::{Error as RegexError, do_regexs_intersect};
+use crate::sebnf::Sebnf;
+use crate::sets::{strip_regex_delimiters, strip_terminal_quotes};
+use crate::vocabulary::{Token, TokenKind, vocabulary};
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum LexCheckError {
+    #[error("invalid regex pattern in grammar: {pattern}")]
+    #[diagnostic(code(sebnf::invalid_regex))]
+    InvalidRegex {
+        pattern: String,
+        #[source]
+        source: RegexError,
+    },
+}
+
+/// Two tokens that match a common string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexOverlap {
+    /// A terminal, or the regex that comes first by spelling
+    pub first: Token,
+    /// Always a regex
+    pub second: Token,
+    /// The shortest string both match
+    pub witness: String,
+}
+
+impl LexOverlap {
+    /// Whether neither token is a terminal, so no precedence decides
+    pub fn is_ambiguous(&self) -> bool {
+        self.first.kind == TokenKind::Regex
+    }
+}
+
+impl fmt::Display for LexOverlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_ambiguous() {
+            true => writeln!(
+                f,
+                "{} and {} both match {:?}",
+                self.first.symbol, self.second.symbol, self.witness
+            )?,
+            false => writeln!(
+                f,
+                "{} shadows {} on {:?}",
+                self.first.symbol, self.second.symbol, self.witness
+            )?,
+        }
+        for token in [&self.first, &self.second] {
+            let rules: Vec<_> = token.rules.iter().map(|r| format!("'{}'", r)).collect();
+            writeln!(f, "   {} in {}", token.symbol, rules.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Every pair of tokens of `sebnf` that match a common string, in the order
+/// of [`vocabulary`]. Two terminals never overlap, as distinct terminals are
+/// distinct strings.
+pub fn lex_check(sebnf: &Sebnf) -> Result<Vec<LexOverlap>, LexCheckError> {
+    let tokens = vocabulary(sebnf);
+    let pattern = |token: &Token| match token.kind {
+        TokenKind::Terminal => regex_syntax::escape(strip_terminal_quotes(&token.symbol)),
+        TokenKind::Regex => strip_regex_delimiters(&token.symbol).to_string(),
+    };
+    let mut overlaps = Vec::new();
+    for (i, first) in tokens.iter().enumerate() {
+        // Terminals against every regex, regexes against those sorted after
+        // them, so each pair is compared once
+        let regexes = (tokens.iter().enumerate()).filter(|&(j, token)| {
+            token.kind == TokenKind::Regex
+                && (j > i || (j < i && first.kind == TokenKind::Terminal))
+        });
+        for (_, second) in regexes {
+            let invalid = |token: &Token| {
+                let pattern = token.symbol.clone();
+                move |source| LexCheckError::InvalidRegex { pattern, source }
+            };
+            let witness = match do_regexs_intersect(&pattern(first), &pattern(second)) {
+                Ok(witness) => witness,
+                Err(source @ RegexError::InvalidRegexA(_)) => return Err(invalid(first)(source)),
+                Err(source) => return Err(invalid(second)(source)),
+            };
+            if let Some(witness) = witness {
+                overlaps.push(LexOverlap {
+                    first: first.clone(),
+                    second: second.clone(),
+                    witness,
+                });
+            }
+        }
+    }
+    Ok(overlaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_keywords_and_ambiguous_regexes() {
+        let source = "s := \"let\" id \"=\" num | id.\nid := /[a-z_]+/.\nnum := /[0-9]+|_/.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let overlaps = lex_check(&sebnf).unwrap();
+        let text: Vec<_> = overlaps.iter().map(|o| o.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "/[0-9]+|_/ and /[a-z_]+/ both match \"_\"\n   \
+                 /[0-9]+|_/ in 'num'\n   \
+                 /[a-z_]+/ in 'id'\n",
+                "\"let\" shadows /[a-z_]+/ on \"let\"\n   \
+                 \"let\" in 's'\n   \
+                 /[a-z_]+/ in 'id'\n",
+            ]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Parsing and analysis of SEBNF grammars.
+//!
+//! The usual pipeline is: parse the SEBNF source with [`Sebnf::parse_str`],
+//! convert it to plain BNF with [`Sebnf::to_bnf`], then compute the
+//! FIRST/FOLLOW [`Sets`] or check the grammar with [`Bnf::is_ll1`].
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//!
+//! let grammar = "list := \"[\" [ item { \",\" item } ] \"]\".\nitem := /[0-9]+/.";
+//! let sebnf = Sebnf::parse_str(grammar, "list.sebnf").unwrap();
+//! let bnf = sebnf.to_bnf();
+//!
+//! let sets = bnf.first_and_follow_sets();
+//! assert_eq!(sets.first["list"].len(), 1);
+//!
+//! let result = bnf.is_ll1(false).unwrap();
+//! assert!(result.is_ll1());
+//! ```
+
+pub mod analysis;
+pub mod annotate;
+pub mod api;
+pub mod bnf;
+pub mod changelog;
+pub mod codegen;
+pub mod compare;
+pub mod config;
+pub mod converter;
+pub mod cycles;
+pub mod cyk;
+pub mod description;
+pub mod disjoint;
+pub mod document;
+pub mod earley;
+pub mod encoding;
+pub mod equiv;
+pub mod explain;
+pub mod finite;
+pub mod format;
+pub mod fuzz;
+pub mod glr;
+pub mod graph;
+pub mod html_report;
+pub mod interpret;
+pub mod keywords;
+pub mod lalrpop;
+pub mod left_recursion;
+pub mod lex;
+pub mod lex_check;
+pub mod lookahead;
+pub mod lr;
+pub mod merge;
+pub mod nom;
+pub mod nullable;
+pub mod peg;
+pub mod productivity;
+pub mod query;
+pub mod railroad;
+pub mod reachability;
+pub mod recursion;
+pub mod regex_intersect;
+pub mod regex_usage;
+pub mod render;
+pub mod repair;
+pub mod resugar;
+pub mod sarif;
+pub mod sebnf;
+pub mod sections;
+pub mod self_check;
+pub mod sentences;
+pub mod sets;
+pub mod snapshot;
+pub mod symbols;
+pub mod table;
+pub mod token_class;
+pub mod trace;
+pub mod transform;
+pub mod tree;
+pub mod tree_sitter;
+pub mod typed_ast;
+pub mod visit;
+pub mod vocabulary;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use bnf::Bnf;
+pub use sebnf::{ParseError, ParseErrors, Sebnf};
+pub use sets::{Ll1Conflict, Ll1Error, Ll1Result, Sets};
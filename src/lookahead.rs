@@ -0,0 +1,737 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::regex_intersect::regex_matches_empty;
+use crate::render::{RenderError, render_display};
+use crate::sets::{
+    Ll1Error, SetItem, SetItemConflict, check_item_conflict, format_production,
+    strip_regex_delimiters,
+};
+use crate::symbols::Symbols;
+
+/// A lookahead string of at most k tokens. Strings shorter than k end with
+/// `SetItem::EndOfInput` once FOLLOW information has been mixed in.
+pub type Lookahead = Vec<SetItem>;
+
+/// FIRST_k and FOLLOW_k sets of every non-terminal.
+#[derive(Debug, Clone)]
+pub struct LookaheadSets {
+    pub k: usize,
+    pub first: HashMap<String, HashSet<Lookahead>>,
+    pub follow: HashMap<String, HashSet<Lookahead>>,
+}
+
+/// Outcome of trying to resolve a conflict with more lookahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LookaheadResolution {
+    /// The conflict disappears with k tokens of lookahead.
+    ResolvedAt(usize),
+    /// The conflict persists for every k up to (and including) this bound.
+    Unresolved(usize),
+}
+
+impl fmt::Display for LookaheadResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookaheadResolution::ResolvedAt(k) => write!(f, "resolvable with LL({})", k),
+            LookaheadResolution::Unresolved(k) => {
+                let at_most = Symbols::with_current(|s| s.glyph("≤", "<="));
+                write!(f, "not resolvable with any k {} {}", at_most, k)
+            }
+        }
+    }
+}
+
+/// Ambiguous lookahead strings listed by `Display`; the rest are counted
+const MAX_SHOWN_SEQUENCES: usize = 10;
+
+/// The lookahead on which the two productions of a conflict still overlap,
+/// i.e. the token sequences a hand-written parser has to treat specially.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AmbiguityWindow {
+    /// Length of the longest ambiguous lookahead
+    pub tokens: usize,
+    /// Every ambiguous lookahead of that length (shorter ones end in `$`),
+    /// given token by token as the overlap of the two productions' items
+    pub sequences: Vec<Vec<SetItemConflict>>,
+    /// Whether one more token tells the productions apart; false if the
+    /// search bound was reached
+    pub resolved: bool,
+}
+
+impl AmbiguityWindow {
+    pub fn to_json(&self) -> serde_json::Value {
+        let sequences: Vec<Vec<String>> = self
+            .sequences
+            .iter()
+            .map(|sequence| sequence.iter().map(overlap).collect())
+            .collect();
+        serde_json::json!({
+            "tokens": self.tokens,
+            "resolved": self.resolved,
+            "sequences": sequences,
+        })
+    }
+}
+
+/// One token of an ambiguous lookahead: the item, or both items if they differ
+fn overlap(conflict: &SetItemConflict) -> String {
+    if conflict.item1 == conflict.item2 {
+        conflict.item1.to_string()
+    } else {
+        let intersection = Symbols::with_current(|s| s.glyph("∩", "&"));
+        format!("({} {} {})", conflict.item1, intersection, conflict.item2)
+    }
+}
+
+impl fmt::Display for AmbiguityWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at_most = Symbols::with_current(|s| s.glyph("≤", "<="));
+        write!(f, "ambiguous for {} token(s)", self.tokens)?;
+        if self.resolved {
+            write!(f, ", resolved by token {}", self.tokens + 1)?;
+        } else {
+            write!(f, " or more (searched {} {})", at_most, self.tokens)?;
+        }
+        for sequence in self.sequences.iter().take(MAX_SHOWN_SEQUENCES) {
+            let tokens: Vec<_> = sequence.iter().map(overlap).collect();
+            write!(f, "\n       {}", tokens.join(" "))?;
+        }
+        if self.sequences.len() > MAX_SHOWN_SEQUENCES {
+            write!(
+                f,
+                "\n       ... and {} more",
+                self.sequences.len() - MAX_SHOWN_SEQUENCES
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Two productions of a rule that k tokens of lookahead cannot tell apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LlkConflict {
+    pub non_terminal: String,
+    pub production_indices: (usize, usize),
+    pub productions: (Vec<Item>, Vec<Item>),
+    /// Every lookahead of up to k tokens both productions can start with,
+    /// token by token; shorter ones end in `$`
+    pub sequences: Vec<Vec<SetItemConflict>>,
+}
+
+impl LlkConflict {
+    pub fn to_json(&self) -> serde_json::Value {
+        let sequences: Vec<Vec<String>> = self
+            .sequences
+            .iter()
+            .map(|sequence| sequence.iter().map(overlap).collect())
+            .collect();
+        serde_json::json!({
+            "non_terminal": self.non_terminal,
+            "productions": [
+                format_production(&self.productions.0),
+                format_production(&self.productions.1),
+            ],
+            "production_indices": [self.production_indices.0, self.production_indices.1],
+            "sequences": sequences,
+        })
+    }
+}
+
+impl fmt::Display for LlkConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Non-terminal '{}'", self.non_terminal)?;
+        writeln!(
+            f,
+            "   Production 1: {}",
+            format_production(&self.productions.0)
+        )?;
+        writeln!(
+            f,
+            "   Production 2: {}",
+            format_production(&self.productions.1)
+        )?;
+        writeln!(f, "   Lookahead both start with:")?;
+        for sequence in self.sequences.iter().take(MAX_SHOWN_SEQUENCES) {
+            let tokens: Vec<_> = sequence.iter().map(overlap).collect();
+            writeln!(f, "     - {}", tokens.join(" "))?;
+        }
+        if self.sequences.len() > MAX_SHOWN_SEQUENCES {
+            writeln!(
+                f,
+                "     ... and {} more",
+                self.sequences.len() - MAX_SHOWN_SEQUENCES
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of [`Bnf::is_llk`], conflicts in rule and production order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LlkResult {
+    pub k: usize,
+    pub conflicts: Vec<LlkConflict>,
+}
+
+impl LlkResult {
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
+
+    pub fn is_llk(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    /// JSON object `{"k": k, "llk": bool, "conflicts": [...]}`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "k": self.k,
+            "llk": self.is_llk(),
+            "conflicts": self.conflicts.iter().map(LlkConflict::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for LlkResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.conflicts.is_empty() {
+            return writeln!(f, "Grammar is LL({})", self.k);
+        }
+        writeln!(
+            f,
+            "Grammar is NOT LL({}). Found {} conflict(s):",
+            self.k,
+            self.conflicts.len()
+        )?;
+        for (i, conflict) in self.conflicts.iter().enumerate() {
+            write!(f, "\n{}. {}", i + 1, conflict)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every pair of overlapping strings of `set1` and `set2`, sorted.
+pub fn ambiguous_lookahead(
+    set1: &HashSet<Lookahead>,
+    set2: &HashSet<Lookahead>,
+    ignore_regex_conflicts: bool,
+) -> Result<Vec<Vec<SetItemConflict>>, Ll1Error> {
+    let mut left: Vec<_> = set1.iter().collect();
+    let mut right: Vec<_> = set2.iter().collect();
+    left.sort();
+    right.sort();
+    let mut walk = PrefixWalk {
+        ignore_regex_conflicts,
+        checked: HashMap::new(),
+        sequences: Vec::new(),
+    };
+    walk.walk(&left, &right, &mut Vec::new())?;
+    let mut sequences = walk.sequences;
+    sequences.sort();
+    sequences.dedup();
+    Ok(sequences)
+}
+
+/// k-bounded concatenation: every `x ++ y` truncated to k tokens.
+/// Strings that are already complete (k long or terminated by `$`) are kept as-is.
+pub fn k_concat(
+    left: &HashSet<Lookahead>,
+    right: &HashSet<Lookahead>,
+    k: usize,
+) -> HashSet<Lookahead> {
+    let mut result = HashSet::new();
+    for x in left {
+        if x.len() >= k || x.last() == Some(&SetItem::EndOfInput) {
+            result.insert(x.clone());
+            continue;
+        }
+        for y in right {
+            let mut joined = x.clone();
+            joined.extend(y.iter().take(k - x.len()).cloned());
+            result.insert(joined);
+        }
+    }
+    result
+}
+
+/// FIRST_k of a sequence of items, given the FIRST_k sets of the non-terminals.
+pub fn first_k_of_sequence(
+    sequence: &[Item],
+    first_sets: &HashMap<String, HashSet<Lookahead>>,
+    k: usize,
+) -> HashSet<Lookahead> {
+    let mut result: HashSet<Lookahead> = HashSet::from([Vec::new()]);
+
+    for item in sequence {
+        let item_first = match item {
+            Item::Terminal(s) => Cow::Owned(HashSet::from([vec![SetItem::Terminal(s.clone())]])),
+            Item::Regex(pattern) => {
+                let mut set = HashSet::from([vec![SetItem::Regex(pattern.clone())]]);
+                // Regexes matching "" may be skipped, as in `first_of_sequence`
+                if regex_matches_empty(strip_regex_delimiters(pattern)) {
+                    set.insert(Vec::new());
+                }
+                Cow::Owned(set)
+            }
+            Item::TokenClass(class) => {
+                Cow::Owned(HashSet::from([vec![SetItem::TokenClass(class.clone())]]))
+            }
+            Item::EndOfInput => Cow::Owned(HashSet::from([vec![SetItem::EndOfInput]])),
+            Item::NonTerminal(nt) => first_sets
+                .get(nt)
+                .map_or_else(|| Cow::Owned(HashSet::new()), Cow::Borrowed),
+        };
+        result = k_concat(&result, &item_first, k);
+        if result.iter().all(|s| s.len() >= k) {
+            break;
+        }
+    }
+
+    result
+}
+
+pub fn extract_lookahead_sets(bnf: &Bnf, k: usize) -> LookaheadSets {
+    let k = k.max(1);
+    let first_sets = first_k_sets(bnf, k);
+    let follow_sets = follow_k_sets(bnf, &first_sets, k);
+    LookaheadSets {
+        k,
+        first: first_sets,
+        follow: follow_sets,
+    }
+}
+
+/// Adds `strings` to the set of `nt`, recording the new ones as its delta and
+/// queueing `nt` if it has no pending delta yet
+fn propagate(
+    sets: &mut HashMap<String, HashSet<Lookahead>>,
+    deltas: &mut HashMap<String, HashSet<Lookahead>>,
+    worklist: &mut VecDeque<String>,
+    nt: &str,
+    strings: HashSet<Lookahead>,
+) {
+    let Some(set) = sets.get_mut(nt) else {
+        return;
+    };
+    let delta = deltas.entry(nt.to_string()).or_default();
+    let was_pending = !delta.is_empty();
+    for string in strings {
+        if !set.contains(&string) {
+            set.insert(string.clone());
+            delta.insert(string);
+        }
+    }
+    if !was_pending && !delta.is_empty() {
+        worklist.push_back(nt.to_string());
+    }
+}
+
+/// Where a non-terminal is used: the rule, the production and the index in it
+type Occurrence<'a> = (&'a str, &'a [Item], usize);
+
+/// FIRST_k of every non-terminal. Only the strings a non-terminal gained since
+/// it was last processed are pushed into the productions using it.
+fn first_k_sets(bnf: &Bnf, k: usize) -> HashMap<String, HashSet<Lookahead>> {
+    let mut first_sets: HashMap<String, HashSet<Lookahead>> = bnf
+        .rules
+        .keys()
+        .map(|nt| (nt.clone(), HashSet::new()))
+        .collect();
+    let mut uses: HashMap<&str, Vec<Occurrence>> = HashMap::new();
+    for (lhs, productions) in &bnf.rules {
+        for production in productions {
+            for (i, item) in production.iter().enumerate() {
+                if let Item::NonTerminal(nt) = item {
+                    uses.entry(nt).or_default().push((lhs, production, i));
+                }
+            }
+        }
+    }
+
+    let mut deltas = HashMap::new();
+    let mut worklist = VecDeque::new();
+    for (lhs, productions) in &bnf.rules {
+        for production in productions {
+            let firsts = first_k_of_sequence(production, &first_sets, k);
+            propagate(&mut first_sets, &mut deltas, &mut worklist, lhs, firsts);
+        }
+    }
+    while let Some(nt) = worklist.pop_front() {
+        let delta = deltas.remove(&nt).unwrap_or_default();
+        for &(lhs, production, i) in uses.get(nt.as_str()).into_iter().flatten() {
+            let before = first_k_of_sequence(&production[..i], &first_sets, k);
+            let through = k_concat(&before, &delta, k);
+            let firsts = k_concat(
+                &through,
+                &first_k_of_sequence(&production[i + 1..], &first_sets, k),
+                k,
+            );
+            propagate(&mut first_sets, &mut deltas, &mut worklist, lhs, firsts);
+        }
+    }
+    first_sets
+}
+
+/// FOLLOW_k of every non-terminal, given their FIRST_k sets. For A -> αBβ,
+/// FOLLOW_k(B) ∪= FIRST_k(β) ·k FOLLOW_k(A), where FIRST_k(β) is computed once
+/// and only the strings FOLLOW_k(A) gained are concatenated to it.
+fn follow_k_sets(
+    bnf: &Bnf,
+    first_sets: &HashMap<String, HashSet<Lookahead>>,
+    k: usize,
+) -> HashMap<String, HashSet<Lookahead>> {
+    let mut follow_sets: HashMap<String, HashSet<Lookahead>> = bnf
+        .rules
+        .keys()
+        .map(|nt| (nt.clone(), HashSet::new()))
+        .collect();
+    // For every rule, the non-terminals in its productions and the strings of
+    // FIRST_k of what follows them that FOLLOW_k of the rule still extends
+    let mut followers: HashMap<&str, Vec<(&str, HashSet<Lookahead>)>> = HashMap::new();
+    let mut deltas = HashMap::new();
+    let mut worklist = VecDeque::new();
+    for (lhs, productions) in &bnf.rules {
+        for production in productions {
+            for (i, item) in production.iter().enumerate() {
+                let Item::NonTerminal(current_nt) = item else {
+                    continue;
+                };
+                let (complete, beta_first): (HashSet<_>, HashSet<_>) =
+                    first_k_of_sequence(&production[i + 1..], first_sets, k)
+                        .into_iter()
+                        .partition(|s| s.len() >= k || s.last() == Some(&SetItem::EndOfInput));
+                propagate(
+                    &mut follow_sets,
+                    &mut deltas,
+                    &mut worklist,
+                    current_nt,
+                    complete,
+                );
+                if !beta_first.is_empty() {
+                    followers
+                        .entry(lhs)
+                        .or_default()
+                        .push((current_nt, beta_first));
+                }
+            }
+        }
+    }
+    if let Some((start_symbol, _)) = bnf.rules.first() {
+        let end = HashSet::from([vec![SetItem::EndOfInput]]);
+        propagate(
+            &mut follow_sets,
+            &mut deltas,
+            &mut worklist,
+            start_symbol,
+            end,
+        );
+    }
+
+    while let Some(nt) = worklist.pop_front() {
+        let delta = deltas.remove(&nt).unwrap_or_default();
+        for (current_nt, beta_first) in followers.get(nt.as_str()).into_iter().flatten() {
+            let additions = k_concat(beta_first, &delta, k);
+            propagate(
+                &mut follow_sets,
+                &mut deltas,
+                &mut worklist,
+                current_nt,
+                additions,
+            );
+        }
+    }
+    follow_sets
+}
+
+impl LookaheadSets {
+    /// The k-token lookahead strings that select `production` of `non_terminal`
+    /// (FIRST_k(production) ·k FOLLOW_k(non_terminal)).
+    pub fn production_lookahead(
+        &self,
+        non_terminal: &str,
+        production: &[Item],
+    ) -> HashSet<Lookahead> {
+        let first = first_k_of_sequence(production, &self.first, self.k);
+        let follow = self.follow.get(non_terminal).cloned().unwrap_or_default();
+        k_concat(&first, &follow, self.k)
+    }
+}
+
+/// Checks whether two lookahead strings can be matched by the same input.
+///
+/// Returns the per-token conflicts if every position overlaps, `None` otherwise.
+pub fn sequences_conflict(
+    seq1: &[SetItem],
+    seq2: &[SetItem],
+    ignore_regex_conflicts: bool,
+) -> Result<Option<Vec<SetItemConflict>>, Ll1Error> {
+    let mut conflicts = Vec::new();
+    for (item1, item2) in seq1.iter().zip(seq2) {
+        match check_item_conflict(item1, item2, ignore_regex_conflicts)? {
+            Some(conflict) => conflicts.push(conflict),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(conflicts))
+}
+
+/// Overlapping strings of two lookahead sets, found by walking their common
+/// prefixes together instead of comparing every pair of strings. Strings
+/// sharing a prefix are adjacent once sorted, so each group of strings
+/// continuing with the same token is checked against the other side's
+/// groups once.
+struct PrefixWalk<'a> {
+    ignore_regex_conflicts: bool,
+    /// [`check_item_conflict`] of every pair of tokens checked so far
+    checked: HashMap<(&'a SetItem, &'a SetItem), Option<SetItemConflict>>,
+    sequences: Vec<Vec<SetItemConflict>>,
+}
+
+impl<'a> PrefixWalk<'a> {
+    fn check(
+        &mut self,
+        item1: &'a SetItem,
+        item2: &'a SetItem,
+    ) -> Result<Option<SetItemConflict>, Ll1Error> {
+        if let Some(conflict) = self.checked.get(&(item1, item2)) {
+            return Ok(conflict.clone());
+        }
+        let conflict = check_item_conflict(item1, item2, self.ignore_regex_conflicts)?;
+        self.checked.insert((item1, item2), conflict.clone());
+        Ok(conflict)
+    }
+
+    /// Walks sorted strings that agree with `prefix` on their first
+    /// `prefix.len()` tokens
+    fn walk(
+        &mut self,
+        left: &[&'a Lookahead],
+        right: &[&'a Lookahead],
+        prefix: &mut Vec<SetItemConflict>,
+    ) -> Result<(), Ll1Error> {
+        let depth = prefix.len();
+        // Sorting puts strings ending here first; they overlap anything
+        // longer, as `sequences_conflict` stops at the shorter string
+        let left_ended = left.iter().take_while(|s| s.len() == depth).count();
+        let right_ended = right.iter().take_while(|s| s.len() == depth).count();
+        if (left_ended > 0 && !right.is_empty()) || (right_ended > 0 && !left.is_empty()) {
+            self.sequences.push(prefix.clone());
+        }
+        let left_groups = groups(&left[left_ended..], depth);
+        let right_groups = groups(&right[right_ended..], depth);
+        for left_group in &left_groups {
+            for right_group in &right_groups {
+                let item1 = &left_group[0][depth];
+                let item2 = &right_group[0][depth];
+                if let Some(conflict) = self.check(item1, item2)? {
+                    prefix.push(conflict);
+                    self.walk(left_group, right_group, prefix)?;
+                    prefix.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits sorted strings into runs with the same token at `depth`
+fn groups<'s, 'a>(strings: &'s [&'a Lookahead], depth: usize) -> Vec<&'s [&'a Lookahead]> {
+    strings.chunk_by(|s1, s2| s1[depth] == s2[depth]).collect()
+}
+
+/// Returns true if any string of `set1` overlaps any string of `set2`.
+pub fn lookahead_sets_conflict(
+    set1: &HashSet<Lookahead>,
+    set2: &HashSet<Lookahead>,
+    ignore_regex_conflicts: bool,
+) -> Result<bool, Ll1Error> {
+    Ok(!ambiguous_lookahead(set1, set2, ignore_regex_conflicts)?.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use super::*;
+
+    fn t(s: &str) -> Item {
+        Item::Terminal(format!("\"{}\"", s))
+    }
+
+    fn nt(s: &str) -> Item {
+        Item::NonTerminal(s.to_string())
+    }
+
+    #[test]
+    fn k_concat_truncates_and_stops_at_end_of_input() {
+        let a = SetItem::Terminal("\"a\"".to_string());
+        let b = SetItem::Terminal("\"b\"".to_string());
+        let left = HashSet::from([vec![a.clone()], vec![SetItem::EndOfInput]]);
+        let right = HashSet::from([vec![b.clone(), b.clone()]]);
+
+        let result = k_concat(&left, &right, 2);
+        assert_eq!(
+            result,
+            HashSet::from([vec![a, b], vec![SetItem::EndOfInput]])
+        );
+    }
+
+    #[test]
+    fn sets_reach_through_recursion() {
+        // S := L "z".  L := "x" L | "y".
+        let mut rules = IndexMap::new();
+        rules.insert("S".to_string(), vec![vec![nt("L"), t("z")]]);
+        rules.insert("L".to_string(), vec![vec![t("x"), nt("L")], vec![t("y")]]);
+        let bnf = Bnf {
+            rules,
+            spans: IndexMap::new(),
+        };
+        let x = SetItem::Terminal("\"x\"".to_string());
+        let y = SetItem::Terminal("\"y\"".to_string());
+        let z = SetItem::Terminal("\"z\"".to_string());
+
+        let sets = extract_lookahead_sets(&bnf, 3);
+        assert_eq!(
+            sets.first["L"],
+            HashSet::from([
+                vec![y.clone()],
+                vec![x.clone(), y.clone()],
+                vec![x.clone(), x.clone(), y.clone()],
+                vec![x.clone(), x.clone(), x.clone()],
+            ])
+        );
+        assert_eq!(
+            sets.first["S"],
+            HashSet::from([
+                vec![y.clone(), z.clone()],
+                vec![x.clone(), y.clone(), z.clone()],
+                vec![x.clone(), x.clone(), y],
+                vec![x.clone(), x.clone(), x],
+            ])
+        );
+        assert_eq!(
+            sets.follow["L"],
+            HashSet::from([vec![z, SetItem::EndOfInput]])
+        );
+    }
+
+    #[test]
+    fn ambiguous_lookahead_stops_at_the_shorter_string() {
+        let a = SetItem::Terminal("\"a\"".to_string());
+        let b = SetItem::Terminal("\"b\"".to_string());
+        let c = SetItem::Terminal("\"c\"".to_string());
+        let set1 = HashSet::from([vec![a.clone(), b.clone()], vec![a.clone(), c.clone()]]);
+        let set2 = HashSet::from([vec![a.clone()], vec![b.clone(), c]]);
+
+        let sequences = ambiguous_lookahead(&set1, &set2, false).unwrap();
+        assert_eq!(sequences.len(), 1);
+        assert_eq!(sequences[0].len(), 1);
+        assert_eq!(sequences[0][0].item1, a);
+        assert!(!lookahead_sets_conflict(&set1, &HashSet::from([vec![b]]), false).unwrap());
+    }
+
+    #[test]
+    fn lookahead_separates_common_prefix() {
+        // S := "a" "b" | "a" "c".
+        let mut rules = IndexMap::new();
+        rules.insert(
+            "S".to_string(),
+            vec![vec![t("a"), t("b")], vec![t("a"), t("c")]],
+        );
+        let bnf = Bnf {
+            rules,
+            spans: IndexMap::new(),
+        };
+
+        let mut result = bnf.is_ll1(false).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        bnf.annotate_lookahead(&mut result, 4, false).unwrap();
+        assert_eq!(
+            result.conflicts[0].lookahead,
+            Some(LookaheadResolution::ResolvedAt(2))
+        );
+    }
+
+    #[test]
+    fn llk_reports_the_shared_lookahead() {
+        // S := "a" "b" | "a" "c" | X.  X := "a" "b" "d".
+        let mut rules = IndexMap::new();
+        rules.insert(
+            "S".to_string(),
+            vec![vec![t("a"), t("b")], vec![t("a"), t("c")], vec![nt("X")]],
+        );
+        rules.insert("X".to_string(), vec![vec![t("a"), t("b"), t("d")]]);
+        let bnf = Bnf {
+            rules,
+            spans: IndexMap::new(),
+        };
+
+        assert_eq!(bnf.is_llk(1, false).unwrap().conflicts.len(), 3);
+        let result = bnf.is_llk(2, false).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].production_indices, (0, 2));
+        assert_eq!(
+            result.to_json()["conflicts"][0]["sequences"],
+            serde_json::json!([["a", "b"]])
+        );
+        assert!(bnf.is_llk(3, false).unwrap().is_llk());
+        assert_eq!(bnf.smallest_llk(5, false).unwrap().k, 3);
+        assert_eq!(bnf.smallest_llk(2, false).unwrap().conflicts.len(), 1);
+    }
+
+    #[test]
+    fn identical_expansions_are_never_resolved() {
+        // S := A | B.  A := "x".  B := "x".
+        let mut rules = IndexMap::new();
+        rules.insert("S".to_string(), vec![vec![nt("A")], vec![nt("B")]]);
+        rules.insert("A".to_string(), vec![vec![t("x")]]);
+        rules.insert("B".to_string(), vec![vec![t("x")]]);
+        let bnf = Bnf {
+            rules,
+            spans: IndexMap::new(),
+        };
+
+        let mut result = bnf.is_ll1(false).unwrap();
+        bnf.annotate_lookahead(&mut result, 3, false).unwrap();
+        assert_eq!(
+            result.conflicts[0].lookahead,
+            Some(LookaheadResolution::Unresolved(3))
+        );
+    }
+
+    #[test]
+    fn ambiguity_window_lists_the_shared_prefixes() {
+        // S := "a" "b" "c" | "a" "b" "d" | "a" "e".
+        let mut rules = IndexMap::new();
+        rules.insert(
+            "S".to_string(),
+            vec![
+                vec![t("a"), t("b"), t("c")],
+                vec![t("a"), t("b"), t("d")],
+                vec![t("a"), t("e")],
+            ],
+        );
+        let bnf = Bnf {
+            rules,
+            spans: IndexMap::new(),
+        };
+
+        let mut result = bnf.is_ll1(false).unwrap();
+        bnf.annotate_ambiguity(&mut result, 4, false).unwrap();
+        let window = result.conflicts[0].ambiguity.as_ref().unwrap();
+        assert_eq!((window.tokens, window.resolved), (2, true));
+        assert_eq!(
+            window.to_json()["sequences"],
+            serde_json::json!([["a", "b"]])
+        );
+        let window = result.conflicts[1].ambiguity.as_ref().unwrap();
+        assert_eq!((window.tokens, window.resolved), (1, true));
+    }
+}
@@ -0,0 +1,779 @@
+//! The LR(0) automaton of a grammar and the LR conflicts found with it.
+//!
+//! [`Bnf::lr0_automaton`] builds the canonical collection of LR(0) item sets
+//! of the grammar with a fresh start rule `___start := S $`.
+//! [`Bnf::is_slr1`] adds lookaheads to its reductions from the FOLLOW sets
+//! and reports the states where a shift and a reduction, or two reductions,
+//! apply to overlapping lookaheads, as [`Bnf::is_ll1`] reports overlapping
+//! FIRST sets. [`Bnf::is_lalr1`] does the same with the LALR(1) lookaheads:
+//! those of the LR(1) states merged into each LR(0) state, found by
+//! propagating lookaheads between the kernel items as yacc does.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//!
+//! let bnf = Sebnf::parse_str("e := e \"+\" t | t.\nt := /[0-9]+/.", "g").unwrap().to_bnf();
+//! // Left recursion rules out LL(1), but not SLR(1)
+//! assert!(!bnf.is_ll1(false)?.is_ll1());
+//! assert!(bnf.is_slr1(false)?.is_conflict_free());
+//! # Ok::<(), sebnf_tool::sets::Ll1Error>(())
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::bnf::{AUGMENTED_START, Bnf, Item};
+use crate::render::{RenderError, render_display};
+use crate::sets::{
+    Ll1Error, SetItem, SetItemConflict, check_item_conflict, first_of_sequence, format_production,
+};
+use crate::symbols::Symbols;
+
+/// A production with a dot before the symbol it expects next.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LrItem {
+    pub rule: String,
+    /// Index into the rule's productions
+    pub production: usize,
+    pub symbols: Vec<Item>,
+    /// Number of symbols before the dot
+    pub dot: usize,
+}
+
+impl LrItem {
+    pub fn next_symbol(&self) -> Option<&Item> {
+        self.symbols.get(self.dot)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.dot == self.symbols.len()
+    }
+
+    fn advanced(&self) -> LrItem {
+        LrItem {
+            dot: self.dot + 1,
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Display for LrItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (arrow, dot) = Symbols::with_current(|s| (s.glyph("→", "->"), s.glyph("•", ".")));
+        write!(f, "{} {}", self.rule, arrow)?;
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            if i == self.dot {
+                write!(f, " {}", dot)?;
+            }
+            write!(f, " {}", symbol)?;
+        }
+        if self.is_complete() {
+            write!(f, " {}", dot)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LrState {
+    /// The kernel items first, then those added by the closure
+    pub items: Vec<LrItem>,
+    pub kernel_len: usize,
+    /// The state reached by each symbol after a dot, in the order of the items
+    pub transitions: IndexMap<Item, usize>,
+    /// A shortest sequence of symbols leading to the state from state 0
+    pub prefix: Vec<Item>,
+}
+
+impl LrState {
+    pub fn kernel(&self) -> &[LrItem] {
+        &self.items[..self.kernel_len]
+    }
+}
+
+/// The canonical collection of LR(0) item sets; state 0 is the start state.
+#[derive(Debug, Clone)]
+pub struct Lr0Automaton {
+    pub states: Vec<LrState>,
+}
+
+/// Ways to add lookaheads to the reductions of the LR(0) automaton
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LrMethod {
+    /// The FOLLOW set of the reduced rule
+    Slr1,
+    /// The lookaheads of the reduced item in the LR(1) states merged into
+    /// the state
+    Lalr1,
+}
+
+impl fmt::Display for LrMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LrMethod::Slr1 => write!(f, "SLR(1)"),
+            LrMethod::Lalr1 => write!(f, "LALR(1)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LrConflictKind {
+    /// Shifting the next symbol of the `shift` items, or reducing `reduce`
+    ShiftReduce {
+        shift: Vec<LrItem>,
+        reduce: LrItem,
+    },
+    ReduceReduce {
+        reduce1: LrItem,
+        reduce2: LrItem,
+    },
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LrConflict {
+    pub state: usize,
+    pub kind: LrConflictKind,
+    /// The kernel items of the state
+    pub items: Vec<LrItem>,
+    /// A shortest sequence of symbols leading to the state
+    pub prefix: Vec<Item>,
+    /// The overlapping lookaheads, shifted symbol or first lookahead first
+    pub conflicts: Vec<SetItemConflict>,
+}
+
+impl LrConflict {
+    /// The items reduced in the conflict, whose productions it is about
+    pub fn reductions(&self) -> Vec<&LrItem> {
+        match &self.kind {
+            LrConflictKind::ShiftReduce { reduce, .. } => vec![reduce],
+            LrConflictKind::ReduceReduce { reduce1, reduce2 } => vec![reduce1, reduce2],
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let strings =
+            |items: &[LrItem]| -> Vec<String> { items.iter().map(LrItem::to_string).collect() };
+        let (kind, shift, reduce) = match &self.kind {
+            LrConflictKind::ShiftReduce { shift, reduce } => {
+                ("shift_reduce", strings(shift), vec![reduce.to_string()])
+            }
+            LrConflictKind::ReduceReduce { reduce1, reduce2 } => (
+                "reduce_reduce",
+                Vec::new(),
+                vec![reduce1.to_string(), reduce2.to_string()],
+            ),
+        };
+        serde_json::json!({
+            "state": self.state,
+            "kind": kind,
+            "items": strings(&self.items),
+            "prefix": format_production(&self.prefix),
+            "shift": shift,
+            "reduce": reduce,
+            "conflicts": self.conflicts.iter().map(SetItemConflict::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for LrConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            LrConflictKind::ShiftReduce { .. } => {
+                writeln!(f, "State {}: shift/reduce conflict", self.state)?
+            }
+            LrConflictKind::ReduceReduce { .. } => {
+                writeln!(f, "State {}: reduce/reduce conflict", self.state)?
+            }
+        }
+        writeln!(f, "   Reached by: {}", format_production(&self.prefix))?;
+        writeln!(f, "   Items:")?;
+        for item in &self.items {
+            writeln!(f, "     {}", item)?;
+        }
+        match &self.kind {
+            LrConflictKind::ShiftReduce { shift, reduce } => {
+                for item in shift {
+                    writeln!(f, "   Shift: {}", item)?;
+                }
+                writeln!(f, "   Reduce: {}", reduce)?;
+            }
+            LrConflictKind::ReduceReduce { reduce1, reduce2 } => {
+                writeln!(f, "   Reduce: {}", reduce1)?;
+                writeln!(f, "   Reduce: {}", reduce2)?;
+            }
+        }
+        writeln!(f, "   Conflicts:")?;
+        for conflict in &self.conflicts {
+            writeln!(f, "     - {}", conflict)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LrResult {
+    pub method: LrMethod,
+    /// Number of states of the LR(0) automaton
+    pub states: usize,
+    pub conflicts: Vec<LrConflict>,
+}
+
+impl LrResult {
+    pub fn is_conflict_free(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
+
+    /// JSON object `{"method": "SLR(1)", "conflict_free": bool, "states": n, "conflicts": [...]}`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "method": self.method.to_string(),
+            "conflict_free": self.is_conflict_free(),
+            "states": self.states,
+            "conflicts": self.conflicts.iter().map(LrConflict::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for LrResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.conflicts.is_empty() {
+            writeln!(f, "Grammar is {} ({} states)", self.method, self.states)?;
+        } else {
+            writeln!(
+                f,
+                "Grammar is NOT {}. Found {} conflict(s) in {} states:",
+                self.method,
+                self.conflicts.len(),
+                self.states
+            )?;
+            for (i, conflict) in self.conflicts.iter().enumerate() {
+                writeln!(f, "\n{}. {}", i + 1, conflict)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LrAction {
+    /// Go to the state
+    Shift(usize),
+    Reduce {
+        rule: String,
+        production: usize,
+    },
+    /// Shift the `$` of the start rule, which ends the parse
+    Accept,
+}
+
+/// A row of an LR parse table.
+#[derive(Debug, Clone, Default)]
+pub struct LrRow {
+    /// The actions on each lookahead, sorted by lookahead; more than one is
+    /// a conflict
+    pub actions: Vec<(SetItem, Vec<LrAction>)>,
+    /// The state after reducing to each rule
+    pub gotos: Vec<(String, usize)>,
+}
+
+/// An LR parse table, with a row per state of the LR(0) automaton.
+#[derive(Debug, Clone)]
+pub struct LrTable {
+    pub method: LrMethod,
+    pub rows: Vec<LrRow>,
+}
+
+impl LrTable {
+    pub fn has_conflicts(&self) -> bool {
+        (self.rows.iter())
+            .flat_map(|row| &row.actions)
+            .any(|(_, actions)| actions.len() > 1)
+    }
+
+    /// Renders the table, with the productions of `bnf` reduced by name
+    pub fn render(&self, bnf: &Bnf) -> Result<String, RenderError> {
+        render_display(&LrTableDisplay { table: self, bnf })
+    }
+}
+
+struct LrTableDisplay<'a> {
+    table: &'a LrTable,
+    bnf: &'a Bnf,
+}
+
+impl fmt::Display for LrTableDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = Symbols::with_current(|s| s.glyph("→", "->"));
+        for (state, row) in self.table.rows.iter().enumerate() {
+            if row.actions.is_empty() && row.gotos.is_empty() {
+                continue;
+            }
+            writeln!(f, "State {}:", state)?;
+            let width = (row
+                .actions
+                .iter()
+                .map(|(lookahead, _)| lookahead.to_string()))
+            .chain(row.gotos.iter().map(|(rule, _)| rule.clone()))
+            .map(|symbol| symbol.chars().count())
+            .max()
+            .unwrap_or(0);
+            for (lookahead, actions) in &row.actions {
+                let actions: Vec<String> = (actions.iter())
+                    .map(|action| match action {
+                        LrAction::Shift(target) => format!("shift {}", target),
+                        LrAction::Reduce { rule, production } => {
+                            let symbols = self.bnf.rules.get(rule).and_then(|p| p.get(*production));
+                            let symbols = symbols.map_or("?".to_string(), |s| format_production(s));
+                            format!("reduce {} {} {}", rule, arrow, symbols)
+                        }
+                        LrAction::Accept => "accept".to_string(),
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "  {:width$} {}",
+                    lookahead.to_string(),
+                    actions.join(", "),
+                    width = width
+                )?;
+                if actions.len() > 1 {
+                    write!(f, "  (conflict)")?;
+                }
+                writeln!(f)?;
+            }
+            for (rule, target) in &row.gotos {
+                writeln!(f, "  {:width$} goto {}", rule, target, width = width)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the automaton of `bnf`, whose first rule is `___start := S $`.
+pub(crate) fn build_lr0_automaton(bnf: &Bnf) -> Lr0Automaton {
+    let closure = |kernel: Vec<LrItem>| -> Vec<LrItem> {
+        let mut items = kernel;
+        let mut added: HashSet<&str> = HashSet::new();
+        let mut i = 0;
+        while i < items.len() {
+            if let Some(Item::NonTerminal(rule)) = items[i].next_symbol()
+                && let Some((rule, productions)) = bnf.rules.get_key_value(rule)
+                && added.insert(rule)
+            {
+                for (production, symbols) in productions.iter().enumerate() {
+                    let item = LrItem {
+                        rule: rule.clone(),
+                        production,
+                        symbols: symbols.clone(),
+                        dot: 0,
+                    };
+                    if !items.contains(&item) {
+                        items.push(item);
+                    }
+                }
+            }
+            i += 1;
+        }
+        items
+    };
+
+    let start = bnf.rules.first().map(|(rule, productions)| {
+        (productions.iter().enumerate())
+            .map(|(production, symbols)| LrItem {
+                rule: rule.clone(),
+                production,
+                symbols: symbols.clone(),
+                dot: 0,
+            })
+            .collect::<Vec<_>>()
+    });
+    let kernel = start.unwrap_or_default();
+    let mut states = vec![LrState {
+        kernel_len: kernel.len(),
+        items: closure(kernel.clone()),
+        transitions: IndexMap::new(),
+        prefix: Vec::new(),
+    }];
+    // The productions identify their symbols, so they identify the items
+    let key = |kernel: &[LrItem]| -> Vec<(String, usize, usize)> {
+        let mut key: Vec<_> = (kernel.iter())
+            .map(|item| (item.rule.clone(), item.production, item.dot))
+            .collect();
+        key.sort();
+        key
+    };
+    let mut by_kernel = HashMap::from([(key(&kernel), 0)]);
+
+    // Breadth first, so the first prefix found for a state is a shortest one
+    let mut queue = VecDeque::from([0]);
+    while let Some(index) = queue.pop_front() {
+        let mut transitions = IndexMap::new();
+        let symbols: Vec<Item> = (states[index].items.iter())
+            .filter_map(|item| item.next_symbol().cloned())
+            .collect();
+        for symbol in symbols {
+            if transitions.contains_key(&symbol) {
+                continue;
+            }
+            let kernel: Vec<LrItem> = (states[index].items.iter())
+                .filter(|item| item.next_symbol() == Some(&symbol))
+                .map(LrItem::advanced)
+                .collect();
+            let target = match by_kernel.get(&key(&kernel)) {
+                Some(&target) => target,
+                None => {
+                    let target = states.len();
+                    by_kernel.insert(key(&kernel), target);
+                    let mut prefix = states[index].prefix.clone();
+                    prefix.push(symbol.clone());
+                    states.push(LrState {
+                        kernel_len: kernel.len(),
+                        items: closure(kernel),
+                        transitions: IndexMap::new(),
+                        prefix,
+                    });
+                    queue.push_back(target);
+                    target
+                }
+            };
+            transitions.insert(symbol, target);
+        }
+        states[index].transitions = transitions;
+    }
+    Lr0Automaton { states }
+}
+
+/// The conflicts of the automaton when every complete item is reduced on
+/// the lookaheads `lookaheads` returns for its state and item.
+pub(crate) fn lr_conflicts(
+    automaton: &Lr0Automaton,
+    lookaheads: impl Fn(usize, &LrItem) -> HashSet<SetItem>,
+    ignore_regex_conflicts: bool,
+) -> Result<Vec<LrConflict>, Ll1Error> {
+    let overlaps = |a: &HashSet<SetItem>,
+                    b: &HashSet<SetItem>|
+     -> Result<Vec<SetItemConflict>, Ll1Error> {
+        let mut conflicts = Vec::new();
+        for item1 in a {
+            for item2 in b {
+                if let Some(conflict) = check_item_conflict(item1, item2, ignore_regex_conflicts)? {
+                    conflicts.push(conflict);
+                }
+            }
+        }
+        conflicts.sort();
+        Ok(conflicts)
+    };
+
+    let mut conflicts = Vec::new();
+    for (index, state) in automaton.states.iter().enumerate() {
+        // The start rule is only complete after `$`, where the input is accepted
+        let reductions: Vec<(&LrItem, HashSet<SetItem>)> = (state.items.iter())
+            .filter(|item| item.is_complete() && item.rule != AUGMENTED_START)
+            .map(|item| (item, lookaheads(index, item)))
+            .collect();
+        let conflict = |kind, conflicts| LrConflict {
+            state: index,
+            kind,
+            items: state.kernel().to_vec(),
+            prefix: state.prefix.clone(),
+            conflicts,
+        };
+
+        for (reduce, lookahead) in &reductions {
+            let mut shift = Vec::new();
+            let mut overlapping = Vec::new();
+            for symbol in state.transitions.keys() {
+                let Ok(shifted) = SetItem::try_from(symbol) else {
+                    continue;
+                };
+                let found = overlaps(&HashSet::from([shifted]), lookahead)?;
+                if found.is_empty() {
+                    continue;
+                }
+                overlapping.extend(found);
+                shift.extend(
+                    (state.items.iter())
+                        .filter(|item| item.next_symbol() == Some(symbol))
+                        .cloned(),
+                );
+            }
+            if !overlapping.is_empty() {
+                let kind = LrConflictKind::ShiftReduce {
+                    shift,
+                    reduce: (*reduce).clone(),
+                };
+                conflicts.push(conflict(kind, overlapping));
+            }
+        }
+        for (i, (reduce1, lookahead1)) in reductions.iter().enumerate() {
+            for (reduce2, lookahead2) in &reductions[i + 1..] {
+                let overlapping = overlaps(lookahead1, lookahead2)?;
+                if !overlapping.is_empty() {
+                    let kind = LrConflictKind::ReduceReduce {
+                        reduce1: (*reduce1).clone(),
+                        reduce2: (*reduce2).clone(),
+                    };
+                    conflicts.push(conflict(kind, overlapping));
+                }
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+/// The parse table of the automaton when every complete item is reduced on
+/// the lookaheads `lookaheads` returns for its state and item.
+pub(crate) fn build_lr_table(
+    automaton: &Lr0Automaton,
+    method: LrMethod,
+    lookaheads: impl Fn(usize, &LrItem) -> HashSet<SetItem>,
+) -> LrTable {
+    let mut rows = Vec::new();
+    for (index, state) in automaton.states.iter().enumerate() {
+        let mut actions: IndexMap<SetItem, Vec<LrAction>> = IndexMap::new();
+        let mut gotos = Vec::new();
+        for (symbol, &target) in &state.transitions {
+            match (symbol, SetItem::try_from(symbol)) {
+                (Item::NonTerminal(rule), _) => gotos.push((rule.clone(), target)),
+                (_, Ok(lookahead)) => {
+                    let accepts = *symbol == Item::EndOfInput
+                        && (state.items.iter()).any(|item| {
+                            item.rule == AUGMENTED_START && item.next_symbol() == Some(symbol)
+                        });
+                    let action = match accepts {
+                        true => LrAction::Accept,
+                        false => LrAction::Shift(target),
+                    };
+                    actions.entry(lookahead).or_default().push(action);
+                }
+                (_, Err(_)) => {}
+            }
+        }
+        for item in state
+            .items
+            .iter()
+            .filter(|item| item.is_complete() && item.rule != AUGMENTED_START)
+        {
+            for lookahead in lookaheads(index, item) {
+                actions
+                    .entry(lookahead)
+                    .or_default()
+                    .push(LrAction::Reduce {
+                        rule: item.rule.clone(),
+                        production: item.production,
+                    });
+            }
+        }
+        let mut actions: Vec<_> = actions.into_iter().collect();
+        actions.sort_by(|a, b| a.0.cmp(&b.0));
+        rows.push(LrRow { actions, gotos });
+    }
+    LrTable { method, rows }
+}
+
+/// Lookaheads of an item in an LR(1) closure; `None` stands for those of
+/// the kernel item the closure started from
+type Lookaheads = HashSet<Option<SetItem>>;
+
+/// The LR(1) closure of `kernel`, kernel items first.
+fn lr1_closure(
+    bnf: &Bnf,
+    first: &HashMap<String, HashSet<SetItem>>,
+    kernel: Vec<(LrItem, Lookaheads)>,
+) -> Vec<(LrItem, Lookaheads)> {
+    let mut items = kernel;
+    let mut index: HashMap<(String, usize, usize), usize> = (items.iter().enumerate())
+        .map(|(i, (item, _))| ((item.rule.clone(), item.production, item.dot), i))
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..items.len() {
+            let (item, lookaheads) = &items[i];
+            let Some(Item::NonTerminal(rule)) = item.next_symbol() else {
+                continue;
+            };
+            let Some((rule, productions)) = bnf.rules.get_key_value(rule) else {
+                continue;
+            };
+            let (firsts, nullable) = first_of_sequence(&item.symbols[item.dot + 1..], first);
+            let mut added: Lookaheads = (firsts.into_iter())
+                .filter(|lookahead| *lookahead != SetItem::Epsilon)
+                .map(Some)
+                .collect();
+            if nullable {
+                added.extend(lookaheads.iter().cloned());
+            }
+            for (production, symbols) in productions.iter().enumerate() {
+                match index.get(&(rule.clone(), production, 0)) {
+                    Some(&j) => {
+                        let before = items[j].1.len();
+                        items[j].1.extend(added.iter().cloned());
+                        changed |= items[j].1.len() > before;
+                    }
+                    None => {
+                        index.insert((rule.clone(), production, 0), items.len());
+                        let item = LrItem {
+                            rule: rule.clone(),
+                            production,
+                            symbols: symbols.clone(),
+                            dot: 0,
+                        };
+                        items.push((item, added.clone()));
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    items
+}
+
+/// The LALR(1) lookaheads of the complete items of every state, keyed by
+/// rule and production.
+///
+/// A lookahead an item's LR(1) closure adds to a successor's kernel item is
+/// generated there; one it only passes on is propagated from the item, until
+/// nothing changes (the algorithm of the dragon book).
+pub(crate) fn lalr1_lookaheads(
+    bnf: &Bnf,
+    automaton: &Lr0Automaton,
+    first: &HashMap<String, HashSet<SetItem>>,
+) -> Vec<HashMap<(String, usize), HashSet<SetItem>>> {
+    let states = &automaton.states;
+    let kernel_index = |state: usize, item: &LrItem| -> Option<usize> {
+        (states[state].kernel().iter()).position(|kernel| {
+            kernel.rule == item.rule
+                && kernel.production == item.production
+                && kernel.dot == item.dot + 1
+        })
+    };
+    let mut lookaheads: Vec<Vec<HashSet<SetItem>>> = (states.iter())
+        .map(|state| vec![HashSet::new(); state.kernel_len])
+        .collect();
+    // Kernel items each kernel item passes its lookaheads on to
+    let mut propagates: Vec<Vec<Vec<(usize, usize)>>> = (states.iter())
+        .map(|state| vec![Vec::new(); state.kernel_len])
+        .collect();
+    for (index, state) in states.iter().enumerate() {
+        for (k, kernel) in state.kernel().iter().enumerate() {
+            let closure = lr1_closure(bnf, first, vec![(kernel.clone(), HashSet::from([None]))]);
+            for (item, item_lookaheads) in closure {
+                let Some(symbol) = item.next_symbol() else {
+                    continue;
+                };
+                let Some(&target) = state.transitions.get(symbol) else {
+                    continue;
+                };
+                let Some(t) = kernel_index(target, &item) else {
+                    continue;
+                };
+                for lookahead in item_lookaheads {
+                    match lookahead {
+                        Some(lookahead) => {
+                            lookaheads[target][t].insert(lookahead);
+                        }
+                        None => propagates[index][k].push((target, t)),
+                    }
+                }
+            }
+        }
+    }
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, kernel) in propagates.iter().enumerate() {
+            for (k, targets) in kernel.iter().enumerate() {
+                for &(target, t) in targets {
+                    let passed: Vec<SetItem> = lookaheads[index][k]
+                        .difference(&lookaheads[target][t])
+                        .cloned()
+                        .collect();
+                    changed |= !passed.is_empty();
+                    lookaheads[target][t].extend(passed);
+                }
+            }
+        }
+    }
+
+    (states.iter().enumerate())
+        .map(|(index, state)| {
+            let kernel = (state.kernel().iter().zip(&lookaheads[index]))
+                .map(|(item, lookaheads)| {
+                    (item.clone(), lookaheads.iter().cloned().map(Some).collect())
+                })
+                .collect();
+            let mut complete: HashMap<(String, usize), HashSet<SetItem>> = HashMap::new();
+            for (item, lookaheads) in lr1_closure(bnf, first, kernel) {
+                if item.is_complete() {
+                    (complete.entry((item.rule, item.production)).or_default())
+                        .extend(lookaheads.into_iter().flatten());
+                }
+            }
+            complete
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sebnf;
+
+    #[test]
+    fn reports_shift_reduce_conflicts_with_their_state() {
+        let bnf = Sebnf::parse_str("s := \"if\" s | \"if\" s \"else\" s | \"x\".", "g")
+            .unwrap()
+            .to_bnf();
+        let automaton = bnf.lr0_automaton();
+        assert_eq!(automaton.states.len(), 8);
+
+        let result = bnf.is_slr1(false).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(
+            result.conflicts[0].to_string(),
+            "State 5: shift/reduce conflict\n   \
+             Reached by: \"if\" s\n   \
+             Items:\n     \
+             s → \"if\" s •\n     \
+             s → \"if\" s • \"else\" s\n   \
+             Shift: s → \"if\" s • \"else\" s\n   \
+             Reduce: s → \"if\" s •\n   \
+             Conflicts:\n     \
+             - else ∩ else (e.g., \"else\")\n"
+        );
+    }
+    #[test]
+    fn lalr1_lookaheads_split_what_follow_sets_merge() {
+        let bnf = Sebnf::parse_str(
+            "s := l \"=\" r | r.\nl := \"*\" r | /[a-z]+/.\nr := l.",
+            "g",
+        )
+        .unwrap()
+        .to_bnf();
+        let slr = bnf.is_slr1(false).unwrap();
+        assert_eq!(slr.conflicts.len(), 1);
+        assert_eq!(slr.conflicts[0].state, 2);
+
+        let lalr = bnf.is_lalr1(false).unwrap();
+        assert!(lalr.is_conflict_free(), "{}", lalr);
+        let table = bnf.lalr1_table();
+        assert!(!table.has_conflicts());
+        let actions: Vec<String> = (table.rows[2].actions.iter())
+            .map(|(lookahead, actions)| format!("{} {:?}", lookahead, actions))
+            .collect();
+        assert_eq!(
+            actions,
+            ["= [Shift(7)]", "$ [Reduce { rule: \"r\", production: 0 }]"]
+        );
+    }
+}
@@ -1,126 +1,2766 @@
-use std::io::Read;
-use std::process::ExitCode;
+mod repl;
+mod serve;
 
-use clap::{Parser, Subcommand};
-use miette::NamedSource;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-use crate::lex::Token;
-use crate::sebnf::{ParseError, Sebnf};
-use crate::sets::Ll1Error;
-use logos::Logos;
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
 
-pub mod bnf;
-pub mod converter;
-pub mod lex;
-pub mod regex_intersect;
-pub mod sebnf;
-pub mod sets;
+use sebnf_tool::Bnf;
+use sebnf_tool::annotate::annotate_conflicts;
+use sebnf_tool::changelog;
+use sebnf_tool::codegen::{self, CodegenError, CrateOptions};
+use sebnf_tool::compare;
+use sebnf_tool::config::{Config, ConfigError};
+use sebnf_tool::converter::{
+    self, DedupeHelpers, DesugarBuiltins, DesugarChoices, DesugarOptionals, DesugarRepetitions,
+    InlineHelpers, LoweringOptions, NameHelpers, Pipeline, SharedEpsilon, SoftKeywords,
+    StableNames,
+};
+use sebnf_tool::cycles;
+use sebnf_tool::cyk;
+use sebnf_tool::description::{self, DescriptionError};
+use sebnf_tool::disjoint::{self, DisjointError};
+use sebnf_tool::earley::{self, EarleyOptions};
+use sebnf_tool::encoding::{self, Decoded, Encoding, EncodingError};
+use sebnf_tool::equiv;
+use sebnf_tool::explain;
+use sebnf_tool::finite;
+use sebnf_tool::format::{Align, FormatOptions, Wrap, format_source};
+use sebnf_tool::fuzz;
+use sebnf_tool::glr;
+use sebnf_tool::graph::{self, GraphOptions};
+use sebnf_tool::html_report::html_report;
+use sebnf_tool::interpret::{self, InterpretError, ParseOptions};
+use sebnf_tool::keywords::{self, KeywordError};
+use sebnf_tool::lalrpop;
+use sebnf_tool::left_recursion;
+use sebnf_tool::lex_check::{self, LexCheckError};
+use sebnf_tool::merge;
+use sebnf_tool::nom;
+use sebnf_tool::nullable;
+use sebnf_tool::peg::{self, PegError};
+use sebnf_tool::productivity;
+use sebnf_tool::query::{self, QueryError};
+use sebnf_tool::railroad;
+use sebnf_tool::reachability;
+use sebnf_tool::recursion;
+use sebnf_tool::regex_intersect::WitnessOptions;
+use sebnf_tool::regex_usage::{self, FoldLiterals, LiteralForm, RegexToLiterals};
+use sebnf_tool::render::RenderError;
+use sebnf_tool::repair;
+use sebnf_tool::resugar::resugar;
+use sebnf_tool::sarif::{Level, SarifLog, rule_span};
+use sebnf_tool::sebnf::{GrammarWarning, ParseErrors, Sebnf};
+use sebnf_tool::sections::{self, SectionError};
+use sebnf_tool::self_check::{self, SelfCheckError};
+use sebnf_tool::sentences::{self, EnumerateOptions, GenerateError, GenerateOptions};
+use sebnf_tool::sets::{self, Ll1Error, SetKind};
+use sebnf_tool::snapshot::{self, Mismatch, SnapshotError};
+use sebnf_tool::symbols::Symbols;
+use sebnf_tool::token_class::{TokenClassError, TokenClasses};
+use sebnf_tool::trace::{self, ExplainError};
+use sebnf_tool::transform::{
+    Dedupe, EliminateUnitProductions, Engine, ExtractRoot, InlineRule, Normalize, Prune,
+    RenameSymbol, Simplify, TransformError,
+};
+use sebnf_tool::tree::{TreeOptions, render_tree};
+use sebnf_tool::tree_sitter;
+use sebnf_tool::typed_ast;
+use sebnf_tool::vocabulary::{self, TokenKind};
 
 #[derive(Parser)]
-#[command(name = "ebnf_set_calc")]
-#[command(about = "EBNF grammar analysis tool")]
+#[command(name = env!("CARGO_PKG_NAME"))]
+#[command(about = "EBNF grammar analysis tool", version)]
+#[command(after_help = "\
+Exit codes:
+  0  success
+  1  analysis failure (e.g. the grammar is not LL(1))
+  2  parse error in the grammar or another input, or a usage error
+  3  I/O error")]
 struct Cli {
+    /// Read the grammar from this file instead of stdin
+    #[arg(long, short, global = true, value_name = "PATH")]
+    input: Option<PathBuf>,
+    /// Write the result to this file instead of stdout. The file is replaced
+    /// atomically, so readers never observe a partially written result.
+    #[arg(long, short, global = true, value_name = "PATH")]
+    output: Option<PathBuf>,
+    /// Analyze at the level of an external lexer's token classes, declared
+    /// in this file as lines like `"if" -> IF` or `/[0-9]+/ -> INT_LIT`
+    #[arg(long, global = true, value_name = "PATH")]
+    token_classes: Option<PathBuf>,
+    /// Use this rule as the start symbol instead of the first rule
+    #[arg(long, global = true, value_name = "RULE")]
+    start: Option<String>,
+    /// Add a fresh start rule `___start := S $` before analyzing, so the start
+    /// symbol S may be referenced by other rules
+    #[arg(long, global = true)]
+    augment_start: bool,
+    /// Lower every `[ ]`, `{ }` and `sep_by` to a rule with a production
+    /// `___empty` instead of an empty one, sharing a single `___empty := ε`
+    #[arg(long, global = true)]
+    shared_epsilon: bool,
+    /// Inline generated rules whose body is a single symbol or that are used
+    /// only once, where this duplicates no items
+    #[arg(long, global = true)]
+    inline_helpers: bool,
+    /// Name generated rules after the rule using them and a hash of their
+    /// body, like `___expr_opt_1f0c`, so edits elsewhere do not renumber them
+    #[arg(long, global = true)]
+    stable_names: bool,
+    /// Spell a regex that matches a single string, like `/while/`, and the
+    /// terminal of that string the same way: as the terminal, or with
+    /// `--fold-literals=regex` as the regex
+    #[arg(
+        long,
+        global = true,
+        value_name = "FORM",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "terminal"
+    )]
+    fold_literals: Option<FoldInto>,
+    /// Report regex conflicts whose shortest common string is longer than
+    /// this many bytes without the string, as "witness longer than N bytes"
+    #[arg(long, global = true, value_name = "N")]
+    max_witness_len: Option<usize>,
+    /// Only decide whether regexes overlap, without searching for a common
+    /// string, which is faster on regex-heavy grammars
+    #[arg(long, global = true, conflicts_with = "max_witness_len")]
+    no_witness: bool,
+    /// Print nothing on stdout; only the exit code reports the result
+    #[arg(long, short, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print progress notes on stderr
+    #[arg(long, short, global = true)]
+    verbose: bool,
+    /// Use the `grammar NAME { ... }` section of the input; repeat to compose
+    /// several sections, the first one providing the start rule
+    #[arg(long, global = true, value_name = "NAME")]
+    grammar: Vec<String>,
+    /// Read settings such as `symbols.epsilon = "eps"` from this file
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Print only ASCII: `eps` for ε and ASCII stand-ins for the other
+    /// glyphs. Overrides the symbols of `--config`.
+    #[arg(long, global = true)]
+    ascii: bool,
+    /// Language of the grammar input. Given before the subcommand, as
+    /// `rename` has a `--from` of its own.
+    #[arg(long, value_enum, default_value_t, value_name = "DIALECT")]
+    from: Dialect,
+    /// Encoding of the grammar files. A byte order mark is honored without
+    /// it; files without one are read as UTF-8.
+    #[arg(long, global = true, value_enum, value_name = "ENCODING")]
+    input_encoding: Option<InputEncoding>,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human-readable output
+    #[default]
+    Text,
+    /// SARIF 2.1.0 log for code scanning tools
+    Sarif,
+    /// Machine-readable JSON
+    Json,
+}
+
+/// Encodings of `--input-encoding`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputEncoding {
+    #[value(name = "utf-8")]
+    Utf8,
+    /// ISO 8859-1; byte order marks are read as characters
+    #[value(name = "latin-1")]
+    Latin1,
+    #[value(name = "utf-16le")]
+    Utf16le,
+    #[value(name = "utf-16be")]
+    Utf16be,
+}
+
+impl From<InputEncoding> for Encoding {
+    fn from(encoding: InputEncoding) -> Self {
+        match encoding {
+            InputEncoding::Utf8 => Encoding::Utf8,
+            InputEncoding::Latin1 => Encoding::Latin1,
+            InputEncoding::Utf16le => Encoding::Utf16Le,
+            InputEncoding::Utf16be => Encoding::Utf16Be,
+        }
+    }
+}
+
+/// The set `explain-set` looks into
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExplainedSet {
+    First,
+    Follow,
+}
+
+impl From<ExplainedSet> for SetKind {
+    fn from(set: ExplainedSet) -> Self {
+        match set {
+            ExplainedSet::First => SetKind::First,
+            ExplainedSet::Follow => SetKind::Follow,
+        }
+    }
+}
+
+/// Spelling of the strings unified by `--fold-literals`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FoldInto {
+    /// `/while/` becomes `"while"`
+    Terminal,
+    /// `"while"` becomes `/while/` where the grammar has that regex
+    Regex,
+}
+
+impl From<FoldInto> for LiteralForm {
+    fn from(form: FoldInto) -> Self {
+        match form {
+            FoldInto::Terminal => LiteralForm::Terminal,
+            FoldInto::Regex => LiteralForm::Regex,
+        }
+    }
+}
+
+/// Modes of `fmt --align`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum AlignMode {
+    /// Align `:=` across the whole file
+    File,
+    /// Align `:=` across rules not separated by a blank line or comment
+    Block,
+    /// Do not align
+    None,
+}
+
+impl From<AlignMode> for Align {
+    fn from(mode: AlignMode) -> Self {
+        match mode {
+            AlignMode::File => Align::File,
+            AlignMode::Block => Align::Block,
+            AlignMode::None => Align::None,
+        }
+    }
+}
+
+/// Modes of `fmt --wrap`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WrapMode {
+    /// Every alternative on its own line
+    Always,
+    /// All alternatives on one line if they fit into the width
+    Fit,
+}
+
+impl From<WrapMode> for Wrap {
+    fn from(mode: WrapMode) -> Self {
+        match mode {
+            WrapMode::Always => Wrap::Always,
+            WrapMode::Fit => Wrap::Fit,
+        }
+    }
+}
+
+/// Grammar dialects accepted as input
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum Dialect {
+    /// SEBNF source
+    #[default]
+    Sebnf,
+    /// A grammar description as printed by `dump-ast`
+    Json,
+    /// The `dump-ast` description written in YAML
+    #[cfg(feature = "yaml")]
+    Yaml,
+}
+
+#[derive(Subcommand)]
+enum ExportTarget {
+    /// Print an AFL/libFuzzer dictionary of every terminal and a string
+    /// matched by each regex
+    FuzzDict,
+    /// Write generated sentences into DIR, one seed file each
+    FuzzSeeds {
+        dir: PathBuf,
+        /// Number of distinct sentences
+        #[arg(long, default_value_t = GenerateOptions::default().count)]
+        count: usize,
+        /// Nesting of rules up to which productions are chosen at random;
+        /// deeper rules take their shortest expansion
+        #[arg(long, default_value_t = GenerateOptions::default().max_depth)]
+        max_depth: usize,
+        /// Seed of the random choices
+        #[arg(long, default_value_t = GenerateOptions::default().seed)]
+        seed: u64,
+    },
+    /// Print a LALRPOP grammar with a nonterminal per rule, each of type
+    /// `()`, on LALRPOP's lexer
+    Lalrpop {
+        /// Read the `TokenKind`s of `codegen lexer`, declared in an `extern`
+        /// block, instead
+        #[arg(long)]
+        extern_tokens: bool,
+    },
+    /// Print a tree-sitter `grammar.js` with a rule per rule, and on stderr
+    /// the constructs tree-sitter treats differently or rejects
+    TreeSitter {
+        /// Name of the language; defaults to the start rule's
+        #[arg(long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CodegenTarget {
+    /// Write a Cargo crate with a lexer, an LL(1) parser, syntax tree types,
+    /// a visitor and tests into DIR. Its build script regenerates the
+    /// sources when the copied `grammar.sebnf` changes.
+    Crate {
+        #[arg(long, value_name = "DIR")]
+        out_dir: PathBuf,
+        /// Package name; defaults to the name of DIR
+        #[arg(long)]
+        name: Option<String>,
+        /// Number of generated sentences the crate's tests parse
+        #[arg(long, default_value_t = CrateOptions::new("").tests)]
+        tests: usize,
+    },
+    /// Print a recursive descent parser as a single Rust module, with a
+    /// function per rule that picks the production by the next token
+    RustRd,
+    /// Print a table-driven LL(1) parser as a single Rust module: the parse
+    /// table as constant arrays and a loop that expands rules on a stack
+    RustTable,
+    /// Print the `typed.rs` of a generated crate: a struct or enum per rule
+    /// mirroring its definition, converted from the nodes of the parse tree
+    TypedAst,
+    /// Print a module of `nom` parser combinators, a function per rule
+    /// recognizing it, to fill in with results by hand
+    Nom,
+    /// Print a lexer for the grammar's terminals and regexes, built on
+    /// `logos`, as a drop-in for the `lexer.rs` of a generated crate
+    Lexer {
+        /// Print the lexer of `codegen crate` instead, which needs only
+        /// the `regex` crate
+        #[arg(long)]
+        standalone: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SelfCheck {
+    /// Verify that converting to BNF keeps the language of every rule,
+    /// comparing all sentences of up to MAX_LEN tokens
+    Conversion {
+        #[arg(long, value_name = "MAX_LEN", default_value_t = 5)]
+        max_len: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum Report {
+    /// List every regex terminal and whether it can be written as plain
+    /// terminals instead
+    RegexUsage {
+        /// Print the grammar with those regexes rewritten
+        #[arg(long)]
+        rewrite: bool,
+    },
+    /// List the rules the start rule never reaches, each with a chain of
+    /// references from an unreachable rule nothing refers to
+    Reachability,
+    /// List the rules that derive no string of terminals, with the
+    /// recursions that keep them from it
+    Productivity,
+    /// List the groups of mutually recursive rules, and whether each
+    /// recurses on the left, on the right or both
+    Recursion,
+    /// List the alternatives a PEG parser never tries, since an earlier
+    /// alternative of the same choice always matches first
+    Peg,
+    /// List every terminal that a regex also matches, as a reserved word
+    /// the regex never matches or a soft keyword from `@soft_keywords`
+    Keywords,
+    /// List, for every rule, the terminals that can occur anywhere in the
+    /// strings it derives
+    Terminals {
+        /// Only list the terminals of this rule
+        #[arg(long, value_name = "RULE")]
+        rule: Option<String>,
+    },
+}
+
+/// Output formats of `vocabulary`
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum VocabularyFormat {
+    /// One line per token
+    #[default]
+    Text,
+    /// A header and one row per token
+    Csv,
+}
+
+/// Output formats of `changelog`
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum ChangelogFormat {
+    /// Release notes
+    #[default]
+    Md,
+    Json,
+}
+
+/// The analysis compared by `compare`
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum CompareWhat {
+    /// LL(1) conflicts introduced and resolved
+    #[default]
+    Conflicts,
+    /// Members added to and removed from the FIRST and FOLLOW sets
+    Sets,
+    /// Changed LL(1) parse table cells
+    Table,
+}
+
+/// The parser of `parse`
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum ParseAlgo {
+    /// The LL(1) table, backtracking over its conflicts
+    #[default]
+    Backtrack,
+    /// An Earley parser, which handles any grammar and warns if the input
+    /// has several parses
+    Earley,
+    /// A GLR parser on the LALR(1) table, which forks on its conflicts and
+    /// prints the shared packed parse forest
+    Glr,
+    /// The CYK algorithm on the grammar in Chomsky normal form, which only
+    /// tells whether the input matches
+    Cyk,
+}
+
+/// A pass of `transform --pass`
+#[derive(Debug, Clone)]
+enum Pass {
+    DesugarBuiltins,
+    DesugarOptionals,
+    DesugarRepetitions,
+    DesugarChoices,
+    SharedEpsilon,
+    DedupeHelpers,
+    Dedupe,
+    InlineHelpers,
+    NameHelpers,
+    StableNames,
+    FoldLiterals(LiteralForm),
+    SoftKeywords,
+    Inline(String),
+    Rename(String, String),
+}
+
+impl std::str::FromStr for Pass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rule) = s.strip_prefix("inline:") {
+            return Ok(Pass::Inline(rule.to_string()));
+        }
+        if let Some(names) = s.strip_prefix("rename:") {
+            let (from, to) = names
+                .split_once('=')
+                .ok_or_else(|| format!("expected rename:FROM=TO, got '{}'", s))?;
+            return Ok(Pass::Rename(from.to_string(), to.to_string()));
+        }
+        Ok(match s {
+            "desugar-builtins" => Pass::DesugarBuiltins,
+            "desugar-optionals" => Pass::DesugarOptionals,
+            "desugar-repetitions" => Pass::DesugarRepetitions,
+            "desugar-choices" => Pass::DesugarChoices,
+            "shared-epsilon" => Pass::SharedEpsilon,
+            "dedupe-helpers" => Pass::DedupeHelpers,
+            "dedupe" => Pass::Dedupe,
+            "inline-helpers" => Pass::InlineHelpers,
+            "name-helpers" => Pass::NameHelpers,
+            "stable-names" => Pass::StableNames,
+            "fold-literals" => Pass::FoldLiterals(LiteralForm::Terminal),
+            "fold-literals:regex" => Pass::FoldLiterals(LiteralForm::Regex),
+            "soft-keywords" => Pass::SoftKeywords,
+            _ => return Err(format!("unknown pass '{}'", s)),
+        })
+    }
+}
+
+impl Pass {
+    fn add_to(self, pipeline: &mut Pipeline) {
+        match self {
+            Pass::DesugarBuiltins => pipeline.push(DesugarBuiltins),
+            Pass::DesugarOptionals => pipeline.push(DesugarOptionals),
+            Pass::DesugarRepetitions => pipeline.push(DesugarRepetitions),
+            Pass::DesugarChoices => pipeline.push(DesugarChoices),
+            Pass::SharedEpsilon => pipeline.push(SharedEpsilon),
+            Pass::DedupeHelpers => pipeline.push(DedupeHelpers),
+            Pass::Dedupe => pipeline.push(Dedupe),
+            Pass::InlineHelpers => pipeline.push(InlineHelpers),
+            Pass::NameHelpers => pipeline.push(NameHelpers),
+            Pass::StableNames => pipeline.push(StableNames),
+            Pass::FoldLiterals(form) => pipeline.push(FoldLiterals::new(form)),
+            Pass::SoftKeywords => pipeline.push(SoftKeywords),
+            Pass::Inline(rule) => pipeline.push(InlineRule::new(rule)),
+            Pass::Rename(from, to) => pipeline.push(RenameSymbol::new(from, to)),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Validate SEBNF syntax
-    Validate,
+    Validate {
+        /// Also fail on derivation cycles (a rule deriving itself through
+        /// unit or nullable productions) and rules the start rule never
+        /// reaches
+        #[arg(long)]
+        strict: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
     /// Convert SEBNF to BNF
-    ToBnf,
+    ToBnf {
+        /// More files of the same grammar to combine with the input, e.g.
+        /// its lexical and its syntactic rules. A rule may only be defined
+        /// in several files if every definition is the same.
+        files: Vec<PathBuf>,
+    },
+    /// Convert to BNF and back, turning `X := body X | ε` into `{ body }`
+    /// and `X := body | ε` into `[ body ]`; reads `to-bnf` output as well
+    Resugar,
+    /// Convert SEBNF to BNF without unreachable and non-productive rules,
+    /// listing the dropped rules as comments
+    Simplify {
+        /// First replace unit productions `A := B` by the productions of `B`
+        #[arg(long)]
+        unit_productions: bool,
+    },
+    /// Apply conversion passes in the given order, printing BNF once every
+    /// construct is desugared and the intermediate SEBNF otherwise
+    Transform {
+        /// Pass to apply; repeatable. One of desugar-builtins,
+        /// desugar-optionals, desugar-repetitions, desugar-choices,
+        /// shared-epsilon, dedupe-helpers, inline-helpers, name-helpers,
+        /// stable-names, fold-literals, fold-literals:regex, soft-keywords,
+        /// dedupe, inline:RULE or rename:FROM=TO. Without passes, the
+        /// standard conversion runs.
+        #[arg(long = "pass", value_name = "PASS")]
+        passes: Vec<Pass>,
+    },
+    /// Rename a rule and every reference to it, rewriting the input file in
+    /// place (or writing to `--output`, or stdout when reading stdin)
+    Rename {
+        /// Current name of the rule
+        #[arg(long, value_name = "RULE")]
+        from: String,
+        /// New name, which no rule may have yet
+        #[arg(long, value_name = "RULE")]
+        to: String,
+    },
+    /// Format an SEBNF file, keeping its comments, rewriting it in place
+    /// (or writing to `--output`, or stdout when reading stdin)
+    Fmt {
+        /// Write nothing and exit with 1 if the file is not formatted
+        #[arg(long)]
+        check: bool,
+        #[arg(long, value_enum, default_value_t = AlignMode::File)]
+        align: AlignMode,
+        /// Line width to break long alternatives at
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+        #[arg(long, value_enum, default_value_t = WrapMode::Always)]
+        wrap: WrapMode,
+    },
+    /// Print the SEBNF of the rules reachable from a rule, which becomes the
+    /// start rule; the others keep their order
+    Extract {
+        /// Rule whose sub-grammar to print
+        #[arg(long, value_name = "RULE")]
+        root: String,
+    },
+    /// Substitute the body of a rule at every reference to it and remove
+    /// the rule, printing the SEBNF; fails for the start rule and for rules
+    /// that refer to themselves
+    Expand {
+        /// Rule to substitute
+        #[arg(long, value_name = "RULE")]
+        symbol: String,
+    },
+    /// Remove alternatives written twice within a rule and replace rules
+    /// identical to an earlier one by it, printing the SEBNF and listing
+    /// each change on stderr
+    Dedupe,
+    /// Remove the rules that derive no string of terminals and every
+    /// alternative, `[ ]` and `{ }` using them, printing the SEBNF and
+    /// listing each removal on stderr
+    Prune,
+    /// Rewrite the grammar into a canonical form, printing the SEBNF and
+    /// listing each kind of change on stderr; grammars that differ only in
+    /// layout, grouping and order print the same
+    Normalize,
     /// Extract FIRST and FOLLOW sets
     ExtractSets,
+    /// List the rules that derive ε, each with a derivation of ε
+    Nullable,
+    /// Find direct and indirect left recursion, with the productions along
+    /// each cycle
+    CheckLeftRecursion,
+    /// Compare every terminal and regex with every regex, and list those
+    /// that match a common string with the shortest one
+    LexCheck,
+    /// Decide whether the grammar has finitely many sentences (counting a
+    /// regex or token class as one token)
+    Finite {
+        /// List the sentences of a finite language, shortest first
+        #[arg(long)]
+        enumerate: bool,
+        /// Most sentences to list
+        #[arg(long, default_value_t = 1000)]
+        limit: usize,
+    },
+    /// Show how the FIRST and FOLLOW sets of one symbol grow, pass by pass,
+    /// with the production that added each member
+    TraceSets {
+        /// A rule of the BNF, including generated helper rules
+        symbol: String,
+    },
+    /// Explain why an item is in the FIRST or FOLLOW set of a symbol, as a
+    /// chain of sets and productions it passes through
+    ExplainSet {
+        #[arg(value_enum)]
+        set: ExplainedSet,
+        /// A rule of the BNF, including generated helper rules
+        symbol: String,
+        /// A member of the set, e.g. '")"', /[0-9]+/, ε or $
+        item: String,
+    },
+    /// Show everything known about one rule: its productions, FIRST and
+    /// FOLLOW sets, nullability, reachability, users and LL(1) conflicts
+    Explain {
+        /// A rule of the BNF, including generated helper rules
+        symbol: String,
+        /// Skip regex-vs-regex conflict checking
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+    },
+    /// Try left factoring and left recursion elimination on every rule with
+    /// an LL(1) conflict, and report which repair resolves its conflicts
+    Repair {
+        /// Print the grammar with the resolving repairs applied instead
+        #[arg(long)]
+        apply: bool,
+        /// Skip regex-vs-regex conflict checking
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+    },
+    /// Check if grammar is strong LL(k), listing the lookahead of up to k
+    /// tokens each conflicting pair of productions shares
+    IsLlk {
+        #[arg(long, default_value_t = 2, value_parser = at_least_one)]
+        k: usize,
+        /// Skip regex-vs-regex conflict checking
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Find the smallest k up to MAX_K for which the grammar is strong LL(k)
+    MinK {
+        #[arg(long, value_name = "MAX_K", default_value_t = 3, value_parser = at_least_one)]
+        max_k: usize,
+        /// Skip regex-vs-regex conflict checking
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Check if the grammar is SLR(1), listing the states of the LR(0)
+    /// automaton with shift/reduce or reduce/reduce conflicts
+    IsSlr1 {
+        /// Skip regex-vs-regex conflict checking
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Check if the grammar is LALR(1), as yacc and lalrpop need, listing
+    /// the states with conflicts after merging lookaheads
+    IsLalr1 {
+        /// Skip regex-vs-regex conflict checking
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+        /// Print the LALR(1) parse table after the result (text format only)
+        #[arg(long)]
+        table: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
     /// Check if grammar is LL(1)
     IsLl1 {
         /// Skip regex-vs-regex conflict checking. Use this when your lexer
         /// resolves regex conflicts via a priority system.
         #[arg(long)]
         ignore_regex_conflicts: bool,
+        /// For every conflict, report the smallest k up to MAX_K for which
+        /// k tokens of lookahead resolve it.
+        #[arg(long, value_name = "MAX_K", value_parser = at_least_one)]
+        lookahead: Option<usize>,
+        /// For every conflict, list the token sequences of up to MAX_K tokens
+        /// on which its productions still overlap, at the longest such length
+        #[arg(long, value_name = "MAX_K")]
+        ambiguity: Option<usize>,
+        /// For every conflict, show the fewest tokens that take a parser from
+        /// the start rule to its decision, and a token both productions accept
+        #[arg(long)]
+        example: bool,
+        /// Print the grammar with a comment listing the conflicts above each
+        /// rule that has them, instead of the report
+        #[arg(long, conflicts_with = "format")]
+        annotate_source: bool,
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Interactively query a grammar loaded from a file
+    Repl {
+        /// Grammar file to load on startup
+        path: Option<PathBuf>,
+    },
+    /// Write or verify golden grammar artifacts (BNF, sets, conflicts, parse table)
+    #[command(group(ArgGroup::new("mode").required(true).args(["update", "check"])))]
+    Snapshot {
+        /// Directory holding the artifacts
+        dir: PathBuf,
+        /// Rewrite the artifacts
+        #[arg(long)]
+        update: bool,
+        /// Fail if the artifacts differ from the stored ones
+        #[arg(long)]
+        check: bool,
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+    },
+    /// Evaluate a set expression such as `first(Expr) & first(Stmt)`.
+    /// Exits with failure if the result is empty or false.
+    Query {
+        /// Expression over first(X), follow(X), nullable(X) and {"a", /re/, ε, $, CLASS}
+        /// combined with & (intersection), | (union) and - (difference)
+        expr: String,
+    },
+    /// Describe this binary's subcommands, formats and features as JSON
+    Capabilities,
+    /// Serve a JSON API (validate, to-bnf, sets, is-ll1) and a web UI for
+    /// pasting grammars
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        /// Address to listen on; use 0.0.0.0 to accept remote connections
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+    },
+    /// Print the semantic differences between the analyses of two grammar
+    /// versions. Exits with failure if there are any.
+    Compare {
+        old: PathBuf,
+        new: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        what: CompareWhat,
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+    },
+    /// Three-way merge two grammar versions rule by rule and alternative by
+    /// alternative, printing the merged grammar. Rules edited differently
+    /// on both sides are wrapped in conflict markers, and the command exits
+    /// with failure. Usable as a git merge driver: `merge %O %A %B -o %A`.
+    Merge {
+        /// The common ancestor
+        base: PathBuf,
+        ours: PathBuf,
+        theirs: PathBuf,
+    },
+    /// Summarize the rules, alternatives, terminals and regexes added and
+    /// removed between two grammar versions, as release notes
+    Changelog {
+        old: PathBuf,
+        new: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        format: ChangelogFormat,
+    },
+    /// Check that two grammars accept the same sentences of up to MAX_LEN
+    /// tokens, showing a shortest one only one accepts
+    Equiv {
+        first: PathBuf,
+        second: PathBuf,
+        #[arg(
+            long,
+            value_name = "MAX_LEN",
+            alias = "max-tokens",
+            default_value_t = 6
+        )]
+        max_len: usize,
+        /// Most sentences per grammar to compare before giving up
+        #[arg(long, default_value_t = 100_000)]
+        limit: usize,
+    },
+    /// Export the grammar for other tools
+    Export {
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+    /// Generate a parser for the grammar, which must be LL(1)
+    Codegen {
+        #[command(subcommand)]
+        target: CodegenTarget,
+    },
+    /// Verify that two rules never derive a common sentence, printing one if
+    /// they do. Exact unless a rule is recursive, whose sentences are only
+    /// compared up to MAX_LEN tokens.
+    Disjoint {
+        first: String,
+        second: String,
+        #[arg(long, value_name = "MAX_LEN", default_value_t = 6)]
+        max_len: usize,
+    },
+    /// Check this tool's own machinery on the grammar. Exits with failure
+    /// if a check fails.
+    SelfCheck {
+        #[command(subcommand)]
+        check: SelfCheck,
+    },
+    /// List every distinct terminal and regex with its number of uses, the
+    /// rules using it and whether it only occurs in generated helper rules,
+    /// sorted by spelling, and the terminals differing only in case or
+    /// surrounding whitespace
+    #[command(visible_alias = "tokens")]
+    Vocabulary {
+        #[arg(long, value_enum, default_value_t)]
+        format: VocabularyFormat,
+    },
+    /// Print a report about the grammar
+    Analyze {
+        #[command(subcommand)]
+        report: Report,
+    },
+    /// Parse FILE with the grammar and print its parse tree. Grammars with
+    /// LL(1) conflicts are parsed by backtracking, or with another --algo
+    Parse {
+        file: PathBuf,
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            default_value_if("all", "true", "earley"),
+            default_value_if("table", "true", "cyk")
+        )]
+        algo: ParseAlgo,
+        /// Give up after trying this many rules and tokens
+        #[arg(long, default_value_t = ParseOptions::default().max_steps)]
+        max_steps: usize,
+        /// Print every parse of an ambiguous input; implies --algo earley
+        #[arg(long)]
+        all: bool,
+        /// List at most this many parses
+        #[arg(long, default_value_t = EarleyOptions::default().max_parses)]
+        max_parses: usize,
+        /// Print the grammar in Chomsky normal form and the CYK table, a row
+        /// per number of tokens listing the rules matching that many tokens
+        /// from each token; implies --algo cyk
+        #[arg(long, conflicts_with = "all")]
+        table: bool,
+    },
+    /// Print random sentences of the grammar, one per line, with a random
+    /// string for each regex
+    Generate {
+        /// Number of distinct sentences
+        #[arg(long, default_value_t = GenerateOptions::default().count)]
+        count: usize,
+        /// Nesting of rules up to which productions are chosen at random;
+        /// deeper rules take their shortest expansion
+        #[arg(long, default_value_t = GenerateOptions::default().max_depth)]
+        max_depth: usize,
+        /// Seed of the random choices
+        #[arg(long, default_value_t = GenerateOptions::default().seed)]
+        seed: u64,
+    },
+    /// Print every sentence of up to --max-tokens tokens and derivations of
+    /// up to --max-depth rules, shortest first and then in token order.
+    /// Exits with failure if a rule has more sentences than --limit.
+    #[command(group(ArgGroup::new("bound").required(true).multiple(true).args(["max_tokens", "max_depth"])))]
+    Enumerate {
+        /// Most tokens of a sentence, counting a regex or token class as one
+        #[arg(long)]
+        max_tokens: Option<usize>,
+        /// Most nested rules of the BNF, including generated helper rules
+        #[arg(long)]
+        max_depth: Option<usize>,
+        /// Most sentences of any rule before giving up
+        #[arg(long, default_value_t = EnumerateOptions::default().limit)]
+        limit: usize,
+    },
+    /// Print railroad diagrams of the rules as one SVG, each diagram linked
+    /// from the boxes of its rule
+    Diagram {
+        /// Only the diagram of this rule
+        #[arg(long, conflicts_with = "out_dir")]
+        rule: Option<String>,
+        /// Write a `RULE.svg` per rule into DIR instead
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+    /// Print the dependency graph of the rules as GraphViz DOT, an edge
+    /// from each rule to the rules it refers to
+    Graph {
+        /// Group mutually recursive rules into clusters
+        #[arg(long)]
+        clusters: bool,
+        /// Draw the start rule filled and bold
+        #[arg(long)]
+        highlight_start: bool,
+        /// Draw unreachable rules dashed and unproductive rules red
+        #[arg(long)]
+        mark_useless: bool,
+    },
+    /// Print a self-contained HTML report with the formatted grammar, the
+    /// FIRST and FOLLOW sets, the LL(1) conflicts and the rule dependencies
+    HtmlReport {
+        /// Report no conflicts between two regexes
+        #[arg(long)]
+        ignore_regex_conflicts: bool,
+    },
+    /// Print the expansion tree of the grammar from the start symbol
+    Tree {
+        /// Maximum number of nested non-terminal expansions
+        #[arg(long)]
+        depth: Option<usize>,
     },
+    /// Print the grammar's rules as a JSON description, which `--from json`
+    /// reads back
+    DumpAst,
 }
 
-fn read_stdin() -> String {
-    let mut input = String::new();
-    std::io::stdin()
-        .read_to_string(&mut input)
-        .expect("Failed to read from stdin");
-    input
+/// Parses a count of tokens or steps that is meaningless at 0, such as k
+fn at_least_one(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(count) => Ok(count),
+        Err(error) => Err(error.to_string()),
+    }
 }
 
-fn parse_sebnf(input: &str) -> Result<Sebnf, ParseError> {
-    let tokens: Result<Vec<_>, _> = Token::lexer(input)
-        .spanned()
-        .map(|(res, span)| res.map(|t| (t, span.clone())).map_err(|_| span))
+/// The SEBNF source of the input and its display name: the selected
+/// `grammar` sections of SEBNF input, or the SEBNF form of a grammar
+/// description, which diagnostics then point into.
+fn sebnf_input(
+    (input, source_name): (String, String),
+    from: Dialect,
+    sections: &[String],
+) -> Result<(String, String), CliError> {
+    let description = match from {
+        Dialect::Sebnf => {
+            let input = sections::select(&input, &source_name, sections)?;
+            return Ok((input, source_name));
+        }
+        Dialect::Json => description::parse_json(&input)?,
+        #[cfg(feature = "yaml")]
+        Dialect::Yaml => description::parse_yaml(&input)?,
+    };
+    let input = description::sebnf_source(&description)?;
+    Ok((input, format!("{} (as SEBNF)", source_name)))
+}
+
+/// Reads and decodes the grammar source, returning it together with its
+/// display name.
+fn read_input(
+    path: Option<&Path>,
+    encoding: Option<InputEncoding>,
+) -> Result<(Decoded, String), CliError> {
+    let (bytes, name) = match path {
+        Some(path) => {
+            let bytes = std::fs::read(path).map_err(|source| CliError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            (bytes, path.to_path_buf())
+        }
+        None => {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(|source| CliError::Io {
+                    path: PathBuf::from("<stdin>"),
+                    source,
+                })?;
+            (bytes, PathBuf::from("<stdin>"))
+        }
+    };
+    let decoded = encoding::decode(&bytes, encoding.map(Encoding::from)).map_err(|source| {
+        CliError::Encoding {
+            path: name.clone(),
+            source,
+        }
+    })?;
+    Ok((decoded, name.display().to_string()))
+}
+
+/// Like [`read_input`], for a file that is only read
+fn read_text(path: &Path, encoding: Option<InputEncoding>) -> Result<(String, String), CliError> {
+    let (decoded, name) = read_input(Some(path), encoding)?;
+    Ok((decoded.text, name))
+}
+
+/// Reads the token class declarations, or an empty mapping without `path`.
+fn read_token_classes(path: Option<&Path>) -> Result<TokenClasses, CliError> {
+    let Some(path) = path else {
+        return Ok(TokenClasses::default());
+    };
+    let source = std::fs::read_to_string(path).map_err(|source| CliError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(TokenClasses::parse(&source, &path.display().to_string())?)
+}
+
+fn read_config(path: Option<&Path>) -> Result<Config, CliError> {
+    let Some(path) = path else {
+        return Ok(Config::default());
+    };
+    let source = std::fs::read_to_string(path).map_err(|source| CliError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(Config::parse(&source, &path.display().to_string())?)
+}
+
+/// Cargo features compiled into this binary
+const FEATURES: &[(&str, bool)] = &[
+    ("yaml", cfg!(feature = "yaml")),
+    ("serde", cfg!(feature = "serde")),
+    ("wasm", cfg!(feature = "wasm")),
+];
+
+fn capabilities() -> String {
+    let command = Cli::command();
+    let subcommands: Vec<_> = command
+        .get_subcommands()
+        .map(|sub| {
+            let options: Vec<_> = sub
+                .get_arguments()
+                .filter(|arg| !arg.is_global_set() && arg.get_id() != "help")
+                .map(|arg| {
+                    let values: Vec<_> = arg
+                        .get_possible_values()
+                        .iter()
+                        .map(|v| v.get_name().to_string())
+                        .collect();
+                    serde_json::json!({
+                        "name": arg.get_id().as_str(),
+                        "long": arg.get_long(),
+                        "positional": arg.is_positional(),
+                        "values": values,
+                    })
+                })
+                .collect();
+            serde_json::json!({
+                "name": sub.get_name(),
+                "about": sub.get_about().map(|a| a.to_string()),
+                "options": options,
+            })
+        })
+        .collect();
+    let global_options: Vec<_> = command
+        .get_arguments()
+        .filter_map(|arg| arg.get_long())
+        .collect();
+    let formats: Vec<_> = OutputFormat::value_variants()
+        .iter()
+        .filter_map(|f| f.to_possible_value())
+        .map(|v| v.get_name().to_string())
+        .collect();
+    let dialects: Vec<_> = Dialect::value_variants()
+        .iter()
+        .filter_map(|d| d.to_possible_value())
+        .map(|v| v.get_name().to_string())
+        .collect();
+    let features: serde_json::Map<_, _> = FEATURES
+        .iter()
+        .map(|(name, enabled)| (name.to_string(), serde_json::Value::Bool(*enabled)))
         .collect();
 
-    let tokens = match tokens {
-        Ok(t) => t,
-        Err(span) => {
-            return Err(ParseError::LexError {
-                src: NamedSource::new("<stdin>", input.to_string()),
-                span: (span.start, span.len()).into(),
-            });
+    let description = serde_json::json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "dialects": dialects,
+        "subcommands": subcommands,
+        "global_options": global_options,
+        "output_formats": formats,
+        "features": features,
+    });
+    to_json_string(&description)
+}
+
+fn to_json_string(value: &serde_json::Value) -> String {
+    let mut out = serde_json::to_string_pretty(value).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+/// `{"code", "message", "span": [start, end]}` for a diagnostic's first label
+fn diagnostic_json(diagnostic: &(impl miette::Diagnostic + ?Sized)) -> serde_json::Value {
+    let span = diagnostic
+        .labels()
+        .and_then(|mut labels| labels.next())
+        .map(|label| [label.offset(), label.offset() + label.len()]);
+    serde_json::json!({
+        "code": diagnostic.code().map(|c| c.to_string()),
+        "message": diagnostic.to_string(),
+        "span": span,
+    })
+}
+
+/// The text of `validate --strict`: "Valid SEBNF", or the derivation cycles
+/// and unreachable rules found
+fn strict_report(
+    cycles: &[cycles::DerivationCycle],
+    unreachable: &[reachability::UnreachableRule],
+) -> String {
+    let mut text = match cycles.len() {
+        0 if unreachable.is_empty() => "Valid SEBNF\n".to_string(),
+        0 => String::new(),
+        n => format!("Found {} derivation cycle(s):\n", n),
+    };
+    for (i, cycle) in cycles.iter().enumerate() {
+        text.push_str(&format!("\n{}. {}", i + 1, cycle));
+    }
+    if !unreachable.is_empty() {
+        if !cycles.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&format!(
+            "Found {} unreachable rule(s):\n",
+            unreachable.len()
+        ));
+        for rule in unreachable {
+            text.push_str(&format!("  {}\n", rule));
         }
+    }
+    text
+}
+
+/// `validate` of a file with several grammar sections and no `--grammar`:
+/// every section is checked as a grammar of its own and reported by name.
+fn validate_sections(
+    file: &Decoded,
+    source_name: &str,
+    found: &[sections::Section],
+    strict: bool,
+    format: OutputFormat,
+    lowering: &LoweringOptions,
+    verbosity: Verbosity,
+) -> Result<(String, Exit), CliError> {
+    // Sections are blanked out, so the offsets of every section point into
+    // the same file
+    let mut log = SarifLog::new(source_name, &file.text).with_file(file);
+    let mut text = String::new();
+    let mut reports = Vec::new();
+    let mut exit = Exit::Ok;
+    for section in found {
+        let input = sections::select(&file.text, source_name, std::slice::from_ref(&section.name))?;
+        let sebnf = match Sebnf::parse_str(&input, source_name) {
+            Ok(sebnf) => sebnf,
+            Err(e) => {
+                exit = Exit::ParseError;
+                for error in &e.errors {
+                    log.add_diagnostic(error, Level::Error);
+                }
+                let errors: Vec<_> = e.errors.iter().map(diagnostic_json).collect();
+                reports.push(serde_json::json!({
+                    "name": section.name,
+                    "valid": false,
+                    "errors": errors,
+                    "warnings": [],
+                }));
+                text.push_str(&format!(
+                    "grammar {}: {} error(s)\n",
+                    section.name,
+                    e.errors.len()
+                ));
+                if format == OutputFormat::Text && verbosity != Verbosity::Quiet {
+                    eprintln!("{:?}", miette::Report::new(e));
+                }
+                continue;
+            }
+        };
+
+        // No FOLLOW sets are computed, see `uses_follow` in `run`
+        let warnings: Vec<_> = (sebnf.warnings(&input, source_name).into_iter())
+            .filter(|w| !matches!(w, GrammarWarning::ReferencedStartSymbol { .. }))
+            .collect();
+        let bnf = sebnf.to_bnf_with(lowering);
+        let (cycles, unreachable) = if strict {
+            let unreachable = reachability::reachability(&sebnf).rules;
+            (cycles::derivation_cycles(&bnf), unreachable)
+        } else {
+            Default::default()
+        };
+        if (!cycles.is_empty() || !unreachable.is_empty()) && exit == Exit::Ok {
+            exit = Exit::AnalysisFailure;
+        }
+
+        for warning in &warnings {
+            log.add_diagnostic(warning, Level::Warning);
+        }
+        for cycle in &cycles {
+            log.add_derivation_cycle(cycle, &bnf);
+        }
+        for rule in &unreachable {
+            log.add_unreachable_rule(rule, sebnf.rule_spans.get(&rule.rule).cloned());
+        }
+        let mut report = serde_json::json!({
+            "name": section.name,
+            "valid": true,
+            "errors": [],
+            "warnings": warnings.iter().map(diagnostic_json).collect::<Vec<_>>(),
+        });
+        if strict {
+            report["cycles"] = (cycles.iter())
+                .map(|cycle| cycle.to_string().trim_end().to_string())
+                .collect();
+            report["unreachable"] = unreachable.iter().map(|rule| rule.rule.clone()).collect();
+        }
+        reports.push(report);
+        text.push_str(&format!(
+            "grammar {}: {}",
+            section.name,
+            strict_report(&cycles, &unreachable)
+        ));
+        if verbosity != Verbosity::Quiet {
+            for warning in warnings {
+                eprintln!("{:?}", miette::Report::new(warning));
+            }
+        }
+    }
+
+    let text = match format {
+        OutputFormat::Text => text,
+        OutputFormat::Sarif => log.to_json(),
+        OutputFormat::Json => to_json_string(&serde_json::json!({ "sections": reports })),
+    };
+    Ok((text, exit))
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it into
+/// place, so an interrupted run never leaves a truncated file behind.
+fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), CliError> {
+    let io_err = |source| CliError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
     };
+    let file_name = path.file_name().ok_or_else(|| {
+        io_err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "output path has no file name",
+        ))
+    })?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(".tmp{}", std::process::id()));
+    let tmp_path = dir.join(tmp_name);
+
+    let result = std::fs::File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_ref())?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result.map_err(io_err)
+}
+
+fn emit(output: Option<&Path>, contents: &str, verbosity: Verbosity) -> Result<(), CliError> {
+    match output {
+        Some(path) => write_atomic(path, contents),
+        None if verbosity == Verbosity::Quiet => Ok(()),
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            stdout
+                .write_all(contents.as_bytes())
+                .and_then(|()| stdout.flush())
+                .map_err(|source| CliError::Io {
+                    path: PathBuf::from("<stdout>"),
+                    source,
+                })
+        }
+    }
+}
+
+/// Documented process exit codes, shared by all subcommands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    /// The command succeeded
+    Ok = 0,
+    /// The grammar was analyzed and failed the check (e.g. it is not LL(1))
+    AnalysisFailure = 1,
+    /// The grammar or another input could not be parsed
+    ParseError = 2,
+    /// Reading input or writing output failed
+    IoError = 3,
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit as u8)
+    }
+}
 
-    let sebnf = Sebnf::parse(tokens, input.to_string(), "<stdin>")?;
-    sebnf.validate(input.to_string(), "<stdin>")?;
-    Ok(sebnf)
+impl Exit {
+    fn failure_if(failed: bool) -> Self {
+        if failed {
+            Exit::AnalysisFailure
+        } else {
+            Exit::Ok
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+impl Verbosity {
+    /// Prints a progress note to stderr in verbose mode.
+    fn note(self, message: impl std::fmt::Display) {
+        if self == Verbosity::Verbose {
+            eprintln!("note: {}", message);
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error, miette::Diagnostic)]
 enum CliError {
     #[error(transparent)]
     #[diagnostic(transparent)]
-    Parse(#[from] ParseError),
+    Parse(#[from] ParseErrors),
     #[error(transparent)]
     #[diagnostic(transparent)]
     Ll1(#[from] Ll1Error),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Render(#[from] RenderError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Query(#[from] QueryError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    TokenClass(#[from] TokenClassError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Section(#[from] SectionError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Transform(#[from] TransformError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Generate(#[from] GenerateError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    SelfCheck(#[from] SelfCheckError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Description(#[from] DescriptionError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Keyword(#[from] KeywordError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Peg(#[from] PegError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    LexCheck(#[from] LexCheckError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Explain(#[from] ExplainError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Disjoint(#[from] DisjointError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Codegen(#[from] CodegenError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Interpret(#[from] InterpretError),
+    #[error("failed to decode '{}'", path.display())]
+    #[diagnostic(
+        code(sebnf::invalid_encoding),
+        help("if the file is not UTF-8, pass its encoding with --input-encoding")
+    )]
+    Encoding {
+        path: PathBuf,
+        #[source]
+        source: EncodingError,
+    },
+    #[error("{command} only works on SEBNF input")]
+    #[diagnostic(code(sebnf::not_sebnf), help("drop --from to read SEBNF"))]
+    NotSebnf { command: &'static str },
+    #[error("failed to access '{}'", path.display())]
+    #[diagnostic(code(sebnf::io_error))]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+impl CliError {
+    fn exit(&self) -> Exit {
+        match self {
+            CliError::Parse(_)
+            | CliError::TokenClass(_)
+            | CliError::Config(_)
+            | CliError::Section(_)
+            | CliError::Description(_)
+            | CliError::Encoding { .. }
+            | CliError::NotSebnf { .. }
+            | CliError::Query(_)
+            | CliError::Interpret(InterpretError::Lex { .. })
+            | CliError::Interpret(InterpretError::Syntax(_))
+            | CliError::Interpret(InterpretError::Rejected { .. }) => Exit::ParseError,
+            // Invalid regexes are only detected while checking the grammar
+            CliError::Ll1(_) | CliError::Snapshot(SnapshotError::Ll1(_)) => Exit::ParseError,
+            CliError::Render(_) | CliError::Snapshot(SnapshotError::Render(_)) => Exit::IoError,
+            CliError::Io { .. } => Exit::IoError,
+            CliError::Transform(_)
+            | CliError::Explain(_)
+            | CliError::Disjoint(DisjointError::UnknownRule(_))
+            | CliError::Disjoint(DisjointError::TooManySentences(_))
+            | CliError::Generate(GenerateError::EmptyLanguage(_))
+            | CliError::SelfCheck(_)
+            | CliError::Interpret(InterpretError::TooManySteps(_))
+            | CliError::Codegen(CodegenError::NoRules)
+            | CliError::Codegen(CodegenError::NotLl1 { .. })
+            | CliError::Codegen(CodegenError::Generate(GenerateError::EmptyLanguage(_))) => {
+                Exit::AnalysisFailure
+            }
+            CliError::Generate(GenerateError::InvalidRegex { .. })
+            | CliError::Disjoint(DisjointError::InvalidRegex { .. })
+            | CliError::Codegen(CodegenError::Generate(GenerateError::InvalidRegex { .. }))
+            | CliError::Keyword(_)
+            | CliError::Peg(_)
+            | CliError::Interpret(InterpretError::InvalidRegex { .. })
+            | CliError::LexCheck(_) => Exit::ParseError,
+        }
+    }
 }
 
 fn main() -> ExitCode {
     let cli = Cli::parse();
-    let input = read_stdin();
+    let verbosity = if cli.quiet {
+        Verbosity::Quiet
+    } else if cli.verbose {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    };
+
+    let result = read_config(cli.config.as_deref()).and_then(|config| {
+        let symbols = if cli.ascii {
+            Symbols::ascii()
+        } else {
+            config.symbols
+        };
+        let witnesses = WitnessOptions {
+            max_len: cli.max_witness_len,
+            skip: cli.no_witness,
+        };
+        symbols.scope(|| witnesses.scope(|| run(cli, verbosity)))
+    });
 
-    let result: Result<ExitCode, CliError> = (|| match cli.command {
-        Commands::Validate => {
-            parse_sebnf(&input)?;
-            println!("Valid SEBNF");
-            Ok(ExitCode::SUCCESS)
+    match result {
+        Ok(exit) => exit.into(),
+        Err(e) => {
+            let exit = e.exit();
+            eprintln!("{:?}", miette::Report::new(e));
+            exit.into()
         }
-        Commands::ToBnf => {
-            let sebnf = parse_sebnf(&input)?;
-            let bnf = sebnf.to_bnf();
-            print!("{}", bnf);
-            Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn run(cli: Cli, verbosity: Verbosity) -> Result<Exit, CliError> {
+    if let Commands::Capabilities = cli.command {
+        emit(cli.output.as_deref(), &capabilities(), verbosity)?;
+        return Ok(Exit::Ok);
+    }
+    let token_classes = read_token_classes(cli.token_classes.as_deref())?;
+    if !token_classes.classes.is_empty() {
+        verbosity.note(format_args!(
+            "loaded {} token class mappings",
+            token_classes.classes.len()
+        ));
+    }
+    if let Commands::Repl { path } = &cli.command {
+        let stdin = std::io::stdin();
+        let prompt = stdin.is_terminal();
+        let initial = path.as_deref().or(cli.input.as_deref());
+        let session = repl::Session::new(token_classes, cli.augment_start);
+        repl::run(session, stdin.lock(), std::io::stdout(), prompt, initial).map_err(|source| {
+            CliError::Io {
+                path: PathBuf::from("<stdin>"),
+                source,
+            }
+        })?;
+        return Ok(Exit::Ok);
+    }
+
+    if let Commands::Serve { port, host } = &cli.command {
+        let address = format!("{}:{}", host, port);
+        let io_err = |source| CliError::Io {
+            path: PathBuf::from(&address),
+            source,
+        };
+        let listener = std::net::TcpListener::bind(&address).map_err(io_err)?;
+        if verbosity != Verbosity::Quiet {
+            eprintln!("Serving on http://{}", address);
         }
-        Commands::ExtractSets => {
-            let sebnf = parse_sebnf(&input)?;
-            let bnf = sebnf.to_bnf();
-            let sets = bnf.first_and_follow_sets();
-            print!("{}", sets);
-            Ok(ExitCode::SUCCESS)
-        }
-        Commands::IsLl1 { ignore_regex_conflicts } => {
-            let sebnf = parse_sebnf(&input)?;
-            let bnf = sebnf.to_bnf();
-            let result = bnf.is_ll1(ignore_regex_conflicts)?;
-            print!("{}", result);
-            if result.is_ll1() {
-                Ok(ExitCode::SUCCESS)
+        let server = serve::Server {
+            token_classes,
+            augment_start: cli.augment_start,
+            symbols: Symbols::with_current(Symbols::clone),
+        };
+        server.run(listener).map_err(io_err)?;
+        return Ok(Exit::Ok);
+    }
+
+    let lowering = LoweringOptions {
+        shared_epsilon: cli.shared_epsilon,
+        inline_helpers: cli.inline_helpers,
+        fold_literals: cli.fold_literals.map(LiteralForm::from),
+        stable_names: cli.stable_names,
+    };
+    if let Commands::Compare {
+        old,
+        new,
+        what,
+        ignore_regex_conflicts,
+    } = &cli.command
+    {
+        let load = |path: &Path| -> Result<Bnf, CliError> {
+            let (input, source_name) =
+                sebnf_input(read_text(path, cli.input_encoding)?, cli.from, &cli.grammar)?;
+            let mut sebnf = Sebnf::parse_str(&input, &source_name)?;
+            if let Some(start) = &cli.start {
+                sebnf.set_start(start).map_err(ParseErrors::from)?;
+            }
+            let bnf = sebnf.to_bnf_with(&lowering);
+            let bnf = if cli.augment_start {
+                bnf.augment_start()
             } else {
-                Ok(ExitCode::FAILURE)
+                bnf
+            };
+            Ok(token_classes.apply(&bnf))
+        };
+        let (old, new) = (load(old)?, load(new)?);
+        let analyze = |bnf| Bnf::analyze(bnf).ignore_regex_conflicts(*ignore_regex_conflicts);
+        let (old, new) = (analyze(&old), analyze(&new));
+        let (text, changed) = match what {
+            CompareWhat::Conflicts => {
+                let diff = compare::compare_conflicts(old.conflicts()?, new.conflicts()?);
+                (diff.to_string(), !diff.is_empty())
+            }
+            CompareWhat::Sets => {
+                let diff = compare::compare_sets(old.sets(), new.sets());
+                (diff.to_string(), !diff.is_empty())
             }
+            CompareWhat::Table => {
+                let diff = compare::compare_tables(old.bnf(), old.table(), new.bnf(), new.table());
+                (diff.to_string(), !diff.is_empty())
+            }
+        };
+        emit(cli.output.as_deref(), &text, verbosity)?;
+        return Ok(Exit::failure_if(changed));
+    }
+
+    if let Commands::Merge { base, ours, theirs } = &cli.command {
+        let load = |path: &Path| -> Result<Sebnf, CliError> {
+            let (input, source_name) =
+                sebnf_input(read_text(path, cli.input_encoding)?, cli.from, &cli.grammar)?;
+            Ok(Sebnf::parse_str(&input, &source_name)?)
+        };
+        let merged = merge::merge(&load(base)?, &load(ours)?, &load(theirs)?);
+        emit(cli.output.as_deref(), &merged.text, verbosity)?;
+        if !merged.is_clean() && verbosity != Verbosity::Quiet {
+            eprintln!("conflicting rules: {}", merged.conflicts.join(", "));
         }
-    })();
+        return Ok(Exit::failure_if(!merged.is_clean()));
+    }
 
-    match result {
-        Ok(code) => code,
-        Err(e) => {
-            eprintln!("{:?}", miette::Report::new(e));
-            ExitCode::FAILURE
+    if let Commands::Changelog { old, new, format } = &cli.command {
+        let load = |path: &Path| -> Result<Sebnf, CliError> {
+            let (input, source_name) =
+                sebnf_input(read_text(path, cli.input_encoding)?, cli.from, &cli.grammar)?;
+            Ok(Sebnf::parse_str(&input, &source_name)?)
+        };
+        let changes = changelog::changelog(&load(old)?, &load(new)?);
+        let text = match format {
+            ChangelogFormat::Md => changes.to_markdown(),
+            ChangelogFormat::Json => to_json_string(&changes.to_json()),
+        };
+        emit(cli.output.as_deref(), &text, verbosity)?;
+        return Ok(Exit::Ok);
+    }
+
+    if let Commands::Equiv {
+        first,
+        second,
+        max_len,
+        limit,
+    } = &cli.command
+    {
+        let load = |path: &Path| -> Result<Bnf, CliError> {
+            let (input, source_name) =
+                sebnf_input(read_text(path, cli.input_encoding)?, cli.from, &cli.grammar)?;
+            let mut sebnf = Sebnf::parse_str(&input, &source_name)?;
+            if let Some(start) = &cli.start {
+                sebnf.set_start(start).map_err(ParseErrors::from)?;
+            }
+            Ok(token_classes.apply(&sebnf.to_bnf_with(&lowering)))
+        };
+        let result = equiv::equivalence(&load(first)?, &load(second)?, *max_len, *limit);
+        emit(cli.output.as_deref(), &result.to_string(), verbosity)?;
+        let equivalent = matches!(result, equiv::Equivalence::Equivalent { .. });
+        return Ok(Exit::failure_if(!equivalent));
+    }
+
+    // `dedupe` and `prune` report what they remove themselves
+    let deduping = matches!(cli.command, Commands::Dedupe);
+    let pruning = matches!(cli.command, Commands::Prune);
+    // LR automatons always get a fresh start rule
+    let augmenting = cli.augment_start
+        || matches!(
+            cli.command,
+            Commands::IsSlr1 { .. } | Commands::IsLalr1 { .. }
+        );
+    // A referenced start symbol only matters where FOLLOW sets are computed
+    let uses_follow = matches!(
+        cli.command,
+        Commands::ExtractSets
+            | Commands::TraceSets { .. }
+            | Commands::ExplainSet { .. }
+            | Commands::Explain { .. }
+            | Commands::Repair { .. }
+            | Commands::IsLlk { .. }
+            | Commands::MinK { .. }
+            | Commands::IsLl1 { .. }
+            | Commands::Snapshot { .. }
+            | Commands::Query { .. }
+            | Commands::Codegen { .. }
+            | Commands::Parse {
+                algo: ParseAlgo::Backtrack,
+                ..
+            }
+            | Commands::HtmlReport { .. }
+    );
+    let reported = |w: &GrammarWarning| match w {
+        GrammarWarning::ReferencedStartSymbol { .. } => uses_follow && !augmenting,
+        GrammarWarning::RepeatedAlternative { .. } | GrammarWarning::IdenticalRules { .. } => {
+            !deduping
+        }
+        GrammarWarning::DeadAlternative { .. } => !pruning,
+        _ => true,
+    };
+    let to_bnf = |sebnf: &Sebnf| {
+        let bnf = sebnf.to_bnf_with(&lowering);
+        if cli.augment_start {
+            bnf.augment_start()
+        } else {
+            bnf
+        }
+    };
+    if let Commands::ToBnf { files } = &cli.command
+        && !files.is_empty()
+    {
+        // Without `-i`, the files alone make up the grammar
+        let sources = (cli.input.iter().chain(files))
+            .map(|path| sebnf_input(read_text(path, cli.input_encoding)?, cli.from, &cli.grammar))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut sebnf = Sebnf::parse_files(&sources)?;
+        if let Some(start) = &cli.start {
+            sebnf.set_start(start).map_err(ParseErrors::from)?;
+        }
+        verbosity.note(format_args!(
+            "combined {} rules from {} files",
+            sebnf.rules.len(),
+            sources.len()
+        ));
+        if verbosity != Verbosity::Quiet {
+            for warning in sebnf.file_warnings(&sources).into_iter().filter(reported) {
+                eprintln!("{:?}", miette::Report::new(warning));
+            }
+        }
+        emit(cli.output.as_deref(), &to_bnf(&sebnf).render()?, verbosity)?;
+        return Ok(Exit::Ok);
+    }
+
+    let (file, file_name) = read_input(cli.input.as_deref(), cli.input_encoding)?;
+    if let Commands::Validate { strict, format } = cli.command
+        && cli.grammar.is_empty()
+        && cli.from == Dialect::Sebnf
+    {
+        let found = sections::sections(&file.text, &file_name)?;
+        if found.len() > 1 {
+            let (text, exit) = validate_sections(
+                &file, &file_name, &found, strict, format, &lowering, verbosity,
+            )?;
+            emit(cli.output.as_deref(), &text, verbosity)?;
+            return Ok(exit);
         }
     }
+    let (input, source_name) = sebnf_input((file.text.clone(), file_name), cli.from, &cli.grammar)?;
+    verbosity.note(format_args!(
+        "read {} bytes from {}",
+        input.len(),
+        source_name
+    ));
+    // Sections are blanked out, so offsets into the input are offsets into
+    // the file, but not those into a converted description
+    let sarif_log = || match cli.from {
+        Dialect::Sebnf => SarifLog::new(&source_name, &input).with_file(&file),
+        _ => SarifLog::new(&source_name, &input),
+    };
+    let warnings = |sebnf: &Sebnf| -> Vec<GrammarWarning> {
+        sebnf
+            .warnings(&input, &source_name)
+            .into_iter()
+            .filter(reported)
+            .collect()
+    };
+    let parse = || {
+        let mut sebnf = Sebnf::parse_str(&input, &source_name)?;
+        if let Some(start) = &cli.start {
+            sebnf.set_start(start)?;
+        }
+        verbosity.note(format_args!("parsed {} rules", sebnf.rules.len()));
+        if verbosity != Verbosity::Quiet {
+            for warning in warnings(&sebnf) {
+                eprintln!("{:?}", miette::Report::new(warning));
+            }
+        }
+        Ok::<_, ParseErrors>(sebnf)
+    };
+    let analysis_bnf = |sebnf: &Sebnf| {
+        let bnf = token_classes.apply(&to_bnf(sebnf));
+        verbosity.note(format_args!("converted to {} BNF rules", bnf.rules.len()));
+        bnf
+    };
+    let (text, exit) = match cli.command {
+        Commands::Validate { strict, format } => match (parse(), format) {
+            (Ok(sebnf), format) if strict => {
+                let bnf = analysis_bnf(&sebnf);
+                let cycles = cycles::derivation_cycles(&bnf);
+                let unreachable = reachability::reachability(&sebnf).rules;
+                let text = match format {
+                    OutputFormat::Text => strict_report(&cycles, &unreachable),
+                    OutputFormat::Sarif => {
+                        let mut log = sarif_log();
+                        for warning in warnings(&sebnf) {
+                            log.add_diagnostic(&warning, Level::Warning);
+                        }
+                        for cycle in &cycles {
+                            log.add_derivation_cycle(cycle, &bnf);
+                        }
+                        for rule in &unreachable {
+                            log.add_unreachable_rule(
+                                rule,
+                                sebnf.rule_spans.get(&rule.rule).cloned(),
+                            );
+                        }
+                        log.to_json()
+                    }
+                    OutputFormat::Json => {
+                        let warnings: Vec<_> =
+                            warnings(&sebnf).iter().map(diagnostic_json).collect();
+                        let cycles: Vec<_> = (cycles.iter())
+                            .map(|cycle| cycle.to_string().trim_end().to_string())
+                            .collect();
+                        let json = serde_json::json!({
+                            "valid": true,
+                            "errors": [],
+                            "warnings": warnings,
+                            "cycles": cycles,
+                            "unreachable": unreachable.iter().map(|rule| &rule.rule).collect::<Vec<_>>(),
+                        });
+                        to_json_string(&json)
+                    }
+                };
+                (
+                    text,
+                    Exit::failure_if(!cycles.is_empty() || !unreachable.is_empty()),
+                )
+            }
+            (Ok(_), OutputFormat::Text) => ("Valid SEBNF\n".to_string(), Exit::Ok),
+            (Ok(sebnf), OutputFormat::Sarif) => {
+                let mut log = sarif_log();
+                for warning in warnings(&sebnf) {
+                    log.add_diagnostic(&warning, Level::Warning);
+                }
+                (log.to_json(), Exit::Ok)
+            }
+            (Err(e), OutputFormat::Sarif) => {
+                let mut log = sarif_log();
+                for error in &e.errors {
+                    log.add_diagnostic(error, Level::Error);
+                }
+                (log.to_json(), Exit::ParseError)
+            }
+            (Ok(sebnf), OutputFormat::Json) => {
+                let warnings: Vec<_> = warnings(&sebnf).iter().map(diagnostic_json).collect();
+                let json = serde_json::json!({ "valid": true, "errors": [], "warnings": warnings });
+                (to_json_string(&json), Exit::Ok)
+            }
+            (Err(e), OutputFormat::Json) => {
+                let errors: Vec<_> = e.errors.iter().map(diagnostic_json).collect();
+                let json = serde_json::json!({ "valid": false, "errors": errors, "warnings": [] });
+                (to_json_string(&json), Exit::ParseError)
+            }
+            (Err(e), OutputFormat::Text) => return Err(e.into()),
+        },
+        Commands::ToBnf { .. } => {
+            let bnf = to_bnf(&parse()?);
+            (bnf.render()?, Exit::Ok)
+        }
+        Commands::Resugar => {
+            let bnf = parse()?.to_bnf_with(&lowering);
+            (resugar(&bnf).to_string(), Exit::Ok)
+        }
+        Commands::Simplify { unit_productions } => {
+            let bnf = to_bnf(&parse()?);
+            let mut engine = Engine::new();
+            if unit_productions {
+                engine.push(EliminateUnitProductions);
+            }
+            let (bnf, log) = engine.with(Simplify).run(&bnf)?;
+            let mut text: String = log
+                .changes
+                .iter()
+                .map(|change| format!("(* {} *)\n", change))
+                .collect();
+            text.push_str(&bnf.render()?);
+            (text, Exit::Ok)
+        }
+        Commands::Transform { passes } => {
+            let sebnf = parse()?;
+            let pipeline = if passes.is_empty() {
+                Pipeline::standard(&lowering)
+            } else {
+                let mut pipeline = Pipeline::new();
+                for pass in passes {
+                    pass.add_to(&mut pipeline);
+                }
+                pipeline
+            };
+            let (sebnf, log) = pipeline.run_passes(&sebnf)?;
+            for change in &log.changes {
+                verbosity.note(change);
+            }
+            let text = match converter::lower(&sebnf) {
+                Ok(bnf) => bnf.render()?,
+                Err(_) => sebnf.to_string(),
+            };
+            (text, Exit::Ok)
+        }
+        Commands::Rename { from, to } => {
+            let sebnf = parse()?;
+            let rename = RenameSymbol::new(from, to);
+            let renamed = match cli.from {
+                // Only the names change, so comments and layout are kept
+                Dialect::Sebnf => rename.rewrite_source(&sebnf, &file.text)?,
+                Dialect::Json => {
+                    let (sebnf, _) = Engine::new().with(rename).run(&sebnf)?;
+                    to_json_string(&description::describe(&sebnf))
+                }
+                #[cfg(feature = "yaml")]
+                Dialect::Yaml => {
+                    let (sebnf, _) = Engine::new().with(rename).run(&sebnf)?;
+                    serde_yaml::to_string(&description::describe(&sebnf)).unwrap_or_default()
+                }
+            };
+            match (&cli.output, &cli.input) {
+                (None, Some(path)) => {
+                    // In the file's own encoding
+                    write_atomic(path, file.encode(&renamed))?;
+                    verbosity.note(format_args!("rewrote {}", path.display()));
+                    (String::new(), Exit::Ok)
+                }
+                _ => (renamed, Exit::Ok),
+            }
+        }
+        Commands::Fmt {
+            check,
+            align,
+            width,
+            wrap,
+        } => {
+            if cli.from != Dialect::Sebnf {
+                return Err(CliError::NotSebnf { command: "fmt" });
+            }
+            let options = FormatOptions {
+                align: align.into(),
+                width,
+                wrap: wrap.into(),
+            };
+            // Spans into the chosen section are spans into the whole file
+            let formatted = format_source(&file.text, &parse()?, &options);
+            match (&cli.output, &cli.input) {
+                _ if check => {
+                    let unformatted = formatted != file.text;
+                    if unformatted && verbosity != Verbosity::Quiet {
+                        eprintln!("{} is not formatted", source_name);
+                    }
+                    (String::new(), Exit::failure_if(unformatted))
+                }
+                (None, Some(path)) => {
+                    if formatted != file.text {
+                        write_atomic(path, file.encode(&formatted))?;
+                        verbosity.note(format_args!("formatted {}", path.display()));
+                    }
+                    (String::new(), Exit::Ok)
+                }
+                _ => (formatted, Exit::Ok),
+            }
+        }
+        Commands::Extract { root } => {
+            let (sebnf, log) = Engine::new().with(ExtractRoot::new(root)).run(&parse()?)?;
+            for change in &log.changes {
+                verbosity.note(change);
+            }
+            (sebnf.to_string(), Exit::Ok)
+        }
+        Commands::Expand { symbol } => {
+            let (sebnf, log) = Engine::new().with(InlineRule::new(symbol)).run(&parse()?)?;
+            for change in &log.changes {
+                verbosity.note(change);
+            }
+            (sebnf.to_string(), Exit::Ok)
+        }
+        Commands::Prune => {
+            let (sebnf, log) = Engine::new().with(Prune).run(&parse()?)?;
+            if verbosity != Verbosity::Quiet {
+                eprint!("{}", log);
+            }
+            (sebnf.to_string(), Exit::Ok)
+        }
+        Commands::Dedupe => {
+            let (sebnf, log) = Engine::new().with(Dedupe).run(&parse()?)?;
+            if verbosity != Verbosity::Quiet {
+                eprint!("{}", log);
+            }
+            (sebnf.to_string(), Exit::Ok)
+        }
+        Commands::Normalize => {
+            let (sebnf, log) = Engine::new().with(Normalize).run(&parse()?)?;
+            if verbosity != Verbosity::Quiet {
+                eprint!("{}", log);
+            }
+            (sebnf.to_string(), Exit::Ok)
+        }
+        Commands::ExtractSets => {
+            let bnf = analysis_bnf(&parse()?);
+            let sets = bnf.first_and_follow_sets();
+            (sets.render()?, Exit::Ok)
+        }
+        Commands::Nullable => {
+            let bnf = analysis_bnf(&parse()?);
+            (nullable::nullable_rules(&bnf).to_string(), Exit::Ok)
+        }
+        Commands::CheckLeftRecursion => {
+            let bnf = analysis_bnf(&parse()?);
+            let cycles = left_recursion::left_recursion(&bnf);
+            let mut text = match cycles.len() {
+                0 => "No left recursion found.\n".to_string(),
+                n => format!("Found {} left-recursive cycle(s):\n", n),
+            };
+            for (i, cycle) in cycles.iter().enumerate() {
+                text.push_str(&format!("\n{}. {}", i + 1, cycle));
+            }
+            (text, Exit::failure_if(!cycles.is_empty()))
+        }
+        Commands::LexCheck => {
+            let overlaps = lex_check::lex_check(&parse()?)?;
+            let ambiguous = overlaps.iter().filter(|o| o.is_ambiguous()).count();
+            let mut text: String = overlaps.iter().map(|o| o.to_string()).collect();
+            text.push_str(&match overlaps.len() {
+                0 => "No two tokens match a common string.\n".to_string(),
+                n => format!(
+                    "{} terminal(s) shadowing a regex, {} ambiguous pair(s) of regexes\n",
+                    n - ambiguous,
+                    ambiguous
+                ),
+            });
+            (text, Exit::failure_if(ambiguous > 0))
+        }
+        Commands::Finite { enumerate, limit } => {
+            let bnf = analysis_bnf(&parse()?);
+            let size = finite::language_size(&bnf, limit);
+            let mut text = size.to_string();
+            if let finite::LanguageSize::Finite { sentences, .. } = &size
+                && enumerate
+            {
+                for sentence in sentences {
+                    text.push_str(&format!("{}\n", sentence));
+                }
+            }
+            (text, Exit::failure_if(!size.is_finite()))
+        }
+        Commands::TraceSets { symbol } => {
+            let bnf = analysis_bnf(&parse()?);
+            if !bnf.rules.contains_key(&symbol) {
+                return Err(TransformError::UnknownRule(symbol).into());
+            }
+            (trace::trace_sets(&bnf, &symbol).to_string(), Exit::Ok)
+        }
+        Commands::ExplainSet { set, symbol, item } => {
+            let bnf = analysis_bnf(&parse()?);
+            if !bnf.rules.contains_key(&symbol) {
+                return Err(TransformError::UnknownRule(symbol).into());
+            }
+            let explanation = trace::explain_member(&bnf, set.into(), &symbol, &item)?;
+            (explanation.to_string(), Exit::Ok)
+        }
+        Commands::Explain {
+            symbol,
+            ignore_regex_conflicts,
+        } => {
+            let bnf = analysis_bnf(&parse()?);
+            if !bnf.rules.contains_key(&symbol) {
+                return Err(TransformError::UnknownRule(symbol).into());
+            }
+            let report = explain::explain_symbol(&bnf, &symbol, ignore_regex_conflicts)?;
+            (report.render()?, Exit::Ok)
+        }
+        Commands::Repair {
+            apply,
+            ignore_regex_conflicts,
+        } => {
+            let bnf = analysis_bnf(&parse()?);
+            if apply {
+                let (bnf, log) = repair::apply_repairs(&bnf, ignore_regex_conflicts)?;
+                let mut text: String = (log.changes.iter())
+                    .map(|change| format!("(* {} *)\n", change))
+                    .collect();
+                text.push_str(&bnf.render()?);
+                let ll1 = bnf.is_ll1(ignore_regex_conflicts)?.is_ll1();
+                (text, Exit::failure_if(!ll1))
+            } else {
+                let rules = repair::suggest_repairs(&bnf, ignore_regex_conflicts)?;
+                let mut text = match rules.len() {
+                    0 => "Grammar is LL(1), nothing to repair.\n".to_string(),
+                    n => format!("Found conflicts in {} rule(s):\n", n),
+                };
+                for (i, rule) in rules.iter().enumerate() {
+                    text.push_str(&format!("\n{}. {}", i + 1, rule));
+                }
+                let unresolved = rules.iter().any(|rule| rule.resolving().is_none());
+                (text, Exit::failure_if(unresolved))
+            }
+        }
+        Commands::IsLlk {
+            k,
+            ignore_regex_conflicts,
+            format,
+        } => {
+            let sebnf = parse()?;
+            let bnf = analysis_bnf(&sebnf);
+            let result = bnf.is_llk(k, ignore_regex_conflicts)?;
+            let text = match format {
+                OutputFormat::Text => result.render()?,
+                OutputFormat::Json => to_json_string(&result.to_json()),
+                OutputFormat::Sarif => {
+                    let mut log = sarif_log();
+                    for conflict in &result.conflicts {
+                        let span = (bnf.llk_conflict_span(conflict))
+                            .or_else(|| rule_span(&sebnf, &bnf, &conflict.non_terminal));
+                        log.add_llk_conflict(conflict, span);
+                    }
+                    log.to_json()
+                }
+            };
+            (text, Exit::failure_if(!result.is_llk()))
+        }
+        Commands::MinK {
+            max_k,
+            ignore_regex_conflicts,
+            format,
+        } => {
+            let sebnf = parse()?;
+            let bnf = analysis_bnf(&sebnf);
+            let result = bnf.smallest_llk(max_k, ignore_regex_conflicts)?;
+            let found = result.is_llk();
+            let text = match format {
+                // The conflicts left at MAX_K
+                OutputFormat::Sarif => {
+                    let mut log = sarif_log();
+                    for conflict in &result.conflicts {
+                        let span = (bnf.llk_conflict_span(conflict))
+                            .or_else(|| rule_span(&sebnf, &bnf, &conflict.non_terminal));
+                        log.add_llk_conflict(conflict, span);
+                    }
+                    log.to_json()
+                }
+                OutputFormat::Json => to_json_string(&serde_json::json!({
+                    "k": found.then_some(result.k),
+                    "max_k": max_k,
+                    "conflicts": result.to_json()["conflicts"],
+                })),
+                _ if !found => format!(
+                    "Grammar is not LL(k) for any k up to {}\n\n{}",
+                    result.k,
+                    result.render()?
+                ),
+                _ if result.k == 1 => "Grammar is LL(1)\n".to_string(),
+                _ => format!(
+                    "Grammar is LL({}), but not LL({})\n",
+                    result.k,
+                    result.k - 1
+                ),
+            };
+            (text, Exit::failure_if(!found))
+        }
+        Commands::IsSlr1 {
+            ignore_regex_conflicts,
+            format,
+        } => {
+            let sebnf = parse()?;
+            let bnf = analysis_bnf(&sebnf);
+            let result = bnf.is_slr1(ignore_regex_conflicts)?;
+            let text = match format {
+                OutputFormat::Text => result.render()?,
+                OutputFormat::Json => to_json_string(&result.to_json()),
+                OutputFormat::Sarif => {
+                    let mut log = sarif_log();
+                    for conflict in &result.conflicts {
+                        log.add_lr_conflict(conflict, bnf.lr_conflict_span(conflict));
+                    }
+                    log.to_json()
+                }
+            };
+            (text, Exit::failure_if(!result.is_conflict_free()))
+        }
+        Commands::IsLalr1 {
+            ignore_regex_conflicts,
+            table,
+            format,
+        } => {
+            let sebnf = parse()?;
+            let bnf = analysis_bnf(&sebnf);
+            let result = bnf.is_lalr1(ignore_regex_conflicts)?;
+            let text = match format {
+                OutputFormat::Text if table => {
+                    let table = bnf.lalr1_table();
+                    format!("{}\n{}", result.render()?, table.render(&bnf)?)
+                }
+                OutputFormat::Text => result.render()?,
+                OutputFormat::Json => to_json_string(&result.to_json()),
+                OutputFormat::Sarif => {
+                    let mut log = sarif_log();
+                    for conflict in &result.conflicts {
+                        log.add_lr_conflict(conflict, bnf.lr_conflict_span(conflict));
+                    }
+                    log.to_json()
+                }
+            };
+            (text, Exit::failure_if(!result.is_conflict_free()))
+        }
+        Commands::IsLl1 {
+            ignore_regex_conflicts,
+            lookahead,
+            ambiguity,
+            example,
+            format: OutputFormat::Sarif,
+            ..
+        } => {
+            let mut log = sarif_log();
+            let exit = match parse() {
+                Ok(sebnf) => {
+                    let bnf = analysis_bnf(&sebnf);
+                    match bnf.is_ll1(ignore_regex_conflicts) {
+                        Ok(mut result) => {
+                            if let Some(max_k) = lookahead {
+                                bnf.annotate_lookahead(&mut result, max_k, ignore_regex_conflicts)?;
+                            }
+                            if let Some(max_k) = ambiguity {
+                                bnf.annotate_ambiguity(&mut result, max_k, ignore_regex_conflicts)?;
+                            }
+                            if example {
+                                bnf.annotate_input(&mut result)?;
+                            }
+                            for conflict in &result.conflicts {
+                                let span = bnf
+                                    .conflict_span(conflict)
+                                    .or_else(|| rule_span(&sebnf, &bnf, &conflict.non_terminal));
+                                log.add_ll1_conflict(conflict, span);
+                            }
+                            Exit::failure_if(!result.is_ll1())
+                        }
+                        Err(e) => {
+                            log.add_diagnostic(&e, Level::Error);
+                            Exit::ParseError
+                        }
+                    }
+                }
+                Err(e) => {
+                    for error in &e.errors {
+                        log.add_diagnostic(error, Level::Error);
+                    }
+                    Exit::ParseError
+                }
+            };
+            (log.to_json(), exit)
+        }
+        Commands::IsLl1 {
+            ignore_regex_conflicts,
+            lookahead,
+            ambiguity,
+            example,
+            annotate_source,
+            format,
+        } => {
+            let sebnf = parse()?;
+            let bnf = analysis_bnf(&sebnf);
+            let mut result = bnf.is_ll1(ignore_regex_conflicts)?;
+            if let Some(max_k) = lookahead {
+                bnf.annotate_lookahead(&mut result, max_k, ignore_regex_conflicts)?;
+            }
+            if let Some(max_k) = ambiguity {
+                bnf.annotate_ambiguity(&mut result, max_k, ignore_regex_conflicts)?;
+            }
+            if example {
+                bnf.annotate_input(&mut result)?;
+            }
+            let text = match format {
+                // Offsets into a converted description are into `input`
+                _ if annotate_source => match cli.from {
+                    Dialect::Sebnf => annotate_conflicts(&file.text, &sebnf, &bnf, &result),
+                    _ => annotate_conflicts(&input, &sebnf, &bnf, &result),
+                },
+                OutputFormat::Json => to_json_string(&result.to_json()),
+                _ => result.render()?,
+            };
+            (text, Exit::failure_if(!result.is_ll1()))
+        }
+        Commands::Analyze {
+            report: Report::RegexUsage { rewrite },
+        } => {
+            let sebnf = parse()?;
+            if rewrite {
+                let (sebnf, log) = Engine::new()
+                    .with(RegexToLiterals)
+                    .run(&sebnf)
+                    .expect("rewriting regexes cannot fail");
+                verbosity.note(format_args!("rewrote {} regexes", log.changes.len()));
+                (sebnf.to_string(), Exit::Ok)
+            } else {
+                let usages = regex_usage::regex_usage(&sebnf);
+                let mut text: String = usages.iter().map(|u| format!("{}\n", u)).collect();
+                let rewritable = usages.iter().filter(|u| u.replacement().is_some()).count();
+                text.push_str(&format!(
+                    "{} of {} regexes can be rewritten as terminals\n",
+                    rewritable,
+                    usages.len()
+                ));
+                (text, Exit::Ok)
+            }
+        }
+        Commands::Vocabulary { format } => {
+            let tokens = vocabulary::vocabulary(&parse()?);
+            let text = match format {
+                VocabularyFormat::Csv => vocabulary::to_csv(&tokens),
+                VocabularyFormat::Text => {
+                    let mut text: String = tokens.iter().map(|t| format!("{}\n", t)).collect();
+                    let regexes = tokens.iter().filter(|t| t.kind == TokenKind::Regex).count();
+                    for group in vocabulary::near_duplicates(&tokens) {
+                        let symbols: Vec<&str> = group.iter().map(|t| t.symbol.as_str()).collect();
+                        text.push_str(&format!("Near-duplicates: {}\n", symbols.join(", ")));
+                    }
+                    text.push_str(&format!(
+                        "{} terminals, {} regexes\n",
+                        tokens.len() - regexes,
+                        regexes
+                    ));
+                    text
+                }
+            };
+            (text, Exit::Ok)
+        }
+        Commands::Analyze {
+            report: Report::Reachability,
+        } => {
+            let report = reachability::reachability(&parse()?);
+            (
+                report.to_string(),
+                Exit::failure_if(!report.rules.is_empty()),
+            )
+        }
+        Commands::Analyze {
+            report: Report::Productivity,
+        } => {
+            let report = productivity::productivity(&analysis_bnf(&parse()?));
+            (
+                report.to_string(),
+                Exit::failure_if(!report.rules.is_empty()),
+            )
+        }
+        Commands::Analyze {
+            report: Report::Recursion,
+        } => {
+            let groups = recursion::recursive_groups(&analysis_bnf(&parse()?));
+            let mut text = match groups.len() {
+                0 => "No rule is recursive.\n".to_string(),
+                n => format!("Found {} group(s) of recursive rules:\n", n),
+            };
+            for (i, group) in groups.iter().enumerate() {
+                text.push_str(&format!("\n{}. {}", i + 1, group));
+            }
+            (text, Exit::Ok)
+        }
+        Commands::Analyze {
+            report: Report::Peg,
+        } => {
+            let shadowed = peg::peg_check(&parse()?)?;
+            let mut text: String = shadowed.iter().map(|s| s.to_string()).collect();
+            if shadowed.is_empty() {
+                text.push_str("Ordered choice tries every alternative.\n");
+            }
+            (text, Exit::failure_if(!shadowed.is_empty()))
+        }
+        Commands::Analyze {
+            report: Report::Keywords,
+        } => {
+            let keywords = keywords::keywords(&parse()?)?;
+            let mut text: String = keywords.iter().map(|k| format!("{}\n", k)).collect();
+            let reserved = keywords.iter().filter(|k| k.is_reserved()).count();
+            text.push_str(&format!(
+                "{} reserved, {} soft keywords\n",
+                reserved,
+                keywords.len() - reserved
+            ));
+            (text, Exit::Ok)
+        }
+        Commands::Analyze {
+            report: Report::Terminals { rule },
+        } => {
+            let sebnf = parse()?;
+            let rules: Vec<&String> = match &rule {
+                Some(rule) => match sebnf.rules.get_key_value(rule) {
+                    Some((rule, _)) => vec![rule],
+                    None => return Err(TransformError::UnknownRule(rule.clone()).into()),
+                },
+                None => sebnf.rules.keys().collect(),
+            };
+            let terminals = analysis_bnf(&sebnf).reachable_terminals();
+            let mut text = String::new();
+            for rule in rules {
+                let Some(set) = terminals.get(rule) else {
+                    continue;
+                };
+                if set.is_empty() {
+                    text.push_str(&format!("{}: no terminals\n", rule));
+                    continue;
+                }
+                text.push_str(&format!("{}:\n", rule));
+                for item in sets::sorted(set) {
+                    text.push_str(&format!("  {}\n", item));
+                }
+            }
+            (text, Exit::Ok)
+        }
+        Commands::Export {
+            target: ExportTarget::Lalrpop { extern_tokens },
+        } => {
+            let sebnf = parse()?;
+            (lalrpop::lalrpop_grammar(&sebnf, extern_tokens), Exit::Ok)
+        }
+        Commands::Export {
+            target: ExportTarget::TreeSitter { name },
+        } => {
+            let sebnf = parse()?;
+            let start = sebnf.rules.keys().next().cloned().unwrap_or_default();
+            let export =
+                tree_sitter::tree_sitter_grammar(&sebnf, name.as_deref().unwrap_or(&start));
+            if verbosity != Verbosity::Quiet {
+                for note in &export.notes {
+                    eprintln!("note: {}", note);
+                }
+            }
+            (export.js, Exit::Ok)
+        }
+        Commands::Export {
+            target: ExportTarget::FuzzDict,
+        } => {
+            let bnf = to_bnf(&parse()?);
+            (fuzz::dictionary(&bnf)?, Exit::Ok)
+        }
+        Commands::Export {
+            target:
+                ExportTarget::FuzzSeeds {
+                    dir,
+                    count,
+                    max_depth,
+                    seed,
+                },
+        } => {
+            let sebnf = parse()?;
+            let bnf = to_bnf(&sebnf);
+            let options = GenerateOptions {
+                count,
+                max_depth,
+                seed,
+                rule_depths: sebnf.max_depths(),
+                sample_regexes: false,
+            };
+            let sentences = sentences::generate(&bnf, &options)?;
+            fuzz::write_seeds(&dir, &sentences).map_err(|source| CliError::Io {
+                path: dir.clone(),
+                source,
+            })?;
+            let text = format!("Wrote {} seeds to {}\n", sentences.len(), dir.display());
+            (text, Exit::Ok)
+        }
+        Commands::Codegen {
+            target:
+                CodegenTarget::Crate {
+                    out_dir,
+                    name,
+                    tests,
+                },
+        } => {
+            let sebnf = parse()?;
+            let name = name.unwrap_or_else(|| {
+                let dir = std::path::absolute(&out_dir).unwrap_or_else(|_| out_dir.clone());
+                let dir = dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned());
+                let name: String = (dir.unwrap_or_default().chars())
+                    .map(|c| match c.is_ascii_alphanumeric() || c == '-' {
+                        true => c,
+                        false => '_',
+                    })
+                    .collect();
+                match name.is_empty() {
+                    true => "parser".to_string(),
+                    false => name,
+                }
+            });
+            let options = CrateOptions {
+                name,
+                tests,
+                start: cli.start.clone(),
+            };
+            let files = codegen::parser_crate(&input, &sebnf, &options)?;
+            let mut written = 0;
+            for file in &files {
+                let path = out_dir.join(&file.path);
+                if file.keep_existing && path.exists() {
+                    verbosity.note(format_args!("kept {}", path.display()));
+                    continue;
+                }
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|source| CliError::Io {
+                        path: dir.to_path_buf(),
+                        source,
+                    })?;
+                }
+                write_atomic(&path, &file.contents)?;
+                written += 1;
+            }
+            let text = format!("Wrote {} files to {}\n", written, out_dir.display());
+            (text, Exit::Ok)
+        }
+        Commands::Codegen {
+            target: CodegenTarget::RustRd,
+        } => (codegen::rust_rd_parser(&parse()?)?, Exit::Ok),
+        Commands::Codegen {
+            target: CodegenTarget::RustTable,
+        } => (codegen::rust_table_parser(&parse()?)?, Exit::Ok),
+        Commands::Codegen {
+            target: CodegenTarget::TypedAst,
+        } => (typed_ast::typed_ast(&parse()?), Exit::Ok),
+        Commands::Codegen {
+            target: CodegenTarget::Nom,
+        } => (nom::nom_parser(&parse()?), Exit::Ok),
+        Commands::Codegen {
+            target: CodegenTarget::Lexer { standalone },
+        } => (codegen::lexer_module(&parse()?, !standalone), Exit::Ok),
+        Commands::Disjoint {
+            first,
+            second,
+            max_len,
+        } => {
+            let check = disjoint::check_disjoint(&parse()?, &first, &second, max_len)?;
+            (check.to_string(), Exit::failure_if(!check.is_disjoint()))
+        }
+        Commands::SelfCheck {
+            check: SelfCheck::Conversion { max_len },
+        } => {
+            let sebnf = parse()?;
+            let bnf = sebnf.to_bnf_with(&lowering);
+            let check = self_check::check_conversion(&sebnf, &bnf, max_len)?;
+            (check.to_string(), Exit::failure_if(!check.is_ok()))
+        }
+        Commands::Generate {
+            count,
+            max_depth,
+            seed,
+        } => {
+            let sebnf = parse()?;
+            let options = GenerateOptions {
+                count,
+                max_depth,
+                seed,
+                rule_depths: sebnf.max_depths(),
+                sample_regexes: true,
+            };
+            let sentences = sentences::generate(&to_bnf(&sebnf), &options)?;
+            let text: String = sentences
+                .iter()
+                .map(|sentence| format!("{sentence}\n"))
+                .collect();
+            (text, Exit::Ok)
+        }
+        Commands::Diagram { rule, out_dir } => {
+            let sebnf = parse()?;
+            match (rule, out_dir) {
+                (Some(rule), _) => match railroad::rule_diagram(&sebnf, &rule) {
+                    Some(svg) => (svg, Exit::Ok),
+                    None => return Err(TransformError::UnknownRule(rule).into()),
+                },
+                (None, Some(out_dir)) => {
+                    std::fs::create_dir_all(&out_dir).map_err(|source| CliError::Io {
+                        path: out_dir.clone(),
+                        source,
+                    })?;
+                    for rule in sebnf.rules.keys() {
+                        let svg = railroad::rule_diagram(&sebnf, rule).expect("the rule exists");
+                        write_atomic(&out_dir.join(format!("{}.svg", rule)), svg)?;
+                    }
+                    let text = format!(
+                        "Wrote {} diagrams to {}\n",
+                        sebnf.rules.len(),
+                        out_dir.display()
+                    );
+                    (text, Exit::Ok)
+                }
+                (None, None) => (railroad::diagrams(&sebnf), Exit::Ok),
+            }
+        }
+        Commands::Graph {
+            clusters,
+            highlight_start,
+            mark_useless,
+        } => {
+            let options = GraphOptions {
+                clusters,
+                highlight_start,
+                mark_useless,
+            };
+            (graph::dependency_graph(&parse()?, &options), Exit::Ok)
+        }
+        Commands::HtmlReport {
+            ignore_regex_conflicts,
+        } => {
+            let sebnf = parse()?;
+            let bnf = analysis_bnf(&sebnf);
+            let mut result = bnf.is_ll1(ignore_regex_conflicts)?;
+            bnf.annotate_input(&mut result)?;
+            let grammar = match cli.from {
+                Dialect::Sebnf => format_source(&file.text, &sebnf, &FormatOptions::default()),
+                _ => sebnf.to_string(),
+            };
+            let report = html_report(&source_name, &grammar, &sebnf, &bnf, &result);
+            (report, Exit::Ok)
+        }
+        Commands::Enumerate {
+            max_tokens,
+            max_depth,
+            limit,
+        } => {
+            let bnf = analysis_bnf(&parse()?);
+            let options = EnumerateOptions {
+                max_tokens,
+                max_depth,
+                limit,
+            };
+            let enumeration = sentences::enumerate(&bnf, &options);
+            if !enumeration.complete && verbosity != Verbosity::Quiet {
+                eprintln!(
+                    "a rule has more than {} sentences within the bounds; listing those found",
+                    limit
+                );
+            }
+            (
+                enumeration.to_string(),
+                Exit::failure_if(!enumeration.complete),
+            )
+        }
+        Commands::Parse {
+            file,
+            algo,
+            max_steps,
+            all,
+            max_parses,
+            table,
+        } => {
+            let sebnf = parse()?;
+            let (text, name) = read_text(&file, None)?;
+            match algo {
+                ParseAlgo::Backtrack => {
+                    let options = ParseOptions { max_steps };
+                    let parse = interpret::parse_input(&sebnf, &text, &name, &options)?;
+                    if !parse.ll1 {
+                        verbosity.note("the grammar is not LL(1); parsed by backtracking");
+                    }
+                    (parse.tree.to_string(), Exit::Ok)
+                }
+                ParseAlgo::Earley => {
+                    let options = EarleyOptions { max_parses };
+                    let parse = earley::earley_parse(&sebnf, &text, &name, &options)?;
+                    if let Some(ambiguity) = parse.ambiguity
+                        && verbosity != Verbosity::Quiet
+                    {
+                        eprintln!("{:?}", miette::Report::new(ambiguity));
+                    }
+                    let output = match all {
+                        false => parse.trees[0].to_string(),
+                        true => (parse.trees.iter().enumerate())
+                            .map(|(i, tree)| {
+                                format!("Parse {} of {}:\n{}", i + 1, parse.trees.len(), tree)
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    };
+                    (output, Exit::Ok)
+                }
+                ParseAlgo::Glr => {
+                    let parse = glr::glr_parse(&sebnf, &text, &name)?;
+                    if !parse.lalr1 {
+                        verbosity.note("the grammar is not LALR(1); forked on its conflicts");
+                    }
+                    (parse.forest.render(&text)?, Exit::Ok)
+                }
+                ParseAlgo::Cyk => {
+                    let cyk = cyk::cyk_recognize(&sebnf, &text, &name)?;
+                    match (cyk.accepted(), table) {
+                        (true, true) => (cyk.render()?, Exit::Ok),
+                        (true, false) => {
+                            let tokens = cyk.tokens.len();
+                            let text = format!(
+                                "'{}' matches the input ({} tokens)\n",
+                                cyk.cnf.start, tokens
+                            );
+                            (text, Exit::Ok)
+                        }
+                        // The table shows how far the rules got
+                        (false, true) => {
+                            if verbosity != Verbosity::Quiet {
+                                eprintln!("{:?}", miette::Report::new(cyk.rejection(&text, &name)));
+                            }
+                            (cyk.render()?, Exit::ParseError)
+                        }
+                        (false, false) => return Err(cyk.rejection(&text, &name).into()),
+                    }
+                }
+            }
+        }
+        Commands::Tree { depth } => {
+            let sebnf = parse()?;
+            let options = TreeOptions { max_depth: depth };
+            (render_tree(&sebnf, &options), Exit::Ok)
+        }
+        Commands::DumpAst => {
+            let sebnf = parse()?;
+            (to_json_string(&description::describe(&sebnf)), Exit::Ok)
+        }
+        Commands::Snapshot {
+            dir,
+            update,
+            check: _,
+            ignore_regex_conflicts,
+        } => {
+            let bnf = analysis_bnf(&parse()?);
+            let artifacts = snapshot::artifacts(&bnf, ignore_regex_conflicts)?;
+            let io_err = |source| CliError::Io {
+                path: dir.clone(),
+                source,
+            };
+            if update {
+                let changed = snapshot::update(&dir, &artifacts).map_err(io_err)?;
+                let text = if changed.is_empty() {
+                    "Snapshot up to date\n".to_string()
+                } else {
+                    changed
+                        .iter()
+                        .map(|name| format!("Updated {}\n", name))
+                        .collect()
+                };
+                (text, Exit::Ok)
+            } else {
+                let mismatches = snapshot::check(&dir, &artifacts).map_err(io_err)?;
+                let mut text = String::new();
+                for mismatch in &mismatches {
+                    match mismatch {
+                        Mismatch::Missing { file_name } => {
+                            text.push_str(&format!("Missing {}\n", file_name));
+                        }
+                        Mismatch::Differs { file_name, diff } => {
+                            text.push_str(&format!("Changed {}:\n{}", file_name, diff));
+                        }
+                    }
+                }
+                if mismatches.is_empty() {
+                    ("Snapshot matches\n".to_string(), Exit::Ok)
+                } else {
+                    (text, Exit::AnalysisFailure)
+                }
+            }
+        }
+        Commands::Query { expr } => {
+            let sets = analysis_bnf(&parse()?).first_and_follow_sets();
+            let value = query::evaluate(&expr, &sets)?;
+            (format!("{}\n", value), Exit::failure_if(!value.is_truthy()))
+        }
+        Commands::Repl { .. }
+        | Commands::Serve { .. }
+        | Commands::Compare { .. }
+        | Commands::Merge { .. }
+        | Commands::Changelog { .. }
+        | Commands::Equiv { .. }
+        | Commands::Capabilities => {
+            unreachable!("handled before reading the input")
+        }
+    };
+    emit(cli.output.as_deref(), &text, verbosity)?;
+    Ok(exit)
 }
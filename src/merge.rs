@@ -0,0 +1,266 @@
+//! Structural three-way merges of grammar versions.
+//!
+//! Rules are matched by name and merged alternative by alternative, so two
+//! branches that touch different rules, or different alternatives of the
+//! same rule, merge cleanly even where a line-based merge would conflict.
+//! The merged grammar is printed as by [`Sebnf`]'s `Display`, so comments
+//! are not kept.
+
+use indexmap::IndexMap;
+
+use crate::sebnf::{Item, RuleAnnotations, Sebnf};
+
+/// The merged grammar, with conflict markers around each conflicting rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merge {
+    pub text: String,
+    /// Rules edited differently on both sides, in output order
+    pub conflicts: Vec<String>,
+}
+
+impl Merge {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// A rule in one of the versions
+#[derive(Debug, Clone)]
+struct Version {
+    alternatives: Vec<Vec<Item>>,
+    /// The alternatives as written, which identify them across versions
+    keys: Vec<String>,
+    annotations: Option<RuleAnnotations>,
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.keys == other.keys && self.annotations == other.annotations
+    }
+}
+
+impl Version {
+    fn of(sebnf: &Sebnf, name: &str) -> Option<Self> {
+        let alternatives = sebnf.rules.get(name)?.clone();
+        let keys = alternatives
+            .iter()
+            .map(|alt| {
+                let items: Vec<_> = alt.iter().map(|item| item.to_string()).collect();
+                items.join(" ")
+            })
+            .collect();
+        // Spans differ between the files
+        let annotations = sebnf.annotations.get(name).map(|a| RuleAnnotations {
+            span: 0..0,
+            ..a.clone()
+        });
+        Some(Version {
+            alternatives,
+            keys,
+            annotations,
+        })
+    }
+}
+
+enum Outcome {
+    /// `None` if the rule was deleted
+    Clean(Option<Version>),
+    Conflict(Option<Version>, Option<Version>),
+}
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs`.
+///
+/// Rules keep the order of `ours`; rules only added in `theirs` follow the
+/// rule they follow there.
+pub fn merge(base: &Sebnf, ours: &Sebnf, theirs: &Sebnf) -> Merge {
+    let mut order: Vec<&String> = ours.rules.keys().collect();
+    let mut previous = None;
+    for name in theirs.rules.keys() {
+        if !order.contains(&name) {
+            let at = previous
+                .and_then(|p| order.iter().position(|n| *n == p))
+                .map_or(0, |i| i + 1);
+            order.insert(at, name);
+        }
+        previous = Some(name);
+    }
+
+    let outcomes: Vec<(&String, Outcome)> = order
+        .into_iter()
+        .map(|name| {
+            let outcome = merge_rule(
+                Version::of(base, name),
+                Version::of(ours, name),
+                Version::of(theirs, name),
+            );
+            (name, outcome)
+        })
+        .collect();
+
+    let width = outcomes
+        .iter()
+        .filter(|(_, outcome)| !matches!(outcome, Outcome::Clean(None)))
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let render = |name: &str, version: &Option<Version>| {
+        let Some(version) = version else {
+            return String::new();
+        };
+        let sebnf = Sebnf {
+            rules: IndexMap::from([(name.to_string(), version.alternatives.clone())]),
+            rule_spans: IndexMap::new(),
+            annotations: (version.annotations.iter())
+                .map(|annotations| (name.to_string(), annotations.clone()))
+                .collect(),
+        };
+        let mut text = String::new();
+        // Writing to a String cannot fail
+        let _ = sebnf.write_rule(&mut text, name, width);
+        text
+    };
+
+    let mut text = String::new();
+    let mut conflicts = Vec::new();
+    for (name, outcome) in &outcomes {
+        match outcome {
+            Outcome::Clean(version) => text.push_str(&render(name, version)),
+            Outcome::Conflict(ours, theirs) => {
+                text.push_str("<<<<<<< ours\n");
+                text.push_str(&render(name, ours));
+                text.push_str("=======\n");
+                text.push_str(&render(name, theirs));
+                text.push_str(">>>>>>> theirs\n");
+                conflicts.push(name.to_string());
+            }
+        }
+    }
+    Merge { text, conflicts }
+}
+
+fn merge_rule(base: Option<Version>, ours: Option<Version>, theirs: Option<Version>) -> Outcome {
+    if ours == theirs || theirs == base {
+        return Outcome::Clean(ours);
+    }
+    if ours == base {
+        return Outcome::Clean(theirs);
+    }
+    // Added differently on both sides, or deleted on one and edited on the other
+    let (base, ours, theirs) = match (base, ours, theirs) {
+        (Some(base), Some(ours), Some(theirs)) => (base, ours, theirs),
+        (_, ours, theirs) => return Outcome::Conflict(ours, theirs),
+    };
+    let annotations = if ours.annotations == base.annotations {
+        theirs.annotations.clone()
+    } else if theirs.annotations == base.annotations || ours.annotations == theirs.annotations {
+        ours.annotations.clone()
+    } else {
+        return Outcome::Conflict(Some(ours), Some(theirs));
+    };
+    match merge_alternatives(&base, &ours, &theirs) {
+        Some((alternatives, keys)) => Outcome::Clean(Some(Version {
+            alternatives,
+            keys,
+            annotations,
+        })),
+        None => Outcome::Conflict(Some(ours), Some(theirs)),
+    }
+}
+
+/// diff3 over the alternatives: between alternatives all three versions
+/// share, a stretch edited on only one side takes that side's edit. `None`
+/// if both sides edited the same stretch differently.
+fn merge_alternatives(
+    base: &Version,
+    ours: &Version,
+    theirs: &Version,
+) -> Option<(Vec<Vec<Item>>, Vec<String>)> {
+    let in_ours = common_subsequence(&base.keys, &ours.keys);
+    let in_theirs = common_subsequence(&base.keys, &theirs.keys);
+    let mut syncs: Vec<(usize, usize, usize)> = (0..base.keys.len())
+        .filter_map(|b| Some((b, in_ours[b]?, in_theirs[b]?)))
+        .collect();
+    syncs.push((base.keys.len(), ours.keys.len(), theirs.keys.len()));
+
+    let mut alternatives = Vec::new();
+    let mut keys = Vec::new();
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    for (next_b, next_o, next_t) in syncs {
+        let base_keys = &base.keys[b..next_b];
+        let (from, range) = if ours.keys[o..next_o] == *base_keys {
+            (theirs, t..next_t)
+        } else if theirs.keys[t..next_t] == *base_keys
+            || ours.keys[o..next_o] == theirs.keys[t..next_t]
+        {
+            (ours, o..next_o)
+        } else {
+            return None;
+        };
+        alternatives.extend_from_slice(&from.alternatives[range.clone()]);
+        keys.extend_from_slice(&from.keys[range]);
+        if let Some(alternative) = ours.alternatives.get(next_o) {
+            alternatives.push(alternative.clone());
+            keys.push(ours.keys[next_o].clone());
+        }
+        (b, o, t) = (next_b + 1, next_o + 1, next_t + 1);
+    }
+    Some((alternatives, keys))
+}
+
+/// For each element of `a`, its index in `b` in a longest common subsequence
+fn common_subsequence(a: &[String], b: &[String]) -> Vec<Option<usize>> {
+    // lengths[i][j]: length of the LCS of a[i..] and b[j..]
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut matched = vec![None; a.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            matched[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Sebnf {
+        Sebnf::parse_str(source, "g").unwrap()
+    }
+
+    #[test]
+    fn merges_alternatives_and_marks_conflicting_rules() {
+        let base = parse("expr := term | \"-\" expr.\nterm := /[0-9]+/.\natom := \"x\".");
+        let ours = parse(
+            "expr := term | \"-\" expr | \"(\" expr \")\".\nterm := /[0-9]+/.\natom := \"y\".",
+        );
+        let theirs = parse(
+            "expr := \"+\" term | \"-\" expr.\nterm := /[0-9]+/.\natom := \"z\".\nnew := atom.",
+        );
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts, ["atom"]);
+        assert_eq!(
+            merged.text,
+            "expr := \"+\" term\n      | \"-\" expr\n      | \"(\" expr \")\"\n      .\n\
+             term := /[0-9]+/.\n\
+             <<<<<<< ours\natom := \"y\".\n=======\natom := \"z\".\n>>>>>>> theirs\n\
+             new  := atom.\n"
+        );
+    }
+}
@@ -0,0 +1,452 @@
+//! Parser combinators built on `nom`, for a parser written by hand around
+//! the grammar rather than generated from its tables.
+//!
+//! [`nom_parser`] writes a module with a function per rule that recognizes
+//! it and returns `()`, to fill in with results rule by rule. Terminals are
+//! `tag`s, and keywords are not matched as the start of a longer word;
+//! regexes match at the start of the input through the `regex` crate.
+//! Whitespace is skipped before every token. Alternatives become `alt`,
+//! `[ ]` `opt`, `{ }` `many0`, and the built-in idioms their combinators.
+//!
+//! Combinators try alternatives in order and take the first that matches,
+//! without a lexer: an alternative that can match nothing is tried last,
+//! and a regex may match a keyword. A left-recursive rule calls itself
+//! without end and is noted as such.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::nom::nom_parser;
+//!
+//! let sebnf = Sebnf::parse_str("sum := num { (\"+\" | \"-\") num }.\nnum := /[0-9]+/.", "g").unwrap();
+//! let module = nom_parser(&sebnf);
+//! assert!(module.contains(
+//!     "    value((), (num, many0((alt((literal(\"+\"), literal(\"-\"))), num)))).parse(input)\n"
+//! ));
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+
+use indexmap::IndexMap;
+
+use crate::codegen::{Names, doc_line, unescape, unique};
+use crate::lalrpop::{raw_string, upper_snake};
+use crate::left_recursion::left_recursion;
+use crate::nullable::{Nullable, nullable_rules};
+use crate::sebnf::{self, Builtin, Item, Sebnf};
+use crate::sets::{strip_regex_delimiters, strip_terminal_quotes};
+use crate::typed_ast::identifier;
+
+/// Most parsers `nom` takes in one tuple, for `alt` and for sequences
+const MAX_TUPLE: usize = 21;
+
+/// A module of `nom` parsers recognizing the language of `sebnf`.
+pub fn nom_parser(sebnf: &Sebnf) -> String {
+    let bnf = sebnf.to_bnf();
+    let names = Names::new(sebnf, &bnf);
+    let mut writer = Writer::new(sebnf, &names, nullable_rules(&bnf));
+    let left_recursive: HashSet<String> = (left_recursion(&bnf).into_iter())
+        .flat_map(|cycle| cycle.steps.into_iter().map(|step| step.rule))
+        .collect();
+
+    let mut rules = String::new();
+    for (rule, alternatives) in &sebnf.rules {
+        let written: Vec<String> = alternatives.iter().map(|a| sebnf::written(a)).collect();
+        let _ = writeln!(
+            rules,
+            "\n/// `{}`",
+            doc_line(&format!("{} := {}", rule, written.join(" | ")))
+        );
+        if left_recursive.contains(rule) {
+            let _ = writeln!(
+                rules,
+                "///\n/// Left-recursive: calls itself before reading input, without end"
+            );
+        }
+        let body = writer.body(alternatives);
+        let _ = writeln!(
+            rules,
+            "pub fn {}(input: &str) -> IResult<&str, ()> {{\n{}\n}}",
+            writer.functions[rule], body
+        );
+    }
+
+    let start = writer
+        .functions
+        .values()
+        .next()
+        .cloned()
+        .unwrap_or_default();
+    for (path, name) in [
+        ("nom::combinator", "all_consuming"),
+        ("nom::sequence", "terminated"),
+        ("nom::character::complete", "multispace0"),
+        ("nom::error", "Error"),
+        ("nom", "IResult"),
+        ("nom", "Parser"),
+    ] {
+        writer.uses(path, name);
+    }
+    let mut helpers = String::new();
+    if writer.keyword {
+        writer.literal = true;
+        writer.uses("nom::character::complete", "satisfy");
+        writer.uses("nom::combinator", "not");
+        helpers.push_str(KEYWORD_RS);
+    }
+    if writer.literal {
+        writer.uses("nom::bytes::complete", "tag");
+        writer.uses("nom::sequence", "preceded");
+        helpers.insert_str(0, LITERAL_RS);
+    }
+    if !writer.regexes.is_empty() {
+        writer.uses("nom::error", "ErrorKind");
+        writer.uses("nom::sequence", "preceded");
+        helpers.push_str(PATTERN_RS);
+    }
+
+    let mut code = String::from(
+        "//! Parser combinators for the grammar, generated by `sebnf_tool codegen nom`.\n\
+         //!\n\
+         //! A function per rule recognizes it and returns `()`; replace its\n\
+         //! `value((), ...)` to build a result. Whitespace is skipped before every\n\
+         //! token.\n\n",
+    );
+    if !writer.regexes.is_empty() {
+        code.push_str("use std::sync::LazyLock;\n\n");
+    }
+    for (path, names) in &writer.imports {
+        let _ = match names.len() {
+            1 => writeln!(code, "use {}::{};", path, names.first().unwrap()),
+            _ => {
+                let names: Vec<&str> = names.iter().map(String::as_str).collect();
+                writeln!(code, "use {}::{{{}}};", path, names.join(", "))
+            }
+        };
+    }
+    if !writer.regexes.is_empty() {
+        code.push_str("use regex::Regex;\n\n");
+        for (name, pattern) in writer.regexes.values() {
+            let _ = writeln!(
+                code,
+                "static {}: LazyLock<Regex> =\n    LazyLock::new(|| Regex::new({}).unwrap());",
+                name,
+                raw_string(&format!("^(?:{})", pattern))
+            );
+        }
+    }
+    let _ = write!(
+        code,
+        "\n/// Parses all of `input` as the start rule.\n\
+         pub fn parse(input: &str) -> Result<(), nom::Err<Error<&str>>> {{\n    \
+         all_consuming(terminated({}, multispace0)).parse(input)?;\n    Ok(())\n}}\n",
+        start
+    );
+    code.push_str(&rules);
+    code.push_str(&helpers);
+    code
+}
+
+const LITERAL_RS: &str = r#"
+/// `text`, after any whitespace
+fn literal<'a>(
+    text: &'static str,
+) -> impl Parser<&'a str, Output = &'a str, Error = Error<&'a str>> {
+    preceded(multispace0, tag(text))
+}
+"#;
+
+const KEYWORD_RS: &str = r#"
+/// `text`, after any whitespace, unless a letter, digit or `_` follows
+fn keyword<'a>(
+    text: &'static str,
+) -> impl Parser<&'a str, Output = &'a str, Error = Error<&'a str>> {
+    terminated(
+        literal(text),
+        not(satisfy(|c: char| c.is_alphanumeric() || c == '_')),
+    )
+}
+"#;
+
+const PATTERN_RS: &str = r#"
+/// A match of `regex` at the start of the input, after any whitespace
+fn pattern<'a>(
+    regex: &'static LazyLock<Regex>,
+) -> impl Parser<&'a str, Output = &'a str, Error = Error<&'a str>> {
+    preceded(multispace0, move |input: &'a str| match regex.find(input) {
+        Some(found) => Ok((&input[found.end()..], found.as_str())),
+        None => Err(nom::Err::Error(Error::new(input, ErrorKind::RegexpMatch))),
+    })
+}
+"#;
+
+struct Writer<'a> {
+    names: &'a Names,
+    nullable: Nullable,
+    /// Rules to their function
+    functions: IndexMap<String, String>,
+    /// Regexes as written to their static and pattern
+    regexes: IndexMap<String, (String, String)>,
+    /// Module paths to the names imported from them
+    imports: BTreeMap<&'static str, BTreeSet<String>>,
+    literal: bool,
+    keyword: bool,
+}
+
+impl<'a> Writer<'a> {
+    fn new(sebnf: &Sebnf, names: &'a Names, nullable: Nullable) -> Self {
+        // The combinators and helpers the functions are called next to
+        let mut taken: HashSet<String> = [
+            "alt",
+            "all_consuming",
+            "delimited",
+            "keyword",
+            "literal",
+            "many0",
+            "many1",
+            "multispace0",
+            "not",
+            "opt",
+            "parse",
+            "pattern",
+            "preceded",
+            "satisfy",
+            "separated_list0",
+            "success",
+            "tag",
+            "terminated",
+            "value",
+        ]
+        .map(str::to_string)
+        .into();
+        let functions = (sebnf.rules.keys())
+            .map(|rule| (rule.clone(), unique(identifier(rule), &mut taken)))
+            .collect();
+        Writer {
+            names,
+            nullable,
+            functions,
+            regexes: IndexMap::new(),
+            imports: BTreeMap::new(),
+            literal: false,
+            keyword: false,
+        }
+    }
+
+    fn uses(&mut self, path: &'static str, name: &str) {
+        self.imports
+            .entry(path)
+            .or_default()
+            .insert(name.to_string());
+    }
+
+    /// The body of a rule's function, its parser in `value`, with the
+    /// outermost tuple a line per parser if too long for one line
+    fn body(&mut self, alternatives: &[Vec<Item>]) -> String {
+        self.uses("nom::combinator", "value");
+        let (combinator, parsers) = match alternatives {
+            [alternative] => ("", self.sequence_parsers(alternative)),
+            _ => ("alt", self.alternative_parsers(alternatives)),
+        };
+        let parser = self.nest(combinator, parsers.clone());
+        let line = format!("    value((), {}).parse(input)", parser);
+        if line.len() <= 100 || parsers.len() > MAX_TUPLE {
+            return line;
+        }
+        let mut body = format!("    value(\n        (),\n        {}(\n", combinator);
+        if !combinator.is_empty() {
+            body.insert(body.len() - 1, '(');
+        }
+        for parser in parsers {
+            let _ = writeln!(body, "            {},", parser);
+        }
+        match combinator.is_empty() {
+            true => body.push_str("        ),\n"),
+            false => body.push_str("        )),\n"),
+        }
+        body.push_str("    )\n    .parse(input)");
+        body
+    }
+
+    /// `alt` over the alternatives, those that can match nothing last
+    fn alternatives(&mut self, alternatives: &[Vec<Item>]) -> String {
+        let parsers = self.alternative_parsers(alternatives);
+        self.nest("alt", parsers)
+    }
+
+    fn alternative_parsers(&mut self, alternatives: &[Vec<Item>]) -> Vec<String> {
+        let mut ordered: Vec<&Vec<Item>> = alternatives.iter().collect();
+        ordered.sort_by_key(|alternative| self.nullable(alternative));
+        // `alt` takes parsers of one output: rules give `()`, tokens `&str`
+        let outputs: Vec<Output> = ordered.iter().map(|a| output(a)).collect();
+        let same = outputs
+            .iter()
+            .all(|o| *o == outputs[0] && *o != Output::Other);
+        (ordered.into_iter().zip(outputs))
+            .map(|(alternative, output)| {
+                let parser = self.sequence(alternative);
+                match same || output == Output::Unit {
+                    true => parser,
+                    false => format!("value((), {})", parser),
+                }
+            })
+            .collect()
+    }
+
+    fn sequence(&mut self, items: &[Item]) -> String {
+        let parsers = self.sequence_parsers(items);
+        self.nest("", parsers)
+    }
+
+    /// The parsers of `items` in turn, `success(())` if none
+    fn sequence_parsers(&mut self, items: &[Item]) -> Vec<String> {
+        let parsers: Vec<String> = (items.iter())
+            .filter(|item| !matches!(item, Item::Epsilon(_)))
+            .map(|item| self.item(item))
+            .collect();
+        if parsers.is_empty() {
+            self.uses("nom::combinator", "success");
+            return vec!["success(())".to_string()];
+        }
+        parsers
+    }
+
+    /// `parsers` as one: itself if alone, else a tuple, passed to
+    /// `combinator` unless empty, nested to keep within [`MAX_TUPLE`]
+    fn nest(&mut self, combinator: &str, mut parsers: Vec<String>) -> String {
+        if parsers.len() == 1 {
+            return parsers.pop().expect("one parser");
+        }
+        if !combinator.is_empty() {
+            self.uses("nom::branch", combinator);
+        }
+        while parsers.len() > MAX_TUPLE {
+            let rest = parsers.split_off(MAX_TUPLE - 1);
+            let rest = self.nest(combinator, rest);
+            parsers.push(rest);
+        }
+        match combinator.is_empty() {
+            true => format!("({})", parsers.join(", ")),
+            false => format!("{}(({}))", combinator, parsers.join(", ")),
+        }
+    }
+
+    fn item(&mut self, item: &Item) -> String {
+        match item {
+            Item::NonTerminal(rule, _) => {
+                (self.functions.get(rule).cloned()).unwrap_or_else(|| identifier(rule))
+            }
+            Item::Terminal(symbol, _) => {
+                let text = unescape(strip_terminal_quotes(symbol));
+                let word = text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                    && text.chars().all(|c| c.is_alphanumeric() || c == '_');
+                match word {
+                    true => {
+                        self.keyword = true;
+                        format!("keyword({:?})", text)
+                    }
+                    false => {
+                        self.literal = true;
+                        format!("literal({:?})", text)
+                    }
+                }
+            }
+            Item::Regex(symbol, _) => {
+                if !self.regexes.contains_key(symbol) {
+                    let name = upper_snake(&self.names.tokens[symbol]);
+                    let pattern = strip_regex_delimiters(symbol).to_string();
+                    self.regexes.insert(symbol.clone(), (name, pattern));
+                }
+                format!("pattern(&{})", self.regexes[symbol].0)
+            }
+            Item::Optional(items, _) => {
+                self.uses("nom::combinator", "opt");
+                format!("opt({})", self.sequence(items))
+            }
+            Item::AnyAmount(items, _) => {
+                self.uses("nom::multi", "many0");
+                format!("many0({})", self.sequence(items))
+            }
+            Item::Choice(alternatives, _) => self.alternatives(alternatives),
+            Item::Builtin(builtin, arguments, _) => {
+                let arguments: Vec<String> = (arguments.iter())
+                    .map(|argument| self.sequence(argument))
+                    .collect();
+                let (path, combinator) = match builtin {
+                    Builtin::SepBy => ("nom::multi", "separated_list0"),
+                    Builtin::Delimited => ("nom::sequence", "delimited"),
+                    Builtin::NonEmpty => ("nom::multi", "many1"),
+                };
+                self.uses(path, combinator);
+                match (builtin, arguments.as_slice()) {
+                    // `nom` takes the separator first
+                    (Builtin::SepBy, [item, separator]) => {
+                        format!("{}({}, {})", combinator, separator, item)
+                    }
+                    _ => format!("{}({})", combinator, arguments.join(", ")),
+                }
+            }
+            Item::Labeled(_, item, _) => self.item(item),
+            Item::Epsilon(_) => {
+                self.uses("nom::combinator", "success");
+                "success(())".to_string()
+            }
+        }
+    }
+
+    /// Whether `items` can match no input
+    fn nullable(&self, items: &[Item]) -> bool {
+        items.iter().all(|item| match item {
+            Item::NonTerminal(rule, _) => self.nullable.is_nullable(rule),
+            Item::Terminal(..) | Item::Regex(..) => false,
+            Item::Optional(..) | Item::AnyAmount(..) | Item::Epsilon(_) => true,
+            Item::Choice(alternatives, _) => alternatives.iter().any(|a| self.nullable(a)),
+            Item::Builtin(Builtin::SepBy, _, _) => true,
+            Item::Builtin(_, arguments, _) => arguments.iter().all(|a| self.nullable(a)),
+            Item::Labeled(_, item, _) => self.nullable(std::slice::from_ref(item)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// `()`, of a rule or of nothing
+    Unit,
+    /// `&str`, of a token
+    Token,
+    Other,
+}
+
+/// What the parser of `items` outputs
+fn output(items: &[Item]) -> Output {
+    match items {
+        [] | [Item::NonTerminal(..)] | [Item::Epsilon(_)] => Output::Unit,
+        [Item::Terminal(..) | Item::Regex(..)] => Output::Token,
+        [Item::Labeled(_, item, _)] => output(std::slice::from_ref(item)),
+        _ => Output::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_alternatives_and_imports_what_is_used() {
+        let source = "block := \"{\" sep_by(stmt, \";\") \"}\".\n\
+                      stmt := [ \"return\" ] | \"if\" /[a-z]+/ block.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let module = nom_parser(&sebnf);
+        assert!(module.contains(
+            "    value((), (literal(\"{\"), separated_list0(literal(\";\"), stmt), literal(\"}\")))\
+             .parse(input)\n"
+        ));
+        assert!(module.contains(
+            "        alt((\n            value((), (keyword(\"if\"), pattern(&REGEX), block)),\n            \
+             value((), opt(keyword(\"return\"))),\n        )),\n"
+        ));
+        assert!(module.contains("use nom::combinator::{all_consuming, not, opt, value};\n"));
+        assert!(module.contains("Regex::new(r\"^(?:[a-z]+)\")"));
+        assert!(!module.contains("many0"));
+    }
+}
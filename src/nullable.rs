@@ -0,0 +1,163 @@
+//! The rules that derive ε.
+//!
+//! The sets record this as ε in a FIRST set, which says nothing about why.
+//! [`nullable_rules`] lists the nullable rules, each with a derivation of
+//! ε that only expands rules the fixed-point iteration found nullable before
+//! it, so the derivation always ends.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::nullable::nullable_rules;
+//!
+//! let bnf = Sebnf::parse_str("call := id \"(\" args \")\".\nargs := [ id ].\nid := /[a-z]+/.", "g")
+//!     .unwrap()
+//!     .to_bnf();
+//! let nullable = nullable_rules(&bnf);
+//! assert_eq!(nullable.rules.len(), 2);
+//! assert_eq!(nullable.rules[0].rule, "args");
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::regex_intersect::regex_matches_empty;
+use crate::sets::{format_production, strip_regex_delimiters};
+use crate::symbols::Symbols;
+
+/// The nullable rules of a grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nullable {
+    /// In grammar order
+    pub rules: Vec<NullableRule>,
+    /// How many rules the grammar has
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NullableRule {
+    pub rule: String,
+    /// The production the derivation starts with
+    pub production: Vec<Item>,
+    /// Leftmost derivation of ε from the rule: the rule itself, then each
+    /// sentential form after expanding or dropping its first nullable
+    /// symbol, down to the empty one
+    pub derivation: Vec<Vec<Item>>,
+}
+
+impl Nullable {
+    pub fn is_nullable(&self, rule: &str) -> bool {
+        self.rules.iter().any(|nullable| nullable.rule == rule)
+    }
+}
+
+/// Whether `item` is a regex that matches the empty string
+pub(crate) fn is_empty_regex(item: &Item) -> bool {
+    matches!(item, Item::Regex(pattern) if regex_matches_empty(strip_regex_delimiters(pattern)))
+}
+
+/// Finds the nullable rules of `bnf`, with a derivation of ε for each.
+pub fn nullable_rules(bnf: &Bnf) -> Nullable {
+    // The production that first made each rule nullable
+    let mut found: HashMap<&str, &Vec<Item>> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (rule, productions) in &bnf.rules {
+            if found.contains_key(rule.as_str()) {
+                continue;
+            }
+            let production = productions.iter().find(|production| {
+                production.iter().all(|item| match item {
+                    Item::NonTerminal(nt) => found.contains_key(nt.as_str()),
+                    item => is_empty_regex(item),
+                })
+            });
+            if let Some(production) = production {
+                found.insert(rule, production);
+                changed = true;
+            }
+        }
+    }
+
+    let rules = (bnf.rules.keys())
+        .filter_map(|rule| {
+            let production = found.get(rule.as_str())?;
+            let mut derivation = vec![vec![Item::NonTerminal(rule.clone())]];
+            let mut form: Vec<Item> = production.to_vec();
+            derivation.push(form.clone());
+            while let Some(first) = form.first() {
+                match first {
+                    Item::NonTerminal(nt) => {
+                        let expansion = found[nt.as_str()].iter().cloned();
+                        form.splice(0..1, expansion);
+                    }
+                    _ => {
+                        form.remove(0);
+                    }
+                }
+                derivation.push(form.clone());
+            }
+            Some(NullableRule {
+                rule: rule.clone(),
+                production: production.to_vec(),
+                derivation,
+            })
+        })
+        .collect();
+    Nullable {
+        rules,
+        total: bnf.rules.len(),
+    }
+}
+
+impl fmt::Display for NullableRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = Symbols::with_current(|s| s.glyph("⇒", "=>"));
+        let forms: Vec<String> = self
+            .derivation
+            .iter()
+            .map(|form| format_production(form))
+            .collect();
+        write!(f, "{}", forms.join(&format!(" {} ", arrow)))
+    }
+}
+
+impl fmt::Display for Nullable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rules.is_empty() {
+            let epsilon = Symbols::with_current(|s| s.epsilon.clone());
+            return writeln!(f, "No rule derives {}.", epsilon);
+        }
+        writeln!(
+            f,
+            "Nullable rules ({} of {}):",
+            self.rules.len(),
+            self.total
+        )?;
+        for rule in &self.rules {
+            writeln!(f, "  {}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn derives_epsilon_through_earlier_rules() {
+        let source = "s := a b \"x\".\na := b b | \"y\".\nb := /z*/ | \"w\".";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let nullable = nullable_rules(&bnf);
+        assert!(!nullable.is_nullable("s"));
+        assert_eq!(
+            nullable.to_string(),
+            "Nullable rules (2 of 3):\n  \
+             a ⇒ b b ⇒ /z*/ b ⇒ b ⇒ /z*/ ⇒ ε\n  \
+             b ⇒ /z*/ ⇒ ε\n"
+        );
+    }
+}
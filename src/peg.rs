@@ -0,0 +1,299 @@
+//! Alternatives that ordered choice never tries.
+//!
+//! A PEG parser, such as one generated by pest, commits to the first
+//! alternative that matches, so `op := "=" | "==".` never reads `==` as one
+//! operator: `"="` already matched its start. [`peg_check`] reads every rule
+//! and group of the grammar as an ordered choice and reports each alternative
+//! that an earlier one always wins against, which is worth fixing before
+//! exporting the grammar to a PEG tool. An earlier alternative wins when it
+//! can match nothing, when the later one starts with everything it needs, or
+//! when one of its tokens matches the start of a terminal in the later one.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::peg::peg_check;
+//!
+//! let sebnf = Sebnf::parse_str("s := op \"x\".\nop := \"=\" | \"==\" | \"!=\".", "g").unwrap();
+//! let shadowed = peg_check(&sebnf).unwrap();
+//! assert_eq!(shadowed.len(), 1);
+//! assert_eq!((shadowed[0].earlier, shadowed[0].later), (0, 1));
+//! ```
+
+use std::fmt;
+
+use crate::nullable::{Nullable, nullable_rules};
+use crate::regex_intersect::{Error as RegexError, do_regexs_intersect, regex_matches_empty};
+use crate::sebnf::{Builtin, Item, Sebnf, written};
+use crate::sets::{strip_regex_delimiters, strip_terminal_quotes};
+use crate::visit::{Visitor, walk_alternatives, walk_item, walk_rule, walk_sebnf};
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum PegError {
+    #[error("invalid regex pattern in grammar: {pattern}")]
+    #[diagnostic(code(sebnf::invalid_regex))]
+    InvalidRegex {
+        pattern: String,
+        #[source]
+        source: RegexError,
+    },
+}
+
+/// An alternative that an earlier one of the same choice always wins against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShadowedAlternative {
+    pub rule: String,
+    /// The group the alternatives belong to, as written; `None` for the
+    /// alternatives of the rule itself
+    pub group: Option<String>,
+    /// Index of the alternative that wins
+    pub earlier: usize,
+    /// Index of the alternative never tried
+    pub later: usize,
+    pub earlier_items: String,
+    pub later_items: String,
+    pub reason: Shadowing,
+}
+
+/// Why the earlier alternative wins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shadowing {
+    /// It can match nothing, so it never fails
+    MatchesEmpty,
+    /// The later alternative starts with everything it needs
+    Prefix,
+    /// Where the two first differ, a token of the earlier alternative
+    /// matches the start of a terminal of the later one, and nothing after
+    /// it is needed
+    Token {
+        earlier: String,
+        later: String,
+        /// The start of `later` that `earlier` matches, without quotes
+        matched: String,
+    },
+}
+
+impl fmt::Display for ShadowedAlternative {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: alternative {} `{}` is never tried after `{}`",
+            self.rule,
+            self.later + 1,
+            self.later_items,
+            self.earlier_items
+        )?;
+        if let Some(group) = &self.group {
+            write!(f, " in {}", group)?;
+        }
+        writeln!(f)?;
+        match &self.reason {
+            Shadowing::MatchesEmpty => {
+                writeln!(f, "   `{}` can match nothing", self.earlier_items)
+            }
+            Shadowing::Prefix => writeln!(
+                f,
+                "   `{}` starts with everything `{}` requires",
+                self.later_items, self.earlier_items
+            ),
+            Shadowing::Token { earlier, later, .. } if earlier.starts_with('"') => {
+                writeln!(f, "   {} matches the start of {}", earlier, later)
+            }
+            Shadowing::Token {
+                earlier,
+                later,
+                matched,
+            } => writeln!(
+                f,
+                "   {} matches {:?} at the start of {}",
+                earlier, matched, later
+            ),
+        }
+    }
+}
+
+/// Every alternative of `sebnf` that ordered choice never tries, by rule and
+/// by position in the grammar. Each one is reported with the first earlier
+/// alternative that wins against it.
+pub fn peg_check(sebnf: &Sebnf) -> Result<Vec<ShadowedAlternative>, PegError> {
+    let mut checker = Checker {
+        nullable: nullable_rules(&sebnf.to_bnf()),
+        rule: String::new(),
+        groups: Vec::new(),
+        shadowed: Vec::new(),
+        error: None,
+    };
+    walk_sebnf(&mut checker, sebnf);
+    match checker.error {
+        Some(error) => Err(error),
+        None => Ok(checker.shadowed),
+    }
+}
+
+struct Checker {
+    nullable: Nullable,
+    rule: String,
+    /// The groups the visited alternatives are nested in, as written
+    groups: Vec<String>,
+    shadowed: Vec<ShadowedAlternative>,
+    error: Option<PegError>,
+}
+
+impl Visitor for Checker {
+    fn visit_rule(&mut self, name: &str, alternatives: &[Vec<Item>]) {
+        self.rule = name.to_string();
+        walk_rule(self, name, alternatives);
+    }
+
+    fn visit_item(&mut self, item: &Item) {
+        if let Item::Choice(..) = item {
+            self.groups.push(item.to_string());
+            walk_item(self, item);
+            self.groups.pop();
+        } else {
+            walk_item(self, item);
+        }
+    }
+
+    fn visit_alternatives(&mut self, alternatives: &[Vec<Item>]) {
+        for (later, alternative) in alternatives.iter().enumerate() {
+            for (earlier, winner) in alternatives[..later].iter().enumerate() {
+                let reason = match self.shadowing(winner, alternative) {
+                    Ok(reason) => reason,
+                    Err(error) => {
+                        self.error.get_or_insert(error);
+                        None
+                    }
+                };
+                if let Some(reason) = reason {
+                    self.shadowed.push(ShadowedAlternative {
+                        rule: self.rule.clone(),
+                        group: self.groups.last().cloned(),
+                        earlier,
+                        later,
+                        earlier_items: written(winner),
+                        later_items: written(alternative),
+                        reason,
+                    });
+                    break;
+                }
+            }
+        }
+        walk_alternatives(self, alternatives);
+    }
+}
+
+impl Checker {
+    /// Why `earlier` matches wherever `later` does, if it does
+    fn shadowing(&self, earlier: &[Item], later: &[Item]) -> Result<Option<Shadowing>, PegError> {
+        let earlier: Vec<&Item> = (earlier.iter())
+            .filter(|item| !matches!(item, Item::Epsilon(_)))
+            .collect();
+        let later: Vec<&Item> = (later.iter())
+            .filter(|item| !matches!(item, Item::Epsilon(_)))
+            .collect();
+        let rest_nullable = |from: usize| earlier[from..].iter().all(|item| self.is_nullable(item));
+        if rest_nullable(0) {
+            return Ok(Some(Shadowing::MatchesEmpty));
+        }
+        for (index, (&e, &l)) in earlier.iter().zip(&later).enumerate() {
+            if e.to_string() == l.to_string() {
+                if rest_nullable(index + 1) {
+                    return Ok(Some(Shadowing::Prefix));
+                }
+                continue;
+            }
+            // The two differ here; the earlier one only wins if this token
+            // matches the start of the terminal and nothing else is needed
+            return Ok(match matched_start(unlabeled(e), unlabeled(l))? {
+                Some(matched) if rest_nullable(index + 1) => Some(Shadowing::Token {
+                    earlier: e.to_string(),
+                    later: l.to_string(),
+                    matched,
+                }),
+                _ => None,
+            });
+        }
+        Ok(None)
+    }
+
+    /// Whether `item` can match nothing, which makes it a PEG expression
+    /// that never fails
+    fn is_nullable(&self, item: &Item) -> bool {
+        let sequence = |items: &[Item]| items.iter().all(|item| self.is_nullable(item));
+        match item {
+            Item::NonTerminal(name, _) => self.nullable.is_nullable(name),
+            Item::Terminal(terminal, _) => strip_terminal_quotes(terminal).is_empty(),
+            Item::Regex(regex, _) => regex_matches_empty(strip_regex_delimiters(regex)),
+            Item::Optional(..) | Item::AnyAmount(..) | Item::Epsilon(_) => true,
+            Item::Choice(alternatives, _) => alternatives.iter().any(|alt| sequence(alt)),
+            Item::Builtin(Builtin::SepBy, _, _) => true,
+            Item::Builtin(_, args, _) => args.iter().all(|arg| sequence(arg)),
+            Item::Labeled(_, inner, _) => self.is_nullable(inner),
+        }
+    }
+}
+
+fn unlabeled(item: &Item) -> &Item {
+    match item {
+        Item::Labeled(_, inner, _) => unlabeled(inner),
+        _ => item,
+    }
+}
+
+/// The start of the terminal `later` that the terminal or regex `earlier`
+/// matches, if it matches one
+fn matched_start(earlier: &Item, later: &Item) -> Result<Option<String>, PegError> {
+    let Item::Terminal(later, _) = later else {
+        return Ok(None);
+    };
+    let later = strip_terminal_quotes(later);
+    match earlier {
+        Item::Terminal(terminal, _) => {
+            let terminal = strip_terminal_quotes(terminal);
+            Ok(later.starts_with(terminal).then(|| terminal.to_string()))
+        }
+        Item::Regex(regex, _) => {
+            // Every non-empty start of the terminal, as one pattern
+            let starts: Vec<String> = (later.char_indices())
+                .map(|(i, c)| regex_syntax::escape(&later[..i + c.len_utf8()]))
+                .collect();
+            do_regexs_intersect(strip_regex_delimiters(regex), &starts.join("|")).map_err(
+                |source| PegError::InvalidRegex {
+                    pattern: regex.clone(),
+                    source,
+                },
+            )
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_alternatives_an_earlier_one_wins_against() {
+        let source = "s := op | ( /[a-z]+/ | \"if\" \"(\" ).\n\
+                      op := \"=\" [ \"!\" ] | \"==\" | \"=\" | ε | \"x\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let text: Vec<_> = (peg_check(&sebnf).unwrap().iter())
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            text,
+            [
+                "s: alternative 2 `( /[a-z]+/ | \"if\" \"(\" )` is never tried after `op`\n   \
+                 `op` can match nothing\n",
+                "s: alternative 2 `\"if\" \"(\"` is never tried after `/[a-z]+/` in ( /[a-z]+/ | \"if\" \"(\" )\n   \
+                 /[a-z]+/ matches \"i\" at the start of \"if\"\n",
+                "op: alternative 2 `\"==\"` is never tried after `\"=\" [ \"!\" ]`\n   \
+                 \"=\" matches the start of \"==\"\n",
+                "op: alternative 3 `\"=\"` is never tried after `\"=\" [ \"!\" ]`\n   \
+                 `\"=\"` starts with everything `\"=\" [ \"!\" ]` requires\n",
+                "op: alternative 5 `\"x\"` is never tried after `ε`\n   \
+                 `ε` can match nothing\n",
+            ]
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Rules that derive no string of terminals.
+//!
+//! A rule such as `a := "(" a ")".` without a base case never ends, so it
+//! derives no string at all. The sets still give it a FIRST set, `"("`, which
+//! hides that no parse can ever complete it. [`productivity`] lists such
+//! rules together with the recursion that blocks them: rules that each need
+//! the next, the last the first, since every production of an unproductive
+//! rule refers to another unproductive rule.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::productivity::productivity;
+//!
+//! let bnf = Sebnf::parse_str("s := \"x\" | a.\na := \"(\" a \")\".", "g")
+//!     .unwrap()
+//!     .to_bnf();
+//! let report = productivity(&bnf);
+//! assert_eq!(report.rules, ["a"]);
+//! assert_eq!(report.cycles.len(), 1);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::left_recursion::{Edges, LeftStep, shortest_cycles};
+use crate::sets::format_production;
+use crate::symbols::Symbols;
+
+/// The unproductive rules of a grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Productivity {
+    /// In grammar order
+    pub rules: Vec<String>,
+    /// How many rules the grammar has
+    pub total: usize,
+    /// Rules that each need the next, the last the first, each starting at
+    /// its rule that comes first in the grammar
+    pub cycles: Vec<Vec<LeftStep>>,
+}
+
+impl fmt::Display for Productivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rules.is_empty() {
+            return writeln!(f, "Every rule derives a string of terminals.");
+        }
+        writeln!(
+            f,
+            "Unproductive rules ({} of {}): {}",
+            self.rules.len(),
+            self.total,
+            self.rules.join(", ")
+        )?;
+        let arrow = Symbols::with_current(|s| s.glyph("→", "->"));
+        for cycle in &self.cycles {
+            let mut rules: Vec<&str> = cycle.iter().map(|step| step.rule.as_str()).collect();
+            rules.extend(cycle.first().map(|step| step.rule.as_str()));
+            writeln!(
+                f,
+                "Blocking recursion: {}",
+                rules.join(&format!(" {} ", arrow))
+            )?;
+            for step in cycle {
+                writeln!(
+                    f,
+                    "   {} := {}",
+                    step.rule,
+                    format_production(&step.production)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds the rules of `bnf` that derive no string of terminals, and the
+/// recursions that keep them from it.
+pub fn productivity(bnf: &Bnf) -> Productivity {
+    let productive = bnf.productive_rules();
+    let unproductive = |name: &str| bnf.rules.contains_key(name) && !productive.contains(name);
+    // The unproductive rules each production of an unproductive rule needs
+    let mut edges: Edges = HashMap::new();
+    for (rule, productions) in bnf.rules.iter().filter(|(rule, _)| unproductive(rule)) {
+        let rule_edges = edges.entry(rule).or_default();
+        for production in productions {
+            for item in production {
+                if let Item::NonTerminal(nt) = item
+                    && unproductive(nt)
+                    && !rule_edges.iter().any(|(next, _)| next == nt)
+                {
+                    rule_edges.push((nt, production));
+                }
+            }
+        }
+    }
+
+    Productivity {
+        rules: (bnf.rules.keys())
+            .filter(|rule| unproductive(rule))
+            .cloned()
+            .collect(),
+        total: bnf.rules.len(),
+        cycles: shortest_cycles(bnf, &edges),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn reports_the_recursion_without_a_base_case() {
+        let source = "s := \"x\" | a.\na := \"(\" b \")\" | \"[\" a.\nb := a \";\".\nc := b.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        assert_eq!(
+            productivity(&bnf).to_string(),
+            "Unproductive rules (3 of 4): a, b, c\n\
+             Blocking recursion: a → a\n   \
+             a := \"[\" a\n\
+             Blocking recursion: a → b → a\n   \
+             a := \"(\" b \")\"\n   \
+             b := a \";\"\n"
+        );
+    }
+}
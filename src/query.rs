@@ -0,0 +1,325 @@
+//! A small set-algebra language over FIRST/FOLLOW sets.
+//!
+//! ```text
+//! expr    := term { ("&" | "|" | "-") term }.
+//! term    := "first" "(" symbol ")"
+//!          | "follow" "(" symbol ")"
+//!          | "nullable" "(" symbol ")"
+//!          | "{" [ element { "," element } ] "}"
+//!          | "(" expr ")"
+//!          .
+//! element := /"..."/ | /\/...\// | "ε" | "$" | token_class.
+//! ```
+//!
+//! Operators are left-associative and share one precedence level.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::sets::{SetItem, Sets};
+use crate::symbols::Symbols;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, miette::Diagnostic)]
+pub enum QueryError {
+    #[error("syntax error at offset {offset}: {message}")]
+    #[diagnostic(code(sebnf::query_syntax))]
+    Syntax { offset: usize, message: String },
+    #[error("unknown non-terminal '{0}'")]
+    #[diagnostic(code(sebnf::query_unknown_symbol))]
+    UnknownSymbol(String),
+    #[error("operator '{0}' needs two sets, found a boolean")]
+    #[diagnostic(code(sebnf::query_type_error))]
+    TypeError(char),
+}
+
+/// Result of evaluating a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryValue {
+    Set(BTreeSet<String>),
+    Bool(bool),
+}
+
+impl QueryValue {
+    /// True for `true` and for non-empty sets
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            QueryValue::Set(set) => !set.is_empty(),
+            QueryValue::Bool(b) => *b,
+        }
+    }
+}
+
+impl fmt::Display for QueryValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryValue::Bool(b) => write!(f, "{}", b),
+            QueryValue::Set(set) => {
+                let items: Vec<&str> = set.iter().map(String::as_str).collect();
+                write!(f, "{{ {} }}", items.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    First(String),
+    Follow(String),
+    Nullable(String),
+    Literal(BTreeSet<String>),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+/// Parses and evaluates `query` against `sets`.
+pub fn evaluate(query: &str, sets: &Sets) -> Result<QueryValue, QueryError> {
+    let mut parser = QueryParser { src: query, pos: 0 };
+    let expr = parser.expr()?;
+    parser.skip_whitespace();
+    if parser.pos < query.len() {
+        return Err(parser.error("unexpected trailing input"));
+    }
+    eval(&expr, sets)
+}
+
+fn lookup(
+    map: &HashMap<String, HashSet<SetItem>>,
+    symbol: &str,
+) -> Result<BTreeSet<String>, QueryError> {
+    map.get(symbol)
+        .map(|set| set.iter().map(SetItem::to_string).collect())
+        .ok_or_else(|| QueryError::UnknownSymbol(symbol.to_string()))
+}
+
+fn eval(expr: &Expr, sets: &Sets) -> Result<QueryValue, QueryError> {
+    match expr {
+        Expr::First(symbol) => lookup(&sets.first, symbol).map(QueryValue::Set),
+        Expr::Follow(symbol) => lookup(&sets.follow, symbol).map(QueryValue::Set),
+        Expr::Nullable(symbol) => {
+            let first = sets
+                .first
+                .get(symbol)
+                .ok_or_else(|| QueryError::UnknownSymbol(symbol.clone()))?;
+            Ok(QueryValue::Bool(first.contains(&SetItem::Epsilon)))
+        }
+        Expr::Literal(set) => Ok(QueryValue::Set(set.clone())),
+        Expr::Binary(lhs, op, rhs) => {
+            let (QueryValue::Set(lhs), QueryValue::Set(rhs)) = (eval(lhs, sets)?, eval(rhs, sets)?)
+            else {
+                return Err(QueryError::TypeError(*op));
+            };
+            let result = match op {
+                '&' => lhs.intersection(&rhs).cloned().collect(),
+                '|' => lhs.union(&rhs).cloned().collect(),
+                _ => lhs.difference(&rhs).cloned().collect(),
+            };
+            Ok(QueryValue::Set(result))
+        }
+    }
+}
+
+struct QueryParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> QueryParser<'a> {
+    fn error(&self, message: &str) -> QueryError {
+        QueryError::Syntax {
+            offset: self.pos,
+            message: message.to_string(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.src.len() - trimmed.len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), QueryError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, QueryError> {
+        self.skip_whitespace();
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.error("expected identifier"));
+        }
+        let ident = self.rest()[..len].to_string();
+        self.pos += len;
+        Ok(ident)
+    }
+
+    fn expr(&mut self) -> Result<Expr, QueryError> {
+        let mut lhs = self.term()?;
+        loop {
+            self.skip_whitespace();
+            let Some(op) = self.rest().chars().next().filter(|c| "&|-".contains(*c)) else {
+                return Ok(lhs);
+            };
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, QueryError> {
+        if self.eat('(') {
+            let expr = self.expr()?;
+            self.expect(')')?;
+            return Ok(expr);
+        }
+        if self.eat('{') {
+            return self.literal();
+        }
+
+        let function = self.identifier()?;
+        self.expect('(')?;
+        let symbol = self.identifier()?;
+        self.expect(')')?;
+        match function.as_str() {
+            "first" => Ok(Expr::First(symbol)),
+            "follow" => Ok(Expr::Follow(symbol)),
+            "nullable" => Ok(Expr::Nullable(symbol)),
+            _ => Err(self.error(&format!("unknown function '{}'", function))),
+        }
+    }
+
+    /// Parses the elements of `{ ... }`; the opening brace is already consumed.
+    fn literal(&mut self) -> Result<Expr, QueryError> {
+        let mut set = BTreeSet::new();
+        if self.eat('}') {
+            return Ok(Expr::Literal(set));
+        }
+        loop {
+            self.skip_whitespace();
+            set.insert(self.element()?);
+            if self.eat('}') {
+                return Ok(Expr::Literal(set));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    /// A quoted terminal, a /regex/, ε, $ or a token class name, rendered
+    /// like `SetItem`'s Display.
+    fn element(&mut self) -> Result<String, QueryError> {
+        let rest = self.rest();
+        // The default spellings work regardless of the configured symbols
+        let (epsilon, end_of_input) =
+            Symbols::with_current(|s| (s.epsilon.clone(), s.end_of_input.clone()));
+        for (special, item) in [
+            (epsilon.as_str(), SetItem::Epsilon),
+            (end_of_input.as_str(), SetItem::EndOfInput),
+            ("ε", SetItem::Epsilon),
+            ("$", SetItem::EndOfInput),
+        ] {
+            let Some(after) = rest.strip_prefix(special) else {
+                continue;
+            };
+            // `eps` must not match the start of a token class like `eps_token`
+            let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+            if special.ends_with(is_ident) && after.starts_with(is_ident) {
+                continue;
+            }
+            self.pos += special.len();
+            return Ok(item.to_string());
+        }
+        if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            return self.identifier();
+        }
+        let Some(delimiter) = rest.chars().next().filter(|c| *c == '"' || *c == '/') else {
+            return Err(self.error("expected a \"terminal\", /regex/, ε, $ or token class"));
+        };
+
+        let mut escaped = false;
+        for (i, c) in rest.char_indices().skip(1) {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == delimiter {
+                let element = &rest[..=i];
+                self.pos += i + 1;
+                // Terminals are displayed without their quotes
+                return Ok(match delimiter {
+                    '"' => element[1..element.len() - 1].to_string(),
+                    _ => element.to_string(),
+                });
+            }
+        }
+        Err(self.error("unterminated literal"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sets() -> Sets {
+        let t = |s: &str| SetItem::Terminal(format!("\"{}\"", s));
+        let mut first = HashMap::new();
+        first.insert("A".to_string(), HashSet::from([t("a"), t("b")]));
+        first.insert("B".to_string(), HashSet::from([t("b"), SetItem::Epsilon]));
+        let mut follow = HashMap::new();
+        follow.insert("A".to_string(), HashSet::from([SetItem::EndOfInput]));
+        follow.insert("B".to_string(), HashSet::from([t("c")]));
+        Sets { first, follow }
+    }
+
+    fn set(items: &[&str]) -> QueryValue {
+        QueryValue::Set(items.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn set_algebra() {
+        let sets = sets();
+        assert_eq!(evaluate("first(A) & first(B)", &sets), Ok(set(&["b"])));
+        assert_eq!(evaluate("first(A) - {\"a\"}", &sets), Ok(set(&["b"])));
+        assert_eq!(
+            evaluate("(first(A) | follow(B)) - first(B)", &sets),
+            Ok(set(&["a", "c"]))
+        );
+        assert_eq!(evaluate("follow(A) & {$, ε}", &sets), Ok(set(&["$"])));
+    }
+
+    #[test]
+    fn nullable_and_errors() {
+        let sets = sets();
+        assert_eq!(evaluate("nullable(B)", &sets), Ok(QueryValue::Bool(true)));
+        assert_eq!(evaluate("nullable(A)", &sets), Ok(QueryValue::Bool(false)));
+        assert_eq!(
+            evaluate("first(C)", &sets),
+            Err(QueryError::UnknownSymbol("C".to_string()))
+        );
+        assert_eq!(
+            evaluate("nullable(A) & first(A)", &sets),
+            Err(QueryError::TypeError('&'))
+        );
+        assert!(matches!(
+            evaluate("first(A) &", &sets),
+            Err(QueryError::Syntax { .. })
+        ));
+    }
+}
@@ -0,0 +1,428 @@
+//! Railroad diagrams of the rules as SVG, for documentation.
+//!
+//! Diagrams are drawn from the SEBNF rules, not the BNF, so the structure
+//! written in the grammar shows: a choice branches, `[ ]` adds a bypass
+//! above the optional items, `{ }` a bypass and a loop back below them, and
+//! `sep_by` loops back through the separator. Terminals are drawn as
+//! rounded boxes, regexes as dashed boxes and non-terminals as boxes linking
+//! to their rule's diagram, labels above what they name.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::railroad::rule_diagram;
+//!
+//! let sebnf = Sebnf::parse_str("list := \"[\" [ num { \",\" num } ] \"]\".\nnum := /[0-9]+/.", "g").unwrap();
+//! let svg = rule_diagram(&sebnf, "list").unwrap();
+//! assert!(svg.starts_with("<svg "));
+//! assert!(svg.contains("<a href=\"num.svg\">"));
+//! ```
+
+use std::fmt::Write;
+
+use crate::sebnf::{Builtin, Item, Sebnf};
+use crate::sets::strip_terminal_quotes;
+
+/// Radius of the curves, and the room they take on either side of a branch
+const ARC: i32 = 10;
+/// Height of a box
+const BOX: i32 = 24;
+/// Width of a character of box text
+const CHAR: i32 = 8;
+/// Line between the items of a sequence
+const GAP: i32 = 10;
+/// Space between the branches of a choice or a loop
+const SPACING: i32 = 8;
+/// Room above an item for its label
+const LABEL: i32 = 16;
+/// Margin around a diagram, and room for its title above
+const MARGIN: i32 = 20;
+const TITLE: i32 = 24;
+
+const STYLE: &str = "<style>\n\
+    path { fill: none; stroke: #333; stroke-width: 2; }\n\
+    rect { fill: #fefce8; stroke: #333; stroke-width: 2; }\n\
+    rect.regex { fill: #f0f9ff; stroke-dasharray: 4 3; }\n\
+    text { font: 14px monospace; fill: #111; text-anchor: middle; }\n\
+    text.title { font: bold 16px sans-serif; text-anchor: start; }\n\
+    text.label { font: italic 12px sans-serif; fill: #555; text-anchor: start; }\n\
+    a text { fill: #1d4ed8; }\n\
+</style>\n";
+
+/// The diagram of `rule` as a standalone SVG document, or `None` if the
+/// grammar has no such rule. Non-terminals link to `RULE.svg`.
+pub fn rule_diagram(sebnf: &Sebnf, rule: &str) -> Option<String> {
+    let alternatives = sebnf.rules.get(rule)?;
+    let diagram = Node::choice(alternatives);
+    let (width, height) = diagram_size(&diagram, rule);
+    let mut body = String::new();
+    draw_diagram(&mut body, Links::Files, &diagram, rule, 0);
+    Some(svg(width, height, &body))
+}
+
+/// The diagrams of all rules in one SVG document, one below the other in
+/// the order of the grammar, each in a group with the rule's name as `id`.
+pub fn diagrams(sebnf: &Sebnf) -> String {
+    let mut body = String::new();
+    let (mut width, mut height) = (0, 0);
+    for (rule, alternatives) in &sebnf.rules {
+        let diagram = Node::choice(alternatives);
+        let size = diagram_size(&diagram, rule);
+        draw_diagram(&mut body, Links::Groups, &diagram, rule, height);
+        width = width.max(size.0);
+        height += size.1;
+    }
+    svg(width, height, &body)
+}
+
+fn svg(width: i32, height: i32, body: &str) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\" class=\"railroad\">\n{STYLE}{body}</svg>\n"
+    )
+}
+
+/// Width and height of the diagram of a rule, with its title, margins and
+/// the bars marking its start and end
+fn diagram_size(diagram: &Node, rule: &str) -> (i32, i32) {
+    let width = (MARGIN * 2 + GAP * 2 + diagram.width()).max(MARGIN * 2 + text_width(rule));
+    let height = MARGIN * 2 + TITLE + diagram.up() + diagram.down();
+    (width, height)
+}
+
+fn draw_diagram(svg: &mut String, links: Links, diagram: &Node, rule: &str, top: i32) {
+    let title = top + MARGIN + 14;
+    let y = top + MARGIN + TITLE + diagram.up();
+    let (start, end) = (MARGIN, MARGIN + GAP * 2 + diagram.width());
+    let _ = writeln!(svg, "<g id=\"{}\">", escape(rule));
+    let _ = writeln!(
+        svg,
+        "<text class=\"title\" x=\"{}\" y=\"{}\">{}</text>",
+        MARGIN,
+        title,
+        escape(rule)
+    );
+    // A double bar at the start and at the end
+    for x in [start, start + 4, end - 4, end] {
+        let _ = writeln!(svg, "<path d=\"M{} {} v{}\"/>", x, y - ARC, ARC * 2);
+    }
+    line(svg, start, y, start + GAP);
+    diagram.draw(svg, links, start + GAP, y);
+    line(svg, start + GAP + diagram.width(), y, end);
+    let _ = writeln!(svg, "</g>");
+}
+
+/// A part of a diagram. It is entered on the left and left on the right,
+/// both on its main line, `up` above which and `down` below which it takes
+/// room.
+enum Node {
+    /// A line, where nothing is matched
+    Skip,
+    Terminal(String),
+    Regex(String),
+    NonTerminal(String),
+    Sequence(Vec<Node>),
+    /// The first branch on the main line, the others below it
+    Choice(Vec<Node>),
+    /// The item on the main line, looping back below it through the
+    /// separator
+    Repeat(Box<Node>, Box<Node>),
+    Labeled(String, Box<Node>),
+}
+
+impl Node {
+    fn choice(alternatives: &[Vec<Item>]) -> Node {
+        match alternatives {
+            [alternative] => Node::sequence(alternative),
+            _ => Node::Choice(alternatives.iter().map(|a| Node::sequence(a)).collect()),
+        }
+    }
+
+    fn sequence(items: &[Item]) -> Node {
+        let mut nodes: Vec<Node> = (items.iter())
+            .map(Node::item)
+            .filter(|node| !matches!(node, Node::Skip))
+            .collect();
+        match nodes.len() {
+            0 => Node::Skip,
+            1 => nodes.remove(0),
+            _ => Node::Sequence(nodes),
+        }
+    }
+
+    fn item(item: &Item) -> Node {
+        match item {
+            Item::NonTerminal(name, _) => Node::NonTerminal(name.clone()),
+            Item::Terminal(terminal, _) => {
+                Node::Terminal(strip_terminal_quotes(terminal).to_string())
+            }
+            Item::Regex(regex, _) => Node::Regex(regex.clone()),
+            Item::Optional(items, _) => Node::optional(Node::sequence(items)),
+            Item::AnyAmount(items, _) => Node::optional(Node::Repeat(
+                Box::new(Node::sequence(items)),
+                Box::new(Node::Skip),
+            )),
+            Item::Choice(alternatives, _) => Node::choice(alternatives),
+            Item::Builtin(builtin, arguments, _) => match (builtin, arguments.as_slice()) {
+                (Builtin::SepBy, [item, separator]) => Node::optional(Node::Repeat(
+                    Box::new(Node::sequence(item)),
+                    Box::new(Node::sequence(separator)),
+                )),
+                (Builtin::Delimited, [left, item, right]) => Node::Sequence(vec![
+                    Node::sequence(left),
+                    Node::sequence(item),
+                    Node::sequence(right),
+                ]),
+                (Builtin::NonEmpty, [item]) => {
+                    Node::Repeat(Box::new(Node::sequence(item)), Box::new(Node::Skip))
+                }
+                _ => unreachable!("built-in calls are checked when parsing"),
+            },
+            Item::Labeled(label, item, _) => {
+                Node::Labeled(label.clone(), Box::new(Node::item(item)))
+            }
+            Item::Epsilon(_) => Node::Skip,
+        }
+    }
+
+    fn optional(node: Node) -> Node {
+        Node::Choice(vec![Node::Skip, node])
+    }
+
+    fn width(&self) -> i32 {
+        match self {
+            Node::Skip => 0,
+            Node::Terminal(text) | Node::Regex(text) | Node::NonTerminal(text) => {
+                text_width(text) + 2 * GAP
+            }
+            Node::Sequence(nodes) => {
+                nodes.iter().map(Node::width).sum::<i32>() + GAP * (nodes.len() as i32 - 1)
+            }
+            Node::Choice(branches) => branches.iter().map(Node::width).max().unwrap_or(0) + 4 * ARC,
+            Node::Repeat(item, separator) => item.width().max(separator.width()) + 2 * ARC,
+            Node::Labeled(label, node) => node.width().max(text_width(label) * 3 / 4),
+        }
+    }
+
+    fn up(&self) -> i32 {
+        match self {
+            Node::Skip => 0,
+            Node::Terminal(_) | Node::Regex(_) | Node::NonTerminal(_) => BOX / 2,
+            Node::Sequence(nodes) => nodes.iter().map(Node::up).max().unwrap_or(0),
+            Node::Choice(branches) => branches[0].up(),
+            Node::Repeat(item, _) => item.up(),
+            Node::Labeled(_, node) => node.up() + LABEL,
+        }
+    }
+
+    fn down(&self) -> i32 {
+        match self {
+            Node::Skip => 0,
+            Node::Terminal(_) | Node::Regex(_) | Node::NonTerminal(_) => BOX / 2,
+            Node::Sequence(nodes) => nodes.iter().map(Node::down).max().unwrap_or(0),
+            Node::Choice(branches) => {
+                let last = branches.last().expect("a choice has branches");
+                self.branch_offsets().last().copied().unwrap_or(0) + last.down()
+            }
+            Node::Repeat(item, separator) => Node::loop_offset(item, separator) + separator.down(),
+            Node::Labeled(_, node) => node.down(),
+        }
+    }
+
+    /// How far below the main line each branch of a choice runs, at least
+    /// far enough for the curves to it
+    fn branch_offsets(&self) -> Vec<i32> {
+        let Node::Choice(branches) = self else {
+            return Vec::new();
+        };
+        let mut offsets = vec![0];
+        for pair in branches.windows(2) {
+            let previous = *offsets.last().unwrap();
+            let offset = previous + (pair[0].down() + SPACING + pair[1].up()).max(2 * ARC);
+            offsets.push(offset);
+        }
+        offsets
+    }
+
+    /// How far below the main line the way back of a loop runs
+    fn loop_offset(item: &Node, separator: &Node) -> i32 {
+        (item.down() + SPACING + separator.up()).max(2 * ARC)
+    }
+
+    /// Draws the node entered at `(x, y)`.
+    fn draw(&self, svg: &mut String, links: Links, x: i32, y: i32) {
+        match self {
+            Node::Skip => {}
+            Node::Terminal(text) => draw_box(svg, x, y, text, BoxKind::Terminal),
+            Node::Regex(text) => draw_box(svg, x, y, text, BoxKind::Regex),
+            Node::NonTerminal(text) => draw_box(svg, x, y, text, BoxKind::NonTerminal(links)),
+            Node::Sequence(nodes) => {
+                let mut x = x;
+                for (i, node) in nodes.iter().enumerate() {
+                    if i > 0 {
+                        line(svg, x, y, x + GAP);
+                        x += GAP;
+                    }
+                    node.draw(svg, links, x, y);
+                    x += node.width();
+                }
+            }
+            Node::Choice(branches) => {
+                let width = self.width();
+                let (inner, outer) = (x + 2 * ARC, x + width - 2 * ARC);
+                for (branch, offset) in branches.iter().zip(self.branch_offsets()) {
+                    let branch_y = y + offset;
+                    if offset == 0 {
+                        line(svg, x, y, inner);
+                    } else {
+                        let straight = offset - 2 * ARC;
+                        let _ = writeln!(
+                            svg,
+                            "<path d=\"M{x} {y} a{r} {r} 0 0 1 {r} {r} v{straight} a{r} {r} 0 0 0 {r} {r}\"/>",
+                            r = ARC
+                        );
+                        let _ = writeln!(
+                            svg,
+                            "<path d=\"M{outer} {branch_y} a{r} {r} 0 0 0 {r} -{r} v-{straight} a{r} {r} 0 0 1 {r} -{r}\"/>",
+                            r = ARC
+                        );
+                    }
+                    branch.draw(svg, links, inner, branch_y);
+                    line(svg, inner + branch.width(), branch_y, outer);
+                    if offset == 0 {
+                        line(svg, outer, y, x + width);
+                    }
+                }
+            }
+            Node::Repeat(item, separator) => {
+                let width = self.width();
+                let (inner, outer) = (x + ARC, x + width - ARC);
+                line(svg, x, y, inner);
+                item.draw(svg, links, inner, y);
+                line(svg, inner + item.width(), y, x + width);
+                let offset = Node::loop_offset(item, separator);
+                let (back_y, straight) = (y + offset, offset - 2 * ARC);
+                let _ = writeln!(
+                    svg,
+                    "<path d=\"M{outer} {y} a{r} {r} 0 0 1 {r} {r} v{straight} a{r} {r} 0 0 1 -{r} {r}\"/>",
+                    r = ARC
+                );
+                let _ = writeln!(
+                    svg,
+                    "<path d=\"M{inner} {back_y} a{r} {r} 0 0 1 -{r} -{r} v-{straight} a{r} {r} 0 0 1 {r} -{r}\"/>",
+                    r = ARC
+                );
+                let start = inner + (outer - inner - separator.width()) / 2;
+                line(svg, inner, back_y, start);
+                separator.draw(svg, links, start, back_y);
+                line(svg, start + separator.width(), back_y, outer);
+            }
+            Node::Labeled(label, node) => {
+                let _ = writeln!(
+                    svg,
+                    "<text class=\"label\" x=\"{}\" y=\"{}\">{}</text>",
+                    x,
+                    y - node.up() - 4,
+                    escape(label)
+                );
+                node.draw(svg, links, x, y);
+                line(svg, x + node.width(), y, x + self.width());
+            }
+        }
+    }
+}
+
+enum BoxKind {
+    Terminal,
+    Regex,
+    NonTerminal(Links),
+}
+
+/// Where the box of a non-terminal links to
+#[derive(Clone, Copy)]
+enum Links {
+    /// The group of the rule's diagram in the same document
+    Groups,
+    /// The rule's diagram in `RULE.svg` next to this one
+    Files,
+}
+
+fn draw_box(svg: &mut String, x: i32, y: i32, text: &str, kind: BoxKind) {
+    let width = text_width(text) + 2 * GAP;
+    let rect = |class: &str, radius: i32| {
+        format!(
+            "<rect{} x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"/>",
+            class,
+            x,
+            y - BOX / 2,
+            width,
+            BOX,
+            radius
+        )
+    };
+    let label = format!(
+        "<text x=\"{}\" y=\"{}\">{}</text>",
+        x + width / 2,
+        y + 5,
+        escape(text)
+    );
+    let _ = match kind {
+        BoxKind::Terminal => writeln!(svg, "{}{}", rect("", BOX / 2), label),
+        BoxKind::Regex => writeln!(svg, "{}{}", rect(" class=\"regex\"", 0), label),
+        BoxKind::NonTerminal(links) => {
+            let href = match links {
+                Links::Groups => format!("#{}", escape(text)),
+                Links::Files => format!("{}.svg", escape(text)),
+            };
+            writeln!(svg, "<a href=\"{}\">{}{}</a>", href, rect("", 0), label)
+        }
+    };
+}
+
+fn line(svg: &mut String, from: i32, y: i32, to: i32) {
+    if to > from {
+        let _ = writeln!(svg, "<path d=\"M{} {} h{}\"/>", from, y, to - from);
+    }
+}
+
+fn text_width(text: &str) -> i32 {
+    text.chars().count() as i32 * CHAR
+}
+
+/// `text` with the characters markup gives a meaning to escaped, for
+/// SVG and HTML
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_branches_bypasses_and_loops() {
+        let source = "s := \"if\" [ cond ] { \"x\" } | args:sep_by(/[0-9]+/, \",\").\ncond := \"<\" | \"&\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let svg = rule_diagram(&sebnf, "s").unwrap();
+        // Four choices, of `[ ]`, `{ }`, `sep_by` and the alternatives, each
+        // branching down on the left and back up on the right, and two loops
+        // turning down on the right
+        assert_eq!(svg.matches(" a10 10 0 0 1 10 10 ").count(), 6);
+        assert_eq!(svg.matches(" a10 10 0 0 0 10 -10 ").count(), 4);
+        assert_eq!(svg.matches(" a10 10 0 0 1 -10 10").count(), 2);
+        // A rounded box for the terminal, centered on the main line
+        assert!(svg.contains("<rect x=\"50\" y=\"44\" width=\"36\" height=\"24\" rx=\"12\"/>"));
+        assert!(svg.contains("<text x=\"68\" y=\"61\">if</text>"));
+        assert!(svg.contains("<a href=\"cond.svg\">"));
+        assert!(svg.contains("<rect class=\"regex\""));
+        assert!(svg.contains(">args</text>"));
+        assert!(!svg.contains("&\""));
+
+        let cond = rule_diagram(&sebnf, "cond").unwrap();
+        assert!(cond.contains(">&lt;</text>") && cond.contains(">&amp;</text>"));
+        let all = diagrams(&sebnf);
+        assert!(all.contains("<g id=\"cond\">") && all.contains("<a href=\"#cond\">"));
+    }
+}
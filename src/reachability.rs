@@ -0,0 +1,214 @@
+//! Rules the start rule never reaches.
+//!
+//! An unreachable rule adds nothing to the language, but is easy to miss:
+//! the grammar still parses, and a rule that was meant to be used looks no
+//! different from one that is. [`reachability`] lists them, and since every
+//! rule referring to an unreachable rule is unreachable itself, traces each
+//! back to an unreachable rule that nothing refers to, which is usually the
+//! one that was forgotten.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::reachability::reachability;
+//!
+//! let source = "s := \"x\".\nold := \"y\" old_item.\nold_item := \"z\".";
+//! let sebnf = Sebnf::parse_str(source, "g").unwrap();
+//! let report = reachability(&sebnf);
+//! assert_eq!(report.rules.len(), 2);
+//! assert_eq!(report.rules[1].path, ["old", "old_item"]);
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+use indexmap::IndexMap;
+
+use crate::sebnf::Sebnf;
+use crate::symbols::Symbols;
+use crate::visit::Visitor;
+
+/// The unreachable rules of a grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reachability {
+    pub start: String,
+    /// In grammar order
+    pub rules: Vec<UnreachableRule>,
+    /// How many rules the grammar has
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachableRule {
+    pub rule: String,
+    /// Rules referring to it, all unreachable as well, in grammar order
+    pub referenced_by: Vec<String>,
+    /// A shortest chain of references to the rule from an unreachable rule
+    /// nothing refers to, or from a rule of an unreachable cycle; just the
+    /// rule if it is one of those
+    pub path: Vec<String>,
+}
+
+impl fmt::Display for UnreachableRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arrow = Symbols::with_current(|s| s.glyph("→", "->"));
+        write!(f, "{}: ", self.rule)?;
+        if self.path.len() > 1 {
+            write!(
+                f,
+                "reached from {}",
+                self.path.join(&format!(" {} ", arrow))
+            )
+        } else if self.referenced_by.is_empty() {
+            write!(f, "referenced by no rule")
+        } else {
+            let rules: Vec<_> = (self.referenced_by.iter())
+                .map(|r| format!("'{}'", r))
+                .collect();
+            write!(f, "referenced only by {}", rules.join(", "))
+        }
+    }
+}
+
+impl fmt::Display for Reachability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rules.is_empty() {
+            return writeln!(
+                f,
+                "All {} rule(s) are reachable from '{}'.",
+                self.total, self.start
+            );
+        }
+        writeln!(
+            f,
+            "{} of {} rules are unreachable from '{}':",
+            self.rules.len(),
+            self.total,
+            self.start
+        )?;
+        for rule in &self.rules {
+            writeln!(f, "  {}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+/// Finds the rules of `sebnf` its first rule does not reach.
+pub fn reachability(sebnf: &Sebnf) -> Reachability {
+    struct References(Vec<String>);
+    impl Visitor for References {
+        fn visit_non_terminal(&mut self, name: &str, _span: &Range<usize>) {
+            if !self.0.iter().any(|n| n == name) {
+                self.0.push(name.to_string());
+            }
+        }
+    }
+    let references: IndexMap<&str, Vec<String>> = (sebnf.rules.iter())
+        .map(|(name, alternatives)| {
+            let mut references = References(Vec::new());
+            references.visit_alternatives(alternatives);
+            references.0.retain(|n| sebnf.rules.contains_key(n));
+            (name.as_str(), references.0)
+        })
+        .collect();
+
+    let start = sebnf.rules.keys().next().cloned().unwrap_or_default();
+    let mut reachable: HashSet<&str> = HashSet::from([start.as_str()]);
+    let mut stack = vec![start.as_str()];
+    while let Some(rule) = stack.pop() {
+        for next in references.get(rule).into_iter().flatten() {
+            if reachable.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+    let unreachable: Vec<&str> = (references.keys().copied())
+        .filter(|rule| !reachable.contains(rule))
+        .collect();
+    let referenced_by = |rule: &str| -> Vec<String> {
+        (references.iter())
+            .filter(|&(&other, refs)| other != rule && refs.iter().any(|r| r == rule))
+            .map(|(other, _)| other.to_string())
+            .collect()
+    };
+
+    // Breadth first from the rules nothing refers to, then from the first
+    // rule of each cycle that is still left
+    let mut previous: IndexMap<&str, Option<&str>> = IndexMap::new();
+    let mut roots: Vec<&str> = (unreachable.iter().copied())
+        .filter(|rule| referenced_by(rule).is_empty())
+        .collect();
+    loop {
+        let mut queue: VecDeque<&str> = VecDeque::new();
+        for root in roots.drain(..) {
+            previous.insert(root, None);
+            queue.push_back(root);
+        }
+        while let Some(rule) = queue.pop_front() {
+            for next in &references[rule] {
+                if !previous.contains_key(next.as_str()) {
+                    previous.insert(next, Some(rule));
+                    queue.push_back(next);
+                }
+            }
+        }
+        match unreachable
+            .iter()
+            .find(|rule| !previous.contains_key(*rule))
+        {
+            Some(rule) => roots.push(rule),
+            None => break,
+        }
+    }
+
+    let rules = (unreachable.iter())
+        .map(|&rule| {
+            let mut path = vec![rule.to_string()];
+            let mut current = rule;
+            while let Some(&Some(before)) = previous.get(current) {
+                path.push(before.to_string());
+                current = before;
+            }
+            path.reverse();
+            UnreachableRule {
+                rule: rule.to_string(),
+                referenced_by: referenced_by(rule),
+                path,
+            }
+        })
+        .collect();
+    Reachability {
+        start,
+        rules,
+        total: sebnf.rules.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traces_unreachable_rules_to_an_unreferenced_one() {
+        let source = "s := a.\na := \"x\".\n\
+                      old := [ old_item ] loop.\nold_item := \"y\".\n\
+                      loop := \"(\" cycle \")\".\ncycle := loop | \"z\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        assert_eq!(
+            reachability(&sebnf).to_string(),
+            "4 of 6 rules are unreachable from 's':\n  \
+             old: referenced by no rule\n  \
+             old_item: reached from old → old_item\n  \
+             loop: reached from old → loop\n  \
+             cycle: reached from old → loop → cycle\n"
+        );
+
+        let sebnf = Sebnf::parse_str("s := \"x\".\na := b.\nb := a | \"y\".", "g").unwrap();
+        assert_eq!(
+            reachability(&sebnf).to_string(),
+            "2 of 3 rules are unreachable from 's':\n  \
+             a: referenced only by 'b'\n  \
+             b: reached from a → b\n"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! Groups of mutually recursive rules.
+//!
+//! Rules that refer to each other, directly or through other rules, form a
+//! strongly connected component of the grammar's reference graph, and have
+//! to be rewritten together: removing left recursion from one rule of a group
+//! substitutes the productions of the others into it, so the larger the
+//! group, the more the grammar grows. [`recursive_groups`] lists the groups
+//! and tells whether each recurses on the left, on the right, both, or only
+//! in between, skipping nullable symbols as [`left_recursion`] does.
+//!
+//! Groups are made of the rules of the grammar: the helper rules the BNF
+//! conversion adds pass references through, but do not count as recursive
+//! themselves, so the loop of `{ "+" term }` does not make `expr` right
+//! recursive.
+//!
+//! [`left_recursion`]: crate::left_recursion::left_recursion
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::recursion::{RecursionKind, recursive_groups};
+//!
+//! let bnf = Sebnf::parse_str("expr := expr \"+\" atom | atom.\natom := \"(\" expr \")\" | /[0-9]+/.", "g")
+//!     .unwrap()
+//!     .to_bnf();
+//! let groups = recursive_groups(&bnf);
+//! assert_eq!(groups.len(), 1);
+//! assert_eq!(groups[0].rules, ["expr", "atom"]);
+//! assert_eq!(groups[0].kind, RecursionKind::Left);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::converter::helper_kind;
+use crate::nullable::{Nullable, is_empty_regex, nullable_rules};
+
+/// Rules that each reach every other one of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecursiveGroup {
+    /// In grammar order
+    pub rules: Vec<String>,
+    /// How many productions the rules have together
+    pub productions: usize,
+    pub kind: RecursionKind,
+    /// The rules that can start with themselves
+    pub left: Vec<String>,
+    /// The rules that can end with themselves
+    pub right: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecursionKind {
+    /// Some rules can start with themselves, none end with themselves
+    Left,
+    /// Some rules can end with themselves, none start with themselves
+    Right,
+    /// Some rules can start and some end with themselves
+    Mixed,
+    /// The rules only recur with something on either side, e.g.
+    /// `a := "(" a ")"`
+    Nested,
+}
+
+impl fmt::Display for RecursionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RecursionKind::Left => "Left",
+            RecursionKind::Right => "Right",
+            RecursionKind::Mixed => "Mixed",
+            RecursionKind::Nested => "Nested",
+        })
+    }
+}
+
+impl fmt::Display for RecursiveGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} recursion: {} ({} rule(s), {} production(s))",
+            self.kind,
+            self.rules.join(", "),
+            self.rules.len(),
+            self.productions
+        )?;
+        if !self.left.is_empty() {
+            writeln!(f, "   left-recursive: {}", self.left.join(", "))?;
+        }
+        if !self.right.is_empty() {
+            writeln!(f, "   right-recursive: {}", self.right.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The groups of mutually recursive rules of `bnf`, including single rules
+/// that refer to themselves, ordered by their first rule.
+pub fn recursive_groups(bnf: &Bnf) -> Vec<RecursiveGroup> {
+    let nullable = nullable_rules(bnf);
+    let order: HashMap<&str, usize> = (bnf.rules.keys().enumerate())
+        .map(|(i, rule)| (rule.as_str(), i))
+        .collect();
+    // Every reference, and only those a rule can start or end with
+    let mut all = vec![Vec::new(); order.len()];
+    let mut left = vec![Vec::new(); order.len()];
+    let mut right = vec![Vec::new(); order.len()];
+    for (from, productions) in bnf.rules.values().enumerate() {
+        for production in productions {
+            for (position, item) in production.iter().enumerate() {
+                let Item::NonTerminal(nt) = item else {
+                    continue;
+                };
+                let Some(&to) = order.get(nt.as_str()) else {
+                    continue;
+                };
+                let skippable =
+                    |items: &[Item]| items.iter().all(|item| is_skippable(item, &nullable));
+                all[from].push(to);
+                if skippable(&production[..position]) {
+                    left[from].push(to);
+                }
+                if skippable(&production[position + 1..]) {
+                    right[from].push(to);
+                }
+            }
+        }
+    }
+    let on_cycle = |successors: &[Vec<usize>]| -> Vec<bool> {
+        let mut on_cycle = vec![false; successors.len()];
+        for component in components(successors) {
+            if component.len() > 1 || successors[component[0]].contains(&component[0]) {
+                for rule in component {
+                    on_cycle[rule] = true;
+                }
+            }
+        }
+        on_cycle
+    };
+    let recursive = on_cycle(&all);
+    let left = on_cycle(&left);
+    let right = on_cycle(&right);
+
+    let names: Vec<&String> = bnf.rules.keys().collect();
+    let mut groups: Vec<Vec<usize>> = (components(&all).into_iter())
+        .filter(|component| recursive[component[0]])
+        .map(|component| {
+            (component.into_iter())
+                .filter(|&rule| helper_kind(names[rule]).is_none())
+                .collect::<Vec<_>>()
+        })
+        .filter(|group| !group.is_empty())
+        .collect();
+    groups.sort();
+    (groups.into_iter())
+        .map(|group| {
+            let select = |pick: &[bool]| -> Vec<String> {
+                (group.iter())
+                    .filter(|&&rule| pick[rule])
+                    .map(|&rule| names[rule].clone())
+                    .collect()
+            };
+            let (left, right) = (select(&left), select(&right));
+            let kind = match (left.is_empty(), right.is_empty()) {
+                (false, true) => RecursionKind::Left,
+                (true, false) => RecursionKind::Right,
+                (false, false) => RecursionKind::Mixed,
+                (true, true) => RecursionKind::Nested,
+            };
+            RecursiveGroup {
+                rules: select(&recursive),
+                productions: group.iter().map(|&rule| bnf.rules[rule].len()).sum(),
+                kind,
+                left,
+                right,
+            }
+        })
+        .collect()
+}
+
+/// Whether `item` can match nothing
+fn is_skippable(item: &Item, nullable: &Nullable) -> bool {
+    match item {
+        Item::NonTerminal(nt) => nullable.is_nullable(nt),
+        item => is_empty_regex(item),
+    }
+}
+
+/// The strongly connected components of a graph given by the successors of
+/// each node, each sorted, by Tarjan's algorithm
+fn components(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct Tarjan<'a> {
+        successors: &'a [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+    impl Tarjan<'_> {
+        fn visit(&mut self, node: usize, next: &mut usize) {
+            self.index[node] = Some(*next);
+            self.low[node] = *next;
+            *next += 1;
+            self.stack.push(node);
+            self.on_stack[node] = true;
+            let successors = self.successors;
+            for &successor in &successors[node] {
+                match self.index[successor] {
+                    None => {
+                        self.visit(successor, next);
+                        self.low[node] = self.low[node].min(self.low[successor]);
+                    }
+                    Some(index) if self.on_stack[successor] => {
+                        self.low[node] = self.low[node].min(index);
+                    }
+                    Some(_) => {}
+                }
+            }
+            if self.index[node] == Some(self.low[node]) {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                component.sort();
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        successors,
+        index: vec![None; successors.len()],
+        low: vec![0; successors.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; successors.len()],
+        components: Vec::new(),
+    };
+    let mut next = 0;
+    for node in 0..successors.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.visit(node, &mut next);
+        }
+    }
+    tarjan.components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn classifies_each_group_of_mutually_recursive_rules() {
+        let source = "stmt := \"if\" cond stmt | expr \";\".\n\
+                      cond := \"(\" cond \")\" | expr.\n\
+                      expr := expr \"+\" atom | atom.\n\
+                      atom := /[0-9]+/ | \"-\" atom | \"(\" expr \")\".";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let text: Vec<_> = (recursive_groups(&bnf).iter())
+            .map(|group| group.to_string())
+            .collect();
+        assert_eq!(
+            text,
+            [
+                "Right recursion: stmt (1 rule(s), 2 production(s))\n   \
+                 right-recursive: stmt\n",
+                "Nested recursion: cond (1 rule(s), 2 production(s))\n",
+                "Mixed recursion: expr, atom (2 rule(s), 5 production(s))\n   \
+                 left-recursive: expr\n   \
+                 right-recursive: atom\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn repetitions_are_not_recursion() {
+        let source = "expr := term { \"+\" term }.\n\
+                      term := factor { \"*\" factor }.\n\
+                      factor := /[0-9]+/ | \"(\" expr \")\".\n\
+                      list := { \"x\" }.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let groups = recursive_groups(&bnf);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].rules, ["expr", "term", "factor"]);
+        assert_eq!(groups[0].kind, RecursionKind::Nested);
+    }
+}
@@ -1,7 +1,8 @@
-use std::collections::{HashMap, VecDeque};
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque, hash_map::Entry};
 
 use regex_automata::{
-    Anchored, Input,
+    Anchored, Input, MatchError,
     dfa::{
         Automaton,
         dense::{BuildError, DFA},
@@ -11,8 +12,10 @@ use regex_automata::{
 
 #[derive(Debug)]
 pub enum Error {
-    InvalidRegexA(BuildError),
-    InvalidRegexB(BuildError),
+    InvalidRegexA(Box<BuildError>),
+    InvalidRegexB(Box<BuildError>),
+    /// The DFA could not provide a start state (e.g. unsupported look-around).
+    StartState(MatchError),
 }
 
 impl std::fmt::Display for Error {
@@ -20,6 +23,7 @@ impl std::fmt::Display for Error {
         match self {
             Error::InvalidRegexA(e) => write!(f, "invalid regex pattern a: {e}"),
             Error::InvalidRegexB(e) => write!(f, "invalid regex pattern b: {e}"),
+            Error::StartState(e) => write!(f, "unable to start regex search: {e}"),
         }
     }
 }
@@ -28,10 +32,61 @@ impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::InvalidRegexA(e) | Error::InvalidRegexB(e) => Some(e),
+            Error::StartState(e) => Some(e),
         }
     }
 }
 
+type ProductState = (StateID, StateID);
+
+/// How much effort goes into the string two regexes both match.
+///
+/// The LL(1) check reads the options of the current thread, set with
+/// [`WitnessOptions::scope`], like the printed symbols.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WitnessOptions {
+    /// Longest witness to report, in bytes; a longer one is only said to
+    /// exist. The search still runs to the end to decide whether it does.
+    pub max_len: Option<usize>,
+    /// Only decide whether the regexes intersect, without a witness
+    pub skip: bool,
+}
+
+thread_local! {
+    static CURRENT: Cell<WitnessOptions> = const { Cell::new(WitnessOptions { max_len: None, skip: false }) };
+}
+
+impl WitnessOptions {
+    /// Runs `f` with these options as the current ones of this thread.
+    pub fn scope<R>(self, f: impl FnOnce() -> R) -> R {
+        struct Restore(WitnessOptions);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| current.set(self.0));
+            }
+        }
+
+        let _restore = Restore(CURRENT.with(|current| current.replace(self)));
+        f()
+    }
+
+    pub fn current() -> Self {
+        CURRENT.with(Cell::get)
+    }
+}
+
+/// What two regexes have in common.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Intersection {
+    Empty,
+    /// The shortest string both match
+    Witness(String),
+    /// Both match a string, but none of at most this many bytes
+    LongerThan(usize),
+    /// Both match a string; no witness was asked for
+    Exists,
+}
+
 /// Check if a regex pattern can match the empty string.
 ///
 /// Returns true if the pattern matches "", false otherwise.
@@ -41,7 +96,9 @@ pub fn regex_matches_empty(pattern: &str) -> bool {
         return false;
     };
     let input = Input::new(&[] as &[u8]).anchored(Anchored::Yes);
-    let start = dfa.start_state_forward(&input).unwrap();
+    let Ok(start) = dfa.start_state_forward(&input) else {
+        return false;
+    };
     let eoi = dfa.next_eoi_state(start);
     dfa.is_match_state(eoi)
 }
@@ -55,25 +112,52 @@ pub fn regex_matches_empty(pattern: &str) -> bool {
 ///
 /// Uses full-string match semantics.
 pub fn do_regexs_intersect(a: &str, b: &str) -> Result<Option<String>, Error> {
-    let dfa_a = DFA::new(a).map_err(Error::InvalidRegexA)?;
-    let dfa_b = DFA::new(b).map_err(Error::InvalidRegexB)?;
+    match regexs_intersection(a, b, WitnessOptions::default())? {
+        Intersection::Witness(witness) => Ok(Some(witness)),
+        Intersection::Empty => Ok(None),
+        Intersection::LongerThan(_) | Intersection::Exists => {
+            unreachable!("a witness of any length was asked for")
+        }
+    }
+}
+
+/// Like [`do_regexs_intersect`], with a limit on the witness length or none
+/// at all. The search is breadth first, so the first match found is the
+/// shortest, and one deeper than the limit ends it.
+pub fn regexs_intersection(
+    a: &str,
+    b: &str,
+    options: WitnessOptions,
+) -> Result<Intersection, Error> {
+    let dfa_a = DFA::new(a).map_err(|e| Error::InvalidRegexA(Box::new(e)))?;
+    let dfa_b = DFA::new(b).map_err(|e| Error::InvalidRegexB(Box::new(e)))?;
 
     let input = Input::new(&[] as &[u8]).anchored(Anchored::Yes);
-    let start_a = dfa_a.start_state_forward(&input).unwrap();
-    let start_b = dfa_b.start_state_forward(&input).unwrap();
+    let start_a = dfa_a
+        .start_state_forward(&input)
+        .map_err(Error::StartState)?;
+    let start_b = dfa_b
+        .start_state_forward(&input)
+        .map_err(Error::StartState)?;
 
     // BFS over the product automaton, tracking parent states for path reconstruction
-    let mut parent: HashMap<(StateID, StateID), Option<((StateID, StateID), u8)>> = HashMap::new();
-    let mut queue: VecDeque<(StateID, StateID)> = VecDeque::new();
+    // unless no witness is wanted
+    let mut parent: HashMap<ProductState, Option<(ProductState, u8)>> = HashMap::new();
+    let mut queue: VecDeque<(StateID, StateID, usize)> = VecDeque::new();
 
     parent.insert((start_a, start_b), None);
-    queue.push_back((start_a, start_b));
+    queue.push_back((start_a, start_b, 0));
 
-    while let Some((state_a, state_b)) = queue.pop_front() {
+    while let Some((state_a, state_b, depth)) = queue.pop_front() {
         let eoi_a = dfa_a.next_eoi_state(state_a);
         let eoi_b = dfa_b.next_eoi_state(state_b);
 
         if dfa_a.is_match_state(eoi_a) && dfa_b.is_match_state(eoi_b) {
+            match options.max_len {
+                _ if options.skip => return Ok(Intersection::Exists),
+                Some(max_len) if depth > max_len => return Ok(Intersection::LongerThan(max_len)),
+                _ => {}
+            }
             let mut bytes = Vec::new();
             let mut current = (state_a, state_b);
             while let Some(Some((prev, byte))) = parent.get(&current) {
@@ -81,7 +165,9 @@ pub fn do_regexs_intersect(a: &str, b: &str) -> Result<Option<String>, Error> {
                 current = *prev;
             }
             bytes.reverse();
-            return Ok(Some(String::from_utf8_lossy(&bytes).into_owned()));
+            return Ok(Intersection::Witness(
+                String::from_utf8_lossy(&bytes).into_owned(),
+            ));
         }
 
         for byte in 0u8..=255u8 {
@@ -92,16 +178,197 @@ pub fn do_regexs_intersect(a: &str, b: &str) -> Result<Option<String>, Error> {
                 continue;
             }
 
-            if !parent.contains_key(&(next_a, next_b)) {
-                parent.insert((next_a, next_b), Some(((state_a, state_b), byte)));
-                queue.push_back((next_a, next_b));
+            if let Entry::Vacant(entry) = parent.entry((next_a, next_b)) {
+                entry.insert((!options.skip).then_some(((state_a, state_b), byte)));
+                queue.push_back((next_a, next_b, depth + 1));
+            }
+        }
+    }
+
+    Ok(Intersection::Empty)
+}
+
+/// Find a short string, not the empty one, that `pattern` fully matches.
+///
+/// Returns Ok(None) if the pattern matches nothing but the empty string.
+/// Among the shortest matches, printable ASCII is preferred, so `[\s\S]`
+/// yields "!" rather than "\0". Errors are reported as `InvalidRegexA`.
+pub fn regex_witness(pattern: &str) -> Result<Option<String>, Error> {
+    let dfa = DFA::new(pattern).map_err(|e| Error::InvalidRegexA(Box::new(e)))?;
+    let input = Input::new(&[] as &[u8]).anchored(Anchored::Yes);
+    let start = dfa.start_state_forward(&input).map_err(Error::StartState)?;
+
+    let printable_first: Vec<u8> = (0x21..=0x7e)
+        .chain([b' '])
+        .chain((0u8..=255).filter(|b| !(0x20..=0x7e).contains(b)))
+        .collect();
+    // Paths of at least one byte: the start state is only a root, not a match
+    let mut parent: HashMap<StateID, (Option<StateID>, u8)> = HashMap::new();
+    let mut queue: VecDeque<StateID> = VecDeque::new();
+    // `from` is None for the start state, which is never part of a path
+    let expand = |state: StateID,
+                  from: Option<StateID>,
+                  parent: &mut HashMap<StateID, (Option<StateID>, u8)>,
+                  queue: &mut VecDeque<StateID>| {
+        for &byte in &printable_first {
+            let next = dfa.next_state(state, byte);
+            if dfa.is_dead_state(next) {
+                continue;
+            }
+            if let Entry::Vacant(entry) = parent.entry(next) {
+                entry.insert((from, byte));
+                queue.push_back(next);
+            }
+        }
+    };
+    expand(start, None, &mut parent, &mut queue);
+
+    while let Some(state) = queue.pop_front() {
+        if dfa.is_match_state(dfa.next_eoi_state(state)) {
+            let mut bytes = Vec::new();
+            let mut current = Some(state);
+            while let Some(state) = current {
+                let (prev, byte) = parent[&state];
+                bytes.push(byte);
+                current = prev;
             }
+            bytes.reverse();
+            return Ok(Some(String::from_utf8_lossy(&bytes).into_owned()));
         }
+        expand(state, Some(state), &mut parent, &mut queue);
     }
 
     Ok(None)
 }
 
+/// Draws random strings a regex fully matches, by a random walk over its DFA
+/// that only takes bytes from which a match can still be reached.
+pub struct RegexSampler {
+    dfa: DFA<Vec<u32>>,
+    start: StateID,
+    /// For each state that can reach a match, its live transitions
+    moves: HashMap<StateID, Vec<(u8, StateID)>>,
+    /// Fewest bytes from each state to a match
+    distance: HashMap<StateID, usize>,
+    /// Whether the regex matches a string other than the empty one
+    non_empty: bool,
+}
+
+impl RegexSampler {
+    /// Errors are reported as `InvalidRegexA`, like [`regex_witness`].
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        let dfa = DFA::new(pattern).map_err(|e| Error::InvalidRegexA(Box::new(e)))?;
+        let input = Input::new(&[] as &[u8]).anchored(Anchored::Yes);
+        let start = dfa.start_state_forward(&input).map_err(Error::StartState)?;
+
+        let mut moves: HashMap<StateID, Vec<(u8, StateID)>> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        moves.insert(start, Vec::new());
+        while let Some(state) = queue.pop_front() {
+            let mut live = Vec::new();
+            for byte in 0u8..=255u8 {
+                let next = dfa.next_state(state, byte);
+                if dfa.is_dead_state(next) {
+                    continue;
+                }
+                live.push((byte, next));
+                if let Entry::Vacant(entry) = moves.entry(next) {
+                    entry.insert(Vec::new());
+                    queue.push_back(next);
+                }
+            }
+            moves.insert(state, live);
+        }
+
+        // Breadth first from the matching states, against the transitions
+        let mut incoming: HashMap<StateID, Vec<StateID>> = HashMap::new();
+        for (&state, live) in &moves {
+            for &(_, next) in live {
+                incoming.entry(next).or_default().push(state);
+            }
+        }
+        let mut distance: HashMap<StateID, usize> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for &state in moves.keys() {
+            if dfa.is_match_state(dfa.next_eoi_state(state)) {
+                distance.insert(state, 0);
+                queue.push_back(state);
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            let further = distance[&state] + 1;
+            for &prev in incoming.get(&state).into_iter().flatten() {
+                if let Entry::Vacant(entry) = distance.entry(prev) {
+                    entry.insert(further);
+                    queue.push_back(prev);
+                }
+            }
+        }
+        moves.retain(|state, _| distance.contains_key(state));
+        for live in moves.values_mut() {
+            live.retain(|(_, next)| distance.contains_key(next));
+        }
+        let non_empty = moves.get(&start).is_some_and(|live| !live.is_empty());
+
+        Ok(RegexSampler {
+            dfa,
+            start,
+            moves,
+            distance,
+            non_empty,
+        })
+    }
+
+    /// Whether the regex matches any string at all.
+    pub fn matches_anything(&self) -> bool {
+        self.distance.contains_key(&self.start)
+    }
+
+    /// A random string the regex matches, not the empty one unless that is
+    /// all it matches, or `None` if it matches nothing. After `max_len`
+    /// bytes the walk heads for the nearest match. Printable ASCII is
+    /// preferred, but every byte the regex allows is sometimes chosen.
+    pub fn sample(&self, max_len: usize, mut random: impl FnMut() -> u64) -> Option<String> {
+        if !self.matches_anything() {
+            return None;
+        }
+        let mut bytes = Vec::new();
+        let mut state = self.start;
+        loop {
+            let can_stop = self.dfa.is_match_state(self.dfa.next_eoi_state(state))
+                && (!bytes.is_empty() || !self.non_empty);
+            let live = &self.moves[&state];
+            if live.is_empty()
+                || (can_stop && (bytes.len() >= max_len || random().is_multiple_of(4)))
+            {
+                break;
+            }
+            let candidates: Vec<&(u8, StateID)> = if bytes.len() >= max_len {
+                let nearest = live.iter().map(|(_, next)| self.distance[next]).min();
+                live.iter()
+                    .filter(|(_, next)| Some(self.distance[next]) == nearest)
+                    .collect()
+            } else {
+                live.iter().collect()
+            };
+            let printable: Vec<&(u8, StateID)> = candidates
+                .iter()
+                .copied()
+                .filter(|(byte, _)| (0x20..=0x7e).contains(byte))
+                .collect();
+            let pool = if printable.is_empty() || random().is_multiple_of(8) {
+                candidates
+            } else {
+                printable
+            };
+            let &(byte, next) = pool[(random() % pool.len() as u64) as usize];
+            bytes.push(byte);
+            state = next;
+        }
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,6 +468,46 @@ mod tests {
         assert!(matches!(result, Err(Error::InvalidRegexB(_))));
     }
 
+    #[test]
+    fn witness_length_is_limited_or_skipped() {
+        let options = WitnessOptions {
+            max_len: Some(3),
+            skip: false,
+        };
+        assert_eq!(
+            regexs_intersection("a{2,}", "a{2}", options).unwrap(),
+            Intersection::Witness("aa".to_string())
+        );
+        assert_eq!(
+            regexs_intersection("a{4,}", "a+", options).unwrap(),
+            Intersection::LongerThan(3)
+        );
+        assert_eq!(
+            regexs_intersection("a{4}", "b+", options).unwrap(),
+            Intersection::Empty
+        );
+
+        let skip = WitnessOptions {
+            max_len: None,
+            skip: true,
+        };
+        assert_eq!(
+            regexs_intersection("[a-z]+", "x", skip).unwrap(),
+            Intersection::Exists
+        );
+        assert_eq!(skip.scope(WitnessOptions::current), skip);
+        assert_eq!(WitnessOptions::current(), WitnessOptions::default());
+    }
+
+    #[test]
+    fn witness_is_short_printable_and_not_empty() {
+        assert_eq!(regex_witness("[0-9]+").unwrap().as_deref(), Some("0"));
+        assert_eq!(regex_witness("a*b?").unwrap().as_deref(), Some("a"));
+        assert_eq!(regex_witness("[\\s\\S]").unwrap().as_deref(), Some("!"));
+        assert_eq!(regex_witness("(foo|ba)r").unwrap().as_deref(), Some("bar"));
+        assert_eq!(regex_witness("").unwrap(), None);
+    }
+
     fn assert_intersects(a: &str, b: &str, expected: Option<&str>) {
         let result = do_regexs_intersect(a, b).unwrap();
         if let Some(exp) = expected {
@@ -611,4 +918,44 @@ mod tests {
         assert_intersects(r"[a-z]+\.(txt|pdf|doc)", r"report\.pdf", Some("report.pdf"));
         assert_intersects(r"[0-2][0-9]:[0-5][0-9]", r"12:\d{2}", Some("12:00"));
     }
+
+    #[test]
+    fn test_regex_sampler() {
+        let mut state = 1u64;
+        let mut random = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            state >> 33
+        };
+        for pattern in [
+            r"[a-z]+",
+            r"-?[0-9]+(\.[0-9]+)?",
+            r".",
+            r"a*",
+            r"x{20,}",
+            r"(ab|cd)*e",
+        ] {
+            let sampler = RegexSampler::new(pattern).unwrap();
+            let whole = regex_automata::meta::Regex::new(&format!("^(?:{pattern})$")).unwrap();
+            for _ in 0..50 {
+                let sample = sampler.sample(8, &mut random).unwrap();
+                assert!(
+                    whole.is_match(&sample),
+                    "{pattern} does not match {sample:?}"
+                );
+                assert!(!sample.is_empty());
+            }
+        }
+
+        // Only the empty string, and nothing at all
+        assert_eq!(
+            RegexSampler::new("").unwrap().sample(8, &mut random),
+            Some(String::new())
+        );
+        assert_eq!(
+            RegexSampler::new(r"[a&&b]").unwrap().sample(8, &mut random),
+            None
+        );
+    }
 }
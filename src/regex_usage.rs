@@ -0,0 +1,439 @@
+//! Finds regex terminals that only match a few fixed strings.
+//!
+//! Such regexes can be written as plain terminals or a choice of them, which
+//! avoids DFA-based conflict checking and lets the grammar be exported to
+//! targets without regex support. [`RegexToLiterals`] performs the rewrite.
+//! [`FoldLiterals`] only unifies a regex matching a single string with the
+//! terminal for that string, as a conversion pass.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+use crate::sebnf::{Item, Sebnf};
+use crate::sets::strip_regex_delimiters;
+use crate::transform::{ChangeLog, Transform, TransformError};
+use crate::visit::{Visitor, VisitorMut, walk_item_mut, walk_rule, walk_sebnf, walk_sebnf_mut};
+
+/// Regexes matching more strings than this are left alone
+pub const MAX_LITERALS: usize = 16;
+
+/// How a regex terminal can be expressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexKind {
+    /// The regex matches these strings (at most [`MAX_LITERALS`], sorted)
+    Literals(Vec<String>),
+    /// The regex needs to stay a regex
+    Irreducible(Reason),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    UnboundedRepetition,
+    TooManyStrings,
+    Assertion,
+    MatchesEmpty,
+    /// Terminals compare their spelling verbatim, so `"`, `\` and control
+    /// characters cannot be spelled
+    Unspellable,
+    Invalid(String),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::UnboundedRepetition => write!(f, "it repeats without an upper bound"),
+            Reason::TooManyStrings => write!(f, "it matches more than {} strings", MAX_LITERALS),
+            Reason::Assertion => write!(f, "it uses anchors or word boundaries"),
+            Reason::MatchesEmpty => write!(f, "it matches the empty string"),
+            Reason::Unspellable => write!(
+                f,
+                "it matches '\"', '\\' or control characters, which terminals cannot spell"
+            ),
+            Reason::Invalid(message) => write!(f, "it is invalid: {}", message),
+        }
+    }
+}
+
+/// One occurrence of a regex in the grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexUsage {
+    /// The regex with its delimiters, e.g. `/[0-9]+/`
+    pub regex: String,
+    pub rule: String,
+    pub span: Range<usize>,
+    pub kind: RegexKind,
+}
+
+impl RegexUsage {
+    /// The SEBNF that can replace the regex, if any
+    pub fn replacement(&self) -> Option<Item> {
+        let RegexKind::Literals(literals) = &self.kind else {
+            return None;
+        };
+        let terminal = |s: &String| Item::Terminal(format!("\"{}\"", s), self.span.clone());
+        Some(match literals.as_slice() {
+            [literal] => terminal(literal),
+            _ => Item::Choice(
+                literals.iter().map(|s| vec![terminal(s)]).collect(),
+                self.span.clone(),
+            ),
+        })
+    }
+}
+
+impl fmt::Display for RegexUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in '{}': ", self.regex, self.rule)?;
+        match (&self.kind, self.replacement()) {
+            (RegexKind::Literals(_), Some(replacement)) => write!(f, "can be {}", replacement),
+            (RegexKind::Irreducible(reason), _) => write!(f, "needs a regex, {}", reason),
+            (RegexKind::Literals(_), None) => unreachable!("literals have a replacement"),
+        }
+    }
+}
+
+/// Classifies every regex of the grammar, in rule order.
+pub fn regex_usage(sebnf: &Sebnf) -> Vec<RegexUsage> {
+    let mut collector = Collector {
+        rule: String::new(),
+        usages: Vec::new(),
+    };
+    walk_sebnf(&mut collector, sebnf);
+    collector.usages
+}
+
+/// Classifies a regex given with its delimiters.
+pub fn classify(regex: &str) -> RegexKind {
+    let hir = match regex_syntax::Parser::new().parse(strip_regex_delimiters(regex)) {
+        Ok(hir) => hir,
+        Err(e) => return RegexKind::Irreducible(Reason::Invalid(e.to_string())),
+    };
+    let literals = match strings(&hir) {
+        Ok(literals) => literals,
+        Err(reason) => return RegexKind::Irreducible(reason),
+    };
+    if literals.contains("") {
+        RegexKind::Irreducible(Reason::MatchesEmpty)
+    } else if literals
+        .iter()
+        .flat_map(|s| s.chars())
+        .any(|c| c == '"' || c == '\\' || c.is_control())
+    {
+        RegexKind::Irreducible(Reason::Unspellable)
+    } else {
+        RegexKind::Literals(literals.into_iter().collect())
+    }
+}
+
+/// Every string `hir` matches, if there are at most [`MAX_LITERALS`]
+fn strings(hir: &Hir) -> Result<BTreeSet<String>, Reason> {
+    let limited = |set: BTreeSet<String>| {
+        if set.len() > MAX_LITERALS {
+            Err(Reason::TooManyStrings)
+        } else {
+            Ok(set)
+        }
+    };
+    match hir.kind() {
+        HirKind::Empty => Ok(BTreeSet::from([String::new()])),
+        HirKind::Literal(literal) => match std::str::from_utf8(&literal.0) {
+            Ok(s) => Ok(BTreeSet::from([s.to_string()])),
+            Err(_) => Err(Reason::Unspellable),
+        },
+        HirKind::Class(Class::Unicode(class)) => {
+            let mut set = BTreeSet::new();
+            for range in class.ranges() {
+                if (range.end() as u32 - range.start() as u32) as usize >= MAX_LITERALS {
+                    return Err(Reason::TooManyStrings);
+                }
+                set.extend((range.start()..=range.end()).map(String::from));
+                set = limited(set)?;
+            }
+            Ok(set)
+        }
+        HirKind::Class(Class::Bytes(class)) => {
+            let mut set = BTreeSet::new();
+            for range in class.ranges() {
+                if !range.end().is_ascii() {
+                    return Err(Reason::Unspellable);
+                }
+                set.extend((range.start()..=range.end()).map(|b| char::from(b).to_string()));
+                set = limited(set)?;
+            }
+            Ok(set)
+        }
+        HirKind::Look(_) => Err(Reason::Assertion),
+        HirKind::Repetition(repetition) => {
+            let max = repetition.max.ok_or(Reason::UnboundedRepetition)?;
+            // Every count gives strings of a different length
+            if (max - repetition.min) as usize >= MAX_LITERALS {
+                return Err(Reason::TooManyStrings);
+            }
+            let sub = strings(&repetition.sub)?;
+            let mut set = BTreeSet::new();
+            let mut power = BTreeSet::from([String::new()]);
+            for count in 0..=max {
+                if count >= repetition.min {
+                    set.extend(power.iter().cloned());
+                    set = limited(set)?;
+                }
+                if count < max {
+                    power = limited(concat(&power, &sub)?)?;
+                }
+            }
+            Ok(set)
+        }
+        HirKind::Capture(capture) => strings(&capture.sub),
+        HirKind::Concat(subs) => subs
+            .iter()
+            .try_fold(BTreeSet::from([String::new()]), |set, sub| {
+                limited(concat(&set, &strings(sub)?)?)
+            }),
+        HirKind::Alternation(subs) => subs.iter().try_fold(BTreeSet::new(), |mut set, sub| {
+            set.extend(strings(sub)?);
+            limited(set)
+        }),
+    }
+}
+
+fn concat(
+    prefixes: &BTreeSet<String>,
+    suffixes: &BTreeSet<String>,
+) -> Result<BTreeSet<String>, Reason> {
+    if prefixes.len() * suffixes.len() > MAX_LITERALS {
+        return Err(Reason::TooManyStrings);
+    }
+    Ok(prefixes
+        .iter()
+        .flat_map(|p| suffixes.iter().map(move |s| format!("{}{}", p, s)))
+        .collect())
+}
+
+struct Collector {
+    rule: String,
+    usages: Vec<RegexUsage>,
+}
+
+impl Visitor for Collector {
+    fn visit_rule(&mut self, name: &str, alternatives: &[Vec<Item>]) {
+        self.rule = name.to_string();
+        walk_rule(self, name, alternatives);
+    }
+
+    fn visit_regex(&mut self, regex: &str, span: &Range<usize>) {
+        self.usages.push(RegexUsage {
+            regex: regex.to_string(),
+            rule: self.rule.clone(),
+            span: span.clone(),
+            kind: classify(regex),
+        });
+    }
+}
+
+/// Replaces every regex that matches only a few strings by a terminal or a
+/// choice of terminals.
+#[derive(Debug, Clone, Default)]
+pub struct RegexToLiterals;
+
+impl Transform<Sebnf> for RegexToLiterals {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        struct Rewrite<'a> {
+            rule: String,
+            log: &'a mut ChangeLog,
+        }
+        impl VisitorMut for Rewrite<'_> {
+            fn visit_rule_mut(&mut self, name: &str, alternatives: &mut Vec<Vec<Item>>) {
+                self.rule = name.to_string();
+                self.visit_alternatives_mut(alternatives);
+            }
+
+            fn visit_item_mut(&mut self, item: &mut Item) {
+                if let Item::Regex(regex, span) = item {
+                    let usage = RegexUsage {
+                        regex: regex.clone(),
+                        rule: self.rule.clone(),
+                        span: span.clone(),
+                        kind: classify(regex),
+                    };
+                    if let Some(replacement) = usage.replacement() {
+                        self.log.record(
+                            "regex-to-literals",
+                            format!("replaced {} in '{}' by {}", regex, self.rule, replacement),
+                        );
+                        *item = replacement;
+                    }
+                }
+                walk_item_mut(self, item);
+            }
+        }
+
+        let mut rewrite = Rewrite {
+            rule: String::new(),
+            log,
+        };
+        walk_sebnf_mut(&mut rewrite, sebnf);
+        Ok(())
+    }
+}
+
+/// How [`FoldLiterals`] spells a string written both ways.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LiteralForm {
+    /// `/while/` becomes `"while"`
+    #[default]
+    Terminal,
+    /// `"while"` becomes the first regex of the grammar that only matches
+    /// `while`; terminals without such a regex stay
+    Regex,
+}
+
+/// Gives every regex that matches exactly one string and the terminal of
+/// that string a single spelling, so they are one symbol in the sets and
+/// conflict reports.
+#[derive(Debug, Clone, Default)]
+pub struct FoldLiterals {
+    pub form: LiteralForm,
+}
+
+impl FoldLiterals {
+    pub fn new(form: LiteralForm) -> Self {
+        Self { form }
+    }
+}
+
+/// The terminal for the single string `regex` matches
+fn single_literal(regex: &str) -> Option<String> {
+    match classify(regex) {
+        RegexKind::Literals(literals) if literals.len() == 1 => {
+            Some(format!("\"{}\"", literals[0]))
+        }
+        _ => None,
+    }
+}
+
+impl Transform<Sebnf> for FoldLiterals {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        // Terminal spelling of each single-string regex, and the first regex
+        // for each such terminal
+        struct Literals(Vec<(String, String)>);
+        impl Visitor for Literals {
+            fn visit_regex(&mut self, regex: &str, _span: &Range<usize>) {
+                if !self.0.iter().any(|(r, _)| r == regex)
+                    && let Some(terminal) = single_literal(regex)
+                {
+                    self.0.push((regex.to_string(), terminal));
+                }
+            }
+        }
+        let mut literals = Literals(Vec::new());
+        walk_sebnf(&mut literals, sebnf);
+
+        struct Fold<'a> {
+            form: LiteralForm,
+            literals: &'a [(String, String)],
+            rule: String,
+            log: &'a mut ChangeLog,
+        }
+        impl VisitorMut for Fold<'_> {
+            fn visit_rule_mut(&mut self, name: &str, alternatives: &mut Vec<Vec<Item>>) {
+                self.rule = name.to_string();
+                self.visit_alternatives_mut(alternatives);
+            }
+
+            fn visit_item_mut(&mut self, item: &mut Item) {
+                let replacement = match (self.form, &*item) {
+                    (LiteralForm::Terminal, Item::Regex(regex, span)) => self
+                        .literals
+                        .iter()
+                        .find(|(r, _)| r == regex)
+                        .map(|(_, terminal)| Item::Terminal(terminal.clone(), span.clone())),
+                    (LiteralForm::Regex, Item::Terminal(terminal, span)) => self
+                        .literals
+                        .iter()
+                        .find(|(_, t)| t == terminal)
+                        .map(|(regex, _)| Item::Regex(regex.clone(), span.clone())),
+                    _ => None,
+                };
+                if let Some(replacement) = replacement {
+                    self.log.record(
+                        "fold-literals",
+                        format!("replaced {} in '{}' by {}", item, self.rule, replacement),
+                    );
+                    *item = replacement;
+                }
+                walk_item_mut(self, item);
+            }
+        }
+
+        let mut fold = Fold {
+            form: self.form,
+            literals: &literals.0,
+            rule: String::new(),
+            log,
+        };
+        walk_sebnf_mut(&mut fold, sebnf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::Engine;
+
+    #[test]
+    fn classifies_and_rewrites_regexes() {
+        let literals =
+            |strings: &[&str]| RegexKind::Literals(strings.iter().map(|s| s.to_string()).collect());
+        assert_eq!(classify("/true|false/"), literals(&["false", "true"]));
+        assert_eq!(classify("/[ab]c?/"), literals(&["a", "ac", "b", "bc"]));
+        assert_eq!(classify("/(?i)if/"), literals(&["IF", "If", "iF", "if"]));
+        assert_eq!(
+            classify("/[0-9]+/"),
+            RegexKind::Irreducible(Reason::UnboundedRepetition)
+        );
+        assert_eq!(
+            classify("/[a-z]/"),
+            RegexKind::Irreducible(Reason::TooManyStrings)
+        );
+        assert_eq!(
+            classify("/a?/"),
+            RegexKind::Irreducible(Reason::MatchesEmpty)
+        );
+
+        let sebnf = Sebnf::parse_str("a := /true|false/ /x/ /[0-9]+/.", "g").unwrap();
+        let (sebnf, log) = Engine::new().with(RegexToLiterals).run(&sebnf).unwrap();
+        assert_eq!(
+            sebnf.to_string(),
+            "a := ( \"false\" | \"true\" ) \"x\" /[0-9]+/.\n"
+        );
+        assert_eq!(log.changes.len(), 2);
+    }
+
+    #[test]
+    fn folds_single_string_regexes() {
+        let source = "a := /while/ \"while\" \"do\" /d(o)/ /[0-9]+/ /true|false/.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let (terminals, log) = Engine::new()
+            .with(FoldLiterals::new(LiteralForm::Terminal))
+            .run(&sebnf)
+            .unwrap();
+        assert_eq!(
+            terminals.to_string(),
+            "a := \"while\" \"while\" \"do\" \"do\" /[0-9]+/ /true|false/.\n"
+        );
+        assert_eq!(log.changes.len(), 2);
+
+        let (regexes, _) = Engine::new()
+            .with(FoldLiterals::new(LiteralForm::Regex))
+            .run(&sebnf)
+            .unwrap();
+        assert_eq!(
+            regexes.to_string(),
+            "a := /while/ /while/ /d(o)/ /d(o)/ /[0-9]+/ /true|false/.\n"
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use std::fmt::{self, Write};
+
+/// Error returned by the `render()` methods.
+///
+/// `Display` implementations cannot report failures to the caller of
+/// `to_string()` (it panics instead), so the library offers `render()` as a
+/// fallible alternative for long-running embedders.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum RenderError {
+    #[error("failed to format output")]
+    #[diagnostic(code(sebnf::render_error))]
+    Fmt(#[from] fmt::Error),
+}
+
+pub(crate) fn render_display(value: &impl fmt::Display) -> Result<String, RenderError> {
+    let mut out = String::new();
+    write!(out, "{}", value)?;
+    Ok(out)
+}
@@ -0,0 +1,256 @@
+//! Mechanical repairs of LL(1) conflicts.
+//!
+//! Two rewrites remove the most common causes of LL(1) conflicts without
+//! changing the language: [`LeftFactor`] for productions that start alike,
+//! and [`EliminateLeftRecursion`] for a rule that starts with itself.
+//! [`suggest_repairs`] tries each of them on a copy of the grammar for every
+//! rule with a conflict and reports whether it leaves any conflict in that
+//! rule or in the rules it added. [`apply_repairs`] applies the first repair
+//! that does not, rule by rule.
+//!
+//! Reordering alternatives is not tried: the FIRST and FOLLOW sets do not
+//! depend on the order, so it cannot resolve an LL(1) conflict.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::repair::{RepairKind, RepairOutcome, suggest_repairs};
+//!
+//! let bnf = Sebnf::parse_str("s := \"if\" \"x\" | \"if\" \"x\" \"else\" \"y\".", "g")
+//!     .unwrap()
+//!     .to_bnf();
+//! let rules = suggest_repairs(&bnf, false).unwrap();
+//! let repair = rules[0].resolving().unwrap();
+//! assert_eq!(repair.kind, RepairKind::LeftFactoring);
+//! assert_eq!(repair.outcome, RepairOutcome::Resolves);
+//! ```
+
+use std::fmt;
+
+use crate::bnf::Bnf;
+use crate::sets::{Ll1Conflict, Ll1ConflictKind, Ll1Error};
+use crate::transform::{ChangeLog, EliminateLeftRecursion, Engine, LeftFactor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairKind {
+    LeftFactoring,
+    LeftRecursionElimination,
+}
+
+impl RepairKind {
+    /// Every repair, in the order they are tried
+    pub const ALL: [RepairKind; 2] = [
+        RepairKind::LeftFactoring,
+        RepairKind::LeftRecursionElimination,
+    ];
+
+    fn engine(self, rule: &str) -> Engine<Bnf> {
+        match self {
+            RepairKind::LeftFactoring => Engine::new().with(LeftFactor::new(rule)),
+            RepairKind::LeftRecursionElimination => {
+                Engine::new().with(EliminateLeftRecursion::new(rule))
+            }
+        }
+    }
+}
+
+impl fmt::Display for RepairKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RepairKind::LeftFactoring => "left factoring",
+            RepairKind::LeftRecursionElimination => "left recursion elimination",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairOutcome {
+    /// The repair does not change the rule
+    NotApplicable,
+    /// No conflict is left in the rule or in the rules the repair added
+    Resolves,
+    /// How many conflicts are left in the rule and the rules it added
+    Remains(usize),
+}
+
+/// One repair tried on a rule.
+#[derive(Debug, Clone)]
+pub struct Repair {
+    pub kind: RepairKind,
+    pub outcome: RepairOutcome,
+    /// What the repair changed
+    pub changes: ChangeLog,
+}
+
+/// The repairs tried on one rule with conflicts.
+#[derive(Debug, Clone)]
+pub struct RuleRepairs {
+    pub rule: String,
+    pub conflicts: Vec<Ll1Conflict>,
+    /// In the order of [`RepairKind::ALL`]
+    pub repairs: Vec<Repair>,
+}
+
+impl RuleRepairs {
+    /// The first repair that resolves every conflict of the rule
+    pub fn resolving(&self) -> Option<&Repair> {
+        (self.repairs.iter()).find(|repair| repair.outcome == RepairOutcome::Resolves)
+    }
+}
+
+impl fmt::Display for RuleRepairs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "'{}' ({} conflict(s)):", self.rule, self.conflicts.len())?;
+        for conflict in &self.conflicts {
+            let (first, second) = conflict.production_indices;
+            let kind = match conflict.kind {
+                Ll1ConflictKind::FirstFirst { .. } => "FIRST/FIRST",
+                Ll1ConflictKind::FirstFollow { .. } => "FIRST/FOLLOW",
+            };
+            writeln!(
+                f,
+                "   {} conflict between productions {} and {}",
+                kind,
+                first + 1,
+                second + 1
+            )?;
+        }
+        for repair in &self.repairs {
+            match repair.outcome {
+                RepairOutcome::NotApplicable => writeln!(f, "   {}: not applicable", repair.kind)?,
+                RepairOutcome::Resolves => writeln!(f, "   {}: resolves them", repair.kind)?,
+                RepairOutcome::Remains(n) => {
+                    writeln!(f, "   {}: leaves {} conflict(s)", repair.kind, n)?
+                }
+            }
+            for change in &repair.changes.changes {
+                writeln!(f, "      {}", change.description)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tries every repair on every rule of `bnf` with an LL(1) conflict, each on
+/// its own copy of the grammar.
+pub fn suggest_repairs(
+    bnf: &Bnf,
+    ignore_regex_conflicts: bool,
+) -> Result<Vec<RuleRepairs>, Ll1Error> {
+    let mut rules: Vec<RuleRepairs> = Vec::new();
+    for conflict in bnf.is_ll1(ignore_regex_conflicts)?.conflicts {
+        match rules.last_mut() {
+            Some(rule) if rule.rule == conflict.non_terminal => rule.conflicts.push(conflict),
+            _ => rules.push(RuleRepairs {
+                rule: conflict.non_terminal.clone(),
+                conflicts: vec![conflict],
+                repairs: Vec::new(),
+            }),
+        }
+    }
+    for rule in &mut rules {
+        for kind in RepairKind::ALL {
+            let (repair, _) = try_repair(bnf, &rule.rule, kind, ignore_regex_conflicts)?;
+            rule.repairs.push(repair);
+        }
+    }
+    Ok(rules)
+}
+
+/// Applies, rule by rule, the first repair that resolves the rule's
+/// conflicts to `bnf`, and returns the repaired grammar with what changed.
+/// Rules no repair resolves are left as they are.
+pub fn apply_repairs(
+    bnf: &Bnf,
+    ignore_regex_conflicts: bool,
+) -> Result<(Bnf, ChangeLog), Ll1Error> {
+    let mut repaired = bnf.clone();
+    let mut log = ChangeLog::default();
+    for rule in suggest_repairs(bnf, ignore_regex_conflicts)? {
+        for kind in RepairKind::ALL {
+            let (repair, grammar) =
+                try_repair(&repaired, &rule.rule, kind, ignore_regex_conflicts)?;
+            if repair.outcome == RepairOutcome::Resolves {
+                log.changes.extend(repair.changes.changes);
+                repaired = grammar;
+                break;
+            }
+        }
+    }
+    Ok((repaired, log))
+}
+
+/// Applies one repair to a copy of `bnf`, returning it with the outcome.
+fn try_repair(
+    bnf: &Bnf,
+    rule: &str,
+    kind: RepairKind,
+    ignore_regex_conflicts: bool,
+) -> Result<(Repair, Bnf), Ll1Error> {
+    let not_applicable = Repair {
+        kind,
+        outcome: RepairOutcome::NotApplicable,
+        changes: ChangeLog::default(),
+    };
+    let Ok((repaired, changes)) = kind.engine(rule).run(bnf) else {
+        return Ok((not_applicable, bnf.clone()));
+    };
+    if changes.changes.is_empty() {
+        return Ok((not_applicable, repaired));
+    }
+    let involved = |nt: &str| nt == rule || !bnf.rules.contains_key(nt);
+    let left = (repaired.is_ll1(ignore_regex_conflicts)?.conflicts.iter())
+        .filter(|conflict| involved(&conflict.non_terminal))
+        .count();
+    let outcome = match left {
+        0 => RepairOutcome::Resolves,
+        n => RepairOutcome::Remains(n),
+    };
+    Ok((
+        Repair {
+            kind,
+            outcome,
+            changes,
+        },
+        repaired,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn picks_the_repair_that_resolves_each_rule() {
+        let source = "s := e \";\" | e \"!\".\ne := e \"+\" t | t.\nt := /[0-9]+/.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let rules = suggest_repairs(&bnf, false).unwrap();
+        let resolving: Vec<_> = (rules.iter())
+            .map(|rule| {
+                (
+                    rule.rule.as_str(),
+                    rule.resolving().map(|repair| repair.kind),
+                )
+            })
+            .collect();
+        assert_eq!(
+            resolving,
+            [
+                ("s", Some(RepairKind::LeftFactoring)),
+                ("e", Some(RepairKind::LeftRecursionElimination)),
+            ]
+        );
+
+        let (repaired, log) = apply_repairs(&bnf, false).unwrap();
+        assert_eq!(log.changes.len(), 2);
+        assert!(repaired.is_ll1(false).unwrap().is_ll1());
+        assert_eq!(
+            repaired.to_string(),
+            "s      := e s_tail.\n\
+             s_tail := \";\"\n        | \"!\"\n        .\n\
+             e      := t e_rest.\n\
+             e_rest := \"+\" t e_rest\n        | ε\n        .\n\
+             t      := /[0-9]+/.\n"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use sebnf_tool::bnf::Bnf;
+use sebnf_tool::sebnf::{ParseErrors, Sebnf};
+use sebnf_tool::sets::{self, Ll1Error, SetItem, Sets};
+use sebnf_tool::token_class::TokenClasses;
+
+const HELP: &str = "\
+Commands:
+  load <path>      Load a grammar file
+  reload           Re-read the current grammar file
+  rules            List the non-terminals of the BNF
+  bnf              Print the grammar converted to BNF
+  sets             Print all FIRST and FOLLOW sets
+  first <symbol>   Print the FIRST set of a non-terminal
+  follow <symbol>  Print the FOLLOW set of a non-terminal
+  is-ll1           Check if the grammar is LL(1)
+  help             Show this help
+  quit             Leave the REPL
+";
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum ReplError {
+    #[error("unknown command '{0}'")]
+    #[diagnostic(help("type 'help' for a list of commands"))]
+    UnknownCommand(String),
+    #[error("usage: {0}")]
+    Usage(&'static str),
+    #[error("no grammar loaded")]
+    #[diagnostic(help("load one with 'load <path>'"))]
+    NoGrammar,
+    #[error("unknown non-terminal '{0}'")]
+    UnknownSymbol(String),
+    #[error("failed to read '{}'", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Parse(#[from] ParseErrors),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Ll1(#[from] Ll1Error),
+}
+
+struct LoadedGrammar {
+    path: PathBuf,
+    bnf: Bnf,
+    sets: Sets,
+}
+
+/// Whether the REPL should keep reading commands.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+/// Interpreter state: the currently loaded grammar and its analyses.
+#[derive(Default)]
+pub struct Session {
+    grammar: Option<LoadedGrammar>,
+    token_classes: TokenClasses,
+    augment_start: bool,
+}
+
+impl Session {
+    /// A session that prepares every loaded grammar the same way the
+    /// non-interactive commands do.
+    pub fn new(token_classes: TokenClasses, augment_start: bool) -> Self {
+        Self {
+            grammar: None,
+            token_classes,
+            augment_start,
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<String, ReplError> {
+        let source = std::fs::read_to_string(path).map_err(|source| ReplError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let sebnf = Sebnf::parse_str(&source, &path.display().to_string())?;
+        let mut bnf = sebnf.to_bnf();
+        if self.augment_start {
+            bnf = bnf.augment_start();
+        }
+        let bnf = self.token_classes.apply(&bnf);
+        let sets = bnf.first_and_follow_sets();
+        let message = format!(
+            "Loaded {} ({} rules, {} BNF rules)\n",
+            path.display(),
+            sebnf.rules.len(),
+            bnf.rules.len()
+        );
+        self.grammar = Some(LoadedGrammar {
+            path: path.to_path_buf(),
+            bnf,
+            sets,
+        });
+        Ok(message)
+    }
+
+    fn grammar(&self) -> Result<&LoadedGrammar, ReplError> {
+        self.grammar.as_ref().ok_or(ReplError::NoGrammar)
+    }
+
+    /// Executes one command line, returning the text to print.
+    pub fn execute(&mut self, line: &str) -> Result<(String, Control), ReplError> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok((String::new(), Control::Continue));
+        };
+        let argument = words.next();
+
+        let output = match (command, argument) {
+            ("quit" | "exit", _) => return Ok((String::new(), Control::Quit)),
+            ("help", _) => HELP.to_string(),
+            ("load", Some(path)) => self.load(Path::new(path))?,
+            ("load", None) => return Err(ReplError::Usage("load <path>")),
+            ("reload", _) => {
+                let path = self.grammar()?.path.clone();
+                self.load(&path)?
+            }
+            ("rules", _) => {
+                let grammar = self.grammar()?;
+                grammar
+                    .bnf
+                    .rules
+                    .keys()
+                    .map(|k| format!("{}\n", k))
+                    .collect()
+            }
+            ("bnf", _) => self.grammar()?.bnf.to_string(),
+            ("sets", _) => self.grammar()?.sets.to_string(),
+            ("first" | "follow", None) => {
+                return Err(ReplError::Usage("first|follow <symbol>"));
+            }
+            ("first", Some(symbol)) => {
+                let grammar = self.grammar()?;
+                format_set(grammar.sets.first.get(symbol), symbol)?
+            }
+            ("follow", Some(symbol)) => {
+                let grammar = self.grammar()?;
+                format_set(grammar.sets.follow.get(symbol), symbol)?
+            }
+            ("is-ll1", _) => self.grammar()?.bnf.is_ll1(false)?.to_string(),
+            (other, _) => return Err(ReplError::UnknownCommand(other.to_string())),
+        };
+        Ok((output, Control::Continue))
+    }
+
+    /// Executes one command line, returning the text to print, errors
+    /// included, or `None` to quit.
+    pub fn respond(&mut self, line: &str) -> Option<String> {
+        match self.execute(line) {
+            Ok((_, Control::Quit)) => None,
+            result => Some(render(result.map(|(text, _)| text))),
+        }
+    }
+}
+
+fn format_set(
+    set: Option<&std::collections::HashSet<SetItem>>,
+    symbol: &str,
+) -> Result<String, ReplError> {
+    let set = set.ok_or_else(|| ReplError::UnknownSymbol(symbol.to_string()))?;
+    let items: Vec<_> = sets::sorted(set).iter().map(|i| i.to_string()).collect();
+    Ok(format!("{{ {} }}\n", items.join(", ")))
+}
+
+/// Runs the read-eval-print loop until `quit` or end of input.
+pub fn run(
+    mut session: Session,
+    input: impl BufRead,
+    mut output: impl Write,
+    prompt: bool,
+    initial: Option<&Path>,
+) -> io::Result<()> {
+    if let Some(path) = initial {
+        write!(output, "{}", render(session.load(path)))?;
+    }
+
+    let mut lines = input.lines();
+    loop {
+        if prompt {
+            write!(output, "sebnf> ")?;
+            output.flush()?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        match session.respond(&line?) {
+            Some(text) => write!(output, "{}", text)?,
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// The output of a command, or its error as a diagnostic
+fn render(result: Result<String, ReplError>) -> String {
+    match result {
+        Ok(text) => text,
+        Err(e) => format!("{:?}\n", miette::Report::new(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `input` to a session without a prompt, as from a pipe
+    fn transcript(input: &str, initial: Option<&Path>) -> String {
+        let mut output = Vec::new();
+        run(
+            Session::default(),
+            input.as_bytes(),
+            &mut output,
+            false,
+            initial,
+        )
+        .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn answers_queries_about_the_loaded_grammar() {
+        let path = std::env::temp_dir().join(format!("sebnf_repl_{}.sebnf", std::process::id()));
+        std::fs::write(
+            &path,
+            "list := \"[\" [ item { \",\" item } ] \"]\".\nitem := /[0-9]+/.",
+        )
+        .unwrap();
+
+        let mut session = Session::default();
+        let loaded = session
+            .respond(&format!("load {}", path.display()))
+            .unwrap();
+        assert!(loaded.starts_with("Loaded ") && loaded.contains("(2 rules, 4 BNF rules)"));
+        assert_eq!(session.respond("first list").unwrap(), "{ [ }\n");
+        assert_eq!(session.respond("follow item").unwrap(), "{ ,, ] }\n");
+        assert!(
+            session
+                .respond("first nope")
+                .unwrap()
+                .contains("unknown non-terminal 'nope'")
+        );
+        assert_eq!(session.respond(""), Some(String::new()));
+        assert_eq!(session.respond("quit"), None);
+
+        let output = transcript("first item\nis-ll1\nquit\nrules\n", Some(&path));
+        assert!(output.starts_with("Loaded "));
+        assert!(output.contains("{ /[0-9]+/ }\n"));
+        assert!(output.ends_with("Grammar is LL(1)\n"), "{}", output);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reports_errors_and_keeps_going() {
+        let output = transcript("frobnicate\nfirst x\nload\nhelp\n", None);
+        assert!(output.contains("unknown command 'frobnicate'"));
+        assert!(output.contains("no grammar loaded"));
+        assert!(output.contains("usage: load <path>"));
+        assert!(output.ends_with(HELP));
+    }
+}
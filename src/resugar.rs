@@ -0,0 +1,317 @@
+//! Re-sugaring of plain BNF into SEBNF, the reverse of the conversion.
+//!
+//! A rule shaped `X := body X | ε` (or `X := X body | ε`) is a repetition
+//! `{ body }`, and `X := body | ε` an option `[ body ]`. Rules of the grammar
+//! keep their names and only get their productions re-sugared. Generated
+//! helper rules (`___opt_N`, `___choice_N`, ...) are folded back into the
+//! items referencing them, so the output of `to-bnf` reads like the grammar
+//! it came from. The shapes the built-in idioms lower to come back as calls:
+//! `[ X { "," X } ]` as `sep_by(X, ",")`, `X { X }` as `non_empty(X)`, and a
+//! generated `___delimited_N` rule as `delimited(l, X, r)`.
+//!
+//! The language stays the same, but a left-recursive `X := X body | ε` no
+//! longer refers to itself as `{ body }`, so `FOLLOW(X)` may shrink.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use indexmap::IndexMap;
+
+use crate::bnf::{self, Bnf};
+use crate::converter::{EMPTY_RULE, helper_kind};
+use crate::sebnf::{Builtin, Item, Sebnf};
+
+/// Re-sugars `bnf`, keeping the rule order and the spans of its items.
+/// Token classes become references, end of input markers are dropped.
+pub fn resugar(bnf: &Bnf) -> Sebnf {
+    let resugar = Resugar {
+        bnf,
+        start: bnf.rules.keys().next().map(String::as_str),
+        shared_empty: bnf
+            .rules
+            .get(EMPTY_RULE)
+            .is_some_and(|productions| *productions == [vec![]]),
+    };
+    let mut rules = IndexMap::new();
+    for name in bnf.rules.keys() {
+        if !resugar.is_folded(name) {
+            let mut expanding = HashSet::from([name.as_str()]);
+            rules.insert(name.as_str(), resugar.rule(name, &mut expanding));
+        }
+    }
+
+    // Generated rules still referenced, e.g. from within themselves, stay
+    let mut pending: Vec<String> = Vec::new();
+    for item in rules.values().flatten().flatten() {
+        collect_references(item, &mut pending);
+    }
+    while let Some(name) = pending.pop() {
+        let Some((name, _)) = bnf.rules.get_key_value(&name) else {
+            continue;
+        };
+        if rules.contains_key(name.as_str()) {
+            continue;
+        }
+        let mut expanding = HashSet::from([name.as_str()]);
+        let alternatives = resugar.rule(name, &mut expanding);
+        for item in alternatives.iter().flatten() {
+            collect_references(item, &mut pending);
+        }
+        rules.insert(name.as_str(), alternatives);
+    }
+
+    let mut sebnf = Sebnf {
+        rules: IndexMap::new(),
+        rule_spans: IndexMap::new(),
+        annotations: IndexMap::new(),
+    };
+    for name in bnf.rules.keys() {
+        if let Some(alternatives) = rules.shift_remove(name.as_str()) {
+            sebnf.rules.insert(name.clone(), alternatives);
+        }
+    }
+    sebnf
+}
+
+/// How a rule reads in SEBNF
+enum Shape {
+    /// `{ body }`: the production and the range of its items without the
+    /// recursive reference
+    Repetition(usize, Range<usize>),
+    /// `[ body ]`: the production of the body
+    Optional(usize),
+    Plain,
+}
+
+struct Resugar<'a> {
+    bnf: &'a Bnf,
+    start: Option<&'a str>,
+    /// Whether `___empty := ε` stands for the empty production
+    shared_empty: bool,
+}
+
+impl<'a> Resugar<'a> {
+    /// Generated rules that are folded into their references
+    fn is_folded(&self, name: &str) -> bool {
+        Some(name) != self.start
+            && (helper_kind(name).is_some() || (self.shared_empty && name == EMPTY_RULE))
+    }
+
+    fn is_empty(&self, production: &[bnf::Item]) -> bool {
+        match production {
+            [] => true,
+            [bnf::Item::NonTerminal(name)] => self.shared_empty && name == EMPTY_RULE,
+            _ => false,
+        }
+    }
+
+    fn shape(&self, name: &str) -> Shape {
+        let refers_to = |items: &[bnf::Item]| {
+            items
+                .iter()
+                .any(|item| matches!(item, bnf::Item::NonTerminal(n) if n == name))
+        };
+        let productions = &self.bnf.rules[name];
+        let [first, second] = productions.as_slice() else {
+            return Shape::Plain;
+        };
+        let body = match (self.is_empty(first), self.is_empty(second)) {
+            (false, true) => 0,
+            (true, false) => 1,
+            _ => return Shape::Plain,
+        };
+        let production = &productions[body];
+        let len = production.len();
+        match production.as_slice() {
+            [rest @ .., bnf::Item::NonTerminal(n)] if n == name && !refers_to(rest) => {
+                Shape::Repetition(body, 0..len - 1)
+            }
+            [bnf::Item::NonTerminal(n), rest @ ..] if n == name && !refers_to(rest) => {
+                Shape::Repetition(body, 1..len)
+            }
+            production if !refers_to(production) => Shape::Optional(body),
+            _ => Shape::Plain,
+        }
+    }
+
+    /// The SEBNF productions of a rule
+    fn rule(&self, name: &'a str, expanding: &mut HashSet<&'a str>) -> Vec<Vec<Item>> {
+        match self.shape(name) {
+            Shape::Repetition(body, range) => {
+                let items = self.items(name, body, range, expanding);
+                let span = span_of(&items);
+                vec![vec![Item::AnyAmount(items, span)]]
+            }
+            Shape::Optional(body) => {
+                let len = self.bnf.rules[name][body].len();
+                let items = self.items(name, body, 0..len, expanding);
+                let span = span_of(&items);
+                vec![fold_idioms(vec![Item::Optional(items, span)])]
+            }
+            Shape::Plain => self.bnf.rules[name]
+                .iter()
+                .enumerate()
+                .map(|(index, production)| {
+                    if self.is_empty(production) {
+                        return Vec::new();
+                    }
+                    self.items(name, index, 0..production.len(), expanding)
+                })
+                .collect(),
+        }
+    }
+
+    /// The SEBNF items of a production, with references to generated rules
+    /// folded in, unless they are already being expanded
+    fn items(
+        &self,
+        name: &'a str,
+        production: usize,
+        range: Range<usize>,
+        expanding: &mut HashSet<&'a str>,
+    ) -> Vec<Item> {
+        let bnf = self.bnf;
+        let mut items = Vec::new();
+        for index in range {
+            let span = bnf.item_span(name, production, index).unwrap_or_default();
+            match &bnf.rules[name][production][index] {
+                bnf::Item::NonTerminal(reference) => {
+                    if self.is_folded(reference)
+                        && let Some((reference, _)) = bnf.rules.get_key_value(reference)
+                        && expanding.insert(reference)
+                    {
+                        let mut folded = self.rule(reference, expanding);
+                        expanding.remove(reference.as_str());
+                        if helper_kind(reference) == Some("delimited")
+                            && let [production] = folded.as_mut_slice()
+                            && production.len() >= 3
+                        {
+                            let close = production.pop().into_iter().collect();
+                            let open = vec![production.remove(0)];
+                            let args = vec![open, std::mem::take(production), close];
+                            items.push(Item::Builtin(Builtin::Delimited, args, span));
+                        } else {
+                            items.extend(fold(folded, span));
+                        }
+                    } else {
+                        items.push(Item::NonTerminal(reference.clone(), span));
+                    }
+                }
+                bnf::Item::Terminal(terminal) => items.push(Item::Terminal(terminal.clone(), span)),
+                bnf::Item::Regex(regex) => items.push(Item::Regex(regex.clone(), span)),
+                bnf::Item::TokenClass(class) => items.push(Item::NonTerminal(class.clone(), span)),
+                bnf::Item::EndOfInput => {}
+            }
+        }
+        fold_idioms(items)
+    }
+}
+
+/// Replaces the shapes built-in calls lower to by the calls: `[ X { d X } ]`
+/// by `sep_by(X, d)` and `X { X }` by `non_empty(X)`
+fn fold_idioms(items: Vec<Item>) -> Vec<Item> {
+    let mut folded: Vec<Item> = Vec::new();
+    for item in items {
+        let item = match item {
+            Item::Optional(body, span) => match sep_by_args(&body) {
+                Some(args) => Item::Builtin(Builtin::SepBy, args, span),
+                None => Item::Optional(body, span),
+            },
+            item => item,
+        };
+        if let Item::AnyAmount(body, span) = &item
+            && let Some(start) = folded.len().checked_sub(body.len())
+            && !body.is_empty()
+            && same(&folded[start..], body)
+        {
+            let element = folded.split_off(start);
+            let span = element[0].span().start..span.end;
+            folded.push(Item::Builtin(Builtin::NonEmpty, vec![element], span));
+            continue;
+        }
+        folded.push(item);
+    }
+    folded
+}
+
+/// The element and separator of `[ X { d X } ]`
+fn sep_by_args(body: &[Item]) -> Option<Vec<Vec<Item>>> {
+    let (Item::AnyAmount(repeated, _), element) = body.split_last()? else {
+        return None;
+    };
+    let separator = repeated.len().checked_sub(element.len())?;
+    if element.is_empty() || separator == 0 || !same(&repeated[separator..], element) {
+        return None;
+    }
+    Some(vec![element.to_vec(), repeated[..separator].to_vec()])
+}
+
+/// Whether two sequences are the same apart from their spans
+fn same(a: &[Item], b: &[Item]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.to_string() == b.to_string())
+}
+
+/// The items a folded rule with the given productions stands for
+fn fold(mut alternatives: Vec<Vec<Item>>, span: Range<usize>) -> Vec<Item> {
+    if alternatives.len() == 1 {
+        return alternatives.remove(0);
+    }
+    vec![Item::Choice(alternatives, span)]
+}
+
+fn span_of(items: &[Item]) -> Range<usize> {
+    let start = items.iter().map(|i| i.span().start).min().unwrap_or(0);
+    let end = items.iter().map(|i| i.span().end).max().unwrap_or(0);
+    start..end
+}
+
+fn collect_references(item: &Item, references: &mut Vec<String>) {
+    match item {
+        Item::NonTerminal(name, _) => references.push(name.clone()),
+        Item::Terminal(..) | Item::Regex(..) | Item::Epsilon(..) => {}
+        Item::Optional(items, _) | Item::AnyAmount(items, _) => {
+            items.iter().for_each(|i| collect_references(i, references))
+        }
+        Item::Labeled(_, inner, _) => collect_references(inner, references),
+        Item::Choice(alternatives, _) | Item::Builtin(_, alternatives, _) => alternatives
+            .iter()
+            .flatten()
+            .for_each(|i| collect_references(i, references)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resugars_converted_grammar() {
+        let source =
+            "a := \"x\" [ \"y\" ] { \"z\" [ \"w\" ] } b.\nb := ( \"p\" | \"q\" ) | [ b ].\n";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        assert_eq!(resugar(&sebnf.to_bnf()).to_string(), sebnf.to_string());
+
+        // Plain BNF with the same shapes, left recursion included
+        let source = "s := s \",\" t | .\nt := \"t\" | .\n";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        assert_eq!(
+            resugar(&bnf).to_string(),
+            "s := { \",\" t }.\nt := [ \"t\" ].\n"
+        );
+    }
+
+    #[test]
+    fn resugars_builtin_idioms() {
+        let source = "a := delimited(\"(\", sep_by(b, \",\"), \")\") non_empty(b).\nb := \"x\".\n";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        assert_eq!(resugar(&sebnf.to_bnf()).to_string(), sebnf.to_string());
+
+        // The same shapes written out
+        let source = "s := [ x { \";\" \";\" x } ] x { x } { x }.\nx := \"x\".\n";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        assert_eq!(
+            resugar(&bnf).to_string(),
+            "s := sep_by( x, \";\" \";\" ) non_empty( x ) { x }.\nx := \"x\".\n"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use miette::Diagnostic;
+use serde_json::{Value, json};
+
+use crate::bnf::{Bnf, Item};
+use crate::cycles::DerivationCycle;
+use crate::encoding::Decoded;
+use crate::lookahead::LlkConflict;
+use crate::lr::LrConflict;
+use crate::reachability::UnreachableRule;
+use crate::sebnf::Sebnf;
+use crate::sets::{Ll1Conflict, Ll1ConflictKind};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Note => "note",
+        }
+    }
+}
+
+/// Collects diagnostics for one grammar file and renders them as a SARIF 2.1.0 log.
+pub struct SarifLog<'a> {
+    artifact_uri: String,
+    source: &'a str,
+    /// The file `source` was decoded from, for byte offsets into it
+    file: Option<&'a Decoded>,
+    rules: BTreeMap<String, String>,
+    results: Vec<Value>,
+}
+
+impl<'a> SarifLog<'a> {
+    pub fn new(artifact_uri: impl Into<String>, source: &'a str) -> Self {
+        Self {
+            artifact_uri: artifact_uri.into(),
+            source,
+            file: None,
+            rules: BTreeMap::new(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Reports byte offsets into the file `source` was decoded from, which
+    /// differ from those into `source` unless it is UTF-8 without a BOM.
+    pub fn with_file(mut self, file: &'a Decoded) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// Adds a miette diagnostic, using its code as rule id and its labels as locations.
+    pub fn add_diagnostic(&mut self, diagnostic: &dyn Diagnostic, level: Level) {
+        let rule_id = diagnostic
+            .code()
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "sebnf::error".to_string());
+        let mut message = diagnostic.to_string();
+        if let Some(help) = diagnostic.help() {
+            message.push_str(&format!(" ({})", help));
+        }
+
+        let spans: Vec<Range<usize>> = diagnostic
+            .labels()
+            .into_iter()
+            .flatten()
+            .map(|label| label.offset()..label.offset() + label.len())
+            .collect();
+
+        self.push_result(&rule_id, &diagnostic.to_string(), level, message, &spans);
+    }
+
+    /// Adds an LL(1) conflict, anchored at `span` (usually the rule it belongs to).
+    pub fn add_ll1_conflict(&mut self, conflict: &Ll1Conflict, span: Option<Range<usize>>) {
+        let (rule_id, description) = match conflict.kind {
+            Ll1ConflictKind::FirstFirst { .. } => (
+                "sebnf::first_first_conflict",
+                "two productions have overlapping FIRST sets",
+            ),
+            Ll1ConflictKind::FirstFollow { .. } => (
+                "sebnf::first_follow_conflict",
+                "a nullable production's FOLLOW set overlaps another production's FIRST set",
+            ),
+        };
+        let message = conflict.to_string().trim_end().to_string();
+        let spans: Vec<_> = span.into_iter().collect();
+        self.push_result(rule_id, description, Level::Error, message, &spans);
+    }
+
+    pub fn add_llk_conflict(&mut self, conflict: &LlkConflict, span: Option<Range<usize>>) {
+        let message = conflict.to_string().trim_end().to_string();
+        let spans: Vec<_> = span.into_iter().collect();
+        self.push_result(
+            "sebnf::llk_conflict",
+            "two productions share a lookahead of k tokens",
+            Level::Error,
+            message,
+            &spans,
+        );
+    }
+
+    pub fn add_lr_conflict(&mut self, conflict: &LrConflict, span: Option<Range<usize>>) {
+        let message = conflict.to_string().trim_end().to_string();
+        let spans: Vec<_> = span.into_iter().collect();
+        self.push_result(
+            "sebnf::lr_conflict",
+            "a state of the LR automaton has two actions on one lookahead",
+            Level::Error,
+            message,
+            &spans,
+        );
+    }
+
+    /// A derivation cycle, located at the productions along it
+    pub fn add_derivation_cycle(&mut self, cycle: &DerivationCycle, bnf: &Bnf) {
+        let message = cycle.to_string().trim_end().to_string();
+        let spans: Vec<_> = (cycle.steps.iter())
+            .filter_map(|step| {
+                let production = (bnf.rules.get(&step.rule)?.iter())
+                    .position(|production| *production == step.production)?;
+                bnf.production_span(&step.rule, production)
+            })
+            .collect();
+        self.push_result(
+            "sebnf::derivation_cycle",
+            "a rule derives itself, so the grammar is ambiguous",
+            Level::Error,
+            message,
+            &spans,
+        );
+    }
+
+    /// A rule the start rule never reaches, located at its name
+    pub fn add_unreachable_rule(&mut self, rule: &UnreachableRule, span: Option<Range<usize>>) {
+        let spans: Vec<_> = span.into_iter().collect();
+        self.push_result(
+            "sebnf::unreachable_rule",
+            "the start rule never reaches this rule",
+            Level::Error,
+            rule.to_string(),
+            &spans,
+        );
+    }
+
+    fn push_result(
+        &mut self,
+        rule_id: &str,
+        description: &str,
+        level: Level,
+        message: String,
+        spans: &[Range<usize>],
+    ) {
+        self.rules
+            .entry(rule_id.to_string())
+            .or_insert_with(|| description.to_string());
+
+        let locations: Vec<Value> = spans
+            .iter()
+            .map(|span| {
+                json!({
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": self.artifact_uri },
+                        "region": self.region(span),
+                    }
+                })
+            })
+            .collect();
+
+        self.results.push(json!({
+            "ruleId": rule_id,
+            "level": level.as_str(),
+            "message": { "text": message },
+            "locations": locations,
+        }));
+    }
+
+    fn region(&self, span: &Range<usize>) -> Value {
+        let (start_line, start_column) = line_column(self.source, span.start);
+        let (end_line, end_column) = line_column(self.source, span.end);
+        let span = match self.file {
+            Some(file) => file.original_offset(span.start)..file.original_offset(span.end),
+            None => span.clone(),
+        };
+        json!({
+            "startLine": start_line,
+            "startColumn": start_column,
+            "endLine": end_line,
+            "endColumn": end_column,
+            "byteOffset": span.start,
+            "byteLength": span.len(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let rules: Vec<Value> = self
+            .rules
+            .iter()
+            .map(|(id, description)| {
+                json!({
+                    "id": id,
+                    "shortDescription": { "text": description },
+                })
+            })
+            .collect();
+
+        let log = json!({
+            "$schema": SARIF_SCHEMA,
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    }
+                },
+                "columnKind": "unicodeCodePoints",
+                "results": self.results,
+            }]
+        });
+        let mut out = serde_json::to_string_pretty(&log).unwrap_or_default();
+        out.push('\n');
+        out
+    }
+}
+
+/// 1-based line and column (in code points) of a byte offset.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = source[line_start..offset].chars().count() + 1;
+    (line, column)
+}
+
+/// Finds the span of the SEBNF rule a BNF non-terminal originates from.
+///
+/// Helper rules generated by the converter have no span of their own, so the
+/// lookup follows references back until it reaches a rule written by the user.
+pub fn rule_span(sebnf: &Sebnf, bnf: &Bnf, non_terminal: &str) -> Option<Range<usize>> {
+    let mut current = non_terminal;
+    let mut visited = vec![current];
+    loop {
+        if let Some(span) = sebnf.rule_spans.get(current) {
+            return Some(span.clone());
+        }
+        let (parent, _) = bnf.rules.iter().find(|(_, productions)| {
+            productions
+                .iter()
+                .flatten()
+                .any(|item| matches!(item, Item::NonTerminal(n) if n == current))
+        })?;
+        if visited.contains(&parent.as_str()) {
+            return None;
+        }
+        current = parent;
+        visited.push(current);
+    }
+}
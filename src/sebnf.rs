@@ -1,32 +1,31 @@
 #![allow(unused)]
 
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::ops::Range;
 
 use indexmap::IndexMap;
+use logos::Logos;
 use miette::{Diagnostic, NamedSource, SourceSpan};
 use thiserror::Error;
 
 use crate::bnf::Bnf;
-use crate::converter;
+use crate::converter::{self, LoweringOptions};
 use crate::lex::Token;
+use crate::render::{RenderError, render_display};
+use crate::sets::UnknownStart;
+use crate::symbols::Symbols;
+use crate::visit::{Visitor, walk_alternatives, walk_item, walk_sebnf};
 
 fn to_source_span(span: &Range<usize>) -> SourceSpan {
-    SourceSpan::new(span.start.into(), span.len().into())
+    SourceSpan::new(span.start.into(), span.len())
 }
 
 #[derive(Error, Debug, Diagnostic)]
 pub enum ParseError {
-    #[error("unexpected token")]
-    #[diagnostic(code(sebnf::unexpected_token))]
-    UnexpectedToken {
-        expected: String,
-        found: String,
-        #[source_code]
-        src: NamedSource<String>,
-        #[label("expected {expected}, found {found}")]
-        span: SourceSpan,
-    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnexpectedToken(Box<UnexpectedToken>),
 
     #[error("unexpected end of input, expected {expected}")]
     #[diagnostic(code(sebnf::unexpected_eof))]
@@ -60,6 +59,10 @@ pub enum ParseError {
         span: SourceSpan,
     },
 
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    BuiltinArity(Box<BuiltinArity>),
+
     #[error("undefined non-terminal '{name}'")]
     #[diagnostic(
         code(sebnf::undefined_nonterminal),
@@ -72,21 +75,455 @@ pub enum ParseError {
         #[label("'{name}' is referenced here but has no rule")]
         span: SourceSpan,
     },
+
+    #[error("unknown annotation '@{name}'")]
+    #[diagnostic(
+        code(sebnf::unknown_annotation),
+        help("the supported annotations are @max_depth(N) and @soft_keywords(\"kw\", ...)")
+    )]
+    UnknownAnnotation {
+        name: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("not a known annotation")]
+        span: SourceSpan,
+    },
+
+    #[error("'@{name}' takes a positive whole number")]
+    #[diagnostic(code(sebnf::invalid_annotation_argument))]
+    InvalidAnnotationArgument {
+        name: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("expected a number of at least 1")]
+        span: SourceSpan,
+    },
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    DuplicateLabel(Box<DuplicateLabel>),
+
+    #[error("an item can only have one label")]
+    #[diagnostic(code(sebnf::nested_label))]
+    NestedLabel {
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("labeled again here")]
+        span: SourceSpan,
+    },
+
+    #[error("'@soft_keywords' takes terminals, such as @soft_keywords(\"async\", \"await\")")]
+    #[diagnostic(code(sebnf::invalid_soft_keyword))]
+    InvalidSoftKeyword {
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("expected a terminal")]
+        span: SourceSpan,
+    },
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    ConflictingRule(Box<ConflictingRule>),
+
+    #[error("'@{name}' is given twice for the same rule")]
+    #[diagnostic(code(sebnf::duplicate_annotation))]
+    DuplicateAnnotation {
+        name: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("repeated here")]
+        span: SourceSpan,
+    },
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    UnknownStart(#[from] UnknownStart),
+}
+
+/// A token other than the one the parser expects, boxed in
+/// [`ParseError::UnexpectedToken`] to keep the error small.
+#[derive(Error, Debug, Diagnostic)]
+#[error("unexpected token")]
+#[diagnostic(code(sebnf::unexpected_token))]
+pub struct UnexpectedToken {
+    pub expected: String,
+    pub found: String,
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("expected {expected}, found {found}")]
+    pub span: SourceSpan,
 }
 
+/// A built-in idiom called with the wrong number of arguments, boxed in
+/// [`ParseError::BuiltinArity`] to keep the error small.
+#[derive(Error, Debug, Diagnostic)]
+#[error("'{name}' takes {expected} argument(s), found {found}")]
+#[diagnostic(code(sebnf::builtin_arity))]
+pub struct BuiltinArity {
+    pub name: String,
+    pub expected: usize,
+    pub found: usize,
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("called here")]
+    pub span: SourceSpan,
+}
+
+/// A label used twice in one sequence, boxed in
+/// [`ParseError::DuplicateLabel`] to keep the error small.
+#[derive(Error, Debug, Diagnostic)]
+#[error("label '{label}' is used twice in the same sequence")]
+#[diagnostic(
+    code(sebnf::duplicate_label),
+    help("labels name the fields of a sequence, so they must be distinct")
+)]
+pub struct DuplicateLabel {
+    pub label: String,
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("'{label}' is already used here")]
+    pub first: SourceSpan,
+    #[label("and again here")]
+    pub span: SourceSpan,
+}
+
+/// A rule defined differently in two files, boxed in
+/// [`ParseError::ConflictingRule`] to keep the error small.
+#[derive(Error, Debug, Diagnostic)]
+#[error("rule '{name}' is already defined differently in {first_file}")]
+#[diagnostic(
+    code(sebnf::conflicting_rule),
+    help("rename one of the rules, or give both the same definition")
+)]
+pub struct ConflictingRule {
+    pub name: String,
+    pub first_file: String,
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("defined again here")]
+    pub span: SourceSpan,
+}
+
+/// Every error found in a grammar, in source order.
+///
+/// Renders like its first error, with the remaining ones attached as related
+/// diagnostics, so a single error looks exactly like a plain `ParseError`.
 #[derive(Debug)]
+pub struct ParseErrors {
+    pub errors: Vec<ParseError>,
+}
+
+impl From<ParseError> for ParseErrors {
+    fn from(error: ParseError) -> Self {
+        Self {
+            errors: vec![error],
+        }
+    }
+}
+
+impl ParseErrors {
+    fn first(&self) -> Option<&ParseError> {
+        self.errors.first()
+    }
+}
+
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.first() {
+            Some(first) => write!(f, "{}", first),
+            None => write!(f, "invalid grammar"),
+        }
+    }
+}
+
+impl std::error::Error for ParseErrors {}
+
+impl Diagnostic for ParseErrors {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.first()?.code()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.first()?.help()
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        self.first()?.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        self.first()?.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        if self.errors.len() < 2 {
+            return None;
+        }
+        Some(Box::new(
+            self.errors[1..].iter().map(|e| e as &dyn Diagnostic),
+        ))
+    }
+}
+
+/// Suspicious but valid constructs, reported without failing the command.
+#[derive(Error, Debug, Diagnostic)]
+pub enum GrammarWarning {
+    #[error("start symbol '{name}' is referenced inside the grammar")]
+    #[diagnostic(
+        code(sebnf::referenced_start_symbol),
+        severity(Warning),
+        help(
+            "FOLLOW({name}) mixes end of input with the symbols following its references; \
+             use --augment-start to analyze with a fresh start rule `___start := {name} $`"
+        )
+    )]
+    ReferencedStartSymbol {
+        name: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("'{name}' is referenced here")]
+        span: SourceSpan,
+    },
+
+    #[error("'{name}' never contains itself, so @max_depth({limit}) is never reached")]
+    #[diagnostic(
+        code(sebnf::unreachable_depth_limit),
+        severity(Warning),
+        help("@max_depth only limits how deeply a recursive rule nests within itself")
+    )]
+    UnreachableDepthLimit {
+        name: String,
+        limit: usize,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("'{name}' is not recursive")]
+        span: SourceSpan,
+    },
+
+    #[error("'{rule}' has the same alternative twice")]
+    #[diagnostic(
+        code(sebnf::repeated_alternative),
+        severity(Warning),
+        help("the copy only makes '{rule}' conflict with itself; `dedupe` removes it")
+    )]
+    RepeatedAlternative {
+        rule: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("first written here")]
+        first: SourceSpan,
+        #[label("and again here")]
+        span: SourceSpan,
+    },
+
+    #[error("'{rule}' has an empty alternative")]
+    #[diagnostic(
+        code(sebnf::empty_alternative),
+        severity(Warning),
+        help("write ε if it is meant to match nothing; otherwise remove the '|'")
+    )]
+    EmptyAlternative {
+        rule: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("nothing is written next to this '|'")]
+        span: SourceSpan,
+    },
+
+    #[error("alternative {number} of '{rule}' can never be chosen")]
+    #[diagnostic(
+        code(sebnf::dead_alternative),
+        severity(Warning),
+        help("'{through}' derives no string of terminals; `prune` removes the alternative")
+    )]
+    DeadAlternative {
+        rule: String,
+        /// 1-based, as written
+        number: usize,
+        through: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("uses '{through}'")]
+        span: SourceSpan,
+    },
+
+    #[error("'{name}' has the same alternatives as '{other}'")]
+    #[diagnostic(
+        code(sebnf::identical_rules),
+        severity(Warning),
+        help("`dedupe` replaces '{name}' by '{other}'")
+    )]
+    IdenticalRules {
+        name: String,
+        other: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("a copy of '{other}'")]
+        span: SourceSpan,
+    },
+
+    #[error("soft keyword {keyword} is not used in '{rule}'")]
+    #[diagnostic(
+        code(sebnf::unused_soft_keyword),
+        severity(Warning),
+        help("@soft_keywords only applies to the terminals of its own rule")
+    )]
+    UnusedSoftKeyword {
+        keyword: String,
+        rule: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("declared here")]
+        span: SourceSpan,
+    },
+
+    #[error("{keyword} is a soft keyword in '{soft_rule}' but reserved in '{rule}'")]
+    #[diagnostic(
+        code(sebnf::reserved_soft_keyword),
+        severity(Warning),
+        help(
+            "a lexer reads a word the same way everywhere; add {keyword} to @soft_keywords of '{rule}' too"
+        )
+    )]
+    ReservedSoftKeyword {
+        keyword: String,
+        soft_rule: String,
+        rule: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("a reserved keyword here")]
+        span: SourceSpan,
+    },
+
+    #[error("'{name}(' calls the built-in {name}, not the rule '{name}'")]
+    #[diagnostic(
+        code(sebnf::shadowed_builtin),
+        severity(Warning),
+        help("write `{name} (...)` with a space to use the rule, or rename the rule")
+    )]
+    ShadowedBuiltin {
+        name: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("calls the built-in")]
+        span: SourceSpan,
+        /// `None` if the rule is defined in another file
+        #[label("the rule is defined here")]
+        rule: Option<SourceSpan>,
+    },
+}
+
+/// A parsed SEBNF grammar, as written by the user.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sebnf {
     pub rules: IndexMap<String, Vec<Vec<Item>>>,
+    /// Span of each rule's defining non-terminal
+    pub rule_spans: IndexMap<String, Range<usize>>,
+    /// Annotations of the rules that have any
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub annotations: IndexMap<String, RuleAnnotations>,
 }
 
-#[derive(Debug)]
+/// Annotations written before a rule, such as `@max_depth(5)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuleAnnotations {
+    /// `@max_depth(N)`: in generated sentences, the rule occurs at most `N`
+    /// times nested within itself
+    pub max_depth: Option<usize>,
+    /// `@soft_keywords("async", ...)`: terminals that are keywords in this
+    /// rule only, and lexed as identifiers. Kept with their quotes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub soft_keywords: Vec<String>,
+    /// Span from the first to the last annotation
+    pub span: Range<usize>,
+}
+
+/// An item of a rule. The last field of every variant is its span in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Item {
     NonTerminal(String, Range<usize>),
-    Terminal(String),
-    Regex(String),
-    Optional(Vec<Item>),
-    AnyAmount(Vec<Item>),
-    Choice(Vec<Vec<Item>>),
+    Terminal(String, Range<usize>),
+    Regex(String, Range<usize>),
+    Optional(Vec<Item>, Range<usize>),
+    AnyAmount(Vec<Item>, Range<usize>),
+    Choice(Vec<Vec<Item>>, Range<usize>),
+    /// Call of a built-in idiom such as `sep_by(X, ",")`, one sequence per argument
+    Builtin(Builtin, Vec<Vec<Item>>, Range<usize>),
+    /// An item with a label such as `args:{ "," expr }`, naming it for code
+    /// generators; the conversion to BNF drops labels
+    Labeled(String, Box<Item>, Range<usize>),
+    /// `ε`, `EPSILON` or `()`: matches nothing, to write an empty
+    /// alternative out instead of leaving it blank
+    Epsilon(Range<usize>),
+}
+
+impl Item {
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Item::NonTerminal(_, span)
+            | Item::Terminal(_, span)
+            | Item::Regex(_, span)
+            | Item::Optional(_, span)
+            | Item::AnyAmount(_, span)
+            | Item::Choice(_, span)
+            | Item::Builtin(_, _, span)
+            | Item::Labeled(_, _, span)
+            | Item::Epsilon(span) => span.clone(),
+        }
+    }
+
+    /// The label of a labeled item
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Item::Labeled(label, _, _) => Some(label),
+            _ => None,
+        }
+    }
+}
+
+/// Parametric idioms expanded by the converter.
+///
+/// A call is only recognized when `(` directly follows the name, so
+/// `sep_by (X)` still refers to a rule called `sep_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Builtin {
+    /// `sep_by(X, d)`: zero or more `X` separated by `d`
+    SepBy,
+    /// `delimited(l, X, r)`: `X` enclosed in `l` and `r`
+    Delimited,
+    /// `non_empty(X)`: one or more `X`
+    NonEmpty,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sep_by" => Some(Builtin::SepBy),
+            "delimited" => Some(Builtin::Delimited),
+            "non_empty" => Some(Builtin::NonEmpty),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::SepBy => "sep_by",
+            Builtin::Delimited => "delimited",
+            Builtin::NonEmpty => "non_empty",
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            Builtin::SepBy => 2,
+            Builtin::Delimited => 3,
+            Builtin::NonEmpty => 1,
+        }
+    }
 }
 
 struct Parser {
@@ -119,6 +556,14 @@ impl Parser {
         }
     }
 
+    /// End of the most recently consumed token.
+    fn previous_end(&self) -> usize {
+        self.pos
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map_or(0, |(_, span)| span.end)
+    }
+
     fn peek(&self) -> Option<&Token> {
         self.tokens.get(self.pos).map(|(t, _)| t)
     }
@@ -129,18 +574,20 @@ impl Parser {
         item.map(|(t, s)| (t, s))
     }
 
+    /// Consumes the next token if it is `expected`. A mismatching token is
+    /// left in place, so error recovery can resynchronize on it.
     fn expect(&mut self, expected: &Token) -> Result<(), ParseError> {
-        match self.advance() {
-            Some((tok, _)) if tok == expected => Ok(()),
-            Some((tok, span)) => {
-                let span = span.clone();
-                Err(ParseError::UnexpectedToken {
-                    expected: expected.to_string(),
-                    found: tok.to_string(),
-                    src: self.named_source(),
-                    span: to_source_span(&span),
-                })
+        match self.tokens.get(self.pos) {
+            Some((tok, _)) if tok == expected => {
+                self.pos += 1;
+                Ok(())
             }
+            Some((tok, span)) => Err(ParseError::UnexpectedToken(Box::new(UnexpectedToken {
+                expected: expected.to_string(),
+                found: tok.to_string(),
+                src: self.named_source(),
+                span: to_source_span(span),
+            }))),
             None => Err(ParseError::UnexpectedEof {
                 expected: expected.to_string(),
                 src: self.named_source(),
@@ -155,17 +602,151 @@ impl Parser {
         }
     }
 
-    fn parse_grammar(&mut self) -> Result<Sebnf, ParseError> {
+    /// True if the next tokens are `name :=` or an annotation, the start of
+    /// a new rule.
+    fn at_rule_start(&self) -> bool {
+        matches!(
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1)),
+            (Some((Token::NonTerminal(_), _)), Some((Token::Assign, _)))
+                | (Some((Token::Annotation(_), _)), _)
+        )
+    }
+
+    /// Skips to just after the next `.` or to the start of the next rule.
+    fn recover(&mut self) {
+        while let Some(tok) = self.peek() {
+            if *tok == Token::Dot {
+                self.advance();
+                return;
+            }
+            if self.at_rule_start() {
+                return;
+            }
+            self.advance();
+        }
+    }
+
+    /// Parses all rules, recovering after each broken rule so that every
+    /// syntax error is reported.
+    fn parse_grammar(&mut self) -> (Sebnf, Vec<ParseError>) {
         let mut rules = IndexMap::new();
+        let mut rule_spans = IndexMap::new();
+        let mut annotations = IndexMap::new();
+        let mut errors = Vec::new();
 
         self.skip_newlines();
         while self.peek().is_some() {
-            let (name, alts) = self.parse_rule()?;
-            rules.insert(name, alts);
+            let start = self.pos;
+            let rule = self.parse_annotations().and_then(|rule_annotations| {
+                let name_span = self.current_span();
+                let (name, alts) = self.parse_rule()?;
+                Ok((name, name_span, alts, rule_annotations))
+            });
+            match rule {
+                Ok((name, name_span, alts, rule_annotations)) => {
+                    if let Some(rule_annotations) = rule_annotations {
+                        annotations.insert(name.clone(), rule_annotations);
+                    }
+                    rule_spans.insert(name.clone(), name_span);
+                    rules.insert(name, alts);
+                }
+                Err(e) => {
+                    errors.push(e);
+                    // Always make progress, even if the rule start itself was bad
+                    if self.pos == start {
+                        self.advance();
+                    }
+                    self.recover();
+                }
+            }
             self.skip_newlines();
         }
 
-        Ok(Sebnf { rules })
+        let sebnf = Sebnf {
+            rules,
+            rule_spans,
+            annotations,
+        };
+        (sebnf, errors)
+    }
+
+    /// Parses the annotations before a rule, if any.
+    fn parse_annotations(&mut self) -> Result<Option<RuleAnnotations>, ParseError> {
+        let mut annotations: Option<RuleAnnotations> = None;
+        while let Some(Token::Annotation(name)) = self.peek() {
+            let name = name.clone();
+            let start = self.current_span().start;
+            if name != "max_depth" && name != "soft_keywords" {
+                return Err(ParseError::UnknownAnnotation {
+                    name,
+                    src: self.named_source(),
+                    span: to_source_span(&self.current_span()),
+                });
+            }
+            self.advance();
+            self.expect(&Token::BracketRoundOpen)?;
+            let annotations = annotations.get_or_insert_with(|| RuleAnnotations {
+                span: start..start,
+                ..RuleAnnotations::default()
+            });
+            let repeated = if name == "max_depth" {
+                let limit = match self.peek() {
+                    Some(Token::NonTerminal(n)) => n.parse::<usize>().ok().filter(|n| *n > 0),
+                    _ => None,
+                };
+                let Some(limit) = limit else {
+                    return Err(ParseError::InvalidAnnotationArgument {
+                        name,
+                        src: self.named_source(),
+                        span: to_source_span(&self.current_span()),
+                    });
+                };
+                self.advance();
+                annotations.max_depth.replace(limit).is_some()
+            } else {
+                let keywords = self.parse_soft_keywords()?;
+                let repeated = !annotations.soft_keywords.is_empty();
+                annotations.soft_keywords = keywords;
+                repeated
+            };
+            self.expect(&Token::BracketRoundClose)?;
+            let span = start..self.previous_end();
+
+            if repeated {
+                return Err(ParseError::DuplicateAnnotation {
+                    name,
+                    src: self.named_source(),
+                    span: to_source_span(&span),
+                });
+            }
+            annotations.span.end = span.end;
+            self.skip_newlines();
+        }
+        Ok(annotations)
+    }
+
+    /// Parses the terminals of `@soft_keywords(...)`, up to the `)`.
+    fn parse_soft_keywords(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut keywords = Vec::new();
+        loop {
+            self.skip_newlines();
+            let Some(Token::Terminal(keyword)) = self.peek() else {
+                return Err(ParseError::InvalidSoftKeyword {
+                    src: self.named_source(),
+                    span: to_source_span(&self.current_span()),
+                });
+            };
+            if !keywords.contains(keyword) {
+                keywords.push(keyword.clone());
+            }
+            self.advance();
+            self.skip_newlines();
+            if let Some(Token::Comma) = self.peek() {
+                self.advance();
+            } else {
+                return Ok(keywords);
+            }
+        }
     }
 
     fn parse_rule(&mut self) -> Result<(String, Vec<Vec<Item>>), ParseError> {
@@ -218,11 +799,28 @@ impl Parser {
         let mut items = Vec::new();
         loop {
             self.skip_newlines();
+            // A missing '.' must not swallow the next rule's name
+            if self.at_rule_start() {
+                break;
+            }
             match self.parse_item()? {
                 Some(item) => items.push(item),
                 None => break,
             }
         }
+        for (i, item) in items.iter().enumerate() {
+            let Some(label) = item.label() else {
+                continue;
+            };
+            if let Some(first) = items[..i].iter().find(|other| other.label() == Some(label)) {
+                return Err(ParseError::DuplicateLabel(Box::new(DuplicateLabel {
+                    label: label.to_string(),
+                    src: self.named_source(),
+                    first: to_source_span(&first.span()),
+                    span: to_source_span(&item.span()),
+                })));
+            }
+        }
         Ok(items)
     }
 
@@ -232,160 +830,794 @@ impl Parser {
         };
 
         match tok {
-            Token::NonTerminal(_) => {
-                if let Some((Token::NonTerminal(s), span)) = self.advance() {
-                    Ok(Some(Item::NonTerminal(s.clone(), span.clone())))
-                } else {
-                    unreachable!()
+            Token::NonTerminal(s) => {
+                let span = self.current_span();
+                let call_follows = matches!(
+                    self.tokens.get(self.pos + 1),
+                    Some((Token::BracketRoundOpen, next)) if next.start == span.end
+                );
+                if let Some(builtin) = Builtin::from_name(s).filter(|_| call_follows) {
+                    return self.parse_builtin(builtin, span).map(Some);
                 }
-            }
-            Token::Terminal(_) => {
-                if let Some((Token::Terminal(s), _)) = self.advance() {
-                    Ok(Some(Item::Terminal(s.clone())))
-                } else {
-                    unreachable!()
+                let label_follows = matches!(
+                    self.tokens.get(self.pos + 1),
+                    Some((Token::Colon, next)) if next.start == span.end
+                );
+                if label_follows {
+                    return self.parse_labeled(s.clone(), span).map(Some);
                 }
+                let item = Item::NonTerminal(s.clone(), span);
+                self.advance();
+                Ok(Some(item))
             }
-            Token::Regex(_) => {
-                if let Some((Token::Regex(s), _)) = self.advance() {
-                    Ok(Some(Item::Regex(s.clone())))
-                } else {
-                    unreachable!()
-                }
+            Token::Terminal(s) => {
+                let item = Item::Terminal(s.clone(), self.current_span());
+                self.advance();
+                Ok(Some(item))
+            }
+            Token::Regex(s) => {
+                let item = Item::Regex(s.clone(), self.current_span());
+                self.advance();
+                Ok(Some(item))
+            }
+            Token::Epsilon => {
+                let item = Item::Epsilon(self.current_span());
+                self.advance();
+                Ok(Some(item))
             }
             Token::BracketSquareOpen => {
+                let start = self.current_span().start;
                 self.advance();
                 self.skip_newlines();
                 let items = self.parse_items()?;
                 self.skip_newlines();
                 self.expect(&Token::BracketSquareClose)?;
-                Ok(Some(Item::Optional(items)))
+                Ok(Some(Item::Optional(items, start..self.previous_end())))
             }
             Token::BracketCurlyOpen => {
+                let start = self.current_span().start;
                 self.advance();
                 self.skip_newlines();
                 let items = self.parse_items()?;
                 self.skip_newlines();
                 self.expect(&Token::BracketCurlyClose)?;
-                Ok(Some(Item::AnyAmount(items)))
+                Ok(Some(Item::AnyAmount(items, start..self.previous_end())))
             }
             Token::BracketRoundOpen => {
+                let start = self.current_span().start;
                 self.advance();
                 self.skip_newlines();
                 let alts = self.parse_alternatives()?;
                 self.skip_newlines();
                 self.expect(&Token::BracketRoundClose)?;
+                let span = start..self.previous_end();
                 if alts.len() == 1 {
-                    let mut items = alts.into_iter().next().unwrap();
-                    if items.len() == 1 {
+                    let mut items = alts.into_iter().next().unwrap_or_default();
+                    if items.is_empty() {
+                        Ok(Some(Item::Epsilon(span)))
+                    } else if items.len() == 1 {
                         Ok(Some(items.remove(0)))
                     } else {
-                        Ok(Some(Item::Choice(vec![items])))
+                        Ok(Some(Item::Choice(vec![items], span)))
                     }
                 } else {
-                    Ok(Some(Item::Choice(alts)))
+                    Ok(Some(Item::Choice(alts, span)))
                 }
             }
             _ => Ok(None),
         }
     }
+
+    /// Parses `label:item`; the current token is the label.
+    fn parse_labeled(
+        &mut self,
+        label: String,
+        label_span: Range<usize>,
+    ) -> Result<Item, ParseError> {
+        self.advance();
+        self.advance();
+        match self.parse_item()? {
+            Some(item @ Item::Labeled(..)) => Err(ParseError::NestedLabel {
+                src: self.named_source(),
+                span: to_source_span(&item.span()),
+            }),
+            Some(item) => {
+                let span = label_span.start..item.span().end;
+                Ok(Item::Labeled(label, Box::new(item), span))
+            }
+            None => match self.tokens.get(self.pos) {
+                Some((tok, span)) => Err(ParseError::UnexpectedToken(Box::new(UnexpectedToken {
+                    expected: "an item after the label".to_string(),
+                    found: tok.to_string(),
+                    src: self.named_source(),
+                    span: to_source_span(span),
+                }))),
+                None => Err(ParseError::UnexpectedEof {
+                    expected: "an item after the label".to_string(),
+                    src: self.named_source(),
+                    span: to_source_span(&self.current_span()),
+                }),
+            },
+        }
+    }
+
+    /// Parses `name(arg, ...)`; the current token is the name.
+    fn parse_builtin(
+        &mut self,
+        builtin: Builtin,
+        name_span: Range<usize>,
+    ) -> Result<Item, ParseError> {
+        self.advance();
+        self.expect(&Token::BracketRoundOpen)?;
+        let mut args = Vec::new();
+        loop {
+            self.skip_newlines();
+            let start = self.current_span().start;
+            let alts = self.parse_alternatives()?;
+            args.push(if alts.len() == 1 {
+                alts.into_iter().next().unwrap_or_default()
+            } else {
+                vec![Item::Choice(alts, start..self.previous_end())]
+            });
+            self.skip_newlines();
+            if let Some(Token::Comma) = self.peek() {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        self.expect(&Token::BracketRoundClose)?;
+        let span = name_span.start..self.previous_end();
+
+        if args.len() != builtin.arity() {
+            return Err(ParseError::BuiltinArity(Box::new(BuiltinArity {
+                name: builtin.name().to_string(),
+                expected: builtin.arity(),
+                found: args.len(),
+                src: self.named_source(),
+                span: to_source_span(&span),
+            })));
+        }
+        Ok(Item::Builtin(builtin, args, span))
+    }
 }
 
 impl Sebnf {
+    /// Lexes, parses and validates an SEBNF grammar.
+    ///
+    /// `source_name` is the name shown in diagnostics, usually the file path.
+    ///
+    /// All lexer and syntax errors are reported together. Undefined
+    /// non-terminals are only checked once the grammar is free of syntax
+    /// errors, since a broken rule would show up as undefined, too.
+    pub fn parse_str(input: &str, source_name: &str) -> Result<Self, ParseErrors> {
+        let sebnf = Self::parse_unvalidated(input, source_name)?;
+        sebnf.validate(input.to_string(), source_name)?;
+        Ok(sebnf)
+    }
+
+    /// Parses a grammar split across several files, given as their sources
+    /// and names, e.g. its lexical and its syntactic rules.
+    ///
+    /// The rules are combined in file order, so the first file's first rule
+    /// is the start rule. A rule may be defined in several files as long as
+    /// every definition is the same; spans point into the file a rule was
+    /// first defined in.
+    pub fn parse_files(files: &[(String, String)]) -> Result<Self, ParseErrors> {
+        let mut parsed = Vec::new();
+        let mut errors = Vec::new();
+        for (input, source_name) in files {
+            match Self::parse_unvalidated(input, source_name) {
+                Ok(sebnf) => parsed.push((sebnf, input, source_name)),
+                Err(e) => errors.extend(e.errors),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ParseErrors { errors });
+        }
+
+        let mut combined = Sebnf {
+            rules: IndexMap::new(),
+            rule_spans: IndexMap::new(),
+            annotations: IndexMap::new(),
+        };
+        let mut defined_in: IndexMap<&str, &String> = IndexMap::new();
+        for (sebnf, input, source_name) in &parsed {
+            for (name, alts) in &sebnf.rules {
+                let Some(first_file) = defined_in.get(name.as_str()) else {
+                    defined_in.insert(name, source_name);
+                    combined.rules.insert(name.clone(), alts.clone());
+                    combined
+                        .rule_spans
+                        .insert(name.clone(), sebnf.rule_spans[name].clone());
+                    if let Some(annotations) = sebnf.annotations.get(name) {
+                        combined
+                            .annotations
+                            .insert(name.clone(), annotations.clone());
+                    }
+                    continue;
+                };
+                if !combined.same_rule(sebnf, name) {
+                    errors.push(ParseError::ConflictingRule(Box::new(ConflictingRule {
+                        name: name.clone(),
+                        first_file: first_file.to_string(),
+                        src: NamedSource::new(source_name.as_str(), input.to_string()),
+                        span: to_source_span(&sebnf.rule_spans[name]),
+                    })));
+                }
+            }
+        }
+        // References are checked against the rules of every file
+        for (sebnf, input, source_name) in &parsed {
+            errors.extend(sebnf.undefined(&combined, input, source_name));
+        }
+        if errors.is_empty() {
+            Ok(combined)
+        } else {
+            Err(ParseErrors { errors })
+        }
+    }
+
+    /// Whether `name` has the same alternatives and annotations in `other`,
+    /// wherever they are written.
+    fn same_rule(&self, other: &Sebnf, name: &str) -> bool {
+        let written = |sebnf: &Sebnf| {
+            let alts = (sebnf.rules.get(name))
+                .map(|alts| alts.iter().map(|alt| written(alt)).collect::<Vec<_>>());
+            (alts, sebnf.annotations_of(name))
+        };
+        written(self) == written(other)
+    }
+
+    /// The annotations of `name` without their span, to compare them
+    fn annotations_of(&self, name: &str) -> Option<RuleAnnotations> {
+        self.annotations.get(name).map(|a| RuleAnnotations {
+            span: 0..0,
+            ..a.clone()
+        })
+    }
+
+    /// Alternatives written twice within a rule, as `(rule, index, index of
+    /// the first copy)`.
+    pub fn repeated_alternatives(&self) -> Vec<(String, usize, usize)> {
+        let mut repeated = Vec::new();
+        for (name, alts) in &self.rules {
+            let alts: Vec<_> = alts.iter().map(|alt| written(alt)).collect();
+            for (index, alt) in alts.iter().enumerate() {
+                if let Some(first) = alts[..index].iter().position(|a| a == alt) {
+                    repeated.push((name.clone(), index, first));
+                }
+            }
+        }
+        repeated
+    }
+
+    /// Rules with the same alternatives, in any order, and the same
+    /// annotations as an earlier rule, as `(rule, earliest such rule)`.
+    pub fn identical_rules(&self) -> Vec<(String, String)> {
+        let mut seen: Vec<(_, &String)> = Vec::new();
+        let mut identical = Vec::new();
+        for (name, alts) in &self.rules {
+            let mut alts: Vec<_> = alts.iter().map(|alt| written(alt)).collect();
+            alts.sort();
+            alts.dedup();
+            let key = (alts, self.annotations_of(name));
+            match seen.iter().find(|(k, _)| *k == key) {
+                Some((_, other)) => identical.push((name.clone(), other.to_string())),
+                None => seen.push((key, name)),
+            }
+        }
+        identical
+    }
+
+    /// Alternatives that derive no string of terminals, so a parse can never
+    /// choose them, as `(rule, index, the rule through which)`. That rule
+    /// derives no string itself, e.g. `a := "x" a.`, or has only such
+    /// alternatives.
+    pub fn dead_alternatives(&self) -> Vec<(String, usize, String)> {
+        let productive = self.to_bnf().productive_rules();
+        let mut dead = Vec::new();
+        for (name, alts) in &self.rules {
+            for (index, alt) in alts.iter().enumerate() {
+                if let Some(through) = dead_end(alt, &productive) {
+                    dead.push((name.clone(), index, through.to_string()));
+                }
+            }
+        }
+        dead
+    }
+
+    /// Each `|` next to an alternative with nothing written, not even `ε`,
+    /// with the rule it is in, among the rules `in_source` says are written
+    /// in `source`. `source` is lexed again, since an empty alternative has
+    /// no span.
+    fn empty_alternatives(
+        &self,
+        source: &str,
+        in_source: impl Fn(&str) -> bool,
+    ) -> Vec<(String, Range<usize>)> {
+        let tokens: Vec<(Token, Range<usize>)> = Token::lexer(source)
+            .spanned()
+            .filter_map(|(token, span)| Some((token.ok()?, span)))
+            .filter(|(token, _)| *token != Token::NewLine)
+            .collect();
+        let mut found = Vec::new();
+        for (i, (token, span)) in tokens.iter().enumerate() {
+            if *token != Token::Separator {
+                continue;
+            }
+            // The alternative after the `|`, or the first one before it
+            let empty_after = matches!(
+                tokens.get(i + 1),
+                None | Some((
+                    Token::Separator | Token::Dot | Token::BracketRoundClose | Token::Comma,
+                    _
+                ))
+            );
+            let empty_before = matches!(
+                tokens[..i].last(),
+                Some((Token::Assign | Token::BracketRoundOpen | Token::Comma, _))
+            );
+            let rule = (self.rule_spans.iter())
+                .filter(|(rule, _)| in_source(rule))
+                .filter(|(_, name_span)| name_span.start <= span.start)
+                .max_by_key(|(_, name_span)| name_span.start);
+            if (empty_after || empty_before)
+                && let Some((rule, _)) = rule
+            {
+                found.push((rule.clone(), span.clone()));
+            }
+        }
+        found
+    }
+
+    /// Span of the `index`-th alternative of `rule`; the rule name for an
+    /// empty one
+    fn alternative_span(&self, rule: &str, index: usize) -> Range<usize> {
+        match self.rules[rule][index].as_slice() {
+            [first, .., last] => first.span().start..last.span().end,
+            [item] => item.span(),
+            [] => self.rule_spans.get(rule).cloned().unwrap_or(0..0),
+        }
+    }
+
+    /// Lexes and parses, reporting every syntax error, but does not check
+    /// for undefined non-terminals.
+    fn parse_unvalidated(input: &str, source_name: &str) -> Result<Self, ParseErrors> {
+        let mut tokens = Vec::new();
+        // Adjacent unrecognized characters are reported as one error
+        let mut lex_errors: Vec<Range<usize>> = Vec::new();
+        for (res, span) in Token::lexer(input).spanned() {
+            match (res, lex_errors.last_mut()) {
+                (Ok(token), _) => tokens.push((token, span)),
+                (Err(_), Some(last)) if last.end == span.start => last.end = span.end,
+                (Err(_), _) => lex_errors.push(span),
+            }
+        }
+        let mut errors: Vec<_> = lex_errors
+            .iter()
+            .map(|span| ParseError::LexError {
+                src: NamedSource::new(source_name, input.to_string()),
+                span: to_source_span(span),
+            })
+            .collect();
+
+        let mut parser = Parser::new(tokens, input.to_string(), source_name.to_string());
+        let (sebnf, syntax_errors) = parser.parse_grammar();
+        errors.extend(syntax_errors);
+        if !errors.is_empty() {
+            errors.sort_by_key(|e| e.labels().and_then(|mut l| l.next()).map(|l| l.offset()));
+            return Err(ParseErrors { errors });
+        }
+        Ok(sebnf)
+    }
+
+    /// Parses an already lexed token stream without checking that every
+    /// referenced non-terminal is defined; see [`Sebnf::validate`].
     pub fn parse(
         tokens: Vec<(Token, Range<usize>)>,
         source: String,
         source_name: impl Into<String>,
-    ) -> Result<Self, ParseError> {
+    ) -> Result<Self, ParseErrors> {
         let mut parser = Parser::new(tokens, source, source_name.into());
-        parser.parse_grammar()
+        match parser.parse_grammar() {
+            (sebnf, errors) if errors.is_empty() => Ok(sebnf),
+            (_, errors) => Err(ParseErrors { errors }),
+        }
     }
 
+    /// Converts to BNF, replacing `[]`, `{}` and `()` by generated helper rules.
     pub fn to_bnf(&self) -> Bnf {
         converter::sebnf_to_bnf(self)
     }
 
-    pub fn validate(&self, source: String, source_name: &str) -> Result<(), ParseError> {
-        let defined: std::collections::HashSet<&str> =
-            self.rules.keys().map(|s| s.as_str()).collect();
+    /// Like [`Sebnf::to_bnf`], with non-default lowering choices.
+    pub fn to_bnf_with(&self, options: &LoweringOptions) -> Bnf {
+        converter::sebnf_to_bnf_with(self, options)
+    }
+
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
 
-        for alternatives in self.rules.values() {
-            for items in alternatives {
-                Self::check_items_defined(items, &defined, &source, source_name)?;
+    /// The `@max_depth` limit of every annotated rule
+    pub fn max_depths(&self) -> IndexMap<String, usize> {
+        self.annotations
+            .iter()
+            .filter_map(|(name, annotations)| Some((name.clone(), annotations.max_depth?)))
+            .collect()
+    }
+
+    /// True if `rule` can derive a sequence containing `rule` again.
+    pub fn is_recursive(&self, rule: &str) -> bool {
+        let mut pending = vec![rule];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(current) = pending.pop() {
+            let Some(alternatives) = self.rules.get(current) else {
+                continue;
+            };
+            let mut references = References::default();
+            walk_alternatives(&mut references, alternatives);
+            for (_, name, _) in references.found {
+                if name == rule {
+                    return true;
+                }
+                if let Some((name, _)) = self.rules.get_key_value(&name)
+                    && seen.insert(name.as_str())
+                {
+                    pending.push(name);
+                }
             }
         }
-        Ok(())
+        false
     }
 
-    fn check_items_defined(
-        items: &[Item],
-        defined: &std::collections::HashSet<&str>,
-        source: &str,
-        source_name: &str,
-    ) -> Result<(), ParseError> {
-        for item in items {
-            match item {
-                Item::NonTerminal(name, span) => {
-                    if !defined.contains(name.as_str()) {
-                        return Err(ParseError::UndefinedNonTerminal {
-                            name: name.clone(),
-                            src: NamedSource::new(source_name, source.to_string()),
-                            span: to_source_span(span),
-                        });
-                    }
+    /// Checks that every referenced non-terminal has a rule.
+    pub fn validate(&self, source: String, source_name: &str) -> Result<(), ParseErrors> {
+        let errors = self.undefined(self, &source, source_name);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseErrors { errors })
+        }
+    }
+
+    /// References of this grammar to rules that `defined` does not have
+    fn undefined(&self, defined: &Sebnf, source: &str, source_name: &str) -> Vec<ParseError> {
+        let mut references = References::default();
+        walk_sebnf(&mut references, self);
+        references
+            .found
+            .into_iter()
+            .filter(|(_, name, _)| !defined.rules.contains_key(name))
+            .map(|(_, name, span)| ParseError::UndefinedNonTerminal {
+                name,
+                src: NamedSource::new(source_name, source.to_string()),
+                span: to_source_span(&span),
+            })
+            .collect()
+    }
+
+    /// Collects warnings about constructs that are valid but probably unintended.
+    pub fn warnings(&self, source: &str, source_name: &str) -> Vec<GrammarWarning> {
+        let files = [(source.to_string(), source_name.to_string())];
+        self.collect_warnings(&files, &HashMap::new())
+    }
+
+    /// [`Sebnf::warnings`] of a grammar combined by [`Sebnf::parse_files`]
+    /// from the same `files`, each pointing into the file its rule was
+    /// first defined in.
+    pub fn file_warnings(&self, files: &[(String, String)]) -> Vec<GrammarWarning> {
+        let mut defined_in = HashMap::new();
+        for (index, (input, source_name)) in files.iter().enumerate() {
+            if let Ok(sebnf) = Self::parse_unvalidated(input, source_name) {
+                for rule in sebnf.rules.into_keys() {
+                    defined_in.entry(rule).or_insert(index);
                 }
-                Item::Optional(inner) | Item::AnyAmount(inner) => {
-                    Self::check_items_defined(inner, defined, source, source_name)?;
+            }
+        }
+        self.collect_warnings(files, &defined_in)
+    }
+
+    /// The warnings of the grammar, each in the file of `defined_in` its
+    /// rule is written in, the first one for rules missing there.
+    fn collect_warnings(
+        &self,
+        files: &[(String, String)],
+        defined_in: &HashMap<String, usize>,
+    ) -> Vec<GrammarWarning> {
+        let file_of = |rule: &str| defined_in.get(rule).copied().unwrap_or(0);
+        let src = |rule: &str| {
+            let (source, source_name) = &files[file_of(rule)];
+            NamedSource::new(source_name, source.clone())
+        };
+        let mut warnings = Vec::new();
+
+        if let Some(start) = self.rules.keys().next() {
+            let mut references = References::default();
+            walk_sebnf(&mut references, self);
+            let reference = references
+                .found
+                .into_iter()
+                .find(|(_, name, _)| name == start);
+            if let Some((rule, _, span)) = reference {
+                warnings.push(GrammarWarning::ReferencedStartSymbol {
+                    name: start.clone(),
+                    src: src(&rule),
+                    span: to_source_span(&span),
+                });
+            }
+        }
+
+        for (name, limit) in self.max_depths() {
+            if !self.is_recursive(&name) {
+                warnings.push(GrammarWarning::UnreachableDepthLimit {
+                    span: to_source_span(&self.annotations[&name].span),
+                    src: src(&name),
+                    name,
+                    limit,
+                });
+            }
+        }
+
+        for (rule, index, first) in self.repeated_alternatives() {
+            warnings.push(GrammarWarning::RepeatedAlternative {
+                first: to_source_span(&self.alternative_span(&rule, first)),
+                span: to_source_span(&self.alternative_span(&rule, index)),
+                src: src(&rule),
+                rule,
+            });
+        }
+        for (index, (source, _)) in files.iter().enumerate() {
+            for (rule, span) in self.empty_alternatives(source, |rule| file_of(rule) == index) {
+                warnings.push(GrammarWarning::EmptyAlternative {
+                    src: src(&rule),
+                    rule,
+                    span: to_source_span(&span),
+                });
+            }
+        }
+        for (rule, index, through) in self.dead_alternatives() {
+            warnings.push(GrammarWarning::DeadAlternative {
+                span: to_source_span(&self.alternative_span(&rule, index)),
+                src: src(&rule),
+                rule,
+                number: index + 1,
+                through,
+            });
+        }
+        for (name, other) in self.identical_rules() {
+            warnings.push(GrammarWarning::IdenticalRules {
+                span: to_source_span(&self.rule_spans.get(&name).cloned().unwrap_or(0..0)),
+                src: src(&name),
+                name,
+                other,
+            });
+        }
+
+        let mut calls = BuiltinCalls::default();
+        walk_sebnf(&mut calls, self);
+        for (caller, builtin, span) in calls.found {
+            if let Some(rule) = self.rule_spans.get(builtin.name()) {
+                let same_file = file_of(builtin.name()) == file_of(&caller);
+                warnings.push(GrammarWarning::ShadowedBuiltin {
+                    name: builtin.name().to_string(),
+                    src: src(&caller),
+                    span: to_source_span(&span),
+                    rule: same_file.then(|| to_source_span(rule)),
+                });
+            }
+        }
+
+        // Each soft keyword is reported once, at its first reserved use
+        let mut terminals = Terminals::default();
+        walk_sebnf(&mut terminals, self);
+        let mut reported = Vec::new();
+        for (name, annotations) in &self.annotations {
+            for keyword in &annotations.soft_keywords {
+                if !terminals
+                    .found
+                    .iter()
+                    .any(|(r, t, _)| r == name && t == keyword)
+                {
+                    warnings.push(GrammarWarning::UnusedSoftKeyword {
+                        keyword: keyword.clone(),
+                        rule: name.clone(),
+                        src: src(name),
+                        span: to_source_span(&annotations.span),
+                    });
                 }
-                Item::Choice(alts) => {
-                    for alt in alts {
-                        Self::check_items_defined(alt, defined, source, source_name)?;
-                    }
+                let reserved = terminals
+                    .found
+                    .iter()
+                    .find(|(rule, t, _)| t == keyword && !self.is_soft_keyword(rule, keyword));
+                if let Some((rule, _, span)) = reserved
+                    && !reported.contains(&keyword)
+                {
+                    reported.push(keyword);
+                    warnings.push(GrammarWarning::ReservedSoftKeyword {
+                        keyword: keyword.clone(),
+                        soft_rule: name.clone(),
+                        rule: rule.clone(),
+                        src: src(rule),
+                        span: to_source_span(span),
+                    });
                 }
-                _ => {}
             }
         }
+        warnings
+    }
+
+    /// Makes `rule` the start symbol by moving it first; the other rules
+    /// keep their order.
+    pub fn set_start(&mut self, rule: &str) -> Result<(), ParseError> {
+        let index = self
+            .rules
+            .get_index_of(rule)
+            .ok_or_else(|| UnknownStart(rule.to_string()))?;
+        self.rules.move_index(index, 0);
+        if let Some(index) = self.rule_spans.get_index_of(rule) {
+            self.rule_spans.move_index(index, 0);
+        }
         Ok(())
     }
+
+    /// Whether `terminal` (with its quotes) is a soft keyword of `rule`.
+    pub fn is_soft_keyword(&self, rule: &str, terminal: &str) -> bool {
+        self.annotations
+            .get(rule)
+            .is_some_and(|a| a.soft_keywords.iter().any(|k| k == terminal))
+    }
+}
+
+/// Every terminal with its rule, in rule order
+#[derive(Default)]
+struct Terminals {
+    rule: String,
+    found: Vec<(String, String, Range<usize>)>,
+}
+
+impl Visitor for Terminals {
+    fn visit_rule(&mut self, name: &str, alternatives: &[Vec<Item>]) {
+        self.rule = name.to_string();
+        walk_alternatives(self, alternatives);
+    }
+
+    fn visit_terminal(&mut self, terminal: &str, span: &Range<usize>) {
+        let found = (self.rule.clone(), terminal.to_string(), span.clone());
+        self.found.push(found);
+    }
+}
+
+/// Every non-terminal reference with its rule, in rule order
+#[derive(Default)]
+struct References {
+    rule: String,
+    found: Vec<(String, String, Range<usize>)>,
+}
+
+impl Visitor for References {
+    fn visit_rule(&mut self, name: &str, alternatives: &[Vec<Item>]) {
+        self.rule = name.to_string();
+        walk_alternatives(self, alternatives);
+    }
+
+    fn visit_non_terminal(&mut self, name: &str, span: &Range<usize>) {
+        let found = (self.rule.clone(), name.to_string(), span.clone());
+        self.found.push(found);
+    }
+}
+
+/// Every built-in call with its rule, in rule order
+#[derive(Default)]
+struct BuiltinCalls {
+    rule: String,
+    found: Vec<(String, Builtin, Range<usize>)>,
+}
+
+impl Visitor for BuiltinCalls {
+    fn visit_rule(&mut self, name: &str, alternatives: &[Vec<Item>]) {
+        self.rule = name.to_string();
+        walk_alternatives(self, alternatives);
+    }
+
+    fn visit_item(&mut self, item: &Item) {
+        if let Item::Builtin(builtin, _, span) = item {
+            self.found.push((self.rule.clone(), *builtin, span.clone()));
+        }
+        walk_item(self, item);
+    }
 }
 
 impl fmt::Display for Item {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Item::NonTerminal(s, _) => write!(f, "{}", s),
-            Item::Terminal(s) => write!(f, "{}", s),
-            Item::Regex(s) => write!(f, "{}", s),
-            Item::Optional(items) => {
+            Item::Terminal(s, _) => write!(f, "{}", s),
+            Item::Regex(s, _) => write!(f, "{}", s),
+            Item::Optional(items, _) => {
                 write!(f, "[")?;
                 for item in items {
                     write!(f, " {}", item)?;
                 }
                 write!(f, " ]")
             }
-            Item::AnyAmount(items) => {
+            Item::AnyAmount(items, _) => {
                 write!(f, "{{")?;
                 for item in items {
                     write!(f, " {}", item)?;
                 }
                 write!(f, " }}")
             }
-            Item::Choice(alts) => {
+            Item::Choice(alts, _) => {
                 write!(f, "(")?;
                 for (i, alt) in alts.iter().enumerate() {
                     if i > 0 {
                         write!(f, " |")?;
                     }
+                    if alt.is_empty() {
+                        write!(f, " ε")?;
+                    }
                     for item in alt {
                         write!(f, " {}", item)?;
                     }
                 }
                 write!(f, " )")
             }
+            Item::Builtin(builtin, args, _) => {
+                write!(f, "{}(", builtin.name())?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    for item in arg {
+                        write!(f, " {}", item)?;
+                    }
+                }
+                write!(f, " )")
+            }
+            Item::Labeled(label, item, _) => write!(f, "{}:{}", label, item),
+            Item::Epsilon(_) => Symbols::with_current(|s| write!(f, "{}", s.grammar_epsilon())),
         }
     }
 }
 
-fn write_items(f: &mut fmt::Formatter<'_>, items: &[Item]) -> fmt::Result {
+/// An alternative as written, without spans; `ε` only where it is all
+pub(crate) fn written(alt: &[Item]) -> String {
+    let items: Vec<_> = (alt.iter())
+        .filter(|item| !matches!(item, Item::Epsilon(_)))
+        .map(|item| item.to_string())
+        .collect();
+    match items.is_empty() {
+        true => Symbols::with_current(|s| s.grammar_epsilon().to_string()),
+        false => items.join(" "),
+    }
+}
+
+/// The first rule outside `productive` that keeps a sequence from deriving
+/// any string of terminals; `None` if it derives one. Only what is required
+/// counts: a `[ ]`, `{ }` or `sep_by` can always match nothing, and a group
+/// needs one live alternative.
+pub(crate) fn dead_end<'a>(items: &'a [Item], productive: &HashSet<String>) -> Option<&'a str> {
+    items.iter().find_map(|item| match item {
+        Item::NonTerminal(name, _) if !productive.contains(name) => Some(name.as_str()),
+        Item::Choice(alts, _) => {
+            let mut ends = alts.iter().map(|alt| dead_end(alt, productive));
+            let first = ends.next()??;
+            ends.all(|end| end.is_some()).then_some(first)
+        }
+        Item::Builtin(Builtin::SepBy, _, _) => None,
+        Item::Builtin(_, args, _) => args.iter().find_map(|arg| dead_end(arg, productive)),
+        Item::Labeled(_, inner, _) => dead_end(std::slice::from_ref(inner.as_ref()), productive),
+        _ => None,
+    })
+}
+
+/// Writes an empty sequence as `ε`, so it cannot be mistaken for a stray `|`
+fn write_items(f: &mut impl fmt::Write, items: &[Item]) -> fmt::Result {
+    if items.is_empty() {
+        return Symbols::with_current(|s| write!(f, "{}", s.grammar_epsilon()));
+    }
     for (i, item) in items.iter().enumerate() {
         if i > 0 {
             write!(f, " ")?;
@@ -395,31 +1627,245 @@ fn write_items(f: &mut fmt::Formatter<'_>, items: &[Item]) -> fmt::Result {
     Ok(())
 }
 
-impl fmt::Display for Sebnf {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let max_len = self.rules.keys().map(|k| k.len()).max().unwrap_or(0);
+impl Sebnf {
+    /// Writes a rule as [`Display`](fmt::Display) does, with its annotations
+    /// and its name padded to `width`.
+    pub fn write_rule(&self, f: &mut impl fmt::Write, name: &str, width: usize) -> fmt::Result {
+        let Some(alts) = self.rules.get(name) else {
+            return Ok(());
+        };
+        if let Some(annotations) = self.annotations.get(name) {
+            if let Some(limit) = annotations.max_depth {
+                writeln!(f, "@max_depth({})", limit)?;
+            }
+            if !annotations.soft_keywords.is_empty() {
+                writeln!(
+                    f,
+                    "@soft_keywords({})",
+                    annotations.soft_keywords.join(", ")
+                )?;
+            }
+        }
+        if alts.len() == 1 {
+            write!(f, "{:width$} := ", name, width = width)?;
+            write_items(f, &alts[0])?;
+            writeln!(f, ".")
+        } else {
+            let indent = " ".repeat(width + 2);
 
-        for (name, alts) in &self.rules {
-            if alts.len() == 1 {
-                write!(f, "{:width$} := ", name, width = max_len)?;
-                write_items(f, &alts[0])?;
-                writeln!(f, ".")?;
-            } else {
-                let indent = " ".repeat(max_len + 2);
+            write!(f, "{:width$} := ", name, width = width)?;
+            write_items(f, &alts[0])?;
+            writeln!(f)?;
 
-                write!(f, "{:width$} := ", name, width = max_len)?;
-                write_items(f, &alts[0])?;
+            for alt in &alts[1..] {
+                write!(f, "{}| ", indent)?;
+                write_items(f, alt)?;
                 writeln!(f)?;
-
-                for alt in &alts[1..] {
-                    write!(f, "{}| ", indent)?;
-                    write_items(f, alt)?;
-                    writeln!(f)?;
-                }
-                writeln!(f, "{}.", indent)?;
             }
+            writeln!(f, "{}.", indent)
+        }
+    }
+}
+
+impl fmt::Display for Sebnf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max_len = self.rules.keys().map(|k| k.len()).max().unwrap_or(0);
+
+        for name in self.rules.keys() {
+            self.write_rule(f, name, max_len)?;
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_and_reports_every_syntax_error() {
+        let source = "a := \"x\" b\nb := \"y\" ] .\nc := ?? \"z\".\nd := \"ok\".";
+        let errors = Sebnf::parse_str(source, "test").unwrap_err().errors;
+        let codes: Vec<_> = errors
+            .iter()
+            .map(|e| e.code().map(|c| c.to_string()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                Some("sebnf::unexpected_token".to_string()),
+                Some("sebnf::unexpected_token".to_string()),
+                Some("sebnf::lex_error".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_max_depth_annotations() {
+        let source = "@max_depth(3)\na := \"(\" a \")\" | b.\n@max_depth(2) b := \"x\".";
+        let sebnf = Sebnf::parse_str(source, "test").unwrap();
+        assert_eq!(
+            sebnf.max_depths().into_iter().collect::<Vec<_>>(),
+            [("a".to_string(), 3), ("b".to_string(), 2)]
+        );
+        assert_eq!(sebnf.rule_spans["a"], 14..15);
+        assert!(sebnf.to_string().starts_with("@max_depth(3)\na := "));
+
+        let warnings = sebnf.warnings(source, "test");
+        assert!(matches!(
+            warnings.as_slice(),
+            [GrammarWarning::ReferencedStartSymbol { .. }, GrammarWarning::UnreachableDepthLimit { name, .. }] if name == "b"
+        ));
+    }
+
+    #[test]
+    fn parses_builtin_calls() {
+        let source = "a := sep_by(b, \",\") delimited(\"(\", b, \")\") non_empty(b).\nb := \"x\".";
+        let sebnf = Sebnf::parse_str(source, "test").unwrap();
+        let calls: Vec<_> = sebnf.rules["a"][0]
+            .iter()
+            .map(|item| match item {
+                Item::Builtin(builtin, args, span) => (*builtin, args.len(), &source[span.clone()]),
+                _ => panic!("not a built-in call: {}", item),
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                (Builtin::SepBy, 2, "sep_by(b, \",\")"),
+                (Builtin::Delimited, 3, "delimited(\"(\", b, \")\")"),
+                (Builtin::NonEmpty, 1, "non_empty(b)"),
+            ]
+        );
+        assert!(matches!(
+            Sebnf::parse_str("a := sep_by(b).\nb := \"x\".", "test")
+                .unwrap_err()
+                .errors
+                .as_slice(),
+            [ParseError::BuiltinArity(arity)] if arity.found == 1
+        ));
+    }
+
+    #[test]
+    fn warns_about_rules_shadowed_by_builtins() {
+        let source = "a := sep_by(b, \",\") sep_by (b).\nsep_by := \"y\".\nb := \"x\".";
+        let sebnf = Sebnf::parse_str(source, "test").unwrap();
+        // With a space, the name refers to the rule
+        assert!(matches!(
+            sebnf.rules["a"][0].as_slice(),
+            [Item::Builtin(Builtin::SepBy, _, _), Item::NonTerminal(name, _), ..]
+                if name == "sep_by"
+        ));
+        let warnings = sebnf.warnings(source, "test");
+        assert!(matches!(
+            warnings.as_slice(),
+            [GrammarWarning::ShadowedBuiltin { name, span, .. }]
+                if name == "sep_by" && span.offset() == 5
+        ));
+
+        let source = "a := sep_by(b, \",\").\nb := \"x\".";
+        let sebnf = Sebnf::parse_str(source, "test").unwrap();
+        assert!(sebnf.warnings(source, "test").is_empty());
+    }
+
+    #[test]
+    fn parses_labels() {
+        let source = "a := head:b args:{ \",\" b } b.\nb := \"x\".";
+        let sebnf = Sebnf::parse_str(source, "test").unwrap();
+        let items = &sebnf.rules["a"][0];
+        assert_eq!(items[0].label(), Some("head"));
+        assert_eq!(items[0].span(), 5..11);
+        assert_eq!(items[1].label(), Some("args"));
+        assert_eq!(items[2].label(), None);
+        assert!(
+            sebnf
+                .to_string()
+                .starts_with("a := head:b args:{ \",\" b } b.")
+        );
+        assert_eq!(sebnf.to_bnf().rules["a"][0].len(), 3);
+
+        let errors = Sebnf::parse_str("a := x:b x:b.\nb := y:z:\"c\".", "test")
+            .unwrap_err()
+            .errors;
+        assert!(matches!(
+            errors.as_slice(),
+            [ParseError::DuplicateLabel(duplicate), ParseError::NestedLabel { .. }] if duplicate.label == "x"
+        ));
+    }
+
+    #[test]
+    fn parses_epsilon_and_warns_about_blank_alternatives() {
+        let source = "a := \"x\" | ε | ( EPSILON ) | ().\nb := \"y\" |\n   | \"z\".";
+        let sebnf = Sebnf::parse_str(source, "test").unwrap();
+        assert!(
+            sebnf.rules["a"][1..]
+                .iter()
+                .all(|alt| matches!(alt.as_slice(), [Item::Epsilon(_)]))
+        );
+        assert_eq!(sebnf.rules["b"][1], []);
+        assert_eq!(sebnf.to_bnf().rules["a"][1], []);
+        assert!(sebnf.to_string().contains("b := \"y\"\n   | ε\n"));
+
+        let warnings = sebnf.warnings(source, "test");
+        let blank: Vec<_> = (warnings.iter())
+            .filter_map(|w| match w {
+                GrammarWarning::EmptyAlternative { rule, span, .. } => Some((rule, span.offset())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(blank, [(&"b".to_string(), 43)]);
+    }
+
+    #[test]
+    fn reports_every_undefined_non_terminal() {
+        let errors = Sebnf::parse_str("a := b c.", "test").unwrap_err().errors;
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn combines_files_and_reports_conflicting_rules() {
+        let file = |source: &str, name: &str| (source.to_string(), name.to_string());
+        let syntax = file("expr := num { \"+\" num }.", "syntax");
+        let lexical = file("num := /[0-9]+/.", "lexical");
+        let sebnf =
+            Sebnf::parse_files(&[syntax.clone(), lexical.clone(), lexical.clone()]).unwrap();
+        assert_eq!(sebnf.rules.keys().collect::<Vec<_>>(), ["expr", "num"]);
+
+        let other = file("num := /[0-9]+/ | \"-\" num.", "other");
+        let errors = Sebnf::parse_files(&[syntax, lexical, other])
+            .unwrap_err()
+            .errors;
+        assert!(
+            matches!(&errors[..], [ParseError::ConflictingRule(conflict)]
+                if conflict.name == "num" && conflict.first_file == "lexical")
+        );
+    }
+
+    #[test]
+    fn file_warnings_point_into_the_file_of_their_rule() {
+        let file = |source: &str, name: &str| (source.to_string(), name.to_string());
+        let files = [
+            file("expr := term { \"+\" term }.", "syntax"),
+            file("term := num | num.\nnum := /[0-9]+/ | .", "lexical"),
+        ];
+        let sebnf = Sebnf::parse_files(&files).unwrap();
+        let warnings = sebnf.file_warnings(&files);
+        let found: Vec<_> = (warnings.iter())
+            .map(|w| {
+                let src = w.source_code().unwrap();
+                let span = w.labels().unwrap().next().unwrap();
+                let contents = src.read_span(span.inner(), 0, 0).unwrap();
+                let text = String::from_utf8_lossy(contents.data()).into_owned();
+                (contents.name().unwrap().to_string(), text)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("lexical".to_string(), "num".to_string()),
+                ("lexical".to_string(), "|".to_string()),
+            ]
+        );
+    }
+}
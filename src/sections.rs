@@ -0,0 +1,266 @@
+//! Several named grammars in one file.
+//!
+//! A file may hold sections like `grammar Expr { ... }`, each an independent
+//! grammar with its own rules. [`select`] picks one or more of them and
+//! returns a source with everything else blanked out, so spans and line
+//! numbers still point into the original file. Selecting several sections
+//! composes them: their rules are combined, and the first section's first
+//! rule is the start rule.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::sections::select;
+//!
+//! let source = "grammar A {\n  a := \"x\".\n}\ngrammar B {\n  b := { \"y\" }.\n}\n";
+//! let selected = select(source, "g", &["B".to_string()]).unwrap();
+//! let sebnf = Sebnf::parse_str(&selected, "g").unwrap();
+//! assert_eq!(sebnf.rules.keys().collect::<Vec<_>>(), ["b"]);
+//! ```
+
+use std::ops::Range;
+
+use logos::Logos;
+use miette::{NamedSource, SourceSpan};
+
+use crate::lex::Token;
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum SectionError {
+    #[error("grammar section '{name}' is never closed")]
+    #[diagnostic(code(sebnf::unclosed_section), help("add a '}}' after its last rule"))]
+    Unclosed {
+        name: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("opened here")]
+        span: SourceSpan,
+    },
+    #[error("rules outside of a grammar section")]
+    #[diagnostic(
+        code(sebnf::outside_section),
+        help("once a file has `grammar Name {{ ... }}` sections, every rule must be in one")
+    )]
+    OutsideSection {
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("not in any section")]
+        span: SourceSpan,
+    },
+    #[error("grammar section '{name}' is defined twice")]
+    #[diagnostic(code(sebnf::duplicate_section))]
+    Duplicate {
+        name: String,
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("defined again here")]
+        span: SourceSpan,
+    },
+    #[error("no grammar section named '{name}'")]
+    #[diagnostic(code(sebnf::unknown_section), help("the file defines: {available}"))]
+    Unknown { name: String, available: String },
+    #[error("the input defines several grammars")]
+    #[diagnostic(
+        code(sebnf::ambiguous_section),
+        help("choose one with --grammar; the file defines: {available}")
+    )]
+    Ambiguous { available: String },
+}
+
+/// A `grammar Name { ... }` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    /// From `grammar` to the closing `}`
+    pub span: Range<usize>,
+    /// Between the braces
+    pub body: Range<usize>,
+}
+
+/// Finds the grammar sections of `source`; a file without sections has none.
+pub fn sections(source: &str, source_name: &str) -> Result<Vec<Section>, SectionError> {
+    let src = || NamedSource::new(source_name, source.to_string());
+    let tokens: Vec<_> = Token::lexer(source)
+        .spanned()
+        .filter(|(token, _)| !matches!(token, Ok(Token::NewLine)))
+        .collect();
+
+    let mut sections: Vec<Section> = Vec::new();
+    let mut outside = None;
+    // A rule may refer to a non-terminal called `grammar`
+    let mut at_rule_start = true;
+    let mut i = 0;
+    while i < tokens.len() {
+        let header = match &tokens[i..] {
+            [
+                (Ok(Token::NonTerminal(keyword)), start),
+                (Ok(Token::NonTerminal(name)), _),
+                (Ok(Token::BracketCurlyOpen), open),
+                ..,
+            ] if at_rule_start && keyword == "grammar" => {
+                Some((name.clone(), start.start, open.end))
+            }
+            _ => None,
+        };
+        let Some((name, start, body_start)) = header else {
+            outside.get_or_insert(tokens[i].1.clone());
+            at_rule_start = matches!(tokens[i].0, Ok(Token::Dot));
+            i += 1;
+            continue;
+        };
+
+        // Find the matching `}`; repetitions inside the body nest
+        let mut depth = 1;
+        let mut j = i + 3;
+        while j < tokens.len() {
+            match tokens[j].0 {
+                Ok(Token::BracketCurlyOpen) => depth += 1,
+                Ok(Token::BracketCurlyClose) => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            j += 1;
+        }
+        let Some((_, close)) = tokens.get(j) else {
+            return Err(SectionError::Unclosed {
+                name,
+                src: src(),
+                span: (start..body_start).into(),
+            });
+        };
+        if sections.iter().any(|s| s.name == name) {
+            return Err(SectionError::Duplicate {
+                name,
+                src: src(),
+                span: (start..body_start).into(),
+            });
+        }
+        sections.push(Section {
+            name,
+            span: start..close.end,
+            body: body_start..close.start,
+        });
+        i = j + 1;
+    }
+
+    match outside {
+        Some(span) if !sections.is_empty() => Err(SectionError::OutsideSection {
+            src: src(),
+            span: span.into(),
+        }),
+        _ => Ok(sections),
+    }
+}
+
+/// Returns `source` with only the bodies of the named sections left, every
+/// other byte but newlines replaced by a space.
+///
+/// A file without sections is returned as is when no names are given. A
+/// file with exactly one section needs no name either. Sections chosen out
+/// of file order are concatenated instead, so their spans no longer match
+/// the file.
+pub fn select(source: &str, source_name: &str, names: &[String]) -> Result<String, SectionError> {
+    let sections = sections(source, source_name)?;
+    if sections.is_empty() {
+        return match names.first() {
+            None => Ok(source.to_string()),
+            Some(name) => Err(SectionError::Unknown {
+                name: name.clone(),
+                available: "no sections".to_string(),
+            }),
+        };
+    }
+    let available = || {
+        sections
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut bodies = Vec::new();
+    for name in names {
+        let section =
+            sections
+                .iter()
+                .find(|s| &s.name == name)
+                .ok_or_else(|| SectionError::Unknown {
+                    name: name.clone(),
+                    available: available(),
+                })?;
+        if !bodies.contains(&section.body) {
+            bodies.push(section.body.clone());
+        }
+    }
+    if names.is_empty() {
+        match sections.as_slice() {
+            [section] => bodies.push(section.body.clone()),
+            _ => {
+                return Err(SectionError::Ambiguous {
+                    available: available(),
+                });
+            }
+        }
+    }
+
+    if !bodies.is_sorted_by_key(|b| b.start) {
+        // The parser takes the first rule as the start rule, so the chosen
+        // order wins over keeping the offsets
+        return Ok(bodies
+            .iter()
+            .map(|b| format!("{}\n", &source[b.clone()]))
+            .collect());
+    }
+    let mut out = blank(source);
+    for body in bodies {
+        out.replace_range(body.clone(), &source[body]);
+    }
+    Ok(out)
+}
+
+/// Blanks out everything but newlines, keeping byte offsets.
+fn blank(text: &str) -> String {
+    text.bytes()
+        .map(|b| if b == b'\n' { '\n' } else { ' ' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn selects_and_composes_sections() {
+        let source = "grammar Expr {\n  e := n { \"+\" n }.\n  n := /[0-9]+/.\n}\n\ngrammar List {\n  l := \"[\" e \"]\".\n}\n";
+        let found = sections(source, "g").unwrap();
+        assert_eq!(
+            found.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+            ["Expr", "List"]
+        );
+
+        let selected = select(source, "g", &["Expr".to_string()]).unwrap();
+        assert_eq!(selected.len(), source.len());
+        let sebnf = Sebnf::parse_str(&selected, "g").unwrap();
+        assert_eq!(sebnf.rules.keys().collect::<Vec<_>>(), ["e", "n"]);
+
+        let composed = select(source, "g", &["List".to_string(), "Expr".to_string()]).unwrap();
+        let sebnf = Sebnf::parse_str(&composed, "g").unwrap();
+        assert_eq!(sebnf.rules.keys().next().unwrap(), "l");
+
+        assert!(matches!(
+            select(source, "g", &[]),
+            Err(SectionError::Ambiguous { .. })
+        ));
+        assert!(matches!(
+            sections("a := \"x\".\ngrammar B { b := \"y\". }", "g"),
+            Err(SectionError::OutsideSection { .. })
+        ));
+        assert!(
+            sections("a := grammar x { \"y\" }.", "g")
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
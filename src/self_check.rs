@@ -0,0 +1,451 @@
+//! Checks of the tool's own machinery on a user's grammar.
+//!
+//! [`check_conversion`] guards the SEBNF to BNF converter: it computes, for
+//! every rule, all sentences of up to `max_len` tokens once by interpreting
+//! `[ ]`, `{ }`, `( | )` and the built-in idioms directly, and once from the
+//! converted BNF, and reports every sentence only one of them derives.
+//! Sentences are compared as sequences of terminals and regexes, so the
+//! check does not depend on what a regex matches. A soft keyword and the
+//! regex the converter spells it as count as the same token.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::bnf::{self, Bnf};
+use crate::converter::soft_keyword_regex;
+use crate::sebnf::{Builtin, Item, Sebnf};
+use crate::symbols::Symbols;
+use crate::visit::{Visitor, walk_sebnf};
+
+/// Largest number of sentences computed for a single rule or item
+pub const MAX_SENTENCES: usize = 100_000;
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum SelfCheckError {
+    #[error("rule '{rule}' derives more than {MAX_SENTENCES} sentences of up to {max_len} tokens")]
+    #[diagnostic(code(sebnf::self_check_too_many_sentences), help("lower --max-len"))]
+    TooManySentences { rule: String, max_len: usize },
+}
+
+/// A sentence as the terminals and regexes it consists of, e.g. `"(" /[0-9]+/ ")"`
+pub type Sentence = Vec<String>;
+
+/// A sentence as indices into [`Tokens`]
+type Word = Vec<u32>;
+
+type Language = HashSet<Word>;
+
+/// A sentence derived by a rule of only one of the two grammars.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub rule: String,
+    pub sentence: Sentence,
+    /// Whether the SEBNF derives it (and the BNF does not)
+    pub in_sebnf: bool,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (has, lacks) = if self.in_sebnf {
+            ("SEBNF", "BNF")
+        } else {
+            ("BNF", "SEBNF")
+        };
+        write!(f, "'{}' derives ", self.rule)?;
+        if self.sentence.is_empty() {
+            Symbols::with_current(|s| write!(f, "{}", s.epsilon))?;
+        } else {
+            write!(f, "{}", self.sentence.join(" "))?;
+        }
+        write!(f, " in the {} but not in the {}", has, lacks)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionCheck {
+    pub max_len: usize,
+    /// Number of SEBNF rules compared
+    pub rules: usize,
+    /// Number of sentences the SEBNF rules derive together
+    pub sentences: usize,
+    /// Shortest divergence of every rule whose languages differ, in rule order
+    pub divergences: Vec<Divergence>,
+}
+
+impl ConversionCheck {
+    pub fn is_ok(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl fmt::Display for ConversionCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return writeln!(
+                f,
+                "Conversion preserves the language of all {} rules ({} sentences of up to {} tokens)",
+                self.rules, self.sentences, self.max_len
+            );
+        }
+        writeln!(
+            f,
+            "Conversion changes the language of {} rule(s):",
+            self.divergences.len()
+        )?;
+        for divergence in &self.divergences {
+            writeln!(f, "  {}", divergence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the sentences of up to `max_len` tokens of every rule of
+/// `sebnf` with those of the same rule in `bnf`, its conversion.
+pub fn check_conversion(
+    sebnf: &Sebnf,
+    bnf: &Bnf,
+    max_len: usize,
+) -> Result<ConversionCheck, SelfCheckError> {
+    let tokens = Tokens::of(sebnf, Some(bnf));
+    // The converter spells soft keywords as regexes matching only them
+    let soft_keywords: HashMap<String, &str> = (sebnf.annotations.values())
+        .flat_map(|annotations| &annotations.soft_keywords)
+        .map(|keyword| (soft_keyword_regex(keyword), keyword.as_str()))
+        .collect();
+    let bounded = Bounded { max_len };
+    let sebnf_languages = fixpoint(&sebnf.rules, |rule, alternatives, languages| {
+        bounded
+            .alternatives(alternatives, languages, &tokens)
+            .ok_or_else(|| too_many(rule, max_len))
+    })?;
+    let bnf_languages = fixpoint(&bnf.rules, |rule, productions, languages| {
+        let mut language = Language::new();
+        for production in productions {
+            let mut sequence = Language::from([Word::new()]);
+            for item in production {
+                let item_language = match item {
+                    bnf::Item::NonTerminal(name) => {
+                        languages.get(name).cloned().unwrap_or_default()
+                    }
+                    bnf::Item::EndOfInput => Language::from([Word::new()]),
+                    bnf::Item::Regex(regex) if soft_keywords.contains_key(regex) => {
+                        Language::from([vec![tokens.id(soft_keywords[regex])]])
+                    }
+                    item => Language::from([vec![tokens.id(&item.to_string())]]),
+                };
+                sequence = bounded
+                    .concat(&sequence, &item_language)
+                    .ok_or_else(|| too_many(rule, max_len))?;
+            }
+            language.extend(sequence);
+        }
+        Ok(language)
+    })?;
+
+    let mut divergences = Vec::new();
+    let mut sentences = 0;
+    for rule in sebnf.rules.keys() {
+        let expected = &sebnf_languages[rule];
+        let actual = bnf_languages.get(rule).cloned().unwrap_or_default();
+        sentences += expected.len();
+        let shortest = expected
+            .symmetric_difference(&actual)
+            .map(|word| tokens.sentence(word))
+            .min_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+        if let Some(sentence) = shortest {
+            let word: Word = sentence.iter().map(|token| tokens.id(token)).collect();
+            divergences.push(Divergence {
+                rule: rule.clone(),
+                in_sebnf: expected.contains(&word),
+                sentence,
+            });
+        }
+    }
+    Ok(ConversionCheck {
+        max_len,
+        rules: sebnf.rules.len(),
+        sentences,
+        divergences,
+    })
+}
+
+/// The sentences of up to `max_len` tokens of every rule of `sebnf`.
+pub fn bounded_sentences(
+    sebnf: &Sebnf,
+    max_len: usize,
+) -> Result<IndexMap<String, HashSet<Sentence>>, SelfCheckError> {
+    let tokens = Tokens::of(sebnf, None);
+    let bounded = Bounded { max_len };
+    let languages = fixpoint(&sebnf.rules, |rule, alternatives, languages| {
+        bounded
+            .alternatives(alternatives, languages, &tokens)
+            .ok_or_else(|| too_many(rule, max_len))
+    })?;
+    Ok(languages
+        .into_iter()
+        .map(|(rule, language)| {
+            let sentences = language.iter().map(|word| tokens.sentence(word)).collect();
+            (rule, sentences)
+        })
+        .collect())
+}
+
+/// The terminals and regexes of both grammars, numbered
+struct Tokens {
+    ids: HashMap<String, u32>,
+    names: Vec<String>,
+}
+
+impl Tokens {
+    fn of(sebnf: &Sebnf, bnf: Option<&Bnf>) -> Tokens {
+        struct Collector(Tokens);
+        impl Collector {
+            fn add(&mut self, token: &str) {
+                if !self.0.ids.contains_key(token) {
+                    self.0
+                        .ids
+                        .insert(token.to_string(), self.0.names.len() as u32);
+                    self.0.names.push(token.to_string());
+                }
+            }
+        }
+        impl Visitor for Collector {
+            fn visit_terminal(&mut self, terminal: &str, _span: &std::ops::Range<usize>) {
+                self.add(terminal);
+            }
+            fn visit_regex(&mut self, regex: &str, _span: &std::ops::Range<usize>) {
+                self.add(regex);
+            }
+        }
+
+        let mut collector = Collector(Tokens {
+            ids: HashMap::new(),
+            names: Vec::new(),
+        });
+        walk_sebnf(&mut collector, sebnf);
+        for item in bnf
+            .into_iter()
+            .flat_map(|bnf| bnf.rules.values().flatten().flatten())
+        {
+            if !matches!(item, bnf::Item::NonTerminal(_) | bnf::Item::EndOfInput) {
+                collector.add(&item.to_string());
+            }
+        }
+        collector.0
+    }
+
+    fn id(&self, token: &str) -> u32 {
+        self.ids[token]
+    }
+
+    fn sentence(&self, word: &Word) -> Sentence {
+        word.iter()
+            .map(|&id| self.names[id as usize].clone())
+            .collect()
+    }
+}
+
+fn too_many(rule: &str, max_len: usize) -> SelfCheckError {
+    SelfCheckError::TooManySentences {
+        rule: rule.to_string(),
+        max_len,
+    }
+}
+
+/// Recomputes the language of every rule until none changes. Languages only
+/// grow and are bounded in length, so this terminates.
+fn fixpoint<R>(
+    rules: &IndexMap<String, R>,
+    language_of: impl Fn(&str, &R, &IndexMap<String, Language>) -> Result<Language, SelfCheckError>,
+) -> Result<IndexMap<String, Language>, SelfCheckError> {
+    let mut languages: IndexMap<String, Language> = rules
+        .keys()
+        .map(|rule| (rule.clone(), Language::new()))
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (rule, body) in rules {
+            let language = language_of(rule, body, &languages)?;
+            if language.len() != languages[rule].len() {
+                languages.insert(rule.clone(), language);
+                changed = true;
+            }
+        }
+    }
+    Ok(languages)
+}
+
+/// Operations on sets of sentences of at most `max_len` tokens; `None` once
+/// a set exceeds [`MAX_SENTENCES`].
+struct Bounded {
+    max_len: usize,
+}
+
+impl Bounded {
+    fn concat(&self, prefixes: &Language, suffixes: &Language) -> Option<Language> {
+        let mut by_len: Vec<Vec<&Word>> = vec![Vec::new(); self.max_len + 1];
+        for suffix in suffixes {
+            by_len[suffix.len()].push(suffix);
+        }
+        let mut language = Language::new();
+        for prefix in prefixes {
+            for suffix in by_len[..=self.max_len - prefix.len()].iter().flatten() {
+                language.insert(prefix.iter().chain(*suffix).copied().collect());
+            }
+            if language.len() > MAX_SENTENCES {
+                return None;
+            }
+        }
+        Some(language)
+    }
+
+    fn star(&self, language: &Language) -> Option<Language> {
+        let mut repeated = Language::from([Word::new()]);
+        // Only the words added last round can lead to new ones
+        let mut added = repeated.clone();
+        while !added.is_empty() {
+            added = self
+                .concat(&added, language)?
+                .into_iter()
+                .filter(|word| !repeated.contains(word))
+                .collect();
+            repeated.extend(added.iter().cloned());
+            if repeated.len() > MAX_SENTENCES {
+                return None;
+            }
+        }
+        Some(repeated)
+    }
+
+    fn alternatives(
+        &self,
+        alternatives: &[Vec<Item>],
+        languages: &IndexMap<String, Language>,
+        tokens: &Tokens,
+    ) -> Option<Language> {
+        let mut language = Language::new();
+        for sequence in alternatives {
+            language.extend(self.sequence(sequence, languages, tokens)?);
+            if language.len() > MAX_SENTENCES {
+                return None;
+            }
+        }
+        Some(language)
+    }
+
+    fn sequence(
+        &self,
+        items: &[Item],
+        languages: &IndexMap<String, Language>,
+        tokens: &Tokens,
+    ) -> Option<Language> {
+        let mut language = Language::from([Word::new()]);
+        for item in items {
+            language = self.concat(&language, &self.item(item, languages, tokens)?)?;
+        }
+        Some(language)
+    }
+
+    fn item(
+        &self,
+        item: &Item,
+        languages: &IndexMap<String, Language>,
+        tokens: &Tokens,
+    ) -> Option<Language> {
+        let empty = || Language::from([Word::new()]);
+        match item {
+            Item::NonTerminal(name, _) => Some(languages.get(name).cloned().unwrap_or_default()),
+            Item::Terminal(token, _) | Item::Regex(token, _) => {
+                Some(Language::from([vec![tokens.id(token)]]))
+            }
+            Item::Optional(items, _) => {
+                let mut language = self.sequence(items, languages, tokens)?;
+                language.extend(empty());
+                Some(language)
+            }
+            Item::AnyAmount(items, _) => self.star(&self.sequence(items, languages, tokens)?),
+            Item::Choice(alternatives, _) => self.alternatives(alternatives, languages, tokens),
+            Item::Labeled(_, inner, _) => self.item(inner, languages, tokens),
+            Item::Epsilon(_) => Some(empty()),
+            Item::Builtin(builtin, args, _) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.sequence(arg, languages, tokens))
+                    .collect::<Option<Vec<_>>>()?;
+                match (builtin, args.as_slice()) {
+                    (Builtin::SepBy, [element, separator]) => {
+                        let rest = self.star(&self.concat(separator, element)?)?;
+                        let mut language = self.concat(element, &rest)?;
+                        language.extend(empty());
+                        Some(language)
+                    }
+                    (Builtin::Delimited, [left, element, right]) => {
+                        self.concat(&self.concat(left, element)?, right)
+                    }
+                    (Builtin::NonEmpty, [element]) => self.concat(element, &self.star(element)?),
+                    // The parser rejects calls with the wrong number of arguments
+                    _ => Some(Language::new()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::LoweringOptions;
+
+    #[test]
+    fn conversion_preserves_languages() {
+        let source = "list := delimited(\"[\", sep_by(item, \",\"), \"]\").\n\
+                      item := /[0-9]+/ [ \"!\" ] | non_empty(\"x\") | list | { ( \"a\" | \"b\" ) }.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        for (shared_epsilon, inline_helpers) in [(false, false), (true, false), (false, true)] {
+            let options = LoweringOptions {
+                shared_epsilon,
+                inline_helpers,
+                ..LoweringOptions::default()
+            };
+            let bnf = sebnf.to_bnf_with(&options);
+            let check = check_conversion(&sebnf, &bnf, 5).unwrap();
+            assert!(check.is_ok(), "{}", check);
+            assert!(check.sentences > 100);
+        }
+
+        // A converter that forgot the empty production of `[ ]`
+        let mut bnf = sebnf.to_bnf();
+        let (helper, productions) = bnf
+            .rules
+            .iter_mut()
+            .find(|(name, _)| name.starts_with("___opt"))
+            .unwrap();
+        let helper = helper.clone();
+        productions.retain(|production| !production.is_empty());
+        let check = check_conversion(&sebnf, &bnf, 5).unwrap();
+        assert_eq!(
+            check.divergences[0],
+            Divergence {
+                rule: "list".to_string(),
+                sentence: vec!["\"[\"".into(), "/[0-9]+/".into(), "\"]\"".into()],
+                in_sebnf: true,
+            },
+            "{} lost its empty production",
+            helper
+        );
+    }
+
+    #[test]
+    fn soft_keywords_match_their_regexes() {
+        let source = "@soft_keywords(\"async\")\nf := [ \"async\" ] \"fn\" name.\n\
+                      name := /[a-z]+/ | \"async\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let bnf = sebnf.to_bnf();
+        assert!(bnf.to_string().contains("/async/"));
+        let check = check_conversion(&sebnf, &bnf, 3).unwrap();
+        assert!(check.is_ok(), "{}", check);
+    }
+}
@@ -0,0 +1,611 @@
+//! Generation of sentences of a grammar, e.g. as seed inputs for fuzzers.
+//!
+//! Sentences are sequences of tokens joined by a space. Terminals are spelled
+//! out with their escapes replaced, regexes are replaced by a short string
+//! they match (see [`regex_witness`]), or a random one with
+//! [`GenerateOptions::sample_regexes`], and token classes by their name. Below the maximum
+//! depth productions are picked at random; from there on, each non-terminal
+//! takes the production with the shortest expansion, so generation always
+//! terminates. Rules with a `@max_depth(N)` annotation (see
+//! [`GenerateOptions::rule_depths`]) never occur more than `N` times nested
+//! within themselves.
+//!
+//! [`enumerate`] lists every sentence within a bound instead, in a fixed
+//! order, with regexes and token classes as written.
+//!
+//! The same spelling is used for the [`ConflictInput`] of an LL(1)
+//! conflict, the shortest input that takes a parser to the decision.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::bnf::{Bnf, Item};
+use crate::codegen::unescape;
+use crate::finite::derive_sentences;
+use crate::regex_intersect::{Error as RegexError, RegexSampler, regex_witness};
+use crate::sets::{escape_witness, strip_regex_delimiters, strip_terminal_quotes};
+use crate::symbols::Symbols;
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum GenerateError {
+    #[error("the start rule '{0}' derives no string of terminals")]
+    #[diagnostic(code(sebnf::empty_language))]
+    EmptyLanguage(String),
+    #[error("invalid regex pattern in grammar: {pattern}")]
+    #[diagnostic(code(sebnf::invalid_regex))]
+    InvalidRegex {
+        pattern: String,
+        #[source]
+        source: RegexError,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerateOptions {
+    /// Number of distinct sentences to generate; fewer are returned if the
+    /// grammar does not have that many within `max_depth`
+    pub count: usize,
+    /// Nesting of non-terminals up to which productions are picked at random
+    pub max_depth: usize,
+    /// Same seed, same sentences
+    pub seed: u64,
+    /// Most times a rule occurs nested within itself, usually
+    /// [`Sebnf::max_depths`](crate::Sebnf::max_depths)
+    pub rule_depths: IndexMap<String, usize>,
+    /// Spell each occurrence of a regex by a random string it matches of up
+    /// to [`MAX_SAMPLE_LEN`] bytes, instead of always the same short one
+    pub sample_regexes: bool,
+}
+
+/// Bytes after which a random string for a regex heads for the nearest end
+/// of a match
+pub const MAX_SAMPLE_LEN: usize = 12;
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            count: 100,
+            max_depth: 8,
+            seed: 0,
+            rule_depths: IndexMap::new(),
+            sample_regexes: false,
+        }
+    }
+}
+
+/// For every regex of the grammar, a non-empty string it matches, keyed by the
+/// regex with its delimiters. Regexes that only match the empty string map
+/// to `""`.
+pub fn regex_witnesses(bnf: &Bnf) -> Result<BTreeMap<String, String>, GenerateError> {
+    let mut witnesses = BTreeMap::new();
+    for item in bnf.rules.values().flatten().flatten() {
+        if let Item::Regex(regex) = item
+            && !witnesses.contains_key(regex)
+        {
+            let witness = regex_witness(strip_regex_delimiters(regex)).map_err(|source| {
+                GenerateError::InvalidRegex {
+                    pattern: regex.clone(),
+                    source,
+                }
+            })?;
+            witnesses.insert(regex.clone(), witness.unwrap_or_default());
+        }
+    }
+    Ok(witnesses)
+}
+
+/// Generates up to `options.count` distinct sentences of `bnf`.
+pub fn generate(bnf: &Bnf, options: &GenerateOptions) -> Result<Vec<String>, GenerateError> {
+    let mut generator = Generator::new(bnf, options)?;
+    let mut seen = HashSet::new();
+    let mut sentences = Vec::new();
+    // Small languages run out of new sentences long before `count`
+    for _ in 0..options.count.saturating_mul(10) {
+        if sentences.len() == options.count {
+            break;
+        }
+        let sentence = generator.sentence(options.max_depth);
+        if seen.insert(sentence.clone()) {
+            sentences.push(sentence);
+        }
+    }
+    Ok(sentences)
+}
+
+/// Bounds of [`enumerate`]. Without `max_tokens` and `max_depth` the search
+/// only ends early if the language is finite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnumerateOptions {
+    /// Most tokens of a sentence
+    pub max_tokens: Option<usize>,
+    /// Most rules of the BNF nested in a derivation of a sentence, counting
+    /// generated helper rules, so each repetition of `{ }` adds one
+    pub max_depth: Option<usize>,
+    /// Most sentences any rule may have before the search gives up
+    pub limit: usize,
+}
+
+impl Default for EnumerateOptions {
+    fn default() -> Self {
+        Self {
+            max_tokens: None,
+            max_depth: None,
+            limit: 10_000,
+        }
+    }
+}
+
+/// The sentences [`enumerate`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enumeration {
+    /// Shortest first, then ordered token by token; terminals without their
+    /// quotes, regexes and token classes as written
+    pub sentences: Vec<Vec<String>>,
+    /// False if a rule had more sentences than the limit, and only those of
+    /// shallower derivations are listed
+    pub complete: bool,
+}
+
+impl fmt::Display for Enumeration {
+    /// One sentence per line, its tokens separated by a space, and the empty
+    /// sentence as ε
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for sentence in &self.sentences {
+            if sentence.is_empty() {
+                writeln!(f, "{}", Symbols::with_current(|s| s.epsilon.clone()))?;
+            } else {
+                writeln!(f, "{}", sentence.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lists every sentence of `bnf` within the bounds of `options`, the same
+/// ones in the same order on every run.
+pub fn enumerate(bnf: &Bnf, options: &EnumerateOptions) -> Enumeration {
+    let Some(start) = bnf.rules.keys().next() else {
+        return Enumeration {
+            sentences: Vec::new(),
+            complete: true,
+        };
+    };
+    let (sentences, complete) = derive_sentences(
+        bnf,
+        start,
+        |_| true,
+        options.max_tokens.unwrap_or(usize::MAX),
+        options.max_depth.unwrap_or(usize::MAX),
+        options.limit,
+    );
+    Enumeration {
+        sentences,
+        complete,
+    }
+}
+
+/// Input that leads a parser from the start rule to the decision of an
+/// LL(1) conflict, set by [`Bnf::annotate_input`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConflictInput {
+    /// The fewest tokens after which the parser has to pick a production of
+    /// the conflicting rule
+    pub prefix: Vec<String>,
+    /// A token both productions accept next; `None` for the end of input
+    pub next: Option<String>,
+}
+
+impl ConflictInput {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "prefix": self.prefix,
+            "next": self.next,
+        })
+    }
+}
+
+impl fmt::Display for ConflictInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (dot, end) = Symbols::with_current(|s| (s.glyph("•", "."), s.end_of_input.clone()));
+        for token in &self.prefix {
+            write!(f, "{} ", escape_witness(token))?;
+        }
+        match &self.next {
+            Some(next) => write!(f, "{} {}", dot, escape_witness(next)),
+            None => write!(f, "{} {}", dot, end),
+        }
+    }
+}
+
+/// For every rule a parser can reach from the start rule, the fewest tokens
+/// it reads before expanding that rule, spelled like generated sentences:
+/// the shortest `w` with `S ⇒* w A β` in a leftmost derivation, where `A β`
+/// still derives a string of terminals.
+pub(crate) fn shortest_prefixes(bnf: &Bnf) -> Result<HashMap<&str, Vec<String>>, GenerateError> {
+    let witnesses = regex_witnesses(bnf)?;
+    let shortest = shortest_productions(bnf);
+    let cost = |item: &Item| match item {
+        Item::NonTerminal(name) => shortest.get(name.as_str()).map(|(cost, _)| *cost),
+        Item::EndOfInput => Some(0),
+        _ => Some(1),
+    };
+
+    // Dijkstra from the start rule; each rule is reached through the items
+    // of a production before it
+    let mut distance: HashMap<&str, usize> = HashMap::new();
+    let mut previous: HashMap<&str, (&str, &[Item])> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    if let Some(start) = bnf.rules.keys().next()
+        && shortest.contains_key(start.as_str())
+    {
+        distance.insert(start, 0);
+        queue.push(Reverse((0, 0)));
+    }
+    while let Some(Reverse((reached, index))) = queue.pop() {
+        let (rule, productions) = bnf.rules.get_index(index).expect("queued by index");
+        if distance[rule.as_str()] < reached {
+            continue;
+        }
+        for production in productions {
+            if production.iter().any(|item| cost(item).is_none()) {
+                continue;
+            }
+            let mut before = 0;
+            for (i, item) in production.iter().enumerate() {
+                if let Item::NonTerminal(nt) = item
+                    && let Some(next) = bnf.rules.get_index_of(nt)
+                    && distance
+                        .get(nt.as_str())
+                        .is_none_or(|&d| reached + before < d)
+                {
+                    distance.insert(nt, reached + before);
+                    previous.insert(nt, (rule, &production[..i]));
+                    queue.push(Reverse((reached + before, next)));
+                }
+                before += cost(item).unwrap_or_default();
+            }
+        }
+    }
+
+    let mut prefixes = HashMap::new();
+    for &rule in distance.keys() {
+        let mut parts = Vec::new();
+        let mut current = rule;
+        while let Some(&(before, items)) = previous.get(current) {
+            parts.push(items);
+            current = before;
+        }
+        let mut tokens = Vec::new();
+        for items in parts.into_iter().rev() {
+            spell_shortest(items, &shortest, &witnesses, &mut tokens);
+        }
+        prefixes.insert(rule, tokens);
+    }
+    Ok(prefixes)
+}
+
+/// Fewest tokens each productive non-terminal derives, with a production
+/// that does. A production is only replaced by one that derives fewer
+/// tokens, so following them always ends.
+fn shortest_productions(bnf: &Bnf) -> HashMap<&str, (usize, &Vec<Item>)> {
+    let mut shortest: HashMap<&str, (usize, &Vec<Item>)> = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (nt, productions) in &bnf.rules {
+            let costs: Costs = shortest
+                .iter()
+                .map(|(nt, (cost, _))| (*nt, *cost))
+                .collect();
+            let best = (productions.iter())
+                .filter_map(|p| Some((production_cost(&costs, p)?, p)))
+                .min_by_key(|(cost, _)| *cost);
+            if let Some((cost, production)) = best
+                && shortest.get(nt.as_str()).is_none_or(|(c, _)| cost < *c)
+            {
+                shortest.insert(nt, (cost, production));
+                changed = true;
+            }
+        }
+    }
+    shortest
+}
+
+/// Appends the tokens of a shortest string `items` derive to `tokens`
+fn spell_shortest(
+    items: &[Item],
+    shortest: &HashMap<&str, (usize, &Vec<Item>)>,
+    witnesses: &BTreeMap<String, String>,
+    tokens: &mut Vec<String>,
+) {
+    for item in items {
+        match item {
+            Item::NonTerminal(name) => {
+                let (_, production) = shortest[name.as_str()];
+                spell_shortest(production, shortest, witnesses, tokens);
+            }
+            Item::Terminal(terminal) => tokens.push(unescape(strip_terminal_quotes(terminal))),
+            // A regex matching only the empty string adds nothing to type
+            Item::Regex(regex) if witnesses[regex].is_empty() => {}
+            Item::Regex(regex) => tokens.push(witnesses[regex].clone()),
+            Item::TokenClass(class) => tokens.push(class.clone()),
+            Item::EndOfInput => {}
+        }
+    }
+}
+
+/// Fewest tokens each productive non-terminal derives
+type Costs<'a> = HashMap<&'a str, usize>;
+
+struct Generator<'a> {
+    bnf: &'a Bnf,
+    /// Costs without using the rules of the key, which have reached their
+    /// `@max_depth`
+    costs: HashMap<Vec<&'a str>, Costs<'a>>,
+    rule_depths: HashMap<&'a str, usize>,
+    /// How often each rule occurs on the current expansion path
+    nesting: HashMap<&'a str, usize>,
+    witnesses: BTreeMap<String, String>,
+    /// Set if regexes are spelled by random strings
+    samplers: BTreeMap<String, RegexSampler>,
+    state: u64,
+}
+
+impl<'a> Generator<'a> {
+    fn new(bnf: &'a Bnf, options: &GenerateOptions) -> Result<Self, GenerateError> {
+        let rule_depths = options
+            .rule_depths
+            .iter()
+            .filter_map(|(rule, depth)| Some((bnf.rules.get_key_value(rule)?.0.as_str(), *depth)))
+            .collect();
+        let generator = Generator {
+            bnf,
+            costs: HashMap::from([(Vec::new(), costs_without(bnf, &[]))]),
+            rule_depths,
+            nesting: HashMap::new(),
+            witnesses: regex_witnesses(bnf)?,
+            samplers: if options.sample_regexes {
+                regex_samplers(bnf)?
+            } else {
+                BTreeMap::new()
+            },
+            // xorshift must not start at zero
+            state: options.seed ^ 0x9e37_79b9_7f4a_7c15,
+        };
+        match bnf.rules.keys().next() {
+            Some(start) if !generator.costs[&Vec::new()].contains_key(start.as_str()) => {
+                Err(GenerateError::EmptyLanguage(start.clone()))
+            }
+            _ => Ok(generator),
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        xorshift(&mut self.state)
+    }
+
+    fn sentence(&mut self, max_depth: usize) -> String {
+        let mut tokens = Vec::new();
+        if let Some(start) = self.bnf.rules.keys().next() {
+            self.expand(start, 0, max_depth, &mut tokens);
+        }
+        tokens.join(" ")
+    }
+
+    fn expand(&mut self, nt: &'a str, depth: usize, max_depth: usize, tokens: &mut Vec<String>) {
+        let bnf = self.bnf;
+        *self.nesting.entry(nt).or_default() += 1;
+        // A rule at its limit must not occur again below; a shortest
+        // expansion never nests a rule within itself, so one without the
+        // banned rules is still found.
+        let mut banned: Vec<&'a str> = self
+            .rule_depths
+            .iter()
+            .filter(|(rule, limit)| self.nesting.get(*rule).is_some_and(|n| n >= limit))
+            .map(|(rule, _)| *rule)
+            .collect();
+        banned.sort_unstable();
+        let costs = self
+            .costs
+            .entry(banned)
+            .or_insert_with_key(|banned| costs_without(bnf, banned));
+        let candidates: Vec<(&'a Vec<Item>, usize)> = bnf.rules[nt]
+            .iter()
+            .filter_map(|p| Some((p, production_cost(costs, p)?)))
+            .collect();
+        let production = if depth < max_depth {
+            let index = (self.next_random() % candidates.len() as u64) as usize;
+            candidates[index].0
+        } else {
+            candidates
+                .iter()
+                .min_by_key(|(_, cost)| *cost)
+                .expect("only productive rules are expanded")
+                .0
+        };
+        for item in production {
+            match item {
+                Item::NonTerminal(name) => self.expand(name, depth + 1, max_depth, tokens),
+                Item::Terminal(terminal) => tokens.push(unescape(strip_terminal_quotes(terminal))),
+                Item::Regex(regex) => match self.samplers.get(regex) {
+                    Some(sampler) => {
+                        let sample = sampler.sample(MAX_SAMPLE_LEN, || xorshift(&mut self.state));
+                        tokens.extend(sample.filter(|sample| !sample.is_empty()));
+                    }
+                    None => tokens.push(self.witnesses[regex].clone()),
+                },
+                Item::TokenClass(class) => tokens.push(class.clone()),
+                Item::EndOfInput => {}
+            }
+        }
+        *self.nesting.entry(nt).or_default() -= 1;
+    }
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+fn regex_samplers(bnf: &Bnf) -> Result<BTreeMap<String, RegexSampler>, GenerateError> {
+    let mut samplers = BTreeMap::new();
+    for item in bnf.rules.values().flatten().flatten() {
+        if let Item::Regex(regex) = item
+            && !samplers.contains_key(regex)
+        {
+            let sampler = RegexSampler::new(strip_regex_delimiters(regex)).map_err(|source| {
+                GenerateError::InvalidRegex {
+                    pattern: regex.clone(),
+                    source,
+                }
+            })?;
+            samplers.insert(regex.clone(), sampler);
+        }
+    }
+    Ok(samplers)
+}
+
+/// Fewest tokens each non-terminal derives without using the `banned` rules
+fn costs_without<'a>(bnf: &'a Bnf, banned: &[&str]) -> Costs<'a> {
+    let mut costs = Costs::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (nt, productions) in &bnf.rules {
+            if banned.contains(&nt.as_str()) {
+                continue;
+            }
+            let cost = productions
+                .iter()
+                .filter_map(|p| production_cost(&costs, p))
+                .min();
+            if let Some(cost) = cost
+                && costs.get(nt.as_str()).is_none_or(|c| cost < *c)
+            {
+                costs.insert(nt, cost);
+                changed = true;
+            }
+        }
+    }
+    costs
+}
+
+/// Fewest tokens `production` derives, `None` if it derives no sentence
+fn production_cost(costs: &Costs, production: &[Item]) -> Option<usize> {
+    production.iter().try_fold(0, |sum, item| match item {
+        Item::NonTerminal(name) => Some(sum + costs.get(name.as_str())?),
+        Item::EndOfInput => Some(sum),
+        _ => Some(sum + 1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+    use crate::interpret::{ParseOptions, parse_input};
+
+    #[test]
+    fn generates_distinct_sentences_of_the_grammar() {
+        let source = "list := \"[\" [ item { \",\" item } ] \"]\".\nitem := /[0-9]+/ | list.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let options = GenerateOptions {
+            count: 10,
+            max_depth: 4,
+            seed: 7,
+            ..GenerateOptions::default()
+        };
+        let sentences = generate(&bnf, &options).unwrap();
+        assert_eq!(sentences.len(), 10);
+        assert!(sentences.contains(&"[ ]".to_string()));
+        for sentence in &sentences {
+            assert!(sentence.starts_with("[ ") && sentence.ends_with(" ]"));
+        }
+        assert_eq!(generate(&bnf, &options).unwrap(), sentences);
+
+        // Only two sentences exist
+        let bnf = Sebnf::parse_str("s := \"a\" [ /b+/ ].", "g")
+            .unwrap()
+            .to_bnf();
+        assert_eq!(
+            generate(&bnf, &GenerateOptions::default()).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn generated_sentences_parse_back() {
+        let source = "s := \"say\" \"\\\"\" /[a-z]+/ \"\\\"\" { \"\\\\\" \"n\" }.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let options = GenerateOptions {
+            count: 10,
+            sample_regexes: true,
+            ..GenerateOptions::default()
+        };
+        for sentence in generate(&sebnf.to_bnf(), &options).unwrap() {
+            assert!(sentence.starts_with("say \" "), "{}", sentence);
+            parse_input(&sebnf, &sentence, "sentence", &ParseOptions::default()).unwrap();
+        }
+    }
+
+    #[test]
+    fn enumerates_sentences_within_the_bounds() {
+        let bnf = Sebnf::parse_str("s := [ \"a\" ] \"b\" | s \"c\".", "g")
+            .unwrap()
+            .to_bnf();
+        let spell = |options: &EnumerateOptions| enumerate(&bnf, options).to_string();
+        let options = EnumerateOptions {
+            max_tokens: Some(3),
+            ..EnumerateOptions::default()
+        };
+        assert_eq!(spell(&options), "b\na b\nb c\na b c\nb c c\n");
+        let options = EnumerateOptions {
+            max_depth: Some(3),
+            ..EnumerateOptions::default()
+        };
+        assert_eq!(spell(&options), "b\na b\nb c\na b c\n");
+
+        let options = EnumerateOptions {
+            limit: 20,
+            ..EnumerateOptions::default()
+        };
+        assert!(!enumerate(&bnf, &options).complete);
+    }
+
+    #[test]
+    fn conflict_input_leads_to_the_decision() {
+        let source = "s := \"begin\" { stmt } \"end\".\n\
+                      stmt := \"print\" expr \";\" | id \"=\" expr \";\".\n\
+                      expr := id | id \"(\" \")\".\nid := /[a-z]+/.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let mut result = bnf.is_ll1(false).unwrap();
+        bnf.annotate_input(&mut result).unwrap();
+        let input = result.conflicts[0].input.as_ref().unwrap();
+        assert_eq!(input.prefix, ["begin", "print"]);
+        assert_eq!(input.to_string(), "begin print • a");
+    }
+
+    #[test]
+    fn max_depth_annotation_bounds_nesting() {
+        let source =
+            "s := \"(\" s \")\" | \"x\" | \"(\" t \")\".\n@max_depth(2)\nt := \"[\" t \"]\" | s.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let options = GenerateOptions {
+            count: 50,
+            max_depth: 20,
+            rule_depths: sebnf.max_depths(),
+            ..GenerateOptions::default()
+        };
+        let sentences = generate(&sebnf.to_bnf(), &options).unwrap();
+        assert!(sentences.iter().any(|s| s.contains("[ ")));
+        for sentence in sentences {
+            assert!(!sentence.contains("[ ["), "{}", sentence);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! A small HTTP server with a JSON API and a single-page UI, so the analyzer
+//! can be shared without a Rust toolchain.
+//!
+//! Every API endpoint takes `POST` with a body like
+//! `{"grammar": "...", "ignore_regex_conflicts": false}` and answers with the
+//! result of [`api::analyze`]: `/api/validate`, `/api/to-bnf`, `/api/sets`
+//! and `/api/is-ll1`.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use sebnf_tool::api::{self, Endpoint, Options};
+use sebnf_tool::symbols::Symbols;
+use sebnf_tool::token_class::TokenClasses;
+
+const INDEX_HTML: &str = include_str!("serve.html");
+
+/// Requests with a larger body are rejected
+const MAX_BODY: usize = 1 << 20;
+/// Requests with a longer request line or header line are rejected
+const MAX_LINE: usize = 8 << 10;
+/// Requests whose header lines add up to more are rejected
+const MAX_HEADERS: usize = 64 << 10;
+/// Connections served at the same time; further clients get a 503
+const MAX_CONNECTIONS: usize = 32;
+/// How long a client may take to send its request or read the response
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// How long an analysis may take before the client gets a 503. The analysis
+/// cannot be interrupted and finishes in the background, still counted
+/// against [`MAX_CONNECTIONS`] analyses at a time.
+const ANALYSIS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connections being served
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+/// Analyses running, including those whose client got a 503
+static ANALYSES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, value: &Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: value.to_string(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, &json!({ "error": message }))
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Prepares grammars the same way the non-interactive commands do.
+#[derive(Clone)]
+pub struct Server {
+    pub token_classes: TokenClasses,
+    pub augment_start: bool,
+    pub symbols: Symbols,
+}
+
+impl Server {
+    /// Serves until the process is killed, one thread per connection and at
+    /// most [`MAX_CONNECTIONS`] at a time.
+    pub fn run(self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            // A slow client times out instead of holding its thread forever
+            if stream.set_read_timeout(Some(TIMEOUT)).is_err()
+                || stream.set_write_timeout(Some(TIMEOUT)).is_err()
+            {
+                continue;
+            }
+            let Some(open) = Open::acquire(&CONNECTIONS) else {
+                let busy = Response::error(503, "too many connections");
+                let _ = write_response(stream, &busy);
+                continue;
+            };
+            let server = self.clone();
+            thread::spawn(move || {
+                let _open = open;
+                let symbols = server.symbols.clone();
+                // A broken connection only affects its own client
+                let _ = symbols.scope(|| server.serve_connection(stream));
+            });
+        }
+        Ok(())
+    }
+
+    fn serve_connection(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let response = match read_request(&mut reader) {
+            Ok((method, path, body)) => self.handle(&method, &path, &body),
+            Err(response) => response,
+        };
+        write_response(stream, &response)
+    }
+
+    pub fn handle(&self, method: &str, path: &str, body: &[u8]) -> Response {
+        // Query strings are not used
+        let path = path.split('?').next().unwrap_or(path);
+        match (method, path) {
+            ("GET", "/") => Response {
+                status: 200,
+                content_type: "text/html",
+                body: INDEX_HTML.to_string(),
+            },
+            (_, "/") => Response::error(405, "method not allowed"),
+            _ => match (method, api_endpoint(path)) {
+                ("POST", Some(endpoint)) => self.api(endpoint, body),
+                (_, Some(_)) => Response::error(405, "method not allowed"),
+                (_, None) => Response::error(404, "not found"),
+            },
+        }
+    }
+
+    fn api(&self, endpoint: Endpoint, body: &[u8]) -> Response {
+        let request: Value = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(e) => return Response::error(400, &format!("invalid JSON: {}", e)),
+        };
+        let Some(grammar) = request["grammar"].as_str() else {
+            return Response::error(400, "missing string field 'grammar'");
+        };
+        let options = Options {
+            token_classes: self.token_classes.clone(),
+            augment_start: self.augment_start,
+            ignore_regex_conflicts: request["ignore_regex_conflicts"].as_bool().unwrap_or(false),
+        };
+        let Some(open) = Open::acquire(&ANALYSES) else {
+            return Response::error(503, "too many analyses running");
+        };
+        // On a worker, so that a slow analysis can be abandoned
+        let (sender, receiver) = mpsc::channel();
+        let grammar = grammar.to_string();
+        let symbols = Symbols::with_current(Symbols::clone);
+        thread::spawn(move || {
+            let _open = open;
+            let result = symbols.scope(|| api::analyze(endpoint, &grammar, &options));
+            let _ = sender.send(result);
+        });
+        match receiver.recv_timeout(ANALYSIS_TIMEOUT) {
+            Ok(result) => Response::json(200, &result),
+            Err(mpsc::RecvTimeoutError::Timeout) => Response::error(
+                503,
+                &format!(
+                    "analysis took longer than {} seconds",
+                    ANALYSIS_TIMEOUT.as_secs()
+                ),
+            ),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Response::error(500, "analysis failed"),
+        }
+    }
+}
+
+/// Counts one of at most [`MAX_CONNECTIONS`] connections or analyses as
+/// running until dropped
+struct Open(&'static AtomicUsize);
+
+impl Open {
+    fn acquire(counter: &'static AtomicUsize) -> Option<Self> {
+        if counter.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            counter.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(Self(counter))
+    }
+}
+
+impl Drop for Open {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn write_response(mut stream: TcpStream, response: &Response) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(response.body.as_bytes())?;
+    stream.flush()
+}
+
+fn api_endpoint(path: &str) -> Option<Endpoint> {
+    path.strip_prefix("/api/").and_then(Endpoint::from_name)
+}
+
+/// Reads one line of at most [`MAX_LINE`] bytes, without reading further
+/// into a longer one.
+fn read_line(reader: &mut impl BufRead) -> Result<String, Response> {
+    let mut line = String::new();
+    (reader.take(MAX_LINE as u64 + 1))
+        .read_line(&mut line)
+        .map_err(|_| Response::error(400, "malformed request"))?;
+    if line.len() > MAX_LINE {
+        return Err(Response::error(431, "request line or header too long"));
+    }
+    Ok(line)
+}
+
+/// Reads one request, returning its method, path and body, or the error
+/// response to send instead.
+fn read_request(reader: &mut impl BufRead) -> Result<(String, String, Vec<u8>), Response> {
+    let bad_request = |_| Response::error(400, "malformed request");
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(Response::error(400, "malformed request line"));
+    };
+
+    let mut content_length = 0;
+    let mut header_size = 0;
+    loop {
+        let header = read_line(reader)?;
+        header_size += header.len();
+        if header_size > MAX_HEADERS {
+            return Err(Response::error(431, "request headers too large"));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value
+                .trim()
+                .parse()
+                .map_err(|_| Response::error(400, "invalid Content-Length"))?;
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(Response::error(413, "request body too large"));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(bad_request)?;
+    Ok((method.to_string(), path.to_string(), body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> Server {
+        Server {
+            token_classes: TokenClasses::default(),
+            augment_start: false,
+            symbols: Symbols::default(),
+        }
+    }
+
+    #[test]
+    fn api_passes_request_options() {
+        let body = json!({
+            "grammar": "a := /x/ | /x+/.",
+            "ignore_regex_conflicts": true,
+        })
+        .to_string();
+        let response = server().handle("POST", "/api/is-ll1", body.as_bytes());
+        assert_eq!(response.status, 200);
+        let value: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(value["ll1"], true);
+        assert_eq!(server().handle("POST", "/api/nope", b"{}").status, 404);
+    }
+
+    #[test]
+    fn parses_requests() {
+        let mut request =
+            "POST /api/sets HTTP/1.1\r\nHost: x\r\ncontent-length: 2\r\n\r\n{}".as_bytes();
+        let (method, path, body) = read_request(&mut request).unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("POST", "/api/sets"));
+        assert_eq!(body, b"{}");
+        let response = server().handle(&method, &path, &body);
+        assert_eq!(response.status, 400);
+        assert_eq!(server().handle("GET", "/api/sets", b"").status, 405);
+    }
+
+    #[test]
+    fn rejects_oversized_requests() {
+        let status = |request: String| read_request(&mut request.as_bytes()).unwrap_err().status;
+
+        let long_path = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(status(long_path), 431);
+        let long_header = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(status(long_header), 431);
+        let headers = "X: a\r\n".repeat(MAX_HEADERS / 6 + 1);
+        assert_eq!(status(format!("GET / HTTP/1.1\r\n{}\r\n", headers)), 431);
+        let body = format!(
+            "POST /api/sets HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert_eq!(status(body), 413);
+    }
+}
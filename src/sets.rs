@@ -2,28 +2,52 @@ use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 use crate::bnf::*;
-use crate::regex_intersect::{Error as RegexError, do_regexs_intersect, regex_matches_empty};
-
+use crate::lookahead::{AmbiguityWindow, LookaheadResolution};
+use crate::regex_intersect::{
+    Error as RegexError, Intersection, WitnessOptions, regex_matches_empty, regexs_intersection,
+};
+use crate::render::{RenderError, render_display};
+use crate::sentences::ConflictInput;
+use crate::symbols::Symbols;
+
+/// FIRST and FOLLOW sets, keyed by non-terminal.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sets {
     pub first: HashMap<String, HashSet<SetItem>>,
     pub follow: HashMap<String, HashSet<SetItem>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// An element of a FIRST or FOLLOW set.
+///
+/// The derived order (terminals, regexes, token classes, ε, then $, each
+/// group sorted by its spelling) is the order of every printed set, so
+/// outputs do not depend on hashing or on the configured symbols.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SetItem {
     Terminal(String),
     Regex(String),
+    TokenClass(String),
     Epsilon,
     EndOfInput,
 }
 
-impl From<&Item> for SetItem {
-    fn from(item: &Item) -> Self {
+/// Returned when converting a non-terminal `Item` into a `SetItem`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("non-terminal '{0}' cannot be converted to a set item")]
+pub struct NonTerminalSetItem(pub String);
+
+impl TryFrom<&Item> for SetItem {
+    type Error = NonTerminalSetItem;
+
+    fn try_from(item: &Item) -> Result<Self, Self::Error> {
         match item {
-            Item::Terminal(s) => SetItem::Terminal(s.clone()),
-            Item::Regex(s) => SetItem::Regex(s.clone()),
-            Item::NonTerminal(_) => panic!("NonTerminal cannot be converted to SetItem"),
+            Item::Terminal(s) => Ok(SetItem::Terminal(s.clone())),
+            Item::Regex(s) => Ok(SetItem::Regex(s.clone())),
+            Item::TokenClass(s) => Ok(SetItem::TokenClass(s.clone())),
+            Item::EndOfInput => Ok(SetItem::EndOfInput),
+            Item::NonTerminal(s) => Err(NonTerminalSetItem(s.clone())),
         }
     }
 }
@@ -36,46 +60,81 @@ impl fmt::Display for SetItem {
                 write!(f, "{}", s)
             }
             SetItem::Regex(s) => write!(f, "{}", s),
-            SetItem::Epsilon => write!(f, "ε"),
-            SetItem::EndOfInput => write!(f, "$"),
+            SetItem::TokenClass(s) => write!(f, "{}", s),
+            SetItem::Epsilon => Symbols::with_current(|s| write!(f, "{}", s.epsilon)),
+            SetItem::EndOfInput => Symbols::with_current(|s| write!(f, "{}", s.end_of_input)),
+        }
+    }
+}
+
+impl Sets {
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
+
+    /// JSON object `{"first": {nt: [items]}, "follow": {...}}` with sorted keys and items
+    pub fn to_json(&self) -> serde_json::Value {
+        fn set_map(sets: &HashMap<String, HashSet<SetItem>>) -> serde_json::Value {
+            let mut entries: Vec<_> = sets.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let map: serde_json::Map<_, _> = entries
+                .into_iter()
+                .map(|(nt, items)| {
+                    let items: Vec<_> = sorted(items).iter().map(|i| i.to_string()).collect();
+                    (nt.clone(), serde_json::Value::from(items))
+                })
+                .collect();
+            serde_json::Value::Object(map)
+        }
+
+        serde_json::json!({
+            "first": set_map(&self.first),
+            "follow": set_map(&self.follow),
+        })
+    }
+}
+
+/// The items of `set` in [`SetItem`] order
+pub fn sorted(set: &HashSet<SetItem>) -> Vec<&SetItem> {
+    let mut items: Vec<_> = set.iter().collect();
+    items.sort();
+    items
+}
+
+fn write_set_map(
+    f: &mut fmt::Formatter<'_>,
+    sets: &HashMap<String, HashSet<SetItem>>,
+) -> fmt::Result {
+    let mut entries: Vec<_> = sets.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, items) in entries {
+        writeln!(f, "  {}:", key)?;
+        for item in sorted(items) {
+            writeln!(f, "    {}", item)?;
         }
     }
+    Ok(())
 }
 
 impl fmt::Display for Sets {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "FIRST Sets:")?;
-        let mut first_keys: Vec<_> = self.first.keys().collect();
-        first_keys.sort();
-        for key in first_keys {
-            let items = &self.first[key];
-            let mut sorted_items: Vec<_> = items.iter().map(|i| i.to_string()).collect();
-            sorted_items.sort();
-            writeln!(f, "  {}:", key)?;
-            for item in &sorted_items {
-                writeln!(f, "    {}", item)?;
-            }
-        }
+        write_set_map(f, &self.first)?;
 
         writeln!(f)?;
         writeln!(f, "FOLLOW Sets:")?;
-        let mut follow_keys: Vec<_> = self.follow.keys().collect();
-        follow_keys.sort();
-        for key in follow_keys {
-            let items = &self.follow[key];
-            let mut sorted_items: Vec<_> = items.iter().map(|i| i.to_string()).collect();
-            sorted_items.sort();
-            writeln!(f, "  {}:", key)?;
-            for item in &sorted_items {
-                writeln!(f, "    {}", item)?;
-            }
-        }
+        write_set_map(f, &self.follow)?;
 
         Ok(())
     }
 }
 
+/// Outcome of [`Bnf::is_ll1`]: the grammar is LL(1) iff there are no conflicts.
+///
+/// Conflicts are ordered by rule, then by `production_indices`, then
+/// FIRST/FIRST before FIRST/FOLLOW, so their numbers are stable across runs.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ll1Result {
     pub conflicts: Vec<Ll1Conflict>,
 }
@@ -84,6 +143,18 @@ impl Ll1Result {
     pub fn is_ll1(&self) -> bool {
         self.conflicts.is_empty()
     }
+
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
+
+    /// JSON object `{"ll1": bool, "conflicts": [...]}`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "ll1": self.is_ll1(),
+            "conflicts": self.conflicts.iter().map(Ll1Conflict::to_json).collect::<Vec<_>>(),
+        })
+    }
 }
 
 impl fmt::Display for Ll1Result {
@@ -105,10 +176,43 @@ impl fmt::Display for Ll1Result {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ll1Conflict {
     pub non_terminal: String,
     pub kind: Ll1ConflictKind,
+    /// Indices of the two productions of `kind` in the rule of `non_terminal`
+    pub production_indices: (usize, usize),
     pub conflicts: Vec<SetItemConflict>,
+    /// Set by `Bnf::annotate_lookahead`
+    pub lookahead: Option<LookaheadResolution>,
+    /// Set by `Bnf::annotate_ambiguity`
+    pub ambiguity: Option<AmbiguityWindow>,
+    /// Set by `Bnf::annotate_input`
+    pub input: Option<ConflictInput>,
+}
+
+impl Ll1Conflict {
+    pub fn render(&self) -> Result<String, RenderError> {
+        render_display(self)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let kind = match self.kind {
+            Ll1ConflictKind::FirstFirst { .. } => "first_first",
+            Ll1ConflictKind::FirstFollow { .. } => "first_follow",
+        };
+        let (production1, production2) = self.kind.productions();
+        serde_json::json!({
+            "non_terminal": self.non_terminal,
+            "kind": kind,
+            "productions": [format_production(production1), format_production(production2)],
+            "production_indices": [self.production_indices.0, self.production_indices.1],
+            "conflicts": self.conflicts.iter().map(SetItemConflict::to_json).collect::<Vec<_>>(),
+            "lookahead": self.lookahead.as_ref().map(|l| l.to_string()),
+            "ambiguity": self.ambiguity.as_ref().map(AmbiguityWindow::to_json),
+            "input": self.input.as_ref().map(ConflictInput::to_json),
+        })
+    }
 }
 
 impl fmt::Display for Ll1Conflict {
@@ -144,13 +248,22 @@ impl fmt::Display for Ll1Conflict {
         for conflict in &self.conflicts {
             writeln!(f, "     - {}", conflict)?;
         }
+        if let Some(lookahead) = &self.lookahead {
+            writeln!(f, "   Lookahead: {}", lookahead)?;
+        }
+        if let Some(ambiguity) = &self.ambiguity {
+            writeln!(f, "   Ambiguity: {}", ambiguity)?;
+        }
+        if let Some(input) = &self.input {
+            writeln!(f, "   Input: {}", input)?;
+        }
         Ok(())
     }
 }
 
-fn format_production(items: &[Item]) -> String {
+pub(crate) fn format_production(items: &[Item]) -> String {
     if items.is_empty() {
-        "ε".to_string()
+        Symbols::with_current(|s| s.epsilon.clone())
     } else {
         items
             .iter()
@@ -161,6 +274,7 @@ fn format_production(items: &[Item]) -> String {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Ll1ConflictKind {
     /// Two productions have overlapping FIRST sets
     FirstFirst {
@@ -174,6 +288,22 @@ pub enum Ll1ConflictKind {
     },
 }
 
+impl Ll1ConflictKind {
+    /// The two productions the parser cannot choose between
+    pub fn productions(&self) -> (&[Item], &[Item]) {
+        match self {
+            Ll1ConflictKind::FirstFirst {
+                production1,
+                production2,
+            } => (production1, production2),
+            Ll1ConflictKind::FirstFollow {
+                nullable_production,
+                other_production,
+            } => (nullable_production, other_production),
+        }
+    }
+}
+
 /// Returns (FIRST(sequence) without ε, sequence_is_nullable)
 pub fn first_of_sequence(
     sequence: &[Item],
@@ -184,13 +314,13 @@ pub fn first_of_sequence(
 
     for item in sequence {
         match item {
-            Item::Terminal(_) => {
-                firsts.insert(SetItem::from(item));
+            Item::Terminal(s) => {
+                firsts.insert(SetItem::Terminal(s.clone()));
                 nullable = false;
                 break;
             }
             Item::Regex(pattern) => {
-                firsts.insert(SetItem::from(item));
+                firsts.insert(SetItem::Regex(pattern.clone()));
                 let pattern = strip_regex_delimiters(pattern);
                 if !regex_matches_empty(pattern) {
                     nullable = false;
@@ -198,6 +328,17 @@ pub fn first_of_sequence(
                 }
                 // Regex can match empty, continue to next item
             }
+            // A token class always stands for exactly one token
+            Item::TokenClass(class) => {
+                firsts.insert(SetItem::TokenClass(class.clone()));
+                nullable = false;
+                break;
+            }
+            Item::EndOfInput => {
+                firsts.insert(SetItem::EndOfInput);
+                nullable = false;
+                break;
+            }
             Item::NonTerminal(nt) => {
                 let nt_firsts = first_sets.get(nt).cloned().unwrap_or_default();
                 let has_epsilon = nt_firsts.contains(&SetItem::Epsilon);
@@ -227,37 +368,93 @@ pub enum Ll1Error {
     },
 }
 
-fn strip_regex_delimiters(s: &str) -> &str {
+/// Returned for a start symbol that is not a rule of the grammar.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, miette::Diagnostic)]
+#[error("no rule named '{0}' to start from")]
+#[diagnostic(
+    code(sebnf::unknown_start),
+    help("the start symbol must be one of the grammar's rules")
+)]
+pub struct UnknownStart(pub String);
+
+pub(crate) fn strip_regex_delimiters(s: &str) -> &str {
     s.strip_prefix('/')
         .unwrap_or(s)
         .strip_suffix('/')
         .unwrap_or(s)
 }
 
-fn strip_terminal_quotes(s: &str) -> &str {
+pub(crate) fn strip_terminal_quotes(s: &str) -> &str {
     s.strip_prefix('"')
         .unwrap_or(s)
         .strip_suffix('"')
         .unwrap_or(s)
 }
 
-#[derive(Debug, Clone)]
+/// Ordered by the two items, then by the witness.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SetItemConflict {
     pub item1: SetItem,
     pub item2: SetItem,
     pub witness: Option<String>,
+    /// Set instead of the witness when none of at most this many bytes
+    /// exists, under a [`WitnessOptions`] limit
+    pub witness_longer_than: Option<usize>,
+}
+
+impl SetItemConflict {
+    /// The witness is given both raw and escaped, since control characters
+    /// in the raw form are invisible in most viewers.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "item1": self.item1.to_string(),
+            "item2": self.item2.to_string(),
+            "witness": self.witness.as_ref().map(|w| serde_json::json!({
+                "raw": w,
+                "escaped": escape_witness(w),
+            })),
+            "witness_longer_than": self.witness_longer_than,
+        })
+    }
 }
 
 impl fmt::Display for SetItemConflict {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} ∩ {}", self.item1, self.item2)?;
+        let intersection = Symbols::with_current(|s| s.glyph("∩", "&"));
+        write!(f, "{} {} {}", self.item1, intersection, self.item2)?;
         if let Some(ref w) = self.witness {
-            write!(f, " (e.g., \"{}\")", w)?;
+            write!(f, " (e.g., \"{}\")", escape_witness(w))?;
+        }
+        if let Some(max_len) = self.witness_longer_than {
+            write!(f, " (witness longer than {} bytes)", max_len)?;
         }
         Ok(())
     }
 }
 
+/// Escapes a witness string like a Rust string literal (`\t`, `\"`, `\u{0}`),
+/// so non-printable characters stay visible and the result can be pasted
+/// between quotes.
+pub fn escape_witness(witness: &str) -> String {
+    let mut escaped = String::with_capacity(witness.len());
+    for c in witness.chars() {
+        match c {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            // Other whitespace than ' ' is as easy to miss as a control character
+            c if c.is_control() || (c.is_whitespace() && c != ' ') => {
+                escaped.push_str(&format!("\\u{{{:x}}}", c as u32));
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 pub fn find_set_conflicts(
     set1: &HashSet<SetItem>,
     set2: &HashSet<SetItem>,
@@ -272,11 +469,13 @@ pub fn find_set_conflicts(
             }
         }
     }
+    // Set iteration order is arbitrary; keep reports reproducible
+    conflicts.sort();
 
     Ok(conflicts)
 }
 
-fn check_item_conflict(
+pub(crate) fn check_item_conflict(
     item1: &SetItem,
     item2: &SetItem,
     ignore_regex_conflicts: bool,
@@ -290,6 +489,7 @@ fn check_item_conflict(
                     item1: item1.clone(),
                     item2: item2.clone(),
                     witness: Some(s1.to_string()),
+                    witness_longer_than: None,
                 }))
             } else {
                 Ok(None)
@@ -301,13 +501,19 @@ fn check_item_conflict(
         (SetItem::Regex(r1), SetItem::Regex(r2)) => {
             let p1 = strip_regex_delimiters(r1);
             let p2 = strip_regex_delimiters(r2);
-            match do_regexs_intersect(p1, p2) {
-                Ok(Some(witness)) => Ok(Some(SetItemConflict {
+            let conflict = |witness, witness_longer_than| {
+                Ok(Some(SetItemConflict {
                     item1: item1.clone(),
                     item2: item2.clone(),
-                    witness: Some(witness),
-                })),
-                Ok(None) => Ok(None),
+                    witness,
+                    witness_longer_than,
+                }))
+            };
+            match regexs_intersection(p1, p2, WitnessOptions::current()) {
+                Ok(Intersection::Witness(witness)) => conflict(Some(witness), None),
+                Ok(Intersection::LongerThan(max_len)) => conflict(None, Some(max_len)),
+                Ok(Intersection::Exists) => conflict(None, None),
+                Ok(Intersection::Empty) => Ok(None),
                 Err(e) => Err(Ll1Error::InvalidRegex {
                     pattern: format!("{} or {}", r1, r2),
                     source: e,
@@ -321,16 +527,29 @@ fn check_item_conflict(
             Ok(None)
         }
 
+        // Different classes are different tokens; terminals and regexes that
+        // were not mapped to a class are assumed to be tokens of their own
+        (SetItem::TokenClass(c1), SetItem::TokenClass(c2)) if c1 == c2 => {
+            Ok(Some(SetItemConflict {
+                item1: item1.clone(),
+                item2: item2.clone(),
+                witness: None,
+                witness_longer_than: None,
+            }))
+        }
+
         (SetItem::Epsilon, SetItem::Epsilon) => Ok(Some(SetItemConflict {
             item1: item1.clone(),
             item2: item2.clone(),
             witness: None,
+            witness_longer_than: None,
         })),
 
         (SetItem::EndOfInput, SetItem::EndOfInput) => Ok(Some(SetItemConflict {
             item1: item1.clone(),
             item2: item2.clone(),
             witness: None,
+            witness_longer_than: None,
         })),
 
         _ => Ok(None),
@@ -338,6 +557,45 @@ fn check_item_conflict(
 }
 
 pub fn extract_sets(bnf: &Bnf) -> Sets {
+    extract_sets_from(bnf, bnf.rules.keys().next().map(String::as_str))
+}
+
+/// Like [`extract_sets`], with `start` instead of the first rule as the
+/// start symbol, whose FOLLOW set gets `$`.
+pub(crate) fn extract_sets_from(bnf: &Bnf, start: Option<&str>) -> Sets {
+    compute_sets(bnf, start, &mut ())
+}
+
+/// The FIRST or the FOLLOW set of a symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SetKind {
+    First,
+    Follow,
+}
+
+/// Why a member was added to a set
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Cause<'a> {
+    /// FIRST of the production of the rule, or for FOLLOW, FIRST of what
+    /// follows the symbol in it
+    Production(&'a str, &'a [Item]),
+    /// FOLLOW of the rule, as the rest of its production can be empty
+    FollowOf(&'a str, &'a [Item]),
+    StartSymbol,
+}
+
+/// Watches [`compute_sets`] grow the sets.
+pub(crate) trait SetObserver {
+    /// A new pass over the productions begins; the start symbol's `$` is
+    /// added in FOLLOW pass 0
+    fn pass(&mut self, _kind: SetKind, _pass: usize) {}
+
+    fn added(&mut self, _kind: SetKind, _symbol: &str, _item: &SetItem, _cause: Cause<'_>) {}
+}
+
+impl SetObserver for () {}
+
+pub(crate) fn compute_sets(bnf: &Bnf, start: Option<&str>, observer: &mut dyn SetObserver) -> Sets {
     let mut first_sets: HashMap<String, HashSet<SetItem>> = HashMap::new();
     let mut follow_sets: HashMap<String, HashSet<SetItem>> = HashMap::new();
 
@@ -348,37 +606,54 @@ pub fn extract_sets(bnf: &Bnf) -> Sets {
 
     // FIRST sets: fixed-point iteration until no changes
     let mut changed = true;
+    let mut pass = 0;
     while changed {
         changed = false;
+        pass += 1;
+        observer.pass(SetKind::First, pass);
 
         for (lhs, productions) in &bnf.rules {
             for production in productions {
-                let (firsts, nullable) = first_of_sequence(production, &first_sets);
+                let (mut firsts, nullable) = first_of_sequence(production, &first_sets);
+                if nullable {
+                    firsts.insert(SetItem::Epsilon);
+                }
 
-                let lhs_set = first_sets.get_mut(lhs).unwrap();
+                let lhs_set = first_sets.entry(lhs.clone()).or_default();
                 for f in firsts {
-                    changed |= lhs_set.insert(f);
-                }
-                if nullable {
-                    changed |= lhs_set.insert(SetItem::Epsilon);
+                    if lhs_set.insert(f.clone()) {
+                        changed = true;
+                        let cause = Cause::Production(lhs, production);
+                        observer.added(SetKind::First, lhs, &f, cause);
+                    }
                 }
             }
         }
     }
 
     // FOLLOW sets: start symbol gets $
-    if let Some((start_symbol, _)) = bnf.rules.first() {
+    observer.pass(SetKind::Follow, 0);
+    if let Some(start_symbol) = start {
         follow_sets
-            .get_mut(start_symbol)
-            .unwrap()
+            .entry(start_symbol.to_string())
+            .or_default()
             .insert(SetItem::EndOfInput);
+        observer.added(
+            SetKind::Follow,
+            start_symbol,
+            &SetItem::EndOfInput,
+            Cause::StartSymbol,
+        );
     }
 
     // FOLLOW sets: fixed-point iteration
     // For A -> αBβ: FOLLOW(B) ∪= FIRST(β)\{ε}; if β ⇒* ε then FOLLOW(B) ∪= FOLLOW(A)
     changed = true;
+    pass = 0;
     while changed {
         changed = false;
+        pass += 1;
+        observer.pass(SetKind::Follow, pass);
 
         for (lhs, productions) in &bnf.rules {
             for production in productions {
@@ -394,14 +669,22 @@ pub fn extract_sets(bnf: &Bnf) -> Sets {
                     let (beta_firsts, beta_nullable) = first_of_sequence(beta, &first_sets);
 
                     for f in beta_firsts {
-                        changed |= current_follow.insert(f);
+                        if current_follow.insert(f.clone()) {
+                            changed = true;
+                            let cause = Cause::Production(lhs, production);
+                            observer.added(SetKind::Follow, current_nt, &f, cause);
+                        }
                     }
 
                     if beta_nullable {
                         let lhs_follows = follow_sets.get(lhs).cloned().unwrap_or_default();
-                        let current_follow = follow_sets.get_mut(current_nt).unwrap();
+                        let current_follow = follow_sets.entry(current_nt.clone()).or_default();
                         for f in lhs_follows {
-                            changed |= current_follow.insert(f);
+                            if current_follow.insert(f.clone()) {
+                                changed = true;
+                                let cause = Cause::FollowOf(lhs, production);
+                                observer.added(SetKind::Follow, current_nt, &f, cause);
+                            }
                         }
                     }
                 }
@@ -414,3 +697,103 @@ pub fn extract_sets(bnf: &Bnf) -> Sets {
         follow: follow_sets,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn witnesses_are_escaped() {
+        let conflict = SetItemConflict {
+            item1: SetItem::Regex("/[\\x00-\\t]/".to_string()),
+            item2: SetItem::Regex("/[\\x00\"]/".to_string()),
+            witness: Some("\0\t\"\u{a0}é".to_string()),
+            witness_longer_than: None,
+        };
+        assert_eq!(
+            conflict.to_string(),
+            "/[\\x00-\\t]/ ∩ /[\\x00\"]/ (e.g., \"\\u{0}\\t\\\"\\u{a0}é\")"
+        );
+        let json = conflict.to_json();
+        assert_eq!(json["witness"]["raw"], "\0\t\"\u{a0}é");
+        assert_eq!(json["witness"]["escaped"], "\\u{0}\\t\\\"\\u{a0}é");
+    }
+
+    #[test]
+    fn follow_sets_from_another_start() {
+        let bnf = crate::Sebnf::parse_str("e := \"x\".\nstmt := e \";\".", "g")
+            .unwrap()
+            .to_bnf();
+        let sets = bnf.first_and_follow_sets_from("stmt").unwrap();
+        assert_eq!(sets.follow["stmt"], HashSet::from([SetItem::EndOfInput]));
+        assert_eq!(
+            sets.follow["e"],
+            HashSet::from([SetItem::Terminal("\";\"".to_string())])
+        );
+        let unknown = bnf.first_and_follow_sets_from("s").unwrap_err();
+        assert_eq!(unknown, UnknownStart("s".to_string()));
+    }
+
+    #[test]
+    fn orders_are_deterministic() {
+        let bnf = crate::Sebnf::parse_str(
+            "s := a \"x\" | b.\na := [ \"x\" ] | \"x\" | /x|y/ .\nb := \"x\" | /y/ \"z\".",
+            "g",
+        )
+        .unwrap()
+        .to_bnf();
+        // Rule order, then production indices, FIRST/FIRST before FIRST/FOLLOW,
+        // and the overlapping items in set order
+        let result = bnf.is_ll1(false).unwrap();
+        let conflicts: Vec<_> = (result.conflicts.iter())
+            .map(|c| {
+                let kind = match c.kind {
+                    Ll1ConflictKind::FirstFirst { .. } => "FIRST/FIRST",
+                    Ll1ConflictKind::FirstFollow { .. } => "FIRST/FOLLOW",
+                };
+                let items = (c.conflicts.iter())
+                    .map(|item| format!("{} {}", item.item1, item.item2))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (c.non_terminal.as_str(), kind, c.production_indices, items)
+            })
+            .collect();
+        assert_eq!(
+            conflicts,
+            [
+                ("s", "FIRST/FIRST", (0, 1), "x x, /x|y/ /y/".to_string()),
+                ("a", "FIRST/FIRST", (0, 1), "x x".to_string()),
+                ("a", "FIRST/FOLLOW", (0, 1), "x x".to_string()),
+                ("___opt_0", "FIRST/FOLLOW", (1, 0), "x x".to_string()),
+            ]
+        );
+
+        // Independent of the symbols, unlike sorting the printed items
+        let first = &bnf.first_and_follow_sets().first["a"];
+        let items: Vec<_> = sorted(first).into_iter().cloned().collect();
+        assert_eq!(
+            items,
+            [
+                SetItem::Terminal("\"x\"".to_string()),
+                SetItem::Regex("/x|y/".to_string()),
+                SetItem::Epsilon,
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn ll1_result_round_trips_through_serde() {
+        let bnf = crate::Sebnf::parse_str("a := [ \"x\" ] \"x\".", "g")
+            .unwrap()
+            .to_bnf();
+        let result = bnf.is_ll1(false).unwrap();
+        let json = serde_json::to_string(&result).unwrap();
+        let back: Ll1Result = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_string(), result.to_string());
+        assert_eq!(
+            back.conflicts[0].production_indices,
+            result.conflicts[0].production_indices
+        );
+    }
+}
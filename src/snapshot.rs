@@ -0,0 +1,135 @@
+use std::io;
+use std::path::Path;
+
+use crate::bnf::Bnf;
+use crate::render::RenderError;
+use crate::sets::Ll1Error;
+
+/// A canonical text artifact derived from a grammar.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub file_name: &'static str,
+    pub contents: String,
+}
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum SnapshotError {
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Ll1(#[from] Ll1Error),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Render(#[from] RenderError),
+}
+
+/// Computes every snapshot artifact of `bnf`.
+pub fn artifacts(bnf: &Bnf, ignore_regex_conflicts: bool) -> Result<Vec<Artifact>, SnapshotError> {
+    let analysis = bnf.analyze().ignore_regex_conflicts(ignore_regex_conflicts);
+    let (sets, table, ll1) = (analysis.sets(), analysis.table(), analysis.conflicts()?);
+
+    let mut sets_json = serde_json::to_string_pretty(&sets.to_json()).unwrap_or_default();
+    sets_json.push('\n');
+
+    Ok(vec![
+        Artifact {
+            file_name: "grammar.bnf",
+            contents: bnf.render()?,
+        },
+        Artifact {
+            file_name: "sets.json",
+            contents: sets_json,
+        },
+        Artifact {
+            file_name: "conflicts.txt",
+            contents: ll1.render()?,
+        },
+        Artifact {
+            file_name: "table.txt",
+            contents: table.render(bnf)?,
+        },
+    ])
+}
+
+/// Writes all artifacts into `dir`, creating it if needed.
+/// Returns the names of the files whose contents changed.
+pub fn update(dir: &Path, artifacts: &[Artifact]) -> io::Result<Vec<&'static str>> {
+    std::fs::create_dir_all(dir)?;
+    let mut changed = Vec::new();
+    for artifact in artifacts {
+        let path = dir.join(artifact.file_name);
+        let current = std::fs::read_to_string(&path).ok();
+        if current.as_deref() != Some(artifact.contents.as_str()) {
+            std::fs::write(&path, &artifact.contents)?;
+            changed.push(artifact.file_name);
+        }
+    }
+    Ok(changed)
+}
+
+#[derive(Debug, Clone)]
+pub enum Mismatch {
+    Missing {
+        file_name: &'static str,
+    },
+    Differs {
+        file_name: &'static str,
+        diff: String,
+    },
+}
+
+/// Compares the artifacts against the files stored in `dir`.
+pub fn check(dir: &Path, artifacts: &[Artifact]) -> io::Result<Vec<Mismatch>> {
+    let mut mismatches = Vec::new();
+    for artifact in artifacts {
+        let path = dir.join(artifact.file_name);
+        match std::fs::read_to_string(&path) {
+            Ok(stored) if stored == artifact.contents => {}
+            Ok(stored) => mismatches.push(Mismatch::Differs {
+                file_name: artifact.file_name,
+                diff: diff_lines(&stored, &artifact.contents),
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                mismatches.push(Mismatch::Missing {
+                    file_name: artifact.file_name,
+                });
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Line diff of `old` and `new` based on their longest common subsequence.
+/// Unchanged lines are omitted; removed lines start with `-`, added with `+`.
+pub fn diff_lines(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        }
+    }
+    out
+}
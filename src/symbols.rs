@@ -0,0 +1,101 @@
+//! Symbols used when printing ε, end of input and the other non-ASCII glyphs.
+//!
+//! `Display` implementations take no parameters, so the symbols are set per
+//! thread with [`Symbols::scope`] and read by every renderer through
+//! [`Symbols::with_current`].
+
+use std::cell::RefCell;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbols {
+    pub epsilon: String,
+    pub end_of_input: String,
+    /// Print ASCII stand-ins for the remaining glyphs (`∩`, `→`, `⇒`, `≤`, `…`,
+    /// `•` and the tree lines)
+    pub ascii: bool,
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        Self {
+            epsilon: "ε".to_string(),
+            end_of_input: "$".to_string(),
+            ascii: false,
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Symbols> = RefCell::new(Symbols::default());
+}
+
+impl Symbols {
+    /// Pure ASCII output, for terminals and tools that cannot handle Unicode.
+    pub fn ascii() -> Self {
+        Self {
+            epsilon: "eps".to_string(),
+            end_of_input: "$".to_string(),
+            ascii: true,
+        }
+    }
+
+    /// Runs `f` with these symbols as the current ones of this thread.
+    pub fn scope<R>(self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Symbols>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                if let Some(previous) = self.0.take() {
+                    CURRENT.with(|current| *current.borrow_mut() = previous);
+                }
+            }
+        }
+
+        let previous = CURRENT.with(|current| current.replace(self));
+        let _restore = Restore(Some(previous));
+        f()
+    }
+
+    pub fn with_current<R>(f: impl FnOnce(&Symbols) -> R) -> R {
+        CURRENT.with(|current| f(&current.borrow()))
+    }
+
+    /// ε as written in printed grammars: the configured symbol when the
+    /// grammar lexer reads it as ε, otherwise `ε`, or `EPSILON` in ASCII mode.
+    /// Keeps printed grammars parseable whatever the configured symbol is.
+    pub fn grammar_epsilon(&self) -> &str {
+        match self.epsilon.as_str() {
+            epsilon @ ("ε" | "EPSILON") => epsilon,
+            _ => self.glyph("ε", "EPSILON"),
+        }
+    }
+
+    /// `unicode` normally, `ascii` in ASCII mode.
+    pub(crate) fn glyph(&self, unicode: &'static str, ascii: &'static str) -> &'static str {
+        if self.ascii { ascii } else { unicode }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sets::SetItem;
+
+    #[test]
+    fn scope_sets_and_restores_symbols() {
+        let ascii = Symbols::ascii().scope(|| SetItem::Epsilon.to_string());
+        assert_eq!(ascii, "eps");
+        assert_eq!(SetItem::Epsilon.to_string(), "ε");
+    }
+
+    #[test]
+    fn grammar_epsilon_stays_readable() {
+        assert_eq!(Symbols::default().grammar_epsilon(), "ε");
+        assert_eq!(Symbols::ascii().grammar_epsilon(), "EPSILON");
+        let configured = |epsilon: &str| Symbols {
+            epsilon: epsilon.to_string(),
+            ..Symbols::default()
+        };
+        assert_eq!(configured("EPSILON").grammar_epsilon(), "EPSILON");
+        assert_eq!(configured("eps").grammar_epsilon(), "ε");
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+
+use crate::bnf::Bnf;
+use crate::render::{RenderError, render_display};
+use crate::sets::{SetItem, Sets, first_of_sequence, format_production};
+use crate::symbols::Symbols;
+
+/// LL(1) parse table: for every non-terminal, the productions selected by each
+/// lookahead item. Cells with more than one production are conflicts.
+#[derive(Debug, Clone)]
+pub struct ParseTable {
+    pub rows: IndexMap<String, Vec<TableCell>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableCell {
+    pub lookahead: SetItem,
+    /// Indices into the non-terminal's productions
+    pub productions: Vec<usize>,
+}
+
+impl TableCell {
+    pub fn is_conflict(&self) -> bool {
+        self.productions.len() > 1
+    }
+}
+
+pub fn build_parse_table(bnf: &Bnf, sets: &Sets) -> ParseTable {
+    let mut rows = IndexMap::new();
+
+    for (nt, productions) in &bnf.rules {
+        let mut cells: IndexMap<SetItem, Vec<usize>> = IndexMap::new();
+        let follow = sets.follow.get(nt);
+
+        for (i, production) in productions.iter().enumerate() {
+            let (firsts, nullable) = first_of_sequence(production, &sets.first);
+            let mut lookaheads: Vec<&SetItem> = firsts.iter().collect();
+            if nullable {
+                lookaheads.extend(follow.into_iter().flatten());
+            }
+            for lookahead in lookaheads {
+                let cell = cells.entry(lookahead.clone()).or_default();
+                if !cell.contains(&i) {
+                    cell.push(i);
+                }
+            }
+        }
+
+        let mut cells: Vec<TableCell> = cells
+            .into_iter()
+            .map(|(lookahead, productions)| TableCell {
+                lookahead,
+                productions,
+            })
+            .collect();
+        cells.sort_by(|a, b| a.lookahead.cmp(&b.lookahead));
+        rows.insert(nt.clone(), cells);
+    }
+
+    ParseTable { rows }
+}
+
+impl ParseTable {
+    pub fn has_conflicts(&self) -> bool {
+        self.rows.values().flatten().any(TableCell::is_conflict)
+    }
+
+    pub fn render(&self, bnf: &Bnf) -> Result<String, RenderError> {
+        render_display(&ParseTableDisplay { table: self, bnf })
+    }
+}
+
+struct ParseTableDisplay<'a> {
+    table: &'a ParseTable,
+    bnf: &'a Bnf,
+}
+
+impl fmt::Display for ParseTableDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (nt, cells) in &self.table.rows {
+            writeln!(f, "{}:", nt)?;
+            let width = cells
+                .iter()
+                .map(|c| c.lookahead.to_string().chars().count())
+                .max()
+                .unwrap_or(0);
+            let arrow = Symbols::with_current(|s| s.glyph("→", "->"));
+            for cell in cells {
+                let productions: Vec<String> = cell
+                    .productions
+                    .iter()
+                    .map(|&i| match self.bnf.rules.get(nt).and_then(|p| p.get(i)) {
+                        Some(items) => format_production(items),
+                        None => "?".to_string(),
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "  {:width$} {} {}",
+                    cell.lookahead.to_string(),
+                    arrow,
+                    productions.join(" | "),
+                    width = width
+                )?;
+                if cell.is_conflict() {
+                    write!(f, "  (conflict)")?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! Mapping of grammar terminals to the token classes of an external lexer.
+//!
+//! A declaration file has one mapping per line:
+//!
+//! ```text
+//! # comments start with '#'
+//! "ID"     -> IDENT
+//! /[0-9]+/ -> INT_LIT
+//! ```
+//!
+//! Every terminal or regex listed is replaced by its class before analysis, so
+//! two spellings of the same class conflict and distinct classes never do.
+
+use indexmap::IndexMap;
+use miette::{NamedSource, SourceSpan};
+
+use crate::bnf::{Bnf, Item};
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum TokenClassError {
+    #[error("invalid token class declaration")]
+    #[diagnostic(
+        code(sebnf::token_class_syntax),
+        help("declarations look like `\"if\" -> IF` or `/[0-9]+/ -> INT_LIT`")
+    )]
+    Syntax {
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("{message}")]
+        span: SourceSpan,
+        message: String,
+    },
+
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Duplicate(Box<DuplicateClass>),
+}
+
+/// A spelling mapped twice, boxed in [`TokenClassError::Duplicate`] to keep
+/// the error small.
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+#[error("'{spelling}' is mapped to more than one token class")]
+#[diagnostic(code(sebnf::duplicate_token_class))]
+pub struct DuplicateClass {
+    pub spelling: String,
+    #[source_code]
+    pub src: NamedSource<String>,
+    #[label("already mapped to {previous}")]
+    pub span: SourceSpan,
+    pub previous: String,
+}
+
+/// Terminal and regex spellings (with their delimiters) mapped to class names.
+#[derive(Debug, Clone, Default)]
+pub struct TokenClasses {
+    pub classes: IndexMap<String, String>,
+}
+
+impl TokenClasses {
+    pub fn parse(source: &str, source_name: &str) -> Result<Self, TokenClassError> {
+        let mut classes = IndexMap::new();
+        let mut line_start = 0;
+
+        for line in source.split_inclusive('\n') {
+            let offset = line_start;
+            line_start += line.len();
+
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let start = offset + (line.len() - line.trim_start().len());
+            let syntax_error = |message: &str| TokenClassError::Syntax {
+                src: NamedSource::new(source_name, source.to_string()),
+                span: (start, trimmed.len()).into(),
+                message: message.to_string(),
+            };
+
+            let Some((spelling, class)) = trimmed.rsplit_once("->") else {
+                return Err(syntax_error("missing '->'"));
+            };
+            let (spelling, class) = (spelling.trim(), class.trim());
+            if !is_delimited(spelling, '"') && !is_delimited(spelling, '/') {
+                return Err(syntax_error("expected a \"terminal\" or /regex/"));
+            }
+            if class.is_empty() || !class.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(syntax_error("expected a token class name"));
+            }
+
+            if let Some(previous) = classes.insert(spelling.to_string(), class.to_string()) {
+                return Err(TokenClassError::Duplicate(Box::new(DuplicateClass {
+                    spelling: spelling.to_string(),
+                    src: NamedSource::new(source_name, source.to_string()),
+                    span: (start, trimmed.len()).into(),
+                    previous,
+                })));
+            }
+        }
+
+        Ok(Self { classes })
+    }
+
+    /// Returns a copy of `bnf` where every mapped terminal and regex is
+    /// replaced by its token class.
+    pub fn apply(&self, bnf: &Bnf) -> Bnf {
+        let rules = bnf
+            .rules
+            .iter()
+            .map(|(nt, productions)| {
+                let productions = productions
+                    .iter()
+                    .map(|production| production.iter().map(|item| self.map_item(item)).collect())
+                    .collect();
+                (nt.clone(), productions)
+            })
+            .collect();
+        Bnf {
+            rules,
+            spans: bnf.spans.clone(),
+        }
+    }
+
+    fn map_item(&self, item: &Item) -> Item {
+        match item {
+            Item::Terminal(s) | Item::Regex(s) => match self.classes.get(s) {
+                Some(class) => Item::TokenClass(class.clone()),
+                None => item.clone(),
+            },
+            _ => item.clone(),
+        }
+    }
+}
+
+fn is_delimited(s: &str, delimiter: char) -> bool {
+    s.len() >= 2 && s.starts_with(delimiter) && s.ends_with(delimiter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_terminals_and_regexes() {
+        let classes = TokenClasses::parse(
+            "# keywords\n\"ID\" -> IDENT\n/[a-z]+/ -> IDENT\n\n/[0-9]+/->INT_LIT\n",
+            "classes",
+        )
+        .unwrap();
+        assert_eq!(classes.classes.len(), 3);
+
+        let mut rules = IndexMap::new();
+        rules.insert(
+            "A".to_string(),
+            vec![
+                vec![Item::Terminal("\"ID\"".to_string())],
+                vec![
+                    Item::Regex("/[a-z]+/".to_string()),
+                    Item::Terminal("\";\"".to_string()),
+                ],
+            ],
+        );
+        let bnf = classes.apply(&Bnf {
+            rules,
+            spans: IndexMap::new(),
+        });
+        assert_eq!(
+            bnf.rules["A"],
+            vec![
+                vec![Item::TokenClass("IDENT".to_string())],
+                vec![
+                    Item::TokenClass("IDENT".to_string()),
+                    Item::Terminal("\";\"".to_string()),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_declarations() {
+        assert!(matches!(
+            TokenClasses::parse("ID -> IDENT", "classes"),
+            Err(TokenClassError::Syntax { .. })
+        ));
+        assert!(matches!(
+            TokenClasses::parse("\"a\" -> A\n\"a\" -> B\n", "classes"),
+            Err(TokenClassError::Duplicate(_))
+        ));
+    }
+}
@@ -0,0 +1,425 @@
+//! How the FIRST and FOLLOW sets of one symbol grow.
+//!
+//! [`trace_sets`] records, for every pass of the fixed-point iterations
+//! that compute the sets, which members the symbol's sets gained and the
+//! production responsible for each, to find out where a surprising member
+//! comes from. [`explain_member`] follows one member instead, from set to
+//! set, back to the production it is first written in.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::bnf::{Bnf, Item};
+use crate::nullable::is_empty_regex;
+use crate::sets::{Cause, SetItem, SetKind, SetObserver, compute_sets, format_production};
+use crate::symbols::Symbols;
+
+#[derive(Debug, thiserror::Error, miette::Diagnostic)]
+pub enum ExplainError {
+    #[error("{item} is not in {set}({symbol})")]
+    #[diagnostic(code(sebnf::not_a_member), help("{set}({symbol}) is {{ {members} }}"))]
+    NotAMember {
+        item: String,
+        set: &'static str,
+        symbol: String,
+        members: String,
+    },
+}
+
+/// The growth of both sets of a symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetTrace {
+    pub symbol: String,
+    pub first: Timeline,
+    pub follow: Timeline,
+}
+
+/// The passes that changed one set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timeline {
+    pub kind: SetKind,
+    /// Passes of the fixed-point iteration, including the last one that
+    /// changed nothing
+    pub passes: usize,
+    pub steps: Vec<Step>,
+}
+
+/// What one pass added to the set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// 1-based; the start symbol's `$` is added in FOLLOW pass 0
+    pub pass: usize,
+    pub added: Vec<Addition>,
+    /// The set after the pass
+    pub contents: Vec<SetItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Addition {
+    pub item: SetItem,
+    pub reason: Reason,
+}
+
+/// Why a member was added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// In FIRST sets, FIRST of the rule's production; in FOLLOW sets,
+    /// FIRST of what follows the symbol in it
+    Production {
+        rule: String,
+        production: Vec<Item>,
+    },
+    /// FOLLOW of the rule, because the rest of the production after the
+    /// symbol can be empty
+    FollowOf {
+        rule: String,
+        production: Vec<Item>,
+    },
+    StartSymbol,
+}
+
+/// Why an item is in a set: each step puts it in a set because it is in the
+/// set of the next step, down to a production that starts with it, has it
+/// after the symbol, or the start symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub item: SetItem,
+    pub steps: Vec<ExplainStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplainStep {
+    pub kind: SetKind,
+    pub symbol: String,
+    pub reason: Reason,
+}
+
+/// Traces the sets of `symbol`, with the first rule of `bnf` as the start
+/// symbol. A symbol without a rule has two empty timelines.
+pub fn trace_sets(bnf: &Bnf, symbol: &str) -> SetTrace {
+    let mut recorder = Recorder {
+        symbol,
+        trace: SetTrace {
+            symbol: symbol.to_string(),
+            first: Timeline::new(SetKind::First),
+            follow: Timeline::new(SetKind::Follow),
+        },
+        contents: BTreeSet::new(),
+    };
+    compute_sets(
+        bnf,
+        bnf.rules.keys().next().map(String::as_str),
+        &mut recorder,
+    );
+    recorder.trace
+}
+
+impl Timeline {
+    fn new(kind: SetKind) -> Self {
+        Timeline {
+            kind,
+            passes: 0,
+            steps: Vec::new(),
+        }
+    }
+}
+
+struct Recorder<'a> {
+    symbol: &'a str,
+    trace: SetTrace,
+    /// The set being computed so far
+    contents: BTreeSet<SetItem>,
+}
+
+impl Recorder<'_> {
+    fn timeline(&mut self, kind: SetKind) -> &mut Timeline {
+        match kind {
+            SetKind::First => &mut self.trace.first,
+            SetKind::Follow => &mut self.trace.follow,
+        }
+    }
+}
+
+impl SetObserver for Recorder<'_> {
+    fn pass(&mut self, kind: SetKind, pass: usize) {
+        if kind == SetKind::Follow && pass == 0 {
+            self.contents.clear();
+        }
+        self.timeline(kind).passes = pass;
+    }
+
+    fn added(&mut self, kind: SetKind, symbol: &str, item: &SetItem, cause: Cause<'_>) {
+        if symbol != self.symbol {
+            return;
+        }
+        self.contents.insert(item.clone());
+        let contents = self.contents.iter().cloned().collect();
+        let addition = Addition {
+            item: item.clone(),
+            reason: cause.into(),
+        };
+        let timeline = self.timeline(kind);
+        let pass = timeline.passes;
+        match timeline.steps.last_mut() {
+            Some(step) if step.pass == pass => {
+                step.added.push(addition);
+                step.contents = contents;
+            }
+            _ => timeline.steps.push(Step {
+                pass,
+                added: vec![addition],
+                contents,
+            }),
+        }
+    }
+}
+
+/// Explains why `item` is in the `kind` set of `symbol`, with the first rule
+/// of `bnf` as the start symbol. `item` is spelled like in the printed sets,
+/// or as a terminal with its quotes.
+pub fn explain_member(
+    bnf: &Bnf,
+    kind: SetKind,
+    symbol: &str,
+    item: &str,
+) -> Result<Explanation, ExplainError> {
+    let mut log = Log::default();
+    let sets = compute_sets(bnf, bnf.rules.keys().next().map(String::as_str), &mut log);
+    let set = match kind {
+        SetKind::First => sets.first.get(symbol),
+        SetKind::Follow => sets.follow.get(symbol),
+    };
+    let mut members: Vec<&SetItem> = set.into_iter().flatten().collect();
+    members.sort();
+    let spelled = |member: &SetItem| match member {
+        SetItem::Terminal(terminal) => terminal == item || member.to_string() == item,
+        SetItem::Epsilon => item == "ε" || item == "eps" || member.to_string() == item,
+        SetItem::EndOfInput => item == "$" || member.to_string() == item,
+        _ => member.to_string() == item,
+    };
+    let Some(member) = members.iter().find(|member| spelled(member)) else {
+        let members: Vec<_> = members.iter().map(|m| m.to_string()).collect();
+        return Err(ExplainError::NotAMember {
+            item: item.to_string(),
+            set: kind_name(kind),
+            symbol: symbol.to_string(),
+            members: members.join(", "),
+        });
+    };
+
+    let mut steps = Vec::new();
+    let mut claim = Some((kind, symbol.to_string()));
+    while let Some((kind, symbol)) = claim.take() {
+        let index = log.index[&(kind, symbol.clone(), (*member).clone())];
+        let reason = log.additions[index].clone();
+        claim = match &reason {
+            Reason::Production { production, .. } => {
+                // The rest of the production after the symbol, for FOLLOW
+                // any of its occurrences
+                let sequences: Vec<&[Item]> = match kind {
+                    SetKind::First => vec![production],
+                    SetKind::Follow => (production.iter().enumerate())
+                        .filter(|(_, i)| matches!(i, Item::NonTerminal(nt) if *nt == symbol))
+                        .map(|(i, _)| &production[i + 1..])
+                        .collect(),
+                };
+                (sequences.into_iter())
+                    .find_map(|sequence| log.first_source(sequence, member, index))
+                    .map(|next| (SetKind::First, next))
+            }
+            Reason::FollowOf { rule, .. } => Some((SetKind::Follow, rule.clone())),
+            Reason::StartSymbol => None,
+        };
+        steps.push(ExplainStep {
+            kind,
+            symbol,
+            reason,
+        });
+    }
+    Ok(Explanation {
+        item: (*member).clone(),
+        steps,
+    })
+}
+
+fn kind_name(kind: SetKind) -> &'static str {
+    match kind {
+        SetKind::First => "FIRST",
+        SetKind::Follow => "FOLLOW",
+    }
+}
+
+/// Every addition to any set, in the order they were made
+#[derive(Default)]
+struct Log {
+    additions: Vec<Reason>,
+    index: HashMap<(SetKind, String, SetItem), usize>,
+}
+
+impl Log {
+    /// The rule whose FIRST set put `item` into FIRST of `sequence`, before
+    /// addition `before`; `None` if `sequence` starts with `item` itself
+    fn first_source(&self, sequence: &[Item], item: &SetItem, before: usize) -> Option<String> {
+        let added = |symbol: &str, item: &SetItem| {
+            let key = (SetKind::First, symbol.to_string(), item.clone());
+            self.index.get(&key).is_some_and(|&i| i < before)
+        };
+        for element in sequence {
+            match element {
+                Item::NonTerminal(nt) if added(nt, item) => return Some(nt.clone()),
+                Item::NonTerminal(nt) if added(nt, &SetItem::Epsilon) => {}
+                item if is_empty_regex(item) => {}
+                _ => return None,
+            }
+        }
+        None
+    }
+}
+
+impl SetObserver for Log {
+    fn added(&mut self, kind: SetKind, symbol: &str, item: &SetItem, cause: Cause<'_>) {
+        self.index.insert(
+            (kind, symbol.to_string(), item.clone()),
+            self.additions.len(),
+        );
+        self.additions.push(cause.into());
+    }
+}
+
+impl From<Cause<'_>> for Reason {
+    fn from(cause: Cause<'_>) -> Self {
+        match cause {
+            Cause::Production(rule, production) => Reason::Production {
+                rule: rule.to_string(),
+                production: production.to_vec(),
+            },
+            Cause::FollowOf(rule, production) => Reason::FollowOf {
+                rule: rule.to_string(),
+                production: production.to_vec(),
+            },
+            Cause::StartSymbol => Reason::StartSymbol,
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let element = Symbols::with_current(|s| s.glyph("∈", "in"));
+        let claims: Vec<String> = (self.steps.iter())
+            .map(|step| {
+                let set = kind_name(step.kind);
+                format!("{} {} {}({})", self.item, element, set, step.symbol)
+            })
+            .collect();
+        let width = claims.iter().map(|claim| claim.chars().count()).max();
+        for (claim, step) in claims.iter().zip(&self.steps) {
+            let reason = step.reason.describe(step.kind, &step.symbol);
+            writeln!(
+                f,
+                "{:width$}  {}",
+                claim,
+                reason,
+                width = width.unwrap_or(0)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Reason {
+    /// Why the member is in the `kind` set of `symbol`
+    fn describe(&self, kind: SetKind, symbol: &str) -> String {
+        match self {
+            Reason::Production { rule, production } => {
+                let relation = match kind {
+                    SetKind::First => "from".to_string(),
+                    SetKind::Follow => format!("after {} in", symbol),
+                };
+                format!("{} {} := {}", relation, rule, format_production(production))
+            }
+            Reason::FollowOf { rule, production } => format!(
+                "from FOLLOW({}) in {} := {}",
+                rule,
+                rule,
+                format_production(production)
+            ),
+            Reason::StartSymbol => "start symbol".to_string(),
+        }
+    }
+}
+
+fn join(items: &[SetItem]) -> String {
+    let items: Vec<_> = items.iter().map(|item| item.to_string()).collect();
+    items.join(", ")
+}
+
+impl fmt::Display for SetTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, timeline) in [&self.first, &self.follow].into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let name = kind_name(timeline.kind);
+            let contents = match timeline.steps.last() {
+                Some(step) => format!("{{ {} }}", join(&step.contents)),
+                None => "{}".to_string(),
+            };
+            writeln!(
+                f,
+                "{}({}) after {} passes: {}",
+                name, self.symbol, timeline.passes, contents
+            )?;
+            for step in &timeline.steps {
+                match step.pass {
+                    0 => writeln!(f, "  start: {}", join(&step.contents))?,
+                    pass => writeln!(f, "  pass {}: {}", pass, join(&step.contents))?,
+                }
+                let added: Vec<_> = step.added.iter().map(|a| a.item.to_string()).collect();
+                let width = added.iter().map(|item| item.chars().count()).max();
+                for (item, addition) in added.iter().zip(&step.added) {
+                    let width = width.unwrap_or(0);
+                    let reason = addition.reason.describe(timeline.kind, &self.symbol);
+                    writeln!(f, "    + {:width$}  {}", item, reason, width = width)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sebnf;
+
+    #[test]
+    fn records_each_addition_with_its_pass_and_production() {
+        let source = "expr := term { \"+\" term }.\nterm := /[0-9]+/ | \"(\" expr \")\".";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let trace = trace_sets(&bnf, "term");
+        assert_eq!(trace.first.steps.len(), 1);
+        let passes: Vec<_> = trace.follow.steps.iter().map(|s| s.pass).collect();
+        assert_eq!(passes, [1, 2]);
+        assert!(matches!(
+            &trace.follow.steps[1].added[..],
+            [Addition { item: SetItem::Terminal(t), reason: Reason::FollowOf { rule, .. } }]
+                if t == "\")\"" && rule == "expr"
+        ));
+    }
+
+    #[test]
+    fn explains_a_member_back_to_where_it_enters() {
+        let source = "expr := term { \"+\" term }.\nterm := /[0-9]+/ | \"(\" expr \")\".";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let explanation = explain_member(&bnf, SetKind::Follow, "term", "\")\"").unwrap();
+        assert_eq!(
+            explanation.to_string(),
+            ") ∈ FOLLOW(term)  from FOLLOW(expr) in expr := term ___rep_0\n\
+             ) ∈ FOLLOW(expr)  after expr in term := \"(\" expr \")\"\n"
+        );
+        assert!(matches!(
+            explain_member(&bnf, SetKind::First, "term", "\"+\""),
+            Err(ExplainError::NotAMember { .. })
+        ));
+    }
+}
@@ -0,0 +1,1511 @@
+//! Rewrites of SEBNF and BNF grammars.
+//!
+//! A [`Transform`] changes a grammar in place and records what it did in a
+//! [`ChangeLog`]. An [`Engine`] applies a list of transforms to a copy of the
+//! grammar, so a failing transform leaves the input untouched. Every
+//! transform keeps the order of the remaining rules, except that
+//! [`ExtractRoot`] moves its root to the front and [`Normalize`] reorders
+//! them from the start rule.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::transform::{Engine, InlineRule, RenameSymbol};
+//!
+//! let sebnf = Sebnf::parse_str("list := item { \",\" item }.\nitem := /[0-9]+/.", "g").unwrap();
+//! let (sebnf, log) = Engine::new()
+//!     .with(InlineRule::new("item"))
+//!     .with(RenameSymbol::new("list", "numbers"))
+//!     .run(&sebnf)
+//!     .unwrap();
+//! assert_eq!(sebnf.to_string(), "numbers := /[0-9]+/ { \",\" /[0-9]+/ }.\n");
+//! assert_eq!(log.changes.len(), 2);
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+use indexmap::IndexMap;
+
+use crate::bnf::{self, Bnf};
+use crate::sebnf::{self, Sebnf};
+use crate::sets::format_production;
+use crate::visit::{
+    Visitor, VisitorMut, walk_alternatives_mut, walk_item_mut, walk_sebnf, walk_sebnf_mut,
+    walk_sequence_mut,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, miette::Diagnostic)]
+pub enum TransformError {
+    #[error("no rule named '{0}'")]
+    #[diagnostic(code(sebnf::transform_unknown_rule))]
+    UnknownRule(String),
+    #[error("a rule named '{0}' already exists")]
+    #[diagnostic(code(sebnf::transform_name_taken))]
+    NameTaken(String),
+    #[error("'{0}' is not a valid rule name")]
+    #[diagnostic(
+        code(sebnf::transform_invalid_name),
+        help("rule names consist of letters, digits and '_'")
+    )]
+    InvalidName(String),
+    #[error("rule '{rule}' has no production {index} (it has {len})")]
+    #[diagnostic(code(sebnf::transform_no_such_production))]
+    NoSuchProduction {
+        rule: String,
+        index: usize,
+        len: usize,
+    },
+    #[error("cannot inline '{0}': it refers to itself")]
+    #[diagnostic(code(sebnf::transform_recursive_inline))]
+    RecursiveInline(String),
+    #[error("cannot inline the start rule '{0}'")]
+    #[diagnostic(code(sebnf::transform_inline_start))]
+    InlineStart(String),
+    #[error("the start rule '{0}' derives no string of terminals")]
+    #[diagnostic(
+        code(sebnf::transform_empty_language),
+        help("every production of it recurses without a way out")
+    )]
+    EmptyLanguage(String),
+    #[error("rule '{0}' still contains [ ], {{ }}, ( | ) or a built-in call")]
+    #[diagnostic(
+        code(sebnf::transform_not_lowered),
+        help("only grammars without them can be turned into BNF")
+    )]
+    NotLowered(String),
+}
+
+/// One applied rewrite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Name of the transform, e.g. `rename`
+    pub transform: &'static str,
+    pub description: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.transform, self.description)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeLog {
+    pub changes: Vec<Change>,
+}
+
+impl ChangeLog {
+    pub fn record(&mut self, transform: &'static str, description: impl Into<String>) {
+        self.changes.push(Change {
+            transform,
+            description: description.into(),
+        });
+    }
+}
+
+impl fmt::Display for ChangeLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// A rewrite of a grammar of type `G` (`Sebnf` or `Bnf`).
+pub trait Transform<G> {
+    fn apply(&self, grammar: &mut G, log: &mut ChangeLog) -> Result<(), TransformError>;
+}
+
+/// Applies transforms in order.
+pub struct Engine<G> {
+    transforms: Vec<Box<dyn Transform<G>>>,
+}
+
+impl<G> Default for Engine<G> {
+    fn default() -> Self {
+        Self {
+            transforms: Vec::new(),
+        }
+    }
+}
+
+impl<G: Clone> Engine<G> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, transform: impl Transform<G> + 'static) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    pub fn push(&mut self, transform: impl Transform<G> + 'static) {
+        self.transforms.push(Box::new(transform));
+    }
+
+    /// Returns the transformed copy of `grammar` and the log of all changes.
+    pub fn run(&self, grammar: &G) -> Result<(G, ChangeLog), TransformError> {
+        let mut grammar = grammar.clone();
+        let mut log = ChangeLog::default();
+        for transform in &self.transforms {
+            transform.apply(&mut grammar, &mut log)?;
+        }
+        Ok((grammar, log))
+    }
+}
+
+/// Renames `key` of an ordered map without moving it.
+fn rename_key<V>(map: &mut IndexMap<String, V>, from: &str, to: &str) {
+    if let Some(index) = map.get_index_of(from) {
+        let (_, value) = map.shift_remove_index(index).expect("index is valid");
+        map.shift_insert(index, to.to_string(), value);
+    }
+}
+
+fn check_production(len: usize, rule: &str, index: usize) -> Result<(), TransformError> {
+    if index < len {
+        Ok(())
+    } else {
+        Err(TransformError::NoSuchProduction {
+            rule: rule.to_string(),
+            index,
+            len,
+        })
+    }
+}
+
+/// Renames a non-terminal: its rule and every reference to it.
+#[derive(Debug, Clone)]
+pub struct RenameSymbol {
+    pub from: String,
+    pub to: String,
+}
+
+impl RenameSymbol {
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    fn check(&self, has_rule: impl Fn(&str) -> bool) -> Result<(), TransformError> {
+        if !has_rule(&self.from) {
+            return Err(TransformError::UnknownRule(self.from.clone()));
+        }
+        if self.from != self.to && has_rule(&self.to) {
+            return Err(TransformError::NameTaken(self.to.clone()));
+        }
+        if self.to.is_empty()
+            || !self
+                .to
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(TransformError::InvalidName(self.to.clone()));
+        }
+        Ok(())
+    }
+
+    /// Applies the rename to `source`, the text `sebnf` was parsed from,
+    /// replacing only the names so that comments and layout are kept.
+    pub fn rewrite_source(&self, sebnf: &Sebnf, source: &str) -> Result<String, TransformError> {
+        self.check(|name| sebnf.rules.contains_key(name))?;
+
+        struct Occurrences<'a>(&'a str, Vec<Range<usize>>);
+        impl Visitor for Occurrences<'_> {
+            fn visit_non_terminal(&mut self, name: &str, span: &Range<usize>) {
+                if name == self.0 {
+                    self.1.push(span.clone());
+                }
+            }
+        }
+        let mut occurrences = Occurrences(&self.from, Vec::new());
+        walk_sebnf(&mut occurrences, sebnf);
+        let mut spans = occurrences.1;
+        spans.extend(sebnf.rule_spans.get(&self.from).cloned());
+        spans.sort_by_key(|span| span.start);
+
+        let mut rewritten = String::with_capacity(source.len());
+        let mut end = 0;
+        for span in spans {
+            rewritten.push_str(&source[end..span.start]);
+            rewritten.push_str(&self.to);
+            end = span.end;
+        }
+        rewritten.push_str(&source[end..]);
+        Ok(rewritten)
+    }
+
+    fn record(&self, log: &mut ChangeLog, references: usize) {
+        log.record(
+            "rename",
+            format!(
+                "renamed '{}' to '{}' ({} references)",
+                self.from, self.to, references
+            ),
+        );
+    }
+}
+
+impl Transform<Sebnf> for RenameSymbol {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        self.check(|name| sebnf.rules.contains_key(name))?;
+
+        struct Rename<'a>(&'a RenameSymbol, usize);
+        impl VisitorMut for Rename<'_> {
+            fn visit_item_mut(&mut self, item: &mut sebnf::Item) {
+                if let sebnf::Item::NonTerminal(name, _) = item
+                    && *name == self.0.from
+                {
+                    *name = self.0.to.clone();
+                    self.1 += 1;
+                }
+                walk_item_mut(self, item);
+            }
+        }
+        let mut rename = Rename(self, 0);
+        walk_sebnf_mut(&mut rename, sebnf);
+        rename_key(&mut sebnf.rules, &self.from, &self.to);
+        rename_key(&mut sebnf.rule_spans, &self.from, &self.to);
+        rename_key(&mut sebnf.annotations, &self.from, &self.to);
+        self.record(log, rename.1);
+        Ok(())
+    }
+}
+
+impl Transform<Bnf> for RenameSymbol {
+    fn apply(&self, bnf: &mut Bnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        self.check(|name| bnf.rules.contains_key(name))?;
+
+        let mut references = 0;
+        for item in bnf.rules.values_mut().flatten().flatten() {
+            if let bnf::Item::NonTerminal(name) = item
+                && *name == self.from
+            {
+                *name = self.to.clone();
+                references += 1;
+            }
+        }
+        rename_key(&mut bnf.rules, &self.from, &self.to);
+        rename_key(&mut bnf.spans, &self.from, &self.to);
+        self.record(log, references);
+        Ok(())
+    }
+}
+
+/// Replaces the `index`-th production of `rule`.
+#[derive(Debug, Clone)]
+pub struct ReplaceProduction<I> {
+    pub rule: String,
+    pub index: usize,
+    pub items: Vec<I>,
+}
+
+impl<I> ReplaceProduction<I> {
+    pub fn new(rule: impl Into<String>, index: usize, items: Vec<I>) -> Self {
+        Self {
+            rule: rule.into(),
+            index,
+            items,
+        }
+    }
+}
+
+impl Transform<Sebnf> for ReplaceProduction<sebnf::Item> {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let alternatives = sebnf
+            .rules
+            .get_mut(&self.rule)
+            .ok_or_else(|| TransformError::UnknownRule(self.rule.clone()))?;
+        check_production(alternatives.len(), &self.rule, self.index)?;
+        alternatives[self.index] = self.items.clone();
+        log.record(
+            "replace",
+            format!("replaced production {} of '{}'", self.index, self.rule),
+        );
+        Ok(())
+    }
+}
+
+impl Transform<Bnf> for ReplaceProduction<bnf::Item> {
+    fn apply(&self, bnf: &mut Bnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let productions = bnf
+            .rules
+            .get_mut(&self.rule)
+            .ok_or_else(|| TransformError::UnknownRule(self.rule.clone()))?;
+        check_production(productions.len(), &self.rule, self.index)?;
+        productions[self.index] = self.items.clone();
+        // The new items have no source
+        if let Some(spans) = bnf
+            .spans
+            .get_mut(&self.rule)
+            .and_then(|spans| spans.get_mut(self.index))
+        {
+            spans.clear();
+        }
+        log.record(
+            "replace",
+            format!("replaced production {} of '{}'", self.index, self.rule),
+        );
+        Ok(())
+    }
+}
+
+/// Replaces every reference to a rule by its body and removes the rule.
+#[derive(Debug, Clone)]
+pub struct InlineRule {
+    pub rule: String,
+}
+
+impl InlineRule {
+    pub fn new(rule: impl Into<String>) -> Self {
+        Self { rule: rule.into() }
+    }
+
+    fn check<'a, P>(
+        &self,
+        rules: &'a IndexMap<String, P>,
+        refers_to_itself: impl Fn(&P) -> bool,
+    ) -> Result<&'a P, TransformError> {
+        let body = rules
+            .get(&self.rule)
+            .ok_or_else(|| TransformError::UnknownRule(self.rule.clone()))?;
+        if rules.get_index_of(&self.rule) == Some(0) {
+            return Err(TransformError::InlineStart(self.rule.clone()));
+        }
+        if refers_to_itself(body) {
+            return Err(TransformError::RecursiveInline(self.rule.clone()));
+        }
+        Ok(body)
+    }
+
+    fn record(&self, log: &mut ChangeLog, references: usize) {
+        log.record(
+            "inline",
+            format!("inlined '{}' at {} references", self.rule, references),
+        );
+    }
+}
+
+impl Transform<Sebnf> for InlineRule {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let body = self
+            .check(&sebnf.rules, |alternatives| {
+                let mut finder = Finder(&self.rule, false);
+                finder.visit_alternatives(alternatives);
+                finder.1
+            })?
+            .clone();
+
+        struct Finder<'a>(&'a str, bool);
+        impl Visitor for Finder<'_> {
+            fn visit_non_terminal(&mut self, name: &str, _span: &Range<usize>) {
+                self.1 |= name == self.0;
+            }
+        }
+
+        struct Inliner<'a> {
+            name: &'a str,
+            body: &'a [Vec<sebnf::Item>],
+            references: usize,
+        }
+        impl VisitorMut for Inliner<'_> {
+            // A labeled reference keeps its label, on a group if need be
+            fn visit_item_mut(&mut self, item: &mut sebnf::Item) {
+                if let sebnf::Item::Labeled(_, inner, _) = item
+                    && let sebnf::Item::NonTerminal(name, span) = inner.as_ref()
+                    && name == self.name
+                {
+                    self.references += 1;
+                    **inner = match self.body {
+                        [single] if single.len() == 1 => match &single[0] {
+                            sebnf::Item::Labeled(_, item, _) => item.as_ref().clone(),
+                            item => item.clone(),
+                        },
+                        alternatives => sebnf::Item::Choice(alternatives.to_vec(), span.clone()),
+                    };
+                }
+                walk_item_mut(self, item);
+            }
+
+            fn visit_sequence_mut(&mut self, items: &mut Vec<sebnf::Item>) {
+                walk_sequence_mut(self, items);
+                let mut inlined = Vec::with_capacity(items.len());
+                for item in items.drain(..) {
+                    match item {
+                        sebnf::Item::NonTerminal(name, span) if name == self.name => {
+                            self.references += 1;
+                            match self.body {
+                                [single] => inlined.extend(single.iter().cloned()),
+                                alternatives => {
+                                    inlined.push(sebnf::Item::Choice(alternatives.to_vec(), span))
+                                }
+                            }
+                        }
+                        item => inlined.push(item),
+                    }
+                }
+                *items = inlined;
+            }
+        }
+
+        let mut inliner = Inliner {
+            name: &self.rule,
+            body: &body,
+            references: 0,
+        };
+        sebnf.rules.shift_remove(&self.rule);
+        sebnf.rule_spans.shift_remove(&self.rule);
+        let annotations = sebnf.annotations.shift_remove(&self.rule);
+
+        // The soft keywords of the rule stay soft where its body ends up
+        let soft_keywords = annotations.map(|a| a.soft_keywords).unwrap_or_default();
+        if !soft_keywords.is_empty() {
+            for (name, alternatives) in &sebnf.rules {
+                let mut finder = Finder(&self.rule, false);
+                finder.visit_alternatives(alternatives);
+                if finder.1 {
+                    let host = sebnf.annotations.entry(name.clone()).or_default();
+                    for keyword in &soft_keywords {
+                        if !host.soft_keywords.contains(keyword) {
+                            host.soft_keywords.push(keyword.clone());
+                        }
+                    }
+                }
+            }
+        }
+        walk_sebnf_mut(&mut inliner, sebnf);
+        self.record(log, inliner.references);
+        Ok(())
+    }
+}
+
+impl Transform<Bnf> for InlineRule {
+    fn apply(&self, bnf: &mut Bnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let is_reference =
+            |item: &bnf::Item| matches!(item, bnf::Item::NonTerminal(n) if *n == self.rule);
+        let body = self
+            .check(&bnf.rules, |productions| {
+                productions.iter().flatten().any(is_reference)
+            })?
+            .clone();
+        let body_spans = production_spans(bnf, &self.rule);
+        bnf.rules.shift_remove(&self.rule);
+        bnf.spans.shift_remove(&self.rule);
+
+        let mut references = 0;
+        for (nt, productions) in bnf.rules.iter_mut() {
+            if !productions.iter().flatten().any(is_reference) {
+                continue;
+            }
+            let spans = bnf.spans.get(nt);
+            let mut new_productions = Vec::new();
+            let mut new_spans = Vec::new();
+            for (i, production) in productions.iter().enumerate() {
+                let spans = spans
+                    .and_then(|spans| spans.get(i))
+                    .filter(|spans| spans.len() == production.len());
+                // Every reference multiplies the production by the body's alternatives
+                let mut expanded: Vec<SpannedProduction> = vec![(Vec::new(), Some(Vec::new()))];
+                for (j, item) in production.iter().enumerate() {
+                    let span = spans.map(|spans| spans[j].clone());
+                    if !is_reference(item) {
+                        for (items, item_spans) in &mut expanded {
+                            items.push(item.clone());
+                            push_span(item_spans, span.clone());
+                        }
+                        continue;
+                    }
+                    references += 1;
+                    expanded = expanded
+                        .into_iter()
+                        .flat_map(|(items, item_spans)| {
+                            body.iter().zip(&body_spans).map(move |(alt, alt_spans)| {
+                                let mut items = items.clone();
+                                items.extend(alt.iter().cloned());
+                                let item_spans = match (item_spans.clone(), alt_spans) {
+                                    (Some(mut s), Some(alt_spans)) => {
+                                        s.extend(alt_spans.iter().cloned());
+                                        Some(s)
+                                    }
+                                    _ => None,
+                                };
+                                (items, item_spans)
+                            })
+                        })
+                        .collect();
+                }
+                for (items, item_spans) in expanded {
+                    new_productions.push(items);
+                    new_spans.push(item_spans.unwrap_or_default());
+                }
+            }
+            *productions = new_productions;
+            bnf.spans.insert(nt.clone(), new_spans);
+        }
+        self.record(log, references);
+        Ok(())
+    }
+}
+
+/// Removes useless rules: first those that derive no string of terminals,
+/// together with the productions using them, then those no longer reachable
+/// from the start rule.
+#[derive(Debug, Clone, Default)]
+pub struct Simplify;
+
+impl Transform<Bnf> for Simplify {
+    fn apply(&self, bnf: &mut Bnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let Some(start) = bnf.rules.keys().next().cloned() else {
+            return Ok(());
+        };
+        let productive = bnf.productive_rules();
+        if !productive.contains(&start) {
+            return Err(TransformError::EmptyLanguage(start));
+        }
+
+        let uses_productive_rules = |production: &Vec<bnf::Item>| {
+            production.iter().all(|item| match item {
+                bnf::Item::NonTerminal(name) => productive.contains(name),
+                _ => true,
+            })
+        };
+        for (nt, productions) in bnf.rules.iter_mut() {
+            if !productive.contains(nt) {
+                log.record(
+                    "simplify",
+                    format!("dropped '{}': it derives no string of terminals", nt),
+                );
+                continue;
+            }
+            let keep: Vec<bool> = productions.iter().map(uses_productive_rules).collect();
+            let removed = keep.iter().filter(|keep| !**keep).count();
+            if removed == 0 {
+                continue;
+            }
+            let mut keep_production = keep.iter().copied();
+            productions.retain(|_| keep_production.next().unwrap_or(true));
+            if let Some(spans) = bnf.spans.get_mut(nt)
+                && spans.len() == keep.len()
+            {
+                let mut keep_span = keep.iter().copied();
+                spans.retain(|_| keep_span.next().unwrap_or(true));
+            }
+            log.record(
+                "simplify",
+                format!(
+                    "removed {} production(s) of '{}' that use non-productive rules",
+                    removed, nt
+                ),
+            );
+        }
+        bnf.rules.retain(|nt, _| productive.contains(nt));
+
+        let reachable = bnf.reachable_rules();
+        for nt in bnf.rules.keys().filter(|nt| !reachable.contains(*nt)) {
+            log.record(
+                "simplify",
+                format!("dropped '{}': it is unreachable from '{}'", nt, start),
+            );
+        }
+        bnf.rules.retain(|nt, _| reachable.contains(nt));
+        let rules = &bnf.rules;
+        bnf.spans.retain(|nt, _| rules.contains_key(nt));
+        Ok(())
+    }
+}
+
+/// Replaces every unit production `A := B`, where `B` is a lone
+/// non-terminal, by the productions of `B` (following chains `B := C`), so no
+/// rule refers to another one without consuming input. Rules that are no
+/// longer referenced stay; [`Simplify`] removes them.
+#[derive(Debug, Clone, Default)]
+pub struct EliminateUnitProductions;
+
+impl Transform<Bnf> for EliminateUnitProductions {
+    fn apply(&self, bnf: &mut Bnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let unit_target = |production: &[bnf::Item]| match production {
+            [bnf::Item::NonTerminal(name)] => Some(name.clone()),
+            _ => None,
+        };
+        let original = bnf.clone();
+
+        for (nt, productions) in bnf.rules.iter_mut() {
+            if !productions.iter().any(|p| unit_target(p).is_some()) {
+                continue;
+            }
+            // Rules reachable through unit productions, in the order met
+            let mut closure = vec![nt.clone()];
+            let mut i = 0;
+            while i < closure.len() {
+                for production in original.rules.get(&closure[i]).into_iter().flatten() {
+                    if let Some(target) = unit_target(production)
+                        && !closure.contains(&target)
+                    {
+                        closure.push(target);
+                    }
+                }
+                i += 1;
+            }
+
+            let mut new_productions = Vec::new();
+            let mut new_spans = Vec::new();
+            for member in &closure {
+                let Some(member_productions) = original.rules.get(member) else {
+                    continue;
+                };
+                let spans = production_spans(&original, member);
+                for (production, spans) in member_productions.iter().zip(spans) {
+                    if unit_target(production).is_none() && !new_productions.contains(production) {
+                        new_productions.push(production.clone());
+                        new_spans.push(spans.unwrap_or_default());
+                    }
+                }
+            }
+            let units: Vec<_> = closure[1..].iter().map(|n| format!("'{}'", n)).collect();
+            log.record(
+                "unit-productions",
+                format!(
+                    "replaced the unit productions of '{}' (through {}) by {} production(s)",
+                    nt,
+                    units.join(", "),
+                    new_productions.len()
+                ),
+            );
+            *productions = new_productions;
+            bnf.spans.insert(nt.clone(), new_spans);
+        }
+        Ok(())
+    }
+}
+
+/// A rule name based on `base` that `bnf` does not use yet: `base`, then
+/// `base_2`, `base_3`, ...
+fn fresh_rule_name(bnf: &Bnf, base: &str) -> String {
+    let mut name = base.to_string();
+    let mut n = 1;
+    while bnf.rules.contains_key(&name) {
+        n += 1;
+        name = format!("{}_{}", base, n);
+    }
+    name
+}
+
+/// Adds `rule` right after `after`, without spans.
+fn insert_rule_after(bnf: &mut Bnf, after: &str, rule: String, productions: Vec<Vec<bnf::Item>>) {
+    let index = bnf
+        .rules
+        .get_index_of(after)
+        .map_or(bnf.rules.len(), |i| i + 1);
+    bnf.rules.shift_insert(index, rule, productions);
+}
+
+/// Factors the longest common prefix out of the productions of a rule that
+/// start with the same item, into a new rule for what follows it:
+/// `a := "x" "y" | "x" "z".` becomes `a := "x" a_tail.` with
+/// `a_tail := "y" | "z".`. The new rules are factored in turn, so afterwards
+/// no two productions of the rule or of its new rules start alike.
+#[derive(Debug, Clone)]
+pub struct LeftFactor {
+    pub rule: String,
+}
+
+impl LeftFactor {
+    pub fn new(rule: impl Into<String>) -> Self {
+        Self { rule: rule.into() }
+    }
+}
+
+impl Transform<Bnf> for LeftFactor {
+    fn apply(&self, bnf: &mut Bnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        if !bnf.rules.contains_key(&self.rule) {
+            return Err(TransformError::UnknownRule(self.rule.clone()));
+        }
+        let mut pending = vec![self.rule.clone()];
+        while let Some(rule) = pending.pop() {
+            let productions = bnf.rules[&rule].clone();
+            let mut factored = vec![false; productions.len()];
+            let mut new_productions = Vec::new();
+            let mut tails = Vec::new();
+            for (i, production) in productions.iter().enumerate() {
+                if factored[i] {
+                    continue;
+                }
+                let group: Vec<usize> = (i..productions.len())
+                    .filter(|&j| {
+                        !production.is_empty() && productions[j].first() == production.first()
+                    })
+                    .collect();
+                if group.len() < 2 {
+                    new_productions.push(production.clone());
+                    continue;
+                }
+                let prefix_len = (1..production.len())
+                    .take_while(|&len| {
+                        group
+                            .iter()
+                            .all(|&j| productions[j].get(len) == production.get(len))
+                    })
+                    .last()
+                    .map_or(1, |len| len + 1);
+                let mut tail_productions: Vec<Vec<bnf::Item>> = Vec::new();
+                for &j in &group {
+                    factored[j] = true;
+                    let tail = productions[j][prefix_len..].to_vec();
+                    if !tail_productions.contains(&tail) {
+                        tail_productions.push(tail);
+                    }
+                }
+                let tail = fresh_rule_name(bnf, &format!("{}_tail", self.rule));
+                let mut prefix = production[..prefix_len].to_vec();
+                log.record(
+                    "left-factor",
+                    format!(
+                        "factored `{}` out of {} productions of '{}' into '{}'",
+                        format_production(&prefix),
+                        group.len(),
+                        rule,
+                        tail
+                    ),
+                );
+                prefix.push(bnf::Item::NonTerminal(tail.clone()));
+                new_productions.push(prefix);
+                // Reserve the name before looking for the next one
+                bnf.rules.insert(tail.clone(), Vec::new());
+                tails.push((tail, tail_productions));
+            }
+            if tails.is_empty() {
+                continue;
+            }
+            for (tail, tail_productions) in tails.into_iter().rev() {
+                bnf.rules.shift_remove(&tail);
+                insert_rule_after(bnf, &rule, tail.clone(), tail_productions);
+                pending.push(tail);
+            }
+            bnf.rules.insert(rule.clone(), new_productions);
+            bnf.spans.shift_remove(&rule);
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the direct left recursion of a rule by right recursion through
+/// a new rule: `a := a "x" | "y".` becomes `a := "y" a_rest.` with
+/// `a_rest := "x" a_rest | ε.`. A rule with no production that does not
+/// start with itself derives no string and is left as it is, as is one that
+/// is not directly left-recursive.
+#[derive(Debug, Clone)]
+pub struct EliminateLeftRecursion {
+    pub rule: String,
+}
+
+impl EliminateLeftRecursion {
+    pub fn new(rule: impl Into<String>) -> Self {
+        Self { rule: rule.into() }
+    }
+}
+
+impl Transform<Bnf> for EliminateLeftRecursion {
+    fn apply(&self, bnf: &mut Bnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let Some(productions) = bnf.rules.get(&self.rule) else {
+            return Err(TransformError::UnknownRule(self.rule.clone()));
+        };
+        let starts_with_rule = |production: &[bnf::Item]| matches!(production.first(), Some(bnf::Item::NonTerminal(nt)) if *nt == self.rule);
+        let (recursive, others): (Vec<_>, Vec<_>) = (productions.iter())
+            .cloned()
+            .partition(|production| starts_with_rule(production));
+        if recursive.is_empty() || others.is_empty() {
+            return Ok(());
+        }
+        let rest = fresh_rule_name(bnf, &format!("{}_rest", self.rule));
+        let with_rest = |mut items: Vec<bnf::Item>| {
+            items.push(bnf::Item::NonTerminal(rest.clone()));
+            items
+        };
+        let mut rest_productions: Vec<Vec<bnf::Item>> = (recursive.iter())
+            .filter(|production| production.len() > 1)
+            .map(|production| with_rest(production[1..].to_vec()))
+            .collect();
+        rest_productions.push(Vec::new());
+        let new_productions = others.into_iter().map(with_rest).collect();
+        log.record(
+            "left-recursion",
+            format!(
+                "replaced the left recursion of '{}' in {} production(s) by right recursion through '{}'",
+                self.rule,
+                recursive.len(),
+                rest
+            ),
+        );
+        bnf.rules.insert(self.rule.clone(), new_productions);
+        bnf.spans.shift_remove(&self.rule);
+        insert_rule_after(bnf, &self.rule.clone(), rest, rest_productions);
+        Ok(())
+    }
+}
+
+/// Items of a production and their spans, `None` once a span is unknown
+type SpannedProduction = (Vec<bnf::Item>, Option<Vec<Range<usize>>>);
+
+/// Spans of each production of `nt`, `None` where they are incomplete.
+fn production_spans(bnf: &Bnf, nt: &str) -> Vec<Option<Vec<Range<usize>>>> {
+    bnf.rules[nt]
+        .iter()
+        .enumerate()
+        .map(|(i, production)| {
+            bnf.spans
+                .get(nt)
+                .and_then(|spans| spans.get(i))
+                .filter(|spans| spans.len() == production.len())
+                .cloned()
+        })
+        .collect()
+}
+
+/// Keeps only the rules reachable from `root`, which becomes the start rule.
+/// The other rules keep their order.
+#[derive(Debug, Clone)]
+pub struct ExtractRoot {
+    pub root: String,
+}
+
+impl ExtractRoot {
+    pub fn new(root: impl Into<String>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+/// Adds the rules reachable from `root` that are not in `reachable` yet to
+/// it, breadth first and in the order they are referenced.
+fn extend_reachable(sebnf: &Sebnf, root: &str, reachable: &mut Vec<String>) {
+    struct References(Vec<String>);
+    impl Visitor for References {
+        fn visit_non_terminal(&mut self, name: &str, _span: &Range<usize>) {
+            self.0.push(name.to_string());
+        }
+    }
+
+    let mut i = reachable.len();
+    reachable.push(root.to_string());
+    while i < reachable.len() {
+        let mut references = References(Vec::new());
+        if let Some(alternatives) = sebnf.rules.get(&reachable[i]) {
+            references.visit_alternatives(alternatives);
+        }
+        for name in references.0 {
+            if !reachable.contains(&name) {
+                reachable.push(name);
+            }
+        }
+        i += 1;
+    }
+}
+
+impl Transform<Sebnf> for ExtractRoot {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        if !sebnf.rules.contains_key(&self.root) {
+            return Err(TransformError::UnknownRule(self.root.clone()));
+        }
+        let mut reachable = Vec::new();
+        extend_reachable(sebnf, &self.root, &mut reachable);
+
+        let dropped: Vec<String> = sebnf
+            .rules
+            .keys()
+            .filter(|name| !reachable.contains(name))
+            .cloned()
+            .collect();
+        for name in &dropped {
+            sebnf.rules.shift_remove(name);
+            sebnf.rule_spans.shift_remove(name);
+            sebnf.annotations.shift_remove(name);
+        }
+        if let Some(index) = sebnf.rules.get_index_of(&self.root) {
+            sebnf.rules.move_index(index, 0);
+        }
+        if let Some(index) = sebnf.rule_spans.get_index_of(&self.root) {
+            sebnf.rule_spans.move_index(index, 0);
+        }
+        if !dropped.is_empty() {
+            log.record(
+                "extract",
+                format!(
+                    "dropped {} rule(s) unreachable from '{}': {}",
+                    dropped.len(),
+                    self.root,
+                    dropped.join(", ")
+                ),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn push_span(spans: &mut Option<Vec<Range<usize>>>, span: Option<Range<usize>>) {
+    match span {
+        Some(span) => {
+            if let Some(spans) = spans {
+                spans.push(span);
+            }
+        }
+        None => *spans = None,
+    }
+}
+
+/// Removes alternatives written twice within a rule, and replaces each rule
+/// with the same alternatives as an earlier rule by that rule, until no
+/// copies are left. See [`Sebnf::repeated_alternatives`] and
+/// [`Sebnf::identical_rules`].
+#[derive(Debug, Clone, Default)]
+pub struct Dedupe;
+
+impl Transform<Sebnf> for Dedupe {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        loop {
+            // Back to front, so the indices stay valid
+            for (rule, index, first) in sebnf.repeated_alternatives().into_iter().rev() {
+                if let Some(alternatives) = sebnf.rules.get_mut(&rule) {
+                    alternatives.remove(index);
+                }
+                log.record(
+                    "dedupe",
+                    format!(
+                        "removed alternative {} of '{}', a copy of alternative {}",
+                        index + 1,
+                        rule,
+                        first + 1
+                    ),
+                );
+            }
+
+            // Replacing a rule can make others identical, or repeat alternatives
+            let identical = sebnf.identical_rules();
+            if identical.is_empty() {
+                return Ok(());
+            }
+            struct Replace(Vec<(String, String)>);
+            impl VisitorMut for Replace {
+                fn visit_item_mut(&mut self, item: &mut sebnf::Item) {
+                    if let sebnf::Item::NonTerminal(name, _) = item
+                        && let Some((_, other)) = self.0.iter().find(|(n, _)| n == name)
+                    {
+                        *name = other.clone();
+                    }
+                    walk_item_mut(self, item);
+                }
+            }
+            for (name, other) in &identical {
+                sebnf.rules.shift_remove(name);
+                sebnf.rule_spans.shift_remove(name);
+                sebnf.annotations.shift_remove(name);
+                log.record(
+                    "dedupe",
+                    format!(
+                        "replaced '{}' by '{}', which has the same alternatives",
+                        name, other
+                    ),
+                );
+            }
+            walk_sebnf_mut(&mut Replace(identical), sebnf);
+        }
+    }
+}
+
+/// Removes what can never take part in a parse: rules that derive no string
+/// of terminals, the alternatives using them (see
+/// [`Sebnf::dead_alternatives`]), and the `[ ]`, `{ }` and group alternatives
+/// using them. A `sep_by` whose element is dead only matches nothing and is
+/// removed too; one whose separator is dead becomes `[ element ]`. Each
+/// removal is recorded with the rule that made it dead.
+#[derive(Debug, Clone, Default)]
+pub struct Prune;
+
+impl Transform<Sebnf> for Prune {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        let Some(start) = sebnf.rules.keys().next().cloned() else {
+            return Ok(());
+        };
+        let productive = sebnf.to_bnf().productive_rules();
+        if !productive.contains(&start) {
+            return Err(TransformError::EmptyLanguage(start));
+        }
+
+        let dead_rules: Vec<String> = (sebnf.rules.keys())
+            .filter(|name| !productive.contains(*name))
+            .cloned()
+            .collect();
+        for name in &dead_rules {
+            sebnf.rules.shift_remove(name);
+            sebnf.rule_spans.shift_remove(name);
+            sebnf.annotations.shift_remove(name);
+            log.record(
+                "prune",
+                format!("dropped '{}': it derives no string of terminals", name),
+            );
+        }
+        for (name, alternatives) in sebnf.rules.iter_mut() {
+            let mut number = 0;
+            alternatives.retain(|alt| {
+                number += 1;
+                let Some(through) = sebnf::dead_end(alt, &productive) else {
+                    return true;
+                };
+                log.record(
+                    "prune",
+                    format!(
+                        "removed alternative {} of '{}': it uses '{}'",
+                        number, name, through
+                    ),
+                );
+                false
+            });
+        }
+
+        struct Pruner<'a> {
+            productive: &'a HashSet<String>,
+            /// What was removed or replaced, and the rule that made it dead
+            changes: Vec<(String, String)>,
+        }
+        impl Pruner<'_> {
+            fn dead(&self, items: &[sebnf::Item]) -> Option<String> {
+                sebnf::dead_end(items, self.productive).map(str::to_string)
+            }
+        }
+        impl VisitorMut for Pruner<'_> {
+            fn visit_item_mut(&mut self, item: &mut sebnf::Item) {
+                match item {
+                    sebnf::Item::Choice(alternatives, _) => {
+                        let mut changes = Vec::new();
+                        alternatives.retain(|alt| {
+                            let Some(through) = self.dead(alt) else {
+                                return true;
+                            };
+                            let removed = format!("removed `{}` of a group", sebnf::written(alt));
+                            changes.push((removed, through));
+                            false
+                        });
+                        self.changes.extend(changes);
+                    }
+                    // With a dead element, it is removed as it only matches nothing
+                    sebnf::Item::Builtin(sebnf::Builtin::SepBy, args, span) => {
+                        if let [element, separator] = args.as_slice()
+                            && let Some(through) = self.dead(separator)
+                        {
+                            let optional = sebnf::Item::Optional(element.clone(), span.clone());
+                            let replaced = format!("replaced `{}` by `{}`", item, optional);
+                            self.changes.push((replaced, through));
+                            *item = optional;
+                        }
+                    }
+                    _ => {}
+                }
+                walk_item_mut(self, item);
+            }
+
+            fn visit_sequence_mut(&mut self, items: &mut Vec<sebnf::Item>) {
+                let mut changes = Vec::new();
+                items.retain(|item| {
+                    let inner = match item {
+                        sebnf::Item::Labeled(_, inner, _) => inner.as_ref(),
+                        item => item,
+                    };
+                    let through = match inner {
+                        sebnf::Item::Optional(inner, _) | sebnf::Item::AnyAmount(inner, _) => {
+                            self.dead(inner)
+                        }
+                        sebnf::Item::Builtin(sebnf::Builtin::SepBy, args, _) => {
+                            args.first().and_then(|element| self.dead(element))
+                        }
+                        _ => None,
+                    };
+                    let Some(through) = through else {
+                        return true;
+                    };
+                    changes.push((format!("removed `{}`", item), through));
+                    false
+                });
+                self.changes.extend(changes);
+                walk_sequence_mut(self, items);
+            }
+        }
+
+        for (name, alternatives) in sebnf.rules.iter_mut() {
+            let mut pruner = Pruner {
+                productive: &productive,
+                changes: Vec::new(),
+            };
+            walk_alternatives_mut(&mut pruner, alternatives);
+            for (change, through) in pruner.changes {
+                log.record(
+                    "prune",
+                    format!("{} in '{}': it uses '{}'", change, name, through),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rewrites a grammar into a canonical form, so that grammars which differ
+/// only in how they are written print the same:
+///
+/// - whitespace runs within terminals become one space, and leading and
+///   trailing whitespace is dropped, since the lexer skips it anyway
+/// - groups of a single alternative, and groups that are the only item of
+///   an alternative or of another `[ ]` or `{ }`, are merged into what
+///   contains them; labeled groups are kept
+/// - alternatives are sorted as written
+/// - rules are ordered breadth first from the start rule, in the order they
+///   are referenced; unreachable rules follow by name, each with the rules
+///   only it reaches
+#[derive(Debug, Clone, Default)]
+pub struct Normalize;
+
+impl Transform<Sebnf> for Normalize {
+    fn apply(&self, sebnf: &mut Sebnf, log: &mut ChangeLog) -> Result<(), TransformError> {
+        #[derive(Default)]
+        struct Canonical {
+            terminals: usize,
+            groups: usize,
+            sorted: usize,
+        }
+        impl VisitorMut for Canonical {
+            fn visit_alternatives_mut(&mut self, alternatives: &mut Vec<Vec<sebnf::Item>>) {
+                walk_alternatives_mut(self, alternatives);
+                let mut flat = Vec::with_capacity(alternatives.len());
+                for alternative in alternatives.drain(..) {
+                    match <[sebnf::Item; 1]>::try_from(alternative) {
+                        Ok([sebnf::Item::Choice(inner, _)]) => {
+                            self.groups += 1;
+                            flat.extend(inner);
+                        }
+                        Ok([item]) => flat.push(vec![item]),
+                        Err(alternative) => flat.push(alternative),
+                    }
+                }
+                let written: Vec<String> = flat.iter().map(|alt| sebnf::written(alt)).collect();
+                if !written.is_sorted() {
+                    self.sorted += 1;
+                    let mut keyed: Vec<_> = written.into_iter().zip(flat).collect();
+                    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    flat = keyed.into_iter().map(|(_, alt)| alt).collect();
+                }
+                *alternatives = flat;
+            }
+
+            fn visit_sequence_mut(&mut self, items: &mut Vec<sebnf::Item>) {
+                walk_sequence_mut(self, items);
+                let mut flat = Vec::with_capacity(items.len());
+                for item in items.drain(..) {
+                    match item {
+                        sebnf::Item::Choice(mut alternatives, _) if alternatives.len() == 1 => {
+                            self.groups += 1;
+                            flat.append(&mut alternatives[0]);
+                        }
+                        item => flat.push(item),
+                    }
+                }
+                *items = flat;
+            }
+
+            fn visit_item_mut(&mut self, item: &mut sebnf::Item) {
+                walk_item_mut(self, item);
+                match item {
+                    sebnf::Item::Terminal(text, _) => {
+                        let normalized = normalize_terminal(text);
+                        if *text != normalized {
+                            self.terminals += 1;
+                            *text = normalized;
+                        }
+                    }
+                    // `[ [ x ] ]` is `[ x ]`; with a `{ }` on either side, it is `{ x }`
+                    sebnf::Item::Optional(inner, span) | sebnf::Item::AnyAmount(inner, span)
+                        if matches!(
+                            inner.as_slice(),
+                            [sebnf::Item::Optional(..) | sebnf::Item::AnyAmount(..)]
+                        ) =>
+                    {
+                        let span = span.clone();
+                        let repeated = matches!(item, sebnf::Item::AnyAmount(..));
+                        let (sebnf::Item::Optional(inner, _) | sebnf::Item::AnyAmount(inner, _)) =
+                            item
+                        else {
+                            unreachable!()
+                        };
+                        let nested = inner.remove(0);
+                        let repeated = repeated || matches!(nested, sebnf::Item::AnyAmount(..));
+                        let (sebnf::Item::Optional(contents, _)
+                        | sebnf::Item::AnyAmount(contents, _)) = nested
+                        else {
+                            unreachable!()
+                        };
+                        self.groups += 1;
+                        *item = match repeated {
+                            true => sebnf::Item::AnyAmount(contents, span),
+                            false => sebnf::Item::Optional(contents, span),
+                        };
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut canonical = Canonical::default();
+        walk_sebnf_mut(&mut canonical, sebnf);
+        if canonical.terminals > 0 {
+            log.record(
+                "normalize",
+                format!(
+                    "normalized whitespace in {} terminal(s)",
+                    canonical.terminals
+                ),
+            );
+        }
+        if canonical.groups > 0 {
+            log.record(
+                "normalize",
+                format!("merged {} group(s) into their context", canonical.groups),
+            );
+        }
+        if canonical.sorted > 0 {
+            log.record(
+                "normalize",
+                format!("sorted {} list(s) of alternatives", canonical.sorted),
+            );
+        }
+
+        let mut order = Vec::new();
+        if let Some(start) = sebnf.rules.keys().next() {
+            extend_reachable(sebnf, start, &mut order);
+        }
+        let mut unreachable: Vec<String> = (sebnf.rules.keys())
+            .filter(|name| !order.contains(name))
+            .cloned()
+            .collect();
+        unreachable.sort();
+        for name in unreachable {
+            if !order.contains(&name) {
+                extend_reachable(sebnf, &name, &mut order);
+            }
+        }
+        let before: Vec<&String> = sebnf.rules.keys().collect();
+        let defined: Vec<&String> = order
+            .iter()
+            .filter(|name| sebnf.rules.contains_key(*name))
+            .collect();
+        if before != defined {
+            let position = |name: &String| order.iter().position(|n| n == name);
+            sebnf.rules.sort_by_cached_key(|name, _| position(name));
+            sebnf
+                .rule_spans
+                .sort_by_cached_key(|name, _| position(name));
+            log.record("normalize", "reordered the rules from the start rule");
+        }
+        Ok(())
+    }
+}
+
+/// Collapses whitespace runs within the quotes of a terminal to one space and
+/// trims it, keeping a terminal of only whitespace as one space
+fn normalize_terminal(terminal: &str) -> String {
+    let inner = &terminal[1..terminal.len() - 1];
+    let words: Vec<&str> = inner
+        .split([' ', '\t', '\n', '\r'])
+        .filter(|w| !w.is_empty())
+        .collect();
+    match words.is_empty() && !inner.is_empty() {
+        true => "\" \"".to_string(),
+        false => format!("\"{}\"", words.join(" ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalized_grammars_print_the_same() {
+        let a = "s := ( b ) | \"a  b\" | [ { c } ].\nz := \"z\".\nc := \"c\".\nb := \" x \" | c.";
+        let b = "s := \"a b\" | { c } | b.\nb := (c | \"x\").\nc := \"c\".\nz := \"z\".";
+        let normalize = |source| {
+            let sebnf = Sebnf::parse_str(source, "g").unwrap();
+            Engine::new()
+                .with(Normalize)
+                .run(&sebnf)
+                .unwrap()
+                .0
+                .to_string()
+        };
+        assert_eq!(normalize(a), normalize(b));
+        assert_eq!(
+            normalize(a),
+            "s := \"a b\"\n   | b\n   | { c }\n   .\nb := \"x\"\n   | c\n   .\nc := \"c\".\nz := \"z\".\n"
+        );
+    }
+
+    #[test]
+    fn dedupe_removes_copies_until_none_are_left() {
+        let source = "s := a | b | a.\na := \"x\" c.\nb := \"x\" d.\nc := \"y\".\nd := \"y\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let (sebnf, log) = Engine::new().with(Dedupe).run(&sebnf).unwrap();
+        assert_eq!(sebnf.to_string(), "s := a.\na := \"x\" c.\nc := \"y\".\n");
+        assert_eq!(log.changes.len(), 4);
+    }
+
+    #[test]
+    fn prune_removes_what_uses_dead_rules() {
+        let source = "s := a | loop | \"q\" [ loop ] ( \"y\" | loop ).\na := \"a\".\nloop := \"(\" loop \")\".";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        assert_eq!(
+            sebnf.dead_alternatives(),
+            [
+                ("s".to_string(), 1, "loop".to_string()),
+                ("loop".to_string(), 0, "loop".to_string())
+            ]
+        );
+        let (sebnf, log) = Engine::new().with(Prune).run(&sebnf).unwrap();
+        assert_eq!(
+            sebnf.to_string(),
+            "s := a\n   | \"q\" ( \"y\" )\n   .\na := \"a\".\n"
+        );
+        assert_eq!(
+            log.to_string(),
+            "prune: dropped 'loop': it derives no string of terminals\n\
+             prune: removed alternative 2 of 's': it uses 'loop'\n\
+             prune: removed `[ loop ]` in 's': it uses 'loop'\n\
+             prune: removed `loop` of a group in 's': it uses 'loop'\n"
+        );
+    }
+
+    #[test]
+    fn bnf_inline_multiplies_productions() {
+        let bnf = Sebnf::parse_str("s := x \"a\" x.\nx := \"b\" | \"c\".", "g")
+            .unwrap()
+            .to_bnf();
+        let (bnf, log) = Engine::new()
+            .with(InlineRule::new("x"))
+            .with(RenameSymbol::new("s", "start"))
+            .run(&bnf)
+            .unwrap();
+        assert_eq!(
+            bnf.to_string(),
+            "start := \"b\" \"a\" \"b\"\n       \
+             | \"b\" \"a\" \"c\"\n       \
+             | \"c\" \"a\" \"b\"\n       \
+             | \"c\" \"a\" \"c\"\n       \
+             .\n"
+        );
+        assert_eq!(log.changes[0].description, "inlined 'x' at 2 references");
+        // Spans follow the inlined items
+        assert_eq!(bnf.item_span("start", 3, 2), Some(25..28));
+    }
+
+    #[test]
+    fn rename_rewrites_only_the_names_in_source() {
+        let source = "(* entry *)\ns := ex \"+\" [ x:ex ]  { ( ex | \"ex\" ) }.\n@max_depth(2)\nex := \"(\" ex \")\". (* ex *)\n";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let rename = RenameSymbol::new("ex", "expr");
+        assert_eq!(
+            rename.rewrite_source(&sebnf, source).unwrap(),
+            "(* entry *)\ns := expr \"+\" [ x:expr ]  { ( expr | \"ex\" ) }.\n@max_depth(2)\nexpr := \"(\" expr \")\". (* ex *)\n"
+        );
+        let taken = RenameSymbol::new("ex", "s").rewrite_source(&sebnf, source);
+        assert_eq!(taken, Err(TransformError::NameTaken("s".to_string())));
+    }
+
+    #[test]
+    fn extracts_the_rules_reachable_from_a_root() {
+        let source = "program := { stmt }.\nstmt := expr \";\".\nterm := \"x\" | \"(\" expr \")\".\nexpr := term { \"+\" term }.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let (sebnf, log) = Engine::new()
+            .with(ExtractRoot::new("expr"))
+            .run(&sebnf)
+            .unwrap();
+        assert_eq!(
+            sebnf.to_string(),
+            "expr := term { \"+\" term }.\nterm := \"x\"\n      | \"(\" expr \")\"\n      .\n"
+        );
+        assert_eq!(
+            log.to_string(),
+            "extract: dropped 2 rule(s) unreachable from 'expr': program, stmt\n"
+        );
+    }
+
+    #[test]
+    fn simplify_drops_useless_rules() {
+        let source = "s := \"a\" | loop t.\nloop := \"b\" loop.\nt := \"c\".\nu := \"d\".";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let (bnf, log) = Engine::new().with(Simplify).run(&bnf).unwrap();
+        assert_eq!(bnf.to_string(), "s := \"a\".\n");
+        assert_eq!(bnf.spans.keys().collect::<Vec<_>>(), ["s"]);
+        assert_eq!(
+            log.to_string(),
+            "simplify: removed 1 production(s) of 's' that use non-productive rules\n\
+             simplify: dropped 'loop': it derives no string of terminals\n\
+             simplify: dropped 't': it is unreachable from 's'\n\
+             simplify: dropped 'u': it is unreachable from 's'\n"
+        );
+
+        let bnf = Sebnf::parse_str("s := \"a\" s.", "g").unwrap().to_bnf();
+        assert_eq!(
+            Engine::new().with(Simplify).run(&bnf).unwrap_err(),
+            TransformError::EmptyLanguage("s".to_string())
+        );
+    }
+
+    #[test]
+    fn left_factoring_repeats_on_the_new_rules() {
+        let source = "s := \"a\" \"b\" \"c\" | \"a\" \"b\" \"d\" | \"a\" | \"e\".";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let (bnf, log) = Engine::new().with(LeftFactor::new("s")).run(&bnf).unwrap();
+        assert_eq!(
+            bnf.to_string(),
+            "s        := \"a\" s_tail\n          | \"e\"\n          .\n\
+             s_tail   := \"b\" s_tail_2\n          | ε\n          .\n\
+             s_tail_2 := \"c\"\n          | \"d\"\n          .\n"
+        );
+        assert_eq!(
+            log.changes[1].description,
+            "factored `\"b\"` out of 2 productions of 's_tail' into 's_tail_2'"
+        );
+    }
+
+    #[test]
+    fn unit_productions_are_replaced_by_their_targets() {
+        let source = "e := t | e \"+\" t.\nt := f | \"-\" t.\nf := \"x\" | e.";
+        let bnf = Sebnf::parse_str(source, "g").unwrap().to_bnf();
+        let (bnf, log) = Engine::new()
+            .with(EliminateUnitProductions)
+            .run(&bnf)
+            .unwrap();
+        assert_eq!(
+            bnf.to_string(),
+            "e := e \"+\" t\n   | \"-\" t\n   | \"x\"\n   .\n\
+             t := \"-\" t\n   | \"x\"\n   | e \"+\" t\n   .\n\
+             f := \"x\"\n   | e \"+\" t\n   | \"-\" t\n   .\n"
+        );
+        assert_eq!(
+            log.changes[0].description,
+            "replaced the unit productions of 'e' (through 't', 'f') by 3 production(s)"
+        );
+        assert_eq!(bnf.item_span("f", 1, 1), Some(11..14));
+    }
+
+    #[test]
+    fn failing_transform_reports_error() {
+        let sebnf = Sebnf::parse_str("s := x.\nx := \"a\" x | \"b\".", "g").unwrap();
+        let engine = Engine::new().with(InlineRule::new("x"));
+        assert_eq!(
+            engine.run(&sebnf).unwrap_err(),
+            TransformError::RecursiveInline("x".to_string())
+        );
+        let engine = Engine::new().with(RenameSymbol::new("s", "x"));
+        assert_eq!(
+            engine.run(&sebnf).unwrap_err(),
+            TransformError::NameTaken("x".to_string())
+        );
+    }
+}
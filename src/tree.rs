@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::sebnf::{Item, Sebnf};
+use crate::symbols::Symbols;
+
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    /// Maximum number of nested non-terminal expansions, unlimited if `None`
+    pub max_depth: Option<usize>,
+}
+
+/// Renders the expansion tree of the grammar starting at the start symbol.
+///
+/// Every non-terminal is expanded once, at its first occurrence. Later
+/// occurrences are marked `(see above)`, references back into the current
+/// expansion path are marked `(cycle)`. Rules limited by `@max_depth(N)` show
+/// their limit.
+pub fn render_tree(sebnf: &Sebnf, options: &TreeOptions) -> String {
+    let mut out = String::new();
+    let Some(start) = sebnf.rules.keys().next() else {
+        return out;
+    };
+
+    let mut walker = TreeWalker {
+        sebnf,
+        options,
+        out: &mut out,
+        path: Vec::new(),
+        expanded: HashSet::new(),
+        symbols: Symbols::with_current(Symbols::clone),
+    };
+    walker.non_terminal(start, "", "", 0);
+    out
+}
+
+struct TreeWalker<'a> {
+    sebnf: &'a Sebnf,
+    options: &'a TreeOptions,
+    out: &'a mut String,
+    path: Vec<&'a str>,
+    expanded: HashSet<&'a str>,
+    symbols: Symbols,
+}
+
+impl<'a> TreeWalker<'a> {
+    fn line(&mut self, prefix: &str, label: &str) {
+        let _ = writeln!(self.out, "{}{}", prefix, label);
+    }
+
+    fn non_terminal(&mut self, name: &'a str, prefix: &str, child_prefix: &str, depth: usize) {
+        let Some((name, alternatives)) = self.sebnf.rules.get_key_value(name) else {
+            self.line(prefix, &format!("{} (undefined)", name));
+            return;
+        };
+        let name = name.as_str();
+
+        if self.path.contains(&name) {
+            self.line(prefix, &format!("{} (cycle)", name));
+            return;
+        }
+        if self.expanded.contains(name) {
+            self.line(prefix, &format!("{} (see above)", name));
+            return;
+        }
+        if self.options.max_depth.is_some_and(|max| depth >= max) {
+            let ellipsis = self.symbols.glyph("…", "...");
+            self.line(prefix, &format!("{} {}", name, ellipsis));
+            return;
+        }
+
+        match self.sebnf.annotations.get(name).and_then(|a| a.max_depth) {
+            Some(limit) => self.line(prefix, &format!("{} @max_depth({})", name, limit)),
+            None => self.line(prefix, name),
+        }
+        self.expanded.insert(name);
+        self.path.push(name);
+        self.alternatives(alternatives, child_prefix, depth + 1);
+        self.path.pop();
+    }
+
+    fn alternatives(&mut self, alternatives: &'a [Vec<Item>], prefix: &str, depth: usize) {
+        if let [items] = alternatives {
+            self.items(items, prefix, depth);
+            return;
+        }
+        for (i, items) in alternatives.iter().enumerate() {
+            let last = i + 1 == alternatives.len();
+            let (branch, cont) = self.branch_prefixes(prefix, last);
+            if items.is_empty() {
+                let label = format!("| {}", self.symbols.epsilon);
+                self.line(&branch, &label);
+            } else {
+                self.line(&branch, "|");
+                self.items(items, &cont, depth);
+            }
+        }
+    }
+
+    fn items(&mut self, items: &'a [Item], prefix: &str, depth: usize) {
+        for (i, item) in items.iter().enumerate() {
+            let last = i + 1 == items.len();
+            let (branch, cont) = self.branch_prefixes(prefix, last);
+            self.item(item, &branch, &cont, depth);
+        }
+    }
+
+    fn item(&mut self, item: &'a Item, branch: &str, cont: &str, depth: usize) {
+        match item {
+            Item::NonTerminal(name, _) => self.non_terminal(name, branch, cont, depth),
+            Item::Terminal(s, _) | Item::Regex(s, _) => self.line(branch, s),
+            Item::Epsilon(_) => {
+                let epsilon = self.symbols.epsilon.clone();
+                self.line(branch, &epsilon)
+            }
+            Item::Optional(inner, _) => {
+                self.line(branch, "[ ]");
+                self.items(inner, cont, depth);
+            }
+            Item::AnyAmount(inner, _) => {
+                self.line(branch, "{ }");
+                self.items(inner, cont, depth);
+            }
+            Item::Choice(alternatives, _) => {
+                self.line(branch, "( )");
+                self.alternatives(alternatives, cont, depth);
+            }
+            Item::Labeled(label, inner, _) => {
+                self.item(inner, &format!("{}{}:", branch, label), cont, depth)
+            }
+            Item::Builtin(builtin, args, _) => {
+                self.line(branch, &format!("{}( )", builtin.name()));
+                // One child per argument; multi-item arguments get a group node
+                for (i, arg) in args.iter().enumerate() {
+                    let last = i + 1 == args.len();
+                    let (arg_branch, arg_cont) = self.branch_prefixes(cont, last);
+                    match arg.as_slice() {
+                        [single] => self.item(single, &arg_branch, &arg_cont, depth),
+                        _ => {
+                            self.line(&arg_branch, "( )");
+                            self.items(arg, &arg_cont, depth);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn branch_prefixes(&self, prefix: &str, last: bool) -> (String, String) {
+        match (last, self.symbols.ascii) {
+            (true, false) => (format!("{}└── ", prefix), format!("{}    ", prefix)),
+            (false, false) => (format!("{}├── ", prefix), format!("{}│   ", prefix)),
+            (true, true) => (format!("{}`-- ", prefix), format!("{}    ", prefix)),
+            (false, true) => (format!("{}|-- ", prefix), format!("{}|   ", prefix)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPR: &str =
+        "expr := term { \"+\" term }.\nterm := \"(\" expr \")\" | num.\nnum := /[0-9]+/.";
+
+    fn tree(source: &str, max_depth: Option<usize>) -> String {
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        render_tree(&sebnf, &TreeOptions { max_depth })
+    }
+
+    #[test]
+    fn expands_each_rule_once() {
+        assert_eq!(
+            tree(EXPR, None),
+            "expr\n\
+             ├── term\n\
+             │   ├── |\n\
+             │   │   ├── \"(\"\n\
+             │   │   ├── expr (cycle)\n\
+             │   │   └── \")\"\n\
+             │   └── |\n\
+             │       └── num\n\
+             │           └── /[0-9]+/\n\
+             └── { }\n    \
+                 ├── \"+\"\n    \
+                 └── term (see above)\n"
+        );
+    }
+
+    #[test]
+    fn depth_cuts_off_expansions() {
+        assert_eq!(
+            tree(EXPR, Some(1)),
+            "expr\n├── term …\n└── { }\n    ├── \"+\"\n    └── term …\n"
+        );
+        assert_eq!(tree(EXPR, Some(0)), "expr …\n");
+    }
+
+    #[test]
+    fn ascii_writes_the_configured_epsilon() {
+        let source = "a := \"x\" | ε | ( \"y\" | ε ).";
+        let ascii = Symbols::ascii().scope(|| tree(source, None));
+        assert_eq!(
+            ascii,
+            "a\n\
+             |-- |\n\
+             |   `-- \"x\"\n\
+             |-- |\n\
+             |   `-- eps\n\
+             `-- |\n    \
+                 `-- ( )\n        \
+                     |-- |\n        \
+                     |   `-- \"y\"\n        \
+                     `-- |\n            \
+                         `-- eps\n"
+        );
+        assert!(ascii.is_ascii());
+    }
+}
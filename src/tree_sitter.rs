@@ -0,0 +1,326 @@
+//! Export of a grammar to a tree-sitter `grammar.js`, to get syntax trees in
+//! editors.
+//!
+//! [`tree_sitter_grammar`] writes a rule per SEBNF rule, built from the
+//! DSL's `seq`, `choice`, `optional`, `repeat` and `repeat1` calls, with the
+//! start rule first as tree-sitter expects. Labels become `field`s, and the
+//! first rule that is only a regex matching a terminal of the grammar
+//! becomes the `word` rule, so keywords are lexed as keywords, as the
+//! lexers of [`codegen`](crate::codegen) do.
+//!
+//! Some constructs do not carry over as they are. Each one is listed as a
+//! [`MappingNote`]: rules other than the start rule that match the empty
+//! string, which tree-sitter rejects; regexes that match the empty string or
+//! use syntax JavaScript regexes lack; and LALR(1) conflicts, which
+//! `tree-sitter generate` wants declared in `conflicts` or settled with
+//! `prec`.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::tree_sitter::tree_sitter_grammar;
+//!
+//! let sebnf = Sebnf::parse_str("sum := num { \"+\" num }.\nnum := /[0-9]+/.", "g").unwrap();
+//! let export = tree_sitter_grammar(&sebnf, "sum");
+//! assert!(export.js.contains("    sum: $ => seq($.num, repeat(seq(\"+\", $.num))),\n"));
+//! assert!(export.notes.is_empty());
+//! ```
+
+use std::fmt;
+use std::fmt::Write;
+
+use regex_automata::meta::Regex;
+
+use crate::codegen::unescape;
+use crate::nullable::nullable_rules;
+use crate::regex_intersect::regex_matches_empty;
+use crate::sebnf::{Builtin, Item, Sebnf};
+use crate::sets::{strip_regex_delimiters, strip_terminal_quotes};
+
+/// The `grammar.js` and what did not translate cleanly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeSitterGrammar {
+    pub js: String,
+    pub notes: Vec<MappingNote>,
+}
+
+/// A construct tree-sitter treats differently or rejects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappingNote {
+    /// The rule it is in, if it concerns one
+    pub rule: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for MappingNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.rule {
+            Some(rule) => write!(f, "'{}': {}", rule, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// A tree-sitter grammar named `name` for the language of `sebnf`.
+pub fn tree_sitter_grammar(sebnf: &Sebnf, name: &str) -> TreeSitterGrammar {
+    let mut notes = Vec::new();
+    let bnf = sebnf.to_bnf();
+    let nullable = nullable_rules(&bnf);
+    for rule in sebnf.rules.keys().skip(1) {
+        if nullable.is_nullable(rule) {
+            notes.push(MappingNote {
+                rule: Some(rule.clone()),
+                message: "matches the empty string, which tree-sitter only allows for the \
+                          start rule; make its uses optional instead"
+                    .to_string(),
+            });
+        }
+    }
+    let mut regexes: Vec<(&str, &str)> = Vec::new();
+    for (rule, alternatives) in &sebnf.rules {
+        collect_regexes(rule, alternatives.iter().flatten(), &mut regexes);
+    }
+    for (rule, regex) in regexes {
+        let pattern = strip_regex_delimiters(regex);
+        let mut message = None;
+        if regex_matches_empty(pattern) {
+            message = Some(format!(
+                "{} matches the empty string, which tree-sitter rejects",
+                regex
+            ));
+        } else if let Some(what) = unsupported_syntax(pattern) {
+            message = Some(format!(
+                "{} uses {}, which tree-sitter regexes lack",
+                regex, what
+            ));
+        }
+        notes.extend(message.map(|message| MappingNote {
+            rule: Some(rule.to_string()),
+            message,
+        }));
+    }
+    if bnf.lalr1_table().has_conflicts() {
+        notes.push(MappingNote {
+            rule: None,
+            message: "the grammar has LALR(1) conflicts (see `is-lalr1`), which `tree-sitter \
+                      generate` asks to list in `conflicts` or settle with `prec`"
+                .to_string(),
+        });
+    }
+
+    let mut js = String::new();
+    let _ = writeln!(js, "// Generated by `sebnf_tool export tree-sitter`.");
+    let _ = writeln!(js);
+    let _ = writeln!(js, "module.exports = grammar({{");
+    let _ = writeln!(js, "  name: {},", js_string(name));
+    if let Some(word) = word_rule(sebnf) {
+        let _ = writeln!(js);
+        let _ = writeln!(js, "  word: $ => $.{},", word);
+    }
+    let _ = writeln!(js);
+    let _ = writeln!(js, "  rules: {{");
+    for (rule, alternatives) in &sebnf.rules {
+        match alternatives.as_slice() {
+            [alternative] => {
+                let _ = writeln!(js, "    {}: $ => {},", rule, sequence(alternative));
+            }
+            _ => {
+                let _ = writeln!(js, "    {}: $ => choice(", rule);
+                for alternative in alternatives {
+                    let _ = writeln!(js, "      {},", sequence(alternative));
+                }
+                let _ = writeln!(js, "    ),");
+            }
+        }
+    }
+    let _ = writeln!(js, "  }},");
+    let _ = writeln!(js, "}});");
+    TreeSitterGrammar { js, notes }
+}
+
+/// The first rule that is a single regex matching one of the terminals
+fn word_rule(sebnf: &Sebnf) -> Option<&str> {
+    let terminals: Vec<&str> = (sebnf.rules.values().flatten().flatten())
+        .filter_map(|item| match item {
+            Item::Terminal(terminal, _) => Some(strip_terminal_quotes(terminal)),
+            _ => None,
+        })
+        .collect();
+    sebnf.rules.iter().find_map(|(rule, alternatives)| {
+        let [alternative] = alternatives.as_slice() else {
+            return None;
+        };
+        let [Item::Regex(regex, _)] = alternative.as_slice() else {
+            return None;
+        };
+        let whole = Regex::new(&format!("^(?:{})$", strip_regex_delimiters(regex))).ok()?;
+        let matches = terminals.iter().any(|terminal| whole.is_match(terminal));
+        matches.then_some(rule.as_str())
+    })
+}
+
+/// Every regex with the rule it occurs in, in the order of the grammar
+fn collect_regexes<'a>(
+    rule: &'a str,
+    items: impl Iterator<Item = &'a Item>,
+    regexes: &mut Vec<(&'a str, &'a str)>,
+) {
+    for item in items {
+        match item {
+            Item::Regex(regex, _) => {
+                if !regexes.contains(&(rule, regex.as_str())) {
+                    regexes.push((rule, regex));
+                }
+            }
+            Item::Optional(items, _) | Item::AnyAmount(items, _) => {
+                collect_regexes(rule, items.iter(), regexes)
+            }
+            Item::Choice(sequences, _) | Item::Builtin(_, sequences, _) => {
+                collect_regexes(rule, sequences.iter().flatten(), regexes)
+            }
+            Item::Labeled(_, item, _) => collect_regexes(rule, std::iter::once(&**item), regexes),
+            Item::NonTerminal(..) | Item::Terminal(..) | Item::Epsilon(_) => {}
+        }
+    }
+}
+
+/// The first syntax of the `regex` crate in `pattern` that tree-sitter's
+/// regexes lack: anchors, word boundaries, flags and named groups, POSIX
+/// classes and set operations on classes
+fn unsupported_syntax(pattern: &str) -> Option<&'static str> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut in_class = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let next = chars.get(i + 1).copied();
+        match (chars[i], in_class) {
+            ('\\', _) => {
+                if let Some('A' | 'z' | 'b' | 'B') = next {
+                    return Some("anchors or word boundaries");
+                }
+                i += 1;
+            }
+            ('[', false) => {
+                in_class = true;
+                // A `]` right after `[` or `[^` is a member
+                i += usize::from(next == Some('^'));
+                i += usize::from(chars.get(i + 1) == Some(&']'));
+            }
+            ('[', true) if next == Some(':') => return Some("POSIX character classes"),
+            ('&', true) | ('-', true) | ('~', true) if next == Some(chars[i]) => {
+                return Some("set operations on character classes");
+            }
+            (']', true) => in_class = false,
+            ('^' | '$', false) => return Some("anchors"),
+            ('(', false) if next == Some('?') => return Some("inline flags or named groups"),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn choice(alternatives: &[Vec<Item>]) -> String {
+    match alternatives {
+        [alternative] => sequence(alternative),
+        _ => {
+            let alternatives: Vec<String> = alternatives.iter().map(|a| sequence(a)).collect();
+            format!("choice({})", alternatives.join(", "))
+        }
+    }
+}
+
+fn sequence(items: &[Item]) -> String {
+    let items: Vec<String> = (items.iter())
+        .filter(|item| !matches!(item, Item::Epsilon(_)))
+        .map(item)
+        .collect();
+    match items.as_slice() {
+        [] => "blank()".to_string(),
+        [item] => item.clone(),
+        _ => format!("seq({})", items.join(", ")),
+    }
+}
+
+fn item(item: &Item) -> String {
+    match item {
+        Item::NonTerminal(name, _) => format!("$.{}", name),
+        Item::Terminal(terminal, _) => js_string(&unescape(strip_terminal_quotes(terminal))),
+        Item::Regex(regex, _) => regex.clone(),
+        Item::Optional(items, _) => format!("optional({})", sequence(items)),
+        Item::AnyAmount(items, _) => format!("repeat({})", sequence(items)),
+        Item::Choice(alternatives, _) => choice(alternatives),
+        Item::Builtin(builtin, arguments, _) => {
+            let arguments: Vec<String> = arguments.iter().map(|a| sequence(a)).collect();
+            match (builtin, arguments.as_slice()) {
+                (Builtin::SepBy, [item, separator]) => {
+                    format!(
+                        "optional(seq({}, repeat(seq({}, {}))))",
+                        item, separator, item
+                    )
+                }
+                (Builtin::Delimited, [left, item, right]) => {
+                    format!("seq({}, {}, {})", left, item, right)
+                }
+                (Builtin::NonEmpty, [item]) => format!("repeat1({})", item),
+                _ => unreachable!("built-in calls are checked when parsing"),
+            }
+        }
+        Item::Labeled(label, labeled, _) => {
+            format!("field({}, {})", js_string(label), self::item(labeled))
+        }
+        Item::Epsilon(_) => "blank()".to_string(),
+    }
+}
+
+/// `text` as a double-quoted JavaScript string
+fn js_string(text: &str) -> String {
+    let mut js = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => js.push_str("\\\""),
+            '\\' => js.push_str("\\\\"),
+            '\n' => js.push_str("\\n"),
+            '\t' => js.push_str("\\t"),
+            '\r' => js.push_str("\\r"),
+            c if c.is_control() => {
+                let _ = write!(js, "\\u{{{:x}}}", c as u32);
+            }
+            c => js.push(c),
+        }
+    }
+    js.push('"');
+    js
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_what_tree_sitter_rejects() {
+        let source = "prog := { stmt }.\nstmt := \"if\" id [ \"else\" ] | id \":=\" num | attr.\n\
+                      attr := /(?i)x/ | /[^$a]/ | /\\^/ | /[[:alpha:]]/ | /a*/.\n\
+                      id := /[a-z]+/.\nnum := /[0-9]+/.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let export = tree_sitter_grammar(&sebnf, "demo");
+        assert!(export.js.contains("  word: $ => $.id,\n"));
+        assert!(
+            export
+                .js
+                .contains("    stmt: $ => choice(\n      seq(\"if\", $.id, optional(\"else\")),\n")
+        );
+        let notes: Vec<String> = export.notes.iter().map(MappingNote::to_string).collect();
+        assert_eq!(
+            notes,
+            [
+                "'stmt': matches the empty string, which tree-sitter only allows for the start \
+                 rule; make its uses optional instead",
+                "'attr': matches the empty string, which tree-sitter only allows for the start \
+                 rule; make its uses optional instead",
+                "'attr': /(?i)x/ uses inline flags or named groups, which tree-sitter regexes lack",
+                "'attr': /[[:alpha:]]/ uses POSIX character classes, which tree-sitter regexes lack",
+                "'attr': /a*/ matches the empty string, which tree-sitter rejects",
+            ]
+        );
+    }
+}
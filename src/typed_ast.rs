@@ -0,0 +1,701 @@
+//! Typed syntax trees for the parsers of [`codegen`](crate::codegen).
+//!
+//! [`typed_ast`] writes a Rust type per rule mirroring its definition: a
+//! struct for a rule with one alternative and an enum with a variant per
+//! alternative otherwise. A non-terminal or regex in an alternative is a
+//! field, `[ ]` an `Option`, `{ }`, `non_empty` and `sep_by` a `Vec`, and a
+//! choice among tokens a `Token`; any other choice inside an alternative
+//! gets an enum of its own. Terminals are left out, since the type already
+//! tells which were there, unless labeled. A label names its field, and on
+//! the first item of an alternative its variant; other fields are named
+//! after their rule or token, other variants after their first item.
+//!
+//! Each type's `from_node` converts a `Node` of the tree the generated
+//! parser builds, reading its children with the first alternative that
+//! matches them all, and `parse` parses straight into the start rule's type.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::typed_ast::typed_ast;
+//!
+//! let sebnf = Sebnf::parse_str("sum := num { op:(\"+\" | \"-\") num }.\nnum := /[0-9]+/.", "g").unwrap();
+//! let module = typed_ast(&sebnf);
+//! assert!(module.contains("pub struct Sum {\n    pub num: Num,\n    pub op_nums: Vec<(Token, Num)>,\n}\n"));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use indexmap::IndexMap;
+
+use crate::codegen::{Names, camel, doc_line, snake, unique};
+use crate::converter::soft_keyword_regex;
+use crate::sebnf::{self, Builtin, Item, Sebnf};
+
+/// The typed syntax tree of `sebnf` as a module for the crate of
+/// `codegen crate`, next to its `ast`, `lexer` and `parser`.
+pub fn typed_ast(sebnf: &Sebnf) -> String {
+    typed_rs(sebnf, &Names::new(sebnf, &sebnf.to_bnf()))
+}
+
+pub(crate) fn typed_rs(sebnf: &Sebnf, names: &Names) -> String {
+    let mut generator = Generator::new(sebnf, names);
+    let start = generator.types.values().next().cloned().unwrap_or_default();
+    let mut code = format!(
+        r#"//! Typed syntax trees, generated by `sebnf_tool codegen typed-ast`.
+//!
+//! A type per rule mirrors its definition. `from_node` converts a [`Node`]
+//! of the tree [`crate::parser::parse`] builds, and [`parse`] parses into
+//! the type of the start rule.
+
+use crate::ast::{{Node, Rule, Tree}};
+use crate::lexer::{{Token, TokenKind}};
+use crate::parser::Error;
+
+/// Parses all of `input` as the start rule, into its type.
+pub fn parse(input: &str) -> Result<{start}, Error> {{
+    let node = crate::parser::parse(input)?;
+    Ok({start}::from_node(&node).expect("the parser builds the nodes of the grammar"))
+}}
+"#
+    );
+    for (rule, alternatives) in &sebnf.rules {
+        let written: Vec<String> = alternatives.iter().map(|a| sebnf::written(a)).collect();
+        let doc = doc_line(&format!("{} := {}", rule, written.join(" | ")));
+        let ty = generator.types[rule].clone();
+        let variant = &names.rules[rule].0;
+        let from_node = format!(
+            "    /// Converts a node of [`Rule::{variant}`], or `None` for a node of\n    \
+             /// another rule\n    pub fn from_node(node: &Node) -> Option<Self> {{\n        \
+             if node.rule != Rule::{variant} {{\n            return None;\n        }}\n"
+        );
+        let mut type_code = match alternatives.as_slice() {
+            [alternative] => {
+                let (fields, statements) = generator.alternative(rule, alternative);
+                let closure = closure(&statements, &construct("Self", &fields));
+                let mut code = format!("\n/// `{}`\n{}\n", doc, DERIVES);
+                match fields.is_empty() {
+                    true => {
+                        let _ = writeln!(code, "pub struct {};", ty);
+                    }
+                    false => {
+                        let _ = writeln!(code, "pub struct {} {{", ty);
+                        for (name, field) in &fields {
+                            let _ = writeln!(code, "    pub {}: {},", name, field);
+                        }
+                        let _ = writeln!(code, "}}");
+                    }
+                }
+                let _ = write!(
+                    code,
+                    "\nimpl {} {{\n{}        Cursor::new(&node.children).complete({})\n    }}\n}}\n",
+                    ty,
+                    from_node,
+                    indent(&closure, 8)
+                );
+                code
+            }
+            _ => {
+                let read = "(alternatives.iter())\n            \
+                            .find_map(|read| Cursor::new(&node.children).complete(read))";
+                generator.enum_type(&ty, rule, &doc, alternatives, &from_node, read)
+            }
+        };
+        for helper in generator.helpers.drain(..) {
+            type_code.push_str(&helper);
+        }
+        code.push_str(&type_code);
+    }
+    code.push_str(CURSOR_RS);
+    code
+}
+
+const DERIVES: &str = "#[derive(Debug, Clone, PartialEq, Eq)]";
+
+/// Reads the children of a node for `from_node`
+const CURSOR_RS: &str = r#"
+/// The children of a node, read from left to right
+struct Cursor<'a> {
+    children: &'a [Tree],
+    position: usize,
+}
+
+// Not every grammar reads every kind of item
+#[allow(dead_code)]
+impl<'a> Cursor<'a> {
+    fn new(children: &'a [Tree]) -> Self {
+        Cursor {
+            children,
+            position: 0,
+        }
+    }
+
+    /// `read`, if it reads all children
+    fn complete<T>(mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let value = read(&mut self)?;
+        (self.position == self.children.len()).then_some(value)
+    }
+
+    /// The next child, if it is a token of one of `kinds`
+    fn token(&mut self, kinds: &[TokenKind]) -> Option<Token> {
+        match self.children.get(self.position)? {
+            Tree::Token(token) if kinds.contains(&token.kind) => {
+                self.position += 1;
+                Some(token.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// The next child, if it is a node of `rule`
+    fn node(&mut self, rule: Rule) -> Option<&'a Node> {
+        match self.children.get(self.position)? {
+            Tree::Node(node) if node.rule == rule => {
+                self.position += 1;
+                Some(node)
+            }
+            _ => None,
+        }
+    }
+
+    /// `read`, or `None` with nothing read
+    fn optional<T>(&mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let position = self.position;
+        let value = read(self);
+        if value.is_none() {
+            self.position = position;
+        }
+        value
+    }
+
+    /// `read` as often as it reads something
+    fn many<T>(&mut self, mut read: impl FnMut(&mut Self) -> Option<T>) -> Vec<T> {
+        let mut values = Vec::new();
+        loop {
+            let position = self.position;
+            match self.optional(&mut read) {
+                Some(value) if self.position > position => values.push(value),
+                _ => {
+                    self.position = position;
+                    return values;
+                }
+            }
+        }
+    }
+
+    /// `item`s separated by `separator`s
+    fn sep_by<T, S>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Option<T>,
+        mut separator: impl FnMut(&mut Self) -> Option<S>,
+    ) -> Vec<T> {
+        let mut values: Vec<T> = self.optional(&mut item).into_iter().collect();
+        if !values.is_empty() {
+            values.extend(self.many(|cursor| {
+                separator(cursor)?;
+                item(cursor)
+            }));
+        }
+        values
+    }
+}
+"#;
+
+/// What an item adds to an alternative
+struct Part {
+    /// The field's name, unless a label gives one
+    name: String,
+    /// `None` for a terminal, which is read but not kept
+    ty: Option<String>,
+    /// An expression reading the part from `cursor`, failing with `?`
+    read: String,
+}
+
+struct Generator<'a> {
+    names: &'a Names,
+    /// Rules to their type
+    types: IndexMap<String, String>,
+    /// Rules to the rules whose types theirs holds by value, directly or not
+    reachable: HashMap<String, HashSet<String>>,
+    /// Names of the types so far
+    taken: HashSet<String>,
+    /// Enums of the choices lifted out of the current rule
+    helpers: Vec<String>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(sebnf: &Sebnf, names: &'a Names) -> Self {
+        let mut taken: HashSet<String> = [
+            "Node",
+            "Rule",
+            "Tree",
+            "Token",
+            "TokenKind",
+            "Error",
+            "Cursor",
+        ]
+        .map(str::to_string)
+        .into();
+        let types = (sebnf.rules.keys())
+            .map(|rule| (rule.clone(), unique(camel(rule), &mut taken)))
+            .collect();
+        let held: HashMap<&String, Vec<String>> = (sebnf.rules.iter())
+            .map(|(rule, alternatives)| {
+                let mut rules = Vec::new();
+                alternatives.iter().for_each(|a| held(a, &mut rules));
+                (rule, rules)
+            })
+            .collect();
+        let mut reachable = HashMap::new();
+        for rule in sebnf.rules.keys() {
+            let mut seen = HashSet::new();
+            let mut stack = held[rule].clone();
+            while let Some(next) = stack.pop() {
+                if seen.insert(next.clone()) {
+                    stack.extend(held.get(&next).into_iter().flatten().cloned());
+                }
+            }
+            reachable.insert(rule.clone(), seen);
+        }
+        Generator {
+            names,
+            types,
+            reachable,
+            taken,
+            helpers: Vec::new(),
+        }
+    }
+
+    /// An enum with a variant per alternative, whose `read` tries the
+    /// alternatives that must read a child first, ending in `find`
+    fn enum_type(
+        &mut self,
+        ty: &str,
+        owner: &str,
+        doc: &str,
+        alternatives: &[Vec<Item>],
+        signature: &str,
+        find: &str,
+    ) -> String {
+        let mut code = format!("\n/// `{}`\n{}\npub enum {} {{\n", doc, DERIVES, ty);
+        let mut taken = HashSet::new();
+        let mut reads = Vec::new();
+        for alternative in alternatives {
+            let variant = unique(self.variant_name(alternative), &mut taken);
+            let (fields, statements) = self.alternative(owner, alternative);
+            let _ = writeln!(code, "    /// `{}`", doc_line(&sebnf::written(alternative)));
+            match fields.is_empty() {
+                true => {
+                    let _ = writeln!(code, "    {},", variant);
+                }
+                false => {
+                    let _ = writeln!(code, "    {} {{", variant);
+                    for (name, field) in &fields {
+                        let _ = writeln!(code, "        {}: {},", name, field);
+                    }
+                    let _ = writeln!(code, "    }},");
+                }
+            }
+            let constructor = construct(&format!("Self::{}", variant), &fields);
+            reads.push((skippable(alternative), closure(&statements, &constructor)));
+        }
+        let _ = writeln!(code, "}}");
+        reads.sort_by_key(|(skippable, _)| *skippable);
+
+        let _ = write!(code, "\nimpl {} {{\n{}", ty, signature);
+        let _ = writeln!(
+            code,
+            "        let alternatives: [fn(&mut Cursor) -> Option<Self>; {}] = [",
+            reads.len()
+        );
+        for (_, read) in reads {
+            let _ = writeln!(code, "            {},", indent(&read, 12));
+        }
+        let _ = write!(code, "        ];\n        {}\n    }}\n}}\n", find);
+        code
+    }
+
+    /// The fields of an alternative of `owner` and the statements reading it
+    fn alternative(&mut self, owner: &str, items: &[Item]) -> (Vec<(String, String)>, String) {
+        let parts = self.parts(owner, items, false);
+        bind(&parts)
+    }
+
+    fn variant_name(&self, items: &[Item]) -> String {
+        match items.first() {
+            Some(Item::Labeled(label, _, _)) => camel(label),
+            Some(Item::NonTerminal(rule, _)) => {
+                self.types.get(rule).cloned().unwrap_or_else(|| camel(rule))
+            }
+            Some(Item::Terminal(symbol, _) | Item::Regex(symbol, _)) => {
+                self.token_kind(symbol).to_string()
+            }
+            Some(Item::Epsilon(_)) | None => "Empty".to_string(),
+            Some(_) => "Alternative".to_string(),
+        }
+    }
+
+    /// The `TokenKind` variant of a terminal or regex. The BNF the tokens
+    /// are named from spells soft keywords as regexes.
+    fn token_kind(&self, symbol: &str) -> &'a str {
+        (self.names.tokens.get(symbol))
+            .or_else(|| self.names.tokens.get(&soft_keyword_regex(symbol)))
+            .expect("every token of the grammar is named")
+    }
+
+    /// `in_vec` tells whether the parts end up in a `Vec`, which needs no
+    /// `Box` for a recursive type
+    fn parts(&mut self, owner: &str, items: &[Item], in_vec: bool) -> Vec<Part> {
+        let mut parts = Vec::new();
+        for item in items {
+            self.item(owner, item, in_vec, &mut parts);
+        }
+        parts
+    }
+
+    fn item(&mut self, owner: &str, item: &Item, in_vec: bool, parts: &mut Vec<Part>) {
+        match item {
+            Item::NonTerminal(rule, _) => {
+                let Some(ty) = self.types.get(rule).cloned() else {
+                    return;
+                };
+                let (variant, name) = &self.names.rules[rule];
+                let read = format!("{}::from_node(cursor.node(Rule::{})?)?", ty, variant);
+                let boxed = !in_vec && self.reachable[rule].contains(owner);
+                parts.push(Part {
+                    name: name.clone(),
+                    ty: Some(match boxed {
+                        true => format!("Box<{}>", ty),
+                        false => ty,
+                    }),
+                    read: match boxed {
+                        true => format!("Box::new({})", read),
+                        false => read,
+                    },
+                });
+            }
+            Item::Terminal(symbol, _) | Item::Regex(symbol, _) => {
+                let kind = self.token_kind(symbol);
+                parts.push(Part {
+                    name: snake(kind),
+                    ty: matches!(item, Item::Regex(..)).then(|| "Token".to_string()),
+                    read: format!("cursor.token(&[TokenKind::{}])?", kind),
+                });
+            }
+            Item::Optional(items, _) => {
+                let inner = self.parts(owner, items, in_vec);
+                let (name, ty, closure) = value(&inner);
+                let read = format!("cursor.optional({})", closure);
+                parts.push(match ty {
+                    Some(ty) => Part {
+                        name,
+                        ty: Some(format!("Option<{}>", ty)),
+                        read,
+                    },
+                    None => Part {
+                        name,
+                        ty: Some("bool".to_string()),
+                        read: format!("{}.is_some()", read),
+                    },
+                });
+            }
+            Item::AnyAmount(items, _) => self.many(owner, items, parts),
+            Item::Builtin(builtin, arguments, _) => match (builtin, arguments.as_slice()) {
+                (Builtin::NonEmpty, [items]) => self.many(owner, items, parts),
+                (Builtin::SepBy, [items, separator]) => {
+                    let item = self.parts(owner, items, true);
+                    let separator = self.parts(owner, separator, true);
+                    let (name, ty, item) = value(&item);
+                    let separator = value(&separator).2;
+                    let read = match item.contains('\n') || separator.contains('\n') {
+                        true => format!(
+                            "cursor.sep_by(\n    {},\n    {},\n)",
+                            indent(&item, 4),
+                            indent(&separator, 4)
+                        ),
+                        false => format!("cursor.sep_by({}, {})", item, separator),
+                    };
+                    parts.push(counted(name, ty, read));
+                }
+                (Builtin::Delimited, [left, items, right]) => {
+                    for item in [left, items, right].into_iter().flatten() {
+                        self.item(owner, item, in_vec, parts);
+                    }
+                }
+                _ => unreachable!("built-in calls are checked when parsing"),
+            },
+            Item::Choice(alternatives, _) => {
+                let tokens: Option<Vec<String>> = (alternatives.iter())
+                    .map(|alternative| match alternative.as_slice() {
+                        [Item::Terminal(symbol, _) | Item::Regex(symbol, _)] => {
+                            Some(format!("TokenKind::{}", self.token_kind(symbol)))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                match (alternatives.as_slice(), tokens) {
+                    ([items], _) => {
+                        for item in items {
+                            self.item(owner, item, in_vec, parts);
+                        }
+                    }
+                    (_, Some(tokens)) => parts.push(Part {
+                        name: "token".to_string(),
+                        ty: Some("Token".to_string()),
+                        read: format!("cursor.token(&[{}])?", tokens.join(", ")),
+                    }),
+                    (_, None) => {
+                        let ty = unique(format!("{}Choice", self.types[owner]), &mut self.taken);
+                        let written: Vec<String> =
+                            alternatives.iter().map(|a| sebnf::written(a)).collect();
+                        let doc = doc_line(&format!("( {} )", written.join(" | ")));
+                        let helper = self.enum_type(
+                            &ty,
+                            owner,
+                            &format!("{}` in `{}", doc, owner),
+                            alternatives,
+                            "    fn read(cursor: &mut Cursor) -> Option<Self> {\n",
+                            "alternatives.iter().find_map(|read| cursor.optional(read))",
+                        );
+                        self.helpers.push(helper);
+                        parts.push(Part {
+                            name: snake(&ty),
+                            ty: Some(ty.clone()),
+                            read: format!("{}::read(cursor)?", ty),
+                        });
+                    }
+                }
+            }
+            Item::Labeled(label, item, _) => {
+                let mut inner = Vec::new();
+                self.item(owner, item, in_vec, &mut inner);
+                let part = match inner.len() {
+                    0 => return,
+                    1 => {
+                        let part = inner.pop().expect("one part");
+                        Part {
+                            // A labeled terminal is kept
+                            ty: part.ty.or_else(|| Some("Token".to_string())),
+                            ..part
+                        }
+                    }
+                    _ => {
+                        let (name, ty, closure) = value(&inner);
+                        Part {
+                            name,
+                            ty,
+                            read: format!("cursor.optional({})?", closure),
+                        }
+                    }
+                };
+                parts.push(Part {
+                    name: snake(label),
+                    ..part
+                });
+            }
+            Item::Epsilon(_) => {}
+        }
+    }
+
+    fn many(&mut self, owner: &str, items: &[Item], parts: &mut Vec<Part>) {
+        let inner = self.parts(owner, items, true);
+        let (name, ty, closure) = value(&inner);
+        parts.push(counted(name, ty, format!("cursor.many({})", closure)));
+    }
+}
+
+/// A `Vec` read by `read`, or its length if it holds nothing
+fn counted(name: String, ty: Option<String>, read: String) -> Part {
+    let name = match name.ends_with('s') {
+        true => name,
+        false => format!("{}s", name),
+    };
+    match ty {
+        Some(ty) => Part {
+            name,
+            ty: Some(format!("Vec<{}>", ty)),
+            read,
+        },
+        None => Part {
+            name,
+            ty: Some("usize".to_string()),
+            read: format!("{}.len()", read),
+        },
+    }
+}
+
+/// The name, type and closure reading `parts` as one value: nothing, the
+/// one kept part or a tuple of them
+fn value(parts: &[Part]) -> (String, Option<String>, String) {
+    let kept: Vec<&Part> = parts.iter().filter(|part| part.ty.is_some()).collect();
+    let name = match kept.as_slice() {
+        [] => parts
+            .first()
+            .map_or("empty".to_string(), |part| part.name.clone()),
+        _ => (kept.iter().map(|part| part.name.as_str()))
+            .collect::<Vec<_>>()
+            .join("_"),
+    };
+    let types: Vec<&str> = (kept.iter())
+        .filter_map(|part| part.ty.as_deref())
+        .collect();
+    let ty = match types.as_slice() {
+        [] => None,
+        [ty] => Some(ty.to_string()),
+        _ => Some(format!("({})", types.join(", "))),
+    };
+    if let [part] = parts
+        && part.ty.is_some()
+        && !part.read.contains('\n')
+    {
+        let closure = match part.read.strip_suffix('?') {
+            Some(read) => format!("|cursor| {}", read),
+            None => format!("|cursor| Some({})", part.read),
+        };
+        return (name, ty, closure);
+    }
+    let (fields, statements) = bind(parts);
+    let value = match fields.as_slice() {
+        [] => "()".to_string(),
+        [(name, _)] => name.clone(),
+        _ => {
+            let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+            format!("({})", names.join(", "))
+        }
+    };
+    (name, ty, closure(&statements, &value))
+}
+
+/// The fields kept of `parts`, named apart, and the statements reading
+/// all of them into variables of the same names
+fn bind(parts: &[Part]) -> (Vec<(String, String)>, String) {
+    let mut taken = HashSet::from(["cursor".to_string()]);
+    let mut fields = Vec::new();
+    let mut statements = String::new();
+    for part in parts {
+        match &part.ty {
+            Some(ty) => {
+                let name = unique(identifier(&part.name), &mut taken);
+                let _ = writeln!(statements, "let {} = {};", name, part.read);
+                fields.push((name, ty.clone()));
+            }
+            None => {
+                let _ = writeln!(statements, "{};", part.read);
+            }
+        }
+    }
+    (fields, statements)
+}
+
+/// `path { a, b }`, or `path` without fields
+fn construct(path: &str, fields: &[(String, String)]) -> String {
+    match fields.is_empty() {
+        true => path.to_string(),
+        false => {
+            let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+            format!("{} {{ {} }}", path, names.join(", "))
+        }
+    }
+}
+
+/// A closure running `statements` and returning `Some(value)`
+fn closure(statements: &str, value: &str) -> String {
+    match statements.is_empty() {
+        true => format!("|_| Some({})", value),
+        false => {
+            let body = format!("{}Some({})", statements, value);
+            format!("|cursor| {{\n    {}\n}}", indent(&body, 4))
+        }
+    }
+}
+
+/// `code` with its lines after the first indented by `width`
+fn indent(code: &str, width: usize) -> String {
+    code.replace('\n', &format!("\n{}", " ".repeat(width)))
+}
+
+/// `name` in snake case, clear of keywords, for a field or function
+pub(crate) fn identifier(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+        "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop",
+        "macro", "match", "mod", "move", "mut", "priv", "pub", "ref", "return", "self", "static",
+        "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe", "use", "virtual",
+        "where", "while", "yield",
+    ];
+    let name = snake(name);
+    match KEYWORDS.contains(&name.as_str()) {
+        true => format!("{}_", name),
+        false => name,
+    }
+}
+
+/// Whether `items` can match no child of a node: a non-terminal is a node
+/// and a token a child even where they match nothing
+fn skippable(items: &[Item]) -> bool {
+    items.iter().all(|item| match item {
+        Item::NonTerminal(..) | Item::Terminal(..) | Item::Regex(..) => false,
+        Item::Optional(..) | Item::AnyAmount(..) | Item::Epsilon(_) => true,
+        Item::Choice(alternatives, _) => alternatives.iter().any(|a| skippable(a)),
+        Item::Builtin(Builtin::SepBy, _, _) => true,
+        Item::Builtin(_, arguments, _) => arguments.iter().all(|a| skippable(a)),
+        Item::Labeled(_, item, _) => skippable(std::slice::from_ref(item)),
+    })
+}
+
+/// Adds the rules `items` refer to outside of a repetition, whose types
+/// the type of `items` holds by value
+fn held(items: &[Item], rules: &mut Vec<String>) {
+    for item in items {
+        match item {
+            Item::NonTerminal(rule, _) => rules.push(rule.clone()),
+            Item::Optional(items, _) => held(items, rules),
+            Item::Choice(alternatives, _) | Item::Builtin(Builtin::Delimited, alternatives, _) => {
+                alternatives.iter().for_each(|a| held(a, rules))
+            }
+            Item::Labeled(_, item, _) => held(std::slice::from_ref(item), rules),
+            Item::AnyAmount(..)
+            | Item::Builtin(..)
+            | Item::Terminal(..)
+            | Item::Regex(..)
+            | Item::Epsilon(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxes_only_what_holds_itself() {
+        let source = "block := \"{\" { stmt } \"}\".\nstmt := block | \"if\" cond:expr block [ \"else\" block ].\n\
+                      expr := atom [ \"?\" expr ].\natom := /[a-z]+/ | ( \"(\" expr \")\" | \"-\" atom ).";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let module = typed_ast(&sebnf);
+        assert!(module.contains("pub struct Block {\n    pub stmts: Vec<Stmt>,\n}\n"));
+        assert!(module.contains(
+            "pub enum Stmt {\n    /// `block`\n    Block {\n        block: Block,\n    },\n    \
+             /// `\"if\" cond:expr block [ \"else\" block ]`\n    If {\n        cond: Expr,\n        \
+             block: Block,\n        block2: Option<Block>,\n    },\n}\n"
+        ));
+        assert!(module.contains("    pub atom: Box<Atom>,\n    pub expr: Option<Box<Expr>>,\n"));
+        assert!(module.contains(
+            "pub enum AtomChoice {\n    /// `\"(\" expr \")\"`\n    LParen {\n        expr: Box<Expr>,\n    },\n    \
+             /// `\"-\" atom`\n    Minus {\n        atom: Box<Atom>,\n    },\n}\n"
+        ));
+        assert!(module.contains(
+            "let stmts = cursor.many(|cursor| Stmt::from_node(cursor.node(Rule::Stmt)?));\n"
+        ));
+    }
+
+    #[test]
+    fn reads_soft_keywords_as_their_regexes() {
+        let source = "@soft_keywords(\"async\")\nfunc := [ \"async\" ] \"fn\" name | \"async\" name.\n\
+                      name := /[a-z]+/.";
+        let sebnf = Sebnf::parse_str(source, "g").unwrap();
+        let module = typed_ast(&sebnf);
+        assert!(module.contains("    /// `\"async\" name`\n    Async {\n"));
+        assert!(module.contains("cursor.token(&[TokenKind::Async])?"));
+    }
+}
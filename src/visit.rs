@@ -0,0 +1,198 @@
+//! Traversal of the SEBNF AST.
+//!
+//! Implement [`Visitor`] and override the methods for the nodes of interest.
+//! Every default method calls the matching `walk_*` function, which visits the
+//! children; an override that should keep descending calls it itself.
+//! [`VisitorMut`] works the same way for rewriting a grammar in place.
+//!
+//! ```
+//! use std::ops::Range;
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::visit::{Visitor, walk_sebnf};
+//!
+//! struct Terminals(Vec<String>);
+//!
+//! impl Visitor for Terminals {
+//!     fn visit_terminal(&mut self, terminal: &str, _span: &Range<usize>) {
+//!         self.0.push(terminal.to_string());
+//!     }
+//! }
+//!
+//! let sebnf = Sebnf::parse_str("a := \"x\" { ( \"y\" | b ) }.\nb := [ \"z\" ].", "g").unwrap();
+//! let mut terminals = Terminals(Vec::new());
+//! walk_sebnf(&mut terminals, &sebnf);
+//! assert_eq!(terminals.0, ["\"x\"", "\"y\"", "\"z\""]);
+//! ```
+
+use std::ops::Range;
+
+use crate::sebnf::{Item, Sebnf};
+
+pub trait Visitor {
+    fn visit_rule(&mut self, name: &str, alternatives: &[Vec<Item>]) {
+        walk_rule(self, name, alternatives);
+    }
+
+    /// The alternatives of a rule or of a `( ... | ... )` choice
+    fn visit_alternatives(&mut self, alternatives: &[Vec<Item>]) {
+        walk_alternatives(self, alternatives);
+    }
+
+    /// An alternative, the body of `[ ]` or `{ }`, or a built-in's argument
+    fn visit_sequence(&mut self, items: &[Item]) {
+        walk_sequence(self, items);
+    }
+
+    fn visit_item(&mut self, item: &Item) {
+        walk_item(self, item);
+    }
+
+    fn visit_non_terminal(&mut self, _name: &str, _span: &Range<usize>) {}
+
+    fn visit_terminal(&mut self, _terminal: &str, _span: &Range<usize>) {}
+
+    fn visit_regex(&mut self, _regex: &str, _span: &Range<usize>) {}
+}
+
+pub fn walk_sebnf<V: Visitor + ?Sized>(visitor: &mut V, sebnf: &Sebnf) {
+    for (name, alternatives) in &sebnf.rules {
+        visitor.visit_rule(name, alternatives);
+    }
+}
+
+pub fn walk_rule<V: Visitor + ?Sized>(visitor: &mut V, _name: &str, alternatives: &[Vec<Item>]) {
+    visitor.visit_alternatives(alternatives);
+}
+
+pub fn walk_alternatives<V: Visitor + ?Sized>(visitor: &mut V, alternatives: &[Vec<Item>]) {
+    for items in alternatives {
+        visitor.visit_sequence(items);
+    }
+}
+
+pub fn walk_sequence<V: Visitor + ?Sized>(visitor: &mut V, items: &[Item]) {
+    for item in items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<V: Visitor + ?Sized>(visitor: &mut V, item: &Item) {
+    match item {
+        Item::NonTerminal(name, span) => visitor.visit_non_terminal(name, span),
+        Item::Terminal(terminal, span) => visitor.visit_terminal(terminal, span),
+        Item::Regex(regex, span) => visitor.visit_regex(regex, span),
+        Item::Optional(inner, _) | Item::AnyAmount(inner, _) => visitor.visit_sequence(inner),
+        Item::Choice(alternatives, _) => visitor.visit_alternatives(alternatives),
+        Item::Builtin(_, args, _) => {
+            for arg in args {
+                visitor.visit_sequence(arg);
+            }
+        }
+        Item::Labeled(_, inner, _) => visitor.visit_item(inner),
+        Item::Epsilon(_) => {}
+    }
+}
+
+/// Like [`Visitor`], with mutable access so items can be rewritten, inserted
+/// or removed.
+pub trait VisitorMut {
+    fn visit_rule_mut(&mut self, name: &str, alternatives: &mut Vec<Vec<Item>>) {
+        walk_rule_mut(self, name, alternatives);
+    }
+
+    fn visit_alternatives_mut(&mut self, alternatives: &mut Vec<Vec<Item>>) {
+        walk_alternatives_mut(self, alternatives);
+    }
+
+    fn visit_sequence_mut(&mut self, items: &mut Vec<Item>) {
+        walk_sequence_mut(self, items);
+    }
+
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item_mut(self, item);
+    }
+}
+
+/// Visits every rule; renaming rules is up to the caller, since the rule
+/// names are the keys of `sebnf.rules`.
+pub fn walk_sebnf_mut<V: VisitorMut + ?Sized>(visitor: &mut V, sebnf: &mut Sebnf) {
+    for (name, alternatives) in &mut sebnf.rules {
+        visitor.visit_rule_mut(name, alternatives);
+    }
+}
+
+pub fn walk_rule_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _name: &str,
+    alternatives: &mut Vec<Vec<Item>>,
+) {
+    visitor.visit_alternatives_mut(alternatives);
+}
+
+pub fn walk_alternatives_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    alternatives: &mut Vec<Vec<Item>>,
+) {
+    for items in alternatives {
+        visitor.visit_sequence_mut(items);
+    }
+}
+
+pub fn walk_sequence_mut<V: VisitorMut + ?Sized>(visitor: &mut V, items: &mut Vec<Item>) {
+    for item in items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+/// Visits the children of `item`; leaves (non-terminals, terminals, regexes
+/// and `ε`) have none.
+pub fn walk_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut Item) {
+    match item {
+        Item::NonTerminal(..) | Item::Terminal(..) | Item::Regex(..) | Item::Epsilon(..) => {}
+        Item::Optional(inner, _) | Item::AnyAmount(inner, _) => visitor.visit_sequence_mut(inner),
+        Item::Choice(alternatives, _) => visitor.visit_alternatives_mut(alternatives),
+        Item::Builtin(_, args, _) => {
+            for arg in args {
+                visitor.visit_sequence_mut(arg);
+            }
+        }
+        Item::Labeled(_, inner, _) => visitor.visit_item_mut(inner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_nested_items() {
+        struct Rename;
+        impl VisitorMut for Rename {
+            fn visit_item_mut(&mut self, item: &mut Item) {
+                if let Item::NonTerminal(name, _) = item
+                    && name == "b"
+                {
+                    *name = "c".to_string();
+                }
+                walk_item_mut(self, item);
+            }
+        }
+
+        let mut sebnf = Sebnf::parse_str(
+            "a := [ b ] { ( b | \"x\" ) } sep_by(b, \",\").\nb := \"y\".",
+            "g",
+        )
+        .unwrap();
+        walk_sebnf_mut(&mut Rename, &mut sebnf);
+
+        struct NonTerminals(Vec<String>);
+        impl Visitor for NonTerminals {
+            fn visit_non_terminal(&mut self, name: &str, _span: &Range<usize>) {
+                self.0.push(name.to_string());
+            }
+        }
+        let mut found = NonTerminals(Vec::new());
+        walk_sebnf(&mut found, &sebnf);
+        assert_eq!(found.0, ["c", "c", "c"]);
+    }
+}
@@ -0,0 +1,227 @@
+//! The tokens a grammar is written with.
+//!
+//! [`vocabulary`] lists every distinct terminal and regex with how often and
+//! where it is used. Sorted by spelling, near-duplicates such as `"="` and
+//! `"=="`, or `":="` next to `"="`, end up next to each other, where an
+//! inconsistent choice of operators is easy to spot. Terminals that only
+//! differ in case or surrounding whitespace, like `"if"` and `"If "`, do
+//! not sort together, so [`near_duplicates`] groups those.
+//!
+//! ```
+//! use sebnf_tool::Sebnf;
+//! use sebnf_tool::vocabulary::vocabulary;
+//!
+//! let sebnf = Sebnf::parse_str("s := \"let\" id \"=\" id { \",\" id }.\nid := /[a-z]+/.", "g").unwrap();
+//! let tokens = vocabulary(&sebnf);
+//! let spelled: Vec<_> = tokens.iter().map(|t| t.symbol.as_str()).collect();
+//! assert_eq!(spelled, ["\",\"", "\"=\"", "/[a-z]+/", "\"let\""]);
+//! assert!(tokens[0].only_in_helpers);
+//! ```
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::sebnf::{Item, Sebnf};
+use crate::sets::{strip_regex_delimiters, strip_terminal_quotes};
+use crate::visit::{Visitor, walk_item, walk_rule, walk_sebnf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TokenKind {
+    Terminal,
+    Regex,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Terminal => write!(f, "terminal"),
+            TokenKind::Regex => write!(f, "regex"),
+        }
+    }
+}
+
+/// A distinct terminal or regex and its uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// As written, with its quotes or slashes
+    pub symbol: String,
+    pub kind: TokenKind,
+    pub uses: usize,
+    /// Rules using it, in grammar order
+    pub rules: Vec<String>,
+    /// Whether every use is inside `[ ]`, `{ }`, `( | )` or a built-in call,
+    /// which the conversion to BNF turns into generated helper rules
+    pub only_in_helpers: bool,
+}
+
+impl Token {
+    /// The spelling without quotes or slashes, which the list is sorted by
+    pub fn spelling(&self) -> &str {
+        match self.kind {
+            TokenKind::Terminal => strip_terminal_quotes(&self.symbol),
+            TokenKind::Regex => strip_regex_delimiters(&self.symbol),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules: Vec<_> = self.rules.iter().map(|r| format!("'{}'", r)).collect();
+        write!(
+            f,
+            "{} {} use{} in {}",
+            self.symbol,
+            self.uses,
+            if self.uses == 1 { "" } else { "s" },
+            rules.join(", ")
+        )?;
+        if self.only_in_helpers {
+            write!(f, ", only in helpers")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every distinct terminal and regex of the grammar, sorted by spelling;
+/// a terminal comes before a regex spelled the same.
+pub fn vocabulary(sebnf: &Sebnf) -> Vec<Token> {
+    let mut collector = Collector {
+        rule: String::new(),
+        depth: 0,
+        tokens: Vec::new(),
+    };
+    walk_sebnf(&mut collector, sebnf);
+    let mut tokens = collector.tokens;
+    tokens.sort_by(|a, b| (a.spelling(), a.kind).cmp(&(b.spelling(), b.kind)));
+    tokens
+}
+
+/// Groups of terminals in `tokens` spelled the same when case and
+/// surrounding whitespace are ignored, in the order of the list.
+pub fn near_duplicates(tokens: &[Token]) -> Vec<Vec<&Token>> {
+    let key = |token: &Token| token.spelling().trim().to_lowercase();
+    let mut groups: Vec<Vec<&Token>> = Vec::new();
+    for token in tokens.iter().filter(|t| t.kind == TokenKind::Terminal) {
+        match groups.iter_mut().find(|group| key(group[0]) == key(token)) {
+            Some(group) => group.push(token),
+            None => groups.push(vec![token]),
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// The list as CSV, with a header and the rules separated by spaces.
+pub fn to_csv(tokens: &[Token]) -> String {
+    let field = |value: &str| match value.contains(['"', ',', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    };
+    let mut csv = String::from("symbol,kind,uses,rules,only_in_helpers\n");
+    for token in tokens {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            field(&token.symbol),
+            token.kind,
+            token.uses,
+            field(&token.rules.join(" ")),
+            token.only_in_helpers
+        ));
+    }
+    csv
+}
+
+struct Collector {
+    rule: String,
+    /// How many `[ ]`, `{ }`, groups and built-in calls the item is inside
+    depth: usize,
+    tokens: Vec<Token>,
+}
+
+impl Collector {
+    fn add(&mut self, symbol: &str, kind: TokenKind) {
+        let nested = self.depth > 0;
+        let index = match self.tokens.iter().position(|t| t.symbol == symbol) {
+            Some(index) => index,
+            None => {
+                self.tokens.push(Token {
+                    symbol: symbol.to_string(),
+                    kind,
+                    uses: 0,
+                    rules: Vec::new(),
+                    only_in_helpers: true,
+                });
+                self.tokens.len() - 1
+            }
+        };
+        let token = &mut self.tokens[index];
+        token.uses += 1;
+        token.only_in_helpers &= nested;
+        if !token.rules.contains(&self.rule) {
+            token.rules.push(self.rule.clone());
+        }
+    }
+}
+
+impl Visitor for Collector {
+    fn visit_rule(&mut self, name: &str, alternatives: &[Vec<Item>]) {
+        self.rule = name.to_string();
+        walk_rule(self, name, alternatives);
+    }
+
+    fn visit_item(&mut self, item: &Item) {
+        let nests = matches!(
+            item,
+            Item::Optional(..) | Item::AnyAmount(..) | Item::Choice(..) | Item::Builtin(..)
+        );
+        self.depth += usize::from(nests);
+        walk_item(self, item);
+        self.depth -= usize::from(nests);
+    }
+
+    fn visit_terminal(&mut self, terminal: &str, _span: &Range<usize>) {
+        self.add(terminal, TokenKind::Terminal);
+    }
+
+    fn visit_regex(&mut self, regex: &str, _span: &Range<usize>) {
+        self.add(regex, TokenKind::Regex);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_uses_and_writes_csv() {
+        let sebnf = Sebnf::parse_str(
+            "s := a \":=\" a | a \"=\" [ \"=\" a ].\na := /[a-z]+/ | \"(\" s \")\" | \"If \" a | \"if\".",
+            "g",
+        )
+        .unwrap();
+        let tokens = vocabulary(&sebnf);
+        let text: Vec<_> = tokens.iter().map(|t| t.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "\"(\" 1 use in 'a'",
+                "\")\" 1 use in 'a'",
+                "\":=\" 1 use in 's'",
+                "\"=\" 2 uses in 's'",
+                "\"If \" 1 use in 'a'",
+                "/[a-z]+/ 1 use in 'a'",
+                "\"if\" 1 use in 'a'",
+            ]
+        );
+        let groups = near_duplicates(&tokens);
+        assert_eq!(groups.len(), 1);
+        let spelled: Vec<_> = groups[0].iter().map(|t| t.symbol.as_str()).collect();
+        assert_eq!(spelled, ["\"If \"", "\"if\""]);
+        assert_eq!(
+            to_csv(&[tokens[3].clone(), tokens[5].clone()]),
+            "symbol,kind,uses,rules,only_in_helpers\n\
+             \"\"\"=\"\"\",terminal,2,s,false\n\
+             /[a-z]+/,regex,1,a,false\n"
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! WebAssembly exports, so a web page can analyze grammars client-side.
+//!
+//! Every function returns the JSON of [`api::analyze`] as a string, for
+//! `JSON.parse` on the JavaScript side:
+//!
+//! ```js
+//! import init, { isLl1 } from "./pkg/sebnf_tool.js";
+//! await init();
+//! const result = JSON.parse(isLl1('a := "x" | "x".', false));
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::api::{self, Endpoint, Options};
+
+fn analyze(endpoint: Endpoint, grammar: &str, options: &Options) -> String {
+    api::analyze(endpoint, grammar, options).to_string()
+}
+
+#[wasm_bindgen]
+pub fn validate(grammar: &str) -> String {
+    analyze(Endpoint::Validate, grammar, &Options::default())
+}
+
+#[wasm_bindgen(js_name = toBnf)]
+pub fn to_bnf(grammar: &str) -> String {
+    analyze(Endpoint::ToBnf, grammar, &Options::default())
+}
+
+#[wasm_bindgen(js_name = extractSets)]
+pub fn extract_sets(grammar: &str) -> String {
+    analyze(Endpoint::Sets, grammar, &Options::default())
+}
+
+#[wasm_bindgen(js_name = isLl1)]
+pub fn is_ll1(grammar: &str, ignore_regex_conflicts: bool) -> String {
+    let options = Options {
+        ignore_regex_conflicts,
+        ..Options::default()
+    };
+    analyze(Endpoint::IsLl1, grammar, &options)
+}
@@ -0,0 +1,424 @@
+//! The command-line tool run as a user would, for behaviour only visible from
+//! outside the process: exit codes, written files and machine-readable output.
+#![cfg(feature = "cli")]
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+const LL1: &str = "list := \"[\" [ item { \",\" item } ] \"]\".\nitem := /[0-9]+/.\n";
+
+/// Runs the tool with `args`, feeding `stdin` to it
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sebnf_tool"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Commands reading a file close stdin without reading it
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+/// A fresh, empty directory for one test
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("sebnf_tool_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn output_replaces_the_file() {
+    let dir = scratch_dir("output");
+    let path = dir.join("list.bnf");
+    fs::write(&path, "stale contents\n".repeat(100)).unwrap();
+
+    let printed = run(&["to-bnf"], LL1);
+    let written = run(&["to-bnf", "--output", path.to_str().unwrap()], LL1);
+    assert!(written.status.success());
+    assert!(written.stdout.is_empty());
+    assert_eq!(fs::read(&path).unwrap(), printed.stdout);
+    // No temporary file is left next to the output
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    let missing = dir.join("missing").join("list.bnf");
+    let failed = run(&["to-bnf", "-o", missing.to_str().unwrap()], LL1);
+    assert_eq!(failed.status.code(), Some(3));
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// The single run of a SARIF log, checked against the fields the schema requires
+fn sarif_run(output: &Output) -> serde_json::Value {
+    let log: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(log["version"], "2.1.0");
+    assert!(log["$schema"].as_str().unwrap().contains("sarif-2.1.0"));
+    let run = &log["runs"][0];
+    assert_eq!(run["tool"]["driver"]["name"], "sebnf_tool");
+    // Every result refers to a rule declared by the driver
+    let rule_ids: Vec<_> = run["tool"]["driver"]["rules"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|rule| rule["id"].clone())
+        .collect();
+    for result in run["results"].as_array().unwrap() {
+        assert!(rule_ids.contains(&result["ruleId"]));
+    }
+    run.clone()
+}
+
+#[test]
+fn sarif_reports_conflicts_and_parse_errors() {
+    let grammar = "a := \"x\" \"y\" | \"x\" \"z\".\n";
+    let output = run(&["is-ll1", "--format", "sarif"], grammar);
+    assert_eq!(output.status.code(), Some(1));
+    let sarif = sarif_run(&output);
+    let result = &sarif["results"][0];
+    assert_eq!(result["level"], "error");
+    assert_eq!(result["ruleId"], "sebnf::first_first_conflict");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "<stdin>");
+    let region = &location["region"];
+    let start = region["byteOffset"].as_u64().unwrap() as usize;
+    let len = region["byteLength"].as_u64().unwrap() as usize;
+    assert_eq!(&grammar[start..start + len], "\"x\" \"z\"");
+    assert_eq!(region["startLine"], 1);
+    assert_eq!(region["startColumn"], start as u64 + 1);
+
+    let output = run(&["validate", "--format", "sarif"], "a := \"x\"\n");
+    assert_eq!(output.status.code(), Some(2));
+    let sarif = sarif_run(&output);
+    assert_eq!(sarif["results"][0]["ruleId"], "sebnf::unexpected_eof");
+
+    let output = run(&["is-ll1", "--format", "sarif"], LL1);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(sarif_run(&output)["results"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn snapshot_check_fails_after_a_change() {
+    let dir = scratch_dir("snapshot");
+    let snapshot = dir.to_str().unwrap();
+
+    let update = run(&["snapshot", snapshot, "--update"], LL1);
+    assert!(update.status.success());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+
+    let check = run(&["snapshot", snapshot, "--check"], LL1);
+    assert_eq!(check.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&check.stdout), "Snapshot matches\n");
+
+    let changed = LL1.replace("\",\"", "\";\"");
+    let check = run(&["snapshot", snapshot, "--check"], &changed);
+    assert_eq!(check.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&check.stdout);
+    assert!(stdout.contains("Changed grammar.bnf:"), "{}", stdout);
+    assert!(stdout.contains("\";\""), "{}", stdout);
+
+    // Checking never rewrites the stored artifacts
+    let check = run(&["snapshot", snapshot, "--check"], LL1);
+    assert_eq!(check.status.code(), Some(0));
+
+    fs::remove_file(dir.join("table.txt")).unwrap();
+    let check = run(&["snapshot", snapshot, "--check"], LL1);
+    assert_eq!(check.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&check.stdout),
+        "Missing table.txt\n"
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(run(&["is-ll1"], LL1).status.code(), Some(0));
+    assert_eq!(
+        run(&["is-ll1"], "a := \"x\" | \"x\" \"y\".\n")
+            .status
+            .code(),
+        Some(1)
+    );
+    assert_eq!(run(&["is-ll1"], "a := \"x\"\n").status.code(), Some(2));
+    assert_eq!(
+        run(&["--token-classes", "/nonexistent/classes", "is-ll1"], LL1)
+            .status
+            .code(),
+        Some(3)
+    );
+
+    let dir = scratch_dir("exit_codes");
+    let missing = dir.join("missing.sebnf");
+    let output = run(&["-i", missing.to_str().unwrap(), "is-ll1"], "");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+    assert!(!output.stderr.is_empty());
+
+    // --quiet silences the report, but not the exit code
+    let output = run(&["--quiet", "is-ll1"], "a := \"x\" | \"x\" \"y\".\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn referenced_start_symbol_warns_only_for_follow_sets() {
+    let grammar = "a := \"x\" [ a ].\n";
+    let warning = "referenced_start_symbol";
+    let stderr = |args: &[&str]| String::from_utf8(run(args, grammar).stderr).unwrap();
+    assert!(stderr(&["is-ll1"]).contains(warning));
+    assert!(stderr(&["extract-sets"]).contains(warning));
+    assert!(!stderr(&["--augment-start", "is-ll1"]).contains(warning));
+    assert!(!stderr(&["to-bnf"]).contains(warning));
+    assert!(!stderr(&["fmt"]).contains(warning));
+    assert!(!stderr(&["validate"]).contains(warning));
+}
+
+#[test]
+fn validate_checks_every_section() {
+    let dir = scratch_dir("sections");
+    let path = dir.join("grammars.sebnf");
+    let path = path.to_str().unwrap();
+    let expr = "grammar Expr {\n  expr := num { \"+\" num }.\n  num := /[0-9]+/.\n}\n";
+    fs::write(
+        path,
+        format!("{}grammar List {{\n  list := \"[\" \"]\".\n}}\n", expr),
+    )
+    .unwrap();
+    let output = run(&["-i", path, "validate"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "grammar Expr: Valid SEBNF\ngrammar List: Valid SEBNF\n"
+    );
+    // A single section is still selected with --grammar
+    let output = run(&["-i", path, "--grammar", "List", "validate"], "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Valid SEBNF\n");
+
+    fs::write(
+        path,
+        format!("{}grammar List {{\n  list := \"[\"\n}}\n", expr),
+    )
+    .unwrap();
+    let output = run(&["-i", path, "validate", "--format", "json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let sections = json["sections"].as_array().unwrap();
+    assert_eq!(sections.len(), 2);
+    assert_eq!(sections[0]["valid"], true);
+    assert_eq!(sections[1]["name"], "List");
+    assert_eq!(sections[1]["valid"], false);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn rename_rewrites_the_file() {
+    let dir = scratch_dir("rename");
+    let path = dir.join("list.sebnf");
+    let path = path.to_str().unwrap();
+    fs::write(path, format!("(* a list *)\n{}", LL1)).unwrap();
+
+    let output = run(
+        &["-i", path, "rename", "--from", "item", "--to", "element"],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(path).unwrap(),
+        format!("(* a list *)\n{}", LL1.replace("item", "element"))
+    );
+
+    let output = run(
+        &["-i", path, "rename", "--from", "list", "--to", "element"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let output = run(
+        &[
+            "--from", "json", "-i", path, "rename", "--from", "list", "--to", "l",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Runs a command with a count that must be at least 1 set to 0
+fn assert_rejects_zero(args: &[&str]) {
+    let output = run(args, LL1);
+    assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("at least 1"));
+}
+
+#[test]
+fn zero_lookahead_is_a_usage_error() {
+    assert_rejects_zero(&["is-llk", "--k", "0"]);
+    assert_rejects_zero(&["min-k", "--max-k", "0"]);
+    assert_rejects_zero(&["is-ll1", "--lookahead", "0"]);
+}
+
+#[test]
+fn only_the_ll_parser_warns_about_a_referenced_start_symbol() {
+    let dir = scratch_dir("parse_start");
+    let input = dir.join("input.txt");
+    fs::write(&input, "a a b").unwrap();
+    let grammar = "s := \"a\" s | \"b\".\n";
+    let stderr = |algo: &str| {
+        let output = run(&["parse", input.to_str().unwrap(), "--algo", algo], grammar);
+        assert!(output.status.success(), "{}", algo);
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+
+    assert!(stderr("backtrack").contains("sebnf::referenced_start_symbol"));
+    for algo in ["earley", "glr", "cyk"] {
+        assert!(
+            !stderr(algo).contains("sebnf::referenced_start_symbol"),
+            "{}",
+            algo
+        );
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn capabilities_keep_their_shape() {
+    let output = run(&["capabilities"], "");
+    assert!(output.status.success());
+    let capabilities: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    let keys: Vec<_> = capabilities.as_object().unwrap().keys().cloned().collect();
+    assert_eq!(
+        keys,
+        [
+            "dialects",
+            "features",
+            "global_options",
+            "name",
+            "output_formats",
+            "subcommands",
+            "version"
+        ]
+    );
+    assert_eq!(capabilities["name"], "sebnf_tool");
+    assert_eq!(capabilities["version"], env!("CARGO_PKG_VERSION"));
+    let version = run(&["--version"], "");
+    assert_eq!(
+        String::from_utf8_lossy(&version.stdout).trim(),
+        format!("sebnf_tool {}", env!("CARGO_PKG_VERSION"))
+    );
+    assert_eq!(
+        capabilities["features"],
+        serde_json::json!({
+            "serde": cfg!(feature = "serde"),
+            "wasm": cfg!(feature = "wasm"),
+            "yaml": cfg!(feature = "yaml"),
+        })
+    );
+    assert_eq!(
+        capabilities["output_formats"],
+        serde_json::json!(["text", "sarif", "json"])
+    );
+
+    let subcommands = capabilities["subcommands"].as_array().unwrap();
+    let is_ll1 = (subcommands.iter())
+        .find(|sub| sub["name"] == "is-ll1")
+        .unwrap();
+    assert!(is_ll1["about"].is_string());
+    let format = (is_ll1["options"].as_array().unwrap().iter())
+        .find(|option| option["name"] == "format")
+        .unwrap();
+    assert_eq!(
+        format,
+        &serde_json::json!({
+            "long": "format",
+            "name": "format",
+            "positional": false,
+            "values": ["text", "sarif", "json"],
+        })
+    );
+}
+
+#[test]
+fn enumerate_requires_a_bound() {
+    let grammar = "a := { \"x\" }.\n";
+    let output = run(&["enumerate"], grammar);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["enumerate", "--max-tokens", "2"], grammar);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ε\nx\nx x\n");
+}
+
+#[test]
+fn to_bnf_warns_about_every_file() {
+    let dir = scratch_dir("to_bnf_files");
+    let syntax = dir.join("syntax.sebnf");
+    let lexical = dir.join("lexical.sebnf");
+    fs::write(&syntax, "expr := term { \"+\" term }.\n").unwrap();
+    fs::write(&lexical, "term := num | num.\nnum := /[0-9]+/.\n").unwrap();
+
+    let output = run(
+        &[
+            "to-bnf",
+            syntax.to_str().unwrap(),
+            lexical.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("sebnf::repeated_alternative"), "{}", stderr);
+    assert!(stderr.contains("lexical.sebnf"), "{}", stderr);
+    assert!(!output.stdout.is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dedupe_pass_removes_copies() {
+    let grammar = "a := \"x\" | \"x\" | b.\nb := \"y\".\nc := \"y\".\n";
+    let output = run(&["transform", "--pass", "dedupe"], grammar);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "a := \"x\"\n   | b\n   .\nb := \"y\".\n"
+    );
+    assert_eq!(output.stdout, run(&["dedupe"], grammar).stdout);
+}
+
+#[test]
+fn equiv_bounds_the_sentence_length() {
+    let dir = scratch_dir("equiv");
+    let short = dir.join("short.sebnf");
+    let long = dir.join("long.sebnf");
+    fs::write(&short, "s := \"a\" [\"a\"].\n").unwrap();
+    fs::write(&long, "s := \"a\" {\"a\"}.\n").unwrap();
+    let equiv = |flag: &str, max: &str| {
+        let args = [
+            "equiv",
+            short.to_str().unwrap(),
+            long.to_str().unwrap(),
+            flag,
+            max,
+        ];
+        run(&args, "").status.code()
+    };
+
+    assert_eq!(equiv("--max-len", "2"), Some(0));
+    assert_eq!(equiv("--max-len", "3"), Some(1));
+    assert_eq!(equiv("--max-tokens", "3"), Some(1));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,113 @@
+//! The generated code compiled and run on `codegen/grammar.sebnf`.
+//!
+//! The generated sources are checked in next to the grammar, and
+//! `generated_sources_are_up_to_date` fails when the generators' output
+//! changes. Regenerate them with e.g.
+//! `sebnf_tool -i tests/codegen/grammar.sebnf codegen rust-rd -o tests/codegen/rd.rs`,
+//! and likewise `rust-table` into `table.rs` and `lexer` into `lexer.rs`.
+//! They are left as generated, so rustfmt skips them.
+
+#[path = "codegen/lexer.rs"]
+#[allow(dead_code)]
+#[rustfmt::skip]
+mod lexer;
+#[path = "codegen/rd.rs"]
+#[allow(dead_code)]
+#[rustfmt::skip]
+mod rd;
+#[path = "codegen/table.rs"]
+#[allow(dead_code)]
+#[rustfmt::skip]
+mod table;
+
+use sebnf_tool::Sebnf;
+use sebnf_tool::codegen;
+
+const INPUTS: &[&str] = &[
+    "1",
+    "x * (2 + y) + 3",
+    "((a))*b*c",
+    "1 +",
+    "(1 2)",
+    "a $ b",
+    "",
+];
+
+#[test]
+fn generated_sources_are_up_to_date() {
+    let source = include_str!("codegen/grammar.sebnf");
+    let sebnf = Sebnf::parse_str(source, "grammar.sebnf").unwrap();
+    assert_eq!(
+        codegen::rust_rd_parser(&sebnf).unwrap(),
+        include_str!("codegen/rd.rs")
+    );
+    assert_eq!(
+        codegen::rust_table_parser(&sebnf).unwrap(),
+        include_str!("codegen/table.rs")
+    );
+    assert_eq!(
+        codegen::lexer_module(&sebnf, true),
+        include_str!("codegen/lexer.rs")
+    );
+}
+
+#[test]
+fn recursive_descent_parser_builds_the_tree() {
+    let input = "x * (2 + y) + 3";
+    let expr = rd::parse(input).unwrap();
+    assert_eq!(expr.rule, rd::Rule::Expr);
+    assert_eq!(expr.span, 0..input.len());
+    let terms: Vec<_> = expr.nodes(rd::Rule::Term).collect();
+    assert_eq!(terms.len(), 2);
+    assert_eq!(&input[terms[0].span.clone()], "x * (2 + y)");
+    assert_eq!(
+        expr.tokens().map(|t| t.text.as_str()).collect::<Vec<_>>(),
+        ["+"]
+    );
+    let atoms: Vec<_> = terms[0].nodes(rd::Rule::Atom).collect();
+    assert_eq!(atoms.len(), 2);
+    let inner = atoms[1].nodes(rd::Rule::Expr).next().unwrap();
+    assert_eq!(&input[inner.span.clone()], "2 + y");
+
+    assert_eq!(
+        rd::parse("1 +"),
+        Err(rd::Error::Unexpected {
+            found: None,
+            offset: 3,
+            expected: vec!["\"(\"", "/[0-9]+/", "/[a-z]+/"],
+        })
+    );
+    assert_eq!(
+        rd::parse("(1 2)").unwrap_err().to_string(),
+        "unexpected \"2\" at offset 3, expected \"*\" or \")\" or \"+\" or end of input"
+    );
+    assert_eq!(
+        rd::parse("a $ b"),
+        Err(rd::Error::Lex(rd::LexError { offset: 2 }))
+    );
+}
+
+#[test]
+fn table_parser_builds_the_same_tree() {
+    // Both modules declare the same types, so equal results print the same
+    for input in INPUTS {
+        assert_eq!(
+            format!("{:?}", table::parse(input)),
+            format!("{:?}", rd::parse(input)),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn logos_lexer_splits_like_the_generated_one() {
+    for input in INPUTS.iter().chain(&["abc12", "12abc  (\n)"]) {
+        assert_eq!(
+            format!("{:?}", lexer::tokenize(input)),
+            format!("{:?}", rd::tokenize(input)),
+            "{}",
+            input
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! The tokens of the grammar and a lexer for them, generated by
+//! `sebnf_tool codegen lexer`.
+
+use std::fmt;
+use std::ops::Range;
+
+use logos::Logos;
+
+#[derive(Logos, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[logos(skip r"\s+")]
+pub enum TokenKind {
+    /// `"("`
+    #[token("(", priority = 3)]
+    LParen,
+    /// `")"`
+    #[token(")", priority = 3)]
+    RParen,
+    /// `/[0-9]+/`
+    #[regex("[0-9]+", priority = 2)]
+    Num,
+    /// `/[a-z]+/`
+    #[regex("[a-z]+", priority = 1)]
+    Name,
+    /// `"+"`
+    #[token("+", priority = 3)]
+    Plus,
+    /// `"*"`
+    #[token("*", priority = 3)]
+    Star,
+}
+
+impl TokenKind {
+    /// The terminal or regex as written in the grammar
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TokenKind::LParen => "\"(\"",
+            TokenKind::RParen => "\")\"",
+            TokenKind::Num => "/[0-9]+/",
+            TokenKind::Name => "/[a-z]+/",
+            TokenKind::Plus => "\"+\"",
+            TokenKind::Star => "\"*\"",
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    /// Byte offsets into the input
+    pub span: Range<usize>,
+}
+
+/// No token matches the input at `offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub offset: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no token matches the input at offset {}", self.offset)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// Splits `input` into tokens, skipping whitespace. The longest match wins,
+/// and a terminal wins over a regex matching the same text.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    let mut lexer = TokenKind::lexer(input);
+    let mut tokens = Vec::new();
+    while let Some(kind) = lexer.next() {
+        let Ok(kind) = kind else {
+            return Err(LexError {
+                offset: lexer.span().start,
+            });
+        };
+        tokens.push(Token {
+            kind,
+            text: lexer.slice().to_string(),
+            span: lexer.span(),
+        });
+    }
+    Ok(tokens)
+}
@@ -0,0 +1,417 @@
+//! A recursive descent parser for the grammar, generated by
+//! `sebnf_tool codegen rust-rd`.
+//!
+//! [`parse`] turns a string into a tree with a [`Node`] for every rule it
+//! matched and the tokens as leaves.
+
+use std::fmt;
+use std::ops::Range;
+
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// `"("`
+    LParen,
+    /// `")"`
+    RParen,
+    /// `/[0-9]+/`
+    Num,
+    /// `/[a-z]+/`
+    Name,
+    /// `"+"`
+    Plus,
+    /// `"*"`
+    Star,
+}
+
+impl TokenKind {
+    /// The terminal or regex as written in the grammar
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TokenKind::LParen => "\"(\"",
+            TokenKind::RParen => "\")\"",
+            TokenKind::Num => "/[0-9]+/",
+            TokenKind::Name => "/[a-z]+/",
+            TokenKind::Plus => "\"+\"",
+            TokenKind::Star => "\"*\"",
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    /// Byte offsets into the input
+    pub span: Range<usize>,
+}
+
+/// No token matches the input at `offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub offset: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no token matches the input at offset {}", self.offset)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+const TERMINALS: &[(&str, TokenKind)] = &[
+    ("(", TokenKind::LParen),
+    (")", TokenKind::RParen),
+    ("+", TokenKind::Plus),
+    ("*", TokenKind::Star),
+];
+
+const REGEXES: &[(&str, TokenKind)] = &[
+    ("[0-9]+", TokenKind::Num),
+    ("[a-z]+", TokenKind::Name),
+];
+
+/// Splits `input` into tokens, skipping whitespace. The longest match wins,
+/// and a terminal wins over a regex matching the same text.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    let regexes: Vec<(Regex, TokenKind)> = REGEXES
+        .iter()
+        .map(|(pattern, kind)| {
+            let regex = Regex::new(&format!("^(?:{})", pattern));
+            (regex.expect("the grammar's regexes are valid"), *kind)
+        })
+        .collect();
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    while offset < input.len() {
+        let rest = &input[offset..];
+        let trimmed = rest.trim_start();
+        if trimmed.len() < rest.len() {
+            offset += rest.len() - trimmed.len();
+            continue;
+        }
+        let mut longest: Option<(usize, TokenKind)> = None;
+        for (terminal, kind) in TERMINALS {
+            if !terminal.is_empty()
+                && rest.starts_with(terminal)
+                && longest.is_none_or(|(len, _)| terminal.len() > len)
+            {
+                longest = Some((terminal.len(), *kind));
+            }
+        }
+        for (regex, kind) in &regexes {
+            if let Some(found) = regex.find(rest)
+                && found.end() > 0
+                && longest.is_none_or(|(len, _)| found.end() > len)
+            {
+                longest = Some((found.end(), *kind));
+            }
+        }
+        let Some((len, kind)) = longest else {
+            return Err(LexError { offset });
+        };
+        tokens.push(Token {
+            kind,
+            text: rest[..len].to_string(),
+            span: offset..offset + len,
+        });
+        offset += len;
+    }
+    Ok(tokens)
+}
+
+/// The rules of the grammar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// `expr := term { "+" term }`
+    Expr,
+    /// `term := atom { "*" atom }`
+    Term,
+    /// `atom := num | name | "(" expr ")"`
+    Atom,
+    /// `num := /[0-9]+/`
+    Num,
+    /// `name := /[a-z]+/`
+    Name,
+}
+
+impl Rule {
+    /// The name of the rule in the grammar
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::Expr => "expr",
+            Rule::Term => "term",
+            Rule::Atom => "atom",
+            Rule::Num => "num",
+            Rule::Name => "name",
+        }
+    }
+}
+
+/// What a rule matched. Its `[ ]`, `{ }` and groups add no nodes of their
+/// own, so their children are the node's children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub rule: Rule,
+    pub children: Vec<Tree>,
+    /// Byte offsets into the input; empty if the rule matched no tokens
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tree {
+    Node(Node),
+    Token(Token),
+}
+
+impl Node {
+    /// The child nodes of `rule`
+    pub fn nodes(&self, rule: Rule) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter_map(move |child| match child {
+            Tree::Node(node) if node.rule == rule => Some(node),
+            _ => None,
+        })
+    }
+
+    /// The tokens among the children
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.children.iter().filter_map(|child| match child {
+            Tree::Token(token) => Some(token),
+            Tree::Node(_) => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Lex(LexError),
+    /// A token, or the end of the input, where one of `expected` must come
+    Unexpected {
+        found: Option<Token>,
+        offset: usize,
+        expected: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Lex(error) => write!(f, "{}", error),
+            Error::Unexpected {
+                found,
+                offset,
+                expected,
+            } => {
+                match found {
+                    Some(token) => write!(f, "unexpected {:?} at offset {}", token.text, offset)?,
+                    None => write!(f, "unexpected end of input at offset {}", offset)?,
+                }
+                write!(f, ", expected {}", expected.join(" or "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<LexError> for Error {
+    fn from(error: LexError) -> Self {
+        Error::Lex(error)
+    }
+}
+
+/// Parses all of `input` as the start rule.
+pub fn parse(input: &str) -> Result<Node, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+        len: input.len(),
+    };
+    let mut children = Vec::new();
+    parser.parse_expr(&mut children)?;
+    if parser.peek().is_some() {
+        return Err(parser.unexpected(vec!["end of input"]));
+    }
+    match children.pop() {
+        Some(Tree::Node(node)) => Ok(node),
+        _ => unreachable!("the start rule adds a node"),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<TokenKind> {
+        self.tokens.get(self.position).map(|token| token.kind)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.len, |token| token.span.start)
+    }
+
+    /// From the token at `first` to the last one consumed
+    fn span_from(&self, first: usize) -> Range<usize> {
+        match self.position > first {
+            true => self.tokens[first].span.start..self.tokens[self.position - 1].span.end,
+            false => self.offset()..self.offset(),
+        }
+    }
+
+    fn unexpected(&self, expected: Vec<&'static str>) -> Error {
+        Error::Unexpected {
+            found: self.tokens.get(self.position).cloned(),
+            offset: self.offset(),
+            expected,
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, Error> {
+        if self.peek() != Some(kind) {
+            return Err(self.unexpected(vec![kind.symbol()]));
+        }
+        self.position += 1;
+        Ok(self.tokens[self.position - 1].clone())
+    }
+
+    /// `expr := term ___rep_0`
+    fn parse_expr(&mut self, parent: &mut Vec<Tree>) -> Result<(), Error> {
+        let first = self.position;
+        let mut children = Vec::new();
+        match self.peek() {
+            Some(TokenKind::LParen) | Some(TokenKind::Num) | Some(TokenKind::Name) => {
+                self.parse_term(&mut children)?;
+                self.parse_rep_0(&mut children)?;
+            }
+            _ => return Err(self.unexpected(vec!["\"(\"", "/[0-9]+/", "/[a-z]+/"])),
+        }
+        parent.push(Tree::Node(Node {
+            rule: Rule::Expr,
+            children,
+            span: self.span_from(first),
+        }));
+        Ok(())
+    }
+
+    /// `term := atom ___rep_1`
+    fn parse_term(&mut self, parent: &mut Vec<Tree>) -> Result<(), Error> {
+        let first = self.position;
+        let mut children = Vec::new();
+        match self.peek() {
+            Some(TokenKind::LParen) | Some(TokenKind::Num) | Some(TokenKind::Name) => {
+                self.parse_atom(&mut children)?;
+                self.parse_rep_1(&mut children)?;
+            }
+            _ => return Err(self.unexpected(vec!["\"(\"", "/[0-9]+/", "/[a-z]+/"])),
+        }
+        parent.push(Tree::Node(Node {
+            rule: Rule::Term,
+            children,
+            span: self.span_from(first),
+        }));
+        Ok(())
+    }
+
+    /// `atom := num | name | "(" expr ")"`
+    fn parse_atom(&mut self, parent: &mut Vec<Tree>) -> Result<(), Error> {
+        let first = self.position;
+        let mut children = Vec::new();
+        match self.peek() {
+            Some(TokenKind::Num) => {
+                self.parse_num(&mut children)?;
+            }
+            Some(TokenKind::Name) => {
+                self.parse_name(&mut children)?;
+            }
+            Some(TokenKind::LParen) => {
+                children.push(Tree::Token(self.expect(TokenKind::LParen)?));
+                self.parse_expr(&mut children)?;
+                children.push(Tree::Token(self.expect(TokenKind::RParen)?));
+            }
+            _ => return Err(self.unexpected(vec!["/[0-9]+/", "/[a-z]+/", "\"(\""])),
+        }
+        parent.push(Tree::Node(Node {
+            rule: Rule::Atom,
+            children,
+            span: self.span_from(first),
+        }));
+        Ok(())
+    }
+
+    /// `num := /[0-9]+/`
+    fn parse_num(&mut self, parent: &mut Vec<Tree>) -> Result<(), Error> {
+        let first = self.position;
+        let mut children = Vec::new();
+        match self.peek() {
+            Some(TokenKind::Num) => {
+                children.push(Tree::Token(self.expect(TokenKind::Num)?));
+            }
+            _ => return Err(self.unexpected(vec!["/[0-9]+/"])),
+        }
+        parent.push(Tree::Node(Node {
+            rule: Rule::Num,
+            children,
+            span: self.span_from(first),
+        }));
+        Ok(())
+    }
+
+    /// `name := /[a-z]+/`
+    fn parse_name(&mut self, parent: &mut Vec<Tree>) -> Result<(), Error> {
+        let first = self.position;
+        let mut children = Vec::new();
+        match self.peek() {
+            Some(TokenKind::Name) => {
+                children.push(Tree::Token(self.expect(TokenKind::Name)?));
+            }
+            _ => return Err(self.unexpected(vec!["/[a-z]+/"])),
+        }
+        parent.push(Tree::Node(Node {
+            rule: Rule::Name,
+            children,
+            span: self.span_from(first),
+        }));
+        Ok(())
+    }
+
+    /// `___rep_0 := "+" term ___rep_0 | ε`
+    fn parse_rep_0(&mut self, children: &mut Vec<Tree>) -> Result<(), Error> {
+        match self.peek() {
+            Some(TokenKind::Plus) => {
+                children.push(Tree::Token(self.expect(TokenKind::Plus)?));
+                self.parse_term(children)?;
+                self.parse_rep_0(children)?;
+            }
+            Some(TokenKind::RParen) | None => {}
+            _ => return Err(self.unexpected(vec!["\"+\"", "\")\"", "end of input"])),
+        }
+        Ok(())
+    }
+
+    /// `___rep_1 := "*" atom ___rep_1 | ε`
+    fn parse_rep_1(&mut self, children: &mut Vec<Tree>) -> Result<(), Error> {
+        match self.peek() {
+            Some(TokenKind::Star) => {
+                children.push(Tree::Token(self.expect(TokenKind::Star)?));
+                self.parse_atom(children)?;
+                self.parse_rep_1(children)?;
+            }
+            Some(TokenKind::RParen) | Some(TokenKind::Plus) | None => {}
+            _ => return Err(self.unexpected(vec!["\"*\"", "\")\"", "\"+\"", "end of input"])),
+        }
+        Ok(())
+    }
+}
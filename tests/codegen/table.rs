@@ -0,0 +1,365 @@
+//! A table-driven LL(1) parser for the grammar, generated by
+//! `sebnf_tool codegen rust-table`.
+//!
+//! [`parse`] turns a string into a tree with a [`Node`] for every rule it
+//! matched and the tokens as leaves.
+
+use std::fmt;
+use std::ops::Range;
+
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// `"("`
+    LParen,
+    /// `")"`
+    RParen,
+    /// `/[0-9]+/`
+    Num,
+    /// `/[a-z]+/`
+    Name,
+    /// `"+"`
+    Plus,
+    /// `"*"`
+    Star,
+}
+
+impl TokenKind {
+    /// The terminal or regex as written in the grammar
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TokenKind::LParen => "\"(\"",
+            TokenKind::RParen => "\")\"",
+            TokenKind::Num => "/[0-9]+/",
+            TokenKind::Name => "/[a-z]+/",
+            TokenKind::Plus => "\"+\"",
+            TokenKind::Star => "\"*\"",
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    /// Byte offsets into the input
+    pub span: Range<usize>,
+}
+
+/// No token matches the input at `offset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    pub offset: usize,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no token matches the input at offset {}", self.offset)
+    }
+}
+
+impl std::error::Error for LexError {}
+
+const TERMINALS: &[(&str, TokenKind)] = &[
+    ("(", TokenKind::LParen),
+    (")", TokenKind::RParen),
+    ("+", TokenKind::Plus),
+    ("*", TokenKind::Star),
+];
+
+const REGEXES: &[(&str, TokenKind)] = &[
+    ("[0-9]+", TokenKind::Num),
+    ("[a-z]+", TokenKind::Name),
+];
+
+/// Splits `input` into tokens, skipping whitespace. The longest match wins,
+/// and a terminal wins over a regex matching the same text.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
+    let regexes: Vec<(Regex, TokenKind)> = REGEXES
+        .iter()
+        .map(|(pattern, kind)| {
+            let regex = Regex::new(&format!("^(?:{})", pattern));
+            (regex.expect("the grammar's regexes are valid"), *kind)
+        })
+        .collect();
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    while offset < input.len() {
+        let rest = &input[offset..];
+        let trimmed = rest.trim_start();
+        if trimmed.len() < rest.len() {
+            offset += rest.len() - trimmed.len();
+            continue;
+        }
+        let mut longest: Option<(usize, TokenKind)> = None;
+        for (terminal, kind) in TERMINALS {
+            if !terminal.is_empty()
+                && rest.starts_with(terminal)
+                && longest.is_none_or(|(len, _)| terminal.len() > len)
+            {
+                longest = Some((terminal.len(), *kind));
+            }
+        }
+        for (regex, kind) in &regexes {
+            if let Some(found) = regex.find(rest)
+                && found.end() > 0
+                && longest.is_none_or(|(len, _)| found.end() > len)
+            {
+                longest = Some((found.end(), *kind));
+            }
+        }
+        let Some((len, kind)) = longest else {
+            return Err(LexError { offset });
+        };
+        tokens.push(Token {
+            kind,
+            text: rest[..len].to_string(),
+            span: offset..offset + len,
+        });
+        offset += len;
+    }
+    Ok(tokens)
+}
+
+/// The rules of the grammar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// `expr := term { "+" term }`
+    Expr,
+    /// `term := atom { "*" atom }`
+    Term,
+    /// `atom := num | name | "(" expr ")"`
+    Atom,
+    /// `num := /[0-9]+/`
+    Num,
+    /// `name := /[a-z]+/`
+    Name,
+}
+
+impl Rule {
+    /// The name of the rule in the grammar
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::Expr => "expr",
+            Rule::Term => "term",
+            Rule::Atom => "atom",
+            Rule::Num => "num",
+            Rule::Name => "name",
+        }
+    }
+}
+
+/// What a rule matched. Its `[ ]`, `{ }` and groups add no nodes of their
+/// own, so their children are the node's children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub rule: Rule,
+    pub children: Vec<Tree>,
+    /// Byte offsets into the input; empty if the rule matched no tokens
+    pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tree {
+    Node(Node),
+    Token(Token),
+}
+
+impl Node {
+    /// The child nodes of `rule`
+    pub fn nodes(&self, rule: Rule) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter_map(move |child| match child {
+            Tree::Node(node) if node.rule == rule => Some(node),
+            _ => None,
+        })
+    }
+
+    /// The tokens among the children
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.children.iter().filter_map(|child| match child {
+            Tree::Token(token) => Some(token),
+            Tree::Node(_) => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Lex(LexError),
+    /// A token, or the end of the input, where one of `expected` must come
+    Unexpected {
+        found: Option<Token>,
+        offset: usize,
+        expected: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Lex(error) => write!(f, "{}", error),
+            Error::Unexpected {
+                found,
+                offset,
+                expected,
+            } => {
+                match found {
+                    Some(token) => write!(f, "unexpected {:?} at offset {}", token.text, offset)?,
+                    None => write!(f, "unexpected end of input at offset {}", offset)?,
+                }
+                write!(f, ", expected {}", expected.join(" or "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<LexError> for Error {
+    fn from(error: LexError) -> Self {
+        Error::Lex(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Symbol {
+    Token(TokenKind),
+    /// An index into `RULES`
+    Rule(usize),
+}
+
+/// The rules of the BNF, with the node each one builds; helper rules add
+/// their children to the node of the rule using them
+const RULES: &[(&str, Option<Rule>)] = &[
+    ("expr", Some(Rule::Expr)),
+    ("term", Some(Rule::Term)),
+    ("atom", Some(Rule::Atom)),
+    ("num", Some(Rule::Num)),
+    ("name", Some(Rule::Name)),
+    ("___rep_0", None),
+    ("___rep_1", None),
+];
+
+/// The productions of the BNF
+const PRODUCTIONS: &[&[Symbol]] = &[
+    // 0: expr := term ___rep_0
+    &[Symbol::Rule(1), Symbol::Rule(5)],
+    // 1: term := atom ___rep_1
+    &[Symbol::Rule(2), Symbol::Rule(6)],
+    // 2: atom := num
+    &[Symbol::Rule(3)],
+    // 3: atom := name
+    &[Symbol::Rule(4)],
+    // 4: atom := "(" expr ")"
+    &[Symbol::Token(TokenKind::LParen), Symbol::Rule(0), Symbol::Token(TokenKind::RParen)],
+    // 5: num := /[0-9]+/
+    &[Symbol::Token(TokenKind::Num)],
+    // 6: name := /[a-z]+/
+    &[Symbol::Token(TokenKind::Name)],
+    // 7: ___rep_0 := "+" term ___rep_0
+    &[Symbol::Token(TokenKind::Plus), Symbol::Rule(1), Symbol::Rule(5)],
+    // 8: ___rep_0 := ε
+    &[],
+    // 9: ___rep_1 := "*" atom ___rep_1
+    &[Symbol::Token(TokenKind::Star), Symbol::Rule(2), Symbol::Rule(6)],
+    // 10: ___rep_1 := ε
+    &[],
+];
+
+/// For every rule, the production to expand it by on each next token, or
+/// `None` at the end of the input
+const TABLE: &[&[(Option<TokenKind>, usize)]] = &[
+    // expr
+    &[(Some(TokenKind::LParen), 0), (Some(TokenKind::Num), 0), (Some(TokenKind::Name), 0)],
+    // term
+    &[(Some(TokenKind::LParen), 1), (Some(TokenKind::Num), 1), (Some(TokenKind::Name), 1)],
+    // atom
+    &[(Some(TokenKind::Num), 2), (Some(TokenKind::Name), 3), (Some(TokenKind::LParen), 4)],
+    // num
+    &[(Some(TokenKind::Num), 5)],
+    // name
+    &[(Some(TokenKind::Name), 6)],
+    // ___rep_0
+    &[(Some(TokenKind::Plus), 7), (Some(TokenKind::RParen), 8), (None, 8)],
+    // ___rep_1
+    &[(Some(TokenKind::Star), 9), (Some(TokenKind::RParen), 10), (Some(TokenKind::Plus), 10), (None, 10)],
+];
+
+enum Task {
+    Expand(Symbol),
+    /// Builds the node of a rule from the children parsed since the token at
+    /// `first`
+    Close(Rule, usize),
+}
+
+/// Parses all of `input` as the start rule.
+pub fn parse(input: &str) -> Result<Node, Error> {
+    let tokens = tokenize(input)?;
+    let offset = |position: usize| tokens.get(position).map_or(input.len(), |token| token.span.start);
+    let unexpected = |position: usize, expected: Vec<&'static str>| Error::Unexpected {
+        found: tokens.get(position).cloned(),
+        offset: offset(position),
+        expected,
+    };
+    let mut position = 0;
+    let mut stack = vec![Task::Expand(Symbol::Rule(0))];
+    // The children of the nodes being built, innermost last
+    let mut children: Vec<Vec<Tree>> = vec![Vec::new()];
+    while let Some(task) = stack.pop() {
+        match task {
+            Task::Expand(Symbol::Token(kind)) => {
+                match tokens.get(position) {
+                    Some(token) if token.kind == kind => {
+                        let token = Tree::Token(token.clone());
+                        children.last_mut().expect("a node is open").push(token);
+                        position += 1;
+                    }
+                    _ => return Err(unexpected(position, vec![kind.symbol()])),
+                }
+            }
+            Task::Expand(Symbol::Rule(rule)) => {
+                let next = tokens.get(position).map(|token| token.kind);
+                let Some(&(_, production)) = TABLE[rule].iter().find(|(kind, _)| *kind == next) else {
+                    let expected = TABLE[rule]
+                        .iter()
+                        .map(|(kind, _)| kind.map_or("end of input", TokenKind::symbol))
+                        .collect();
+                    return Err(unexpected(position, expected));
+                };
+                if let (_, Some(node)) = RULES[rule] {
+                    stack.push(Task::Close(node, position));
+                    children.push(Vec::new());
+                }
+                for &symbol in PRODUCTIONS[production].iter().rev() {
+                    stack.push(Task::Expand(symbol));
+                }
+            }
+            Task::Close(rule, first) => {
+                let span = match position > first {
+                    true => tokens[first].span.start..tokens[position - 1].span.end,
+                    false => offset(position)..offset(position),
+                };
+                let node = Node {
+                    rule,
+                    children: children.pop().expect("the node is open"),
+                    span,
+                };
+                children.last_mut().expect("a node is open").push(Tree::Node(node));
+            }
+        }
+    }
+    if position < tokens.len() {
+        return Err(unexpected(position, vec!["end of input"]));
+    }
+    match children.pop().and_then(|mut nodes| nodes.pop()) {
+        Some(Tree::Node(node)) => Ok(node),
+        _ => unreachable!("the start rule adds a node"),
+    }
+}